@@ -0,0 +1,52 @@
+//! Compare the JSON backend with serde_json on typical CDP messages, select the backend with
+//! e.g. `cargo bench --bench json --features json-simd`.
+
+use chromiumoxide::cdp::CdpEventMessage;
+use chromiumoxide::types::Message;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const REQUEST_WILL_BE_SENT: &str = r#"{"method":"Network.requestWillBeSent","sessionId":"S1","params":{"requestId":"R1","loaderId":"L1","documentURL":"https://example.com/","request":{"url":"https://example.com/","method":"GET","headers":{"Accept":"text/html","User-Agent":"Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36"},"initialPriority":"VeryHigh","referrerPolicy":"strict-origin-when-cross-origin"},"timestamp":1.5,"wallTime":1700000000.5,"initiator":{"type":"other"},"redirectHasExtraInfo":false,"type":"Document","frameId":"F1","hasUserGesture":false}}"#;
+
+/// A response of a `Page.captureScreenshot` sized payload.
+fn screenshot_response() -> String {
+    format!(
+        r#"{{"id":7,"sessionId":"S1","result":{{"data":"{}"}}}}"#,
+        "iVBORw0KGgo".repeat(64 * 1024)
+    )
+}
+
+fn decode(c: &mut Criterion) {
+    let screenshot = screenshot_response();
+    let mut group = c.benchmark_group(format!("decode/{}", chromiumoxide::json::BACKEND));
+
+    for (name, message) in [
+        ("event", REQUEST_WILL_BE_SENT),
+        ("screenshot", screenshot.as_str()),
+    ] {
+        group.bench_with_input(BenchmarkId::new("backend", name), message, |b, message| {
+            b.iter(|| {
+                chromiumoxide::json::from_slice::<Box<Message<CdpEventMessage>>>(black_box(
+                    message.as_bytes(),
+                ))
+                .unwrap()
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("serde_json", name),
+            message,
+            |b, message| {
+                b.iter(|| {
+                    serde_json::from_slice::<Box<Message<CdpEventMessage>>>(black_box(
+                        message.as_bytes(),
+                    ))
+                    .unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+// RUST_LOG=debug cargo run --example rpc-server --features server
+// printf '%s\n%s\n' '{"jsonrpc":"2.0","id":0,"method":"rpc.authenticate","params":{"token":"<token>"}}' \
+//   '{"jsonrpc":"2.0","id":1,"method":"browser.newPage","params":{"url":"https://example.com"}}' | nc localhost 9333
+use std::sync::Arc;
+
+use chromiumoxide::browser::{Browser, BrowserConfig};
+use chromiumoxide::server::RpcServer;
+use futures::StreamExt;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+
+    let (browser, mut handler) = Browser::launch(BrowserConfig::builder().build()?).await?;
+
+    let handle = tokio::task::spawn(async move {
+        while let Some(h) = handler.next().await {
+            if h.is_err() {
+                break;
+            }
+        }
+    });
+
+    let server = RpcServer::new(Arc::new(browser));
+
+    println!("rpc token: {}", server.token());
+
+    // bind to the loopback only, the token is the only protection of the server.
+    let server = server.serve("127.0.0.1:9333").await?;
+
+    let _ = tokio::join!(server, handle);
+
+    Ok(())
+}
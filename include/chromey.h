@@ -0,0 +1,58 @@
+/*
+ * C API of chromey, available with the `capi` feature.
+ *
+ * Build a shared library with:
+ *   cargo rustc --release --features capi --crate-type cdylib
+ *
+ * Calls returning a pointer return NULL on error and calls returning an int return
+ * CHROMEY_ERR, the message of the error is returned by chromey_last_error. A panic inside
+ * a call fails the call the same way, it never unwinds into the caller.
+ *
+ * The calls block the calling thread on an internal runtime. They fail when called from a
+ * runtime thread, e.g. from a ChromeyCallback: hand the work to another thread instead.
+ *
+ * The callback of chromey_page_goto_async runs on a runtime thread, not on the thread of the
+ * call. Its user_data must be safe to use from that thread: synchronize any state shared with
+ * the caller and keep the data alive until the callback ran.
+ */
+#ifndef CHROMEY_H
+#define CHROMEY_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CHROMEY_OK 0
+#define CHROMEY_ERR -1
+
+typedef struct ChromeyBrowser ChromeyBrowser;
+typedef struct ChromeyPage ChromeyPage;
+
+typedef void (*ChromeyCallback)(int status, void *user_data);
+
+const char *chromey_last_error(void);
+
+ChromeyBrowser *chromey_browser_launch(bool headless);
+ChromeyBrowser *chromey_browser_connect(const char *url);
+int chromey_browser_close(ChromeyBrowser *browser);
+ChromeyPage *chromey_browser_new_page(const ChromeyBrowser *browser, const char *url);
+
+int chromey_page_goto(const ChromeyPage *page, const char *url);
+int chromey_page_goto_async(const ChromeyPage *page, const char *url, ChromeyCallback callback,
+                            void *user_data);
+char *chromey_page_content(const ChromeyPage *page);
+uint8_t *chromey_page_screenshot(const ChromeyPage *page, bool full_page, size_t *len);
+int chromey_page_close(ChromeyPage *page);
+
+void chromey_string_free(char *value);
+void chromey_bytes_free(uint8_t *bytes, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
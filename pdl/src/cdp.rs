@@ -334,6 +334,17 @@ pub mod events {
         fn session_id(&self) -> Option<&str> {
             self.session_id.as_deref()
         }
+        fn from_raw(
+            method: String,
+            session_id: Option<String>,
+            params: serde_json::Value,
+        ) -> Option<Self> {
+            Some(CdpEventMessage {
+                method: method.into(),
+                session_id,
+                params: CdpEvent::Other(params),
+            })
+        }
     }
     #[derive(Debug, Clone, PartialEq)]
     pub enum CdpEvent {
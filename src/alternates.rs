@@ -0,0 +1,187 @@
+/// Extract the canonical, AMP and mobile alternate links of the page.
+pub(crate) const ALTERNATE_LINKS_JS: &str = r###"(()=>{const h=document.documentElement;const c=document.querySelector('link[rel~="canonical" i][href]');const a=document.querySelector('link[rel~="amphtml" i][href]');const m=[];for(const l of document.querySelectorAll('link[rel~="alternate" i][media][href]')){if(!l.hasAttribute('hreflang')&&!l.hasAttribute('type')){m.push({url:l.href,media:l.getAttribute('media')})}}return{url:location.href,canonical:c?c.href:null,amp:a?a.href:null,mobile:m,isAmp:!!h&&(h.hasAttribute('amp')||h.hasAttribute('⚡'))}})()"###;
+
+/// A mobile alternate of the page, e.g. `<link rel="alternate" media="only screen and
+/// (max-width: 640px)">`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MobileAlternate {
+    /// The absolute url of the alternate.
+    pub url: String,
+    /// The media query the alternate serves.
+    pub media: String,
+}
+
+/// The kind of a representation of the page.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RepresentationKind {
+    /// The canonical document.
+    #[default]
+    Canonical,
+    /// The AMP version of the document.
+    Amp,
+    /// The mobile version of the document.
+    Mobile,
+}
+
+/// A representation of the page to crawl.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Representation {
+    /// The url of the representation.
+    pub url: String,
+    /// The kind of the representation.
+    pub kind: RepresentationKind,
+}
+
+/// The alternate representations of the page.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alternates {
+    /// The url of the page.
+    pub url: String,
+    /// The canonical url declared by the page.
+    pub canonical: Option<String>,
+    /// The AMP version declared by the page.
+    pub amp: Option<String>,
+    /// The mobile alternates declared by the page.
+    #[serde(default)]
+    pub mobile: Vec<MobileAlternate>,
+    /// The page is an AMP document.
+    #[serde(default)]
+    pub is_amp: bool,
+}
+
+/// The url without its fragment, `None` when it is not http(s).
+fn normalize(url: &str) -> Option<String> {
+    let mut url = url::Url::parse(url).ok()?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    url.set_fragment(None);
+    Some(url.to_string())
+}
+
+impl Alternates {
+    /// Drop the links that are not http(s) and strip the fragments.
+    pub(crate) fn normalized(mut self) -> Self {
+        self.url = normalize(&self.url).unwrap_or(self.url);
+        self.canonical = self.canonical.as_deref().and_then(normalize);
+        self.amp = self.amp.as_deref().and_then(normalize);
+        self.mobile
+            .retain_mut(|mobile| match normalize(&mobile.url) {
+                Some(url) => {
+                    mobile.url = url;
+                    true
+                }
+                None => false,
+            });
+        self
+    }
+
+    /// The page declares a canonical document other than itself.
+    pub fn is_canonicalized(&self) -> bool {
+        self.canonical
+            .as_ref()
+            .is_some_and(|canonical| canonical != &self.url)
+    }
+
+    /// The canonical document of the page: the declared canonical, else the page itself.
+    pub fn canonical_url(&self) -> &str {
+        self.canonical.as_deref().unwrap_or(&self.url)
+    }
+
+    /// The representation to crawl with the preference, falling back to the canonical document
+    /// when the page has no such alternate. An AMP or mobile page always resolves to its canonical
+    /// document for `RepresentationKind::Canonical`.
+    pub fn preferred(&self, preference: RepresentationKind) -> Representation {
+        let alternate = match preference {
+            RepresentationKind::Canonical => None,
+            RepresentationKind::Amp => match &self.amp {
+                Some(amp) => Some(amp.clone()),
+                None if self.is_amp => Some(self.url.clone()),
+                None => None,
+            },
+            RepresentationKind::Mobile => self.mobile.first().map(|mobile| mobile.url.clone()),
+        };
+
+        match alternate {
+            Some(url) => Representation {
+                url,
+                kind: preference,
+            },
+            None => Representation {
+                url: self.canonical_url().to_string(),
+                kind: RepresentationKind::Canonical,
+            },
+        }
+    }
+
+    /// The page is a duplicate of the preferred representation and can be skipped when that is
+    /// crawled instead.
+    pub fn is_duplicate(&self, preference: RepresentationKind) -> bool {
+        self.preferred(preference).url != self.url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alternates(value: serde_json::Value) -> Alternates {
+        serde_json::from_value::<Alternates>(value)
+            .unwrap()
+            .normalized()
+    }
+
+    #[test]
+    fn resolves_amp_pages_to_the_canonical() {
+        let page = alternates(serde_json::json!({
+            "url": "https://example.com/amp/post#top",
+            "canonical": "https://example.com/post",
+            "amp": null,
+            "mobile": [],
+            "isAmp": true,
+        }));
+
+        assert_eq!(page.url, "https://example.com/amp/post");
+        assert!(page.is_canonicalized());
+        assert_eq!(
+            page.preferred(RepresentationKind::Canonical).url,
+            "https://example.com/post"
+        );
+        assert_eq!(
+            page.preferred(RepresentationKind::Amp),
+            Representation {
+                url: "https://example.com/amp/post".into(),
+                kind: RepresentationKind::Amp,
+            }
+        );
+        assert!(page.is_duplicate(RepresentationKind::Canonical));
+        assert!(!page.is_duplicate(RepresentationKind::Amp));
+    }
+
+    #[test]
+    fn falls_back_to_the_canonical() {
+        let page = alternates(serde_json::json!({
+            "url": "https://example.com/post",
+            "canonical": "javascript:void(0)",
+            "amp": "https://example.com/amp/post",
+            "mobile": [{ "url": "https://m.example.com/post", "media": "only screen and (max-width: 640px)" }],
+        }));
+
+        assert_eq!(page.canonical, None);
+        assert!(!page.is_duplicate(RepresentationKind::Canonical));
+        assert_eq!(
+            page.preferred(RepresentationKind::Mobile).url,
+            "https://m.example.com/post"
+        );
+        assert_eq!(
+            page.preferred(RepresentationKind::Amp).url,
+            "https://example.com/amp/post"
+        );
+    }
+}
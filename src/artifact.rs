@@ -0,0 +1,521 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use base64::{engine::general_purpose, Engine as _};
+
+use crate::js_errors::JsError;
+
+/// Collect the metadata of the document.
+pub(crate) const METADATA_JS: &str = r###"(()=>{const m=n=>{const e=document.querySelector(`meta[name="${n}"],meta[property="${n}"]`);return e?e.content:null};const og={};for(const e of document.querySelectorAll('meta[property^="og:"]')){og[e.getAttribute('property').slice(3)]=e.content}const c=document.querySelector('link[rel="canonical"]');return{title:document.title||null,description:m('description'),canonical:c?c.href:null,lang:document.documentElement.lang||null,robots:m('robots'),openGraph:og}})()"###;
+
+/// What `Page::crawl` collects besides the html, metadata and network stats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactOptions {
+    /// Collect the http(s) links of the document.
+    pub links: bool,
+    /// Take a png screenshot.
+    pub screenshot: bool,
+    /// Take the screenshot of the full scrollable page.
+    pub full_page_screenshot: bool,
+    /// Build a HAR log of the requests of the navigation.
+    pub har: bool,
+    /// Wait up to the duration for the network to be idle before collecting.
+    pub network_idle_timeout: Option<Duration>,
+}
+
+impl Default for ArtifactOptions {
+    fn default() -> Self {
+        Self {
+            links: true,
+            screenshot: false,
+            full_page_screenshot: false,
+            har: false,
+            network_idle_timeout: Some(Duration::from_secs(5)),
+        }
+    }
+}
+
+/// The metadata of the document.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageMetadata {
+    /// The title.
+    pub title: Option<String>,
+    /// The `description` meta tag.
+    pub description: Option<String>,
+    /// The canonical link.
+    pub canonical: Option<String>,
+    /// The language of the document element.
+    pub lang: Option<String>,
+    /// The `robots` meta tag.
+    pub robots: Option<String>,
+    /// The `og:` meta tags without the prefix.
+    #[serde(default)]
+    pub open_graph: BTreeMap<String, String>,
+}
+
+/// The requests made by the page during the navigation.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct NetworkStats {
+    /// The requests sent, redirects included.
+    pub requests: usize,
+    /// The requests that failed without a response.
+    pub failed: usize,
+    /// The encoded bytes received.
+    pub bytes: u64,
+    /// The requests per resource type.
+    pub resource_types: BTreeMap<String, usize>,
+    /// The responses per status code.
+    pub status_codes: BTreeMap<u16, usize>,
+}
+
+/// An error of a crawl step, the other steps still ran.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArtifactError {
+    /// The step that failed, e.g. `navigation` or `screenshot`.
+    pub step: String,
+    /// The error message.
+    pub message: String,
+}
+
+/// Everything collected by a single `Page::crawl` call.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CrawlArtifact {
+    /// The url requested.
+    pub url: String,
+    /// The url of the document after redirects.
+    pub final_url: Option<String>,
+    /// The status code of the document response.
+    pub status: Option<u16>,
+    /// The html of the document.
+    pub html: Option<String>,
+    /// The metadata of the document.
+    pub metadata: PageMetadata,
+    /// The http(s) links of the document.
+    pub links: Vec<String>,
+    /// The requests of the navigation.
+    pub network: NetworkStats,
+    /// The png screenshot, base64 encoded when serialized.
+    #[serde(with = "base64_bytes", default)]
+    pub screenshot: Option<Vec<u8>>,
+    /// The HAR log of the requests of the navigation.
+    pub har: Option<Har>,
+    /// The uncaught exceptions of the document.
+    pub js_errors: Vec<JsError>,
+    /// The failed crawl steps.
+    pub errors: Vec<ArtifactError>,
+    /// The time the crawl took in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl CrawlArtifact {
+    /// Record the error of the crawl step.
+    pub(crate) fn push_error(&mut self, step: &str, err: impl std::fmt::Display) {
+        self.errors.push(ArtifactError {
+            step: step.to_string(),
+            message: err.to_string(),
+        });
+    }
+
+    /// Every crawl step succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+mod base64_bytes {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_some(&general_purpose::STANDARD.encode(bytes)),
+            _ => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| {
+                general_purpose::STANDARD
+                    .decode(value)
+                    .map_err(serde::de::Error::custom)
+            })
+            .transpose()
+    }
+}
+
+/// A HAR 1.2 log.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Har {
+    /// The log.
+    pub log: HarLog,
+}
+
+/// The log of a HAR file.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HarLog {
+    /// The HAR version.
+    pub version: String,
+    /// The application that created the log.
+    pub creator: HarCreator,
+    /// The requests.
+    pub entries: Vec<HarEntry>,
+}
+
+/// The application that created a HAR log.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HarCreator {
+    /// The name.
+    pub name: String,
+    /// The version.
+    pub version: String,
+}
+
+/// A request of a HAR log.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    /// The ISO 8601 start of the request.
+    pub started_date_time: String,
+    /// The total time of the request in milliseconds, `-1` when unknown.
+    pub time: f64,
+    /// The request.
+    pub request: HarRequest,
+    /// The response.
+    pub response: HarResponse,
+    /// The resource type as reported by chromium.
+    #[serde(rename = "_resourceType", skip_serializing_if = "Option::is_none")]
+    pub resource_type: Option<String>,
+    /// The error of a failed request.
+    #[serde(rename = "_error", skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The request of a HAR entry.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    /// The method.
+    pub method: String,
+    /// The url.
+    pub url: String,
+    /// The http version.
+    pub http_version: String,
+}
+
+/// The response of a HAR entry.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    /// The status code, `0` when no response was received.
+    pub status: u16,
+    /// The status text.
+    pub status_text: String,
+    /// The http version.
+    pub http_version: String,
+    /// The content of the response.
+    pub content: HarContent,
+    /// The encoded bytes received, `-1` when unknown.
+    pub body_size: i64,
+}
+
+/// The content of a HAR response.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    /// The size of the content, `-1` when unknown.
+    pub size: i64,
+    /// The mime type.
+    pub mime_type: String,
+}
+
+/// A request recorded from the network events.
+#[derive(Debug, Default, Clone)]
+struct RecordedRequest {
+    url: String,
+    method: String,
+    resource_type: Option<String>,
+    /// Seconds since the epoch.
+    wall_time: f64,
+    /// The monotonic start in seconds.
+    started: f64,
+    ended: Option<f64>,
+    status: Option<u16>,
+    status_text: String,
+    mime_type: String,
+    protocol: Option<String>,
+    bytes: Option<u64>,
+    error: Option<String>,
+}
+
+/// Records the requests of a navigation from the network events.
+#[derive(Debug, Default)]
+pub(crate) struct NetworkRecorder {
+    requests: Vec<RecordedRequest>,
+    /// The latest request of the request id, redirects reuse the id.
+    index: HashMap<String, usize>,
+    /// The request id of the first document request.
+    document: Option<String>,
+}
+
+impl NetworkRecorder {
+    /// A request was sent, the previous request of the id redirected with the status.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn request(
+        &mut self,
+        id: &str,
+        url: &str,
+        method: &str,
+        resource_type: Option<&str>,
+        wall_time: f64,
+        timestamp: f64,
+        redirect_status: Option<u16>,
+    ) {
+        if let (Some(status), Some(previous)) = (redirect_status, self.latest(id)) {
+            previous.status = Some(status);
+            previous.ended = Some(timestamp);
+        }
+
+        if self.document.is_none() && resource_type == Some("Document") {
+            self.document = Some(id.to_string());
+        }
+
+        self.index.insert(id.to_string(), self.requests.len());
+        self.requests.push(RecordedRequest {
+            url: url.to_string(),
+            method: method.to_string(),
+            resource_type: resource_type.map(str::to_string),
+            wall_time,
+            started: timestamp,
+            ..Default::default()
+        });
+    }
+
+    /// The response of the request was received.
+    pub(crate) fn response(
+        &mut self,
+        id: &str,
+        status: u16,
+        status_text: &str,
+        mime_type: &str,
+        protocol: Option<&str>,
+    ) {
+        if let Some(request) = self.latest(id) {
+            request.status = Some(status);
+            request.status_text = status_text.to_string();
+            request.mime_type = mime_type.to_string();
+            request.protocol = protocol.map(str::to_string);
+        }
+    }
+
+    /// The request finished loading the encoded bytes.
+    pub(crate) fn finished(&mut self, id: &str, timestamp: f64, bytes: f64) {
+        if let Some(request) = self.latest(id) {
+            request.ended = Some(timestamp);
+            request.bytes = Some(bytes.max(0.0) as u64);
+        }
+    }
+
+    /// The request failed.
+    pub(crate) fn failed(&mut self, id: &str, timestamp: f64, error: &str) {
+        if let Some(request) = self.latest(id) {
+            request.ended = Some(timestamp);
+            request.error = Some(error.to_string());
+        }
+    }
+
+    fn latest(&mut self, id: &str) -> Option<&mut RecordedRequest> {
+        let index = *self.index.get(id)?;
+        self.requests.get_mut(index)
+    }
+
+    /// The status of the final response of the document.
+    pub(crate) fn document_status(&self) -> Option<u16> {
+        let index = *self.index.get(self.document.as_ref()?)?;
+        self.requests.get(index)?.status
+    }
+
+    pub(crate) fn stats(&self) -> NetworkStats {
+        let mut stats = NetworkStats {
+            requests: self.requests.len(),
+            ..Default::default()
+        };
+
+        for request in &self.requests {
+            if request.error.is_some() {
+                stats.failed += 1;
+            }
+            if let Some(status) = request.status {
+                *stats.status_codes.entry(status).or_default() += 1;
+            }
+            if let Some(resource_type) = &request.resource_type {
+                *stats
+                    .resource_types
+                    .entry(resource_type.clone())
+                    .or_default() += 1;
+            }
+            stats.bytes += request.bytes.unwrap_or_default();
+        }
+
+        stats
+    }
+
+    pub(crate) fn har(&self) -> Har {
+        let entries = self
+            .requests
+            .iter()
+            .map(|request| {
+                let http_version = request.protocol.clone().unwrap_or_default();
+                let size = request.bytes.map_or(-1, |bytes| bytes as i64);
+
+                HarEntry {
+                    started_date_time: iso8601(request.wall_time),
+                    time: request
+                        .ended
+                        .map_or(-1.0, |ended| ((ended - request.started) * 1000.0).max(0.0)),
+                    request: HarRequest {
+                        method: request.method.clone(),
+                        url: request.url.clone(),
+                        http_version: http_version.clone(),
+                    },
+                    response: HarResponse {
+                        status: request.status.unwrap_or_default(),
+                        status_text: request.status_text.clone(),
+                        http_version,
+                        content: HarContent {
+                            size,
+                            mime_type: request.mime_type.clone(),
+                        },
+                        body_size: size,
+                    },
+                    resource_type: request.resource_type.clone(),
+                    error: request.error.clone(),
+                }
+            })
+            .collect();
+
+        Har {
+            log: HarLog {
+                version: "1.2".to_string(),
+                creator: HarCreator {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                entries,
+            },
+        }
+    }
+}
+
+/// The UTC ISO 8601 date of the seconds since the epoch.
+pub(crate) fn iso8601(secs: f64) -> String {
+    let millis = (secs.max(0.0) * 1000.0) as u64;
+    let (days, rem) = (millis / 86_400_000, millis % 86_400_000);
+
+    // the civil date of the days since the epoch.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3_600_000,
+        rem / 60_000 % 60,
+        rem / 1_000 % 60,
+        rem % 1_000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso8601_dates() {
+        assert_eq!(iso8601(0.0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(iso8601(951_782_400.5), "2000-02-29T00:00:00.500Z");
+        assert_eq!(iso8601(1_700_000_000.0), "2023-11-14T22:13:20.000Z");
+    }
+
+    #[test]
+    fn recorder_follows_redirects() {
+        let mut recorder = NetworkRecorder::default();
+
+        recorder.request(
+            "1",
+            "http://a.com/",
+            "GET",
+            Some("Document"),
+            10.0,
+            1.0,
+            None,
+        );
+        recorder.request(
+            "1",
+            "https://a.com/",
+            "GET",
+            Some("Document"),
+            10.25,
+            1.25,
+            Some(301),
+        );
+        recorder.response("1", 200, "OK", "text/html", Some("h2"));
+        recorder.finished("1", 1.5, 2048.0);
+        recorder.request(
+            "2",
+            "https://a.com/x.js",
+            "GET",
+            Some("Script"),
+            10.2,
+            1.2,
+            None,
+        );
+        recorder.failed("2", 1.3, "net::ERR_FAILED");
+
+        assert_eq!(recorder.document_status(), Some(200));
+
+        let stats = recorder.stats();
+
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.bytes, 2048);
+        assert_eq!(stats.status_codes.get(&301), Some(&1));
+        assert_eq!(stats.resource_types.get("Document"), Some(&2));
+
+        let har = recorder.har();
+
+        assert_eq!(har.log.entries.len(), 3);
+        assert_eq!(har.log.entries[1].response.status, 200);
+        assert_eq!(har.log.entries[1].time, 250.0);
+        assert_eq!(har.log.entries[2].error.as_deref(), Some("net::ERR_FAILED"));
+    }
+
+    #[test]
+    fn artifact_round_trip() {
+        let artifact = CrawlArtifact {
+            url: "https://a.com/".into(),
+            screenshot: Some(vec![137, 80, 78, 71]),
+            ..Default::default()
+        };
+
+        let value = serde_json::to_value(&artifact).unwrap();
+
+        assert_eq!(value["screenshot"], "iVBORw==");
+        assert_eq!(
+            serde_json::from_value::<CrawlArtifact>(value).unwrap(),
+            artifact
+        );
+    }
+}
@@ -53,6 +53,11 @@ impl Command {
         self
     }
 
+    /// Run the command to completion, collecting its stdout and stderr.
+    pub async fn output(&mut self) -> std::io::Result<std::process::Output> {
+        self.inner.output().await
+    }
+
     pub fn spawn(&mut self) -> std::io::Result<Child> {
         let inner = self.inner.spawn()?;
         Ok(Child::new(inner))
@@ -0,0 +1,291 @@
+//! A blocking API mirroring [`crate::Browser`] and [`crate::Page`].
+//!
+//! The browser drives its handler on an internal runtime, every method blocks the calling thread
+//! until the async method completed. The methods must not be called from an async context, they
+//! return an error there instead of blocking a worker of the caller's runtime.
+//!
+//! ```no_run
+//! use chromiumoxide::blocking::Browser;
+//! use chromiumoxide::browser::BrowserConfig;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut browser = Browser::launch(BrowserConfig::builder().build()?)?;
+//! let page = browser.new_page("https://example.com")?;
+//! let html = page.content()?;
+//! browser.close()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+use crate::browser::BrowserConfig;
+use crate::cdp::browser_protocol::browser::GetVersionReturns;
+use crate::cdp::browser_protocol::page::{NavigateParams, PrintToPdfParams};
+use crate::cdp::browser_protocol::target::CreateTargetParams;
+use crate::error::{CdpError, Result};
+use crate::handler::{Handler, HandlerConfig};
+use crate::js::{Evaluation, EvaluationResult};
+use crate::page::ScreenshotParams;
+
+/// The runtime of the blocking browser and its pages.
+fn new_runtime() -> Result<Arc<Runtime>> {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("chromey-blocking")
+        .enable_all()
+        .build()
+        .map(Arc::new)
+        .map_err(CdpError::from)
+}
+
+/// Block the calling thread on the future, failing when called from an async context.
+fn block_on<T>(runtime: &Runtime, fut: impl Future<Output = Result<T>>) -> Result<T> {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the blocking api must not be called from an async context",
+        )
+        .into());
+    }
+
+    runtime.block_on(fut)
+}
+
+/// A blocking [`crate::Browser`].
+#[derive(Debug)]
+pub struct Browser {
+    // dropped before the runtime.
+    inner: crate::Browser,
+    handler: JoinHandle<()>,
+    runtime: Arc<Runtime>,
+}
+
+impl Browser {
+    /// Launch a new chromium instance, see [`crate::Browser::launch`].
+    pub fn launch(config: BrowserConfig) -> Result<Self> {
+        let runtime = new_runtime()?;
+        let (inner, handler) = block_on(&runtime, crate::Browser::launch(config))?;
+
+        Ok(Self::with_handler(inner, handler, runtime))
+    }
+
+    /// Connect to a running chromium instance, see [`crate::Browser::connect`].
+    pub fn connect(url: impl Into<String>) -> Result<Self> {
+        Self::connect_with_config(url, HandlerConfig::default())
+    }
+
+    /// Connect to a running chromium instance with the handler config, see
+    /// [`crate::Browser::connect_with_config`].
+    pub fn connect_with_config(url: impl Into<String>, config: HandlerConfig) -> Result<Self> {
+        let runtime = new_runtime()?;
+        let (inner, handler) =
+            block_on(&runtime, crate::Browser::connect_with_config(url, config))?;
+
+        Ok(Self::with_handler(inner, handler, runtime))
+    }
+
+    fn with_handler(inner: crate::Browser, mut handler: Handler, runtime: Arc<Runtime>) -> Self {
+        let handler = runtime.spawn(async move {
+            while let Some(event) = handler.next().await {
+                if event.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            inner,
+            handler,
+            runtime,
+        }
+    }
+
+    fn block_on<T>(&self, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        block_on(&self.runtime, fut)
+    }
+
+    /// The async browser, e.g. to run a method without a blocking counterpart with
+    /// [`Browser::block_on_inner`].
+    pub fn inner(&self) -> &crate::Browser {
+        &self.inner
+    }
+
+    /// Block on a future using the async browser, panics when called from an async context.
+    pub fn block_on_inner<'a, F, Fut>(&'a self, f: F) -> Fut::Output
+    where
+        F: FnOnce(&'a crate::Browser) -> Fut,
+        Fut: Future + 'a,
+    {
+        self.runtime.block_on(f(&self.inner))
+    }
+
+    /// Create a new page, see [`crate::Browser::new_page`].
+    pub fn new_page(&self, params: impl Into<CreateTargetParams>) -> Result<Page> {
+        let inner = self.block_on(self.inner.new_page(params))?;
+
+        Ok(Page {
+            inner,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// The pages of the browser, see [`crate::Browser::pages`].
+    pub fn pages(&self) -> Result<Vec<Page>> {
+        let pages = self.block_on(self.inner.pages())?;
+
+        Ok(pages
+            .into_iter()
+            .map(|inner| Page {
+                inner,
+                runtime: self.runtime.clone(),
+            })
+            .collect())
+    }
+
+    /// The version of the browser.
+    pub fn version(&self) -> Result<GetVersionReturns> {
+        self.block_on(self.inner.version())
+    }
+
+    /// The user agent of the browser.
+    pub fn user_agent(&self) -> Result<String> {
+        self.block_on(self.inner.user_agent())
+    }
+
+    /// Close the browser and wait for the launched process to exit.
+    pub fn close(&mut self) -> Result<()> {
+        let runtime = self.runtime.clone();
+
+        block_on(&runtime, async {
+            self.inner.close().await?;
+            self.inner.wait().await?;
+            Ok::<_, CdpError>(())
+        })?;
+
+        self.handler.abort();
+
+        Ok(())
+    }
+}
+
+/// A blocking [`crate::Page`].
+#[derive(Debug, Clone)]
+pub struct Page {
+    inner: crate::Page,
+    runtime: Arc<Runtime>,
+}
+
+impl Page {
+    fn block_on<T>(&self, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        block_on(&self.runtime, fut)
+    }
+
+    /// The async page.
+    pub fn inner(&self) -> &crate::Page {
+        &self.inner
+    }
+
+    /// Block on a future using the async page, panics when called from an async context.
+    pub fn block_on_inner<'a, F, Fut>(&'a self, f: F) -> Fut::Output
+    where
+        F: FnOnce(&'a crate::Page) -> Fut,
+        Fut: Future + 'a,
+    {
+        self.runtime.block_on(f(&self.inner))
+    }
+
+    /// Navigate to the url and wait for the navigation, see [`crate::Page::goto`].
+    pub fn goto(&self, params: impl Into<NavigateParams>) -> Result<&Self> {
+        self.block_on(self.inner.goto(params))?;
+        Ok(self)
+    }
+
+    /// Reload the page and wait for the navigation.
+    pub fn reload(&self) -> Result<&Self> {
+        self.block_on(self.inner.reload())?;
+        Ok(self)
+    }
+
+    /// Wait for the pending navigation to finish.
+    pub fn wait_for_navigation(&self) -> Result<&Self> {
+        self.block_on(self.inner.wait_for_navigation())?;
+        Ok(self)
+    }
+
+    /// The url of the page.
+    pub fn url(&self) -> Result<Option<String>> {
+        self.block_on(self.inner.url())
+    }
+
+    /// The title of the page.
+    pub fn get_title(&self) -> Result<Option<String>> {
+        self.block_on(self.inner.get_title())
+    }
+
+    /// The html of the page.
+    pub fn content(&self) -> Result<String> {
+        self.block_on(self.inner.content())
+    }
+
+    /// Set the html of the page.
+    pub fn set_content(&self, html: impl AsRef<str>) -> Result<&Self> {
+        self.block_on(self.inner.set_content(html))?;
+        Ok(self)
+    }
+
+    /// Evaluate the expression or function, see [`crate::Page::evaluate`].
+    pub fn evaluate(&self, evaluate: impl Into<Evaluation>) -> Result<EvaluationResult> {
+        self.block_on(self.inner.evaluate(evaluate))
+    }
+
+    /// Take a screenshot of the page.
+    pub fn screenshot(&self, params: impl Into<ScreenshotParams>) -> Result<Vec<u8>> {
+        self.block_on(self.inner.screenshot(params))
+    }
+
+    /// Print the page as pdf.
+    pub fn pdf(&self, params: PrintToPdfParams) -> Result<Vec<u8>> {
+        self.block_on(self.inner.pdf(params))
+    }
+
+    /// Close the page.
+    pub fn close(self) -> Result<()> {
+        self.block_on(self.inner.close())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_on_the_future() {
+        let runtime = new_runtime().unwrap();
+
+        let value = block_on(&runtime, async {
+            tokio::task::yield_now().await;
+            Ok(tokio::spawn(async { 42 }).await.unwrap())
+        })
+        .unwrap();
+
+        assert_eq!(value, 42);
+    }
+
+    #[tokio::test]
+    async fn fails_in_an_async_context() {
+        let runtime = new_runtime().unwrap();
+
+        let err = block_on(&runtime, async { Ok(()) }).unwrap_err();
+        assert!(matches!(err, CdpError::Io(ref e) if e.kind() == io::ErrorKind::Other));
+
+        // the runtime is dropped off the async context.
+        std::thread::spawn(move || drop(runtime)).join().unwrap();
+    }
+}
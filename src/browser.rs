@@ -1,6 +1,7 @@
 use hashbrown::HashMap;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use std::future::Future;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use std::{
     io,
@@ -12,17 +13,23 @@ use futures::channel::oneshot::channel as oneshot_channel;
 use futures::select;
 use futures::SinkExt;
 
+#[cfg(not(target_arch = "wasm32"))]
 use crate::async_process::{self, Child, ExitStatus, Stdio};
 use crate::cmd::{to_command_response, CommandMessage};
-use crate::conn::Connection;
+use crate::conn::{Connection, Transport};
 use crate::detection::{self, DetectionOptions};
+use crate::duplicates::{DuplicateCluster, DuplicateIndex};
 use crate::error::{BrowserStderr, CdpError, Result};
 use crate::handler::browser::BrowserContext;
 use crate::handler::viewport::Viewport;
 use crate::handler::{Handler, HandlerConfig, HandlerMessage, REQUEST_TIMEOUT};
+use crate::injection::{InitScript, InjectionScope};
+use crate::javascript::bundle::ScriptBundler;
 use crate::listeners::{EventListenerRequest, EventStream};
 use crate::page::Page;
+use crate::policy::CrawlPolicy;
 use crate::utils;
+use crate::webhook::{SequencedEvent, WebhookConfig};
 use chromiumoxide_cdp::cdp::browser_protocol::browser::{
     BrowserContextId, CloseReturns, GetVersionParams, GetVersionReturns,
 };
@@ -41,6 +48,7 @@ use spider_network_blocker::intercept_manager::NetworkInterceptManager;
 /// Default `Browser::launch` timeout in MS
 pub const LAUNCH_TIMEOUT: u64 = 20_000;
 
+#[cfg(not(target_arch = "wasm32"))]
 lazy_static::lazy_static! {
     /// The request client to get the web socket url.
     static ref REQUEST_CLIENT: reqwest::Client = reqwest::Client::builder()
@@ -68,11 +76,18 @@ pub struct Browser {
     /// How the spawned chromium instance was configured, if any
     config: Option<BrowserConfig>,
     /// The spawned chromium instance
+    #[cfg(not(target_arch = "wasm32"))]
     child: Option<Child>,
     /// The debug web socket url of the chromium instance
     debug_ws_url: String,
     /// The context of the browser
     pub browser_context: BrowserContext,
+    /// The page events of the targets.
+    page_events: tokio::sync::broadcast::Sender<SequencedEvent>,
+    /// The policies of the browser, swapped with `update_policy`.
+    policy: Arc<RwLock<Arc<CrawlPolicy>>>,
+    /// The simhashes of the pages fingerprinted.
+    duplicate_index: Option<Arc<DuplicateIndex>>,
 }
 
 /// Browser connection information.
@@ -102,6 +117,7 @@ impl Browser {
     /// Connect to an already running chromium instance via the given URL.
     ///
     /// If the URL is a http(s) URL, it will first attempt to retrieve the Websocket URL from the `json/version` endpoint.
+    #[cfg(not(target_arch = "wasm32"))]
     pub async fn connect(url: impl Into<String>) -> Result<(Self, Handler)> {
         Self::connect_with_config(url, HandlerConfig::default()).await
     }
@@ -109,6 +125,7 @@ impl Browser {
     // Connect to an already running chromium instance with a given `HandlerConfig`.
     ///
     /// If the URL is a http URL, it will first attempt to retrieve the Websocket URL from the `json/version` endpoint.
+    #[cfg(not(target_arch = "wasm32"))]
     pub async fn connect_with_config(
         url: impl Into<String>,
         config: HandlerConfig,
@@ -150,6 +167,30 @@ impl Browser {
 
         let conn = Connection::<CdpEventMessage>::connect(&debug_ws_url).await?;
 
+        Ok(Self::with_connection(conn, debug_ws_url, config))
+    }
+
+    /// Connect to a chromium instance over a custom transport, e.g. a browser `WebSocket` on
+    /// targets without the native websocket. The `debug_ws_url` is informational only. On wasm32
+    /// this is the only way to connect, the launching and the native websocket are not built.
+    pub fn connect_with_transport(
+        transport: impl Transport + 'static,
+        debug_ws_url: impl Into<String>,
+        config: HandlerConfig,
+    ) -> (Self, Handler) {
+        Self::with_connection(
+            Connection::with_transport(transport),
+            debug_ws_url.into(),
+            config,
+        )
+    }
+
+    /// The browser and handler of the connection.
+    fn with_connection(
+        conn: Connection<CdpEventMessage>,
+        debug_ws_url: String,
+        config: HandlerConfig,
+    ) -> (Self, Handler) {
         let (tx, rx) = channel(1000);
 
         let handler_config = BrowserConfig {
@@ -168,21 +209,37 @@ impl Browser {
             service_worker_enabled: config.service_worker_enabled,
             intercept_manager: config.intercept_manager,
             max_bytes_allowed: config.max_bytes_allowed,
+            anti_debugging: config.anti_debugging,
+            stability_shims: config.stability_shims,
+            consent_policy: config.consent_policy,
+            header_shaping: config.header_shaping.clone(),
+            request_signing: config.request_signing.clone(),
+            network_rules: config.network_rules.clone(),
+            redirect_policy: config.redirect_policy.clone(),
+            flight_recorder: config.flight_recorder,
+            duplicate_detection: config.duplicate_detection,
             ..Default::default()
         };
 
+        let policy = Arc::new(RwLock::new(Arc::new(CrawlPolicy::from(&config))));
         let fut = Handler::new(conn, rx, config);
         let browser_context = fut.default_browser_context().clone();
+        let page_events = fut.page_events().clone();
+        let duplicate_index = fut.duplicate_index().cloned();
 
         let browser = Self {
             sender: tx,
             config: Some(handler_config),
+            #[cfg(not(target_arch = "wasm32"))]
             child: None,
             debug_ws_url,
             browser_context,
+            page_events,
+            policy,
+            duplicate_index,
         };
 
-        Ok((browser, fut))
+        (browser, fut)
     }
 
     /// Launches a new instance of `chromium` in the background and attaches to
@@ -193,6 +250,7 @@ impl Browser {
     /// This fails if no web socket url could be detected from the child
     /// processes stderr for more than the configured `launch_timeout`
     /// (20 seconds by default).
+    #[cfg(not(target_arch = "wasm32"))]
     pub async fn launch(mut config: BrowserConfig) -> Result<(Self, Handler)> {
         // Canonalize paths to reduce issues with sandboxing
         config.executable = utils::canonicalize_except_snap(config.executable).await?;
@@ -209,7 +267,7 @@ impl Browser {
             child: &mut Child,
         ) -> Result<(String, Connection<CdpEventMessage>)> {
             let dur = config.launch_timeout;
-            let timeout_fut = Box::pin(tokio::time::sleep(dur));
+            let timeout_fut = Box::pin(crate::runtime::sleep(dur));
 
             // extract the ws:
             let debug_ws_url = ws_url_from_output(child, timeout_fut).await?;
@@ -255,10 +313,22 @@ impl Browser {
             created_first_target: false,
             intercept_manager: config.intercept_manager,
             max_bytes_allowed: config.max_bytes_allowed,
+            anti_debugging: config.anti_debugging,
+            stability_shims: config.stability_shims,
+            consent_policy: config.consent_policy,
+            header_shaping: config.header_shaping.clone(),
+            request_signing: config.request_signing.clone(),
+            network_rules: config.network_rules.clone(),
+            redirect_policy: config.redirect_policy.clone(),
+            flight_recorder: config.flight_recorder,
+            duplicate_detection: config.duplicate_detection,
         };
 
+        let policy = Arc::new(RwLock::new(Arc::new(CrawlPolicy::from(&handler_config))));
         let fut = Handler::new(conn, rx, handler_config);
         let browser_context = fut.default_browser_context().clone();
+        let page_events = fut.page_events().clone();
+        let duplicate_index = fut.duplicate_index().cloned();
 
         let browser = Self {
             sender: tx,
@@ -266,6 +336,9 @@ impl Browser {
             child: Some(child),
             debug_ws_url,
             browser_context,
+            page_events,
+            policy,
+            duplicate_index,
         };
 
         Ok((browser, fut))
@@ -316,6 +389,7 @@ impl Browser {
     ///
     /// This call has no effect if this [`Browser`] did not spawn any chromium instance (e.g.
     /// connected to an existing browser through [`Browser::connect`])
+    #[cfg(not(target_arch = "wasm32"))]
     pub async fn wait(&mut self) -> io::Result<Option<ExitStatus>> {
         if let Some(child) = self.child.as_mut() {
             Ok(Some(child.wait().await?))
@@ -332,6 +406,7 @@ impl Browser {
     ///
     /// This call has no effect if this [`Browser`] did not spawn any chromium instance (e.g.
     /// connected to an existing browser through [`Browser::connect`])
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
         if let Some(child) = self.child.as_mut() {
             child.try_wait()
@@ -350,11 +425,13 @@ impl Browser {
     ///
     /// This call has no effect if this [`Browser`] did not spawn any chromium instance (e.g.
     /// connected to an existing browser through [`Browser::connect`])
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn get_mut_child(&mut self) -> Option<&mut Child> {
         self.child.as_mut()
     }
 
     /// Has a browser instance launched on system.
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn has_child(&self) -> bool {
         self.child.is_some()
     }
@@ -369,6 +446,7 @@ impl Browser {
     ///
     /// This call has no effect if this [`Browser`] did not spawn any chromium instance (e.g.
     /// connected to an existing browser through [`Browser::connect`])
+    #[cfg(not(target_arch = "wasm32"))]
     pub async fn kill(&mut self) -> Option<io::Result<()>> {
         match self.child.as_mut() {
             Some(child) => Some(child.kill().await),
@@ -446,6 +524,14 @@ impl Browser {
         self.is_incognito_configured() || self.browser_context.is_incognito()
     }
 
+    /// Replay the pages of the browser context offline: the cache interceptors of its pages
+    /// spawned without a cache strategy serve every request from the local cache and answer the
+    /// misses with a synthetic `504`.
+    #[cfg(feature = "_cache")]
+    pub fn set_offline_replay(&self, offline: bool) {
+        crate::cache::offline::set_offline_context(self.browser_context.id(), offline);
+    }
+
     /// The config of the spawned chromium instance if any.
     pub fn config(&self) -> Option<&BrowserConfig> {
         self.config.as_ref()
@@ -515,6 +601,128 @@ impl Browser {
         rx.await?.ok_or(CdpError::NotFound)
     }
 
+    /// Check the health of the browser, e.g. for the probes of a service deployment. The checks
+    /// never fail, a handler or browser not answering within 5 seconds is reported as such.
+    pub async fn health(&self) -> crate::health::HealthReport {
+        use crate::health::{disk_free, process_tree_rss, HealthReport, HEALTH_TIMEOUT};
+
+        let mut report = HealthReport::default();
+
+        let handler = crate::runtime::timeout(HEALTH_TIMEOUT, async {
+            let (tx, rx) = oneshot_channel();
+            self.sender
+                .clone()
+                .send(HandlerMessage::Health(tx))
+                .await
+                .ok()?;
+            rx.await.ok()
+        })
+        .await
+        .ok()
+        .flatten();
+
+        if let Some(handler) = handler {
+            report.connected = !handler.closing;
+            report.last_event_age_ms = handler.last_message.map(|age| age.as_millis() as u64);
+            report.open_targets = handler.targets;
+            report.open_pages = handler.pages;
+            report.pending_commands = handler.pending_commands;
+
+            let started = crate::runtime::Instant::now();
+
+            if let Ok(Ok(_)) = crate::runtime::timeout(HEALTH_TIMEOUT, self.version()).await {
+                report.latency_ms = Some(started.elapsed().as_millis() as u64);
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            report.chrome_rss_bytes = self
+                .child
+                .as_ref()
+                .and_then(|child| child.inner.id())
+                .and_then(process_tree_rss);
+        }
+
+        report.disk_free_bytes = match self
+            .config
+            .as_ref()
+            .and_then(|config| config.user_data_dir.as_deref())
+        {
+            Some(dir) => disk_free(dir),
+            _ => disk_free(&std::env::temp_dir()),
+        };
+
+        report
+    }
+
+    /// Register a script injected on every new document of all current and future pages in the scope.
+    /// Scripts are injected in registration order, registering the same name again replaces the script.
+    pub async fn register_init_script(
+        &self,
+        name: impl Into<String>,
+        source: impl Into<String>,
+        scope: InjectionScope,
+    ) -> Result<()> {
+        self.sender
+            .clone()
+            .send(HandlerMessage::RegisterInitScript(InitScript {
+                name: name.into(),
+                source: source.into(),
+                scope,
+            }))
+            .await?;
+        Ok(())
+    }
+
+    /// The current policies of the browser.
+    pub fn policy(&self) -> Arc<CrawlPolicy> {
+        match self.policy.read() {
+            Ok(policy) => policy.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Swap the blocking, scope and politeness policies at runtime. The blocking and scope
+    /// policies apply to the existing and new pages at once, from their next intercepted request,
+    /// the politeness policy to the next urls of the bulk jobs.
+    pub async fn update_policy(&self, policy: CrawlPolicy) -> Result<()> {
+        let policy = Arc::new(policy);
+
+        self.sender
+            .clone()
+            .send(HandlerMessage::UpdatePolicy(policy.clone()))
+            .await?;
+
+        match self.policy.write() {
+            Ok(mut current) => *current = policy,
+            Err(poisoned) => *poisoned.into_inner() = policy,
+        }
+
+        Ok(())
+    }
+
+    /// Bundle a local entry file with its imports and register it as an init script.
+    pub async fn register_init_module(
+        &self,
+        name: impl Into<String>,
+        entry: impl AsRef<Path>,
+        scope: InjectionScope,
+        bundler: &dyn ScriptBundler,
+    ) -> Result<()> {
+        let source = bundler.bundle(entry.as_ref()).await?;
+        self.register_init_script(name, source, scope).await
+    }
+
+    /// Remove a registered init script. Pages created afterwards no longer get the script.
+    pub async fn unregister_init_script(&self, name: impl Into<String>) -> Result<()> {
+        self.sender
+            .clone()
+            .send(HandlerMessage::UnregisterInitScript(name.into()))
+            .await?;
+        Ok(())
+    }
+
     /// Set listener for browser event
     pub async fn event_listener<T: IntoEventKind>(&self) -> Result<EventStream<T>> {
         let (tx, rx) = unbounded();
@@ -528,6 +736,36 @@ impl Browser {
         Ok(EventStream::new(rx))
     }
 
+    /// Subscribe to the webhook bus of the browser, e.g. completed navigations and blocked
+    /// requests. The events of a target arrive in order with increasing seqs, a subscriber
+    /// lagging behind the bus misses the oldest events, see [`crate::webhook::SequenceTracker`].
+    /// Only the events of this bus are sequenced, not the events of `Page::event_listener`.
+    pub fn page_events(&self) -> tokio::sync::broadcast::Receiver<SequencedEvent> {
+        self.page_events.subscribe()
+    }
+
+    /// The clusters of near duplicate urls among the pages fingerprinted, the largest first, e.g.
+    /// to stop crawling a template generated infinite space. Empty unless the browser is
+    /// configured with `BrowserConfigBuilder::with_duplicate_detection`.
+    pub fn duplicates(&self) -> Vec<DuplicateCluster> {
+        self.duplicate_index
+            .as_ref()
+            .map(|index| index.clusters())
+            .unwrap_or_default()
+    }
+
+    /// The index of the simhashes of the pages fingerprinted, to index the pages fetched
+    /// outside of the browser as well.
+    pub fn duplicate_index(&self) -> Option<&Arc<DuplicateIndex>> {
+        self.duplicate_index.as_ref()
+    }
+
+    /// POST the selected page events as JSON to the webhook url, retrying failed deliveries.
+    /// The returned task runs until the handler of the browser is dropped.
+    pub fn webhook(&self, config: WebhookConfig) -> crate::runtime::JoinHandle<()> {
+        crate::webhook::spawn_webhook(self.page_events(), config)
+    }
+
     /// Creates a new empty browser context.
     pub async fn create_browser_context(
         &mut self,
@@ -603,6 +841,7 @@ impl Browser {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl Drop for Browser {
     fn drop(&mut self) {
         if let Some(child) = self.child.as_mut() {
@@ -631,6 +870,7 @@ impl Drop for Browser {
 /// - [`CdpError::LaunchExit`]: the browser process exits (or is killed)
 /// - [`CdpError::LaunchIo`]: an input/output error occurs when await the process exit or reading
 ///   the browser's stderr: end of stream, invalid UTF-8, other
+#[cfg(not(target_arch = "wasm32"))]
 async fn ws_url_from_output(
     child_process: &mut Child,
     timeout_fut: impl Future<Output = ()> + Unpin,
@@ -691,6 +931,75 @@ pub enum HeadlessMode {
     New,
 }
 
+/// The HTTP versions the browser may negotiate. Chrome has no switch to forbid a protocol for a
+/// single origin, HTTP/2 and HTTP/3 are allowed or forbidden for all origins while HTTP/3 can be
+/// forced per origin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtocolPolicy {
+    /// Allow HTTP/2 in ALPN, `Some(false)` forces HTTP/1.1. `None` keeps the browser default.
+    pub http2: Option<bool>,
+    /// Allow HTTP/3 (QUIC). `None` keeps the browser default.
+    pub http3: Option<bool>,
+    /// Origins (`host:port`) that use HTTP/3 right away without an `Alt-Svc` discovery.
+    /// Ignored when HTTP/3 is forbidden.
+    pub force_http3_origins: Vec<String>,
+}
+
+impl ProtocolPolicy {
+    /// Only negotiate HTTP/1.1.
+    pub fn http1_only() -> Self {
+        Self {
+            http2: Some(false),
+            http3: Some(false),
+            force_http3_origins: Vec::new(),
+        }
+    }
+
+    /// Allow or forbid HTTP/2.
+    pub fn http2(mut self, enabled: bool) -> Self {
+        self.http2 = Some(enabled);
+        self
+    }
+
+    /// Allow or forbid HTTP/3.
+    pub fn http3(mut self, enabled: bool) -> Self {
+        self.http3 = Some(enabled);
+        self
+    }
+
+    /// Force HTTP/3 for the origin, e.g. `example.com:443`.
+    pub fn force_http3_on(mut self, origin: impl Into<String>) -> Self {
+        self.force_http3_origins.push(origin.into());
+        self
+    }
+
+    /// The launch flags of the policy.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if self.http2 == Some(false) {
+            args.push("--disable-http2".to_string());
+        }
+
+        match self.http3 {
+            Some(false) => args.push("--disable-quic".to_string()),
+            _ => {
+                if self.http3 == Some(true) || !self.force_http3_origins.is_empty() {
+                    args.push("--enable-quic".to_string());
+                }
+                if !self.force_http3_origins.is_empty() {
+                    args.push(format!(
+                        "--origin-to-force-quic-on={}",
+                        self.force_http3_origins.join(",")
+                    ));
+                }
+            }
+        }
+
+        args
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct BrowserConfig {
     /// Determines whether to run headless version of the browser. Defaults to
@@ -771,6 +1080,30 @@ pub struct BrowserConfig {
     pub intercept_manager: NetworkInterceptManager,
     /// The max bytes to receive.
     pub max_bytes_allowed: Option<u64>,
+    /// Neutralize `debugger` loops and devtools detection on hostile pages.
+    pub anti_debugging: bool,
+    /// The shims of the web apis that crash or hang headless browsers.
+    pub stability_shims: crate::stability::StabilityShims,
+    /// Answer the cookie consent banners of every document.
+    pub consent_policy: Option<crate::consent::ConsentPolicy>,
+    /// Shape the Sec-Fetch, Origin and User-Agent headers of intercepted requests.
+    pub header_shaping: Option<std::sync::Arc<crate::sec_fetch::HeaderShaping>>,
+    /// The signing rules of intercepted requests.
+    pub request_signing: Option<std::sync::Arc<crate::request_signing::RequestSigning>>,
+    /// The interception rules of the requests of every page.
+    pub network_rules: Option<std::sync::Arc<crate::network_rules::NetworkRules>>,
+    /// The redirects followed during the navigations, every redirect when `None`.
+    pub redirect_policy: Option<crate::redirect::RedirectPolicy>,
+    /// The last CDP messages kept per target for `Page::dump_flight_record`, disabled when `0`.
+    pub flight_recorder: usize,
+    /// The max bits apart of the simhashes of near duplicate pages, disabled when `None`.
+    pub duplicate_detection: Option<u32>,
+    /// The HTTP versions the browser may negotiate.
+    pub protocol_policy: ProtocolPolicy,
+    /// The font hinting, web fonts and font set of the browser.
+    pub font_config: crate::fonts::FontConfig,
+    /// The base64 SHA-256 public key hashes of the trusted custom CAs.
+    trusted_ca_spki: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -832,6 +1165,30 @@ pub struct BrowserConfigBuilder {
     intercept_manager: NetworkInterceptManager,
     /// Optional upper bound on bytes that may be received (per session/run).
     max_bytes_allowed: Option<u64>,
+    /// Skip all debugger pauses and hide the client from devtools detection.
+    anti_debugging: bool,
+    /// The shims of the web apis that crash or hang headless browsers.
+    stability_shims: crate::stability::StabilityShims,
+    /// Answer the cookie consent banners of every document.
+    consent_policy: Option<crate::consent::ConsentPolicy>,
+    /// Shape the Sec-Fetch, Origin and User-Agent headers of intercepted requests.
+    header_shaping: Option<std::sync::Arc<crate::sec_fetch::HeaderShaping>>,
+    /// The signing rules of intercepted requests.
+    request_signing: Option<std::sync::Arc<crate::request_signing::RequestSigning>>,
+    /// The interception rules of the requests of every page.
+    network_rules: Option<std::sync::Arc<crate::network_rules::NetworkRules>>,
+    /// The redirects followed during the navigations, every redirect when `None`.
+    redirect_policy: Option<crate::redirect::RedirectPolicy>,
+    /// The last CDP messages kept per target, disabled when `0`.
+    flight_recorder: usize,
+    /// The max bits apart of the simhashes of near duplicate pages, disabled when `None`.
+    duplicate_detection: Option<u32>,
+    /// The HTTP versions the browser may negotiate.
+    protocol_policy: ProtocolPolicy,
+    /// The font hinting, web fonts and font set of the browser.
+    font_config: crate::fonts::FontConfig,
+    /// PEM bundles of the custom CAs to trust.
+    custom_ca: Vec<Vec<u8>>,
 }
 
 impl BrowserConfig {
@@ -880,6 +1237,18 @@ impl Default for BrowserConfigBuilder {
             service_worker_enabled: true,
             intercept_manager: NetworkInterceptManager::Unknown,
             max_bytes_allowed: None,
+            anti_debugging: false,
+            stability_shims: Default::default(),
+            consent_policy: None,
+            header_shaping: None,
+            request_signing: None,
+            network_rules: None,
+            redirect_policy: None,
+            flight_recorder: 0,
+            duplicate_detection: None,
+            protocol_policy: Default::default(),
+            font_config: Default::default(),
+            custom_ca: Vec::new(),
         }
     }
 }
@@ -1047,6 +1416,99 @@ impl BrowserConfigBuilder {
         self
     }
 
+    /// Skip all debugger pauses, hide the client from devtools detection and run the crate
+    /// helpers in isolated worlds with frozen builtins. The debugger stays disabled so the
+    /// `debugger` statements never pause, enabling it with `Page::enable_debugger` skips its
+    /// pauses but leaves an attached debugger detectable through its overhead.
+    pub fn set_anti_debugging(mut self, enabled: bool) -> Self {
+        self.anti_debugging = enabled;
+        self
+    }
+
+    /// Stub `speechSynthesis` and settle the WebAudio and media promises that hang in some
+    /// headless environments, at the start of every document.
+    pub fn set_stability_shims(mut self, shims: crate::stability::StabilityShims) -> Self {
+        self.stability_shims = shims;
+        self
+    }
+
+    /// Answer the cookie consent banners of the common CMPs (OneTrust, Didomi, Cookiebot) on
+    /// every document following the policy.
+    pub fn set_consent_policy(mut self, policy: crate::consent::ConsentPolicy) -> Self {
+        self.consent_policy = Some(policy);
+        self
+    }
+
+    /// Normalize or customize the `Sec-Fetch-*`, `Origin` and `User-Agent` headers of intercepted
+    /// requests per url rule. Requires request interception.
+    pub fn with_header_shaping(mut self, shaping: crate::sec_fetch::HeaderShaping) -> Self {
+        self.header_shaping = Some(std::sync::Arc::new(shaping));
+        self
+    }
+
+    /// Sign the matching intercepted requests before they are sent, e.g. with an HMAC or a SigV4
+    /// style signature of the final headers and body. Requires request interception.
+    pub fn with_request_signing(mut self, signing: crate::request_signing::RequestSigning) -> Self {
+        self.request_signing = Some(std::sync::Arc::new(signing));
+        self
+    }
+
+    /// Allow, block, redirect, modify the headers of or fulfill the intercepted requests of every
+    /// page with ordered rules, ahead of the built-in blocking. Requires request interception.
+    pub fn with_network_rules(mut self, rules: crate::network_rules::NetworkRules) -> Self {
+        self.network_rules = Some(std::sync::Arc::new(rules));
+        self
+    }
+
+    /// Check the http redirects, meta refreshes and script redirects of the navigations against
+    /// the policy, failing a navigation with `CdpError::Redirect` as soon as a redirect is not
+    /// followed or loops instead of waiting for the navigation timeout. A navigation can follow
+    /// the immediate client redirects to the final document, opt-in with
+    /// `RedirectPolicy::client_redirect_wait`.
+    pub fn with_redirect_policy(mut self, policy: crate::redirect::RedirectPolicy) -> Self {
+        self.redirect_policy = Some(policy);
+        self
+    }
+
+    /// Keep the last CDP messages of every target in a ring buffer, including the messages which
+    /// failed to parse, to dump them with `Page::dump_flight_record` once an operation failed.
+    pub fn with_flight_recorder(mut self, messages: usize) -> Self {
+        self.flight_recorder = messages;
+        self
+    }
+
+    /// Index the simhash of the content of every page fingerprinted with
+    /// `Page::content_fingerprint`, clustering the pages at most `max_distance` bits apart as
+    /// near duplicates, see `Browser::duplicates` and `crate::duplicates::DEFAULT_DUPLICATE_DISTANCE`.
+    pub fn with_duplicate_detection(mut self, max_distance: u32) -> Self {
+        self.duplicate_detection = Some(max_distance);
+        self
+    }
+
+    /// Force or forbid HTTP/2 and HTTP/3, some targets behave differently or block depending on
+    /// the negotiated protocol.
+    pub fn with_protocol_policy(mut self, policy: ProtocolPolicy) -> Self {
+        self.protocol_policy = policy;
+        self
+    }
+
+    /// Control the font hinting, web fonts and the font set of the browser, to render the same
+    /// screenshots across hosts and to shape the font fingerprint.
+    pub fn with_font_config(mut self, fonts: crate::fonts::FontConfig) -> Self {
+        self.font_config = fonts;
+        self
+    }
+
+    /// Trust the CA certificates of the PEM bundle for the launched browser, e.g. the CA of a
+    /// mitmproxy style proxy. Certificate errors are ignored for chains containing one of the CA
+    /// public keys while other certificates are still verified. Chrome only honours the list with
+    /// a user data dir, the launched browser always gets one, a temporary dir when
+    /// `user_data_dir` is not set.
+    pub fn trust_custom_ca(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.custom_ca.push(pem.into());
+        self
+    }
+
     pub fn set_extra_headers(
         mut self,
         headers: Option<std::collections::HashMap<String, String>>,
@@ -1062,6 +1524,11 @@ impl BrowserConfigBuilder {
             detection::default_executable(self.executation_detection)?
         };
 
+        let mut trusted_ca_spki = Vec::new();
+        for pem in &self.custom_ca {
+            trusted_ca_spki.extend(crate::custom_ca::spki_hashes(pem)?);
+        }
+
         Ok(BrowserConfig {
             headless: self.headless,
             sandbox: self.sandbox,
@@ -1090,18 +1557,48 @@ impl BrowserConfigBuilder {
             intercept_manager: self.intercept_manager,
             service_worker_enabled: self.service_worker_enabled,
             max_bytes_allowed: self.max_bytes_allowed,
+            anti_debugging: self.anti_debugging,
+            stability_shims: self.stability_shims,
+            consent_policy: self.consent_policy,
+            header_shaping: self.header_shaping,
+            request_signing: self.request_signing,
+            network_rules: self.network_rules,
+            redirect_policy: self.redirect_policy,
+            flight_recorder: self.flight_recorder,
+            duplicate_detection: self.duplicate_detection,
+            protocol_policy: self.protocol_policy,
+            font_config: self.font_config,
+            trusted_ca_spki,
         })
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl BrowserConfig {
     pub fn launch(&self) -> io::Result<Child> {
         let mut cmd = async_process::Command::new(&self.executable);
 
+        cmd.args(self.launch_args());
+
+        if let Some(fontconfig) = self.font_config.fontconfig_file()? {
+            cmd.envs([("FONTCONFIG_FILE", fontconfig)]);
+        }
+
+        if let Some(ref envs) = self.process_envs {
+            cmd.envs(envs);
+        }
+        cmd.stderr(Stdio::piped()).spawn()
+    }
+
+    /// The command line args of the launched browser.
+    fn launch_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = Vec::new();
+
         if self.disable_default_args {
-            cmd.args(&self.args);
+            args.extend(self.args.iter().cloned());
         } else {
-            cmd.args(DEFAULT_ARGS).args(&self.args);
+            args.extend(DEFAULT_ARGS.iter().map(|arg| arg.to_string()));
+            args.extend(self.args.iter().cloned());
         }
 
         if !self
@@ -1109,53 +1606,64 @@ impl BrowserConfig {
             .iter()
             .any(|arg| arg.contains("--remote-debugging-port="))
         {
-            cmd.arg(format!("--remote-debugging-port={}", self.port));
+            args.push(format!("--remote-debugging-port={}", self.port));
         }
 
-        cmd.args(
+        args.extend(
             self.extensions
                 .iter()
                 .map(|e| format!("--load-extension={e}")),
         );
 
+        // the user data dir is always set, chrome ignores the spki list of the trusted CAs
+        // without it.
         if let Some(ref user_data) = self.user_data_dir {
-            cmd.arg(format!("--user-data-dir={}", user_data.display()));
+            args.push(format!("--user-data-dir={}", user_data.display()));
         } else {
             // If the user did not specify a data directory, this would default to the systems default
             // data directory. In most cases, we would rather have a fresh instance of Chromium. Specify
             // a temp dir just for chromiumoxide instead.
-            cmd.arg(format!(
+            args.push(format!(
                 "--user-data-dir={}",
                 std::env::temp_dir().join("chromiumoxide-runner").display()
             ));
         }
 
         if let Some((width, height)) = self.window_size {
-            cmd.arg(format!("--window-size={width},{height}"));
+            args.push(format!("--window-size={width},{height}"));
         }
 
         if !self.sandbox {
-            cmd.args(["--no-sandbox", "--disable-setuid-sandbox"]);
+            args.extend(["--no-sandbox".into(), "--disable-setuid-sandbox".into()]);
         }
 
         match self.headless {
             HeadlessMode::False => (),
             HeadlessMode::True => {
-                cmd.args(["--headless", "--hide-scrollbars", "--mute-audio"]);
+                args.extend(["--headless", "--hide-scrollbars", "--mute-audio"].map(String::from));
             }
             HeadlessMode::New => {
-                cmd.args(["--headless=new", "--hide-scrollbars", "--mute-audio"]);
+                args.extend(
+                    ["--headless=new", "--hide-scrollbars", "--mute-audio"].map(String::from),
+                );
             }
         }
 
         if self.incognito {
-            cmd.arg("--incognito");
+            args.push("--incognito".into());
         }
 
-        if let Some(ref envs) = self.process_envs {
-            cmd.envs(envs);
+        args.extend(self.protocol_policy.args());
+        args.extend(self.font_config.args());
+
+        if !self.trusted_ca_spki.is_empty() {
+            args.push(format!(
+                "--ignore-certificate-errors-spki-list={}",
+                self.trusted_ca_spki.join(",")
+            ));
         }
-        cmd.stderr(Stdio::piped()).spawn()
+
+        args
     }
 }
 
@@ -1206,3 +1714,56 @@ static DEFAULT_ARGS: [&str; 26] = [
     "--lang=en_US",
     "--disable-blink-features=AutomationControlled",
 ];
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    const SPKI_ARG: &str = "--ignore-certificate-errors-spki-list=hash-a,hash-b";
+
+    fn trusting_config(builder: BrowserConfigBuilder) -> BrowserConfig {
+        let mut config = builder.chrome_executable("chrome").build().unwrap();
+        config.trusted_ca_spki = vec!["hash-a".into(), "hash-b".into()];
+        config
+    }
+
+    #[test]
+    fn trusts_the_custom_ca_with_the_runner_data_dir() {
+        let args = trusting_config(BrowserConfig::builder()).launch_args();
+        let runner = format!(
+            "--user-data-dir={}",
+            std::env::temp_dir().join("chromiumoxide-runner").display()
+        );
+
+        assert!(args.contains(&runner));
+        assert!(args.iter().any(|arg| arg == SPKI_ARG));
+    }
+
+    #[test]
+    fn trusts_the_custom_ca_with_the_user_data_dir() {
+        let args =
+            trusting_config(BrowserConfig::builder().user_data_dir("/tmp/profile")).launch_args();
+
+        assert!(args.iter().any(|arg| arg == "--user-data-dir=/tmp/profile"));
+        assert!(args.iter().any(|arg| arg == SPKI_ARG));
+        assert_eq!(
+            args.iter()
+                .filter(|arg| arg.starts_with("--user-data-dir="))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn skips_the_spki_list_without_a_custom_ca() {
+        let config = BrowserConfig::builder()
+            .chrome_executable("chrome")
+            .build()
+            .unwrap();
+
+        assert!(!config
+            .launch_args()
+            .iter()
+            .any(|arg| arg.starts_with("--ignore-certificate-errors-spki-list")));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A store of the completed urls of a bulk job and their results, so an interrupted job can
+/// resume without processing them again. Implement it to keep the progress in sled, SQLite or
+/// any other store.
+pub trait Checkpoint: Send + Sync {
+    /// The completed urls and their results.
+    fn load(&self) -> io::Result<HashMap<String, Value>>;
+
+    /// Record the result of a completed url.
+    fn record(&self, url: &str, result: &Value) -> io::Result<()>;
+}
+
+/// A recorded url of a checkpoint file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record<'a> {
+    url: std::borrow::Cow<'a, str>,
+    result: Value,
+}
+
+/// A checkpoint appending one JSON record per completed url to a file.
+#[derive(Debug)]
+pub struct FileCheckpoint {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileCheckpoint {
+    /// A checkpoint of the file, created on the first record.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: Mutex::new(None),
+        }
+    }
+}
+
+impl Checkpoint for FileCheckpoint {
+    fn load(&self) -> io::Result<HashMap<String, Value>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut completed = HashMap::new();
+
+        for line in BufReader::new(file).lines() {
+            // the last record may be cut short by the interruption.
+            if let Ok(record) = serde_json::from_str::<Record<'_>>(&line?) {
+                completed.insert(record.url.into_owned(), record.result);
+            }
+        }
+
+        Ok(completed)
+    }
+
+    fn record(&self, url: &str, result: &Value) -> io::Result<()> {
+        let mut line = serde_json::to_vec(&Record {
+            url: url.into(),
+            result: result.clone(),
+        })?;
+        line.push(b'\n');
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "checkpoint lock poisoned"))?;
+
+        if file.is_none() {
+            let mut opened = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(&self.path)?;
+
+            // end the record cut short by an interruption before appending.
+            if opened.metadata()?.len() > 0 {
+                let mut last = [0u8; 1];
+                opened.seek(SeekFrom::End(-1))?;
+                opened.read_exact(&mut last)?;
+                if last[0] != b'\n' {
+                    opened.write_all(b"\n")?;
+                }
+            }
+
+            *file = Some(opened);
+        }
+
+        match file.as_mut() {
+            Some(file) => file.write_all(&line),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A checkpoint kept in memory, e.g. to resume a stream within the process.
+#[derive(Debug, Default)]
+pub struct MemoryCheckpoint {
+    completed: Mutex<HashMap<String, Value>>,
+}
+
+impl Checkpoint for MemoryCheckpoint {
+    fn load(&self) -> io::Result<HashMap<String, Value>> {
+        Ok(self
+            .completed
+            .lock()
+            .map(|completed| completed.clone())
+            .unwrap_or_default())
+    }
+
+    fn record(&self, url: &str, result: &Value) -> io::Result<()> {
+        if let Ok(mut completed) = self.completed.lock() {
+            completed.insert(url.to_string(), result.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_checkpoint_resumes() {
+        let path =
+            std::env::temp_dir().join(format!("chromey-checkpoint-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let checkpoint = FileCheckpoint::new(&path);
+
+        assert!(checkpoint.load().unwrap().is_empty());
+
+        checkpoint
+            .record("https://example.com/a", &Value::from(1))
+            .unwrap();
+        checkpoint
+            .record("https://example.com/b", &Value::from("b"))
+            .unwrap();
+
+        // an interrupted write.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"url\":\"https://example.com/c\",\"res")
+            .unwrap();
+
+        let resumed = FileCheckpoint::new(&path);
+        resumed
+            .record("https://example.com/c", &Value::Null)
+            .unwrap();
+
+        let completed = resumed.load().unwrap();
+
+        assert_eq!(completed.len(), 3);
+        assert_eq!(completed["https://example.com/c"], Value::Null);
+        assert_eq!(completed["https://example.com/a"], Value::from(1));
+        assert_eq!(completed["https://example.com/b"], Value::from("b"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
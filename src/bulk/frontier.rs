@@ -0,0 +1,392 @@
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture, FutureExt};
+use futures::stream::{self, Stream, StreamExt};
+
+use super::{process_url, BulkOptions, HostSchedule, JobResult, PagePool};
+use crate::browser::Browser;
+use crate::error::Result;
+use crate::page::Page;
+
+/// The wait before asking an empty frontier again while urls are still processed.
+const FRONTIER_POLL: Duration = Duration::from_millis(500);
+
+/// A crawl frontier shared by the workers of a crawl. A url handed out by `next_url` stays
+/// leased until it is marked done, so the frontier is only done once every leased url finished.
+pub trait Frontier: Send + Sync {
+    /// Lease the next url, `None` when no url is queued right now.
+    fn next_url(&self) -> BoxFuture<'_, Result<Option<String>>>;
+
+    /// Release the lease of the processed url.
+    fn mark_done<'a>(&'a self, url: &'a str, success: bool) -> BoxFuture<'a, Result<()>>;
+
+    /// Queue the urls discovered by a page, the urls seen before are skipped.
+    fn push_discovered(&self, urls: Vec<String>) -> BoxFuture<'_, Result<()>>;
+
+    /// No url is queued or leased.
+    fn is_done(&self) -> BoxFuture<'_, Result<bool>>;
+}
+
+/// The state of a [`MemoryFrontier`].
+#[derive(Debug, Default)]
+struct MemoryState {
+    queue: VecDeque<String>,
+    seen: HashSet<String>,
+    leased: HashSet<String>,
+}
+
+/// A frontier of a single process.
+#[derive(Debug, Default)]
+pub struct MemoryFrontier {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryFrontier {
+    /// A frontier of the seed urls.
+    pub fn new(seeds: impl IntoIterator<Item = String>) -> Self {
+        let frontier = Self::default();
+
+        if let Ok(mut state) = frontier.state.lock() {
+            for url in seeds {
+                if state.seen.insert(url.clone()) {
+                    state.queue.push_back(url);
+                }
+            }
+        }
+
+        frontier
+    }
+}
+
+impl Frontier for MemoryFrontier {
+    fn next_url(&self) -> BoxFuture<'_, Result<Option<String>>> {
+        let next = self.state.lock().ok().and_then(|mut state| {
+            let url = state.queue.pop_front()?;
+            state.leased.insert(url.clone());
+            Some(url)
+        });
+
+        future::ready(Ok(next)).boxed()
+    }
+
+    fn mark_done<'a>(&'a self, url: &'a str, _success: bool) -> BoxFuture<'a, Result<()>> {
+        if let Ok(mut state) = self.state.lock() {
+            state.leased.remove(url);
+        }
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn push_discovered(&self, urls: Vec<String>) -> BoxFuture<'_, Result<()>> {
+        if let Ok(mut state) = self.state.lock() {
+            for url in urls {
+                if state.seen.insert(url.clone()) {
+                    state.queue.push_back(url);
+                }
+            }
+        }
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn is_done(&self) -> BoxFuture<'_, Result<bool>> {
+        let done = self
+            .state
+            .lock()
+            .map(|state| state.queue.is_empty() && state.leased.is_empty())
+            .unwrap_or(true);
+
+        future::ready(Ok(done)).boxed()
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use self::redis_frontier::RedisFrontier;
+
+#[cfg(feature = "redis")]
+mod redis_frontier {
+    use super::*;
+    use crate::error::CdpError;
+
+    /// Queue the urls not seen before.
+    const PUSH_SCRIPT: &str = r#"
+for _, url in ipairs(ARGV) do
+  if redis.call('SADD', KEYS[1], url) == 1 then
+    redis.call('RPUSH', KEYS[2], url)
+  end
+end
+return 0
+"#;
+
+    /// Requeue the expired leases, then lease the next url.
+    const LEASE_SCRIPT: &str = r#"
+local expired = redis.call('ZRANGEBYSCORE', KEYS[2], '-inf', ARGV[1] - ARGV[2])
+for _, url in ipairs(expired) do
+  redis.call('ZREM', KEYS[2], url)
+  redis.call('RPUSH', KEYS[1], url)
+end
+local url = redis.call('LPOP', KEYS[1])
+if url then
+  redis.call('ZADD', KEYS[2], ARGV[1], url)
+end
+return url
+"#;
+
+    fn redis_error(err: redis::RedisError) -> CdpError {
+        CdpError::msg(format!("redis frontier: {err}"))
+    }
+
+    fn now_millis() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    /// A frontier in redis shared by the workers of a crawl, under the keys of the namespace:
+    /// `{ns}:queue` (list), `{ns}:seen` (set), `{ns}:leased` (sorted set of the lease times),
+    /// `{ns}:done` and `{ns}:failed` (sets). The leases of a crashed worker are requeued once
+    /// they expired.
+    #[derive(Clone)]
+    pub struct RedisFrontier {
+        connection: redis::aio::MultiplexedConnection,
+        namespace: String,
+        lease_timeout: Duration,
+    }
+
+    impl std::fmt::Debug for RedisFrontier {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("RedisFrontier")
+                .field("namespace", &self.namespace)
+                .field("lease_timeout", &self.lease_timeout)
+                .finish()
+        }
+    }
+
+    impl RedisFrontier {
+        /// Connect to the redis url, e.g. `redis://127.0.0.1/`, with a lease timeout of 10
+        /// minutes.
+        pub async fn connect(url: &str, namespace: impl Into<String>) -> Result<Self> {
+            let connection = redis::Client::open(url)
+                .map_err(redis_error)?
+                .get_multiplexed_async_connection()
+                .await
+                .map_err(redis_error)?;
+
+            Ok(Self {
+                connection,
+                namespace: namespace.into(),
+                lease_timeout: Duration::from_secs(600),
+            })
+        }
+
+        /// Requeue the leased urls not marked done within the timeout.
+        pub fn with_lease_timeout(mut self, lease_timeout: Duration) -> Self {
+            self.lease_timeout = lease_timeout;
+            self
+        }
+
+        fn key(&self, name: &str) -> String {
+            format!("{}:{name}", self.namespace)
+        }
+    }
+
+    impl Frontier for RedisFrontier {
+        fn next_url(&self) -> BoxFuture<'_, Result<Option<String>>> {
+            async move {
+                let mut connection = self.connection.clone();
+
+                let url: Option<String> = redis::Script::new(LEASE_SCRIPT)
+                    .key(self.key("queue"))
+                    .key(self.key("leased"))
+                    .arg(now_millis())
+                    .arg(self.lease_timeout.as_millis() as u64)
+                    .invoke_async(&mut connection)
+                    .await
+                    .map_err(redis_error)?;
+
+                Ok(url)
+            }
+            .boxed()
+        }
+
+        fn mark_done<'a>(&'a self, url: &'a str, success: bool) -> BoxFuture<'a, Result<()>> {
+            async move {
+                let mut connection = self.connection.clone();
+
+                let _: () = redis::pipe()
+                    .atomic()
+                    .zrem(self.key("leased"), url)
+                    .ignore()
+                    .sadd(self.key(if success { "done" } else { "failed" }), url)
+                    .ignore()
+                    .query_async(&mut connection)
+                    .await
+                    .map_err(redis_error)?;
+
+                Ok(())
+            }
+            .boxed()
+        }
+
+        fn push_discovered(&self, urls: Vec<String>) -> BoxFuture<'_, Result<()>> {
+            async move {
+                if urls.is_empty() {
+                    return Ok(());
+                }
+
+                let mut connection = self.connection.clone();
+
+                let _: i64 = redis::Script::new(PUSH_SCRIPT)
+                    .key(self.key("seen"))
+                    .key(self.key("queue"))
+                    .arg(urls)
+                    .invoke_async(&mut connection)
+                    .await
+                    .map_err(redis_error)?;
+
+                Ok(())
+            }
+            .boxed()
+        }
+
+        fn is_done(&self) -> BoxFuture<'_, Result<bool>> {
+            async move {
+                let mut connection = self.connection.clone();
+
+                let (queued, leased): (u64, u64) = redis::pipe()
+                    .llen(self.key("queue"))
+                    .zcard(self.key("leased"))
+                    .query_async(&mut connection)
+                    .await
+                    .map_err(redis_error)?;
+
+                Ok(queued == 0 && leased == 0)
+            }
+            .boxed()
+        }
+    }
+}
+
+impl Browser {
+    /// Process the urls of the frontier on a pool of pages until the frontier is done, with the
+    /// retries, timeouts and host politeness of [`Browser::process_urls_with_options`]. The job
+    /// returns its value and the urls discovered on the page, which are pushed to the frontier.
+    /// Every processed url is marked done, successful or not.
+    pub fn process_frontier<'a, R, T, F, Fut>(
+        &'a self,
+        frontier: R,
+        options: BulkOptions,
+        job: F,
+    ) -> impl Stream<Item = JobResult<T>> + 'a
+    where
+        R: Frontier + 'a,
+        T: 'a,
+        F: Fn(Page, String) -> Fut + 'a,
+        Fut: Future<Output = Result<(T, Vec<String>)>> + 'a,
+    {
+        let concurrency = options.concurrency.max(1);
+        let options = Arc::new(options);
+        let pool = Arc::new(PagePool::default());
+        let hosts = Arc::new(HostSchedule::default());
+        let job = Arc::new(job);
+        let frontier = Arc::new(frontier);
+
+        let urls = stream::unfold(frontier.clone(), |frontier| async move {
+            loop {
+                let done = match frontier.next_url().await {
+                    Ok(Some(url)) => return Some((url, frontier)),
+                    Ok(None) => frontier.is_done().await,
+                    Err(err) => Err(err),
+                };
+
+                match done {
+                    Ok(false) => crate::runtime::sleep(FRONTIER_POLL).await,
+                    Ok(true) => return None,
+                    Err(err) => {
+                        tracing::warn!("bulk frontier failed: {err}");
+                        return None;
+                    }
+                }
+            }
+        });
+
+        let results = {
+            let pool = pool.clone();
+
+            urls.map(move |url| {
+                let (options, pool, hosts, job, frontier) = (
+                    options.clone(),
+                    pool.clone(),
+                    hosts.clone(),
+                    job.clone(),
+                    frontier.clone(),
+                );
+
+                async move {
+                    let processed = process_url(self, url, &options, &pool, &hosts, &*job).await;
+
+                    let result = match processed.result {
+                        Ok((value, discovered)) => {
+                            if let Err(err) = frontier.push_discovered(discovered).await {
+                                tracing::warn!(
+                                    "bulk frontier push failed for {}: {err}",
+                                    processed.url
+                                );
+                            }
+                            Ok(value)
+                        }
+                        Err(err) => Err(err),
+                    };
+
+                    if let Err(err) = frontier.mark_done(&processed.url, result.is_ok()).await {
+                        tracing::warn!("bulk frontier mark failed for {}: {err}", processed.url);
+                    }
+
+                    JobResult {
+                        url: processed.url,
+                        attempts: processed.attempts,
+                        result,
+                    }
+                }
+            })
+            .buffer_unordered(concurrency)
+        };
+
+        let cleanup = stream::once(async move {
+            pool.close_all().await;
+        })
+        .filter_map(|_| async { None });
+
+        results.chain(cleanup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_frontier_leases_urls() {
+        let frontier = MemoryFrontier::new(vec!["a".to_string(), "a".to_string()]);
+
+        assert_eq!(frontier.next_url().await.unwrap().as_deref(), Some("a"));
+        assert_eq!(frontier.next_url().await.unwrap(), None);
+        assert!(!frontier.is_done().await.unwrap());
+
+        frontier
+            .push_discovered(vec!["a".into(), "b".into()])
+            .await
+            .unwrap();
+        frontier.mark_done("a", true).await.unwrap();
+
+        assert_eq!(frontier.next_url().await.unwrap().as_deref(), Some("b"));
+
+        frontier.mark_done("b", false).await.unwrap();
+
+        assert!(frontier.is_done().await.unwrap());
+    }
+}
@@ -0,0 +1,362 @@
+/// Checkpoint stores of bulk jobs.
+pub mod checkpoint;
+
+/// Crawl frontiers shared by bulk workers.
+pub mod frontier;
+/// Prioritized bulk jobs.
+pub mod priority;
+/// Recurring page jobs.
+pub mod schedule;
+
+pub use checkpoint::{Checkpoint, FileCheckpoint, MemoryCheckpoint};
+#[cfg(feature = "redis")]
+pub use frontier::RedisFrontier;
+pub use frontier::{Frontier, MemoryFrontier};
+pub use priority::{PriorityJob, SchedulerHandle};
+pub use schedule::{Cadence, CronSchedule, ScheduledJob, ScheduledRun, Scheduler};
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::stream::{self, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::browser::Browser;
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+use crate::runtime::Instant;
+
+/// How the urls of a bulk job are processed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkOptions {
+    /// The pages processing urls at the same time.
+    pub concurrency: usize,
+    /// The retries of a failed url, on a fresh page.
+    pub retries: u32,
+    /// The timeout of a single attempt.
+    pub timeout: Duration,
+    /// The delay before the first retry, doubled on every retry.
+    pub retry_backoff: Duration,
+    /// The min delay between the starts of two urls of the same host.
+    pub per_host_delay: Duration,
+    /// The wait raising a queued url of a prioritized job by one priority level.
+    pub priority_aging: Duration,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            retries: 2,
+            timeout: Duration::from_secs(30),
+            retry_backoff: Duration::from_millis(500),
+            per_host_delay: Duration::from_millis(250),
+            priority_aging: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The outcome of a url of a bulk job.
+#[derive(Debug)]
+pub struct JobResult<T> {
+    /// The url processed.
+    pub url: String,
+    /// The attempts made, retries included.
+    pub attempts: u32,
+    /// The value of the last attempt.
+    pub result: Result<T>,
+}
+
+/// The idle pages of a bulk job, reused across urls.
+#[derive(Debug, Default)]
+struct PagePool {
+    idle: Mutex<Vec<Page>>,
+}
+
+impl PagePool {
+    async fn acquire(&self, browser: &Browser) -> Result<Page> {
+        let idle = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+
+        match idle {
+            Some(page) => Ok(page),
+            _ => browser.new_page("about:blank").await,
+        }
+    }
+
+    fn release(&self, page: Page) {
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.push(page);
+        }
+    }
+
+    async fn close_all(&self) {
+        let pages = self
+            .idle
+            .lock()
+            .map(|mut idle| std::mem::take(&mut *idle))
+            .unwrap_or_default();
+
+        for page in pages {
+            let _ = page.close().await;
+        }
+    }
+}
+
+/// The next start of the urls per host.
+#[derive(Debug, Default)]
+struct HostSchedule {
+    next_start: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostSchedule {
+    /// Reserve the next start slot of the host of the url, returning the delay until the slot.
+    fn reserve(&self, url: &str, delay: Duration) -> Duration {
+        let host = match url::Url::parse(url) {
+            Ok(url) => url.host_str().unwrap_or_default().to_string(),
+            _ => return Duration::ZERO,
+        };
+
+        let now = Instant::now();
+
+        match self.next_start.lock() {
+            Ok(mut next_start) => {
+                let slot = next_start.get(&host).map_or(now, |next| (*next).max(now));
+                next_start.insert(host, slot + delay);
+                slot - now
+            }
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Run the job on the url, retrying failed attempts on a fresh page.
+async fn process_url<T, F, Fut>(
+    browser: &Browser,
+    url: String,
+    options: &BulkOptions,
+    pool: &PagePool,
+    hosts: &HostSchedule,
+    job: &F,
+) -> JobResult<T>
+where
+    F: Fn(Page, String) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempts = 0;
+    let mut backoff = options.retry_backoff;
+
+    loop {
+        attempts += 1;
+
+        let delay = options
+            .per_host_delay
+            .max(browser.policy().politeness.per_host_delay);
+        let wait = hosts.reserve(&url, delay);
+
+        if !wait.is_zero() {
+            crate::runtime::sleep(wait).await;
+        }
+
+        let result = match pool.acquire(browser).await {
+            Ok(page) => {
+                match crate::runtime::timeout(options.timeout, job(page.clone(), url.clone())).await
+                {
+                    Ok(Ok(value)) => {
+                        pool.release(page);
+                        Ok(value)
+                    }
+                    // the page may be stuck on the failed attempt.
+                    Ok(Err(err)) => {
+                        let _ = page.close().await;
+                        Err(err)
+                    }
+                    Err(_) => {
+                        let _ = page.close().await;
+                        Err(CdpError::Timeout)
+                    }
+                }
+            }
+            Err(err) => Err(err),
+        };
+
+        if result.is_ok() || attempts > options.retries {
+            return JobResult {
+                url,
+                attempts,
+                result,
+            };
+        }
+
+        tracing::debug!("bulk job attempt {attempts} failed for {url}");
+
+        crate::runtime::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+impl Browser {
+    /// Process the urls on a pool of `concurrency` pages, yielding the result of every url as it
+    /// completes. Failed urls are retried on a fresh page with the defaults of [`BulkOptions`].
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::browser::Browser;
+    /// # use futures::StreamExt;
+    /// # async fn demo(browser: Browser) {
+    /// let urls = vec!["https://example.com".to_string()];
+    ///
+    /// let mut results = browser.process_urls(urls, 8, |page, url| async move {
+    ///     page.goto(url).await?;
+    ///     page.content().await
+    /// });
+    ///
+    /// while let Some(job) = results.next().await {
+    ///     println!("{} {:?}", job.url, job.result.map(|html| html.len()));
+    /// }
+    /// # }
+    /// ```
+    pub fn process_urls<'a, I, T, F, Fut>(
+        &'a self,
+        urls: I,
+        concurrency: usize,
+        job: F,
+    ) -> impl Stream<Item = JobResult<T>> + 'a
+    where
+        I: IntoIterator<Item = String>,
+        I::IntoIter: 'a,
+        T: 'a,
+        F: Fn(Page, String) -> Fut + 'a,
+        Fut: Future<Output = Result<T>> + 'a,
+    {
+        self.process_urls_with_options(
+            urls,
+            BulkOptions {
+                concurrency,
+                ..Default::default()
+            },
+            job,
+        )
+    }
+
+    /// Process the urls with the options, see [`Browser::process_urls`]. The idle pages are
+    /// closed once every url was processed.
+    pub fn process_urls_with_options<'a, I, T, F, Fut>(
+        &'a self,
+        urls: I,
+        options: BulkOptions,
+        job: F,
+    ) -> impl Stream<Item = JobResult<T>> + 'a
+    where
+        I: IntoIterator<Item = String>,
+        I::IntoIter: 'a,
+        T: 'a,
+        F: Fn(Page, String) -> Fut + 'a,
+        Fut: Future<Output = Result<T>> + 'a,
+    {
+        let concurrency = options.concurrency.max(1);
+        let options = Arc::new(options);
+        let pool = Arc::new(PagePool::default());
+        let hosts = Arc::new(HostSchedule::default());
+        let job = Arc::new(job);
+
+        let results = {
+            let pool = pool.clone();
+
+            stream::iter(urls)
+                .map(move |url| {
+                    let (options, pool, hosts, job) =
+                        (options.clone(), pool.clone(), hosts.clone(), job.clone());
+
+                    async move { process_url(self, url, &options, &pool, &hosts, &*job).await }
+                })
+                .buffer_unordered(concurrency)
+        };
+
+        let cleanup = stream::once(async move {
+            pool.close_all().await;
+        })
+        .filter_map(|_| async { None });
+
+        results.chain(cleanup)
+    }
+
+    /// Process the urls with the options, recording every successful result in the checkpoint.
+    /// The urls completed by a previous run are yielded from the checkpoint with `0` attempts
+    /// instead of being processed again, so an interrupted job resumes where it stopped.
+    pub fn process_urls_with_checkpoint<'a, I, T, F, Fut, C>(
+        &'a self,
+        urls: I,
+        options: BulkOptions,
+        checkpoint: C,
+        job: F,
+    ) -> Result<impl Stream<Item = JobResult<T>> + 'a>
+    where
+        I: IntoIterator<Item = String>,
+        T: Serialize + DeserializeOwned + 'a,
+        F: Fn(Page, String) -> Fut + 'a,
+        Fut: Future<Output = Result<T>> + 'a,
+        C: Checkpoint + 'a,
+    {
+        let mut completed = checkpoint.load()?;
+        let checkpoint = Arc::new(checkpoint);
+
+        let mut resumed = Vec::new();
+        let mut pending = Vec::new();
+
+        for url in urls {
+            match completed
+                .remove(&url)
+                .and_then(|value| serde_json::from_value::<T>(value).ok())
+            {
+                Some(value) => resumed.push(JobResult {
+                    url,
+                    attempts: 0,
+                    result: Ok(value),
+                }),
+                _ => pending.push(url),
+            }
+        }
+
+        let processed = self
+            .process_urls_with_options(pending, options, job)
+            .map(move |job| {
+                if let Ok(value) = job.result.as_ref() {
+                    let recorded = serde_json::to_value(value)
+                        .map_err(io::Error::from)
+                        .and_then(|value| checkpoint.record(&job.url, &value));
+
+                    if let Err(err) = recorded {
+                        tracing::warn!("bulk checkpoint record failed for {}: {err}", job.url);
+                    }
+                }
+                job
+            });
+
+        Ok(stream::iter(resumed).chain(processed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_schedule_spaces_starts() {
+        let hosts = HostSchedule::default();
+        let delay = Duration::from_secs(10);
+
+        assert_eq!(
+            hosts.reserve("https://a.example.com/1", delay),
+            Duration::ZERO
+        );
+        assert!(hosts.reserve("https://a.example.com/2", delay) > Duration::from_secs(9));
+        assert_eq!(
+            hosts.reserve("https://b.example.com/1", delay),
+            Duration::ZERO
+        );
+        assert_eq!(hosts.reserve("not a url", delay), Duration::ZERO);
+    }
+}
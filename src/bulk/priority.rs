@@ -0,0 +1,373 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::Duration;
+
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt};
+use futures::task::AtomicWaker;
+
+use super::{process_url, BulkOptions, HostSchedule, JobResult, PagePool};
+use crate::browser::Browser;
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+use crate::runtime::Instant;
+
+/// A url of a prioritized bulk job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriorityJob {
+    /// The url to process.
+    pub url: String,
+    /// The priority, higher runs first.
+    pub priority: i32,
+    /// The url is dropped with an error when it did not start before the deadline.
+    pub deadline: Option<Instant>,
+}
+
+impl PriorityJob {
+    /// A job of the url with the priority.
+    pub fn new(url: impl Into<String>, priority: i32) -> Self {
+        Self {
+            url: url.into(),
+            priority,
+            deadline: None,
+        }
+    }
+
+    /// Drop the job when it did not start before the deadline.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+}
+
+/// A queued job.
+#[derive(Debug)]
+struct Entry {
+    job: PriorityJob,
+    enqueued: Instant,
+    seq: u64,
+}
+
+/// The next job of the queue.
+#[derive(Debug)]
+enum Next {
+    /// The job to run.
+    Run(PriorityJob),
+    /// The job missed its deadline.
+    Expired(PriorityJob),
+    /// No job is queued.
+    Empty,
+}
+
+/// The jobs waiting for a page.
+#[derive(Debug, Default)]
+struct PriorityQueue {
+    entries: Vec<Entry>,
+    seq: u64,
+    closed: bool,
+}
+
+impl PriorityQueue {
+    fn push(&mut self, job: PriorityJob) {
+        self.seq += 1;
+        self.entries.push(Entry {
+            job,
+            enqueued: Instant::now(),
+            seq: self.seq,
+        });
+    }
+
+    /// The priority of the entry raised by one for every `aging` it waited, so backfill jobs are
+    /// not starved by a steady flow of high priority jobs.
+    fn effective_priority(entry: &Entry, now: Instant, aging: Duration) -> i64 {
+        let boost = if aging.is_zero() {
+            0
+        } else {
+            (now.saturating_duration_since(entry.enqueued).as_millis() / aging.as_millis()) as i64
+        };
+
+        i64::from(entry.job.priority).saturating_add(boost)
+    }
+
+    /// Take the next job, expired jobs first. Ties run the earliest deadline, then the oldest job.
+    fn pop(&mut self, now: Instant, aging: Duration) -> Next {
+        if let Some(index) = self
+            .entries
+            .iter()
+            .position(|entry| entry.job.deadline.is_some_and(|deadline| deadline <= now))
+        {
+            return Next::Expired(self.entries.swap_remove(index).job);
+        }
+
+        let best = self
+            .entries
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| {
+                Self::effective_priority(a, now, aging)
+                    .cmp(&Self::effective_priority(b, now, aging))
+                    .then_with(|| match (a.job.deadline, b.job.deadline) {
+                        (Some(a), Some(b)) => b.cmp(&a),
+                        (Some(_), None) => std::cmp::Ordering::Greater,
+                        (None, Some(_)) => std::cmp::Ordering::Less,
+                        (None, None) => std::cmp::Ordering::Equal,
+                    })
+                    .then_with(|| b.seq.cmp(&a.seq))
+            })
+            .map(|(index, _)| index);
+
+        match best {
+            Some(index) => Next::Run(self.entries.swap_remove(index).job),
+            _ => Next::Empty,
+        }
+    }
+}
+
+/// The state shared by the scheduler handles and the result stream.
+#[derive(Debug, Default)]
+struct Shared {
+    queue: Mutex<PriorityQueue>,
+    waker: AtomicWaker,
+}
+
+/// Submit and re-prioritize the urls of a running prioritized bulk job. The job ends once the
+/// handle is closed and every queued url was processed.
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerHandle {
+    shared: Arc<Shared>,
+}
+
+impl SchedulerHandle {
+    fn update<R>(&self, f: impl FnOnce(&mut PriorityQueue) -> R) -> Option<R> {
+        let updated = self.shared.queue.lock().ok().map(|mut queue| f(&mut queue));
+        self.shared.waker.wake();
+        updated
+    }
+
+    /// Queue the url with the priority.
+    pub fn push(&self, url: impl Into<String>, priority: i32) {
+        self.push_job(PriorityJob::new(url, priority));
+    }
+
+    /// Queue the job.
+    pub fn push_job(&self, job: PriorityJob) {
+        self.update(|queue| queue.push(job));
+    }
+
+    /// Change the priority of a queued url, `false` when the url is not queued.
+    pub fn reprioritize(&self, url: &str, priority: i32) -> bool {
+        self.update(|queue| {
+            queue
+                .entries
+                .iter_mut()
+                .filter(|entry| entry.job.url == url)
+                .fold(false, |_, entry| {
+                    entry.job.priority = priority;
+                    true
+                })
+        })
+        .unwrap_or_default()
+    }
+
+    /// Remove a queued url, `false` when the url is not queued.
+    pub fn cancel(&self, url: &str) -> bool {
+        self.update(|queue| {
+            let before = queue.entries.len();
+            queue.entries.retain(|entry| entry.job.url != url);
+            queue.entries.len() != before
+        })
+        .unwrap_or_default()
+    }
+
+    /// The urls waiting for a page.
+    pub fn len(&self) -> usize {
+        self.shared
+            .queue
+            .lock()
+            .map(|queue| queue.entries.len())
+            .unwrap_or_default()
+    }
+
+    /// No url is waiting for a page.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stop accepting urls, the job ends once the queued urls were processed.
+    pub fn close(&self) {
+        self.update(|queue| queue.closed = true);
+    }
+
+    fn next(&self, aging: Duration) -> Next {
+        self.shared
+            .queue
+            .lock()
+            .map(|mut queue| queue.pop(Instant::now(), aging))
+            .unwrap_or(Next::Empty)
+    }
+
+    fn is_done(&self) -> bool {
+        self.shared
+            .queue
+            .lock()
+            .map(|queue| queue.closed && queue.entries.is_empty())
+            .unwrap_or(true)
+    }
+}
+
+impl Browser {
+    /// Process the urls queued on the returned handle by priority on a pool of pages, with the
+    /// retries, timeouts and host politeness of [`Browser::process_urls_with_options`]. Queued
+    /// urls gain one priority level per `BulkOptions::priority_aging` waited. Close the handle to
+    /// end the stream once the queue drained.
+    pub fn process_prioritized<'a, T, F, Fut>(
+        &'a self,
+        options: BulkOptions,
+        job: F,
+    ) -> (SchedulerHandle, impl Stream<Item = JobResult<T>> + 'a)
+    where
+        T: 'a,
+        F: Fn(Page, String) -> Fut + 'a,
+        Fut: Future<Output = Result<T>> + 'a,
+    {
+        let handle = SchedulerHandle::default();
+        let scheduler = handle.clone();
+
+        let concurrency = options.concurrency.max(1);
+        let aging = options.priority_aging;
+        let options = Arc::new(options);
+        let pool = Arc::new(PagePool::default());
+        let hosts = Arc::new(HostSchedule::default());
+        let job = Arc::new(job);
+
+        let mut in_flight: FuturesUnordered<Pin<Box<dyn Future<Output = JobResult<T>> + 'a>>> =
+            FuturesUnordered::new();
+
+        let results = {
+            let pool = pool.clone();
+
+            stream::poll_fn(move |cx| loop {
+                while in_flight.len() < concurrency {
+                    match scheduler.next(aging) {
+                        Next::Run(next) => {
+                            let (options, pool, hosts, job) =
+                                (options.clone(), pool.clone(), hosts.clone(), job.clone());
+
+                            in_flight.push(Box::pin(async move {
+                                process_url(self, next.url, &options, &pool, &hosts, &*job).await
+                            }));
+                        }
+                        Next::Expired(expired) => {
+                            return Poll::Ready(Some(JobResult {
+                                url: expired.url,
+                                attempts: 0,
+                                result: Err(CdpError::msg("bulk job deadline exceeded")),
+                            }));
+                        }
+                        Next::Empty => break,
+                    }
+                }
+
+                if let Poll::Ready(Some(result)) = in_flight.poll_next_unpin(cx) {
+                    return Poll::Ready(Some(result));
+                }
+
+                if in_flight.is_empty() && scheduler.is_done() {
+                    return Poll::Ready(None);
+                }
+
+                scheduler.shared.waker.register(cx.waker());
+
+                // a url queued before the waker was registered.
+                if in_flight.len() < concurrency && !scheduler.is_empty() {
+                    continue;
+                }
+
+                return Poll::Pending;
+            })
+        };
+
+        let cleanup = stream::once(async move {
+            pool.close_all().await;
+        })
+        .filter_map(|_| async { None });
+
+        (handle, results.chain(cleanup))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(next: Next) -> String {
+        match next {
+            Next::Run(job) => job.url,
+            other => panic!("expected a job to run, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn pops_by_priority_then_order() {
+        let mut queue = PriorityQueue::default();
+        let now = Instant::now();
+
+        queue.push(PriorityJob::new("backfill-1", 0));
+        queue.push(PriorityJob::new("fresh", 10));
+        queue.push(PriorityJob::new("backfill-2", 0));
+
+        assert_eq!(run(queue.pop(now, Duration::ZERO)), "fresh");
+        assert_eq!(run(queue.pop(now, Duration::ZERO)), "backfill-1");
+        assert_eq!(run(queue.pop(now, Duration::ZERO)), "backfill-2");
+        assert!(matches!(queue.pop(now, Duration::ZERO), Next::Empty));
+    }
+
+    #[test]
+    fn aging_prevents_starvation() {
+        let mut queue = PriorityQueue::default();
+
+        queue.push(PriorityJob::new("backfill", 0));
+        queue.push(PriorityJob::new("fresh", 5));
+
+        let later = Instant::now() + Duration::from_secs(60);
+
+        // both gained 6 levels, the priority order holds.
+        assert_eq!(run(queue.pop(later, Duration::from_secs(10))), "fresh");
+
+        queue.push(PriorityJob::new("fresh", 5));
+
+        // the backfill waited a minute longer than the new job.
+        assert_eq!(run(queue.pop(later, Duration::from_secs(10))), "backfill");
+    }
+
+    #[test]
+    fn expired_jobs_are_dropped() {
+        let mut queue = PriorityQueue::default();
+        let now = Instant::now();
+
+        queue.push(PriorityJob::new("late", 0).with_deadline(now));
+        queue.push(PriorityJob::new("on-time", 0));
+
+        assert!(matches!(queue.pop(now, Duration::ZERO), Next::Expired(job) if job.url == "late"));
+        assert_eq!(run(queue.pop(now, Duration::ZERO)), "on-time");
+    }
+
+    #[test]
+    fn handle_reprioritizes() {
+        let handle = SchedulerHandle::default();
+
+        handle.push("a", 0);
+        handle.push("b", 1);
+
+        assert!(handle.reprioritize("a", 5));
+        assert!(handle.cancel("b"));
+        assert!(!handle.cancel("c"));
+        assert_eq!(run(handle.next(Duration::ZERO)), "a");
+        assert!(handle.is_empty());
+
+        handle.close();
+
+        assert!(handle.is_done());
+    }
+}
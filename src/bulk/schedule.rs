@@ -0,0 +1,596 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt};
+use futures::task::AtomicWaker;
+use futures::FutureExt;
+
+use super::{process_url, BulkOptions, HostSchedule, JobResult, PagePool};
+use crate::browser::Browser;
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+
+/// The days searched for the next run of a cron schedule, e.g. a schedule of february 30 never
+/// runs.
+const CRON_SEARCH_DAYS: i64 = 366 * 5;
+
+/// A cron schedule of the five fields `minute hour day-of-month month day-of-week`, in UTC.
+///
+/// Fields take `*`, values, ranges `a-b`, steps `*/n` or `a-b/n` and lists of them. Sunday is
+/// `0` or `7`. The macros `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Parse a cron field into the bitmask of its values.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let invalid = || CdpError::msg(format!("invalid cron field `{field}`"));
+    let mut mask = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+
+        if step == 0 {
+            return Err(invalid());
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                start.parse().map_err(|_| invalid())?,
+                end.parse().map_err(|_| invalid())?,
+            )
+        } else {
+            let value: u32 = range.parse().map_err(|_| invalid())?;
+            // `5/15` runs from 5 to the max.
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+/// The year, month and day of the days since the unix epoch.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+impl CronSchedule {
+    /// Parse the cron expression.
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CdpError::msg(format!(
+                "a cron expression has 5 fields, got `{expression}`"
+            )));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        // sunday is 0 and 7.
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn matches_day(&self, day: u32, weekday: u32) -> bool {
+        let by_day = self.days & (1 << day) != 0;
+        let by_weekday = self.weekdays & (1 << weekday) != 0;
+
+        // restricting both fields runs on either, like cron.
+        match (self.any_day, self.any_weekday) {
+            (false, false) => by_day || by_weekday,
+            _ => by_day && by_weekday,
+        }
+    }
+
+    /// The first run strictly after the time.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let secs = after.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+        let start = (secs / 60 + 1) * 60;
+        let first_day = start.div_euclid(86_400);
+        let first_minute = start.rem_euclid(86_400) / 60;
+
+        for days in first_day..first_day + CRON_SEARCH_DAYS {
+            let (_, month, day) = civil_from_days(days);
+            // the epoch was a thursday.
+            let weekday = (days + 4).rem_euclid(7) as u32;
+
+            if self.months & (1 << month) == 0 || !self.matches_day(day, weekday) {
+                continue;
+            }
+
+            for hour in 0..24i64 {
+                if self.hours & (1 << hour) == 0 {
+                    continue;
+                }
+
+                for minute in 0..60i64 {
+                    if self.minutes & (1 << minute) == 0
+                        || (days == first_day && hour * 60 + minute < first_minute)
+                    {
+                        continue;
+                    }
+
+                    let at = days * 86_400 + hour * 3_600 + minute * 60;
+                    return Some(UNIX_EPOCH + Duration::from_secs(at as u64));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// How often a scheduled job runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cadence {
+    /// Run at a fixed interval, the first run right away.
+    Every(Duration),
+    /// Run on a cron schedule.
+    Cron(CronSchedule),
+}
+
+impl Cadence {
+    /// The run after the time.
+    fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        match self {
+            Cadence::Every(interval) => Some(after + (*interval).max(Duration::from_secs(1))),
+            Cadence::Cron(cron) => cron.next_after(after),
+        }
+    }
+
+    /// The first run of a job added at the time.
+    fn first(&self, now: SystemTime) -> Option<SystemTime> {
+        match self {
+            Cadence::Every(_) => Some(now),
+            Cadence::Cron(cron) => cron.next_after(now),
+        }
+    }
+}
+
+/// A recurring page job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledJob {
+    /// The name of the job, unique in the scheduler.
+    pub name: String,
+    /// The url the job navigates to.
+    pub url: String,
+    /// How often the job runs.
+    pub cadence: Cadence,
+}
+
+impl ScheduledJob {
+    /// A job running the url at the interval.
+    pub fn every(name: impl Into<String>, url: impl Into<String>, interval: Duration) -> Self {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            cadence: Cadence::Every(interval),
+        }
+    }
+
+    /// A job running the url on the cron schedule.
+    pub fn cron(name: impl Into<String>, url: impl Into<String>, expression: &str) -> Result<Self> {
+        Ok(Self {
+            name: name.into(),
+            url: url.into(),
+            cadence: Cadence::Cron(CronSchedule::parse(expression)?),
+        })
+    }
+}
+
+/// The result of a run of a scheduled job.
+#[derive(Debug)]
+pub struct ScheduledRun<T> {
+    /// The name of the job.
+    pub name: String,
+    /// The run of the job, counting from 1.
+    pub run: u64,
+    /// When the run was due.
+    pub scheduled_at: SystemTime,
+    /// The outcome of the run, retries included.
+    pub result: JobResult<T>,
+}
+
+/// A registered job.
+#[derive(Debug)]
+struct Entry {
+    job: ScheduledJob,
+    next: Option<SystemTime>,
+    runs: u64,
+}
+
+/// The jobs of the scheduler.
+#[derive(Debug, Default)]
+struct Jobs {
+    entries: Vec<Entry>,
+    stopped: bool,
+}
+
+impl Jobs {
+    /// Take the next due job which is not running, skipping the runs it missed while running.
+    fn pop_due(
+        &mut self,
+        now: SystemTime,
+        running: &HashSet<String>,
+    ) -> Option<(ScheduledJob, u64, SystemTime)> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .filter(|entry| !running.contains(&entry.job.name))
+            .filter(|entry| entry.next.is_some_and(|next| next <= now))
+            .min_by_key(|entry| entry.next)?;
+
+        let scheduled_at = entry.next?;
+        let mut next = entry.job.cadence.next_after(scheduled_at);
+
+        while let Some(at) = next.filter(|at| *at <= now) {
+            next = entry.job.cadence.next_after(at);
+        }
+
+        entry.next = next;
+        entry.runs += 1;
+
+        Some((entry.job.clone(), entry.runs, scheduled_at))
+    }
+
+    /// The next due time of the jobs which are not running.
+    fn next_due(&self, running: &HashSet<String>) -> Option<SystemTime> {
+        self.entries
+            .iter()
+            .filter(|entry| !running.contains(&entry.job.name))
+            .filter_map(|entry| entry.next)
+            .min()
+    }
+}
+
+/// The state shared by the scheduler and its result stream.
+#[derive(Debug, Default)]
+struct Shared {
+    jobs: Mutex<Jobs>,
+    waker: AtomicWaker,
+}
+
+/// Re-runs registered page jobs on an interval or a cron schedule, run with
+/// [`Browser::run_scheduler`]. A job never overlaps itself: the runs due while it is still
+/// running collapse into one run once it completed.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    shared: Arc<Shared>,
+}
+
+impl Scheduler {
+    /// A scheduler without jobs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update<R>(&self, f: impl FnOnce(&mut Jobs) -> R) -> Option<R> {
+        let updated = self.shared.jobs.lock().ok().map(|mut jobs| f(&mut jobs));
+        self.shared.waker.wake();
+        updated
+    }
+
+    /// Register the job, replacing the job of the same name.
+    pub fn add(&self, job: ScheduledJob) {
+        self.update(|jobs| {
+            jobs.entries.retain(|entry| entry.job.name != job.name);
+            jobs.entries.push(Entry {
+                next: job.cadence.first(SystemTime::now()),
+                job,
+                runs: 0,
+            });
+        });
+    }
+
+    /// Remove the job, `false` when no job has the name. A running job completes.
+    pub fn remove(&self, name: &str) -> bool {
+        self.update(|jobs| {
+            let before = jobs.entries.len();
+            jobs.entries.retain(|entry| entry.job.name != name);
+            jobs.entries.len() != before
+        })
+        .unwrap_or_default()
+    }
+
+    /// The names of the registered jobs.
+    pub fn jobs(&self) -> Vec<String> {
+        self.shared
+            .jobs
+            .lock()
+            .map(|jobs| {
+                jobs.entries
+                    .iter()
+                    .map(|entry| entry.job.name.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The next due time of the job.
+    pub fn next_run(&self, name: &str) -> Option<SystemTime> {
+        self.shared.jobs.lock().ok().and_then(|jobs| {
+            jobs.entries
+                .iter()
+                .find(|entry| entry.job.name == name)
+                .and_then(|entry| entry.next)
+        })
+    }
+
+    /// Stop scheduling runs, the stream ends once the running jobs completed.
+    pub fn stop(&self) {
+        self.update(|jobs| jobs.stopped = true);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.shared
+            .jobs
+            .lock()
+            .map(|jobs| jobs.stopped)
+            .unwrap_or(true)
+    }
+}
+
+impl Browser {
+    /// Run the jobs of the scheduler on a pool of pages, with the retries, timeouts and host
+    /// politeness of [`Browser::process_urls_with_options`], yielding every run as it completes.
+    /// The stream ends once the scheduler is stopped.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use chromiumoxide::browser::Browser;
+    /// # use chromiumoxide::bulk::{BulkOptions, ScheduledJob, Scheduler};
+    /// # use futures::StreamExt;
+    /// # async fn demo(browser: Browser) -> chromiumoxide::error::Result<()> {
+    /// let scheduler = Scheduler::new();
+    /// scheduler.add(ScheduledJob::every("home", "https://example.com", Duration::from_secs(300)));
+    /// scheduler.add(ScheduledJob::cron("pricing", "https://example.com/pricing", "0 6 * * *")?);
+    ///
+    /// let mut runs = browser.run_scheduler(&scheduler, BulkOptions::default(), |page, url| async move {
+    ///     page.goto(url).await?;
+    ///     page.content().await
+    /// });
+    ///
+    /// while let Some(run) = runs.next().await {
+    ///     println!("{} #{} {:?}", run.name, run.run, run.result.result.map(|html| html.len()));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_scheduler<'a, T, F, Fut>(
+        &'a self,
+        scheduler: &Scheduler,
+        options: BulkOptions,
+        job: F,
+    ) -> impl Stream<Item = ScheduledRun<T>> + 'a
+    where
+        T: 'a,
+        F: Fn(Page, String) -> Fut + 'a,
+        Fut: Future<Output = Result<T>> + 'a,
+    {
+        let scheduler = scheduler.clone();
+
+        let concurrency = options.concurrency.max(1);
+        let options = Arc::new(options);
+        let pool = Arc::new(PagePool::default());
+        let hosts = Arc::new(HostSchedule::default());
+        let job = Arc::new(job);
+
+        let mut running = HashSet::new();
+        let mut in_flight: FuturesUnordered<Pin<Box<dyn Future<Output = ScheduledRun<T>> + 'a>>> =
+            FuturesUnordered::new();
+        let mut timer: Option<Pin<Box<dyn Future<Output = ()> + 'a>>> = None;
+
+        let results = {
+            let pool = pool.clone();
+
+            stream::poll_fn(move |cx| loop {
+                let stopped = scheduler.is_stopped();
+
+                while !stopped && in_flight.len() < concurrency {
+                    let due = scheduler
+                        .shared
+                        .jobs
+                        .lock()
+                        .ok()
+                        .and_then(|mut jobs| jobs.pop_due(SystemTime::now(), &running));
+
+                    let Some((next, run, scheduled_at)) = due else {
+                        break;
+                    };
+
+                    running.insert(next.name.clone());
+
+                    let (options, pool, hosts, job) =
+                        (options.clone(), pool.clone(), hosts.clone(), job.clone());
+
+                    in_flight.push(Box::pin(async move {
+                        let result =
+                            process_url(self, next.url, &options, &pool, &hosts, &*job).await;
+
+                        ScheduledRun {
+                            name: next.name,
+                            run,
+                            scheduled_at,
+                            result,
+                        }
+                    }));
+                }
+
+                if let Poll::Ready(Some(run)) = in_flight.poll_next_unpin(cx) {
+                    running.remove(&run.name);
+                    return Poll::Ready(Some(run));
+                }
+
+                if stopped && in_flight.is_empty() {
+                    return Poll::Ready(None);
+                }
+
+                scheduler.shared.waker.register(cx.waker());
+
+                // wake up for the next due job.
+                let next_due = if stopped || in_flight.len() >= concurrency {
+                    None
+                } else {
+                    scheduler
+                        .shared
+                        .jobs
+                        .lock()
+                        .ok()
+                        .and_then(|jobs| jobs.next_due(&running))
+                };
+
+                match next_due {
+                    Some(next_due) => {
+                        let wait = next_due
+                            .duration_since(SystemTime::now())
+                            .unwrap_or(Duration::ZERO);
+
+                        if wait.is_zero() {
+                            continue;
+                        }
+
+                        let sleep = timer.insert(Box::pin(crate::runtime::sleep(wait)));
+
+                        if sleep.poll_unpin(cx).is_ready() {
+                            continue;
+                        }
+                    }
+                    None => timer = None,
+                }
+
+                return Poll::Pending;
+            })
+        };
+
+        let cleanup = stream::once(async move {
+            pool.close_all().await;
+        })
+        .filter_map(|_| async { None });
+
+        results.chain(cleanup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn parses_cron_fields() {
+        assert_eq!(
+            parse_field("*/15", 0, 59).unwrap(),
+            1 | 1 << 15 | 1 << 30 | 1 << 45
+        );
+        assert_eq!(parse_field("1-3,5", 0, 59).unwrap(), 0b101110);
+        assert_eq!(parse_field("50/5", 0, 59).unwrap(), 1 << 50 | 1 << 55);
+        assert!(parse_field("60", 0, 59).is_err());
+        assert!(parse_field("*/0", 0, 59).is_err());
+        assert!(CronSchedule::parse("* * *").is_err());
+        assert_eq!(
+            CronSchedule::parse("0 0 * * 7").unwrap(),
+            CronSchedule::parse("@weekly").unwrap()
+        );
+    }
+
+    #[test]
+    fn computes_the_next_cron_run() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+
+        // 2024-02-29 12:30:00 UTC, a thursday.
+        let now = at(1_709_209_800);
+
+        let daily = CronSchedule::parse("0 6 * * *").unwrap();
+        // 2024-03-01 06:00:00 UTC.
+        assert_eq!(daily.next_after(now), Some(at(1_709_272_800)));
+
+        let every_minute = CronSchedule::parse("* * * * *").unwrap();
+        assert_eq!(every_minute.next_after(now), Some(at(1_709_209_860)));
+
+        // mondays at 09:00, 2024-03-04.
+        let mondays = CronSchedule::parse("0 9 * * 1").unwrap();
+        assert_eq!(mondays.next_after(now), Some(at(1_709_542_800)));
+
+        let never = CronSchedule::parse("0 0 30 2 *").unwrap();
+        assert_eq!(never.next_after(now), None);
+    }
+
+    #[test]
+    fn skips_runs_missed_while_running() {
+        let mut jobs = Jobs::default();
+        jobs.entries.push(Entry {
+            job: ScheduledJob::every("a", "https://example.com", Duration::from_secs(10)),
+            next: Some(at(100)),
+            runs: 0,
+        });
+
+        let mut running = HashSet::new();
+        running.insert("a".to_string());
+        assert!(jobs.pop_due(at(135), &running).is_none());
+
+        running.clear();
+        let (_, run, scheduled_at) = jobs.pop_due(at(135), &running).unwrap();
+
+        assert_eq!((run, scheduled_at), (1, at(100)));
+        assert_eq!(jobs.entries[0].next, Some(at(140)));
+        assert!(jobs.pop_due(at(135), &running).is_none());
+    }
+}
@@ -0,0 +1,262 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use http_cache_reqwest::{CacheManager, HttpResponse};
+use http_global_cache::CACACHE_MANAGER;
+
+/// The stored header marking a compressed cache body, removed on read.
+pub const CACHE_BODY_ENCODING_HEADER: &str = "x-chromey-cache-encoding";
+
+/// The compression level value disabling compression.
+const DISABLED: i32 = i32::MIN;
+
+/// Bodies smaller than this are stored as is.
+const MIN_COMPRESS_SIZE: usize = 512;
+
+lazy_static::lazy_static! {
+    /// The zstd level of the stored bodies, from `CACHE_ZSTD_LEVEL` or `3`, clamped to `1..=9`
+    /// for gzip. Compression requires the `cache_zstd` or the `cache_gzip` feature.
+    static ref COMPRESSION_LEVEL: AtomicI32 = AtomicI32::new(
+        std::env::var("CACHE_ZSTD_LEVEL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3)
+    );
+}
+
+/// Set the zstd level of the bodies stored from now on, `None` stores them uncompressed.
+/// Compressed bodies are decompressed on read regardless of the level.
+pub fn set_compression_level(level: Option<i32>) {
+    COMPRESSION_LEVEL.store(level.unwrap_or(DISABLED), Ordering::Relaxed);
+}
+
+/// The zstd level of the stored bodies, `None` when compression is disabled.
+pub fn compression_level() -> Option<i32> {
+    if !cfg!(any(feature = "cache_zstd", feature = "cache_gzip")) {
+        return None;
+    }
+
+    match COMPRESSION_LEVEL.load(Ordering::Relaxed) {
+        DISABLED => None,
+        level => Some(level),
+    }
+}
+
+/// Is the stored body compressed?
+pub fn is_compressed(response: &HttpResponse) -> bool {
+    response.headers.contains_key(CACHE_BODY_ENCODING_HEADER)
+}
+
+/// Compress the body with the compression level, zstd or gzip without the `cache_zstd`
+/// feature. Returns the compressed body and its encoding, `None` when compression is disabled
+/// or the body does not shrink.
+pub fn encode_body(body: &[u8]) -> Option<(Vec<u8>, &'static str)> {
+    let level = compression_level()?;
+
+    if body.len() < MIN_COMPRESS_SIZE {
+        return None;
+    }
+
+    #[cfg(feature = "cache_zstd")]
+    let encoded = zstd::bulk::compress(body, level).map(|body| (body, "zstd"));
+
+    #[cfg(all(feature = "cache_gzip", not(feature = "cache_zstd")))]
+    let encoded = {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::with_capacity(body.len() / 2),
+            flate2::Compression::new(level.clamp(1, 9) as u32),
+        );
+        encoder
+            .write_all(body)
+            .and_then(|_| encoder.finish())
+            .map(|body| (body, "gzip"))
+    };
+
+    #[cfg(not(any(feature = "cache_zstd", feature = "cache_gzip")))]
+    let encoded: std::io::Result<(Vec<u8>, &'static str)> = {
+        let _ = level;
+        Err(std::io::ErrorKind::Unsupported.into())
+    };
+
+    match encoded {
+        Ok((encoded, encoding)) if encoded.len() < body.len() => Some((encoded, encoding)),
+        Ok(_) => None,
+        Err(err) => {
+            tracing::debug!("cache compression failed: {err}");
+            None
+        }
+    }
+}
+
+/// Decompress a body of the encoding, `None` when the encoding is not supported or the body
+/// can not be decompressed.
+pub fn decode_body(body: &[u8], encoding: &str) -> Option<Vec<u8>> {
+    let decoded: std::io::Result<Vec<u8>> = match encoding {
+        #[cfg(feature = "cache_zstd")]
+        "zstd" => zstd::stream::decode_all(body),
+        #[cfg(feature = "cache_gzip")]
+        "gzip" => {
+            use std::io::Read;
+
+            let mut decoded = Vec::with_capacity(body.len() * 2);
+            flate2::read::GzDecoder::new(body)
+                .read_to_end(&mut decoded)
+                .map(|_| decoded)
+        }
+        _ => {
+            let _ = body;
+            tracing::debug!("cache body encoding not supported: {encoding}");
+            return None;
+        }
+    };
+
+    match decoded {
+        Ok(decoded) => Some(decoded),
+        Err(err) => {
+            tracing::debug!("cache decompression failed: {err}");
+            None
+        }
+    }
+}
+
+/// Gzip a request body for the remote cache server, `None` without the `cache_gzip` feature.
+pub(crate) fn gzip(body: &[u8]) -> Option<Vec<u8>> {
+    #[cfg(feature = "cache_gzip")]
+    {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::with_capacity(body.len() / 4),
+            flate2::Compression::default(),
+        );
+
+        match encoder.write_all(body).and_then(|_| encoder.finish()) {
+            Ok(encoded) => Some(encoded),
+            Err(err) => {
+                tracing::debug!("gzip failed: {err}");
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "cache_gzip"))]
+    {
+        let _ = body;
+        None
+    }
+}
+
+/// Compress the body of a response before it is stored. Bodies that do not shrink are kept.
+pub fn compress_response(response: &mut HttpResponse) {
+    if is_compressed(response) {
+        return;
+    }
+
+    if let Some((body, encoding)) = encode_body(&response.body) {
+        response.body = body;
+        response
+            .headers
+            .insert(CACHE_BODY_ENCODING_HEADER.into(), encoding.into());
+    }
+}
+
+/// Decompress the body of a stored response. Bodies stored before compression was enabled are
+/// returned as is, `None` when the body can not be decompressed.
+pub fn decompress_response(mut response: HttpResponse) -> Option<HttpResponse> {
+    let encoding = match response.headers.remove(CACHE_BODY_ENCODING_HEADER) {
+        Some(encoding) => encoding,
+        _ => return Some(response),
+    };
+
+    response.body = decode_body(&response.body, &encoding)?;
+
+    Some(response)
+}
+
+/// Compress the stored entries of the cache keys in place, e.g. to migrate a cache written
+/// before compression was enabled. Returns the amount of entries rewritten.
+pub async fn migrate_entries<I>(cache_keys: I) -> usize
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut migrated = 0;
+
+    if compression_level().is_none() {
+        return migrated;
+    }
+
+    for cache_key in cache_keys {
+        let cache_key = cache_key.as_ref();
+
+        let (mut response, policy) = match CACACHE_MANAGER.get(cache_key).await {
+            Ok(Some(entry)) => entry,
+            _ => continue,
+        };
+
+        if is_compressed(&response) {
+            continue;
+        }
+
+        compress_response(&mut response);
+
+        if is_compressed(&response) {
+            match CACACHE_MANAGER
+                .put(cache_key.to_string(), response, policy)
+                .await
+            {
+                Ok(_) => migrated += 1,
+                Err(err) => super::journal::report_write_error(cache_key, err),
+            }
+        }
+    }
+
+    migrated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: Vec<u8>) -> HttpResponse {
+        HttpResponse {
+            body,
+            headers: Default::default(),
+            status: 200,
+            url: url::Url::parse("https://example.com/").unwrap(),
+            version: http_cache::HttpVersion::Http11,
+        }
+    }
+
+    #[test]
+    fn uncompressed_entries_read_as_is() {
+        let stored = response(b"<html></html>".to_vec());
+        let read = decompress_response(stored).unwrap();
+
+        assert_eq!(read.body, b"<html></html>");
+    }
+
+    #[cfg(any(feature = "cache_zstd", feature = "cache_gzip"))]
+    #[test]
+    fn compressed_round_trip() {
+        let html = "<div class=\"item\">chromey</div>".repeat(100).into_bytes();
+        let mut stored = response(html.clone());
+
+        compress_response(&mut stored);
+
+        assert!(is_compressed(&stored));
+        assert!(stored.body.len() < html.len());
+
+        let read = decompress_response(stored).unwrap();
+
+        assert_eq!(read.body, html);
+        assert!(!is_compressed(&read));
+    }
+
+    #[test]
+    fn small_and_unknown_bodies() {
+        assert_eq!(encode_body(b"tiny"), None);
+        assert_eq!(decode_body(b"tiny", "br"), None);
+    }
+}
@@ -1,9 +1,12 @@
 use std::{
     collections::{HashMap, HashSet},
-    time::Duration,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use tokio::sync::{mpsc, OnceCell};
+use tokio::sync::{mpsc, OnceCell, Semaphore};
 
+use super::remote::DumpOutcome;
 use crate::http::HttpVersion;
 
 static REMOTE_DUMP_TX: OnceCell<mpsc::Sender<DumpJob>> = OnceCell::const_new();
@@ -18,6 +21,9 @@ pub struct DumpJob {
     pub request_headers: HashMap<String, String>,
     pub response_headers: HashMap<String, String>,
     pub body: Vec<u8>,
+    /// The encoding of an already compressed body, e.g. `zstd`. A raw body is compressed
+    /// before the dump when the remote cache config compresses the bodies.
+    pub content_encoding: Option<String>,
     pub http_version: HttpVersion,
     /// None => default endpoint
     /// Some("true") => default endpoint
@@ -25,49 +31,427 @@ pub struct DumpJob {
     pub dump_remote: Option<String>,
 }
 
-async fn init_inner(queue_cap: usize, qps: u32, timeout_ms: u64) -> mpsc::Sender<DumpJob> {
-    let (tx, mut rx) = mpsc::channel::<DumpJob>(queue_cap.max(1));
+/// How the worker batches the dumps to the `/cache/index/batch` endpoint of the remote cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpBatchConfig {
+    /// The jobs sent per request, `1` dumps every job on its own to `/cache/index`.
+    pub batch_size: usize,
+    /// Send the pending jobs after this long, even when the batch is not full.
+    pub flush_interval: Duration,
+}
 
-    tokio::spawn(async move {
-        let qps = qps.max(1);
-        let tick_ms = (1000u64 / qps as u64).max(1);
-        let mut tick = tokio::time::interval(Duration::from_millis(tick_ms));
-        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+impl Default for DumpBatchConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: default_batch_size(),
+            flush_interval: Duration::from_millis(default_flush_interval_ms()),
+        }
+    }
+}
 
-        let mut inflight: HashSet<String> = HashSet::new();
+impl DumpBatchConfig {
+    /// The jobs are sent in batches.
+    pub fn is_batched(&self) -> bool {
+        self.batch_size > 1
+    }
+}
 
-        while let Some(job) = rx.recv().await {
-            if !inflight.insert(job.cache_key.clone()) {
-                continue;
+/// How the worker sends the dumps to the remote cache server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpWorkerConfig {
+    /// The jobs queued before new ones are dropped.
+    pub queue_cap: usize,
+    /// The max dumps per second to every remote cache host.
+    pub qps: u32,
+    /// The timeout of a dump.
+    pub timeout_ms: u64,
+    /// The dumps sent at once, so a slow remote cache does not stall the others.
+    pub concurrency: usize,
+    /// The max dumps per second to a remote cache host, `None` to limit the `qps` only.
+    pub host_qps: Option<u32>,
+    /// The retries of a dump failing with a transient error, e.g. a `503` or a timeout.
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled on every retry.
+    pub retry_backoff: Duration,
+    /// The batching of the dumps.
+    pub batch: DumpBatchConfig,
+}
+
+impl Default for DumpWorkerConfig {
+    fn default() -> Self {
+        Self {
+            queue_cap: default_queue_cap(),
+            qps: default_qps(),
+            timeout_ms: default_timeout_ms(),
+            concurrency: default_concurrency(),
+            host_qps: default_host_qps(),
+            max_retries: default_max_retries(),
+            retry_backoff: Duration::from_millis(default_retry_backoff_ms()),
+            batch: DumpBatchConfig::default(),
+        }
+    }
+}
+
+impl DumpWorkerConfig {
+    /// The delay before the retry after the failed attempt, zero based.
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        self.retry_backoff
+            .saturating_mul(1 << attempt.min(16))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+/// The longest delay between two attempts of a dump.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Spaces the dumps to every remote cache host.
+#[derive(Debug, Default)]
+struct HostLimiter {
+    /// The delay between two dumps to a host, `None` without a limit.
+    interval: Option<Duration>,
+    /// The time of the next dump per host.
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostLimiter {
+    fn new(host_qps: Option<u32>) -> Self {
+        Self {
+            interval: host_qps.map(|qps| Duration::from_secs(1) / qps.max(1)),
+            next: Default::default(),
+        }
+    }
+
+    /// Reserve the next slot of the host, returning the delay until it.
+    fn reserve(&self, host: &str, now: Instant) -> Duration {
+        let Some(interval) = self.interval else {
+            return Duration::ZERO;
+        };
+
+        let Ok(mut next) = self.next.lock() else {
+            return Duration::ZERO;
+        };
+
+        let slot = next
+            .get(host)
+            .copied()
+            .filter(|slot| *slot > now)
+            .unwrap_or(now);
+
+        next.insert(host.to_string(), slot + interval);
+
+        slot - now
+    }
+
+    /// Wait for the next slot of the host.
+    async fn acquire(&self, host: &str) {
+        let delay = self.reserve(host, Instant::now());
+
+        if !delay.is_zero() {
+            crate::runtime::sleep(delay).await;
+        }
+    }
+}
+
+/// The host of the remote cache of the dump.
+fn remote_host(dump_remote: Option<&str>) -> String {
+    let config = super::remote_config::remote_cache_config();
+    let base_url = config.base_url(dump_remote);
+
+    url::Url::parse(base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_else(|| base_url.to_string())
+}
+
+/// Sends the dumps of the worker concurrently, within the rate limits.
+#[derive(Debug, Clone)]
+struct DumpSender {
+    config: DumpWorkerConfig,
+    /// Spaces all the dumps at the qps of the worker, under the single empty host.
+    tick: Arc<HostLimiter>,
+    permits: Arc<Semaphore>,
+    hosts: Arc<HostLimiter>,
+    inflight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl DumpSender {
+    fn new(config: DumpWorkerConfig) -> Self {
+        Self {
+            config,
+            tick: Arc::new(HostLimiter::new(Some(config.qps))),
+            permits: Arc::new(Semaphore::new(config.concurrency.max(1))),
+            hosts: Arc::new(HostLimiter::new(config.host_qps)),
+            inflight: Default::default(),
+        }
+    }
+
+    /// Run the dump within the host limit, retrying the transient failures with a backoff.
+    async fn with_retries<F, Fut>(&self, host: &str, label: &str, mut dump: F) -> DumpOutcome
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = DumpOutcome>,
+    {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let mut attempt = 0;
+
+        loop {
+            self.hosts.acquire(host).await;
+
+            let outcome = match crate::runtime::timeout(timeout, dump()).await {
+                Ok(outcome) => outcome,
+                _ => {
+                    tracing::warn!(
+                        "remote cache dump: timed out after {}ms for {}",
+                        self.config.timeout_ms,
+                        label
+                    );
+                    DumpOutcome::Failed
+                }
+            };
+
+            if !outcome.is_transient() || attempt >= self.config.max_retries {
+                return outcome;
+            }
+
+            let delay = self.config.retry_delay(attempt);
+
+            tracing::debug!(
+                "remote cache dump: retrying {} in {}ms after {:?}",
+                label,
+                delay.as_millis(),
+                outcome
+            );
+
+            crate::runtime::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send the job on its own, skipped while the same cache key is in flight.
+    async fn send_job(&self, job: DumpJob) {
+        let inserted = self
+            .inflight
+            .lock()
+            .map(|mut inflight| inflight.insert(job.cache_key.clone()))
+            .unwrap_or(true);
+
+        if !inserted {
+            return;
+        }
+
+        let Ok(permit) = self.permits.clone().acquire_owned().await else {
+            return;
+        };
+
+        self.tick.acquire("").await;
+
+        let sender = self.clone();
+
+        crate::runtime::spawn(async move {
+            let job = &job;
+            let host = remote_host(job.dump_remote.as_deref());
+            let outcome = sender
+                .with_retries(&host, &job.cache_key, move || dump_job(job))
+                .await;
+
+            super::stats::cache_stats().record_dump(outcome.is_stored());
+
+            if let Ok(mut inflight) = sender.inflight.lock() {
+                inflight.remove(&job.cache_key);
             }
-            tick.tick().await;
 
-            let timeout = Duration::from_millis(timeout_ms);
-            let res = tokio::time::timeout(timeout, dump_job(job.clone())).await;
+            drop(permit);
+        });
+    }
+
+    /// Send every group of jobs as one batch.
+    async fn send_batches(&self, groups: Vec<Vec<DumpJob>>) {
+        for jobs in groups {
+            let Ok(permit) = self.permits.clone().acquire_owned().await else {
+                return;
+            };
+
+            self.tick.acquire("").await;
 
-            if res.is_err() {
-                tracing::warn!(
-                    "remote cache dump: timed out after {}ms for {}",
-                    timeout_ms,
-                    job.cache_key
-                );
+            let sender = self.clone();
+
+            crate::runtime::spawn(async move {
+                let payloads: Vec<_> = jobs
+                    .iter()
+                    .map(|job| {
+                        super::remote::hybrid_payload(
+                            &job.cache_key,
+                            &job.url,
+                            &job.body,
+                            job.content_encoding.as_deref(),
+                            &job.method,
+                            job.status,
+                            &job.request_headers,
+                            &job.response_headers,
+                            &job.http_version,
+                        )
+                    })
+                    .collect();
+
+                let cache_site = jobs[0].cache_site.as_str();
+                let dump_remote = jobs[0].dump_remote.as_deref();
+                let payloads = &payloads;
+                let host = remote_host(dump_remote);
+                let label = format!("a batch of {} for {}", jobs.len(), cache_site);
+
+                let outcome = sender
+                    .with_retries(&host, &label, move || {
+                        super::remote::dump_batch_to_remote_cache(cache_site, payloads, dump_remote)
+                    })
+                    .await;
+
+                for _ in &jobs {
+                    super::stats::cache_stats().record_dump(outcome.is_stored());
+                }
+
+                drop(permit);
+            });
+        }
+    }
+}
+
+async fn init_inner(config: DumpWorkerConfig) -> mpsc::Sender<DumpJob> {
+    let (tx, mut rx) = mpsc::channel::<DumpJob>(config.queue_cap.max(1));
+
+    crate::runtime::spawn(async move {
+        let sender = DumpSender::new(config);
+        let batch = config.batch;
+
+        if batch.is_batched() {
+            let mut pending = PendingBatch::default();
+            let mut deadline = Instant::now();
+
+            loop {
+                let job = if pending.is_empty() {
+                    rx.recv().await
+                } else {
+                    let wait = deadline.saturating_duration_since(Instant::now());
+
+                    match crate::runtime::timeout(wait, rx.recv()).await {
+                        Ok(job) => job,
+                        Err(_) => {
+                            sender.send_batches(pending.take()).await;
+                            continue;
+                        }
+                    }
+                };
+
+                let Some(job) = job else {
+                    break;
+                };
+
+                if pending.is_empty() {
+                    deadline = Instant::now() + batch.flush_interval;
+                }
+
+                pending.push(job);
+
+                if pending.len() >= batch.batch_size {
+                    sender.send_batches(pending.take()).await;
+                }
             }
 
-            inflight.remove(&job.cache_key);
+            sender.send_batches(pending.take()).await;
+
+            return;
+        }
+
+        while let Some(job) = rx.recv().await {
+            sender.send_job(job).await;
         }
     });
 
     tx
 }
 
+/// The jobs waiting for the next batch, the latest job of a cache key wins.
+#[derive(Debug, Default)]
+struct PendingBatch {
+    jobs: Vec<DumpJob>,
+}
+
+impl PendingBatch {
+    fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    fn push(&mut self, job: DumpJob) {
+        match self
+            .jobs
+            .iter_mut()
+            .find(|pending| pending.cache_key == job.cache_key)
+        {
+            Some(pending) => *pending = job,
+            _ => self.jobs.push(job),
+        }
+    }
+
+    /// The pending jobs grouped by remote and cache site, in the order received.
+    fn take(&mut self) -> Vec<Vec<DumpJob>> {
+        let mut groups: Vec<Vec<DumpJob>> = Vec::new();
+
+        for job in std::mem::take(&mut self.jobs) {
+            match groups.iter_mut().find(|group| {
+                group[0].dump_remote == job.dump_remote && group[0].cache_site == job.cache_site
+            }) {
+                Some(group) => group.push(job),
+                _ => groups.push(vec![job]),
+            }
+        }
+
+        groups
+    }
+}
+
 /// Manual init (optional). Safe to call multiple times.
 pub async fn init_remote_dump_worker(
     queue_cap: usize,
     qps: u32,
     timeout_ms: u64,
+) -> mpsc::Sender<DumpJob> {
+    init_remote_dump_worker_with_config(DumpWorkerConfig {
+        queue_cap,
+        qps,
+        timeout_ms,
+        ..Default::default()
+    })
+    .await
+}
+
+/// Manual init with the batching of the dumps (optional). Safe to call multiple times, the
+/// first call configures the worker.
+pub async fn init_remote_dump_worker_batched(
+    queue_cap: usize,
+    qps: u32,
+    timeout_ms: u64,
+    batch: DumpBatchConfig,
+) -> mpsc::Sender<DumpJob> {
+    init_remote_dump_worker_with_config(DumpWorkerConfig {
+        queue_cap,
+        qps,
+        timeout_ms,
+        batch,
+        ..Default::default()
+    })
+    .await
+}
+
+/// Manual init with the config of the worker (optional). Safe to call multiple times, the
+/// first call configures the worker.
+pub async fn init_remote_dump_worker_with_config(
+    config: DumpWorkerConfig,
 ) -> mpsc::Sender<DumpJob> {
     REMOTE_DUMP_TX
-        .get_or_init(|| init_inner(queue_cap, qps, timeout_ms))
+        .get_or_init(|| init_inner(config))
         .await
         .clone()
 }
@@ -77,7 +461,13 @@ pub async fn enqueue_best_effort(job: DumpJob) -> bool {
     let tx =
         init_remote_dump_worker(default_queue_cap(), default_qps(), default_timeout_ms()).await;
 
-    tx.try_send(job).is_ok()
+    let queued = tx.try_send(job).is_ok();
+
+    if !queued {
+        super::stats::cache_stats().record_dump_dropped();
+    }
+
+    queued
 }
 
 /// If you prefer backpressure instead of drop-on-full:
@@ -89,12 +479,13 @@ pub async fn enqueue(job: DumpJob) -> Result<(), mpsc::error::SendError<DumpJob>
 }
 
 /// Dump the remote cache job.
-async fn dump_job(job: DumpJob) {
-    super::remote::dump_to_remote_cache_parts(
+async fn dump_job(job: &DumpJob) -> DumpOutcome {
+    super::remote::dump_to_remote_cache_encoded(
         &job.cache_key,
         &job.cache_site,
         &job.url,
         &job.body,
+        job.content_encoding.as_deref(),
         &job.method,
         job.status,
         &job.request_headers,
@@ -102,7 +493,7 @@ async fn dump_job(job: DumpJob) {
         &job.http_version,
         job.dump_remote.as_deref(),
     )
-    .await;
+    .await
 }
 
 /// Worker inited.
@@ -112,10 +503,23 @@ pub fn worke_inited() -> bool {
 
 /// Non-async enqueue (fast path). Drops if queue is full.
 pub fn try_enqueue(job: DumpJob) -> bool {
-    REMOTE_DUMP_TX
+    let queued = REMOTE_DUMP_TX
         .get()
         .and_then(|tx| tx.try_send(job).ok())
-        .is_some()
+        .is_some();
+
+    if !queued {
+        super::stats::cache_stats().record_dump_dropped();
+    }
+
+    queued
+}
+
+/// The jobs waiting in the queue of the worker.
+pub fn queue_depth() -> usize {
+    REMOTE_DUMP_TX
+        .get()
+        .map_or(0, |tx| tx.max_capacity() - tx.capacity())
 }
 
 pub fn default_queue_cap() -> usize {
@@ -139,7 +543,130 @@ pub fn default_timeout_ms() -> u64 {
         .unwrap_or(2_500)
 }
 
+pub fn default_batch_size() -> usize {
+    std::env::var("HYBRID_CACHE_REMOTE_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1)
+}
+
+pub fn default_flush_interval_ms() -> u64 {
+    std::env::var("HYBRID_CACHE_REMOTE_BATCH_FLUSH_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(500)
+}
+
+pub fn default_concurrency() -> usize {
+    std::env::var("HYBRID_CACHE_REMOTE_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(4)
+}
+
+pub fn default_host_qps() -> Option<u32> {
+    std::env::var("HYBRID_CACHE_REMOTE_HOST_QPS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+}
+
+pub fn default_max_retries() -> u32 {
+    std::env::var("HYBRID_CACHE_REMOTE_RETRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2)
+}
+
+pub fn default_retry_backoff_ms() -> u64 {
+    std::env::var("HYBRID_CACHE_REMOTE_RETRY_BACKOFF_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(200)
+}
+
 /// Init the cache worker.
 pub async fn init_default_cache_worker() {
     init_remote_dump_worker(default_queue_cap(), default_qps(), default_timeout_ms()).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(cache_key: &str, cache_site: &str, status: u16) -> DumpJob {
+        DumpJob {
+            cache_key: cache_key.into(),
+            cache_site: cache_site.into(),
+            url: format!("https://{cache_site}/"),
+            method: "GET".into(),
+            status,
+            request_headers: HashMap::new(),
+            response_headers: HashMap::new(),
+            body: Vec::new(),
+            content_encoding: None,
+            http_version: HttpVersion::Http11,
+            dump_remote: None,
+        }
+    }
+
+    #[test]
+    fn batches_by_site() {
+        let mut pending = PendingBatch::default();
+
+        pending.push(job("a", "one.com", 200));
+        pending.push(job("b", "two.com", 200));
+        pending.push(job("a", "one.com", 404));
+        pending.push(job("c", "one.com", 200));
+
+        assert_eq!(pending.len(), 3);
+
+        let groups = pending.take();
+
+        assert!(pending.is_empty());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].len(), 2);
+        assert_eq!(groups[0][0].status, 404);
+        assert_eq!(groups[1][0].cache_site, "two.com");
+    }
+
+    #[test]
+    fn spaces_the_dumps_per_host() {
+        let limiter = HostLimiter::new(Some(10));
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve("a.com", now), Duration::ZERO);
+        assert_eq!(limiter.reserve("a.com", now), Duration::from_millis(100));
+        assert_eq!(limiter.reserve("a.com", now), Duration::from_millis(200));
+        assert_eq!(limiter.reserve("b.com", now), Duration::ZERO);
+        assert_eq!(HostLimiter::new(None).reserve("a.com", now), Duration::ZERO);
+    }
+
+    #[test]
+    fn backs_off_the_retries() {
+        let config = DumpWorkerConfig {
+            retry_backoff: Duration::from_millis(100),
+            ..Default::default()
+        };
+
+        assert_eq!(config.retry_delay(0), Duration::from_millis(100));
+        assert_eq!(config.retry_delay(2), Duration::from_millis(400));
+        assert_eq!(config.retry_delay(40), MAX_RETRY_DELAY);
+        assert!(DumpOutcome::Rejected(503).is_transient());
+        assert!(!DumpOutcome::Rejected(400).is_transient());
+    }
+
+    #[test]
+    fn batching_needs_more_than_one_job() {
+        let batch = DumpBatchConfig {
+            batch_size: 1,
+            flush_interval: Duration::from_millis(100),
+        };
+
+        assert!(!batch.is_batched());
+        assert!(DumpBatchConfig {
+            batch_size: 50,
+            ..batch
+        }
+        .is_batched());
+    }
+}
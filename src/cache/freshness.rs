@@ -0,0 +1,290 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use http_cache_reqwest::CacheManager;
+use http_cache_semantics::CachePolicy;
+use http_global_cache::CACACHE_MANAGER;
+
+lazy_static::lazy_static! {
+    /// The freshness overrides of the local cache, `None` follows the origin headers.
+    static ref FRESHNESS_POLICY: RwLock<Option<Arc<FreshnessPolicy>>> = RwLock::new(None);
+    /// The entries written by this process, by recency of use.
+    static ref LRU_INDEX: Mutex<LruIndex> = Mutex::new(LruIndex::default());
+}
+
+/// Overrides of the freshness the origin `Cache-Control` headers give the cached responses, for
+/// scraping workloads where the server headers are hostile to caching.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FreshnessPolicy {
+    /// The min lifetime of a stored response.
+    pub min_ttl: Option<Duration>,
+    /// The max lifetime of a stored response.
+    pub max_ttl: Option<Duration>,
+    /// The lifetime of the responses of a host and its subdomains, replacing the origin one and
+    /// the floor and ceiling.
+    pub host_ttls: HashMap<String, Duration>,
+    /// Reuse the responses the origin marks uncacheable, e.g. `no-store` or `private`, for the
+    /// `min_ttl` or the ttl of their host.
+    pub cache_uncacheable: bool,
+    /// The max total size in bytes of the bodies written by this process, the least recently
+    /// used entries are evicted past it.
+    pub max_size: Option<u64>,
+}
+
+impl FreshnessPolicy {
+    /// Set the lifetime of the responses of the host and its subdomains.
+    pub fn with_host_ttl(mut self, host: impl Into<String>, ttl: Duration) -> Self {
+        self.host_ttls.insert(host.into().to_ascii_lowercase(), ttl);
+        self
+    }
+
+    /// The ttl of the host or the closest parent domain.
+    fn host_ttl(&self, url: &str) -> Option<Duration> {
+        if self.host_ttls.is_empty() {
+            return None;
+        }
+
+        let host = url::Url::parse(url).ok()?.host_str()?.to_ascii_lowercase();
+        let mut domain = host.as_str();
+
+        loop {
+            if let Some(ttl) = self.host_ttls.get(domain) {
+                return Some(*ttl);
+            }
+            domain = domain.split_once('.')?.1;
+        }
+    }
+
+    /// The lifetime of the stored response of the url, `None` when it may not be reused.
+    pub fn lifetime(
+        &self,
+        url: Option<&str>,
+        policy: &CachePolicy,
+        now: SystemTime,
+    ) -> Option<Duration> {
+        let storable = policy.is_storable();
+
+        if !storable && !self.cache_uncacheable {
+            return None;
+        }
+
+        if let Some(ttl) = url.and_then(|url| self.host_ttl(url)) {
+            return Some(ttl);
+        }
+
+        let origin = if storable {
+            policy.time_to_live(now) + policy.age(now)
+        } else {
+            Duration::ZERO
+        };
+        let lifetime = self.min_ttl.map_or(origin, |min| origin.max(min));
+
+        Some(self.max_ttl.map_or(lifetime, |max| lifetime.min(max)))
+    }
+
+    /// The stored response of the url is stale at `now`.
+    pub fn is_stale(&self, url: Option<&str>, policy: &CachePolicy, now: SystemTime) -> bool {
+        self.lifetime(url, policy, now)
+            .map_or(true, |lifetime| policy.age(now) >= lifetime)
+    }
+}
+
+/// Set the freshness overrides of the local cache, `None` follows the origin headers again.
+pub fn set_freshness_policy(policy: Option<FreshnessPolicy>) {
+    if let Ok(mut current) = FRESHNESS_POLICY.write() {
+        *current = policy.map(Arc::new);
+    }
+}
+
+/// The freshness overrides of the local cache.
+pub fn freshness_policy() -> Option<Arc<FreshnessPolicy>> {
+    FRESHNESS_POLICY
+        .read()
+        .ok()
+        .and_then(|policy| policy.clone())
+}
+
+/// The stored response of the url is stale at `now`, with the freshness overrides when set.
+pub fn is_stale(url: Option<&str>, policy: &CachePolicy, now: SystemTime) -> bool {
+    match freshness_policy() {
+        Some(overrides) => overrides.is_stale(url, policy, now),
+        _ => policy.is_stale(now),
+    }
+}
+
+/// The cache keys and sizes of the stored bodies, by recency of use.
+#[derive(Debug, Default)]
+struct LruIndex {
+    /// The size and the use tick of each key.
+    entries: HashMap<String, (u64, u64)>,
+    /// The keys by use tick, the least recently used first.
+    order: BTreeMap<u64, String>,
+    total: u64,
+    tick: u64,
+}
+
+impl LruIndex {
+    /// Mark the key as used, when known.
+    fn touch(&mut self, key: &str) {
+        if let Some((_, tick)) = self.entries.get_mut(key) {
+            self.order.remove(tick);
+            self.tick += 1;
+            *tick = self.tick;
+            self.order.insert(self.tick, key.to_string());
+        }
+    }
+
+    /// Record the stored size of the key, returning the keys evicted to fit the max size.
+    fn insert(&mut self, key: &str, size: u64, max_size: Option<u64>) -> Vec<String> {
+        self.remove(key);
+        self.tick += 1;
+        self.entries.insert(key.to_string(), (size, self.tick));
+        self.order.insert(self.tick, key.to_string());
+        self.total += size;
+
+        let mut evicted = Vec::new();
+
+        if let Some(max_size) = max_size {
+            while self.total > max_size && self.entries.len() > 1 {
+                let Some((_, oldest)) = self.order.pop_first() else {
+                    break;
+                };
+                if let Some((size, _)) = self.entries.remove(&oldest) {
+                    self.total -= size;
+                }
+                evicted.push(oldest);
+            }
+        }
+
+        evicted
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, tick)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+            self.total -= size;
+        }
+    }
+}
+
+/// Mark the cache entry as used for the eviction.
+pub(crate) fn touch(cache_key: &str) {
+    if let Ok(mut index) = LRU_INDEX.lock() {
+        index.touch(cache_key);
+    }
+}
+
+/// Record a stored cache entry, evicting the least recently used entries past the max size of
+/// the freshness overrides.
+pub(crate) async fn record_put(cache_key: &str, size: usize) {
+    let max_size = freshness_policy().and_then(|policy| policy.max_size);
+
+    let evicted = match LRU_INDEX.lock() {
+        Ok(mut index) => index.insert(cache_key, size as u64, max_size),
+        _ => return,
+    };
+
+    for key in evicted {
+        tracing::debug!("Evicting cache {key}");
+        if let Err(err) = CACACHE_MANAGER.delete(&key).await {
+            tracing::debug!("cache eviction failed for {key}: {err:?}");
+        }
+    }
+}
+
+/// The total size in bytes of the stored bodies written by this process.
+pub fn stored_size() -> u64 {
+    LRU_INDEX
+        .lock()
+        .map(|index| index.total)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{convert_headers, HttpRequestLike, HttpResponseLike};
+
+    fn stored(cache_control: &str) -> CachePolicy {
+        let req = HttpRequestLike {
+            uri: "https://example.com/".parse().unwrap(),
+            method: reqwest::Method::GET,
+            headers: Default::default(),
+        };
+        let res = HttpResponseLike {
+            status: reqwest::StatusCode::OK,
+            headers: convert_headers(&HashMap::from([(
+                "cache-control".to_string(),
+                cache_control.to_string(),
+            )])),
+        };
+        CachePolicy::new(&req, &res)
+    }
+
+    #[test]
+    fn clamps_the_origin_lifetime() {
+        let now = SystemTime::now();
+        let policy = FreshnessPolicy {
+            min_ttl: Some(Duration::from_secs(60)),
+            max_ttl: Some(Duration::from_secs(600)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            policy.lifetime(None, &stored("max-age=1"), now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            policy.lifetime(None, &stored("max-age=86400"), now),
+            Some(Duration::from_secs(600))
+        );
+        assert!(!policy.is_stale(None, &stored("max-age=0"), now + Duration::from_secs(30)));
+        assert!(policy.is_stale(None, &stored("max-age=0"), now + Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn overrides_per_host_and_uncacheable() {
+        let now = SystemTime::now();
+        let policy =
+            FreshnessPolicy::default().with_host_ttl("Example.com", Duration::from_secs(3600));
+
+        assert_eq!(
+            policy.lifetime(Some("https://example.com/"), &stored("no-store"), now),
+            None
+        );
+
+        let policy = FreshnessPolicy {
+            cache_uncacheable: true,
+            ..policy
+        };
+        assert_eq!(
+            policy.lifetime(
+                Some("https://cdn.example.com/a.js"),
+                &stored("no-store"),
+                now
+            ),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            policy.lifetime(Some("https://example.org/"), &stored("no-store"), now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut index = LruIndex::default();
+
+        assert!(index.insert("a", 40, Some(100)).is_empty());
+        assert!(index.insert("b", 40, Some(100)).is_empty());
+        index.touch("a");
+        assert_eq!(index.insert("c", 40, Some(100)), vec!["b".to_string()]);
+        assert_eq!(index.total, 80);
+
+        assert!(index.insert("a", 10, Some(100)).is_empty());
+        assert_eq!(index.total, 50);
+        assert_eq!(index.insert("d", 500, Some(100)), vec!["c", "a"]);
+        assert_eq!(index.total, 500);
+    }
+}
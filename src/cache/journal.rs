@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Notify};
+
+use crate::http::{HttpResponse, HttpVersion};
+
+lazy_static::lazy_static! {
+    /// The write-ahead journal directory, from `CACHE_JOURNAL_DIR`. Journaling is disabled when
+    /// `None`.
+    static ref JOURNAL_DIR: RwLock<Option<PathBuf>> =
+        RwLock::new(std::env::var_os("CACHE_JOURNAL_DIR").map(PathBuf::from));
+    /// Notified when the last in-flight cache write completes.
+    static ref FLUSHED: Notify = Notify::new();
+    /// The failed cache writes.
+    static ref WRITE_ERRORS: broadcast::Sender<CacheWriteError> = broadcast::channel(128).0;
+}
+
+/// The cache writes in flight.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// The sequence of the journal files of this process.
+static JOURNAL_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// A failed write of the local cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheWriteError {
+    /// The key of the entry.
+    pub cache_key: String,
+    /// The error of the cache store.
+    pub error: String,
+}
+
+/// Subscribe to the failed writes of the local cache.
+pub fn subscribe_write_errors() -> broadcast::Receiver<CacheWriteError> {
+    WRITE_ERRORS.subscribe()
+}
+
+/// Report a failed write of the local cache.
+pub(crate) fn report_write_error(cache_key: &str, error: impl std::fmt::Display) {
+    let error = CacheWriteError {
+        cache_key: cache_key.to_string(),
+        error: error.to_string(),
+    };
+
+    tracing::warn!(
+        "cache write failed for {}: {}",
+        error.cache_key,
+        error.error
+    );
+
+    // nobody listening is not an error.
+    let _ = WRITE_ERRORS.send(error);
+}
+
+/// Set the write-ahead journal directory, `None` disables journaling.
+pub fn set_journal_dir(dir: Option<PathBuf>) {
+    if let Ok(mut journal_dir) = JOURNAL_DIR.write() {
+        *journal_dir = dir;
+    }
+}
+
+/// The write-ahead journal directory.
+pub fn journal_dir() -> Option<PathBuf> {
+    JOURNAL_DIR.read().ok().and_then(|dir| dir.clone())
+}
+
+/// A cache write in flight, awaited by `flush`.
+pub(crate) struct InFlight(());
+
+impl InFlight {
+    pub(crate) fn begin() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        Self(())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if IN_FLIGHT.fetch_sub(1, Ordering::SeqCst) == 1 {
+            FLUSHED.notify_waiters();
+        }
+    }
+}
+
+/// Wait for the in-flight cache writes to complete, e.g. before the process exits.
+pub async fn flush() {
+    loop {
+        let flushed = FLUSHED.notified();
+        tokio::pin!(flushed);
+        flushed.as_mut().enable();
+
+        if IN_FLIGHT.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        flushed.await;
+    }
+}
+
+/// A journaled cache write, replayed by `recover` when the process stopped before it completed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The cache key of the entry.
+    pub cache_key: String,
+    /// The request method.
+    pub method: String,
+    /// The response url.
+    pub url: String,
+    /// The response status.
+    pub status: u16,
+    /// The response http version.
+    pub http_version: HttpVersion,
+    /// The request headers.
+    pub request_headers: HashMap<String, String>,
+    /// The response headers.
+    pub response_headers: HashMap<String, String>,
+    /// Base64-encoded response body.
+    pub body_base64: String,
+}
+
+impl JournalEntry {
+    /// The journal entry of a cache write.
+    pub fn new(
+        cache_key: &str,
+        method: &str,
+        response: &HttpResponse,
+        request_headers: &HashMap<String, String>,
+    ) -> Self {
+        Self {
+            cache_key: cache_key.to_string(),
+            method: method.to_string(),
+            url: response.url.to_string(),
+            status: response.status,
+            http_version: response.version,
+            request_headers: request_headers.clone(),
+            response_headers: response.headers.clone(),
+            body_base64: general_purpose::STANDARD.encode(&response.body),
+        }
+    }
+
+    /// The response of the entry.
+    pub fn response(&self) -> Result<HttpResponse, String> {
+        Ok(HttpResponse {
+            body: general_purpose::STANDARD
+                .decode(&self.body_base64)
+                .map_err(|e| e.to_string())?,
+            headers: self.response_headers.clone(),
+            status: self.status,
+            url: url::Url::parse(&self.url).map_err(|e| e.to_string())?,
+            version: self.http_version,
+        })
+    }
+}
+
+/// Journal the cache write before it is stored, returning the journal file to remove once the
+/// write completed. `None` when journaling is disabled or the journal can not be written.
+pub(crate) async fn write_ahead(entry: &JournalEntry) -> Option<PathBuf> {
+    let dir = journal_dir()?;
+    let name = format!(
+        "{}-{}-{}",
+        hex::encode(&blake3::hash(entry.cache_key.as_bytes()).as_bytes()[..16]),
+        std::process::id(),
+        JOURNAL_SEQ.fetch_add(1, Ordering::Relaxed)
+    );
+    let path = dir.join(format!("{name}.wal"));
+    let tmp = dir.join(format!("{name}.tmp"));
+
+    let bytes = serde_json::to_vec(entry).map_err(std::io::Error::from);
+    let result = {
+        let (tmp, path) = (tmp.clone(), path.clone());
+
+        crate::runtime::unblock(move || {
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(&tmp, bytes?)?;
+            std::fs::File::open(&tmp)?.sync_all()?;
+            std::fs::rename(&tmp, &path)
+        })
+        .await
+    };
+
+    match result {
+        Ok(_) => Some(path),
+        Err(err) => {
+            tracing::warn!(
+                "cache journal write failed for {}: {}",
+                entry.cache_key,
+                err
+            );
+            let _ = crate::runtime::unblock(move || std::fs::remove_file(tmp)).await;
+            None
+        }
+    }
+}
+
+/// Remove the journal file of a completed cache write.
+pub(crate) async fn complete(path: Option<PathBuf>) {
+    if let Some(path) = path {
+        let removed = {
+            let path = path.clone();
+            crate::runtime::unblock(move || std::fs::remove_file(path)).await
+        };
+
+        if let Err(err) = removed {
+            tracing::debug!(
+                "cache journal remove failed for {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Replay the cache writes journaled before the process stopped, returning the amount of entries
+/// stored again. Call it on startup before the cache is used.
+pub async fn recover() -> usize {
+    match journal_dir() {
+        Some(dir) => recover_dir(&dir).await,
+        _ => 0,
+    }
+}
+
+/// Replay the journaled cache writes of the directory.
+async fn recover_dir(dir: &Path) -> usize {
+    let dir = dir.to_path_buf();
+
+    // list the journal first, the replayed writes are journaled again.
+    let journal = crate::runtime::unblock(move || {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return Vec::new();
+        };
+        let mut journal = Vec::new();
+
+        for path in entries.flatten().map(|file| file.path()) {
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("wal") => journal.push(path),
+                // an interrupted journal write, the cache write never started.
+                Some("tmp") => {
+                    let _ = std::fs::remove_file(&path);
+                }
+                _ => (),
+            }
+        }
+
+        journal
+    })
+    .await;
+
+    let mut recovered = 0;
+
+    for path in journal {
+        let read = {
+            let path = path.clone();
+            crate::runtime::unblock(move || std::fs::read(path)).await
+        };
+        let entry = read
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                serde_json::from_slice::<JournalEntry>(&bytes).map_err(|e| e.to_string())
+            })
+            .and_then(|entry| entry.response().map(|response| (entry, response)));
+
+        match entry {
+            Ok((entry, response)) => {
+                super::manager::put_hybrid_cache(
+                    &entry.cache_key,
+                    "",
+                    response,
+                    &entry.method,
+                    entry.request_headers,
+                    None,
+                )
+                .await;
+                recovered += 1;
+            }
+            Err(err) => {
+                tracing::warn!("cache journal entry {} is invalid: {}", path.display(), err);
+            }
+        }
+
+        let _ = crate::runtime::unblock(move || std::fs::remove_file(path)).await;
+    }
+
+    recovered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_entry_round_trip() {
+        let response = HttpResponse {
+            body: b"<html></html>".to_vec(),
+            headers: HashMap::from([("content-type".to_string(), "text/html".to_string())]),
+            status: 200,
+            url: url::Url::parse("https://example.com/").unwrap(),
+            version: HttpVersion::H2,
+        };
+
+        let entry = JournalEntry::new(
+            "GET:https://example.com/",
+            "GET",
+            &response,
+            &HashMap::new(),
+        );
+        let json = serde_json::to_vec(&entry).unwrap();
+        let restored = serde_json::from_slice::<JournalEntry>(&json)
+            .unwrap()
+            .response()
+            .unwrap();
+
+        assert_eq!(restored.body, response.body);
+        assert_eq!(restored.headers, response.headers);
+        assert_eq!(restored.version, HttpVersion::H2);
+    }
+
+    #[tokio::test]
+    async fn flush_waits_for_in_flight_writes() {
+        let write = InFlight::begin();
+
+        let flushed = crate::runtime::spawn(flush());
+        tokio::task::yield_now().await;
+        assert!(!flushed.is_finished());
+
+        drop(write);
+        flushed.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn recover_drops_the_interrupted_and_invalid_entries() {
+        let dir = std::env::temp_dir().join(format!("chromey-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.tmp"), b"{").unwrap();
+        std::fs::write(dir.join("b.wal"), b"not json").unwrap();
+
+        assert_eq!(recover_dir(&dir).await, 0);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use super::manager::{create_vary_cache_key, vary_headers};
+
+lazy_static::lazy_static! {
+    /// The request parts added to the cache keys, none until set.
+    static ref CACHE_KEY_OPTIONS: RwLock<Option<Arc<CacheKeyOptions>>> = RwLock::new(None);
+}
+
+/// The request a cache key is computed for.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheKeyRequest<'a> {
+    /// The key of the method, the url and the auth, see `create_cache_key_raw`.
+    pub base_key: &'a str,
+    /// The url of the request.
+    pub url: &'a str,
+    /// The method of the request.
+    pub method: &'a str,
+    /// The headers of the request.
+    pub request_headers: &'a HashMap<String, String>,
+}
+
+impl CacheKeyRequest<'_> {
+    /// The value of the request header, case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.request_headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim())
+    }
+}
+
+/// A user function computing the cache key of a request.
+pub type CacheKeyFn = Arc<dyn Fn(&CacheKeyRequest<'_>) -> String + Send + Sync>;
+
+/// The request parts of the cache keys, so responses varying by e.g. `Accept-Language` or the
+/// cookies of a session do not collide under the same method, url and auth.
+#[derive(Clone, Default)]
+pub struct CacheKeyOptions {
+    /// The request headers added to every key, e.g. `accept-language` or `cookie`.
+    pub headers: Vec<String>,
+    /// The function computing the keys, the headers are ignored when set.
+    pub key_fn: Option<CacheKeyFn>,
+}
+
+impl std::fmt::Debug for CacheKeyOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheKeyOptions")
+            .field("headers", &self.headers)
+            .field("key_fn", &self.key_fn.is_some())
+            .finish()
+    }
+}
+
+impl CacheKeyOptions {
+    /// Add the request header to the keys.
+    pub fn with_header(mut self, name: impl Into<String>) -> Self {
+        self.headers.push(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Compute the keys with the function.
+    pub fn with_key_fn(
+        mut self,
+        key_fn: impl Fn(&CacheKeyRequest<'_>) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.key_fn = Some(Arc::new(key_fn));
+        self
+    }
+
+    /// The cache key of the request.
+    pub fn key(&self, request: &CacheKeyRequest<'_>) -> String {
+        if let Some(key_fn) = self.key_fn.as_ref() {
+            return key_fn(request);
+        }
+
+        if self.headers.is_empty() {
+            return request.base_key.to_string();
+        }
+
+        let mut headers: Vec<String> = self
+            .headers
+            .iter()
+            .map(|name| name.to_ascii_lowercase())
+            .collect();
+        headers.sort();
+        headers.dedup();
+
+        let mut key = format!("{}|headers", request.base_key);
+
+        for name in headers {
+            key.push_str(&format!(
+                "|{name}={}",
+                request.header(&name).unwrap_or_default()
+            ));
+        }
+
+        key
+    }
+}
+
+/// Set the request parts of the cache keys of the local and the remote caches.
+pub fn set_cache_key_options(options: Option<CacheKeyOptions>) {
+    if let Ok(mut current) = CACHE_KEY_OPTIONS.write() {
+        *current = options.map(Arc::new);
+    }
+}
+
+/// The request parts of the cache keys.
+pub fn cache_key_options() -> Option<Arc<CacheKeyOptions>> {
+    CACHE_KEY_OPTIONS
+        .read()
+        .ok()
+        .and_then(|options| options.clone())
+}
+
+/// The cache key of the request with the configured request parts, the base key when none are
+/// set. Lookups without the request headers, e.g. `get_cached_url`, use the base key.
+pub fn request_cache_key(
+    base_key: &str,
+    url: &str,
+    method: &str,
+    request_headers: &HashMap<String, String>,
+) -> String {
+    match cache_key_options() {
+        Some(options) => options.key(&CacheKeyRequest {
+            base_key,
+            url,
+            method,
+            request_headers,
+        }),
+        _ => base_key.to_string(),
+    }
+}
+
+/// The key of the response in the remote cache: the request key, with the values of the request
+/// headers the response varies on.
+pub fn remote_cache_key(
+    base_key: &str,
+    url: &str,
+    method: &str,
+    request_headers: &HashMap<String, String>,
+    response_headers: &HashMap<String, String>,
+) -> String {
+    let key = request_cache_key(base_key, url, method, request_headers);
+    let vary = vary_headers(response_headers);
+
+    if vary.is_empty() || vary.iter().any(|name| name == "*") {
+        key
+    } else {
+        create_vary_cache_key(&key, &vary, request_headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn adds_the_request_headers() {
+        let options = CacheKeyOptions::default()
+            .with_header("Cookie")
+            .with_header("accept-language");
+        let request_headers = headers(&[("Accept-Language", "de-DE"), ("cookie", "sid=1")]);
+        let request = CacheKeyRequest {
+            base_key: "GET:https://example.com/",
+            url: "https://example.com/",
+            method: "GET",
+            request_headers: &request_headers,
+        };
+
+        assert_eq!(
+            options.key(&request),
+            "GET:https://example.com/|headers|accept-language=de-DE|cookie=sid=1"
+        );
+        assert_eq!(
+            CacheKeyOptions::default().key(&request),
+            "GET:https://example.com/"
+        );
+    }
+
+    #[test]
+    fn uses_the_key_fn() {
+        let options = CacheKeyOptions::default()
+            .with_header("cookie")
+            .with_key_fn(|request| {
+                format!(
+                    "{}|tenant={}",
+                    request.base_key,
+                    request.header("x-tenant").unwrap_or("none")
+                )
+            });
+        let request_headers = headers(&[("X-Tenant", "acme")]);
+
+        assert_eq!(
+            options.key(&CacheKeyRequest {
+                base_key: "GET:https://example.com/",
+                url: "https://example.com/",
+                method: "GET",
+                request_headers: &request_headers,
+            }),
+            "GET:https://example.com/|tenant=acme"
+        );
+    }
+
+    #[test]
+    fn remote_keys_respect_vary() {
+        let request_headers = headers(&[("Accept-Language", "fr")]);
+
+        assert_eq!(
+            remote_cache_key(
+                "GET:https://example.com/",
+                "https://example.com/",
+                "GET",
+                &request_headers,
+                &headers(&[("Vary", "Accept-Language")]),
+            ),
+            "GET:https://example.com/|vary|accept-language=fr"
+        );
+        assert_eq!(
+            remote_cache_key(
+                "GET:https://example.com/",
+                "https://example.com/",
+                "GET",
+                &request_headers,
+                &headers(&[("Vary", "*")]),
+            ),
+            "GET:https://example.com/"
+        );
+    }
+}
@@ -1,9 +1,13 @@
 pub use http_global_cache::CACACHE_MANAGER;
 
 use crate::http::{convert_headers, HttpRequestLike, HttpResponse, HttpResponseLike, HttpVersion};
+use crate::runtime::JoinHandle;
 use crate::{
     cdp::browser_protocol::{
-        fetch::{ContinueRequestParams, EventRequestPaused, FulfillRequestParams, HeaderEntry},
+        fetch::{
+            ContinueRequestParams, EventRequestPaused, FailRequestParams, FulfillRequestParams,
+            HeaderEntry,
+        },
         network::{EnableParams, EventResponseReceived, GetResponseBodyParams, ResourceType},
     },
     page::Page,
@@ -16,7 +20,6 @@ use reqwest::StatusCode;
 use spider_fingerprint::http;
 use std::collections::HashMap;
 use std::time::SystemTime;
-use tokio::task::JoinHandle;
 use tokio_stream::StreamExt;
 
 lazy_static::lazy_static! {
@@ -41,7 +44,17 @@ lazy_static::lazy_static! {
 
 /// Rewrite the initial base-tag.
 pub async fn rewrite_base_tag(html: &[u8], base_url: &Option<&str>) -> String {
-    use lol_html::{element, html_content::ContentType};
+    rewrite_base_tag_with_subresources(html, base_url, None).await
+}
+
+/// Rewrite the initial base-tag and, with `rewrite`, the relative `href`, `src`, `srcset` and css
+/// `url()` references of the document, so the cached html renders standalone without its origin.
+pub async fn rewrite_base_tag_with_subresources(
+    html: &[u8],
+    base_url: &Option<&str>,
+    rewrite: Option<&super::subresources::SubresourceRewrite>,
+) -> String {
+    use lol_html::{element, html_content::ContentType, text};
     use std::sync::OnceLock;
 
     if html.is_empty() {
@@ -50,78 +63,155 @@ pub async fn rewrite_base_tag(html: &[u8], base_url: &Option<&str>) -> String {
 
     let base_tag_inserted = OnceLock::new();
     let already_present = OnceLock::new();
+    // the `<base>` of the document the references resolve against.
+    let document_base: OnceLock<url::Url> = OnceLock::new();
+    let page_base = base_url.and_then(|base| url::Url::parse(base).ok());
+    let rewrite = rewrite.filter(|_| page_base.is_some());
 
     let base_url_len = base_url.map(|s| s.len());
 
-    let rewriter_settings: lol_html::Settings<'_, '_, lol_html::send::SendHandlerTypes> =
-        lol_html::send::Settings {
-            element_content_handlers: vec![
-                // Handler for <base> to mark if it is present with href
-                element!("base", {
-                    |el| {
-                        // check base tags that do not exist yet.
-                        if base_tag_inserted.get().is_none() {
-                            // Check if a <base> with href already exists
-                            if let Some(attr) = el.get_attribute("href") {
-                                let valid_http =
-                                    attr.starts_with("http://") || attr.starts_with("https://");
-
-                                // we can validate if the domain is the same if not to remove it.
-                                if valid_http {
-                                    let _ = base_tag_inserted.set(true);
-                                    let _ = already_present.set(true);
-                                } else {
-                                    el.remove();
-                                }
-                            } else {
-                                el.remove();
+    let mut element_content_handlers = vec![
+        // Handler for <base> to mark if it is present with href
+        element!("base", {
+            |el| {
+                // check base tags that do not exist yet.
+                if base_tag_inserted.get().is_none() {
+                    // Check if a <base> with href already exists
+                    if let Some(attr) = el.get_attribute("href") {
+                        let valid_http =
+                            attr.starts_with("http://") || attr.starts_with("https://");
+
+                        // we can validate if the domain is the same if not to remove it.
+                        if valid_http {
+                            if let Ok(base) = url::Url::parse(&attr) {
+                                let _ = document_base.set(base);
                             }
+                            let _ = base_tag_inserted.set(true);
+                            let _ = already_present.set(true);
+                        } else {
+                            el.remove();
                         }
+                    } else {
+                        el.remove();
+                    }
+                }
 
+                Ok(())
+            }
+        }),
+        // Handler for <head> to insert <base> tag if not present
+        element!("head", {
+            |el: &mut lol_html::send::Element<'_, '_>| {
+                if let Some(handlers) = el.end_tag_handlers() {
+                    let base_tag_inserted = base_tag_inserted.clone();
+                    let base_url = format!(r#"<base href="{}">"#, base_url.unwrap_or_default());
+
+                    handlers.push(Box::new(move |end| {
+                        if base_tag_inserted.get().is_none() {
+                            let _ = base_tag_inserted.set(true);
+                            end.before(&base_url, ContentType::Html);
+                        }
                         Ok(())
-                    }
-                }),
-                // Handler for <head> to insert <base> tag if not present
-                element!("head", {
-                    |el: &mut lol_html::send::Element<'_, '_>| {
-                        if let Some(handlers) = el.end_tag_handlers() {
-                            let base_tag_inserted = base_tag_inserted.clone();
-                            let base_url =
-                                format!(r#"<base href="{}">"#, base_url.unwrap_or_default());
-
-                            handlers.push(Box::new(move |end| {
-                                if base_tag_inserted.get().is_none() {
-                                    let _ = base_tag_inserted.set(true);
-                                    end.before(&base_url, ContentType::Html);
-                                }
-                                Ok(())
-                            }))
+                    }))
+                }
+                Ok(())
+            }
+        }),
+        // Handler for html if <head> not present to insert <head><base></head> tag if not present
+        element!("html", {
+            |el: &mut lol_html::send::Element<'_, '_>| {
+                if let Some(handlers) = el.end_tag_handlers() {
+                    let base_tag_inserted = base_tag_inserted.clone();
+                    let base_url = format!(
+                        r#"<head><base href="{}"></head>"#,
+                        base_url.unwrap_or_default()
+                    );
+
+                    handlers.push(Box::new(move |end| {
+                        if base_tag_inserted.get().is_none() {
+                            let _ = base_tag_inserted.set(true);
+                            end.before(&base_url, ContentType::Html);
                         }
                         Ok(())
-                    }
-                }),
-                // Handler for html if <head> not present to insert <head><base></head> tag if not present
-                element!("html", {
-                    |el: &mut lol_html::send::Element<'_, '_>| {
-                        if let Some(handlers) = el.end_tag_handlers() {
-                            let base_tag_inserted = base_tag_inserted.clone();
-                            let base_url = format!(
-                                r#"<head><base href="{}"></head>"#,
-                                base_url.unwrap_or_default()
-                            );
+                    }))
+                }
+                Ok(())
+            }
+        }),
+    ];
+
+    if let (Some(rewrite), Some(page_base)) = (rewrite, page_base.as_ref()) {
+        let document_base = &document_base;
 
-                            handlers.push(Box::new(move |end| {
-                                if base_tag_inserted.get().is_none() {
-                                    let _ = base_tag_inserted.set(true);
-                                    end.before(&base_url, ContentType::Html);
-                                }
-                                Ok(())
-                            }))
+        for (selector, attribute) in super::subresources::REFERENCE_ATTRIBUTES {
+            element_content_handlers.push(element!(
+                *selector,
+                move |el: &mut lol_html::send::Element<'_, '_>| {
+                    let base = document_base.get().unwrap_or(page_base);
+
+                    if let Some(value) = el.get_attribute(attribute) {
+                        if let Some(value) = rewrite.rewrite(base, &value) {
+                            let _ = el.set_attribute(attribute, &value);
                         }
-                        Ok(())
                     }
-                }),
-            ],
+                    Ok(())
+                }
+            ));
+        }
+
+        element_content_handlers.push(element!(
+            "img[srcset], source[srcset]",
+            move |el: &mut lol_html::send::Element<'_, '_>| {
+                let base = document_base.get().unwrap_or(page_base);
+
+                if let Some(value) = el.get_attribute("srcset") {
+                    if let Some(value) = rewrite.rewrite_srcset(base, &value) {
+                        let _ = el.set_attribute("srcset", &value);
+                    }
+                }
+                Ok(())
+            }
+        ));
+
+        element_content_handlers.push(element!(
+            "[style]",
+            move |el: &mut lol_html::send::Element<'_, '_>| {
+                let base = document_base.get().unwrap_or(page_base);
+
+                if let Some(value) = el.get_attribute("style") {
+                    if let Some(value) = rewrite.rewrite_css(base, &value) {
+                        let _ = el.set_attribute("style", &value);
+                    }
+                }
+                Ok(())
+            }
+        ));
+
+        // the text of a stylesheet may be split across chunks, rewrite it whole.
+        let mut css = String::new();
+
+        element_content_handlers.push(text!(
+            "style",
+            move |chunk: &mut lol_html::html_content::TextChunk<'_>| {
+                css.push_str(chunk.as_str());
+
+                if chunk.last_in_text_node() {
+                    let base = document_base.get().unwrap_or(page_base);
+                    let text = std::mem::take(&mut css);
+                    let text = rewrite.rewrite_css(base, &text).unwrap_or(text);
+
+                    chunk.replace(&text, ContentType::Html);
+                } else {
+                    chunk.remove();
+                }
+                Ok(())
+            }
+        ));
+    }
+
+    let rewriter_settings: lol_html::Settings<'_, '_, lol_html::send::SendHandlerTypes> =
+        lol_html::send::Settings {
+            element_content_handlers,
             ..lol_html::send::Settings::new_for_handler_types()
         };
 
@@ -142,8 +232,8 @@ pub async fn rewrite_base_tag(html: &[u8], base_url: &Option<&str>) -> String {
     let mut wrote_error = false;
 
     while let Some(chunk) = stream.next().await {
-        // early exist
-        if already_present.get().is_some() {
+        // early exist, the references are rewritten past the base tag.
+        if rewrite.is_none() && already_present.get().is_some() {
             break;
         }
         if rewriter.write(chunk).is_err() {
@@ -156,7 +246,7 @@ pub async fn rewrite_base_tag(html: &[u8], base_url: &Option<&str>) -> String {
         let _ = rewriter.end();
     }
 
-    if already_present.get().is_some() {
+    if rewrite.is_none() && already_present.get().is_some() {
         std::str::from_utf8(&html).unwrap_or_default().into()
     } else {
         auto_encoder::auto_encode_bytes(&buffer)
@@ -183,12 +273,7 @@ fn hash_key_v1(s: &str) -> String {
 
 /// Site/page grouping key (used for site:{site_key}::{resource_key})
 pub fn create_site_key(target_url: &str, auth: Option<&str>, method: Option<&str>) -> String {
-    let normalized = url::Url::parse(target_url)
-        .map(|mut u| {
-            u.set_fragment(None);
-            u.to_string()
-        })
-        .unwrap_or_else(|_| target_url.to_string());
+    let normalized = crate::urlnorm::without_fragment(target_url);
 
     // If you want method-specific site groups, set method=Some("POST") etc.
     // If you don't, pass None and it won't fragment.
@@ -202,26 +287,100 @@ pub fn create_site_key(target_url: &str, auth: Option<&str>, method: Option<&str
     hash_key_v1(&raw)
 }
 
-/// Get a cached url from the hybrid cache.
+/// Get a cached url from the hybrid cache. The request headers are not known, so the last stored
+/// variant of a response with a `Vary` header is used, see `get_cached_url_with_request_headers`.
 pub async fn get_cached_url(target_url: &str, auth_opt: Option<&str>) -> Option<Vec<u8>> {
     let cache_url = create_cache_key_raw(target_url, None, auth_opt.as_deref());
 
-    let result = tokio::time::timeout(std::time::Duration::from_millis(60), async {
+    let result = crate::runtime::timeout(std::time::Duration::from_millis(60), async {
         CACACHE_MANAGER.get(&cache_url).await
     })
     .await;
 
     if let Ok(cached) = result {
         if let Ok(Some((http_response, cache_policy))) = cached {
-            if !cache_policy.is_stale(SystemTime::now()) {
-                return Some(http_response.body);
+            if !super::freshness::is_stale(Some(target_url), &cache_policy, SystemTime::now()) {
+                super::freshness::touch(&cache_url);
+                let body = super::compression::decompress_response(http_response)
+                    .map(|http_response| http_response.body);
+
+                if let Some(body) = body.as_ref() {
+                    super::stats::cache_stats().record_hit(body.len(), false);
+                }
+
+                return body;
             }
         }
     }
 
+    super::stats::cache_stats().record_miss();
+
     None
 }
 
+/// Request headers a `Vary` on is ignored, the stored bodies are already decoded.
+const IGNORED_VARY_HEADERS: [&str; 1] = ["accept-encoding"];
+
+/// The request header names the response varies on, lowercased and sorted. Contains `*` when the
+/// response can not be reused.
+pub fn vary_headers(response_headers: &HashMap<String, String>) -> Vec<String> {
+    let mut vary: Vec<String> = response_headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("vary"))
+        .flat_map(|(_, value)| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty() && !IGNORED_VARY_HEADERS.contains(&name.as_str()))
+        .collect();
+
+    vary.sort();
+    vary.dedup();
+    vary
+}
+
+/// The cache key of the variant of a response selected by the request headers it varies on.
+pub fn create_vary_cache_key(
+    cache_key: &str,
+    vary: &[String],
+    request_headers: &HashMap<String, String>,
+) -> String {
+    let mut key = format!("{cache_key}|vary");
+
+    for name in vary {
+        let value = request_headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default();
+
+        key.push_str(&format!("|{name}={value}"));
+    }
+
+    key
+}
+
+/// Get an entry of the local cache.
+async fn get_cache_entry(
+    cache_key: &str,
+) -> Option<(
+    http_cache_reqwest::HttpResponse,
+    http_cache_semantics::CachePolicy,
+)> {
+    let (http_response, policy) =
+        crate::runtime::timeout(std::time::Duration::from_millis(250), async {
+            CACACHE_MANAGER.get(cache_key).await
+        })
+        .await
+        .ok()?
+        .ok()??;
+
+    super::freshness::touch(cache_key);
+
+    Some((
+        super::compression::decompress_response(http_response)?,
+        policy,
+    ))
+}
+
 /// Basic cache policy.
 #[derive(Debug, Default, Clone)]
 pub enum BasicCachePolicy {
@@ -238,17 +397,146 @@ impl BasicCachePolicy {
     /// Decide whether a cached entry is usable right now.
     #[inline]
     pub fn allows_cached(&self, cache_policy: &http_cache_semantics::CachePolicy) -> bool {
+        self.allows_cached_url(None, cache_policy)
+    }
+
+    /// Decide whether the cached entry of the url is usable right now, with the ttl of its host
+    /// when freshness overrides are set, see `crate::cache::set_freshness_policy`.
+    pub fn allows_cached_url(
+        &self,
+        url: Option<&str>,
+        cache_policy: &http_cache_semantics::CachePolicy,
+    ) -> bool {
         match self {
             // caller accepts staleness
             BasicCachePolicy::AllowStale => true,
             // use injected time for determinism/testing
-            BasicCachePolicy::Period(now) => !cache_policy.is_stale(*now),
+            BasicCachePolicy::Period(now) => !super::freshness::is_stale(url, cache_policy, *now),
             // default behavior: must not be stale at real "now"
-            BasicCachePolicy::Normal => !cache_policy.is_stale(SystemTime::now()),
+            BasicCachePolicy::Normal => {
+                !super::freshness::is_stale(url, cache_policy, SystemTime::now())
+            }
+        }
+    }
+
+    /// Decide whether a cached entry is usable for the request, matching the request headers the
+    /// stored response varies on.
+    pub fn allows_cached_request(
+        &self,
+        cache_policy: &http_cache_semantics::CachePolicy,
+        request: &HttpRequestLike,
+    ) -> bool {
+        use http_cache_semantics::BeforeRequest;
+
+        let now = match self {
+            BasicCachePolicy::AllowStale => return true,
+            BasicCachePolicy::Period(now) => *now,
+            BasicCachePolicy::Normal => SystemTime::now(),
+        };
+
+        match super::freshness::freshness_policy() {
+            Some(overrides) => {
+                let matches = match cache_policy.before_request(request, now) {
+                    BeforeRequest::Fresh(_) => true,
+                    BeforeRequest::Stale { matches, .. } => matches,
+                };
+
+                matches && !overrides.is_stale(Some(&request.uri.to_string()), cache_policy, now)
+            }
+            _ => matches!(
+                cache_policy.before_request(request, now),
+                BeforeRequest::Fresh(_)
+            ),
         }
     }
 }
 
+/// Get a cached url with headers for the request headers. When the stored response has a `Vary`
+/// header, the variant stored for the same values of the varying request headers is used.
+pub async fn get_cached_url_with_request_headers(
+    target_url: &str,
+    auth_opt: Option<&str>,
+    policy: Option<&BasicCachePolicy>,
+    request_headers: &HashMap<String, String>,
+) -> Option<(Vec<u8>, HashMap<String, String>)> {
+    get_cached_variant(target_url, auth_opt, policy, request_headers)
+        .await
+        .map(|(body, headers, _)| (body, headers))
+}
+
+/// Get the cached variant of the request headers, with whether the served entry is stale.
+async fn get_cached_variant(
+    target_url: &str,
+    auth_opt: Option<&str>,
+    policy: Option<&BasicCachePolicy>,
+    request_headers: &HashMap<String, String>,
+) -> Option<(Vec<u8>, HashMap<String, String>, bool)> {
+    let base_key = create_cache_key_raw(target_url, None, auth_opt);
+    let cache_key =
+        super::keys::request_cache_key(&base_key, target_url, DEFAULT_METHOD, request_headers);
+    let (http_response, stored_policy) = get_cache_entry(&cache_key).await?;
+    let vary = vary_headers(&http_response.headers);
+    let policy = policy.cloned().unwrap_or_default();
+
+    if vary.is_empty() {
+        let stale = super::freshness::is_stale(Some(target_url), &stored_policy, SystemTime::now());
+
+        return policy
+            .allows_cached_url(Some(target_url), &stored_policy)
+            .then_some((http_response.body, http_response.headers, stale));
+    }
+
+    if vary.iter().any(|name| name == "*") {
+        return None;
+    }
+
+    let (http_response, stored_policy) =
+        get_cache_entry(&create_vary_cache_key(&cache_key, &vary, request_headers)).await?;
+
+    let request = HttpRequestLike {
+        uri: target_url.parse().ok()?,
+        method: http::method::Method::GET,
+        headers: convert_headers(request_headers),
+    };
+
+    let stale = !BasicCachePolicy::Normal.allows_cached_request(&stored_policy, &request);
+
+    policy
+        .allows_cached_request(&stored_policy, &request)
+        .then_some((http_response.body, http_response.headers, stale))
+}
+
+/// Get the stored entry of the request headers regardless of its freshness, with the key it is
+/// stored under, to revalidate it.
+async fn get_stale_entry(
+    target_url: &str,
+    auth_opt: Option<&str>,
+    request_headers: &HashMap<String, String>,
+) -> Option<(
+    String,
+    http_cache_reqwest::HttpResponse,
+    http_cache_semantics::CachePolicy,
+)> {
+    let base_key = create_cache_key_raw(target_url, None, auth_opt);
+    let cache_key =
+        super::keys::request_cache_key(&base_key, target_url, DEFAULT_METHOD, request_headers);
+    let (http_response, stored_policy) = get_cache_entry(&cache_key).await?;
+    let vary = vary_headers(&http_response.headers);
+
+    if vary.is_empty() {
+        return Some((cache_key, http_response, stored_policy));
+    }
+
+    if vary.iter().any(|name| name == "*") {
+        return None;
+    }
+
+    let vary_key = create_vary_cache_key(&cache_key, &vary, request_headers);
+    let (http_response, stored_policy) = get_cache_entry(&vary_key).await?;
+
+    Some((vary_key, http_response, stored_policy))
+}
+
 /// Get a cached url with headers.
 pub async fn get_cached_url_with_metadata(
     target_url: &str,
@@ -257,21 +545,22 @@ pub async fn get_cached_url_with_metadata(
 ) -> Option<(Vec<u8>, HashMap<String, String>)> {
     let cache_key = create_cache_key_raw(target_url, None, auth_opt.as_deref());
 
-    let result = tokio::time::timeout(std::time::Duration::from_millis(250), async {
+    let result = crate::runtime::timeout(std::time::Duration::from_millis(250), async {
         CACACHE_MANAGER.get(&cache_key).await
     })
     .await;
 
     if let Ok(cached) = result {
         if let Ok(Some((http_response, stored_policy))) = cached {
-            let allow = match policy {
-                Some(BasicCachePolicy::AllowStale) => true,
-                Some(BasicCachePolicy::Period(now)) => !stored_policy.is_stale(*now),
-                _ => !stored_policy.is_stale(SystemTime::now()),
-            };
+            let allow = policy
+                .cloned()
+                .unwrap_or_default()
+                .allows_cached_url(Some(target_url), &stored_policy);
 
             if allow {
-                return Some((http_response.body, http_response.headers));
+                super::freshness::touch(&cache_key);
+                return super::compression::decompress_response(http_response)
+                    .map(|http_response| (http_response.body, http_response.headers));
             }
         }
     }
@@ -279,6 +568,30 @@ pub async fn get_cached_url_with_metadata(
     None
 }
 
+/// The response header the content fingerprint of the cached documents is stored in, see
+/// `crate::diff::content_fingerprint`.
+pub const CONTENT_FINGERPRINT_HEADER: &str = "x-chromey-content-fingerprint";
+
+/// Get the content fingerprint stored with the cached document, stale entries included. A
+/// scheduler comparing it with the fingerprint of a new response can skip reprocessing an
+/// unchanged page.
+pub async fn get_cached_content_fingerprint(
+    target_url: &str,
+    auth_opt: Option<&str>,
+) -> Option<String> {
+    let (body, mut headers) =
+        get_cached_url_with_metadata(target_url, auth_opt, Some(&BasicCachePolicy::AllowStale))
+            .await?;
+
+    headers.remove(CONTENT_FINGERPRINT_HEADER).or_else(|| {
+        // documents cached before the fingerprint was stored.
+        Some(crate::diff::content_fingerprint(
+            &String::from_utf8_lossy(&body),
+            &Default::default(),
+        ))
+    })
+}
+
 /// Store the page to cache to be re-used across HTTP request.
 /// Store the page to the local HTTP cache (CACACHE_MANAGER) and,
 /// optionally, dump it to the remote hybrid cache server.
@@ -296,6 +609,30 @@ pub async fn put_hybrid_cache(
     use http_cache_reqwest::CacheManager;
     use http_cache_semantics::CachePolicy;
 
+    let vary = vary_headers(&http_response.headers);
+
+    // the response can not be reused for other requests.
+    if vary.iter().any(|name| name == "*") {
+        return;
+    }
+
+    // the key with the request parts of the cache key options, looked up with request headers.
+    let request_key = super::keys::request_cache_key(
+        cache_key,
+        http_response.url.as_str(),
+        method,
+        &http_request_headers,
+    );
+
+    let _in_flight = super::journal::InFlight::begin();
+    let journal = super::journal::write_ahead(&super::journal::JournalEntry::new(
+        cache_key,
+        method,
+        &http_response,
+        &http_request_headers,
+    ))
+    .await;
+
     // We need to do everything that only borrows `http_response` *before*
     // we move it into CACACHE_MANAGER::put.
     if let Ok(u) = http_response.url.as_str().parse::<http::uri::Uri>() {
@@ -303,13 +640,13 @@ pub async fn put_hybrid_cache(
             uri: u,
             method: http::method::Method::from_bytes(method.as_bytes())
                 .unwrap_or(http::method::Method::GET),
-            headers: convert_headers(&http_response.headers),
+            headers: convert_headers(&http_request_headers),
         };
 
         let res = HttpResponseLike {
             status: StatusCode::from_u16(http_response.status)
                 .unwrap_or(StatusCode::EXPECTATION_FAILED),
-            headers: convert_headers(&http_request_headers),
+            headers: convert_headers(&http_response.headers),
         };
 
         let policy = CachePolicy::new(&req, &res);
@@ -318,7 +655,7 @@ pub async fn put_hybrid_cache(
 
         if dump_remote.is_some() {
             // check if the value is in the cache and not stale to dump it
-            let result = tokio::time::timeout(std::time::Duration::from_millis(250), async {
+            let result = crate::runtime::timeout(std::time::Duration::from_millis(250), async {
                 CACACHE_MANAGER.get(&cache_key).await
             })
             .await;
@@ -326,8 +663,12 @@ pub async fn put_hybrid_cache(
             let mut put_cache = false;
 
             if let Ok(cached) = result {
-                if let Ok(Some((_http_response, stored_policy))) = cached {
-                    if stored_policy.is_stale(SystemTime::now()) {
+                if let Ok(Some((http_response, stored_policy))) = cached {
+                    if super::freshness::is_stale(
+                        Some(http_response.url.as_str()),
+                        &stored_policy,
+                        SystemTime::now(),
+                    ) {
                         put_cache = true;
                     }
                 }
@@ -345,7 +686,13 @@ pub async fn put_hybrid_cache(
                 // insert the item into the cache.
                 if !cached {
                     let job = super::dump_remote::DumpJob {
-                        cache_key: cache_key.to_string(),
+                        cache_key: super::keys::remote_cache_key(
+                            cache_key,
+                            &url,
+                            &method,
+                            &http_request_headers,
+                            &http_response.headers,
+                        ),
                         cache_site: cache_site.to_string(),
                         url: url,
                         method: method,
@@ -353,6 +700,7 @@ pub async fn put_hybrid_cache(
                         request_headers: http_request_headers.clone(),
                         response_headers: http_response.headers.clone(),
                         body: http_response.body.clone(),
+                        content_encoding: None,
                         http_version: http_response.version.clone(),
                         dump_remote: dump_remote.map(|s| s.to_string()),
                     };
@@ -375,20 +723,63 @@ pub async fn put_hybrid_cache(
             }
         }
 
+        let mut http_response = http_cache_reqwest::HttpResponse {
+            url: http_response.url,
+            body: http_response.body,
+            headers: http_response.headers,
+            version: http_response.version.into(),
+            status: http_response.status,
+        };
+
+        super::compression::compress_response(&mut http_response);
+
+        let mut stored = true;
+        let size = http_response.body.len();
+
+        // Store the variant of content negotiated responses under the varying request headers.
+        if !vary.is_empty() {
+            let vary_key = create_vary_cache_key(&request_key, &vary, &http_request_headers);
+
+            if let Err(err) = CACACHE_MANAGER
+                .put(vary_key.clone(), http_response.clone(), policy.clone())
+                .await
+            {
+                super::journal::report_write_error(&vary_key, err);
+                stored = false;
+            } else {
+                super::freshness::record_put(&vary_key, size).await;
+            }
+        }
+
+        if request_key != cache_key {
+            if let Err(err) = CACACHE_MANAGER
+                .put(request_key.clone(), http_response.clone(), policy.clone())
+                .await
+            {
+                super::journal::report_write_error(&request_key, err);
+                stored = false;
+            } else {
+                super::freshness::record_put(&request_key, size).await;
+            }
+        }
+
         // Finally, store in your existing local cache.
-        let _ = CACACHE_MANAGER
-            .put(
-                cache_key.into(),
-                http_cache_reqwest::HttpResponse {
-                    url: http_response.url,
-                    body: http_response.body,
-                    headers: http_response.headers,
-                    version: http_response.version.into(),
-                    status: http_response.status,
-                },
-                policy,
-            )
-            .await;
+        if let Err(err) = CACACHE_MANAGER
+            .put(cache_key.into(), http_response, policy)
+            .await
+        {
+            super::journal::report_write_error(cache_key, err);
+            stored = false;
+        } else {
+            super::freshness::record_put(cache_key, size).await;
+        }
+
+        // keep the journal of failed writes to retry them on recovery.
+        if stored {
+            super::journal::complete(journal).await;
+        }
+    } else {
+        super::journal::complete(journal).await;
     }
 }
 
@@ -404,7 +795,7 @@ pub async fn spawn_response_cache_listener(
     page.execute(EnableParams::default()).await?;
     let mut events = page.event_listener::<EventResponseReceived>().await?;
 
-    let handle = tokio::spawn(async move {
+    let handle = crate::runtime::spawn(async move {
         while let Some(ev) = events.next().await {
             if let Err(err) = handle_single_response(
                 &page,
@@ -425,7 +816,7 @@ pub async fn spawn_response_cache_listener(
 }
 
 /// Convert CDP Headers into a plain HashMap<String, String>
-fn headers_to_string_map(
+pub(crate) fn headers_to_string_map(
     headers: &crate::cdp::browser_protocol::network::Headers,
 ) -> HashMap<String, String> {
     let mut out = HashMap::new();
@@ -453,6 +844,9 @@ pub enum CacheStrategy {
     Scraping,
     /// Caching for screenshots.
     Screenshots,
+    /// Deterministic replays: every request is served from the local cache, stale entries
+    /// included, and a miss is answered with a synthetic `504` without touching the network.
+    OfflineOnly,
 }
 
 /// Allow the resource to be cached?
@@ -470,22 +864,17 @@ pub fn allow_cache_response(
     );
 
     // Only cache real network responses, and under Scraping skip media-like assets.
-    if strategy == CacheStrategy::Scraping {
-        !is_data && !is_media_like
-    } else {
-        !is_data
+    match strategy {
+        CacheStrategy::Scraping => !is_data && !is_media_like,
+        CacheStrategy::Screenshots => !is_data,
+        // every request is replayed from the cache.
+        CacheStrategy::OfflineOnly => true,
     }
 }
 
 /// Get the site key for target url.
 pub fn site_key_for_target_url(target_url: &str, auth: Option<&str>) -> String {
-    let normalized = match url::Url::parse(target_url) {
-        Ok(mut u) => {
-            u.set_fragment(None);
-            u.to_string()
-        }
-        Err(_) => target_url.to_string(),
-    };
+    let normalized = crate::urlnorm::without_fragment(target_url);
     let input = format!("v1|url={}|auth={}", normalized, auth.unwrap_or(""));
     hex::encode(blake3::hash(input.as_bytes()).as_bytes()) // 64 hex chars, path-safe
 }
@@ -502,7 +891,8 @@ async fn handle_single_response(
     cache_strategy: Option<CacheStrategy>,
     dump_remote: Option<&str>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    if !ev.response.url.starts_with("http") {
+    // offline replays never record, the responses come from the cache.
+    if !ev.response.url.starts_with("http") || cache_strategy == Some(CacheStrategy::OfflineOnly) {
         return Ok(());
     }
 
@@ -511,7 +901,8 @@ async fn handle_single_response(
     let eligible_for_cache =
         document_resource || allow_cache_response(&ev.r#type, cache_strategy.as_ref());
 
-    if !eligible_for_cache || ev.response.encoded_data_length == 0.0 {
+    // partial bodies are served from the complete cached responses.
+    if !eligible_for_cache || ev.response.encoded_data_length == 0.0 || ev.response.status == 206 {
         return Ok(());
     }
 
@@ -533,7 +924,17 @@ async fn handle_single_response(
             body_ret.body.clone().into_bytes()
         };
 
-        let resp_headers: HashMap<String, String> = headers_to_string_map(&ev.response.headers);
+        let mut resp_headers: HashMap<String, String> = headers_to_string_map(&ev.response.headers);
+
+        if document_resource {
+            resp_headers.insert(
+                CONTENT_FINGERPRINT_HEADER.into(),
+                crate::diff::content_fingerprint(
+                    &String::from_utf8_lossy(&body_bytes),
+                    &Default::default(),
+                ),
+            );
+        }
 
         let req_headers: HashMap<String, String> = ev
             .response
@@ -550,7 +951,13 @@ async fn handle_single_response(
             _ => HttpVersion::Http11,
         };
 
-        let cache_key = create_cache_key_raw(url.as_str(), Some(DEFAULT_METHOD), auth);
+        let cache_key = super::keys::remote_cache_key(
+            &create_cache_key_raw(url.as_str(), Some(DEFAULT_METHOD), auth),
+            url.as_str(),
+            DEFAULT_METHOD,
+            &req_headers,
+            &resp_headers,
+        );
 
         let job = super::dump_remote::DumpJob {
             cache_key: cache_key,
@@ -561,6 +968,7 @@ async fn handle_single_response(
             request_headers: req_headers,
             response_headers: resp_headers,
             body: body_bytes,
+            content_encoding: None,
             http_version: version,
             dump_remote: dump_remote.map(|s| s.to_string()),
         };
@@ -593,9 +1001,32 @@ pub async fn spawn_fetch_cache_interceptor(
     policy: Option<BasicCachePolicy>,
     cache_strategy: Option<CacheStrategy>,
 ) -> Result<JoinHandle<()>, crate::error::CdpError> {
-    page.send_command(crate::cdp::browser_protocol::fetch::EnableParams {
-        handle_auth_requests: Some(false),
-        patterns: Some(vec![
+    spawn_fetch_cache_interceptor_with_remote(page, auth, policy, cache_strategy, None).await
+}
+
+/// Spawn the fetch cache interceptor, querying the remote cache per request on a local miss
+/// when `read_through` is set instead of seeding the whole site up front.
+pub async fn spawn_fetch_cache_interceptor_with_remote(
+    page: Page,
+    auth: Option<String>,
+    policy: Option<BasicCachePolicy>,
+    cache_strategy: Option<CacheStrategy>,
+    mut read_through: Option<super::read_through::RemoteReadThrough>,
+) -> Result<JoinHandle<()>, crate::error::CdpError> {
+    // the pages of an offline browser context replay without a strategy of their own.
+    let cache_strategy = match cache_strategy {
+        None if super::offline::is_offline_page(&page).await => Some(CacheStrategy::OfflineOnly),
+        strategy => strategy,
+    };
+
+    let patterns = if cache_strategy == Some(CacheStrategy::OfflineOnly) {
+        vec![RequestPattern {
+            resource_type: None,
+            request_stage: Some(RequestStage::Request),
+            url_pattern: Some("*".into()),
+        }]
+    } else {
+        vec![
             RequestPattern {
                 resource_type: Some(ResourceType::Document),
                 request_stage: Some(RequestStage::Request),
@@ -606,13 +1037,20 @@ pub async fn spawn_fetch_cache_interceptor(
                 request_stage: Some(RequestStage::Request),
                 url_pattern: Some("*".into()),
             },
-        ]),
+        ]
+    };
+
+    page.send_command(crate::cdp::browser_protocol::fetch::EnableParams {
+        handle_auth_requests: Some(false),
+        patterns: Some(patterns),
     })
     .await?;
 
     let mut events = page.event_listener::<EventRequestPaused>().await?;
 
-    let handle = tokio::spawn(async move {
+    let handle = crate::runtime::spawn(async move {
+        let mut revalidations = super::revalidate::Revalidations::default();
+
         while let Some(ev) = events.next().await {
             if let Err(err) = handle_fetch_paused(
                 &page,
@@ -620,6 +1058,8 @@ pub async fn spawn_fetch_cache_interceptor(
                 auth.as_deref(),
                 policy.as_ref(),
                 cache_strategy.as_ref(),
+                read_through.as_mut(),
+                &mut revalidations,
             )
             .await
             {
@@ -639,8 +1079,17 @@ async fn handle_fetch_paused(
     auth: Option<&str>,
     policy: Option<&BasicCachePolicy>,
     cache_strategy: Option<&CacheStrategy>,
+    read_through: Option<&mut super::read_through::RemoteReadThrough>,
+    revalidations: &mut super::revalidate::Revalidations,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let current_url = ev.request.url.as_str();
+    let offline = cache_strategy == Some(&CacheStrategy::OfflineOnly);
+    // offline replays serve whatever was recorded.
+    let policy = if offline {
+        Some(&BasicCachePolicy::AllowStale)
+    } else {
+        policy
+    };
 
     let eligible_for_cache = allow_cache_response(&ev.resource_type, cache_strategy.as_deref());
 
@@ -651,35 +1100,228 @@ async fn handle_fetch_paused(
     }
 
     if ev.response_status_code.is_some() || ev.response_error_reason.is_some() {
-        let params = ContinueRequestParams::new(ev.request_id.clone());
-        page.send_command(params).await?;
+        let revalidation = revalidations.take(ev.request_id.as_ref());
+
+        // the stored body is still current, only its headers and policy are refreshed.
+        if let Some(revalidation) = revalidation.filter(|_| ev.response_status_code == Some(304)) {
+            let response_headers: HashMap<String, String> = ev
+                .response_headers
+                .iter()
+                .flatten()
+                .map(|header| (header.name.clone(), header.value.clone()))
+                .collect();
+            let (body, metadata) = revalidation.not_modified(&response_headers).await;
+
+            tracing::debug!("Cache REVALIDATED: {}", current_url);
+            super::stats::cache_stats().record_hit(body.len(), false);
+
+            let request_headers = headers_to_string_map(&ev.request.headers);
+            fulfill_from_cache(page, ev, &request_headers, 200, &body, &metadata).await?;
+        } else {
+            let params = ContinueRequestParams::new(ev.request_id.clone());
+            page.send_command(params).await?;
+        }
+
         return Ok(());
     }
 
-    if let Some((body, metadata)) =
-        get_cached_url_with_metadata(&current_url, auth.as_deref(), policy).await
-    {
-        tracing::debug!("Cache HIT: {}", current_url);
-        let mut resp_headers = Vec::<HeaderEntry>::with_capacity(metadata.len());
-
-        for (key, val) in metadata.iter() {
-            resp_headers.push(HeaderEntry {
-                name: key.into(),
-                value: val.into(),
-            });
+    if let Some(outcome) = super::negative::get_failure(current_url) {
+        tracing::debug!("Negative cache HIT: {} - {:?}", current_url, outcome);
+        match outcome {
+            super::negative::NegativeOutcome::Status(status) => {
+                let params = FulfillRequestParams::new(ev.request_id.clone(), status as i64);
+                page.send_command(params).await?;
+            }
+            super::negative::NegativeOutcome::DnsFailure => {
+                let params = FailRequestParams::new(
+                    ev.request_id.clone(),
+                    crate::cdp::browser_protocol::network::ErrorReason::NameNotResolved,
+                );
+                page.send_command(params).await?;
+            }
         }
+        return Ok(());
+    }
 
-        let mut params = FulfillRequestParams::new(ev.request_id.clone(), 200);
-
-        params.body = Some(general_purpose::STANDARD.encode(&body).into());
-        params.response_headers = Some(resp_headers);
+    let request_headers = headers_to_string_map(&ev.request.headers);
+
+    let mut stale = false;
+    let mut cached = get_cached_variant(&current_url, auth.as_deref(), policy, &request_headers)
+        .await
+        .map(|(body, metadata, stale_entry)| {
+            stale = stale_entry;
+            (body, metadata, 200)
+        });
+
+    // the remote cache only stores GET responses.
+    if cached.is_none() && !offline && ev.request.method == DEFAULT_METHOD {
+        if let Some(read_through) = read_through {
+            cached = read_through.lookup(current_url, auth).await;
+            if cached.is_some() {
+                tracing::debug!("Remote cache HIT: {}", current_url);
+            }
+        }
+    }
 
-        page.send_command(params).await?;
+    if let Some((body, metadata, status)) = cached {
+        tracing::debug!("Cache HIT: {}", current_url);
+        super::stats::cache_stats().record_hit(body.len(), stale);
+        fulfill_from_cache(page, ev, &request_headers, status, &body, &metadata).await?;
     } else {
         tracing::debug!("Cache MISS: {}, continuing request", current_url);
-        let params = ContinueRequestParams::new(ev.request_id.clone());
-        page.send_command(params).await?;
+        super::stats::cache_stats().record_miss();
+
+        let stale_entry = if !offline
+            && ev.request.method == DEFAULT_METHOD
+            && super::revalidate::revalidation_enabled()
+        {
+            get_stale_entry(current_url, auth, &request_headers).await
+        } else {
+            None
+        };
+
+        let conditional = stale_entry
+            .as_ref()
+            .map(|(_, response, _)| super::revalidate::conditional_headers(&response.headers))
+            .unwrap_or_default();
+
+        if offline {
+            page.send_command(super::offline::offline_miss(ev.request_id.clone()))
+                .await?;
+        } else if let Some((cache_key, response, policy)) =
+            stale_entry.filter(|_| !conditional.is_empty())
+        {
+            tracing::debug!("Cache REVALIDATE: {}", current_url);
+
+            // the page's own conditional headers are replaced with the validators of the entry.
+            let mut headers: Vec<HeaderEntry> = request_headers
+                .iter()
+                .filter(|(name, _)| {
+                    !name.eq_ignore_ascii_case("if-none-match")
+                        && !name.eq_ignore_ascii_case("if-modified-since")
+                })
+                .map(|(name, value)| HeaderEntry {
+                    name: name.clone(),
+                    value: value.clone(),
+                })
+                .collect();
+
+            headers.extend(
+                conditional
+                    .into_iter()
+                    .map(|(name, value)| HeaderEntry { name, value }),
+            );
+
+            let mut params = ContinueRequestParams::new(ev.request_id.clone());
+            params.headers = Some(headers);
+            params.intercept_response = Some(true);
+
+            revalidations.insert(
+                ev.request_id.as_ref(),
+                super::revalidate::PendingRevalidation {
+                    cache_key,
+                    url: current_url.to_string(),
+                    request_headers: request_headers.clone(),
+                    response,
+                    policy,
+                },
+            );
+
+            page.send_command(params).await?;
+        } else {
+            let params = ContinueRequestParams::new(ev.request_id.clone());
+            page.send_command(params).await?;
+        }
     }
 
     Ok(())
 }
+
+/// Fulfill the paused request with the cached body, or the requested range of it.
+async fn fulfill_from_cache(
+    page: &Page,
+    ev: &EventRequestPaused,
+    request_headers: &HashMap<String, String>,
+    status: u16,
+    body: &[u8],
+    metadata: &HashMap<String, String>,
+) -> Result<(), crate::error::CdpError> {
+    let range = super::range::find_header(request_headers, "range");
+    let (status, body, metadata) = super::range::range_response(range, status, body, metadata);
+    let mut resp_headers = Vec::<HeaderEntry>::with_capacity(metadata.len());
+
+    for (key, val) in metadata.iter() {
+        resp_headers.push(HeaderEntry {
+            name: key.into(),
+            value: val.into(),
+        });
+    }
+
+    let mut params = FulfillRequestParams::new(ev.request_id.clone(), status as i64);
+
+    params.body = Some(general_purpose::STANDARD.encode(body).into());
+    params.response_headers = Some(resp_headers);
+
+    page.send_command(params).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rewrites_the_subresources() {
+        let html = br##"<html><head><base href="https://cdn.example.com/v2/"><style>body{background:url(bg.png)}</style></head><body><a href="/about">a</a><img src="logo.png" srcset="logo.png 1x, logo@2x.png 2x"><a href="#top">t</a></body></html>"##;
+
+        let rewritten = rewrite_base_tag_with_subresources(
+            html,
+            &Some("https://example.com/page"),
+            Some(&crate::cache::SubresourceRewrite::Absolute),
+        )
+        .await;
+
+        assert!(rewritten.contains(r#"url("https://cdn.example.com/v2/bg.png")"#));
+        assert!(rewritten.contains(r#"href="https://cdn.example.com/about""#));
+        assert!(rewritten.contains(r#"src="https://cdn.example.com/v2/logo.png""#));
+        assert!(rewritten.contains("https://cdn.example.com/v2/logo@2x.png 2x"));
+        assert!(rewritten.contains(r##"href="#top""##));
+
+        // without the opt-in the document with a base is left as is.
+        assert_eq!(
+            rewrite_base_tag(html, &Some("https://example.com/page")).await,
+            std::str::from_utf8(html).unwrap()
+        );
+    }
+
+    #[test]
+    fn vary_header_names() {
+        let mut headers = HashMap::new();
+        headers.insert(
+            "Vary".to_string(),
+            "Accept-Language, accept-encoding,Accept".to_string(),
+        );
+
+        assert_eq!(vary_headers(&headers), vec!["accept", "accept-language"]);
+        assert!(vary_headers(&HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn vary_cache_keys_differ_per_variant() {
+        let vary = vec!["accept-language".to_string()];
+        let mut english = HashMap::new();
+        english.insert("Accept-Language".to_string(), "en-US".to_string());
+        let mut german = HashMap::new();
+        german.insert("accept-language".to_string(), "de-DE".to_string());
+
+        assert_eq!(
+            create_vary_cache_key("GET:https://example.com/", &vary, &english),
+            "GET:https://example.com/|vary|accept-language=en-US"
+        );
+        assert_ne!(
+            create_vary_cache_key("GET:https://example.com/", &vary, &english),
+            create_vary_cache_key("GET:https://example.com/", &vary, &german)
+        );
+    }
+}
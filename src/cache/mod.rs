@@ -1,11 +1,52 @@
+/// Compression of the stored bodies.
+pub mod compression;
 /// Dump remote cache.
 pub mod dump_remote;
+/// Freshness overrides and size bounded eviction of the local cache.
+pub mod freshness;
+/// Write-ahead journal and flushing of the local cache writes.
+pub mod journal;
+/// Request parts of the cache keys.
+pub mod keys;
 /// Cache manager.
 pub mod manager;
+/// Negative caching of failed requests.
+pub mod negative;
+/// Offline replays of the cached responses.
+pub mod offline;
+/// Range requests served from cached bodies.
+pub mod range;
+/// Remote cache lookups per request.
+pub mod read_through;
 /// Remote cache.
 pub mod remote;
+/// Endpoint and authentication of the remote cache client.
+pub mod remote_config;
+/// Conditional revalidation of the stale entries.
+pub mod revalidate;
+/// Screenshots keyed by the hash of the rendered content.
+pub mod screenshot;
+/// Cache lookup counters and network metrics reported to the remote cache.
+pub mod stats;
+/// Rewriting of the subresource references of the cached documents.
+pub mod subresources;
+/// Prefetching of url lists into the cache before a crawl.
+pub mod warm;
 
+pub use freshness::{set_freshness_policy, FreshnessPolicy};
+pub use journal::{flush, recover, subscribe_write_errors};
+pub use keys::{set_cache_key_options, CacheKeyOptions, CacheKeyRequest};
 pub use manager::{
-    get_cached_url, put_hybrid_cache, rewrite_base_tag, spawn_fetch_cache_interceptor,
-    spawn_response_cache_listener, BasicCachePolicy, CacheStrategy,
+    get_cached_content_fingerprint, get_cached_url, put_hybrid_cache, rewrite_base_tag,
+    rewrite_base_tag_with_subresources, spawn_fetch_cache_interceptor,
+    spawn_fetch_cache_interceptor_with_remote, spawn_response_cache_listener, BasicCachePolicy,
+    CacheStrategy,
 };
+pub use offline::set_offline_context;
+pub use read_through::RemoteReadThrough;
+pub use remote_config::{set_remote_cache_config, RemoteCacheConfig};
+pub use revalidate::set_revalidation;
+pub use screenshot::{CachedScreenshot, ScreenshotCacheOptions};
+pub use stats::{cache_stats, CacheStats, CacheStatsSnapshot};
+pub use subresources::SubresourceRewrite;
+pub use warm::{warm_urls, WarmOutcome, WarmPolicy, WarmReport};
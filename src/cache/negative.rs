@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    EnableParams, EventLoadingFailed, EventRequestWillBeSent, EventResponseReceived,
+};
+use lazy_static::lazy_static;
+use tokio_stream::StreamExt;
+
+use crate::page::Page;
+use crate::runtime::JoinHandle;
+
+lazy_static! {
+    /// The failed urls and when their entry expires.
+    pub static ref NEGATIVE_CACHE: dashmap::DashMap<String, (NegativeOutcome, Instant)> =
+        dashmap::DashMap::new();
+}
+
+/// A failed request outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegativeOutcome {
+    /// The server responded with a `404`, `410` or `5xx` status.
+    Status(u16),
+    /// The host could not be resolved.
+    DnsFailure,
+}
+
+/// How long failed requests are remembered, `None` disables caching the outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegativeCachePolicy {
+    /// The ttl of `404` and `410` responses.
+    pub not_found_ttl: Option<Duration>,
+    /// The ttl of `5xx` responses.
+    pub server_error_ttl: Option<Duration>,
+    /// The ttl of unresolved hosts.
+    pub dns_failure_ttl: Option<Duration>,
+}
+
+impl Default for NegativeCachePolicy {
+    fn default() -> Self {
+        Self {
+            not_found_ttl: Some(Duration::from_secs(10 * 60)),
+            server_error_ttl: Some(Duration::from_secs(60)),
+            dns_failure_ttl: Some(Duration::from_secs(5 * 60)),
+        }
+    }
+}
+
+impl NegativeCachePolicy {
+    /// The ttl of the outcome, `None` when it is not cached.
+    pub fn ttl(&self, outcome: NegativeOutcome) -> Option<Duration> {
+        match outcome {
+            NegativeOutcome::Status(404 | 410) => self.not_found_ttl,
+            NegativeOutcome::Status(500..=599) => self.server_error_ttl,
+            NegativeOutcome::Status(_) => None,
+            NegativeOutcome::DnsFailure => self.dns_failure_ttl,
+        }
+    }
+}
+
+/// The negative cache key of the url, without the fragment.
+fn negative_key(url: &str) -> &str {
+    url.split_once('#').map_or(url, |(url, _)| url)
+}
+
+/// Remember the failed outcome of the url for the ttl of the policy. Outcomes the policy does
+/// not cache, e.g. a successful response, forget the previous failure of the url.
+pub fn record_failure(url: &str, outcome: NegativeOutcome, policy: &NegativeCachePolicy) {
+    let key = negative_key(url);
+
+    match policy.ttl(outcome) {
+        Some(ttl) => {
+            NEGATIVE_CACHE.insert(key.to_string(), (outcome, Instant::now() + ttl));
+        }
+        _ => {
+            NEGATIVE_CACHE.remove(key);
+        }
+    }
+}
+
+/// The remembered failure of the url, expired entries are removed.
+pub fn get_failure(url: &str) -> Option<NegativeOutcome> {
+    let key = negative_key(url);
+    let (outcome, expires) = *NEGATIVE_CACHE.get(key)?;
+
+    if expires <= Instant::now() {
+        NEGATIVE_CACHE.remove(key);
+        None
+    } else {
+        Some(outcome)
+    }
+}
+
+/// Forget the failures of the host, returning the amount of urls purged.
+pub fn purge_host(host: &str) -> usize {
+    let before = NEGATIVE_CACHE.len();
+
+    NEGATIVE_CACHE.retain(|url, _| {
+        url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| !h.eq_ignore_ascii_case(host)))
+            .unwrap_or(true)
+    });
+
+    before.saturating_sub(NEGATIVE_CACHE.len())
+}
+
+/// Forget every failure.
+pub fn purge_all() {
+    NEGATIVE_CACHE.clear();
+}
+
+/// Is the network error text an unresolved host?
+fn is_dns_failure(error_text: &str) -> bool {
+    matches!(
+        error_text,
+        "net::ERR_NAME_NOT_RESOLVED" | "net::ERR_NAME_RESOLUTION_FAILED"
+    )
+}
+
+/// Spawn a background task recording the failed responses and unresolved hosts of the page in
+/// the negative cache. The fetch cache interceptor replays the failures while they are fresh.
+pub async fn spawn_negative_cache_listener(
+    page: Page,
+    policy: NegativeCachePolicy,
+) -> Result<JoinHandle<()>, crate::error::CdpError> {
+    page.execute(EnableParams::default()).await?;
+
+    let mut requests = page.event_listener::<EventRequestWillBeSent>().await?;
+    let mut responses = page.event_listener::<EventResponseReceived>().await?;
+    let mut failures = page.event_listener::<EventLoadingFailed>().await?;
+
+    let handle = crate::runtime::spawn(async move {
+        // the urls of the requests in flight, loading failures only carry the request id.
+        let mut urls = HashMap::new();
+
+        loop {
+            tokio::select! {
+                Some(ev) = requests.next() => {
+                    urls.insert(ev.request_id.clone(), ev.request.url.clone());
+                }
+                Some(ev) = responses.next() => {
+                    urls.remove(&ev.request_id);
+                    let status = ev.response.status as u16;
+                    if ev.response.url.starts_with("http") {
+                        record_failure(&ev.response.url, NegativeOutcome::Status(status), &policy);
+                    }
+                }
+                Some(ev) = failures.next() => {
+                    if let Some(url) = urls.remove(&ev.request_id) {
+                        if is_dns_failure(&ev.error_text) {
+                            record_failure(&url, NegativeOutcome::DnsFailure, &policy);
+                        }
+                    }
+                }
+                else => break,
+            }
+        }
+    });
+
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_ttls() {
+        let policy = NegativeCachePolicy {
+            server_error_ttl: None,
+            ..Default::default()
+        };
+
+        assert!(policy.ttl(NegativeOutcome::Status(410)).is_some());
+        assert!(policy.ttl(NegativeOutcome::Status(503)).is_none());
+        assert!(policy.ttl(NegativeOutcome::Status(200)).is_none());
+        assert!(policy.ttl(NegativeOutcome::DnsFailure).is_some());
+    }
+
+    #[test]
+    fn record_and_purge_host() {
+        let policy = NegativeCachePolicy::default();
+
+        record_failure(
+            "https://dead.negative.test/a#top",
+            NegativeOutcome::Status(404),
+            &policy,
+        );
+        record_failure(
+            "https://down.negative.test/",
+            NegativeOutcome::DnsFailure,
+            &policy,
+        );
+        record_failure(
+            "https://ok.negative.test/",
+            NegativeOutcome::Status(200),
+            &policy,
+        );
+
+        assert_eq!(
+            get_failure("https://dead.negative.test/a"),
+            Some(NegativeOutcome::Status(404))
+        );
+        assert_eq!(get_failure("https://ok.negative.test/"), None);
+        assert_eq!(purge_host("dead.negative.test"), 1);
+        assert_eq!(get_failure("https://dead.negative.test/a"), None);
+        assert_eq!(
+            get_failure("https://down.negative.test/"),
+            Some(NegativeOutcome::DnsFailure)
+        );
+    }
+}
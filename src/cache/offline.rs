@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use chromiumoxide_cdp::cdp::browser_protocol::browser::BrowserContextId;
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::{FulfillRequestParams, HeaderEntry};
+use chromiumoxide_cdp::cdp::browser_protocol::network::RequestId;
+use chromiumoxide_cdp::cdp::browser_protocol::target::GetTargetInfoParams;
+
+use crate::page::Page;
+
+/// The status of the requests missing in the cache in offline mode.
+pub const OFFLINE_MISS_STATUS: i64 = 504;
+
+/// The header marking the synthetic responses of the misses.
+pub const OFFLINE_MISS_HEADER: &str = "x-chromey-offline";
+
+lazy_static::lazy_static! {
+    /// The browser contexts replayed offline, `None` for the default context.
+    static ref OFFLINE_CONTEXTS: RwLock<HashSet<Option<String>>> = RwLock::new(HashSet::new());
+}
+
+/// Replay the pages of the browser context offline, or not. The cache interceptors of its pages
+/// spawned without a cache strategy use `CacheStrategy::OfflineOnly`. `None` is the default
+/// context of the browser.
+pub fn set_offline_context(context: Option<&BrowserContextId>, offline: bool) {
+    if let Ok(mut contexts) = OFFLINE_CONTEXTS.write() {
+        let context = context.map(|id| id.inner().clone());
+
+        if offline {
+            contexts.insert(context);
+        } else {
+            contexts.remove(&context);
+        }
+    }
+}
+
+/// The browser context is replayed offline.
+pub fn is_offline_context(context: Option<&BrowserContextId>) -> bool {
+    OFFLINE_CONTEXTS
+        .read()
+        .map(|contexts| contexts.contains(&context.map(|id| id.inner().clone())))
+        .unwrap_or_default()
+}
+
+/// The browser context of the page is replayed offline.
+pub(crate) async fn is_offline_page(page: &Page) -> bool {
+    let any_offline = OFFLINE_CONTEXTS
+        .read()
+        .map(|contexts| !contexts.is_empty())
+        .unwrap_or_default();
+
+    if !any_offline {
+        return false;
+    }
+
+    match page
+        .execute(GetTargetInfoParams {
+            target_id: Some(page.target_id().clone()),
+        })
+        .await
+    {
+        Ok(info) => is_offline_context(info.result.target_info.browser_context_id.as_ref()),
+        _ => false,
+    }
+}
+
+/// The synthetic `504 Gateway Timeout` of a request missing in the cache.
+pub(crate) fn offline_miss(request_id: RequestId) -> FulfillRequestParams {
+    let mut params = FulfillRequestParams::new(request_id, OFFLINE_MISS_STATUS);
+
+    params.response_headers = Some(vec![
+        HeaderEntry {
+            name: OFFLINE_MISS_HEADER.into(),
+            value: "miss".into(),
+        },
+        HeaderEntry {
+            name: "cache-control".into(),
+            value: "no-store".into(),
+        },
+    ]);
+
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_the_offline_contexts() {
+        let context = BrowserContextId::new("offline-test");
+
+        assert!(!is_offline_context(Some(&context)));
+        set_offline_context(Some(&context), true);
+        assert!(is_offline_context(Some(&context)));
+        set_offline_context(Some(&context), false);
+        assert!(!is_offline_context(Some(&context)));
+    }
+
+    #[test]
+    fn misses_are_gateway_timeouts() {
+        let params = offline_miss(RequestId::new("1"));
+
+        assert_eq!(params.response_code, OFFLINE_MISS_STATUS);
+        assert!(params
+            .response_headers
+            .unwrap()
+            .iter()
+            .any(|header| header.name == OFFLINE_MISS_HEADER));
+    }
+}
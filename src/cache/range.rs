@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+/// The byte range requested by a `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The whole body, the header is not a single valid byte range.
+    Full,
+    /// The inclusive start and end of the range.
+    Partial(usize, usize),
+    /// The range starts after the end of the body.
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Parse the `Range` header for a body of `len` bytes. Multiple ranges are served as the
+    /// whole body.
+    pub fn parse(range: &str, len: usize) -> Self {
+        let spec = match range.trim().strip_prefix("bytes=") {
+            Some(spec) if !spec.contains(',') => spec.trim(),
+            _ => return ByteRange::Full,
+        };
+
+        let (start, end) = match spec.split_once('-') {
+            Some(bounds) => bounds,
+            _ => return ByteRange::Full,
+        };
+
+        let (start, end) = (start.trim(), end.trim());
+
+        if start.is_empty() {
+            // the suffix of the body.
+            return match end.parse::<usize>() {
+                Ok(0) => ByteRange::Unsatisfiable,
+                Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+                Ok(suffix) => ByteRange::Partial(len.saturating_sub(suffix), len - 1),
+                _ => ByteRange::Full,
+            };
+        }
+
+        let start = match start.parse::<usize>() {
+            Ok(start) => start,
+            _ => return ByteRange::Full,
+        };
+
+        let end = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            match end.parse::<usize>() {
+                Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                _ => return ByteRange::Full,
+            }
+        };
+
+        if start >= len {
+            ByteRange::Unsatisfiable
+        } else {
+            ByteRange::Partial(start, end)
+        }
+    }
+}
+
+/// The value of a header by case insensitive name.
+pub fn find_header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Serve the `Range` request header from a cached body, returning the status, the body and the
+/// headers of the response. Only complete `200` responses are sliced.
+pub fn range_response<'a>(
+    range: Option<&str>,
+    status: u16,
+    body: &'a [u8],
+    headers: &HashMap<String, String>,
+) -> (u16, &'a [u8], HashMap<String, String>) {
+    let byte_range = match range {
+        Some(range) if status == 200 => ByteRange::parse(range, body.len()),
+        _ => ByteRange::Full,
+    };
+
+    if byte_range == ByteRange::Full {
+        return (status, body, headers.clone());
+    }
+
+    let mut headers: HashMap<String, String> = headers
+        .iter()
+        .filter(|(k, _)| {
+            !k.eq_ignore_ascii_case("content-length")
+                && !k.eq_ignore_ascii_case("content-range")
+                && !k.eq_ignore_ascii_case("accept-ranges")
+        })
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+
+    headers.insert("Accept-Ranges".into(), "bytes".into());
+
+    match byte_range {
+        ByteRange::Partial(start, end) => {
+            let slice = &body[start..=end];
+            headers.insert(
+                "Content-Range".into(),
+                format!("bytes {start}-{end}/{}", body.len()),
+            );
+            headers.insert("Content-Length".into(), slice.len().to_string());
+            (206, slice, headers)
+        }
+        _ => {
+            headers.insert("Content-Range".into(), format!("bytes */{}", body.len()));
+            headers.insert("Content-Length".into(), "0".into());
+            (416, &body[..0], headers)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_byte_ranges() {
+        assert_eq!(
+            ByteRange::parse("bytes=0-99", 1000),
+            ByteRange::Partial(0, 99)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=900-", 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=-100", 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=500-5000", 1000),
+            ByteRange::Partial(500, 999)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=1000-", 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(ByteRange::parse("bytes=0-1,5-9", 1000), ByteRange::Full);
+        assert_eq!(ByteRange::parse("items=0-1", 1000), ByteRange::Full);
+        assert_eq!(ByteRange::parse("bytes=9-1", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn partial_response() {
+        let mut headers = HashMap::new();
+        headers.insert("content-length".to_string(), "10".to_string());
+        headers.insert("Content-Type".to_string(), "video/mp4".to_string());
+
+        let (status, body, headers) =
+            range_response(Some("bytes=2-5"), 200, b"0123456789", &headers);
+
+        assert_eq!(status, 206);
+        assert_eq!(body, b"2345");
+        assert_eq!(find_header(&headers, "content-range"), Some("bytes 2-5/10"));
+        assert_eq!(find_header(&headers, "content-length"), Some("4"));
+        assert_eq!(find_header(&headers, "content-type"), Some("video/mp4"));
+
+        let (status, body, headers) =
+            range_response(Some("bytes=20-"), 200, b"0123456789", &HashMap::new());
+
+        assert_eq!(status, 416);
+        assert!(body.is_empty());
+        assert_eq!(find_header(&headers, "content-range"), Some("bytes */10"));
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Recent remote cache misses, bounded by evicting the oldest lookup.
+#[derive(Debug, Clone)]
+pub struct NegativeLookups {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<String, Instant>,
+    order: VecDeque<String>,
+}
+
+impl NegativeLookups {
+    /// Remember up to `capacity` misses for the ttl.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Did the lookup of the url miss recently?
+    pub fn contains(&self, url: &str) -> bool {
+        self.entries
+            .get(url)
+            .map_or(false, |missed| missed.elapsed() < self.ttl)
+    }
+
+    /// Remember the miss of the url.
+    pub fn insert(&mut self, url: &str) {
+        if self
+            .entries
+            .insert(url.to_string(), Instant::now())
+            .is_none()
+        {
+            self.order.push_back(url.to_string());
+        }
+
+        while self.entries.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.entries.remove(&oldest);
+                }
+                _ => break,
+            }
+        }
+    }
+}
+
+/// Query the remote cache per request on a local cache miss instead of seeding the whole site
+/// up front.
+#[derive(Debug, Clone)]
+pub struct RemoteReadThrough {
+    /// The remote cache server, the default endpoint when `None` or `"true"`.
+    pub remote: Option<String>,
+    /// The timeout of a remote lookup, the request continues to the network after it.
+    pub timeout: Duration,
+    /// The recent misses skipped without querying the remote cache.
+    pub negative: NegativeLookups,
+}
+
+impl Default for RemoteReadThrough {
+    fn default() -> Self {
+        Self {
+            remote: None,
+            timeout: Duration::from_millis(150),
+            negative: NegativeLookups::new(1024, Duration::from_secs(60)),
+        }
+    }
+}
+
+impl RemoteReadThrough {
+    /// Read through the remote cache server.
+    pub fn new(remote: Option<String>) -> Self {
+        Self {
+            remote,
+            ..Default::default()
+        }
+    }
+
+    /// Set the timeout of a remote lookup.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Look up the url in the remote cache, seeding the local session cache on a hit.
+    pub async fn lookup(
+        &mut self,
+        target_url: &str,
+        auth: Option<&str>,
+    ) -> Option<(Vec<u8>, HashMap<String, String>, u16)> {
+        if self.negative.contains(target_url) {
+            return None;
+        }
+
+        let hit = super::remote::get_remote_resource(
+            target_url,
+            auth,
+            self.remote.as_deref(),
+            self.timeout,
+        )
+        .await;
+
+        if hit.is_none() {
+            self.negative.insert(target_url);
+        }
+
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_lookups_evict_oldest() {
+        let mut negative = NegativeLookups::new(2, Duration::from_secs(60));
+
+        negative.insert("https://example.com/a");
+        negative.insert("https://example.com/b");
+        negative.insert("https://example.com/a");
+        negative.insert("https://example.com/c");
+
+        assert!(!negative.contains("https://example.com/a"));
+        assert!(negative.contains("https://example.com/b"));
+        assert!(negative.contains("https://example.com/c"));
+    }
+
+    #[test]
+    fn negative_lookups_expire() {
+        let mut negative = NegativeLookups::new(8, Duration::ZERO);
+
+        negative.insert("https://example.com/a");
+
+        assert!(!negative.contains("https://example.com/a"));
+    }
+}
@@ -12,7 +12,7 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::Semaphore;
 use url::Url;
 
-use crate::cache::manager::site_key_for_target_url;
+use crate::cache::manager::{create_vary_cache_key, site_key_for_target_url, vary_headers};
 use crate::http::{convert_headers, HttpRequestLike, HttpResponseLike, HttpVersion};
 
 lazy_static! {
@@ -50,6 +50,28 @@ pub struct HybridCachePayload {
     http_version: HttpVersion,
     /// Base64-encoded HTTP body for JSON transport.
     body_base64: String,
+    /// The encoding of the body before the base64, e.g. `zstd`, `None` for the raw body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_encoding: Option<String>,
+}
+
+impl HybridCachePayload {
+    /// The raw body, decoded and decompressed.
+    fn body(&self) -> Result<Vec<u8>, String> {
+        let body = general_purpose::STANDARD
+            .decode(&self.body_base64)
+            .map_err(|e| format!("invalid base64 body for {}: {e}", self.resource_key))?;
+
+        match self.content_encoding.as_deref() {
+            Some(encoding) => super::compression::decode_body(&body, encoding).ok_or_else(|| {
+                format!(
+                    "invalid {encoding} body for {}, the encoding may not be enabled",
+                    self.resource_key
+                )
+            }),
+            _ => Ok(body),
+        }
+    }
 }
 
 pub async fn dump_to_remote_cache_parts(
@@ -64,18 +86,90 @@ pub async fn dump_to_remote_cache_parts(
     http_version: &HttpVersion,
     dump_remote: Option<&str>,
 ) {
-    let _permit = match REMOTE_CACHE_DUMP_SEM.acquire().await {
-        Ok(p) => p,
-        Err(_) => return,
-    };
+    dump_to_remote_cache_encoded(
+        cache_key,
+        cache_site,
+        url_str,
+        body,
+        None,
+        method,
+        status,
+        http_request_headers,
+        response_headers,
+        http_version,
+        dump_remote,
+    )
+    .await;
+}
 
+/// The outcome of a dump to the remote cache server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DumpOutcome {
+    /// The server stored the payloads.
+    Stored,
+    /// The server answered with the non success status.
+    Rejected(u16),
+    /// The payloads could not be encoded.
+    Invalid,
+    /// The request failed, e.g. the server is unreachable.
+    Failed,
+}
+
+impl DumpOutcome {
+    /// The payloads are stored.
+    pub(crate) fn is_stored(&self) -> bool {
+        matches!(self, Self::Stored)
+    }
+
+    /// A retry may succeed: the server failed with a `5xx`, asked to slow down with a `429` or
+    /// was not reached.
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            Self::Rejected(status) => *status >= 500 || *status == 429,
+            Self::Failed => true,
+            _ => false,
+        }
+    }
+}
+
+/// The payload of a response for the remote cache server, the raw body compressed when the
+/// remote cache config compresses the bodies.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn hybrid_payload(
+    cache_key: &str,
+    url_str: &str,
+    body: &[u8],
+    content_encoding: Option<&str>,
+    method: &str,
+    status: u16,
+    http_request_headers: &std::collections::HashMap<String, String>,
+    response_headers: &std::collections::HashMap<String, String>,
+    http_version: &HttpVersion,
+) -> HybridCachePayload {
     let website_key = url::Url::parse(url_str)
         .ok()
         .and_then(|u| u.host_str().map(|h| h.to_string()));
 
-    let body_base64 = general_purpose::STANDARD.encode(body);
+    let compress_bodies = super::remote_config::remote_cache_config().compress_bodies;
+
+    let (body_base64, content_encoding) = match content_encoding {
+        Some(encoding) => (
+            general_purpose::STANDARD.encode(body),
+            Some(encoding.to_string()),
+        ),
+        _ => match compress_bodies
+            .then(|| super::compression::encode_body(body))
+            .flatten()
+        {
+            Some((encoded, encoding)) => (
+                general_purpose::STANDARD.encode(encoded),
+                Some(encoding.to_string()),
+            ),
+            _ => (general_purpose::STANDARD.encode(body), None),
+        },
+    };
 
-    let payload = HybridCachePayload {
+    HybridCachePayload {
         website_key,
         resource_key: cache_key.to_string(),
         url: url_str.to_string(),
@@ -85,21 +179,58 @@ pub async fn dump_to_remote_cache_parts(
         request_headers: http_request_headers.clone(),
         response_headers: response_headers.clone(),
         body_base64,
+        content_encoding,
+    }
+}
+
+/// Dump the body, already compressed with the `content_encoding` when set. A raw body is
+/// compressed when the remote cache config compresses the bodies.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn dump_to_remote_cache_encoded(
+    cache_key: &str,
+    cache_site: &str,
+    url_str: &str,
+    body: &[u8],
+    content_encoding: Option<&str>,
+    method: &str,
+    status: u16,
+    http_request_headers: &std::collections::HashMap<String, String>,
+    response_headers: &std::collections::HashMap<String, String>,
+    http_version: &HttpVersion,
+    dump_remote: Option<&str>,
+) -> DumpOutcome {
+    let _permit = match REMOTE_CACHE_DUMP_SEM.acquire().await {
+        Ok(p) => p,
+        Err(_) => return DumpOutcome::Failed,
     };
 
-    let mut base_url = HYBRID_CACHE_ENDPOINT.as_str();
+    let config = super::remote_config::remote_cache_config();
 
-    if let Some(remote) = dump_remote {
-        if remote != "true" {
-            base_url = remote.trim_ascii();
-        }
-    }
+    let payload = hybrid_payload(
+        cache_key,
+        url_str,
+        body,
+        content_encoding,
+        method,
+        status,
+        http_request_headers,
+        response_headers,
+        http_version,
+    );
 
-    let endpoint = format!("{}/cache/index", &*base_url);
+    let base_url = config.base_url(dump_remote);
 
-    let result = HYBRID_CACHE_CLIENT
-        .post(&endpoint)
-        .json(&payload)
+    let endpoint = format!("{}/cache/index", base_url);
+
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::warn!("remote cache dump: failed to encode {}: {}", cache_key, err);
+            return DumpOutcome::Invalid;
+        }
+    };
+
+    let result = super::remote_config::remote_request(Method::POST, &endpoint, Some(body))
         .header(
             "x-cache-site",
             HeaderValue::from_str(cache_site).unwrap_or(HeaderValue::from_static("")),
@@ -115,12 +246,14 @@ pub async fn dump_to_remote_cache_parts(
                     cache_key,
                     resp.status()
                 );
+                DumpOutcome::Rejected(resp.status().as_u16())
             } else {
                 tracing::info!(
                     "remote cache dump: success status for {}: {}",
                     cache_key,
                     resp.status()
                 );
+                DumpOutcome::Stored
             }
         }
         Err(err) => {
@@ -130,6 +263,92 @@ pub async fn dump_to_remote_cache_parts(
                 endpoint,
                 err
             );
+            DumpOutcome::Failed
+        }
+    }
+}
+
+/// Dump the payloads of the site in one request to the `/cache/index/batch` endpoint, one
+/// payload per line, gzipped with the `cache_gzip` feature.
+pub(crate) async fn dump_batch_to_remote_cache(
+    cache_site: &str,
+    payloads: &[HybridCachePayload],
+    dump_remote: Option<&str>,
+) -> DumpOutcome {
+    if payloads.is_empty() {
+        return DumpOutcome::Stored;
+    }
+
+    let _permit = match REMOTE_CACHE_DUMP_SEM.acquire().await {
+        Ok(p) => p,
+        Err(_) => return DumpOutcome::Failed,
+    };
+
+    let mut body = Vec::new();
+
+    for payload in payloads {
+        match serde_json::to_writer(&mut body, payload) {
+            Ok(_) => body.push(b'\n'),
+            Err(err) => {
+                tracing::warn!(
+                    "remote cache dump: failed to encode {}: {}",
+                    payload.resource_key,
+                    err
+                );
+            }
+        }
+    }
+
+    let config = super::remote_config::remote_cache_config();
+    let endpoint = format!("{}/cache/index/batch", config.base_url(dump_remote));
+
+    let (body, gzipped) = match super::compression::gzip(&body) {
+        Some(encoded) => (encoded, true),
+        _ => (body, false),
+    };
+
+    let mut request = super::remote_config::remote_request_with_body(
+        Method::POST,
+        &endpoint,
+        Some(body),
+        NDJSON_CONTENT_TYPE,
+    )
+    .header(
+        "x-cache-site",
+        HeaderValue::from_str(cache_site).unwrap_or(HeaderValue::from_static("")),
+    );
+
+    if gzipped {
+        request = request.header(reqwest::header::CONTENT_ENCODING, "gzip");
+    }
+
+    match request.send().await {
+        Ok(resp) if resp.status().is_success() => {
+            tracing::info!(
+                "remote cache dump: batch of {} for {}: {}",
+                payloads.len(),
+                cache_site,
+                resp.status()
+            );
+            DumpOutcome::Stored
+        }
+        Ok(resp) => {
+            tracing::warn!(
+                "remote cache dump: non-success status for a batch of {} for {}: {}",
+                payloads.len(),
+                cache_site,
+                resp.status()
+            );
+            DumpOutcome::Rejected(resp.status().as_u16())
+        }
+        Err(err) => {
+            tracing::warn!(
+                "remote cache dump: failed to POST a batch of {} to {}: {}",
+                payloads.len(),
+                endpoint,
+                err
+            );
+            DumpOutcome::Failed
         }
     }
 }
@@ -158,79 +377,306 @@ pub async fn dump_to_remote_cache(
     .await
 }
 
+/// The content type of the streamed site caches, one payload per line.
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// A page of the site cache, the remote server paginates when asked with a `limit`.
+#[derive(Debug, Deserialize)]
+struct HybridCachePage {
+    entries: Vec<HybridCachePayload>,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+/// A site cache response, paginated or the whole site at once from older servers.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SiteCacheBody {
+    Page(HybridCachePage),
+    All(Vec<HybridCachePayload>),
+}
+
+/// The progress of seeding a site cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeedProgress {
+    /// The entries seeded.
+    pub seeded: usize,
+    /// The entries which failed to parse or seed.
+    pub failed: usize,
+    /// The bytes received from the remote server.
+    pub bytes: u64,
+    /// The pages or streams received.
+    pub pages: usize,
+    /// The site is seeded, or the remote server failed.
+    pub done: bool,
+}
+
+/// The callback of the seeding progress, called after every entry.
+pub type SeedProgressFn = std::sync::Arc<dyn Fn(&SeedProgress) + Send + Sync>;
+
+/// How a site cache is downloaded from the remote server.
+#[derive(Clone, Default)]
+pub struct SiteSeedOptions {
+    /// Ask the remote server for pages of this many entries, when it does not stream them.
+    pub page_size: Option<usize>,
+    /// The callback of the progress.
+    pub progress: Option<SeedProgressFn>,
+}
+
+impl std::fmt::Debug for SiteSeedOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SiteSeedOptions")
+            .field("page_size", &self.page_size)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// Split the streamed bytes into the complete lines.
+#[derive(Debug, Default)]
+struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Append the chunk, returning the non empty lines it completed.
+    fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        self.buf.extend_from_slice(chunk);
+
+        let Some(last) = self.buf.iter().rposition(|b| *b == b'\n') else {
+            return Vec::new();
+        };
+        let rest = self.buf.split_off(last + 1);
+        let complete = std::mem::replace(&mut self.buf, rest);
+
+        complete
+            .split(|b| *b == b'\n')
+            .map(|line| line.trim_ascii())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_vec())
+            .collect()
+    }
+
+    /// The last line without a trailing newline.
+    fn finish(self) -> Option<Vec<u8>> {
+        let line = self.buf.trim_ascii();
+
+        (!line.is_empty()).then(|| line.to_vec())
+    }
+}
+
+/// Seed a downloaded entry and report the progress.
+async fn seed_entry(
+    cache_key: &str,
+    payload: Result<HybridCachePayload, serde_json::Error>,
+    target_url: &str,
+    progress: &mut SeedProgress,
+    options: &SiteSeedOptions,
+) {
+    let result = match payload {
+        Ok(payload) => seed_payload_into_local_cache(cache_key, &payload, target_url).await,
+        Err(err) => Err(format!("invalid payload: {err}")),
+    };
+
+    match result {
+        Ok(_) => progress.seeded += 1,
+        Err(err) => {
+            progress.failed += 1;
+            tracing::warn!(
+                "remote cache get: failed to seed a resource for website {}: {}",
+                cache_key,
+                err
+            );
+        }
+    }
+
+    if let Some(report) = options.progress.as_ref() {
+        report(progress);
+    }
+}
+
 /// Get the cache for a website from the remote cache server and seed
 /// our local hybrid cache (CACACHE_MANAGER) with **all** entries [experimental].
 ///
 /// `cache_key` here is the `website_key` used by the remote server,
 /// e.g. "example.com".
 pub async fn get_cache_site(target_url: &str, auth: Option<&str>, remote: Option<&str>) {
-    let mut base_url = HYBRID_CACHE_ENDPOINT.as_str();
+    get_cache_site_with_options(target_url, auth, remote, &SiteSeedOptions::default()).await;
+}
 
-    if let Some(remote) = remote {
-        if remote != "true" {
-            base_url = remote.trim_ascii();
-        }
-    }
+/// Get the cache for a website from the remote cache server and seed the local hybrid cache
+/// entry by entry [experimental]. An NDJSON stream of the server is seeded as it arrives and a
+/// paginated server is asked page by page, so the whole site is never held in memory. Servers
+/// answering with a single JSON array are still supported.
+pub async fn get_cache_site_with_options(
+    target_url: &str,
+    auth: Option<&str>,
+    remote: Option<&str>,
+    options: &SiteSeedOptions,
+) -> SeedProgress {
+    let config = super::remote_config::remote_cache_config();
+    let base_url = config.base_url(remote);
 
     let cache_key = site_key_for_target_url(target_url, auth.as_deref());
 
-    let endpoint = format!("{}/cache/site/{}", &*base_url, cache_key);
+    let endpoint = format!("{}/cache/site/{}", base_url, cache_key);
 
-    // Fetch all entries for this website from the remote cache server.
-    let result = HYBRID_CACHE_CLIENT.get(&endpoint).send().await;
+    let mut progress = SeedProgress::default();
+    let mut cursor: Option<String> = None;
 
-    let resp = match result {
-        Ok(resp) => resp,
-        Err(err) => {
-            tracing::warn!(
-                "remote cache get: failed to GET {} from {}: {}",
-                cache_key,
-                endpoint,
-                err
-            );
-            return;
+    loop {
+        // the query is part of the signed url.
+        let mut url = match Url::parse(&endpoint) {
+            Ok(url) => url,
+            Err(err) => {
+                tracing::warn!("remote cache get: invalid endpoint {}: {}", endpoint, err);
+                break;
+            }
+        };
+        if let Some(limit) = options.page_size {
+            url.query_pairs_mut()
+                .append_pair("limit", &limit.to_string());
+        }
+        if let Some(cursor) = cursor.as_deref() {
+            url.query_pairs_mut().append_pair("cursor", cursor);
         }
-    };
 
-    if !resp.status().is_success() {
-        tracing::warn!(
-            "remote cache get: non-success status for {}: {}",
-            cache_key,
-            resp.status()
+        let request = super::remote_config::remote_request(Method::GET, url.as_str(), None).header(
+            reqwest::header::ACCEPT,
+            format!("{NDJSON_CONTENT_TYPE}, application/json"),
         );
-        return;
-    }
 
-    // Parse JSON payloads: Vec<HybridCachePayload>
-    let payloads: Vec<Box<HybridCachePayload>> = match resp.json().await {
-        Ok(p) => p,
-        Err(err) => {
+        let mut resp = match request.send().await {
+            Ok(resp) => resp,
+            Err(err) => {
+                tracing::warn!(
+                    "remote cache get: failed to GET {} from {}: {}",
+                    cache_key,
+                    endpoint,
+                    err
+                );
+                break;
+            }
+        };
+
+        if !resp.status().is_success() {
             tracing::warn!(
-                "remote cache get: failed to parse JSON for {} from {}: {}",
+                "remote cache get: non-success status for {}: {}",
                 cache_key,
-                endpoint,
-                err
+                resp.status()
             );
-            return;
+            break;
         }
-    };
 
-    tracing::debug!(
-        "remote cache get: seeding {} entries locally for website {}",
-        payloads.len(),
-        cache_key
-    );
+        progress.pages += 1;
+
+        let streamed = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map_or(false, |v| v.contains("ndjson"));
+
+        if streamed {
+            let mut lines = LineBuffer::default();
+
+            loop {
+                let chunk = match resp.chunk().await {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => break,
+                    Err(err) => {
+                        tracing::warn!(
+                            "remote cache get: the stream of {} from {} failed: {}",
+                            cache_key,
+                            endpoint,
+                            err
+                        );
+                        break;
+                    }
+                };
+
+                progress.bytes += chunk.len() as u64;
+
+                for line in lines.push(&chunk) {
+                    seed_entry(
+                        &cache_key,
+                        serde_json::from_slice(&line),
+                        target_url,
+                        &mut progress,
+                        options,
+                    )
+                    .await;
+                }
+            }
 
-    for payload in payloads {
-        if let Err(err) = seed_payload_into_local_cache(&cache_key, &payload, &target_url).await {
-            tracing::warn!(
-                "remote cache get: failed to seed resource {} for website {}: {}",
-                payload.resource_key,
-                cache_key,
-                err
-            );
+            if let Some(line) = lines.finish() {
+                seed_entry(
+                    &cache_key,
+                    serde_json::from_slice(&line),
+                    target_url,
+                    &mut progress,
+                    options,
+                )
+                .await;
+            }
+
+            break;
         }
+
+        let body = match resp.bytes().await {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::warn!(
+                    "remote cache get: failed to read {} from {}: {}",
+                    cache_key,
+                    endpoint,
+                    err
+                );
+                break;
+            }
+        };
+
+        progress.bytes += body.len() as u64;
+
+        let (entries, next_cursor) = match serde_json::from_slice::<SiteCacheBody>(&body) {
+            Ok(SiteCacheBody::Page(page)) => (page.entries, page.next_cursor),
+            Ok(SiteCacheBody::All(entries)) => (entries, None),
+            Err(err) => {
+                tracing::warn!(
+                    "remote cache get: failed to parse JSON for {} from {}: {}",
+                    cache_key,
+                    endpoint,
+                    err
+                );
+                break;
+            }
+        };
+        drop(body);
+
+        tracing::debug!(
+            "remote cache get: seeding {} entries locally for website {}",
+            entries.len(),
+            cache_key
+        );
+
+        for payload in entries {
+            seed_entry(&cache_key, Ok(payload), target_url, &mut progress, options).await;
+        }
+
+        match next_cursor {
+            Some(next) if !next.is_empty() && cursor.as_deref() != Some(next.as_str()) => {
+                cursor = Some(next)
+            }
+            _ => break,
+        }
+    }
+
+    progress.done = true;
+
+    if let Some(report) = options.progress.as_ref() {
+        report(&progress);
     }
+
+    progress
 }
 
 /// Get the cache for a resource from the remote cache server and seed
@@ -239,20 +685,17 @@ pub async fn get_cache_site(target_url: &str, auth: Option<&str>, remote: Option
 /// `cache_key` here is the `website_key` used by the remote server,
 /// e.g. "example.com".
 pub async fn get_cache_resource(target_url: &str, auth: Option<&str>, remote: Option<&str>) {
-    let mut base_url = HYBRID_CACHE_ENDPOINT.as_str();
-
-    if let Some(remote) = remote {
-        if remote != "true" {
-            base_url = remote.trim_ascii();
-        }
-    }
+    let config = super::remote_config::remote_cache_config();
+    let base_url = config.base_url(remote);
 
     let cache_key = site_key_for_target_url(target_url, auth.as_deref());
 
-    let endpoint = format!("{}/cache/resource/{}", &*base_url, cache_key);
+    let endpoint = format!("{}/cache/resource/{}", base_url, cache_key);
 
     // Fetch all entries for this website from the remote cache server.
-    let result = HYBRID_CACHE_CLIENT.get(&endpoint).send().await;
+    let result = super::remote_config::remote_request(Method::GET, &endpoint, None)
+        .send()
+        .await;
 
     let resp = match result {
         Ok(resp) => resp,
@@ -304,6 +747,48 @@ pub async fn get_cache_resource(target_url: &str, auth: Option<&str>, remote: Op
     }
 }
 
+/// Get a single resource from the remote cache server within the timeout and seed it into the
+/// local session cache. Returns the body, the response headers and the status, `None` on a miss.
+pub async fn get_remote_resource(
+    target_url: &str,
+    auth: Option<&str>,
+    remote: Option<&str>,
+    timeout: std::time::Duration,
+) -> Option<(Vec<u8>, std::collections::HashMap<String, String>, u16)> {
+    let config = super::remote_config::remote_cache_config();
+    let base_url = config.base_url(remote);
+
+    let cache_key = site_key_for_target_url(target_url, auth);
+    let endpoint = format!("{}/cache/resource/{}", base_url, cache_key);
+
+    let payload = crate::runtime::timeout(timeout, async {
+        let resp = super::remote_config::remote_request(Method::GET, &endpoint, None)
+            .send()
+            .await
+            .ok()?;
+
+        if !resp.status().is_success() {
+            return None;
+        }
+
+        resp.json::<HybridCachePayload>().await.ok()
+    })
+    .await
+    .ok()??;
+
+    let body = payload.body().ok()?;
+
+    if let Err(err) = seed_payload_into_local_cache(&cache_key, &payload, target_url).await {
+        tracing::debug!(
+            "remote cache read-through: failed to seed {}: {}",
+            payload.resource_key,
+            err
+        );
+    }
+
+    Some((body, payload.response_headers, payload.status))
+}
+
 /// Remove item from local session cache.
 pub async fn clear_local_session_cache(cache_key: &str) {
     LOCAL_SESSION_CACHE.remove(cache_key);
@@ -351,9 +836,7 @@ async fn seed_payload_into_local_cache(
         .parse()
         .map_err(|e| format!("invalid URI for {}: {e}", payload.url))?;
 
-    let body = general_purpose::STANDARD
-        .decode(&payload.body_base64)
-        .map_err(|e| format!("invalid base64 body for {}: {e}", payload.resource_key))?;
+    let body = payload.body()?;
 
     let req = HttpRequestLike {
         uri,
@@ -383,12 +866,26 @@ async fn seed_payload_into_local_cache(
     let session_key = format!("{}:{}", payload.method, http_res.url);
 
     if same_document {
+        let mut stored = http_res.clone();
+        crate::cache::compression::compress_response(&mut stored);
+
         let put_result = CACACHE_MANAGER
-            .put(key.clone(), http_res.clone(), policy.clone())
+            .put(key.clone(), stored, policy.clone())
             .await;
         if let Err(e) = put_result {
+            crate::cache::journal::report_write_error(&key, &e);
             return Err(format!("CACACHE_MANAGER.put failed for {}: {e}", key));
         }
+
+        crate::cache::freshness::record_put(&key, http_res.body.len()).await;
+    }
+
+    // the variant is stored under the values of the request headers the response varies on.
+    let vary = vary_headers(&payload.response_headers);
+
+    if !vary.is_empty() && !vary.iter().any(|name| name == "*") {
+        let variant_key = create_vary_cache_key(&session_key, &vary, &payload.request_headers);
+        session_cache_insert(cache_key, http_res.clone(), policy.clone(), &variant_key);
     }
 
     session_cache_insert(cache_key, http_res, policy, &session_key);
@@ -396,14 +893,28 @@ async fn seed_payload_into_local_cache(
     Ok(())
 }
 
-/// Get the resource from the cache.
+/// Get the resource from the cache, the variant of the request headers when the response varies
+/// on them.
 pub fn get_session_cache_item(
     cache_key: &str,
     target_url: &str,
+    request_headers: &std::collections::HashMap<String, String>,
 ) -> Option<(http_cache_reqwest::HttpResponse, CachePolicy)> {
-    LOCAL_SESSION_CACHE
-        .get(cache_key)
-        .and_then(|local_cache| local_cache.get(target_url).cloned())
+    let local_cache = LOCAL_SESSION_CACHE.get(cache_key)?;
+    let entry = local_cache.get(target_url)?;
+    let vary = vary_headers(&entry.0.headers);
+
+    if vary.is_empty() {
+        return Some(entry.clone());
+    }
+
+    if vary.iter().any(|name| name == "*") {
+        return None;
+    }
+
+    local_cache
+        .get(&create_vary_cache_key(target_url, &vary, request_headers))
+        .cloned()
 }
 
 /// Check the resource from the cache.
@@ -412,3 +923,95 @@ pub fn check_session_cache_item(cache_key: &str, target_url: &str) -> bool {
         .get(cache_key)
         .map_or(false, |local_cache| local_cache.contains_key(target_url))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_streamed_lines() {
+        let mut lines = LineBuffer::default();
+
+        assert!(lines.push(b"{\"a\":").is_empty());
+        assert_eq!(
+            lines.push(b"1}\n\n{\"b\":2}\r\n{\"c\""),
+            vec![b"{\"a\":1}".to_vec(), b"{\"b\":2}".to_vec()]
+        );
+        assert_eq!(lines.push(b":3}"), Vec::<Vec<u8>>::new());
+        assert_eq!(lines.finish(), Some(b"{\"c\":3}".to_vec()));
+    }
+
+    #[test]
+    fn parses_the_pages_and_the_whole_sites() {
+        let entry = serde_json::json!({
+            "resource_key": "k",
+            "url": "https://example.com/",
+            "method": "GET",
+            "status": 200,
+            "request_headers": {},
+            "response_headers": {},
+            "http_version": "Http11",
+            "body_base64": "",
+        });
+
+        let page: SiteCacheBody =
+            serde_json::from_value(serde_json::json!({"entries": [entry], "next_cursor": "2"}))
+                .unwrap();
+        assert!(matches!(
+            page,
+            SiteCacheBody::Page(HybridCachePage { ref entries, next_cursor: Some(ref c) })
+                if entries.len() == 1 && c == "2"
+        ));
+
+        let all: SiteCacheBody = serde_json::from_value(serde_json::json!([entry, entry])).unwrap();
+        assert!(matches!(all, SiteCacheBody::All(entries) if entries.len() == 2));
+    }
+
+    #[test]
+    fn decodes_the_payload_bodies() {
+        let raw = HybridCachePayload {
+            body_base64: general_purpose::STANDARD.encode("<html></html>"),
+            ..Default::default()
+        };
+        assert_eq!(raw.body().unwrap(), b"<html></html>");
+
+        let unknown = HybridCachePayload {
+            content_encoding: Some("br".into()),
+            ..raw
+        };
+        assert!(unknown.body().is_err());
+    }
+
+    #[tokio::test]
+    async fn serves_the_session_variant_of_the_request_headers() {
+        let payload = |language: &str| HybridCachePayload {
+            resource_key: format!("GET:https://example.com/vary.js|{language}"),
+            url: "https://example.com/vary.js".into(),
+            method: "GET".into(),
+            status: 200,
+            request_headers: [("Accept-Language".to_string(), language.to_string())].into(),
+            response_headers: [("vary".to_string(), "Accept-Language".to_string())].into(),
+            body_base64: general_purpose::STANDARD.encode(language),
+            ..Default::default()
+        };
+        let request = |language: &str| {
+            std::collections::HashMap::from([("accept-language".to_string(), language.to_string())])
+        };
+        let site = "vary-session-site";
+
+        for language in ["en", "de"] {
+            seed_payload_into_local_cache(site, &payload(language), "https://example.com/")
+                .await
+                .unwrap();
+        }
+
+        let url = "GET:https://example.com/vary.js";
+        let body = |language: &str| {
+            get_session_cache_item(site, url, &request(language)).map(|(res, _)| res.body)
+        };
+
+        assert_eq!(body("en"), Some(b"en".to_vec()));
+        assert_eq!(body("de"), Some(b"de".to_vec()));
+        assert_eq!(body("fr"), None);
+    }
+}
@@ -0,0 +1,269 @@
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Method, RequestBuilder};
+
+use super::remote::{HYBRID_CACHE_CLIENT, HYBRID_CACHE_ENDPOINT};
+use crate::request_signing::{hmac_sha256, sha256_hex, to_hex};
+
+/// The header of the unix time a request was signed at.
+pub const TIMESTAMP_HEADER: &str = "x-cache-timestamp";
+/// The header of the HMAC signature of a request.
+pub const SIGNATURE_HEADER: &str = "x-cache-signature";
+/// The header of the id of the signing key.
+pub const KEY_ID_HEADER: &str = "x-cache-key-id";
+
+lazy_static::lazy_static! {
+    /// The authentication of the remote cache requests, from `HYBRID_CACHE_TOKEN` and
+    /// `HYBRID_CACHE_HMAC_SECRET` until set.
+    static ref REMOTE_CACHE_CONFIG: RwLock<Arc<RemoteCacheConfig>> =
+        RwLock::new(Arc::new(RemoteCacheConfig::from_env()));
+}
+
+/// The endpoint and the authentication of the remote hybrid cache server.
+///
+/// With an HMAC secret, every request carries the unix time in `x-cache-timestamp` and the hex
+/// HMAC-SHA256 of `METHOD\npath?query\ntimestamp\nsha256_hex(body)` in `x-cache-signature`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct RemoteCacheConfig {
+    /// The base url of the server, `HYBRID_CACHE_ENDPOINT` when not set. An explicit endpoint
+    /// passed as `dump_remote` or `remote` still takes precedence.
+    pub endpoint: Option<String>,
+    /// The bearer token of the `Authorization` header.
+    pub bearer_token: Option<String>,
+    /// The secret signing the requests.
+    pub hmac_secret: Option<Vec<u8>>,
+    /// The id of the signing secret, sent in `x-cache-key-id`.
+    pub key_id: Option<String>,
+    /// The headers added to every request.
+    pub headers: Vec<(String, String)>,
+    /// Compress the dumped bodies with the cache compression, see
+    /// `crate::cache::compression::encode_body`. The server must keep the `content_encoding` of
+    /// the payloads to serve them back.
+    pub compress_bodies: bool,
+}
+
+impl std::fmt::Debug for RemoteCacheConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteCacheConfig")
+            .field("endpoint", &self.endpoint)
+            .field("bearer_token", &self.bearer_token.is_some())
+            .field("hmac_secret", &self.hmac_secret.is_some())
+            .field("key_id", &self.key_id)
+            .field(
+                "headers",
+                &self.headers.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            )
+            .field("compress_bodies", &self.compress_bodies)
+            .finish()
+    }
+}
+
+impl RemoteCacheConfig {
+    /// The config of the `HYBRID_CACHE_TOKEN`, `HYBRID_CACHE_HMAC_SECRET`,
+    /// `HYBRID_CACHE_KEY_ID` and `HYBRID_CACHE_COMPRESS` env vars.
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+
+        Self {
+            endpoint: None,
+            bearer_token: var("HYBRID_CACHE_TOKEN"),
+            hmac_secret: var("HYBRID_CACHE_HMAC_SECRET").map(String::into_bytes),
+            key_id: var("HYBRID_CACHE_KEY_ID"),
+            headers: Vec::new(),
+            compress_bodies: var("HYBRID_CACHE_COMPRESS")
+                .map_or(false, |v| v == "1" || v.eq_ignore_ascii_case("true")),
+        }
+    }
+
+    /// Authenticate with the bearer token.
+    pub fn with_bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// Sign the requests with the secret.
+    pub fn with_hmac_secret(mut self, secret: impl Into<Vec<u8>>, key_id: Option<String>) -> Self {
+        self.hmac_secret = Some(secret.into());
+        self.key_id = key_id;
+        self
+    }
+
+    /// Add a header to every request.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// The base url of the server, `remote` being `"true"` for the configured endpoint or an
+    /// explicit endpoint.
+    pub fn base_url<'a>(&'a self, remote: Option<&'a str>) -> &'a str {
+        match remote {
+            Some(remote) if remote != "true" => remote.trim_ascii(),
+            _ => self
+                .endpoint
+                .as_deref()
+                .unwrap_or(HYBRID_CACHE_ENDPOINT.as_str()),
+        }
+    }
+
+    /// The hex signature of the request, `None` without a secret.
+    pub fn signature(
+        &self,
+        method: &str,
+        path_and_query: &str,
+        timestamp: u64,
+        body: &[u8],
+    ) -> Option<String> {
+        let secret = self.hmac_secret.as_deref()?;
+        let payload = format!(
+            "{}\n{path_and_query}\n{timestamp}\n{}",
+            method.to_ascii_uppercase(),
+            sha256_hex(body)
+        );
+
+        Some(to_hex(&hmac_sha256(secret, payload.as_bytes())))
+    }
+
+    /// Add the headers, the token and the signature of the request.
+    pub fn authorize(
+        &self,
+        mut request: RequestBuilder,
+        method: &str,
+        url: &str,
+        body: &[u8],
+    ) -> RequestBuilder {
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                request = request.header(name, value);
+            }
+        }
+
+        if let Some(token) = self.bearer_token.as_deref() {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        if self.hmac_secret.is_some() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            let path_and_query = url::Url::parse(url)
+                .map(|url| match url.query() {
+                    Some(query) => format!("{}?{query}", url.path()),
+                    _ => url.path().to_string(),
+                })
+                .unwrap_or_default();
+
+            if let Some(signature) = self.signature(method, &path_and_query, timestamp, body) {
+                request = request
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(SIGNATURE_HEADER, signature);
+            }
+            if let Some(key_id) = self.key_id.as_deref() {
+                request = request.header(KEY_ID_HEADER, key_id);
+            }
+        }
+
+        request
+    }
+}
+
+/// Set the endpoint and the authentication of the remote cache requests.
+pub fn set_remote_cache_config(config: RemoteCacheConfig) {
+    if let Ok(mut current) = REMOTE_CACHE_CONFIG.write() {
+        *current = Arc::new(config);
+    }
+}
+
+/// The endpoint and the authentication of the remote cache requests.
+pub fn remote_cache_config() -> Arc<RemoteCacheConfig> {
+    REMOTE_CACHE_CONFIG
+        .read()
+        .map(|config| config.clone())
+        .unwrap_or_default()
+}
+
+/// An authenticated request to the remote cache server, with the JSON body when any.
+pub(crate) fn remote_request(
+    method: Method,
+    url: &str,
+    json_body: Option<Vec<u8>>,
+) -> RequestBuilder {
+    remote_request_with_body(method, url, json_body, "application/json")
+}
+
+/// An authenticated request to the remote cache server, with the body of the content type
+/// when any. The signature covers the body as sent, e.g. compressed.
+pub(crate) fn remote_request_with_body(
+    method: Method,
+    url: &str,
+    body: Option<Vec<u8>>,
+    content_type: &str,
+) -> RequestBuilder {
+    let config = remote_cache_config();
+    let body = body.unwrap_or_default();
+    let mut request = HYBRID_CACHE_CLIENT.request(method.clone(), url);
+
+    request = config.authorize(request, method.as_str(), url, &body);
+
+    if body.is_empty() {
+        request
+    } else {
+        request.header(CONTENT_TYPE, content_type).body(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_the_requests() {
+        let config = RemoteCacheConfig::default().with_hmac_secret("secret", Some("k1".into()));
+        let payload = format!("POST\n/cache/index\n1700000000\n{}", sha256_hex(b"{}"));
+
+        assert_eq!(
+            config.signature("post", "/cache/index", 1_700_000_000, b"{}"),
+            Some(to_hex(&hmac_sha256(b"secret", payload.as_bytes())))
+        );
+        assert_eq!(
+            RemoteCacheConfig::default().signature("GET", "/", 0, b""),
+            None
+        );
+    }
+
+    #[test]
+    fn resolves_the_base_url() {
+        let config = RemoteCacheConfig {
+            endpoint: Some("https://cache.internal".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(config.base_url(None), "https://cache.internal");
+        assert_eq!(config.base_url(Some("true")), "https://cache.internal");
+        assert_eq!(
+            config.base_url(Some(" http://other:8080 ")),
+            "http://other:8080"
+        );
+        assert_eq!(
+            RemoteCacheConfig::default().base_url(None),
+            HYBRID_CACHE_ENDPOINT.as_str()
+        );
+    }
+
+    #[test]
+    fn hides_the_secrets() {
+        let config = RemoteCacheConfig::default()
+            .with_bearer_token("token")
+            .with_header("x-tenant", "acme");
+        let debug = format!("{config:?}");
+
+        assert!(!debug.contains("token\""));
+        assert!(!debug.contains("acme"));
+    }
+}
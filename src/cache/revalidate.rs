@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+use http_cache_reqwest::CacheManager;
+use http_cache_semantics::{AfterResponse, CachePolicy};
+use http_global_cache::CACACHE_MANAGER;
+use reqwest::StatusCode;
+use spider_fingerprint::http;
+
+use crate::http::{convert_headers, HttpRequestLike, HttpResponseLike};
+
+lazy_static::lazy_static! {
+    /// Revalidate the stale entries with conditional requests, enabled until disabled.
+    static ref REVALIDATION: AtomicBool = AtomicBool::new(true);
+}
+
+/// Revalidate the stale entries of the cache interceptors with conditional requests, or refetch
+/// them in full.
+pub fn set_revalidation(enabled: bool) {
+    REVALIDATION.store(enabled, Ordering::Relaxed);
+}
+
+/// The stale entries are revalidated.
+pub fn revalidation_enabled() -> bool {
+    REVALIDATION.load(Ordering::Relaxed)
+}
+
+/// The conditional request headers of a stored response, `If-None-Match` for its `ETag` and
+/// `If-Modified-Since` for its `Last-Modified`. Empty without validators.
+pub fn conditional_headers(response_headers: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+
+    for (name, value) in response_headers {
+        let value = value.trim();
+
+        if value.is_empty() {
+            continue;
+        }
+
+        if name.eq_ignore_ascii_case("etag") {
+            headers.push(("If-None-Match".to_string(), value.to_string()));
+        } else if name.eq_ignore_ascii_case("last-modified") {
+            headers.push(("If-Modified-Since".to_string(), value.to_string()));
+        }
+    }
+
+    headers.sort();
+    headers
+}
+
+/// A stale entry revalidated by a conditional request in flight.
+#[derive(Debug)]
+pub(crate) struct PendingRevalidation {
+    /// The key the entry is stored under.
+    pub cache_key: String,
+    /// The url of the request.
+    pub url: String,
+    /// The headers of the request, without the conditional headers.
+    pub request_headers: HashMap<String, String>,
+    /// The stored response, decompressed.
+    pub response: http_cache_reqwest::HttpResponse,
+    /// The stored policy.
+    pub policy: CachePolicy,
+}
+
+impl PendingRevalidation {
+    /// Refresh the entry with the headers of the `304 Not Modified`, storing the updated policy.
+    /// Returns the body and the updated headers to serve.
+    pub(crate) async fn not_modified(
+        self,
+        response_headers: &HashMap<String, String>,
+    ) -> (Vec<u8>, HashMap<String, String>) {
+        let request = HttpRequestLike {
+            uri: self.url.parse().unwrap_or_default(),
+            method: http::method::Method::GET,
+            headers: convert_headers(&self.request_headers),
+        };
+        let not_modified = HttpResponseLike {
+            status: StatusCode::NOT_MODIFIED,
+            headers: convert_headers(response_headers),
+        };
+
+        let (policy, parts) =
+            match self
+                .policy
+                .after_response(&request, &not_modified, SystemTime::now())
+            {
+                AfterResponse::NotModified(policy, parts) => (policy, parts),
+                AfterResponse::Modified(policy, parts) => (policy, parts),
+            };
+
+        let mut response = self.response;
+
+        for (name, value) in parts.headers.iter() {
+            if let Ok(value) = value.to_str() {
+                response
+                    .headers
+                    .insert(name.as_str().to_string(), value.to_string());
+            }
+        }
+
+        let body = response.body.clone();
+        let headers = response.headers.clone();
+        let size = body.len();
+
+        super::compression::compress_response(&mut response);
+
+        match CACACHE_MANAGER
+            .put(self.cache_key.clone(), response, policy)
+            .await
+        {
+            Ok(_) => super::freshness::record_put(&self.cache_key, size).await,
+            Err(err) => super::journal::report_write_error(&self.cache_key, err),
+        }
+
+        (body, headers)
+    }
+}
+
+/// The revalidations in flight of an interceptor, by request id.
+#[derive(Debug, Default)]
+pub(crate) struct Revalidations {
+    pending: HashMap<String, PendingRevalidation>,
+}
+
+impl Revalidations {
+    /// Track the revalidation of the request.
+    pub(crate) fn insert(&mut self, request_id: &str, pending: PendingRevalidation) {
+        self.pending.insert(request_id.to_string(), pending);
+    }
+
+    /// The revalidation of the request, removed.
+    pub(crate) fn take(&mut self, request_id: &str) -> Option<PendingRevalidation> {
+        self.pending.remove(request_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_conditional_headers() {
+        let headers: HashMap<String, String> = [
+            ("ETag", "\"abc\""),
+            ("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            ("content-type", "text/html"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        assert_eq!(
+            conditional_headers(&headers),
+            vec![
+                (
+                    "If-Modified-Since".to_string(),
+                    "Wed, 21 Oct 2015 07:28:00 GMT".to_string()
+                ),
+                ("If-None-Match".to_string(), "\"abc\"".to_string()),
+            ]
+        );
+        assert!(conditional_headers(&HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn tracks_the_revalidations() {
+        let mut revalidations = Revalidations::default();
+
+        revalidations.insert(
+            "1",
+            PendingRevalidation {
+                cache_key: "GET:https://example.com/".into(),
+                url: "https://example.com/".into(),
+                request_headers: HashMap::new(),
+                response: http_cache_reqwest::HttpResponse {
+                    body: b"cached".to_vec(),
+                    headers: HashMap::new(),
+                    status: 200,
+                    url: url::Url::parse("https://example.com/").unwrap(),
+                    version: http_cache::HttpVersion::Http11,
+                },
+                policy: CachePolicy::new(&HttpRequestLike::default(), &HttpResponseLike::default()),
+            },
+        );
+
+        assert!(revalidations.take("2").is_none());
+        assert_eq!(revalidations.take("1").unwrap().response.body, b"cached");
+        assert!(revalidations.take("1").is_none());
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use http_cache_reqwest::CacheManager;
+
+use super::manager::{create_cache_key_raw, put_hybrid_cache, CACACHE_MANAGER};
+use crate::http::{HttpResponse, HttpVersion};
+use crate::page::ScreenshotParams;
+
+/// The query parameter of the url a screenshot is stored under in the hybrid cache.
+const SCREENSHOT_QUERY_KEY: &str = "__chromey_screenshot";
+
+/// The method a screenshot is stored under, keeping it apart from the page response.
+const SCREENSHOT_METHOD: &str = "SCREENSHOT";
+
+/// How screenshots are cached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenshotCacheOptions {
+    /// How long a stored screenshot is reused.
+    pub ttl: Duration,
+    /// The auth the screenshots are keyed with, e.g. for pages behind a login.
+    pub auth: Option<String>,
+    /// The cache site the screenshots are grouped under.
+    pub cache_site: String,
+    /// Dump the screenshots to the remote hybrid cache, `"true"` for `HYBRID_CACHE_ENDPOINT`
+    /// or the endpoint.
+    pub dump_remote: Option<String>,
+}
+
+impl Default for ScreenshotCacheOptions {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(24 * 60 * 60),
+            auth: None,
+            cache_site: String::new(),
+            dump_remote: None,
+        }
+    }
+}
+
+/// A screenshot, maybe returned from the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedScreenshot {
+    /// The image.
+    pub image: Vec<u8>,
+    /// The hash of the content the image was rendered from.
+    pub content_hash: String,
+    /// The image was returned from the cache without rendering.
+    pub hit: bool,
+}
+
+/// The hash of what a screenshot renders: the DOM snapshot, the viewport and the options.
+pub fn screenshot_content_hash(dom: &str, viewport: &str, params: &ScreenshotParams) -> String {
+    let mut hasher = blake3::Hasher::new();
+
+    hasher.update(b"screenshot|v1|");
+    hasher.update(dom.as_bytes());
+    hasher.update(b"|viewport=");
+    hasher.update(viewport.as_bytes());
+    hasher.update(b"|params=");
+    hasher.update(
+        serde_json::to_string(&params.cdp_params)
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+    hasher.update(format!("|{:?}|{:?}", params.full_page, params.omit_background).as_bytes());
+
+    hex::encode(hasher.finalize().as_bytes())
+}
+
+/// The url a screenshot of the page is stored under.
+pub fn screenshot_cache_url(page_url: &str, content_hash: &str) -> String {
+    match url::Url::parse(page_url) {
+        Ok(mut url) => {
+            url.set_fragment(None);
+            url.query_pairs_mut()
+                .append_pair(SCREENSHOT_QUERY_KEY, content_hash);
+            url.to_string()
+        }
+        Err(_) => format!("{page_url}?{SCREENSHOT_QUERY_KEY}={content_hash}"),
+    }
+}
+
+/// Get a fresh stored screenshot of the content.
+pub async fn get_cached_screenshot(cache_url: &str, auth: Option<&str>) -> Option<Vec<u8>> {
+    let cache_key = create_cache_key_raw(cache_url, Some(SCREENSHOT_METHOD), auth);
+
+    let cached = crate::runtime::timeout(Duration::from_millis(60), async {
+        CACACHE_MANAGER.get(&cache_key).await
+    })
+    .await;
+
+    match cached {
+        Ok(Ok(Some((http_response, cache_policy))))
+            if !super::freshness::is_stale(Some(cache_url), &cache_policy, SystemTime::now()) =>
+        {
+            super::freshness::touch(&cache_key);
+            super::compression::decompress_response(http_response)
+                .map(|http_response| http_response.body)
+        }
+        _ => None,
+    }
+}
+
+/// Store the screenshot of the content in the hybrid cache.
+pub async fn put_cached_screenshot(
+    cache_url: &str,
+    image: &[u8],
+    content_type: &str,
+    options: &ScreenshotCacheOptions,
+) {
+    let Ok(url) = url::Url::parse(cache_url) else {
+        return;
+    };
+
+    let cache_key =
+        create_cache_key_raw(cache_url, Some(SCREENSHOT_METHOD), options.auth.as_deref());
+
+    let headers = HashMap::from([
+        ("content-type".to_string(), content_type.to_string()),
+        (
+            "cache-control".to_string(),
+            format!("max-age={}", options.ttl.as_secs()),
+        ),
+    ]);
+
+    put_hybrid_cache(
+        &cache_key,
+        &options.cache_site,
+        HttpResponse {
+            body: image.to_vec(),
+            headers,
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        },
+        "GET",
+        HashMap::new(),
+        options.dump_remote.as_deref(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chromiumoxide_cdp::cdp::browser_protocol::page::CaptureScreenshotFormat;
+
+    #[test]
+    fn hashes_what_the_screenshot_renders() {
+        let png = ScreenshotParams::builder()
+            .format(CaptureScreenshotFormat::Png)
+            .build();
+        let full = ScreenshotParams::builder()
+            .format(CaptureScreenshotFormat::Png)
+            .full_page(true)
+            .build();
+
+        let hash = screenshot_content_hash("<html></html>", "[800,600,1]", &png);
+
+        assert_eq!(
+            hash,
+            screenshot_content_hash("<html></html>", "[800,600,1]", &png)
+        );
+        assert_ne!(
+            hash,
+            screenshot_content_hash("<html><p></p></html>", "[800,600,1]", &png)
+        );
+        assert_ne!(
+            hash,
+            screenshot_content_hash("<html></html>", "[800,600,2]", &png)
+        );
+        assert_ne!(
+            hash,
+            screenshot_content_hash("<html></html>", "[800,600,1]", &full)
+        );
+    }
+
+    #[test]
+    fn keys_screenshots_apart_from_the_page() {
+        let url = screenshot_cache_url("https://example.com/a?b=1#top", "abc");
+
+        assert_eq!(url, "https://example.com/a?b=1&__chromey_screenshot=abc");
+    }
+}
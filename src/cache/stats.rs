@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+lazy_static! {
+    /// The network metrics per cache site since the last report.
+    pub static ref SITE_STATS: dashmap::DashMap<String, SiteStats> = dashmap::DashMap::new();
+    /// The cache lookup and dump counters of the process.
+    static ref CACHE_STATS: CacheStats = CacheStats::default();
+}
+
+static STATS_REPORTER: OnceCell<()> = OnceCell::const_new();
+
+/// The network metrics of a cache site.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SiteStats {
+    /// The bytes received from the network.
+    pub bytes: u64,
+    /// The requests continued or fulfilled by the interception.
+    pub requests: u64,
+    /// The requests fulfilled from the cache.
+    pub cache_hits: u64,
+    /// The requests blocked by the interception.
+    pub blocked: u64,
+}
+
+impl SiteStats {
+    /// The share of the requests fulfilled from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / self.requests as f64
+        }
+    }
+
+    /// Add the metrics of another report.
+    pub fn merge(&mut self, other: &SiteStats) {
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.requests = self.requests.saturating_add(other.requests);
+        self.cache_hits = self.cache_hits.saturating_add(other.cache_hits);
+        self.blocked = self.blocked.saturating_add(other.blocked);
+    }
+}
+
+/// A site entry of the `/stats` payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteStatsPayload {
+    /// The cache site key.
+    pub website_key: String,
+    /// The metrics since the last report.
+    #[serde(flatten)]
+    pub stats: SiteStats,
+    /// The share of the requests fulfilled from the cache.
+    pub hit_rate: f64,
+}
+
+/// Payload shape for the remote hybrid cache server `/stats` endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsPayload {
+    /// The metrics per site.
+    pub sites: Vec<SiteStatsPayload>,
+}
+
+impl StatsPayload {
+    /// The payload of the metrics.
+    pub fn new(stats: HashMap<String, SiteStats>) -> Self {
+        let mut sites: Vec<SiteStatsPayload> = stats
+            .into_iter()
+            .map(|(website_key, stats)| SiteStatsPayload {
+                website_key,
+                hit_rate: stats.hit_rate(),
+                stats,
+            })
+            .collect();
+        sites.sort_by(|a, b| a.website_key.cmp(&b.website_key));
+
+        Self { sites }
+    }
+}
+
+/// The counters of the cache lookups and the remote dumps, updated by `get_cached_url`, the
+/// fetch cache interceptor and the remote dump worker. See [`cache_stats`].
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stale_hits: AtomicU64,
+    bytes_served: AtomicU64,
+    dumps: AtomicU64,
+    dump_failures: AtomicU64,
+    dumps_dropped: AtomicU64,
+}
+
+/// The values of the [`CacheStats`] counters at a time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStatsSnapshot {
+    /// The lookups served a fresh entry.
+    pub hits: u64,
+    /// The lookups without a usable entry.
+    pub misses: u64,
+    /// The lookups served a stale entry, allowed by the cache policy.
+    pub stale_hits: u64,
+    /// The body bytes served from the cache.
+    pub bytes_served: u64,
+    /// The jobs waiting in the remote dump queue.
+    pub dump_queue_depth: u64,
+    /// The jobs dumped by the remote dump worker.
+    pub dumps: u64,
+    /// The dumps timed out.
+    pub dump_failures: u64,
+    /// The jobs dropped on a full remote dump queue.
+    pub dumps_dropped: u64,
+}
+
+impl CacheStatsSnapshot {
+    /// The share of the lookups served from the cache, stale hits included.
+    pub fn hit_rate(&self) -> f64 {
+        let served = self.hits + self.stale_hits;
+        let lookups = served + self.misses;
+
+        if lookups == 0 {
+            0.0
+        } else {
+            served as f64 / lookups as f64
+        }
+    }
+}
+
+impl CacheStats {
+    /// Count a lookup serving the bytes of an entry.
+    pub fn record_hit(&self, bytes: usize, stale: bool) {
+        match stale {
+            true => self.stale_hits.fetch_add(1, Ordering::Relaxed),
+            _ => self.hits.fetch_add(1, Ordering::Relaxed),
+        };
+        self.bytes_served.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count a lookup without a usable entry.
+    pub fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a job of the remote dump worker.
+    pub fn record_dump(&self, ok: bool) {
+        match ok {
+            true => self.dumps.fetch_add(1, Ordering::Relaxed),
+            _ => self.dump_failures.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// Count a job dropped on a full queue.
+    pub fn record_dump_dropped(&self) {
+        self.dumps_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The current values of the counters.
+    pub fn snapshot(&self) -> CacheStatsSnapshot {
+        CacheStatsSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            stale_hits: self.stale_hits.load(Ordering::Relaxed),
+            bytes_served: self.bytes_served.load(Ordering::Relaxed),
+            dump_queue_depth: super::dump_remote::queue_depth() as u64,
+            dumps: self.dumps.load(Ordering::Relaxed),
+            dump_failures: self.dump_failures.load(Ordering::Relaxed),
+            dumps_dropped: self.dumps_dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reset the counters, e.g. between the phases of a crawl.
+    pub fn reset(&self) {
+        for counter in [
+            &self.hits,
+            &self.misses,
+            &self.stale_hits,
+            &self.bytes_served,
+            &self.dumps,
+            &self.dump_failures,
+            &self.dumps_dropped,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The cache counters of the process.
+pub fn cache_stats() -> &'static CacheStats {
+    &CACHE_STATS
+}
+
+/// Update the metrics of the cache site.
+pub fn record(site: &str, update: impl FnOnce(&mut SiteStats)) {
+    match SITE_STATS.get_mut(site) {
+        Some(mut stats) => update(&mut stats),
+        _ => update(&mut SITE_STATS.entry(site.to_string()).or_default()),
+    }
+}
+
+/// Take the metrics recorded since the last call.
+pub fn take_site_stats() -> HashMap<String, SiteStats> {
+    let sites: Vec<String> = SITE_STATS.iter().map(|e| e.key().clone()).collect();
+
+    sites
+        .into_iter()
+        .filter_map(|site| SITE_STATS.remove(&site))
+        .filter(|(_, stats)| *stats != SiteStats::default())
+        .collect()
+}
+
+/// Send the metrics recorded since the last report to the remote cache server. The metrics are
+/// kept for the next report when the server can not be reached.
+pub async fn report_stats(dump_remote: Option<&str>) {
+    let stats = take_site_stats();
+
+    if stats.is_empty() {
+        return;
+    }
+
+    let config = super::remote_config::remote_cache_config();
+    let base_url = config.base_url(dump_remote);
+
+    let endpoint = format!("{}/stats", base_url);
+
+    let body = serde_json::to_vec(&StatsPayload::new(stats.clone())).unwrap_or_default();
+    let result = super::remote_config::remote_request(reqwest::Method::POST, &endpoint, Some(body))
+        .send()
+        .await;
+
+    match result {
+        Ok(resp) if resp.status().is_success() => (),
+        Ok(resp) => {
+            tracing::warn!("remote cache stats: non-success status: {}", resp.status());
+        }
+        Err(err) => {
+            tracing::warn!(
+                "remote cache stats: failed to POST to {}: {}",
+                endpoint,
+                err
+            );
+            for (site, stats) in stats {
+                SITE_STATS.entry(site).or_default().merge(&stats);
+            }
+        }
+    }
+}
+
+/// Spawn the task reporting the metrics to the remote cache server every interval. Safe to call
+/// multiple times, only the first call starts the reporter.
+pub async fn init_stats_reporter(interval: Duration, dump_remote: Option<String>) {
+    STATS_REPORTER
+        .get_or_init(|| async move {
+            crate::runtime::spawn(async move {
+                let interval = interval.max(Duration::from_secs(1));
+
+                loop {
+                    crate::runtime::sleep(interval).await;
+                    report_stats(dump_remote.as_deref()).await;
+                }
+            });
+        })
+        .await;
+}
+
+pub fn default_stats_interval_ms() -> u64 {
+    std::env::var("HYBRID_CACHE_STATS_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30_000)
+}
+
+/// Init the stats reporter with the default endpoint and interval.
+pub async fn init_default_stats_reporter() {
+    init_stats_reporter(Duration::from_millis(default_stats_interval_ms()), None).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_payload() {
+        let mut stats = HashMap::new();
+        stats.insert(
+            "example.com".to_string(),
+            SiteStats {
+                bytes: 2048,
+                requests: 4,
+                cache_hits: 3,
+                blocked: 1,
+            },
+        );
+
+        let payload = serde_json::to_value(StatsPayload::new(stats)).unwrap();
+
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "sites": [{
+                    "website_key": "example.com",
+                    "bytes": 2048,
+                    "requests": 4,
+                    "cache_hits": 3,
+                    "blocked": 1,
+                    "hit_rate": 0.75
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn record_and_take() {
+        record("stats.test", |s| s.requests += 1);
+        record("stats.test", |s| {
+            s.requests += 1;
+            s.cache_hits += 1;
+        });
+
+        let stats = take_site_stats();
+
+        assert_eq!(stats["stats.test"].requests, 2);
+        assert_eq!(stats["stats.test"].hit_rate(), 0.5);
+        assert!(!SITE_STATS.contains_key("stats.test"));
+    }
+
+    #[test]
+    fn counts_the_lookups() {
+        let stats = CacheStats::default();
+        stats.record_hit(100, false);
+        stats.record_hit(50, true);
+        stats.record_miss();
+        stats.record_dump(true);
+        stats.record_dump_dropped();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.hits, 1);
+        assert_eq!(snapshot.stale_hits, 1);
+        assert_eq!(snapshot.bytes_served, 150);
+        assert_eq!(snapshot.dumps, 1);
+        assert_eq!(snapshot.dumps_dropped, 1);
+        assert!((snapshot.hit_rate() - 2.0 / 3.0).abs() < f64::EPSILON);
+
+        stats.reset();
+        assert_eq!(stats.snapshot().hits, 0);
+        assert_eq!(stats.snapshot().hit_rate(), 0.0);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Where the subresource references of a cached document point to, see
+/// `rewrite_base_tag_with_subresources`.
+#[derive(Debug, Clone, Default)]
+pub enum SubresourceRewrite {
+    /// The absolute urls of the references.
+    #[default]
+    Absolute,
+    /// The local paths of the references by absolute url, e.g. of the responses dumped from the
+    /// cache. The references without a path are made absolute.
+    LocalPaths(Arc<HashMap<String, String>>),
+}
+
+impl SubresourceRewrite {
+    /// The rewritten reference, `None` when left as is: the fragments and the `data:`,
+    /// `javascript:`, `mailto:`, `tel:`, `blob:` and `about:` urls.
+    pub fn rewrite(&self, base: &url::Url, reference: &str) -> Option<String> {
+        let reference = reference.trim();
+
+        if reference.is_empty() || reference.starts_with('#') {
+            return None;
+        }
+
+        if let Some((scheme, _)) = reference.split_once(':') {
+            if ["data", "javascript", "mailto", "tel", "blob", "about"]
+                .iter()
+                .any(|s| scheme.eq_ignore_ascii_case(s))
+            {
+                return None;
+            }
+        }
+
+        let absolute = base.join(reference).ok()?;
+
+        if let Self::LocalPaths(paths) = self {
+            let mut key = absolute.clone();
+            key.set_fragment(None);
+
+            if let Some(path) = paths.get(key.as_str()) {
+                return Some(match absolute.fragment() {
+                    Some(fragment) => format!("{path}#{fragment}"),
+                    _ => path.clone(),
+                });
+            }
+        }
+
+        Some(absolute.into())
+    }
+
+    /// Rewrite the urls of a `srcset`, keeping the descriptors.
+    pub fn rewrite_srcset(&self, base: &url::Url, srcset: &str) -> Option<String> {
+        // the commas of data urls can not be told apart from the separators.
+        if srcset.contains("data:") {
+            return None;
+        }
+
+        let candidates = srcset
+            .split(',')
+            .map(str::trim)
+            .filter(|candidate| !candidate.is_empty())
+            .map(|candidate| {
+                let (reference, descriptor) = match candidate.split_once(char::is_whitespace) {
+                    Some((reference, descriptor)) => (reference, Some(descriptor.trim())),
+                    _ => (candidate, None),
+                };
+                let reference = self
+                    .rewrite(base, reference)
+                    .unwrap_or_else(|| reference.to_string());
+
+                match descriptor {
+                    Some(descriptor) => format!("{reference} {descriptor}"),
+                    _ => reference,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Some(candidates.join(", "))
+    }
+
+    /// Rewrite the `url()` references of a stylesheet or a `style` attribute.
+    pub fn rewrite_css(&self, base: &url::Url, css: &str) -> Option<String> {
+        let lower = css.to_ascii_lowercase();
+        let mut out = String::with_capacity(css.len());
+        let mut last = 0;
+        let mut changed = false;
+
+        for (start, _) in lower.match_indices("url(") {
+            if start < last {
+                continue;
+            }
+
+            let open = start + 4;
+            let Some(close) = css[open..].find(')').map(|i| open + i) else {
+                break;
+            };
+
+            let inner = css[open..close].trim();
+            let (quote, reference) = match inner.chars().next() {
+                Some(q @ ('"' | '\'')) if inner.len() >= 2 && inner.ends_with(q) => {
+                    (Some(q), &inner[1..inner.len() - 1])
+                }
+                _ => (None, inner),
+            };
+
+            if let Some(rewritten) = self.rewrite(base, reference) {
+                let quote = quote.unwrap_or('"');
+
+                out.push_str(&css[last..open]);
+                out.push(quote);
+                out.push_str(&rewritten);
+                out.push(quote);
+                last = close;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return None;
+        }
+
+        out.push_str(&css[last..]);
+
+        Some(out)
+    }
+}
+
+/// The attributes holding a single reference, by element.
+pub(crate) const REFERENCE_ATTRIBUTES: &[(&str, &str)] = &[
+    ("a[href]", "href"),
+    ("area[href]", "href"),
+    ("link[href]", "href"),
+    ("img[src]", "src"),
+    ("script[src]", "src"),
+    ("iframe[src]", "src"),
+    ("frame[src]", "src"),
+    ("embed[src]", "src"),
+    ("source[src]", "src"),
+    ("track[src]", "src"),
+    ("audio[src]", "src"),
+    ("video[src]", "src"),
+    ("video[poster]", "poster"),
+    ("input[src]", "src"),
+    ("object[data]", "data"),
+    ("form[action]", "action"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> url::Url {
+        url::Url::parse("https://example.com/blog/post.html").unwrap()
+    }
+
+    #[test]
+    fn resolves_the_references() {
+        let rewrite = SubresourceRewrite::Absolute;
+
+        assert_eq!(
+            rewrite.rewrite(&base(), "../img/a.png").as_deref(),
+            Some("https://example.com/img/a.png")
+        );
+        assert_eq!(
+            rewrite
+                .rewrite(&base(), "//cdn.example.com/app.js")
+                .as_deref(),
+            Some("https://cdn.example.com/app.js")
+        );
+        assert!(rewrite.rewrite(&base(), "#comments").is_none());
+        assert!(rewrite
+            .rewrite(&base(), "data:image/png;base64,AA")
+            .is_none());
+        assert!(rewrite.rewrite(&base(), "JavaScript:void(0)").is_none());
+    }
+
+    #[test]
+    fn maps_the_local_paths() {
+        let rewrite = SubresourceRewrite::LocalPaths(Arc::new(HashMap::from([(
+            "https://example.com/style.css".to_string(),
+            "assets/style.css".to_string(),
+        )])));
+
+        assert_eq!(
+            rewrite.rewrite(&base(), "/style.css#x").as_deref(),
+            Some("assets/style.css#x")
+        );
+        assert_eq!(
+            rewrite.rewrite(&base(), "/other.css").as_deref(),
+            Some("https://example.com/other.css")
+        );
+    }
+
+    #[test]
+    fn rewrites_the_srcsets_and_the_css() {
+        let rewrite = SubresourceRewrite::Absolute;
+
+        assert_eq!(
+            rewrite
+                .rewrite_srcset(&base(), "a.png 1x,  b.png 2x")
+                .as_deref(),
+            Some("https://example.com/blog/a.png 1x, https://example.com/blog/b.png 2x")
+        );
+        assert_eq!(
+            rewrite
+                .rewrite_css(
+                    &base(),
+                    "body{background:URL('bg.png')} @font-face{src:url(/f.woff2)} i{background:url(data:x)}"
+                )
+                .as_deref(),
+            Some("body{background:URL('https://example.com/blog/bg.png')} @font-face{src:url(\"https://example.com/f.woff2\")} i{background:url(data:x)}")
+        );
+        assert!(rewrite.rewrite_css(&base(), "a{color:red}").is_none());
+    }
+}
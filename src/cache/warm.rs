@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    LoadNetworkResourceOptions, LoadNetworkResourceParams,
+};
+use futures::StreamExt;
+use http_cache_reqwest::CacheManager;
+
+use super::manager::{create_cache_key_raw, BasicCachePolicy, CACACHE_MANAGER};
+use crate::http::{HttpResponse, HttpVersion};
+use crate::page::Page;
+
+/// How the urls are fetched and stored by `warm_urls`.
+#[derive(Debug, Clone)]
+pub struct WarmPolicy {
+    /// Fetch through the network stack of this page, with its cookies and user agent. The urls
+    /// are fetched with a plain http client when `None`.
+    pub page: Option<Page>,
+    /// The site the entries are grouped under in the remote cache.
+    pub cache_site: String,
+    /// The auth part of the cache keys.
+    pub auth: Option<String>,
+    /// Dump the entries to this remote cache as well.
+    pub dump_remote: Option<String>,
+    /// Skip the urls with a fresh entry.
+    pub skip_fresh: bool,
+    /// The timeout of a fetch.
+    pub timeout: Duration,
+}
+
+impl Default for WarmPolicy {
+    fn default() -> Self {
+        Self {
+            page: None,
+            cache_site: String::new(),
+            auth: None,
+            dump_remote: None,
+            skip_fresh: true,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl WarmPolicy {
+    /// Fetch the urls through the page.
+    pub fn with_page(mut self, page: Page) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// Dump the entries to the remote cache.
+    pub fn with_dump_remote(mut self, dump_remote: impl Into<String>) -> Self {
+        self.dump_remote = Some(dump_remote.into());
+        self
+    }
+}
+
+/// The outcome of warming a url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarmOutcome {
+    /// The response was stored.
+    Stored,
+    /// A fresh entry was already stored.
+    Fresh,
+    /// The response was not cacheable, with its status.
+    Skipped(u16),
+    /// The fetch failed.
+    Failed(String),
+}
+
+/// The outcomes of `warm_urls`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarmReport {
+    /// The outcome of every url, in order of completion.
+    pub outcomes: Vec<(String, WarmOutcome)>,
+}
+
+impl WarmReport {
+    /// The number of urls stored.
+    pub fn stored(&self) -> usize {
+        self.count(|outcome| *outcome == WarmOutcome::Stored)
+    }
+
+    /// The number of urls already fresh.
+    pub fn fresh(&self) -> usize {
+        self.count(|outcome| *outcome == WarmOutcome::Fresh)
+    }
+
+    /// The number of urls failed.
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, WarmOutcome::Failed(_)))
+    }
+
+    fn count(&self, f: impl Fn(&WarmOutcome) -> bool) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| f(outcome))
+            .count()
+    }
+}
+
+/// Prefetch the urls into the local cache, and the remote cache with `WarmPolicy::dump_remote`,
+/// before a crawl, e.g. to prime the fonts and frameworks shared across the pages. At most
+/// `concurrency` urls are fetched at once.
+pub async fn warm_urls<I>(urls: I, concurrency: usize, policy: &WarmPolicy) -> WarmReport
+where
+    I: IntoIterator,
+    I::Item: Into<String>,
+{
+    let outcomes = futures::stream::iter(urls.into_iter().map(Into::into))
+        .map(|url: String| async move {
+            let outcome = warm_url(&url, policy).await;
+            (url, outcome)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    WarmReport { outcomes }
+}
+
+/// Warm a single url.
+async fn warm_url(url: &str, policy: &WarmPolicy) -> WarmOutcome {
+    let Ok(parsed) = url::Url::parse(url) else {
+        return WarmOutcome::Failed("invalid url".into());
+    };
+
+    let cache_key = create_cache_key_raw(url, None, policy.auth.as_deref());
+
+    if policy.skip_fresh {
+        if let Ok(Some((_, stored))) = CACACHE_MANAGER.get(&cache_key).await {
+            if BasicCachePolicy::Normal.allows_cached_url(Some(url), &stored) {
+                return WarmOutcome::Fresh;
+            }
+        }
+    }
+
+    let fetched = crate::runtime::timeout(policy.timeout, async {
+        match &policy.page {
+            Some(page) => fetch_with_page(page, url).await,
+            _ => fetch_with_client(url).await,
+        }
+    })
+    .await
+    .unwrap_or_else(|_| Err("timed out".into()));
+
+    let (status, headers, body, version) = match fetched {
+        Ok(fetched) => fetched,
+        Err(err) => return WarmOutcome::Failed(err),
+    };
+
+    if !is_cacheable_status(status) {
+        return WarmOutcome::Skipped(status);
+    }
+
+    super::manager::put_hybrid_cache(
+        &cache_key,
+        &policy.cache_site,
+        HttpResponse {
+            body,
+            headers,
+            status,
+            url: parsed,
+            version,
+        },
+        "GET",
+        HashMap::new(),
+        policy.dump_remote.as_deref(),
+    )
+    .await;
+
+    WarmOutcome::Stored
+}
+
+/// The statuses stored, the redirects and the errors are left to the crawl.
+fn is_cacheable_status(status: u16) -> bool {
+    matches!(status, 200 | 203 | 204 | 300 | 404 | 410)
+}
+
+type Fetched = Result<(u16, HashMap<String, String>, Vec<u8>, HttpVersion), String>;
+
+/// Fetch the url with the network stack of the page, outside of its documents.
+async fn fetch_with_page(page: &Page, url: &str) -> Fetched {
+    let mut params =
+        LoadNetworkResourceParams::new(url, LoadNetworkResourceOptions::new(false, true));
+    params.frame_id = page.mainframe().await.map_err(|err| err.to_string())?;
+
+    let resource = page
+        .execute(params)
+        .await
+        .map_err(|err| err.to_string())?
+        .result
+        .resource;
+
+    if !resource.success {
+        return Err(resource
+            .net_error_name
+            .unwrap_or_else(|| "network error".into()));
+    }
+
+    let status = resource.http_status_code.unwrap_or_default() as u16;
+    let headers = resource
+        .headers
+        .as_ref()
+        .map(super::manager::headers_to_string_map)
+        .unwrap_or_default();
+    let body = match resource.stream {
+        Some(handle) => page
+            .read_stream(handle)
+            .read_to_end()
+            .await
+            .map_err(|err| err.to_string())?,
+        _ => Vec::new(),
+    };
+
+    Ok((status, headers, body, HttpVersion::Http11))
+}
+
+/// Fetch the url with a plain http client.
+async fn fetch_with_client(url: &str) -> Fetched {
+    let response = crate::http_client::HTTP_CLIENT
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let status = response.status().as_u16();
+    let version = match response.version() {
+        reqwest::Version::HTTP_09 => HttpVersion::Http09,
+        reqwest::Version::HTTP_10 => HttpVersion::Http10,
+        reqwest::Version::HTTP_2 => HttpVersion::H2,
+        reqwest::Version::HTTP_3 => HttpVersion::H3,
+        _ => HttpVersion::Http11,
+    };
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.as_str().to_string(), value.to_string()))
+        })
+        .collect();
+    let body = response.bytes().await.map_err(|err| err.to_string())?;
+
+    Ok((status, headers, body.to_vec(), version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_outcomes() {
+        let report = WarmReport {
+            outcomes: vec![
+                ("https://a.com/app.js".into(), WarmOutcome::Stored),
+                ("https://a.com/font.woff2".into(), WarmOutcome::Fresh),
+                ("https://a.com/old.css".into(), WarmOutcome::Skipped(301)),
+                (
+                    "https://b.com/".into(),
+                    WarmOutcome::Failed("timed out".into()),
+                ),
+            ],
+        };
+
+        assert_eq!(report.stored(), 1);
+        assert_eq!(report.fresh(), 1);
+        assert_eq!(report.failed(), 1);
+    }
+
+    #[test]
+    fn stores_the_final_responses() {
+        assert!(is_cacheable_status(200));
+        assert!(is_cacheable_status(404));
+        assert!(!is_cacheable_status(302));
+        assert!(!is_cacheable_status(503));
+    }
+
+    #[tokio::test]
+    async fn fails_the_invalid_urls() {
+        let report = warm_urls(["not a url"], 4, &WarmPolicy::default()).await;
+
+        assert_eq!(report.failed(), 1);
+    }
+}
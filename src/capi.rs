@@ -0,0 +1,461 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use futures::{FutureExt, StreamExt};
+use tokio::task::JoinHandle;
+
+use crate::browser::{Browser, BrowserConfig};
+use crate::page::{Page, ScreenshotParams};
+
+lazy_static::lazy_static! {
+    /// The runtime driving the browsers of the C API.
+    static ref RUNTIME: tokio::runtime::Runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("chromey-capi")
+        .build()
+        .expect("runtime to build");
+}
+
+thread_local! {
+    /// The error of the last failed call on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Success.
+pub const CHROMEY_OK: c_int = 0;
+/// The call failed, see `chromey_last_error`.
+pub const CHROMEY_ERR: c_int = -1;
+
+/// A browser owned by the C caller.
+#[derive(Debug)]
+pub struct ChromeyBrowser {
+    browser: Browser,
+    handler: JoinHandle<()>,
+}
+
+/// A page owned by the C caller.
+#[derive(Debug)]
+pub struct ChromeyPage {
+    page: Page,
+}
+
+/// The completion callback of an async call with the status and the user data.
+pub type ChromeyCallback = extern "C" fn(status: c_int, user_data: *mut c_void);
+
+/// The user data handed back to the callback on the runtime.
+struct UserData(*mut c_void);
+
+// the user data is only handed back to the callback, on a runtime thread. The caller guarantees
+// the data can be used from another thread, see `chromey_page_goto_async`.
+unsafe impl Send for UserData {}
+
+/// Remember the error of the failed call.
+fn set_last_error(err: impl std::fmt::Display) {
+    let message = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// The message of a panic payload.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Run the body of an exported call, a panic fails the call with `on_panic` rather than
+/// unwinding into the C caller.
+fn guard<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|panic| {
+        set_last_error(format!("panicked: {}", panic_message(&*panic)));
+        on_panic
+    })
+}
+
+/// Block the calling thread on the future. A call from a runtime thread, e.g. from a callback,
+/// fails instead of panicking on the nested runtime.
+fn block_on<T, E: std::fmt::Display>(fut: impl Future<Output = Result<T, E>>) -> Result<T, String> {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err("the C API can not block a runtime thread, e.g. in a callback".into());
+    }
+
+    RUNTIME.block_on(fut).map_err(|err| err.to_string())
+}
+
+/// The string of a C string argument.
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Option<&'a str> {
+    if value.is_null() {
+        set_last_error(format!("`{name}` is null"));
+        return None;
+    }
+
+    match CStr::from_ptr(value).to_str() {
+        Ok(value) => Some(value),
+        Err(err) => {
+            set_last_error(format!("`{name}` is not utf-8: {err}"));
+            None
+        }
+    }
+}
+
+/// Box the result into a pointer owned by the caller, null on error.
+fn into_raw<T, E: std::fmt::Display>(result: Result<T, E>) -> *mut T {
+    match result {
+        Ok(value) => Box::into_raw(Box::new(value)),
+        Err(err) => {
+            set_last_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The status of the result.
+fn status<T, E: std::fmt::Display>(result: Result<T, E>) -> c_int {
+    match result {
+        Ok(_) => CHROMEY_OK,
+        Err(err) => {
+            set_last_error(err);
+            CHROMEY_ERR
+        }
+    }
+}
+
+/// Drive the handler of the browser on the runtime.
+fn spawn_browser(browser: Browser, mut handler: crate::handler::Handler) -> ChromeyBrowser {
+    let handler = RUNTIME.spawn(async move {
+        while let Some(event) = handler.next().await {
+            if event.is_err() {
+                break;
+            }
+        }
+    });
+
+    ChromeyBrowser { browser, handler }
+}
+
+/// The error of the last failed call on the calling thread, null when no call failed. The
+/// string is valid until the next failed call on the thread.
+#[no_mangle]
+pub extern "C" fn chromey_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|last| {
+            last.borrow()
+                .as_ref()
+                .map_or(ptr::null(), |message| message.as_ptr())
+        })
+    })
+}
+
+/// Launch a new chromium instance, null on error.
+#[no_mangle]
+pub extern "C" fn chromey_browser_launch(headless: bool) -> *mut ChromeyBrowser {
+    guard(ptr::null_mut(), || {
+        let mut builder = BrowserConfig::builder();
+
+        if !headless {
+            builder = builder.with_head();
+        }
+
+        into_raw(builder.build().and_then(|config| {
+            block_on(Browser::launch(config))
+                .map(|(browser, handler)| spawn_browser(browser, handler))
+        }))
+    })
+}
+
+/// Connect to a running chromium instance by its debug url, null on error.
+///
+/// # Safety
+///
+/// `url` must be a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_browser_connect(url: *const c_char) -> *mut ChromeyBrowser {
+    guard(ptr::null_mut(), || {
+        let url = match str_arg(url, "url") {
+            Some(url) => url,
+            _ => return ptr::null_mut(),
+        };
+
+        into_raw(
+            block_on(Browser::connect(url))
+                .map(|(browser, handler)| spawn_browser(browser, handler)),
+        )
+    })
+}
+
+/// Close the browser and free it, the pages of the browser must be freed before.
+///
+/// # Safety
+///
+/// `browser` must be a browser returned by `chromey_browser_launch` or `chromey_browser_connect`
+/// that is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_browser_close(browser: *mut ChromeyBrowser) -> c_int {
+    guard(CHROMEY_ERR, || {
+        if browser.is_null() {
+            return CHROMEY_OK;
+        }
+
+        let mut browser = Box::from_raw(browser);
+
+        let result = block_on(async {
+            let closed = browser.browser.close().await;
+            let _ = browser.browser.wait().await;
+            closed
+        });
+
+        browser.handler.abort();
+
+        status(result)
+    })
+}
+
+/// Open a new page of the url, null on error.
+///
+/// # Safety
+///
+/// `browser` must be a live browser and `url` a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_browser_new_page(
+    browser: *const ChromeyBrowser,
+    url: *const c_char,
+) -> *mut ChromeyPage {
+    guard(ptr::null_mut(), || {
+        let (browser, url) = match (browser.as_ref(), str_arg(url, "url")) {
+            (Some(browser), Some(url)) => (browser, url),
+            (None, _) => {
+                set_last_error("`browser` is null");
+                return ptr::null_mut();
+            }
+            _ => return ptr::null_mut(),
+        };
+
+        into_raw(block_on(browser.browser.new_page(url)).map(|page| ChromeyPage { page }))
+    })
+}
+
+/// Navigate the page to the url and wait for the navigation to complete.
+///
+/// # Safety
+///
+/// `page` must be a live page and `url` a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_page_goto(page: *const ChromeyPage, url: *const c_char) -> c_int {
+    guard(CHROMEY_ERR, || {
+        let (page, url) = match (page.as_ref(), str_arg(url, "url")) {
+            (Some(page), Some(url)) => (page, url),
+            (None, _) => {
+                set_last_error("`page` is null");
+                return CHROMEY_ERR;
+            }
+            _ => return CHROMEY_ERR,
+        };
+
+        status(block_on(page.page.goto(url)))
+    })
+}
+
+/// Navigate the page to the url without blocking, calling the callback on a runtime thread with
+/// the status once the navigation completed. Returns `CHROMEY_ERR` when the call could not start.
+/// The callback must not call the blocking functions of the API, they fail on a runtime thread.
+///
+/// # Safety
+///
+/// `page` must be a live page, `url` a valid nul terminated string and `user_data` must be
+/// usable from another thread than the caller, the callback runs on a runtime thread.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_page_goto_async(
+    page: *const ChromeyPage,
+    url: *const c_char,
+    callback: ChromeyCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(CHROMEY_ERR, || {
+        let (page, url) = match (page.as_ref(), str_arg(url, "url")) {
+            (Some(page), Some(url)) => (page.page.clone(), url.to_string()),
+            (None, _) => {
+                set_last_error("`page` is null");
+                return CHROMEY_ERR;
+            }
+            _ => return CHROMEY_ERR,
+        };
+
+        let user_data = UserData(user_data);
+
+        RUNTIME.spawn(async move {
+            let user_data = user_data;
+            let result = match AssertUnwindSafe(page.goto(url)).catch_unwind().await {
+                Ok(result) => result.map(|_| ()).map_err(|err| err.to_string()),
+                Err(panic) => Err(format!("panicked: {}", panic_message(&*panic))),
+            };
+            callback(status(result), user_data.0);
+        });
+
+        CHROMEY_OK
+    })
+}
+
+/// The html of the page, null on error. Free it with `chromey_string_free`.
+///
+/// # Safety
+///
+/// `page` must be a live page.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_page_content(page: *const ChromeyPage) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let page = match page.as_ref() {
+            Some(page) => page,
+            _ => {
+                set_last_error("`page` is null");
+                return ptr::null_mut();
+            }
+        };
+
+        match block_on(page.page.content())
+            .and_then(|html| CString::new(html).map_err(|err| err.to_string()))
+        {
+            Ok(html) => html.into_raw(),
+            Err(err) => {
+                set_last_error(err);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// A png screenshot of the page, null on error. The length is written to `len`, free it with
+/// `chromey_bytes_free`.
+///
+/// # Safety
+///
+/// `page` must be a live page and `len` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_page_screenshot(
+    page: *const ChromeyPage,
+    full_page: bool,
+    len: *mut usize,
+) -> *mut u8 {
+    guard(ptr::null_mut(), || {
+        let page = match page.as_ref() {
+            Some(page) if !len.is_null() => page,
+            _ => {
+                set_last_error("`page` or `len` is null");
+                return ptr::null_mut();
+            }
+        };
+
+        match block_on(
+            page.page
+                .screenshot(ScreenshotParams::builder().full_page(full_page).build()),
+        ) {
+            Ok(bytes) => {
+                let bytes = bytes.into_boxed_slice();
+                *len = bytes.len();
+                Box::into_raw(bytes) as *mut u8
+            }
+            Err(err) => {
+                set_last_error(err);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Close the page and free it.
+///
+/// # Safety
+///
+/// `page` must be a page returned by `chromey_browser_new_page` that is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_page_close(page: *mut ChromeyPage) -> c_int {
+    guard(CHROMEY_ERR, || {
+        if page.is_null() {
+            return CHROMEY_OK;
+        }
+
+        let page = Box::from_raw(page);
+
+        status(block_on(page.page.close()))
+    })
+}
+
+/// Free a string returned by the C API.
+///
+/// # Safety
+///
+/// `value` must be a string returned by the C API that is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_string_free(value: *mut c_char) {
+    guard((), || {
+        if !value.is_null() {
+            drop(CString::from_raw(value));
+        }
+    })
+}
+
+/// Free bytes returned by the C API.
+///
+/// # Safety
+///
+/// `bytes` and `len` must be returned by the C API and the bytes not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chromey_bytes_free(bytes: *mut u8, len: usize) {
+    guard((), || {
+        if !bytes.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_arguments_set_last_error() {
+        let url = CString::new("about:blank").unwrap();
+        let page = unsafe { chromey_browser_new_page(ptr::null(), url.as_ptr()) };
+
+        assert!(page.is_null());
+
+        let error = unsafe { CStr::from_ptr(chromey_last_error()) };
+
+        assert_eq!(error.to_str(), Ok("`browser` is null"));
+    }
+
+    #[test]
+    fn panics_fail_the_call() {
+        let status = guard(CHROMEY_ERR, || -> c_int { panic!("boom") });
+
+        assert_eq!(status, CHROMEY_ERR);
+
+        let error = unsafe { CStr::from_ptr(chromey_last_error()) };
+
+        assert_eq!(error.to_str(), Ok("panicked: boom"));
+    }
+
+    #[test]
+    fn blocking_from_the_runtime_fails() {
+        let result = RUNTIME.block_on(async { block_on(async { Ok::<_, String>(1) }) });
+
+        assert!(result
+            .unwrap_err()
+            .contains("can not block a runtime thread"));
+        assert_eq!(block_on(async { Ok::<_, String>(1) }), Ok(1));
+    }
+
+    #[test]
+    fn free_round_trip() {
+        let value = CString::new("<html></html>").unwrap().into_raw();
+        unsafe { chromey_string_free(value) };
+
+        let bytes = vec![1u8, 2, 3].into_boxed_slice();
+        let len = bytes.len();
+        unsafe { chromey_bytes_free(Box::into_raw(bytes) as *mut u8, len) };
+    }
+}
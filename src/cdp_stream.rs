@@ -0,0 +1,239 @@
+//! Large CDP payloads, e.g. pdfs, traces or response bodies, read in chunks with `IO.read`
+//! instead of one base64 string holding the whole payload in the message.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use chromiumoxide_cdp::cdp::browser_protocol::io::{CloseParams, ReadParams, StreamHandle};
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::AsyncWriteExt;
+
+use crate::error::Result;
+use crate::handler::PageInner;
+use crate::utils;
+
+/// The bytes requested per `IO.read`.
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The chunks of a payload, read from a stream handle of the browser or already received.
+///
+/// The handle is closed once the payload is read, on the first error or, with a tokio runtime,
+/// when the stream is dropped before the end.
+pub struct CdpStream {
+    /// The handle the chunks are read from.
+    handle: Option<StreamHandle>,
+    /// The page to close the handle with.
+    page: Option<Arc<PageInner>>,
+    /// The handle was closed.
+    closed: Arc<AtomicBool>,
+    chunks: BoxStream<'static, Result<Vec<u8>>>,
+}
+
+impl std::fmt::Debug for CdpStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CdpStream")
+            .field("handle", &self.handle)
+            .field("closed", &self.closed.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// The state of the reads of a handle.
+struct Reader {
+    page: Arc<PageInner>,
+    handle: StreamHandle,
+    chunk_size: usize,
+    closed: Arc<AtomicBool>,
+}
+
+impl Reader {
+    /// The next chunk, `None` once the handle was read to the end.
+    async fn next_chunk(&self) -> Option<Result<Vec<u8>>> {
+        if self.closed.load(Ordering::Acquire) {
+            return None;
+        }
+
+        loop {
+            let params = ReadParams {
+                handle: self.handle.clone(),
+                offset: None,
+                size: Some(self.chunk_size as i64),
+            };
+
+            let read = match self.page.execute(params).await {
+                Ok(read) => read.result,
+                Err(err) => {
+                    self.close().await;
+                    return Some(Err(err));
+                }
+            };
+
+            let chunk = if read.base64_encoded.unwrap_or_default() {
+                match utils::base64::decode(&read.data) {
+                    Ok(chunk) => chunk,
+                    Err(err) => {
+                        self.close().await;
+                        return Some(Err(err.into()));
+                    }
+                }
+            } else {
+                read.data.into_bytes()
+            };
+
+            if read.eof {
+                self.close().await;
+            }
+
+            if !chunk.is_empty() {
+                return Some(Ok(chunk));
+            }
+            if read.eof {
+                return None;
+            }
+        }
+    }
+
+    async fn close(&self) {
+        if !self.closed.swap(true, Ordering::AcqRel) {
+            let _ = self
+                .page
+                .execute(CloseParams::new(self.handle.clone()))
+                .await;
+        }
+    }
+}
+
+impl CdpStream {
+    /// Read the handle in chunks of [`DEFAULT_CHUNK_SIZE`] bytes.
+    pub(crate) fn new(page: Arc<PageInner>, handle: StreamHandle) -> Self {
+        Self::with_chunk_size(page, handle, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Read the handle in chunks of the size.
+    pub(crate) fn with_chunk_size(
+        page: Arc<PageInner>,
+        handle: StreamHandle,
+        chunk_size: usize,
+    ) -> Self {
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader = Reader {
+            page: page.clone(),
+            handle: handle.clone(),
+            chunk_size: chunk_size.max(1),
+            closed: closed.clone(),
+        };
+
+        let chunks = futures::stream::unfold(reader, |reader| async move {
+            let chunk = reader.next_chunk().await?;
+            Some((chunk, reader))
+        })
+        .boxed();
+
+        Self {
+            handle: Some(handle),
+            page: Some(page),
+            closed,
+            chunks,
+        }
+    }
+
+    /// A payload already received whole, e.g. a screenshot which the browser can't stream.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let chunks = if bytes.is_empty() {
+            futures::stream::empty().boxed()
+        } else {
+            futures::stream::once(async move { Ok(bytes) }).boxed()
+        };
+
+        Self {
+            handle: None,
+            page: None,
+            closed: Arc::new(AtomicBool::new(true)),
+            chunks,
+        }
+    }
+
+    /// The handle the chunks are read from, `None` for a payload received whole.
+    pub fn handle(&self) -> Option<&StreamHandle> {
+        self.handle.as_ref()
+    }
+
+    /// Read the whole payload.
+    pub async fn read_to_end(mut self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        while let Some(chunk) = self.next().await {
+            out.extend_from_slice(&chunk?);
+        }
+
+        Ok(out)
+    }
+
+    /// Write the payload to the file chunk by chunk, returning the bytes written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save(mut self, output: impl AsRef<Path>) -> Result<u64> {
+        let mut file = tokio::fs::File::create(output.as_ref()).await?;
+        let mut written = 0;
+
+        while let Some(chunk) = self.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+
+        Ok(written)
+    }
+}
+
+impl Stream for CdpStream {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.chunks.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for CdpStream {
+    fn drop(&mut self) {
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        // Closing is async, release the handle of a stream dropped before the end in the
+        // background.
+        if let (Some(page), Some(handle), Ok(runtime)) = (
+            self.page.take(),
+            self.handle.take(),
+            tokio::runtime::Handle::try_current(),
+        ) {
+            runtime.spawn(async move {
+                let _ = page.execute(CloseParams::new(handle)).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_payloads_received_whole() {
+        let stream = CdpStream::from_bytes(b"%PDF-1.7".to_vec());
+
+        assert!(stream.handle().is_none());
+        assert_eq!(stream.read_to_end().await.unwrap(), b"%PDF-1.7");
+        assert!(CdpStream::from_bytes(Vec::new())
+            .read_to_end()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
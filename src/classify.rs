@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::handler::http::HttpRequest;
+use crate::page::Page;
+use crate::ArcHttpRequest;
+
+/// Read the signals of the document telling a real page apart from an error page, a challenge,
+/// a parked domain or a soft redirect.
+const DOCUMENT_SIGNALS_JS: &str = r###"(()=>{const t=((document.body&&document.body.innerText)||'').trim();const title=document.title||'';const h1=(document.querySelector('h1')||{}).textContent||'';const q=s=>{try{return!!document.querySelector(s)}catch(e){return false}};let refresh=null;const m=document.querySelector('meta[http-equiv="refresh" i]');if(m){const c=m.getAttribute('content')||'';const r=/^\s*(\d+)\s*[;,]?\s*(url\s*=\s*)?['"]?([^'"]*)/i.exec(c);if(r&&r[3]&&parseInt(r[1],10)<=5){try{refresh=new URL(r[3],location.href).href}catch(e){}}}const head=(title+' '+h1+' '+t.slice(0,3000));return{title,text_length:t.length,links:document.links.length,meta_refresh:refresh,challenge_dom:q('#challenge-form,#cf-challenge-running,#challenge-running,#px-captcha,.g-recaptcha,.h-captcha,iframe[src*="captcha-delivery.com"],iframe[src*="challenges.cloudflare.com"]')||/^(just a moment|attention required|pardon our interruption|access denied)/i.test(title.trim()),error_copy:/\b(404|page not found|not found|410|gone|500|internal server error|502|bad gateway|503|service unavailable|an error occurred)\b/i.test(title+' '+h1),parked_copy:/(this domain (name )?(is|may be) for sale|buy this domain|domain is parked|parked free|domain (has )?expired|is for sale!)/i.test(head)||q('a[href*="sedoparking.com"],a[href*="afternic.com"],a[href*="dan.com/buy-domain"],script[src*="parkingcrew"],script[src*="bodis.com"]')}})()"###;
+
+/// The hosts of the domain parking services.
+const PARKING_HOSTS: &[&str] = &[
+    "sedoparking.com",
+    "parkingcrew.net",
+    "bodis.com",
+    "above.com",
+    "hugedomains.com",
+    "afternic.com",
+    "dan.com",
+    "parklogic.com",
+];
+
+/// What the final document of a navigation is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentClass {
+    /// A real page.
+    Content,
+    /// An error page, e.g. a `404` or a soft `404` served with a `200`.
+    ErrorPage,
+    /// A bot challenge or captcha.
+    Challenge,
+    /// A parked or for sale domain.
+    Parked,
+    /// A document sending to another page, e.g. with a meta refresh, or a deep link redirected
+    /// to the home page.
+    SoftRedirect,
+}
+
+/// The signals of the final document of a navigation, from its response and its DOM.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentSignals {
+    /// The url requested, before the redirects.
+    #[serde(default)]
+    pub requested_url: Option<String>,
+    /// The url of the final response.
+    #[serde(default)]
+    pub final_url: Option<String>,
+    /// The status of the final response.
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// The challenge provider of the response, e.g. `cloudflare`.
+    #[serde(default)]
+    pub challenge_provider: Option<String>,
+    /// The title of the document.
+    #[serde(default)]
+    pub title: String,
+    /// The length of the visible text.
+    #[serde(default)]
+    pub text_length: usize,
+    /// The number of links.
+    #[serde(default)]
+    pub links: usize,
+    /// The target of a meta refresh of at most 5 seconds.
+    #[serde(default)]
+    pub meta_refresh: Option<String>,
+    /// The challenge elements or titles are present.
+    #[serde(default)]
+    pub challenge_dom: bool,
+    /// The title or heading of an error page.
+    #[serde(default)]
+    pub error_copy: bool,
+    /// The copy or links of a parked domain.
+    #[serde(default)]
+    pub parked_copy: bool,
+}
+
+/// The class of the final document of a navigation with the response.
+#[derive(Debug, Clone)]
+pub struct ClassifiedNavigation {
+    /// The response of the navigation.
+    pub response: ArcHttpRequest,
+    /// The class of the document.
+    pub class: DocumentClass,
+    /// Why the document got its class, e.g. `status:404`.
+    pub reason: String,
+}
+
+/// The visible text of an error page is short, a longer document with an error title is content.
+const ERROR_PAGE_MAX_TEXT: usize = 2_000;
+
+impl DocumentSignals {
+    /// The signals of the navigation response.
+    pub fn from_response(request: &HttpRequest) -> Self {
+        let mut signals = Self {
+            requested_url: request
+                .redirect_chain
+                .first()
+                .and_then(|first| first.url.clone())
+                .or_else(|| request.url.clone()),
+            final_url: request.url.clone(),
+            ..Default::default()
+        };
+
+        if let Some(response) = &request.response {
+            let cf_mitigated = response
+                .headers
+                .inner()
+                .get("cf-mitigated")
+                .and_then(|v| v.as_str());
+
+            signals.status = u16::try_from(response.status).ok();
+            signals.final_url = Some(response.url.clone());
+            signals.challenge_provider =
+                crate::webhook::detect_challenge(&response.url, cf_mitigated).map(Into::into);
+        }
+
+        signals
+    }
+
+    /// Classify the document, with the reason of the class.
+    pub fn classify(&self) -> (DocumentClass, String) {
+        if let Some(provider) = &self.challenge_provider {
+            return (DocumentClass::Challenge, format!("provider:{provider}"));
+        }
+
+        if self.challenge_dom && self.text_length < ERROR_PAGE_MAX_TEXT {
+            return (DocumentClass::Challenge, "challenge_dom".into());
+        }
+
+        let final_host = self
+            .final_url
+            .as_deref()
+            .and_then(|url| url::Url::parse(url).ok())
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+
+        if let Some(host) = &final_host {
+            if let Some(parking) = PARKING_HOSTS
+                .iter()
+                .find(|p| host.as_str() == **p || host.ends_with(&format!(".{p}")))
+            {
+                return (DocumentClass::Parked, format!("host:{parking}"));
+            }
+        }
+
+        if self.parked_copy {
+            return (DocumentClass::Parked, "parked_copy".into());
+        }
+
+        if let Some(status) = self.status.filter(|status| *status >= 400) {
+            return (DocumentClass::ErrorPage, format!("status:{status}"));
+        }
+
+        if self.error_copy && self.text_length < ERROR_PAGE_MAX_TEXT {
+            return (DocumentClass::ErrorPage, "error_copy".into());
+        }
+
+        if let Some(target) = &self.meta_refresh {
+            if Some(target) != self.final_url.as_ref() {
+                return (DocumentClass::SoftRedirect, "meta_refresh".into());
+            }
+        }
+
+        if self.is_redirected_home() {
+            return (DocumentClass::SoftRedirect, "redirected_home".into());
+        }
+
+        (DocumentClass::Content, "content".into())
+    }
+
+    /// A deep link was redirected to the home page of the site.
+    fn is_redirected_home(&self) -> bool {
+        let (Some(requested), Some(final_url)) = (
+            self.requested_url
+                .as_deref()
+                .and_then(|url| url::Url::parse(url).ok()),
+            self.final_url
+                .as_deref()
+                .and_then(|url| url::Url::parse(url).ok()),
+        ) else {
+            return false;
+        };
+
+        let home =
+            |path: &str| matches!(path, "" | "/") || (path.len() <= 4 && path.ends_with('/'));
+
+        !home(requested.path()) && home(final_url.path()) && final_url.query().is_none()
+    }
+}
+
+impl Page {
+    /// Classify the final document of the last navigation as content, an error page, a
+    /// challenge, a parked domain or a soft redirect, from its response and its DOM.
+    pub async fn classify_navigation(&self) -> Result<ClassifiedNavigation> {
+        let response = self.wait_for_navigation_response().await?;
+
+        #[derive(Deserialize)]
+        struct Dom {
+            title: String,
+            text_length: usize,
+            links: usize,
+            meta_refresh: Option<String>,
+            challenge_dom: bool,
+            error_copy: bool,
+            parked_copy: bool,
+        }
+
+        let dom: Dom = self
+            .evaluate_expression(DOCUMENT_SIGNALS_JS)
+            .await?
+            .into_value()?;
+
+        let mut signals = match &response {
+            Some(request) => DocumentSignals::from_response(request),
+            _ => DocumentSignals::default(),
+        };
+
+        signals.title = dom.title;
+        signals.text_length = dom.text_length;
+        signals.links = dom.links;
+        signals.meta_refresh = dom.meta_refresh;
+        signals.challenge_dom = dom.challenge_dom;
+        signals.error_copy = dom.error_copy;
+        signals.parked_copy = dom.parked_copy;
+
+        let (class, reason) = signals.classify();
+
+        Ok(ClassifiedNavigation {
+            response,
+            class,
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signals(final_url: &str, status: u16) -> DocumentSignals {
+        DocumentSignals {
+            requested_url: Some(final_url.into()),
+            final_url: Some(final_url.into()),
+            status: Some(status),
+            text_length: 5_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classifies_the_content_and_the_errors() {
+        assert_eq!(
+            signals("https://a.com/post", 200).classify().0,
+            DocumentClass::Content
+        );
+        assert_eq!(
+            signals("https://a.com/post", 404).classify(),
+            (DocumentClass::ErrorPage, "status:404".into())
+        );
+
+        let soft_404 = DocumentSignals {
+            error_copy: true,
+            text_length: 120,
+            ..signals("https://a.com/post", 200)
+        };
+        assert_eq!(soft_404.classify().0, DocumentClass::ErrorPage);
+    }
+
+    #[test]
+    fn classifies_the_challenges_and_the_parked_domains() {
+        let challenge = DocumentSignals {
+            challenge_provider: Some("cloudflare".into()),
+            ..signals("https://a.com/", 403)
+        };
+        assert_eq!(challenge.classify().0, DocumentClass::Challenge);
+        assert_eq!(
+            signals("https://ww1.sedoparking.com/a.com", 200).classify(),
+            (DocumentClass::Parked, "host:sedoparking.com".into())
+        );
+    }
+
+    #[test]
+    fn classifies_the_soft_redirects() {
+        let home = DocumentSignals {
+            requested_url: Some("https://a.com/2019/old-post".into()),
+            ..signals("https://a.com/", 200)
+        };
+        assert_eq!(
+            home.classify(),
+            (DocumentClass::SoftRedirect, "redirected_home".into())
+        );
+
+        let refresh = DocumentSignals {
+            meta_refresh: Some("https://b.com/".into()),
+            ..signals("https://a.com/", 200)
+        };
+        assert_eq!(refresh.classify().0, DocumentClass::SoftRedirect);
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 use std::iter::FromIterator;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use futures::channel::oneshot::Sender as OneshotSender;
 use futures::task::Poll;
@@ -12,6 +12,7 @@ use chromiumoxide_types::{Command, CommandResponse, Method, MethodId, Request, R
 
 use crate::error::{CdpError, DeadlineExceeded, Result};
 use crate::handler::REQUEST_TIMEOUT;
+use crate::runtime::Instant;
 
 /// Deserialize a response
 pub(crate) fn to_command_response<T: Command>(
@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+use spider_fingerprint::configs::{AgentOs, Tier};
+
+use crate::browser::{BrowserConfig, BrowserConfigBuilder, HeadlessMode};
+use crate::page::Page;
+
+/// The prefix of the environment variables overlaid on the file, `CHROMEY_<SECTION>_<KEY>`,
+/// e.g. `CHROMEY_TIMEOUTS_REQUEST_MS=5000` or `CHROMEY_LAUNCH_HEADLESS=new`.
+pub const ENV_PREFIX: &str = "CHROMEY_";
+
+/// The sections of the file, the env overlay splits a variable on the section name.
+const SECTIONS: [&str; 5] = ["launch", "timeouts", "blocking", "cache", "stealth"];
+
+/// A `headless` setting, either a bool or `"new"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "BoolOrString")]
+pub struct Headless(pub HeadlessMode);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BoolOrString {
+    Bool(bool),
+    String(String),
+}
+
+impl TryFrom<BoolOrString> for Headless {
+    type Error = String;
+
+    fn try_from(value: BoolOrString) -> Result<Self, Self::Error> {
+        match value {
+            BoolOrString::Bool(true) => Ok(Headless(HeadlessMode::True)),
+            BoolOrString::Bool(false) => Ok(Headless(HeadlessMode::False)),
+            BoolOrString::String(mode) => match mode.to_ascii_lowercase().as_str() {
+                "true" => Ok(Headless(HeadlessMode::True)),
+                "false" => Ok(Headless(HeadlessMode::False)),
+                "new" => Ok(Headless(HeadlessMode::New)),
+                _ => Err(format!("unknown headless mode {mode:?}")),
+            },
+        }
+    }
+}
+
+/// The `[launch]` section, the process of the browser.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LaunchSection {
+    /// The Chrome or Chromium executable, detected when unset.
+    pub executable: Option<PathBuf>,
+    /// `true`, `false` or `"new"`.
+    pub headless: Option<Headless>,
+    /// Run the browser with a sandbox.
+    pub sandbox: Option<bool>,
+    /// The debugging port.
+    pub port: Option<u16>,
+    /// The window `[width, height]`.
+    pub window_size: Option<[u32; 2]>,
+    /// The data dir of the browser.
+    pub user_data_dir: Option<PathBuf>,
+    /// Launch in incognito.
+    pub incognito: Option<bool>,
+    /// Ignore the https errors.
+    pub ignore_https_errors: Option<bool>,
+    /// Use only `args` instead of the default flags.
+    pub disable_default_args: Option<bool>,
+    /// The extra command line flags.
+    pub args: Vec<String>,
+    /// The unpacked extensions to load.
+    pub extensions: Vec<String>,
+    /// The environment variables of the browser process.
+    pub env: HashMap<String, String>,
+}
+
+/// The `[timeouts]` section, in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutsSection {
+    /// The timeout of `Browser::launch`.
+    pub launch_ms: Option<u64>,
+    /// The timeout of a request without response.
+    pub request_ms: Option<u64>,
+}
+
+/// The `[blocking]` section, the requests dropped by the interception.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlockingSection {
+    /// Enable the request interception, required by the other settings.
+    pub request_intercept: Option<bool>,
+    /// Drop the images and other visuals.
+    pub ignore_visuals: Option<bool>,
+    /// Drop the stylesheets.
+    pub ignore_stylesheets: Option<bool>,
+    /// Drop the scripts, but the critical framework bundles.
+    pub ignore_javascript: Option<bool>,
+    /// Drop the analytics.
+    pub ignore_analytics: Option<bool>,
+    /// Drop the ads.
+    pub ignore_ads: Option<bool>,
+    /// Only fetch the html documents.
+    pub only_html: Option<bool>,
+    /// The max bytes to receive.
+    pub max_bytes: Option<u64>,
+    /// Enable the service workers.
+    pub service_workers: Option<bool>,
+}
+
+/// How the responses of the pages are cached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// Cache for data collection, skipping the media.
+    #[default]
+    Scraping,
+    /// Cache for screenshots, media included.
+    Screenshots,
+    /// Replay every request from the cache, failing the misses.
+    Offline,
+}
+
+/// The `[cache]` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSection {
+    /// Enable the cache of the browser.
+    pub enabled: Option<bool>,
+    /// The strategy of the response cache.
+    pub strategy: Option<CacheMode>,
+    /// Dump the responses to the remote cache, `"true"` for `HYBRID_CACHE_ENDPOINT` or the
+    /// endpoint of the cache server.
+    pub remote: Option<String>,
+}
+
+/// The `[stealth]` section, applied per page with [`StealthSection::apply`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StealthSection {
+    /// Enable the stealth scripts.
+    pub enabled: bool,
+    /// The tier of the stealth scripts.
+    pub tier: Option<Tier>,
+    /// The operating system to emulate.
+    pub os: Option<AgentOs>,
+    /// The user agent of the pages.
+    pub user_agent: Option<String>,
+}
+
+impl StealthSection {
+    /// Apply the stealth profile to the page.
+    pub async fn apply(&self, page: &Page) -> crate::error::Result<()> {
+        if let Some(user_agent) = &self.user_agent {
+            page.set_user_agent(user_agent.as_str()).await?;
+        }
+
+        if self.enabled {
+            page.enable_stealth_mode_os(self.os, self.tier).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The configuration of a deployment, read from a toml file such as `chromey.toml` with the
+/// `CHROMEY_*` environment variables overlaid, so the browser is tuned without recompiling.
+///
+/// ```toml
+/// [launch]
+/// headless = "new"
+/// args = ["--disable-gpu"]
+///
+/// [timeouts]
+/// request_ms = 15000
+///
+/// [blocking]
+/// request_intercept = true
+/// ignore_visuals = true
+///
+/// [cache]
+/// strategy = "scraping"
+/// remote = "http://cache:8080"
+///
+/// [stealth]
+/// enabled = true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChromeyConfig {
+    /// The process of the browser.
+    pub launch: LaunchSection,
+    /// The timeouts.
+    pub timeouts: TimeoutsSection,
+    /// The blocking policy.
+    pub blocking: BlockingSection,
+    /// The cache.
+    pub cache: CacheSection,
+    /// The stealth profile.
+    pub stealth: StealthSection,
+}
+
+impl ChromeyConfig {
+    /// Read the file and overlay the environment variables.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+
+        Self::parse(&source, std::env::vars())
+    }
+
+    /// Read the environment variables only.
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse("", std::env::vars())
+    }
+
+    /// Parse the toml source with the `CHROMEY_*` variables overlaid.
+    pub fn parse(
+        source: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, String> {
+        let mut table: toml::Table = source.parse().map_err(|e| format!("{e}"))?;
+
+        for (name, value) in vars {
+            let Some((section, key)) = env_key(&name) else {
+                continue;
+            };
+
+            let entry = table
+                .entry(section.clone())
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+
+            match entry {
+                toml::Value::Table(section) => {
+                    section.insert(key, env_value(&value));
+                }
+                _ => return Err(format!("{section} is not a table")),
+            }
+        }
+
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("{e}"))
+    }
+
+    /// The builder of the browser configuration.
+    pub fn builder(&self) -> BrowserConfigBuilder {
+        let launch = &self.launch;
+        let mut builder = BrowserConfig::builder()
+            .args(launch.args.iter())
+            .extensions(launch.extensions.iter())
+            .envs(launch.env.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+
+        if let Some(executable) = &launch.executable {
+            builder = builder.chrome_executable(executable);
+        }
+        if let Some(Headless(mode)) = launch.headless {
+            builder = builder.headless_mode(mode);
+        }
+        if launch.sandbox == Some(false) {
+            builder = builder.no_sandbox();
+        }
+        if let Some(port) = launch.port {
+            builder = builder.port(port);
+        }
+        if let Some([width, height]) = launch.window_size {
+            builder = builder.window_size(width, height);
+        }
+        if let Some(dir) = &launch.user_data_dir {
+            builder = builder.user_data_dir(dir);
+        }
+        if launch.incognito == Some(true) {
+            builder = builder.incognito();
+        }
+        if launch.ignore_https_errors == Some(false) {
+            builder = builder.respect_https_errors();
+        }
+        if launch.disable_default_args == Some(true) {
+            builder = builder.disable_default_args();
+        }
+
+        if let Some(ms) = self.timeouts.launch_ms {
+            builder = builder.launch_timeout(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.timeouts.request_ms {
+            builder = builder.request_timeout(Duration::from_millis(ms));
+        }
+
+        let blocking = &self.blocking;
+        match blocking.request_intercept {
+            Some(true) => builder = builder.enable_request_intercept(),
+            Some(false) => builder = builder.disable_request_intercept(),
+            None => (),
+        }
+        if let Some(enabled) = blocking.service_workers {
+            builder = builder.set_service_worker_enabled(enabled);
+        }
+        if blocking.max_bytes.is_some() {
+            builder = builder.with_max_bytes_allowed(blocking.max_bytes);
+        }
+
+        match self.cache.enabled {
+            Some(true) => builder = builder.enable_cache(),
+            Some(false) => builder = builder.disable_cache(),
+            None => (),
+        }
+
+        builder
+    }
+
+    /// Build the browser configuration.
+    pub fn browser_config(&self) -> Result<BrowserConfig, String> {
+        let mut config = self.builder().build()?;
+        let blocking = &self.blocking;
+
+        // the blocking policy has no builder methods, it is set on the built configuration.
+        let flags = [
+            (blocking.ignore_visuals, &mut config.ignore_visuals),
+            (blocking.ignore_stylesheets, &mut config.ignore_stylesheets),
+            (blocking.ignore_javascript, &mut config.ignore_javascript),
+            (blocking.ignore_analytics, &mut config.ignore_analytics),
+            (blocking.ignore_ads, &mut config.ignore_ads),
+            (blocking.only_html, &mut config.only_html),
+        ];
+
+        for (value, flag) in flags {
+            if let Some(value) = value {
+                *flag = value;
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The strategy of the response cache.
+    #[cfg(feature = "_cache")]
+    pub fn cache_strategy(&self) -> Option<crate::cache::CacheStrategy> {
+        self.cache.strategy.map(|mode| match mode {
+            CacheMode::Scraping => crate::cache::CacheStrategy::Scraping,
+            CacheMode::Screenshots => crate::cache::CacheStrategy::Screenshots,
+            CacheMode::Offline => crate::cache::CacheStrategy::OfflineOnly,
+        })
+    }
+
+    /// The remote cache to dump the responses to, passed as `dump_remote` to the cache methods
+    /// of the page.
+    pub fn remote_cache(&self) -> Option<&str> {
+        self.cache
+            .remote
+            .as_deref()
+            .filter(|remote| !remote.is_empty())
+    }
+}
+
+impl BrowserConfig {
+    /// Read the browser configuration from a toml file such as `chromey.toml`, with the
+    /// `CHROMEY_*` environment variables overlaid. See [`ChromeyConfig`] for the sections.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        ChromeyConfig::from_file(path)?.browser_config()
+    }
+}
+
+/// The section and key of an overlay variable, `CHROMEY_TIMEOUTS_REQUEST_MS` is
+/// `("timeouts", "request_ms")`.
+fn env_key(name: &str) -> Option<(String, String)> {
+    let rest = name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+    let (section, key) = rest.split_once('_')?;
+
+    if !SECTIONS.contains(&section) || key.is_empty() {
+        return None;
+    }
+
+    Some((section.to_string(), key.to_string()))
+}
+
+/// The toml value of an overlay variable, e.g. `5000`, `true` or `["--a", "--b"]`, falling back
+/// to a string.
+fn env_value(raw: &str) -> toml::Value {
+    format!("v = {raw}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+[launch]
+headless = "new"
+sandbox = false
+args = ["--disable-gpu"]
+env = { TZ = "UTC" }
+
+[timeouts]
+request_ms = 15000
+
+[blocking]
+request_intercept = true
+ignore_visuals = true
+
+[cache]
+strategy = "screenshots"
+remote = "http://cache:8080"
+
+[stealth]
+enabled = true
+user_agent = "agent"
+"#;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_sections() {
+        let config = ChromeyConfig::parse(SOURCE, Vec::new()).unwrap();
+
+        assert_eq!(config.launch.headless, Some(Headless(HeadlessMode::New)));
+        assert_eq!(config.launch.sandbox, Some(false));
+        assert_eq!(config.launch.env.get("TZ").map(String::as_str), Some("UTC"));
+        assert_eq!(config.timeouts.request_ms, Some(15000));
+        assert_eq!(config.blocking.ignore_visuals, Some(true));
+        assert_eq!(config.cache.strategy, Some(CacheMode::Screenshots));
+        assert_eq!(config.remote_cache(), Some("http://cache:8080"));
+        assert!(config.stealth.enabled);
+    }
+
+    #[test]
+    fn overlays_the_environment() {
+        let config = ChromeyConfig::parse(
+            SOURCE,
+            vars(&[
+                ("CHROMEY_TIMEOUTS_REQUEST_MS", "5000"),
+                ("CHROMEY_LAUNCH_HEADLESS", "false"),
+                ("CHROMEY_LAUNCH_ARGS", r#"["--a", "--b"]"#),
+                ("CHROMEY_CACHE_REMOTE", "true"),
+                ("CHROMEY_CACHE_ENABLED", "false"),
+                ("CHROMEY_UNKNOWN_KEY", "1"),
+                ("PATH", "/usr/bin"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.timeouts.request_ms, Some(5000));
+        assert_eq!(config.launch.headless, Some(Headless(HeadlessMode::False)));
+        assert_eq!(config.launch.args, vec!["--a", "--b"]);
+        assert_eq!(config.remote_cache(), Some("true"));
+        assert_eq!(config.cache.enabled, Some(false));
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        assert!(ChromeyConfig::parse("[launch]\nheadles = true", Vec::new()).is_err());
+        assert!(ChromeyConfig::parse("[launch]\nheadless = \"old\"", Vec::new()).is_err());
+        assert!(ChromeyConfig::parse("", vars(&[("CHROMEY_BLOCKING_IGNORE_AD", "true")])).is_err());
+        assert_eq!(
+            ChromeyConfig::parse("", Vec::new()).unwrap(),
+            ChromeyConfig::default()
+        );
+    }
+
+    #[test]
+    fn builds_the_browser_config() {
+        let mut config = ChromeyConfig::parse(SOURCE, Vec::new()).unwrap();
+        config.launch.executable = Some("/usr/bin/chromium".into());
+
+        let browser = config.browser_config().unwrap();
+
+        assert!(browser.request_intercept);
+        assert!(browser.ignore_visuals);
+        assert!(!browser.ignore_ads);
+    }
+}
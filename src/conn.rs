@@ -1,13 +1,17 @@
 use std::collections::VecDeque;
 use std::marker::PhantomData;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::ready;
 
 use futures::stream::Stream;
 use futures::task::{Context, Poll};
 use futures::{SinkExt, StreamExt};
+#[cfg(not(target_arch = "wasm32"))]
 use tokio_tungstenite::tungstenite::Message as WsMessage;
+#[cfg(not(target_arch = "wasm32"))]
 use tokio_tungstenite::MaybeTlsStream;
+#[cfg(not(target_arch = "wasm32"))]
 use tokio_tungstenite::{tungstenite::protocol::WebSocketConfig, WebSocketStream};
 
 use chromiumoxide_cdp::cdp::browser_protocol::target::SessionId;
@@ -15,9 +19,86 @@ use chromiumoxide_types::{CallId, EventMessage, Message, MethodCall, MethodId};
 
 use crate::error::CdpError;
 use crate::error::Result;
+use crate::flight_recorder::FlightRecorder;
 
+#[cfg(not(target_arch = "wasm32"))]
 type ConnectStream = MaybeTlsStream<tokio::net::TcpStream>;
 
+/// A custom transport of the devtools messages, e.g. a browser `WebSocket` when the native
+/// websocket is not available. The stream yields the raw message frames and ends when the
+/// connection closed, the sink sends the serialized commands.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait Transport:
+    Stream<Item = Result<Vec<u8>>> + futures::Sink<String, Error = CdpError> + Send + Unpin
+{
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<S> Transport for S where
+    S: Stream<Item = Result<Vec<u8>>> + futures::Sink<String, Error = CdpError> + Send + Unpin
+{
+}
+
+/// A custom transport of the devtools messages, e.g. a browser `WebSocket`. The stream yields
+/// the raw message frames and ends when the connection closed, the sink sends the serialized
+/// commands. The browser objects are not `Send` on wasm32, neither is the transport.
+#[cfg(target_arch = "wasm32")]
+pub trait Transport:
+    Stream<Item = Result<Vec<u8>>> + futures::Sink<String, Error = CdpError> + Unpin
+{
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<S> Transport for S where
+    S: Stream<Item = Result<Vec<u8>>> + futures::Sink<String, Error = CdpError> + Unpin
+{
+}
+
+/// The socket of the connection.
+enum Socket {
+    /// The native websocket.
+    #[cfg(not(target_arch = "wasm32"))]
+    Native(Box<WebSocketStream<ConnectStream>>),
+    /// A custom transport.
+    Custom(Box<dyn Transport>),
+}
+
+impl std::fmt::Debug for Socket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Socket::Native(ws) => f.debug_tuple("Native").field(ws).finish(),
+            Socket::Custom(_) => f.debug_tuple("Custom").finish(),
+        }
+    }
+}
+
+impl Socket {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Socket::Native(ws) => ws.poll_ready_unpin(cx).map_err(Into::into),
+            Socket::Custom(transport) => transport.poll_ready_unpin(cx),
+        }
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Socket::Native(ws) => ws.poll_flush_unpin(cx).map_err(Into::into),
+            Socket::Custom(transport) => transport.poll_flush_unpin(cx),
+        }
+    }
+
+    fn start_send(&mut self, msg: String) -> Result<()> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Socket::Native(ws) => Ok(ws.start_send_unpin(msg.into())?),
+            Socket::Custom(transport) => transport.start_send_unpin(msg),
+        }
+    }
+}
+
 /// Exchanges the messages with the websocket
 #[must_use = "streams do nothing unless polled"]
 #[derive(Debug)]
@@ -25,17 +106,20 @@ pub struct Connection<T: EventMessage> {
     /// Queue of commands to send.
     pending_commands: VecDeque<MethodCall>,
     /// The websocket of the chromium instance
-    ws: WebSocketStream<ConnectStream>,
+    ws: Socket,
     /// The identifier for a specific command
     next_id: usize,
     /// A flush is required.
     needs_flush: bool,
     /// The message that is currently being proceessed
     pending_flush: Option<MethodCall>,
+    /// Records the last messages of every session.
+    recorder: Option<Arc<FlightRecorder>>,
     /// The phantom marker.
     _marker: PhantomData<T>,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 lazy_static::lazy_static! {
     /// Nagle's algorithm disabled?
     static ref DISABLE_NAGLE: bool = match std::env::var("DISABLE_NAGLE") {
@@ -49,6 +133,7 @@ lazy_static::lazy_static! {
     };
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl<T: EventMessage + Unpin> Connection<T> {
     pub async fn connect(debug_ws_url: impl AsRef<str>) -> Result<Self> {
         let mut config = WebSocketConfig::default();
@@ -65,14 +150,31 @@ impl<T: EventMessage + Unpin> Connection<T> {
         )
         .await?;
 
-        Ok(Self {
+        Ok(Self::with_socket(Socket::Native(Box::new(ws))))
+    }
+}
+
+impl<T: EventMessage> Connection<T> {
+    /// Exchange the messages over a custom transport instead of the native websocket.
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self::with_socket(Socket::Custom(Box::new(transport)))
+    }
+
+    fn with_socket(ws: Socket) -> Self {
+        Self {
             pending_commands: Default::default(),
             ws,
             next_id: 0,
             needs_flush: false,
             pending_flush: None,
+            recorder: None,
             _marker: Default::default(),
-        })
+        }
+    }
+
+    /// Record the messages exchanged on the connection.
+    pub(crate) fn set_flight_recorder(&mut self, recorder: Arc<FlightRecorder>) {
+        self.recorder = Some(recorder);
     }
 }
 
@@ -106,15 +208,18 @@ impl<T: EventMessage> Connection<T> {
     /// sink
     fn start_send_next(&mut self, cx: &mut Context<'_>) -> Result<()> {
         if self.needs_flush {
-            if let Poll::Ready(Ok(())) = self.ws.poll_flush_unpin(cx) {
+            if let Poll::Ready(Ok(())) = self.ws.poll_flush(cx) {
                 self.needs_flush = false;
             }
         }
         if self.pending_flush.is_none() && !self.needs_flush {
             if let Some(cmd) = self.pending_commands.pop_front() {
                 tracing::trace!("Sending {:?}", cmd);
-                let msg = serde_json::to_string(&cmd)?;
-                self.ws.start_send_unpin(msg.into())?;
+                let msg = crate::json::to_string(&cmd)?;
+                if let Some(recorder) = self.recorder.as_ref() {
+                    recorder.record_sent(cmd.session_id.as_deref(), &msg);
+                }
+                self.ws.start_send(msg)?;
                 self.pending_flush = Some(cmd);
             }
         }
@@ -135,7 +240,7 @@ impl<T: EventMessage + Unpin> Stream for Connection<T> {
             }
 
             if let Some(call) = pin.pending_flush.take() {
-                if pin.ws.poll_ready_unpin(cx).is_ready() {
+                if pin.ws.poll_ready(cx).is_ready() {
                     pin.needs_flush = true;
                     // try another flush in this same poll
                     continue;
@@ -147,32 +252,99 @@ impl<T: EventMessage + Unpin> Stream for Connection<T> {
             break;
         }
 
-        // read from the websocket
-        match ready!(pin.ws.poll_next_unpin(cx)) {
-            Some(Ok(WsMessage::Text(text))) => {
-                let ready = decode_message::<T>(text.as_bytes(), Some(&text));
-                Poll::Ready(Some(ready))
-            }
-            Some(Ok(WsMessage::Binary(buf))) => {
-                let ready = decode_message::<T>(&buf, None);
-                Poll::Ready(Some(ready))
-            }
-            Some(Ok(WsMessage::Close(_))) => Poll::Ready(None),
-            // ignore ping and pong
-            Some(Ok(WsMessage::Ping(_))) | Some(Ok(WsMessage::Pong(_))) => {
-                cx.waker().wake_by_ref();
-                Poll::Pending
-            }
-            Some(Ok(msg)) => Poll::Ready(Some(Err(CdpError::UnexpectedWsMessage(msg)))),
-            Some(Err(err)) => Poll::Ready(Some(Err(CdpError::Ws(err)))),
-            None => {
-                // ws connection closed
-                Poll::Ready(None)
-            }
+        match &mut pin.ws {
+            #[cfg(not(target_arch = "wasm32"))]
+            Socket::Native(ws) => poll_native::<T>(ws, pin.recorder.as_deref(), cx),
+            Socket::Custom(transport) => match ready!(transport.poll_next_unpin(cx)) {
+                Some(Ok(buf)) => Poll::Ready(Some(decode_recorded::<T>(
+                    pin.recorder.as_deref(),
+                    &buf,
+                    None,
+                ))),
+                Some(Err(err)) => Poll::Ready(Some(Err(err))),
+                None => Poll::Ready(None),
+            },
         }
     }
 }
 
+/// Read the next message from the native websocket.
+#[cfg(not(target_arch = "wasm32"))]
+fn poll_native<T: EventMessage>(
+    ws: &mut WebSocketStream<ConnectStream>,
+    recorder: Option<&FlightRecorder>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<Result<Box<Message<T>>>>> {
+    match ready!(ws.poll_next_unpin(cx)) {
+        Some(Ok(WsMessage::Text(text))) => {
+            let ready = decode_recorded::<T>(recorder, text.as_bytes(), Some(&text));
+            Poll::Ready(Some(ready))
+        }
+        Some(Ok(WsMessage::Binary(buf))) => {
+            let ready = decode_recorded::<T>(recorder, &buf, None);
+            Poll::Ready(Some(ready))
+        }
+        Some(Ok(WsMessage::Close(_))) => Poll::Ready(None),
+        // ignore ping and pong
+        Some(Ok(WsMessage::Ping(_))) | Some(Ok(WsMessage::Pong(_))) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(Ok(msg)) => Poll::Ready(Some(Err(CdpError::UnexpectedWsMessage(msg)))),
+        Some(Err(err)) => Poll::Ready(Some(Err(CdpError::Ws(err)))),
+        None => {
+            // ws connection closed
+            Poll::Ready(None)
+        }
+    }
+}
+
+/// Decode the frame, recording it with its parse error when the flight recorder is enabled.
+fn decode_recorded<T: EventMessage>(
+    recorder: Option<&FlightRecorder>,
+    bytes: &[u8],
+    raw_text_for_logging: Option<&str>,
+) -> Result<Box<Message<T>>> {
+    let decoded = decode_message::<T>(bytes, raw_text_for_logging);
+
+    if let Some(recorder) = recorder {
+        recorder.record_received(bytes, decoded.as_ref().err().map(ToString::to_string));
+    }
+
+    decoded.or_else(|err| decode_raw_event::<T>(bytes).ok_or(err))
+}
+
+/// Decode the frame, an event whose params failed to parse is kept with its raw params instead
+/// of failing the message, see `EventMessage::from_raw`.
+#[cfg(any(test, feature = "protocol-compat"))]
+pub(crate) fn decode_lenient<T: EventMessage>(bytes: &[u8]) -> Result<Box<Message<T>>> {
+    decode_message::<T>(bytes, None).or_else(|err| decode_raw_event::<T>(bytes).ok_or(err))
+}
+
+/// The event of the frame with its raw params, `None` when the frame is not an event.
+fn decode_raw_event<T: EventMessage>(bytes: &[u8]) -> Option<Box<Message<T>>> {
+    #[derive(serde::Deserialize)]
+    struct RawEvent {
+        method: String,
+        #[serde(rename = "sessionId")]
+        session_id: Option<String>,
+        #[serde(default)]
+        params: serde_json::Value,
+    }
+
+    let raw: RawEvent = serde_json::from_slice(bytes).ok()?;
+
+    tracing::warn!(
+        target: "chromiumoxide::conn::raw_ws::parse_errors",
+        "Kept the raw params of the event {}",
+        raw.method
+    );
+
+    let event = T::from_raw(raw.method, raw.session_id, raw.params)?;
+
+    Some(Box::new(Message::Event(event)))
+}
+
 /// Shared decode path for both text and binary WS frames.
 /// `raw_text_for_logging` is only provided for textual frames so we can log the original
 /// payload on parse failure if desired.
@@ -181,7 +353,7 @@ fn decode_message<T: EventMessage>(
     bytes: &[u8],
     raw_text_for_logging: Option<&str>,
 ) -> Result<Box<Message<T>>> {
-    match serde_json::from_slice::<Box<Message<T>>>(bytes) {
+    match crate::json::from_slice::<Box<Message<T>>>(bytes) {
         Ok(msg) => {
             tracing::trace!("Received {:?}", msg);
             Ok(msg)
@@ -199,7 +371,7 @@ fn decode_message<T: EventMessage>(
                     "Failed to parse binary WS message {err}",
                 );
             }
-            Err(err.into())
+            Err(err)
         }
     }
 }
@@ -241,3 +413,64 @@ fn decode_message<T: EventMessage>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chromiumoxide_cdp::cdp::CdpEventMessage;
+
+    /// A transport answering every command with an empty result.
+    #[derive(Default)]
+    struct EchoTransport {
+        responses: VecDeque<Vec<u8>>,
+    }
+
+    impl Stream for EchoTransport {
+        type Item = Result<Vec<u8>>;
+
+        fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            match self.get_mut().responses.pop_front() {
+                Some(response) => Poll::Ready(Some(Ok(response))),
+                _ => Poll::Pending,
+            }
+        }
+    }
+
+    impl futures::Sink<String> for EchoTransport {
+        type Error = CdpError;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: Pin<&mut Self>, msg: String) -> Result<()> {
+            let call: serde_json::Value = serde_json::from_str(&msg)?;
+            let response = serde_json::json!({ "id": call["id"], "result": {} });
+            self.get_mut()
+                .responses
+                .push_back(serde_json::to_vec(&response)?);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_transport_round_trip() {
+        let mut conn = Connection::<CdpEventMessage>::with_transport(EchoTransport::default());
+        let id = conn
+            .submit_command("Browser.getVersion".into(), None, serde_json::json!({}))
+            .unwrap();
+
+        match *conn.next().await.unwrap().unwrap() {
+            Message::Response(response) => assert_eq!(response.id, id),
+            Message::Event(_) => panic!("expected a response"),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use chromiumoxide_cdp::cdp::browser_protocol::page::AddScriptToEvaluateOnNewDocumentParams;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::page::Page;
+
+/// Handle the consent banners of `__RULES__` following `__POLICY__`: answer them with the api of
+/// the CMP or by clicking their button once they show up, or hide them. A rejected banner without
+/// a reject button is hidden after `__MS__`ms.
+const CONSENT_JS: &str = r###"(()=>{if(window.__chromeyConsent)return;window.__chromeyConsent=1;const rules=__RULES__;const policy=__POLICY__;const ms=__MS__;let done=false;const shown=el=>!!el&&(el.offsetParent!==null||getComputedStyle(el).position==='fixed');const hide=()=>{const s=document.createElement('style');s.textContent=rules.flatMap(r=>r.hide).join(',')+'{display:none!important}html,body{overflow:auto!important;position:static!important}';(document.head||document.documentElement).appendChild(s)};const answer=()=>{if(done)return true;for(const r of rules){if(!document.querySelector(r.detect))continue;try{if((policy==='accept'?r.acceptApi:r.rejectApi)()){done=r.name}}catch(e){}if(!done)for(const s of policy==='accept'?r.accept:r.reject){const b=document.querySelector(s);if(shown(b)){b.click();done=r.name;break}}if(done){window.__chromeyConsentHandled=done;return true}}return false};const start=()=>{if(policy==='hide'){hide();return}if(answer())return;const o=new MutationObserver(()=>{if(answer())o.disconnect()});o.observe(document.documentElement,{childList:true,subtree:true});setTimeout(()=>{o.disconnect();if(!done&&policy==='reject')hide()},ms)};document.readyState==='loading'?document.addEventListener('DOMContentLoaded',start):start()})()"###;
+
+/// Find the button of the first shown banner of `__RULES__` following `__POLICY__`, or hide the
+/// banners. Returns the CMP and the selector of the button.
+const FIND_CONSENT_JS: &str = r###"(()=>{const rules=__RULES__;const policy=__POLICY__;const shown=el=>!!el&&(el.offsetParent!==null||getComputedStyle(el).position==='fixed');for(const r of rules){if(!document.querySelector(r.detect))continue;if(policy==='hide'){const s=document.createElement('style');s.textContent=r.hide.join(',')+'{display:none!important}html,body{overflow:auto!important;position:static!important}';(document.head||document.documentElement).appendChild(s);return{cmp:r.name,selector:null}}for(const s of policy==='accept'?r.accept:r.reject)if(shown(document.querySelector(s)))return{cmp:r.name,selector:s};return{cmp:r.name,selector:null}}return null})()"###;
+
+/// How the cookie consent banners are answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConsentPolicy {
+    /// Accept every purpose.
+    AcceptAll,
+    /// Reject every optional purpose, hiding the banners without a reject button.
+    RejectAll,
+    /// Hide the banners and unlock the scrolling without answering them.
+    Hide,
+}
+
+impl ConsentPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::AcceptAll => "accept",
+            Self::RejectAll => "reject",
+            Self::Hide => "hide",
+        }
+    }
+
+    /// The script handling the banners of every new document.
+    pub fn script(&self) -> String {
+        CONSENT_JS
+            .replacen("__RULES__", &rules_js(), 1)
+            .replacen("__POLICY__", &format!("'{}'", self.as_str()), 1)
+            .replacen("__MS__", "10000", 1)
+    }
+
+    fn find_script(&self) -> String {
+        FIND_CONSENT_JS
+            .replacen("__RULES__", &rules_js(), 1)
+            .replacen("__POLICY__", &format!("'{}'", self.as_str()), 1)
+    }
+}
+
+/// The selectors and the api of a consent management platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsentRule {
+    /// The name of the platform.
+    pub name: &'static str,
+    /// The selector present when the banner is.
+    pub detect: &'static str,
+    /// The accept all buttons, in order of preference.
+    pub accept: &'static [&'static str],
+    /// The reject all buttons, in order of preference.
+    pub reject: &'static [&'static str],
+    /// The elements hidden by `ConsentPolicy::Hide`.
+    pub hide: &'static [&'static str],
+    /// The expression accepting every purpose with the api of the platform.
+    pub accept_api: &'static str,
+    /// The expression rejecting every purpose with the api of the platform.
+    pub reject_api: &'static str,
+}
+
+/// The consent management platforms handled.
+pub const CONSENT_RULES: &[ConsentRule] = &[
+    ConsentRule {
+        name: "onetrust",
+        detect: "#onetrust-banner-sdk,#onetrust-consent-sdk",
+        accept: &["#onetrust-accept-btn-handler", ".onetrust-close-btn-handler"],
+        reject: &["#onetrust-reject-all-handler", ".ot-pc-refuse-all-handler"],
+        hide: &["#onetrust-consent-sdk", ".onetrust-pc-dark-filter"],
+        accept_api: "window.OneTrust&&typeof OneTrust.AllowAll==='function'&&(OneTrust.AllowAll(),true)",
+        reject_api: "window.OneTrust&&typeof OneTrust.RejectAll==='function'&&(OneTrust.RejectAll(),true)",
+    },
+    ConsentRule {
+        name: "didomi",
+        detect: "#didomi-host,#didomi-notice",
+        accept: &["#didomi-notice-agree-button"],
+        reject: &["#didomi-notice-disagree-button", ".didomi-continue-without-agreeing"],
+        hide: &["#didomi-host", "#didomi-notice", ".didomi-popup-backdrop"],
+        accept_api: "window.Didomi&&typeof Didomi.setUserAgreeToAll==='function'&&(Didomi.setUserAgreeToAll(),true)",
+        reject_api: "window.Didomi&&typeof Didomi.setUserDisagreeToAll==='function'&&(Didomi.setUserDisagreeToAll(),true)",
+    },
+    ConsentRule {
+        name: "cookiebot",
+        detect: "#CybotCookiebotDialog",
+        accept: &[
+            "#CybotCookiebotDialogBodyLevelButtonLevelOptinAllowAll",
+            "#CybotCookiebotDialogBodyButtonAccept",
+        ],
+        reject: &[
+            "#CybotCookiebotDialogBodyButtonDecline",
+            "#CybotCookiebotDialogBodyLevelButtonLevelOptinDeclineAll",
+        ],
+        hide: &["#CybotCookiebotDialog", "#CybotCookiebotDialogBodyUnderlay"],
+        accept_api: "window.Cookiebot&&typeof Cookiebot.submitCustomConsent==='function'&&(Cookiebot.submitCustomConsent(true,true,true),true)",
+        reject_api: "window.Cookiebot&&typeof Cookiebot.submitCustomConsent==='function'&&(Cookiebot.submitCustomConsent(false,false,false),true)",
+    },
+];
+
+/// The rules as a js array, the apis as functions so no `eval` is needed under a strict CSP.
+fn rules_js() -> String {
+    let rules = CONSENT_RULES
+        .iter()
+        .map(|rule| {
+            format!(
+                "{{name:{},detect:{},accept:{},reject:{},hide:{},acceptApi:()=>{},rejectApi:()=>{}}}",
+                json(&rule.name),
+                json(&rule.detect),
+                json(&rule.accept),
+                json(&rule.reject),
+                json(&rule.hide),
+                rule.accept_api,
+                rule.reject_api
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!("[{rules}]")
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize)]
+struct FoundConsent {
+    cmp: String,
+    selector: Option<String>,
+}
+
+impl Page {
+    /// Answer the cookie consent banners of the page following the policy, from now on and on
+    /// every new document.
+    pub async fn handle_consent(&self, policy: ConsentPolicy) -> Result<&Self> {
+        let script = policy.script();
+
+        self.send_command(AddScriptToEvaluateOnNewDocumentParams::new(script.clone()))
+            .await?;
+        self.evaluate_expression(script).await?;
+
+        Ok(self)
+    }
+
+    /// Answer the cookie consent banner shown on the page once, clicking its button with a
+    /// trusted click. Returns the platform of the banner, `None` without a known banner.
+    pub async fn dismiss_consent(&self, policy: ConsentPolicy) -> Result<Option<String>> {
+        let found: Option<FoundConsent> = self
+            .evaluate_expression(policy.find_script())
+            .await?
+            .into_value()?;
+
+        let Some(found) = found else {
+            return Ok(None);
+        };
+
+        if let Some(selector) = found.selector {
+            self.find_element(selector).await?.click().await?;
+        }
+
+        Ok(Some(found.cmp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_the_policy_and_the_rules() {
+        let script = ConsentPolicy::RejectAll.script();
+
+        assert!(script.contains("const policy='reject';"));
+        assert!(script
+            .contains(r##"name:"onetrust",detect:"#onetrust-banner-sdk,#onetrust-consent-sdk""##));
+        assert!(script.contains("rejectApi:()=>window.Didomi&&"));
+        assert!(script.contains(r##""#CybotCookiebotDialogBodyButtonDecline""##));
+        assert!(!script.contains("__RULES__") && !script.contains("__MS__"));
+    }
+
+    #[test]
+    fn finds_the_banner_of_the_policy() {
+        let script = ConsentPolicy::Hide.find_script();
+
+        assert!(script.contains("const policy='hide';"));
+        assert!(script.starts_with("(()=>{const rules=[{name:"));
+    }
+}
@@ -0,0 +1,122 @@
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+/// The DER blocks of the certificates in a PEM bundle.
+fn pem_certificates(pem: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let text = String::from_utf8_lossy(pem);
+    let mut certificates = Vec::new();
+    let mut block: Option<String> = None;
+
+    for line in text.lines().map(str::trim) {
+        if line == "-----BEGIN CERTIFICATE-----" {
+            block = Some(String::new());
+        } else if line == "-----END CERTIFICATE-----" {
+            if let Some(b64) = block.take() {
+                certificates.push(crate::utils::base64::decode(b64).map_err(|e| e.to_string())?);
+            }
+        } else if let Some(b64) = block.as_mut() {
+            b64.push_str(line);
+        }
+    }
+
+    if certificates.is_empty() {
+        return Err("no PEM certificate found".into());
+    }
+
+    Ok(certificates)
+}
+
+/// Read a DER element, returning its tag, the whole element and the remaining bytes.
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + count)?
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + count)
+    };
+    let end = header.checked_add(len)?;
+    let element = data.get(..end)?;
+
+    Some((tag, &element[header..], element, &data[end..]))
+}
+
+/// The DER `SubjectPublicKeyInfo` of a certificate.
+fn subject_public_key_info(der: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+
+    let (tag, certificate, _, _) = der_element(der)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (tag, mut tbs, _, _) = der_element(certificate)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+
+    // the explicit version is optional.
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_element(tbs)?.3;
+    }
+
+    // skip the serial number, signature algorithm, issuer, validity and subject.
+    for _ in 0..5 {
+        tbs = der_element(tbs)?.3;
+    }
+
+    let (tag, _, spki, _) = der_element(tbs)?;
+
+    (tag == SEQUENCE).then_some(spki)
+}
+
+/// The base64 SHA-256 hashes of the public keys of the certificates in a PEM bundle, as expected
+/// by `--ignore-certificate-errors-spki-list`.
+pub fn spki_hashes(pem: &[u8]) -> Result<Vec<String>, String> {
+    pem_certificates(pem)?
+        .iter()
+        .map(|der| {
+            let spki = subject_public_key_info(der)
+                .ok_or_else(|| "invalid DER certificate".to_string())?;
+            Ok(base64::engine::general_purpose::STANDARD.encode(Sha256::digest(spki)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBiDCCAS+gAwIBAgIUNoxXjahY0Tw/UYkaEuMvtgBxCqAwCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPY2hyb21leSB0ZXN0IGNhMB4XDTI2MTAxNjA4MzU0MVoXDTM2
+MTAxMzA4MzU0MVowGjEYMBYGA1UEAwwPY2hyb21leSB0ZXN0IGNhMFkwEwYHKoZI
+zj0CAQYIKoZIzj0DAQcDQgAE1xKDKW3A92zsBJUM8/RMBH3C7HcOb5xinowrFEPP
+9hrEA6vlvhyDi+tVQDlLbnCXodkaFhQjneMHojJlVY8DmqNTMFEwHQYDVR0OBBYE
+FOJXvuE1qO/e1tESfu8k0tB/Dm1MMB8GA1UdIwQYMBaAFOJXvuE1qO/e1tESfu8k
+0tB/Dm1MMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgM5ko2VHj
+nkirAjqtz4UnLy1bExgTAxAZdwMDvV6LfQECIGsVuEn/PdaxaxHalu7a5eaXAXwj
+69fT56P+Sc9qEGQH
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn spki_hash_of_ca() {
+        assert_eq!(
+            spki_hashes(TEST_CA.as_bytes()).unwrap(),
+            vec!["7UC36gDhRbFlUZKZJVGHSfXWnJNFM9ZhKIq7nahgaro=".to_string()]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_pem() {
+        assert!(spki_hashes(b"not a certificate").is_err());
+    }
+}
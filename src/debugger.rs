@@ -0,0 +1,124 @@
+pub use chromiumoxide_cdp::cdp::js_protocol::debugger::{
+    BreakpointId, CallFrame, CallFrameId, EventPaused, EventResumed, Location, ScopeType,
+};
+use chromiumoxide_cdp::cdp::js_protocol::debugger::{
+    SetBreakpointByUrlParams, SetBreakpointByUrlReturns,
+};
+use chromiumoxide_cdp::cdp::js_protocol::runtime::PropertyDescriptor;
+
+/// A breakpoint set by url and the locations it resolved to in the loaded scripts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    /// The id used to remove the breakpoint.
+    pub id: BreakpointId,
+    /// The resolved locations, scripts loaded later resolve the breakpoint as they are parsed.
+    pub locations: Vec<Location>,
+}
+
+impl From<SetBreakpointByUrlReturns> for Breakpoint {
+    fn from(returns: SetBreakpointByUrlReturns) -> Self {
+        Self {
+            id: returns.breakpoint_id,
+            locations: returns.locations,
+        }
+    }
+}
+
+/// Options of a breakpoint set by url.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BreakpointOptions {
+    /// The zero based column of the breakpoint, the whole line when `None`.
+    pub column: Option<i64>,
+    /// A javascript expression, the breakpoint only pauses when it evaluates to true.
+    pub condition: Option<String>,
+}
+
+impl BreakpointOptions {
+    /// The `Debugger.setBreakpointByUrl` command for the scripts matching the url regex.
+    pub(crate) fn params(
+        self,
+        url_regex: impl Into<String>,
+        line: i64,
+    ) -> SetBreakpointByUrlParams {
+        SetBreakpointByUrlParams {
+            line_number: line,
+            url: None,
+            url_regex: Some(url_regex.into()),
+            script_hash: None,
+            column_number: self.column,
+            condition: self.condition,
+        }
+    }
+}
+
+/// A variable of a scope.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScopeVariable {
+    /// The name of the variable.
+    pub name: String,
+    /// The value for primitives and json serializable objects.
+    pub value: Option<serde_json::Value>,
+    /// The description of the value, e.g. `Array(3)` or the function source.
+    pub description: Option<String>,
+}
+
+impl From<PropertyDescriptor> for ScopeVariable {
+    fn from(property: PropertyDescriptor) -> Self {
+        let (value, description) = match property.value {
+            Some(v) => (v.value, v.description),
+            _ => (None, property.get.map(|_| "(getter)".to_string())),
+        };
+
+        Self {
+            name: property.name,
+            value,
+            description,
+        }
+    }
+}
+
+/// The variables of a scope of a paused call frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeSnapshot {
+    /// The kind of scope.
+    pub kind: ScopeType,
+    /// The name of the scope, e.g. the function of a closure.
+    pub name: Option<String>,
+    /// The variables of the scope. The global scope is skipped unless requested.
+    pub variables: Vec<ScopeVariable>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditional_breakpoint_params() {
+        let params = BreakpointOptions {
+            column: None,
+            condition: Some("user.id === 42".into()),
+        }
+        .params(r".*app\.js", 120);
+
+        assert_eq!(params.line_number, 120);
+        assert_eq!(params.url_regex.as_deref(), Some(r".*app\.js"));
+        assert_eq!(params.condition.as_deref(), Some("user.id === 42"));
+        assert!(params.url.is_none());
+    }
+
+    #[test]
+    fn scope_variable_from_property() {
+        let property: PropertyDescriptor = serde_json::from_value(serde_json::json!({
+            "name": "token",
+            "value": { "type": "string", "value": "abc" },
+            "configurable": true,
+            "enumerable": true
+        }))
+        .unwrap();
+
+        let variable = ScopeVariable::from(property);
+
+        assert_eq!(variable.name, "token");
+        assert_eq!(variable.value, Some(serde_json::json!("abc")));
+    }
+}
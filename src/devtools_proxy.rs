@@ -0,0 +1,578 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::browser::Browser;
+use crate::runtime::JoinHandle;
+use crate::server::http_response;
+
+/// The max bytes of a request head.
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// A parsed HTTP/1.1 request head.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RequestHead {
+    /// The request target, e.g. `/json/list`.
+    pub(crate) path: String,
+    /// The headers, the names lowercased.
+    pub(crate) headers: HashMap<String, String>,
+}
+
+impl RequestHead {
+    /// Parse the head of a request.
+    pub(crate) fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.lines();
+        let path = lines.next()?.split_whitespace().nth(1)?.to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+
+        Some(Self { path, headers })
+    }
+
+    /// The value of the header.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    /// The bearer token of the `Authorization` header or else the `token` query parameter.
+    pub(crate) fn token(&self) -> Option<String> {
+        if let Some(token) = self
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+        {
+            return Some(token.trim().to_string());
+        }
+
+        let (_, query) = self.path.split_once('?')?;
+
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(name, _)| name == "token")
+            .map(|(_, token)| token.into_owned())
+    }
+
+    /// The request upgrades to a websocket.
+    pub(crate) fn is_websocket(&self) -> bool {
+        self.header("upgrade")
+            .map_or(false, |upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+    }
+}
+
+/// The origin of the Chrome DevTools frontend.
+pub const DEVTOOLS_ORIGIN: &str = "devtools://devtools";
+
+/// Who may use a local endpoint. Any site open in a browser of the machine can reach a port of
+/// the loopback, so the requests sent with the `Origin` of another site or the `Host` of a
+/// rebound DNS name are rejected. A bearer token may be required on top.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessPolicy {
+    /// The origins allowed besides the endpoint itself, e.g. [`DEVTOOLS_ORIGIN`].
+    pub allowed_origins: Vec<String>,
+    /// The host names allowed besides `localhost` and the loopback addresses.
+    pub allowed_hosts: Vec<String>,
+    /// The token required as an `Authorization: Bearer` header or a `token` query parameter.
+    pub token: Option<String>,
+}
+
+impl AccessPolicy {
+    /// Allow the requests of the origin, e.g. `https://dashboard.example.com`.
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allowed_origins.push(origin.into());
+        self
+    }
+
+    /// Allow the host name, e.g. the name of the machine when the endpoint is bound to every
+    /// interface.
+    pub fn allow_host(mut self, host: impl Into<String>) -> Self {
+        self.allowed_hosts.push(host.into());
+        self
+    }
+
+    /// Require the bearer token.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The status of the rejection of the request, `None` when it is allowed.
+    pub(crate) fn rejection(&self, request: &RequestHead) -> Option<&'static str> {
+        const FORBIDDEN: &str = "403 Forbidden";
+
+        let Some(host) = request.header("host") else {
+            return Some(FORBIDDEN);
+        };
+
+        let name = host_name(host);
+        let allowed_host = is_loopback(name)
+            || self
+                .allowed_hosts
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(name));
+
+        if !allowed_host {
+            return Some(FORBIDDEN);
+        }
+
+        if let Some(origin) = request.header("origin").filter(|origin| !origin.is_empty()) {
+            let origin = origin.trim_end_matches('/');
+            let same_origin = origin.split_once("://").map_or(false, |(scheme, rest)| {
+                matches!(scheme, "http" | "https") && rest.eq_ignore_ascii_case(host)
+            });
+            let allowed_origin = same_origin
+                || self
+                    .allowed_origins
+                    .iter()
+                    .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin));
+
+            if !allowed_origin {
+                return Some(FORBIDDEN);
+            }
+        }
+
+        match self.token.as_deref() {
+            Some(token)
+                if !request
+                    .token()
+                    .map_or(false, |sent| token_matches(&sent, token)) =>
+            {
+                Some("401 Unauthorized")
+            }
+            _ => None,
+        }
+    }
+
+    /// The query of the urls handed out by the endpoint, carrying the token.
+    pub(crate) fn token_query(&self) -> String {
+        match self.token.as_deref() {
+            Some(token) => format!(
+                "?token={}",
+                url::form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>()
+            ),
+            _ => String::new(),
+        }
+    }
+}
+
+/// The name of the `Host` header without the port.
+fn host_name(host: &str) -> &str {
+    if let Some(rest) = host.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or_default();
+    }
+
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|byte| byte.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+/// The host name is `localhost` or a loopback address.
+fn is_loopback(name: &str) -> bool {
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<std::net::IpAddr>()
+            .map_or(false, |ip| ip.is_loopback())
+}
+
+/// Compare the tokens in constant time.
+pub(crate) fn token_matches(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// The url of the browser websocket serving the path, e.g. `/devtools/page/<target id>`.
+pub(crate) fn upstream_url(browser_ws: &str, path: &str) -> Option<String> {
+    let mut url = url::Url::parse(browser_ws).ok()?;
+
+    url.set_path(path.split('?').next().unwrap_or_default());
+    url.set_query(None);
+
+    Some(url.to_string())
+}
+
+/// The `/json/list` entry of a page served by the proxy, the websocket urls carry the token
+/// query of the proxy.
+pub(crate) fn target_entry(host: &str, id: &str, title: &str, url: &str, query: &str) -> Value {
+    let ws = format!("{host}/devtools/page/{id}");
+    let frontend_query: String = url::form_urlencoded::byte_serialize(query.as_bytes()).collect();
+
+    json!({
+        "id": id,
+        "type": "page",
+        "title": title,
+        "url": url,
+        "description": "",
+        "webSocketDebuggerUrl": format!("ws://{ws}{query}"),
+        "devtoolsFrontendUrl": format!("devtools://devtools/bundled/inspector.html?ws={ws}{frontend_query}"),
+    })
+}
+
+/// A local DevTools compatible endpoint of the pages of a headless browser, to attach Chrome
+/// DevTools (`chrome://inspect`, "Configure..." with the address of the proxy) for live debugging.
+///
+/// The `/json/version` and `/json/list` discovery endpoints list the pages of the browser, and
+/// the `/devtools/...` websockets are relayed to the browser. DevTools attaches its own session
+/// next to the session of chromey: the interception of the cache, the injected scripts and the
+/// requests they fulfill show up live in the Network, Sources and Console panels.
+///
+/// Only the DevTools frontend and the endpoint itself are allowed as origins and only the
+/// loopback as host by default, see [`DevToolsProxy::with_access`]. Bind the proxy to the
+/// loopback, a DevTools session controls the browser.
+#[derive(Debug, Clone)]
+pub struct DevToolsProxy {
+    browser: Arc<Browser>,
+    access: AccessPolicy,
+}
+
+impl DevToolsProxy {
+    /// Expose the pages of the browser.
+    pub fn new(browser: Arc<Browser>) -> Self {
+        Self {
+            browser,
+            access: AccessPolicy::default().allow_origin(DEVTOOLS_ORIGIN),
+        }
+    }
+
+    /// Who may connect, e.g. to require a token.
+    pub fn with_access(mut self, access: AccessPolicy) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Bind the address and spawn the task accepting the DevTools connections.
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> std::io::Result<JoinHandle<()>> {
+        let listener = TcpListener::bind(addr).await?;
+        let local = listener.local_addr()?;
+
+        Ok(crate::runtime::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let proxy = self.clone();
+                        crate::runtime::spawn(async move {
+                            if let Err(err) = proxy.handle_connection(stream, local).await {
+                                tracing::debug!("devtools connection {peer} closed: {err}");
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        tracing::warn!("devtools accept failed: {err}");
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Answer the discovery request or relay the websocket of the connection.
+    async fn handle_connection(
+        &self,
+        mut stream: TcpStream,
+        local: SocketAddr,
+    ) -> std::io::Result<()> {
+        let head = read_head(&mut stream).await?;
+        let request = RequestHead::parse(&head).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid request head")
+        })?;
+
+        if let Some(status) = self.access.rejection(&request) {
+            stream
+                .write_all(http_response(status, "").as_bytes())
+                .await?;
+            return stream.shutdown().await;
+        }
+
+        let host = request
+            .header("host")
+            .map(str::to_string)
+            .unwrap_or_else(|| local.to_string());
+
+        if request.is_websocket() && request.path.starts_with("/devtools/") {
+            return self.relay(stream, &request).await;
+        }
+
+        let response = match request.path.split('?').next() {
+            Some("/json/version") => {
+                http_response("200 OK", &self.version(&host).await.to_string())
+            }
+            Some("/json") | Some("/json/list") => {
+                http_response("200 OK", &self.targets(&host).await.to_string())
+            }
+            _ => http_response("404 Not Found", ""),
+        };
+
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    /// The `/json/version` of the browser, with the websocket of the proxy.
+    async fn version(&self, host: &str) -> Value {
+        let path = url::Url::parse(self.browser.websocket_address())
+            .map(|url| url.path().to_string())
+            .unwrap_or_default();
+        let mut version = json!({
+            "webSocketDebuggerUrl": format!("ws://{host}{path}{}", self.access.token_query())
+        });
+
+        if let Ok(info) = self.browser.version().await {
+            version["Browser"] = Value::from(info.product);
+            version["Protocol-Version"] = Value::from(info.protocol_version);
+            version["User-Agent"] = Value::from(info.user_agent);
+            version["V8-Version"] = Value::from(info.js_version);
+        }
+
+        version
+    }
+
+    /// The `/json/list` of the pages of the browser.
+    async fn targets(&self, host: &str) -> Value {
+        let mut targets = Vec::new();
+
+        for page in self.browser.pages().await.unwrap_or_default() {
+            let title = page.get_title().await.ok().flatten().unwrap_or_default();
+            let url = page.url().await.ok().flatten().unwrap_or_default();
+
+            targets.push(target_entry(
+                host,
+                page.target_id().as_ref(),
+                &title,
+                &url,
+                &self.access.token_query(),
+            ));
+        }
+
+        Value::from(targets)
+    }
+
+    /// Accept the websocket of the connection and relay its messages to the browser.
+    async fn relay(&self, mut stream: TcpStream, request: &RequestHead) -> std::io::Result<()> {
+        let upstream = match upstream_url(self.browser.websocket_address(), &request.path) {
+            Some(url) => tokio_tungstenite::connect_async(url).await.ok(),
+            _ => None,
+        };
+        let (Some((upstream, _)), Some(key)) = (upstream, request.header("sec-websocket-key"))
+        else {
+            stream
+                .write_all(http_response("502 Bad Gateway", "").as_bytes())
+                .await?;
+            return stream.shutdown().await;
+        };
+
+        let accept = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nupgrade: websocket\r\nconnection: Upgrade\r\nsec-websocket-accept: {}\r\n\r\n",
+            derive_accept_key(key.as_bytes())
+        );
+        stream.write_all(accept.as_bytes()).await?;
+
+        let client = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+        let (mut client_tx, mut client_rx) = client.split();
+        let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+        let to_browser = async {
+            while let Some(Ok(message)) = client_rx.next().await {
+                if message.is_close() {
+                    break;
+                }
+                if (message.is_text() || message.is_binary())
+                    && upstream_tx.send(message).await.is_err()
+                {
+                    break;
+                }
+            }
+            let _ = upstream_tx.close().await;
+        };
+        let to_devtools = async {
+            while let Some(Ok(message)) = upstream_rx.next().await {
+                if message.is_close() {
+                    break;
+                }
+                if (message.is_text() || message.is_binary())
+                    && client_tx.send(message).await.is_err()
+                {
+                    break;
+                }
+            }
+            let _ = client_tx.close().await;
+        };
+
+        futures::future::select(std::pin::pin!(to_browser), std::pin::pin!(to_devtools)).await;
+
+        Ok(())
+    }
+}
+
+/// Read the head of the request, up to the blank line. The bytes are read one at a time, the
+/// websocket frames following the head must stay in the stream.
+pub(crate) async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut head = Vec::with_capacity(1024);
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD_BYTES {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "request head too large",
+            ));
+        }
+        head.push(stream.read_u8().await?);
+    }
+
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_upgrade_requests() {
+        let request = RequestHead::parse(
+            "GET /devtools/page/ABC HTTP/1.1\r\nHost: localhost:9333\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(request.path, "/devtools/page/ABC");
+        assert_eq!(request.header("host"), Some("localhost:9333"));
+        assert!(request.is_websocket());
+        assert!(!RequestHead::parse("GET /json HTTP/1.1\r\n\r\n")
+            .unwrap()
+            .is_websocket());
+    }
+
+    #[test]
+    fn relays_to_the_browser_websocket() {
+        assert_eq!(
+            upstream_url(
+                "ws://127.0.0.1:9222/devtools/browser/1f2e",
+                "/devtools/page/ABC?x=1"
+            )
+            .as_deref(),
+            Some("ws://127.0.0.1:9222/devtools/page/ABC")
+        );
+        assert_eq!(upstream_url("not a url", "/devtools/page/ABC"), None);
+    }
+
+    #[test]
+    fn lists_the_targets() {
+        let entry = target_entry(
+            "localhost:9333",
+            "ABC",
+            "Example",
+            "https://example.com/",
+            "",
+        );
+
+        assert_eq!(
+            entry["webSocketDebuggerUrl"],
+            "ws://localhost:9333/devtools/page/ABC"
+        );
+        assert_eq!(
+            entry["devtoolsFrontendUrl"],
+            "devtools://devtools/bundled/inspector.html?ws=localhost:9333/devtools/page/ABC"
+        );
+
+        let query = AccessPolicy::default().with_token("s3cret").token_query();
+        let entry = target_entry("localhost:9333", "ABC", "Example", "", &query);
+
+        assert_eq!(
+            entry["webSocketDebuggerUrl"],
+            "ws://localhost:9333/devtools/page/ABC?token=s3cret"
+        );
+        assert_eq!(
+            entry["devtoolsFrontendUrl"],
+            "devtools://devtools/bundled/inspector.html?ws=localhost:9333/devtools/page/ABC%3Ftoken%3Ds3cret"
+        );
+    }
+
+    fn request(path: &str, headers: &[(&str, &str)]) -> RequestHead {
+        let head: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}\r\n"))
+            .collect();
+
+        RequestHead::parse(&format!("GET {path} HTTP/1.1\r\n{head}\r\n")).unwrap()
+    }
+
+    #[test]
+    fn rejects_the_foreign_hosts() {
+        let access = AccessPolicy::default();
+
+        for host in [
+            "localhost:9333",
+            "127.0.0.1:9333",
+            "[::1]:9333",
+            "LOCALHOST",
+        ] {
+            assert_eq!(access.rejection(&request("/json", &[("Host", host)])), None);
+        }
+        assert_eq!(
+            access.rejection(&request("/json", &[("Host", "attacker.example:9333")])),
+            Some("403 Forbidden")
+        );
+        assert_eq!(
+            access.rejection(&request("/json", &[])),
+            Some("403 Forbidden")
+        );
+        assert_eq!(
+            access
+                .allow_host("devbox")
+                .rejection(&request("/json", &[("Host", "devbox:9333")])),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_the_foreign_origins() {
+        let access = AccessPolicy::default().allow_origin(DEVTOOLS_ORIGIN);
+        let host = ("Host", "localhost:9333");
+
+        for origin in ["", DEVTOOLS_ORIGIN, "http://localhost:9333"] {
+            assert_eq!(
+                access.rejection(&request("/json", &[host, ("Origin", origin)])),
+                None
+            );
+        }
+        for origin in ["https://attacker.example", "null", "http://localhost:8080"] {
+            assert_eq!(
+                access.rejection(&request("/json", &[host, ("Origin", origin)])),
+                Some("403 Forbidden")
+            );
+        }
+    }
+
+    #[test]
+    fn requires_the_token() {
+        let access = AccessPolicy::default().with_token("s3cret");
+        let host = ("Host", "127.0.0.1:9333");
+
+        assert_eq!(
+            access.rejection(&request("/json", &[host])),
+            Some("401 Unauthorized")
+        );
+        assert_eq!(
+            access.rejection(&request("/json?token=wrong", &[host])),
+            Some("401 Unauthorized")
+        );
+        assert_eq!(
+            access.rejection(&request("/devtools/page/ABC?token=s3cret", &[host])),
+            None
+        );
+        assert_eq!(
+            access.rejection(&request(
+                "/json",
+                &[host, ("Authorization", "Bearer s3cret")]
+            )),
+            None
+        );
+    }
+}
@@ -0,0 +1,931 @@
+//! Semantic diffing of two versions of a page: the html is reduced to its blocks of visible
+//! text with the dynamic noise normalized (timestamps, tokens, ad slots), so a recrawl pipeline
+//! can tell a meaningful change from a page only re-rendered.
+
+use std::collections::HashSet;
+
+use sha2::{Digest, Sha256};
+
+/// The elements starting a new block of text.
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "td",
+    "th",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// The elements never rendered as text.
+const HIDDEN_TAGS: &[&str] = &["head", "noscript", "script", "style", "svg", "template"];
+
+/// The elements without an end tag.
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// The max cells of the table matching the blocks, larger pages are compared as sets.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// How the pages are normalized and compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffOptions {
+    /// The elements left out with their content, e.g. ad slots or rotating widgets. Simple
+    /// selectors are supported: `tag`, `#id`, `.class`, `[attr]`, `[attr=value]`,
+    /// `[attr^=prefix]` and their compounds like `ins.adsbygoogle`.
+    pub ignore_selectors: Vec<String>,
+    /// Replace dates, times and relative times like `5 minutes ago`.
+    pub normalize_timestamps: bool,
+    /// Replace long random looking tokens, e.g. csrf tokens, nonces or session ids.
+    pub normalize_tokens: bool,
+    /// The share of common words of a removed and an added block reported as one changed block.
+    pub similarity: f64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            ignore_selectors: [
+                "ins.adsbygoogle",
+                "[id^=google_ads]",
+                "[id^=div-gpt-ad]",
+                "[data-ad-slot]",
+                "[data-ad-unit]",
+                ".advertisement",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            normalize_timestamps: true,
+            normalize_tokens: true,
+            similarity: 0.5,
+        }
+    }
+}
+
+/// A change of a block of text.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BlockChange {
+    /// A block only in the new page.
+    Added {
+        /// The index of the block in the new page.
+        index: usize,
+        /// The normalized text.
+        text: String,
+    },
+    /// A block only in the old page.
+    Removed {
+        /// The index of the block in the old page.
+        index: usize,
+        /// The normalized text.
+        text: String,
+    },
+    /// A block edited between the pages.
+    Changed {
+        /// The index of the block in the old page.
+        old_index: usize,
+        /// The index of the block in the new page.
+        new_index: usize,
+        /// The old normalized text.
+        old: String,
+        /// The new normalized text.
+        new: String,
+    },
+}
+
+/// The changes between two pages.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HtmlDiff {
+    /// The blocks of the old page.
+    pub old_blocks: usize,
+    /// The blocks of the new page.
+    pub new_blocks: usize,
+    /// The changed blocks in page order.
+    pub changes: Vec<BlockChange>,
+}
+
+impl HtmlDiff {
+    /// The pages differ after normalizing.
+    pub fn is_changed(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// The share of blocks changed, between 0 and 1.
+    pub fn change_ratio(&self) -> f64 {
+        let total = self.old_blocks.max(self.new_blocks);
+
+        if total == 0 {
+            0.0
+        } else {
+            (self.changes.len() as f64 / total as f64).min(1.0)
+        }
+    }
+}
+
+/// Compare two versions of a page.
+pub fn html_diff(old: &str, new: &str, options: &DiffOptions) -> HtmlDiff {
+    let old = normalized_blocks(old, options);
+    let new = normalized_blocks(new, options);
+
+    HtmlDiff {
+        old_blocks: old.len(),
+        new_blocks: new.len(),
+        changes: diff_blocks(&old, &new, options.similarity),
+    }
+}
+
+/// A stable hash of the normalized visible text and the key metadata of the page: the title,
+/// the description, the robots directives, the canonical url and the language. Unchanged pages
+/// keep their fingerprint across crawls, so their reprocessing can be skipped.
+pub fn content_fingerprint(html: &str, options: &DiffOptions) -> String {
+    let mut hasher = Sha256::new();
+
+    hasher.update(b"fingerprint|v1\n");
+    for (key, value) in key_metadata(html) {
+        hasher.update(format!("{key}={value}\n").as_bytes());
+    }
+    for block in normalized_blocks(html, options) {
+        hasher.update(b"\n");
+        hasher.update(block.as_bytes());
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// A 64 bit simhash of the normalized visible text, from its shingles of three words. Near
+/// duplicate pages, e.g. the pages of a template with a few words changed, have hashes only a
+/// few bits apart, see `simhash_distance`.
+pub fn content_simhash(html: &str, options: &DiffOptions) -> u64 {
+    let blocks = normalized_blocks(html, options);
+    let words: Vec<&str> = blocks.iter().flat_map(|b| b.split_whitespace()).collect();
+    let mut weights = [0i64; 64];
+
+    if words.is_empty() {
+        return 0;
+    }
+
+    let mut add = |feature: &[&str]| {
+        let hash = fnv1a(feature);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if (hash >> bit) & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    };
+
+    if words.len() < 3 {
+        words.iter().for_each(|word| add(&[*word]));
+    } else {
+        words.windows(3).for_each(&mut add);
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// The number of bits two simhashes differ by.
+pub fn simhash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// The FNV-1a hash of the lowercased words of a shingle.
+fn fnv1a(words: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for (i, word) in words.iter().enumerate() {
+        let separator = (i > 0).then_some(b' ');
+        for byte in separator
+            .into_iter()
+            .chain(word.bytes().map(|b| b.to_ascii_lowercase()))
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    hash
+}
+
+/// The metadata of the page changing how it is indexed.
+fn key_metadata(html: &str) -> Vec<(&'static str, String)> {
+    let mut metadata = Vec::new();
+    let mut rest = html;
+
+    while let Some(at) = rest.find('<') {
+        rest = &rest[at..];
+
+        let Some((tag, read)) = parse_tag(rest) else {
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[read..];
+
+        if tag.end {
+            continue;
+        }
+
+        let attr = |name: &str| {
+            tag.attrs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.trim().to_string())
+        };
+
+        match tag.name.as_str() {
+            "html" => metadata.extend(attr("lang").map(|lang| ("lang", lang))),
+            "title" => {
+                let end = find_ascii_case_insensitive(rest, "</title").unwrap_or(rest.len());
+                let title = decode_entities(&rest[..end]);
+                metadata.push((
+                    "title",
+                    title.split_whitespace().collect::<Vec<_>>().join(" "),
+                ));
+            }
+            "meta" => {
+                let name = attr("name").unwrap_or_default().to_ascii_lowercase();
+
+                if matches!(name.as_str(), "description" | "robots") {
+                    let key = if name == "robots" {
+                        "robots"
+                    } else {
+                        "description"
+                    };
+                    metadata.extend(attr("content").map(|content| (key, content)));
+                }
+            }
+            "link" => {
+                let canonical = attr("rel").is_some_and(|rel| {
+                    rel.split_ascii_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("canonical"))
+                });
+
+                if canonical {
+                    metadata.extend(attr("href").map(|href| ("canonical", href)));
+                }
+            }
+            "body" => break,
+            _ => {}
+        }
+    }
+
+    metadata
+}
+
+/// A parsed simple selector.
+#[derive(Debug, Default)]
+struct Selector {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    /// The name, the operator and the value of the attributes.
+    attrs: Vec<(String, Option<(bool, String)>)>,
+}
+
+impl Selector {
+    fn parse(selector: &str) -> Option<Self> {
+        let mut out = Self::default();
+        let mut rest = selector.trim();
+
+        // combinators are not supported.
+        if rest.is_empty()
+            || rest.contains(|c: char| c.is_whitespace() || c == '>' || c == '+' || c == '~')
+        {
+            return None;
+        }
+
+        let tag_end = rest.find(['#', '.', '[']).unwrap_or(rest.len());
+        if tag_end > 0 {
+            out.tag = Some(rest[..tag_end].to_ascii_lowercase());
+        }
+        rest = &rest[tag_end..];
+
+        while !rest.is_empty() {
+            if let Some(attr) = rest.strip_prefix('[') {
+                let end = attr.find(']')?;
+                let (body, tail) = (&attr[..end], &attr[end + 1..]);
+                let unquote = |v: &str| v.trim().trim_matches(['"', '\'']).to_string();
+
+                let attr = if let Some((name, value)) = body.split_once("^=") {
+                    (
+                        name.trim().to_ascii_lowercase(),
+                        Some((true, unquote(value))),
+                    )
+                } else if let Some((name, value)) = body.split_once('=') {
+                    (
+                        name.trim().to_ascii_lowercase(),
+                        Some((false, unquote(value))),
+                    )
+                } else {
+                    (body.trim().to_ascii_lowercase(), None)
+                };
+
+                out.attrs.push(attr);
+                rest = tail;
+            } else {
+                let (is_id, tail) = match rest.strip_prefix('#') {
+                    Some(tail) => (true, tail),
+                    None => (false, rest.strip_prefix('.')?),
+                };
+                let end = tail.find(['#', '.', '[']).unwrap_or(tail.len());
+
+                if is_id {
+                    out.id = Some(tail[..end].to_string());
+                } else {
+                    out.classes.push(tail[..end].to_string());
+                }
+                rest = &tail[end..];
+            }
+        }
+
+        Some(out)
+    }
+
+    fn matches(&self, tag: &str, attrs: &[(String, String)]) -> bool {
+        let attr = |name: &str| {
+            attrs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        };
+
+        self.tag.as_deref().map_or(true, |t| t == tag)
+            && self.id.as_deref().map_or(true, |id| attr("id") == Some(id))
+            && self.classes.iter().all(|class| {
+                attr("class").is_some_and(|v| v.split_ascii_whitespace().any(|c| c == class))
+            })
+            && self.attrs.iter().all(|(name, op)| match (attr(name), op) {
+                (Some(_), None) => true,
+                (Some(v), Some((true, prefix))) => v.starts_with(prefix.as_str()),
+                (Some(v), Some((false, value))) => v == value,
+                (None, _) => false,
+            })
+    }
+}
+
+/// A tag of the html.
+struct Tag {
+    name: String,
+    attrs: Vec<(String, String)>,
+    end: bool,
+    self_closing: bool,
+}
+
+/// Parse the tag at the start of the input, returning it with the bytes read.
+fn parse_tag(input: &str) -> Option<(Tag, usize)> {
+    let close = input.find('>')?;
+    let mut inner = &input[1..close];
+
+    let end = inner.starts_with('/');
+    if end {
+        inner = &inner[1..];
+    }
+    let self_closing = inner.ends_with('/');
+    if self_closing {
+        inner = &inner[..inner.len() - 1];
+    }
+
+    let name_end = inner
+        .find(|c: char| c.is_ascii_whitespace())
+        .unwrap_or(inner.len());
+    let name = inner[..name_end].to_ascii_lowercase();
+
+    if name.is_empty() || !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let mut attrs = Vec::new();
+    let mut rest = inner[name_end..].trim_start();
+
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_ascii_lowercase();
+        rest = rest[key_end..].trim_start();
+
+        let mut value = String::new();
+
+        if let Some(tail) = rest.strip_prefix('=') {
+            let tail = tail.trim_start();
+
+            let (v, tail) = match tail.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = tail[1..].find(quote).map_or(tail.len(), |i| i + 1);
+                    (&tail[1..end], tail.get(end + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = tail
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(tail.len());
+                    (&tail[..end], &tail[end..])
+                }
+            };
+
+            value = decode_entities(v);
+            rest = tail.trim_start();
+        }
+
+        if key.is_empty() {
+            break;
+        }
+        attrs.push((key, value));
+    }
+
+    Some((
+        Tag {
+            name,
+            attrs,
+            end,
+            self_closing,
+        },
+        close + 1,
+    ))
+}
+
+/// Decode the common character references.
+pub(crate) fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The blocks of visible text of the page, normalized with the options.
+pub fn normalized_blocks(html: &str, options: &DiffOptions) -> Vec<String> {
+    let selectors: Vec<_> = options
+        .ignore_selectors
+        .iter()
+        .filter_map(|selector| Selector::parse(selector))
+        .collect();
+
+    let mut blocks = Vec::new();
+    let mut block = String::new();
+    // the element hiding its content with the depth of its nested elements of the same name.
+    let mut hidden: Option<(String, usize)> = None;
+    let mut rest = html;
+
+    let mut flush = |block: &mut String| {
+        let text = normalize_text(block, options);
+        if !text.is_empty() {
+            blocks.push(text);
+        }
+        block.clear();
+    };
+
+    while let Some(at) = rest.find('<') {
+        if hidden.is_none() {
+            block.push_str(&decode_entities(&rest[..at]));
+            block.push(' ');
+        }
+        rest = &rest[at..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let Some((tag, read)) = parse_tag(rest) else {
+            if hidden.is_none() {
+                block.push('<');
+            }
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[read..];
+
+        if !tag.end && !tag.self_closing && matches!(tag.name.as_str(), "script" | "style") {
+            // raw text, the content may contain tags.
+            let close = format!("</{}", tag.name);
+            rest = find_ascii_case_insensitive(rest, &close).map_or("", |end| &rest[end..]);
+        }
+
+        if let Some((name, depth)) = hidden.as_mut() {
+            if *name == tag.name && !tag.self_closing {
+                if tag.end {
+                    *depth -= 1;
+                } else {
+                    *depth += 1;
+                }
+                if *depth == 0 {
+                    hidden = None;
+                }
+            }
+            continue;
+        }
+
+        if BLOCK_TAGS.contains(&tag.name.as_str()) {
+            flush(&mut block);
+        }
+
+        if tag.end || tag.self_closing || VOID_TAGS.contains(&tag.name.as_str()) {
+            if tag.name == "img" {
+                if let Some((_, alt)) = tag.attrs.iter().find(|(name, _)| name == "alt") {
+                    block.push_str(alt);
+                    block.push(' ');
+                }
+            }
+            continue;
+        }
+
+        if HIDDEN_TAGS.contains(&tag.name.as_str())
+            || selectors
+                .iter()
+                .any(|selector| selector.matches(&tag.name, &tag.attrs))
+        {
+            hidden = Some((tag.name, 1));
+        }
+    }
+
+    if hidden.is_none() {
+        block.push_str(&decode_entities(rest));
+    }
+    flush(&mut block);
+
+    blocks
+}
+
+fn find_ascii_case_insensitive(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// The units of a relative time like `5 minutes ago`.
+const TIME_UNITS: &[&str] = &[
+    "sec", "secs", "second", "seconds", "min", "mins", "minute", "minutes", "hour", "hours", "hr",
+    "hrs", "day", "days", "week", "weeks", "month", "months", "year", "years",
+];
+
+/// A date or a time like `2024-01-31`, `31/01/2024`, `12:30:05` or `2024-01-31T12:30:05Z`.
+fn is_timestamp(word: &str) -> bool {
+    let groups = word
+        .split(|c: char| matches!(c, '-' | '/' | ':' | '.' | 'T' | 'Z' | '+'))
+        .filter(|group| !group.is_empty())
+        .collect::<Vec<_>>();
+
+    groups.len() >= 2
+        && word.len() >= 4
+        && word.contains(['-', '/', ':'])
+        && groups
+            .iter()
+            .all(|group| group.len() <= 4 && group.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// A random looking token like a csrf token, a nonce or a session id.
+fn is_token(word: &str) -> bool {
+    word.len() >= 20
+        && word
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'=' | b'+' | b'/'))
+        && word.bytes().any(|b| b.is_ascii_digit())
+        && word.bytes().any(|b| b.is_ascii_alphabetic())
+}
+
+/// Collapse the whitespace of the text and replace the dynamic noise.
+fn normalize_text(text: &str, options: &DiffOptions) -> String {
+    let words: Vec<_> = text.split_whitespace().collect();
+    let mut out = Vec::with_capacity(words.len());
+
+    for (i, word) in words.iter().enumerate() {
+        let bare = word.trim_matches(|c: char| matches!(c, ',' | ';' | '(' | ')' | '[' | ']'));
+
+        let relative_time = bare.bytes().all(|b| b.is_ascii_digit())
+            && !bare.is_empty()
+            && words
+                .get(i + 1)
+                .is_some_and(|unit| TIME_UNITS.contains(&unit.to_ascii_lowercase().as_str()))
+            && words
+                .get(i + 2)
+                .is_some_and(|ago| ago.eq_ignore_ascii_case("ago"));
+
+        if options.normalize_timestamps && (relative_time || is_timestamp(bare)) {
+            out.push("<time>");
+        } else if options.normalize_tokens && is_token(bare) {
+            out.push("<token>");
+        } else {
+            out.push(word);
+        }
+    }
+
+    out.join(" ")
+}
+
+/// The share of the words common to both texts.
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<_> = a.split_whitespace().collect();
+    let b: HashSet<_> = b.split_whitespace().collect();
+    let union = a.union(&b).count();
+
+    if union == 0 {
+        1.0
+    } else {
+        a.intersection(&b).count() as f64 / union as f64
+    }
+}
+
+/// An edit of the block sequences.
+enum Op {
+    Keep,
+    Remove(usize),
+    Add(usize),
+}
+
+/// The edits turning the old blocks into the new blocks.
+fn edits(old: &[String], new: &[String]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let mut ops: Vec<Op> = (0..prefix).map(|_| Op::Keep).collect();
+
+    if (a.len() + 1) * (b.len() + 1) <= MAX_LCS_CELLS {
+        let width = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * width];
+
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * width + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push(Op::Keep);
+                i += 1;
+                j += 1;
+            } else if j < b.len()
+                && (i == a.len() || lcs[i * width + j + 1] >= lcs[(i + 1) * width + j])
+            {
+                ops.push(Op::Add(prefix + j));
+                j += 1;
+            } else {
+                ops.push(Op::Remove(prefix + i));
+                i += 1;
+            }
+        }
+    } else {
+        // too large for the table, the blocks only in one page are changed.
+        let in_a: HashSet<_> = a.iter().collect();
+        let in_b: HashSet<_> = b.iter().collect();
+
+        ops.extend(
+            a.iter()
+                .enumerate()
+                .filter(|(_, block)| !in_b.contains(block))
+                .map(|(i, _)| Op::Remove(prefix + i)),
+        );
+        ops.extend(
+            b.iter()
+                .enumerate()
+                .filter(|(_, block)| !in_a.contains(block))
+                .map(|(j, _)| Op::Add(prefix + j)),
+        );
+    }
+
+    ops
+}
+
+/// The changes between the blocks, removed and added blocks alike enough are changed blocks.
+fn diff_blocks(old: &[String], new: &[String], min_similarity: f64) -> Vec<BlockChange> {
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+
+    let mut flush = |removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        let mut paired = vec![false; added.len()];
+
+        for &i in removed.iter() {
+            let pair = added
+                .iter()
+                .enumerate()
+                .find(|(k, &j)| !paired[*k] && similarity(&old[i], &new[j]) >= min_similarity);
+
+            match pair {
+                Some((k, &j)) => {
+                    paired[k] = true;
+                    changes.push(BlockChange::Changed {
+                        old_index: i,
+                        new_index: j,
+                        old: old[i].clone(),
+                        new: new[j].clone(),
+                    });
+                }
+                None => changes.push(BlockChange::Removed {
+                    index: i,
+                    text: old[i].clone(),
+                }),
+            }
+        }
+
+        for (k, &j) in added.iter().enumerate() {
+            if !paired[k] {
+                changes.push(BlockChange::Added {
+                    index: j,
+                    text: new[j].clone(),
+                });
+            }
+        }
+
+        removed.clear();
+        added.clear();
+    };
+
+    for op in edits(old, new) {
+        match op {
+            Op::Keep => flush(&mut removed, &mut added),
+            Op::Remove(i) => removed.push(i),
+            Op::Add(j) => added.push(j),
+        }
+    }
+    flush(&mut removed, &mut added);
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_visible_blocks() {
+        let html = r#"<html><head><title>Ignored head</title><script>if (a < b) { x = "<p>" }</script></head>
+            <body><h1>Prices &amp; plans</h1><p>Starter <b>plan</b><img src="a.png" alt="logo"></p>
+            <ins class="adsbygoogle" data-ad-slot="1"><p>Buy now</p></ins>
+            <div id="div-gpt-ad-123"><div>Ad</div></div><ul><li>One</li><li>Two</li></ul></body></html>"#;
+
+        assert_eq!(
+            normalized_blocks(html, &DiffOptions::default()),
+            vec!["Prices & plans", "Starter plan logo", "One", "Two"]
+        );
+    }
+
+    #[test]
+    fn ignores_dynamic_noise() {
+        let options = DiffOptions::default();
+        let old =
+            "<p>Updated 2024-01-31T12:30:05Z, 5 minutes ago</p><p>token a8f3k2j9d8s7f6g5h4j3k2</p>";
+        let new = "<p>Updated 2024-02-01T08:00:00Z, 12 minutes ago</p><p>token z9y8x7w6v5u4t3s2r1q0p9</p>";
+
+        assert!(!html_diff(old, new, &options).is_changed());
+
+        let strict = DiffOptions {
+            normalize_timestamps: false,
+            normalize_tokens: false,
+            ..Default::default()
+        };
+
+        assert!(html_diff(old, new, &strict).is_changed());
+    }
+
+    #[test]
+    fn fingerprints_content_and_metadata() {
+        let options = DiffOptions::default();
+        let page = |title: &str, body: &str| {
+            format!(
+                r#"<html lang="en"><head><title>{title}</title><meta name="description" content="Docs"><link rel="canonical" href="https://example.com/"></head><body>{body}</body></html>"#
+            )
+        };
+
+        let fingerprint = content_fingerprint(&page("Home", "<p>Hello</p>"), &options);
+
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(
+            fingerprint,
+            content_fingerprint(&page("Home", "<p>Hello</p> <!-- 2024-01-31 -->"), &options)
+        );
+        assert_eq!(
+            fingerprint,
+            content_fingerprint(
+                &page("Home", "<p>Hello</p><script>var t=1</script>"),
+                &options
+            )
+        );
+        assert_ne!(
+            fingerprint,
+            content_fingerprint(&page("About", "<p>Hello</p>"), &options)
+        );
+        assert_ne!(
+            fingerprint,
+            content_fingerprint(&page("Home", "<p>Hello world</p>"), &options)
+        );
+    }
+
+    #[test]
+    fn simhashes_near_duplicates_close() {
+        let options = DiffOptions::default();
+        let listing = |day: &str| {
+            format!(
+                "<h1>Events calendar</h1><p>There are no events scheduled for {day}. Browse the previous or the next day to find upcoming concerts, talks and workshops in the city.</p><p>Subscribe to the newsletter to get the weekly agenda of the venue delivered to your inbox.</p>"
+            )
+        };
+
+        let a = content_simhash(&listing("Monday"), &options);
+        let b = content_simhash(&listing("Tuesday"), &options);
+        let other = content_simhash(
+            "<h1>About us</h1><p>We are a small team building tools for archiving the web since 2012.</p>",
+            &options,
+        );
+
+        assert!(simhash_distance(a, b) <= 12);
+        assert!(simhash_distance(a, other) > simhash_distance(a, b));
+        assert_eq!(content_simhash("<p></p>", &options), 0);
+    }
+
+    #[test]
+    fn reports_block_changes() {
+        let old = "<h1>News</h1><p>The quick brown fox jumps</p><p>Old story</p><p>Footer</p>";
+        let new = "<h1>News</h1><p>The quick brown fox leaps</p><p>Footer</p><p>Contact us</p>";
+
+        let diff = html_diff(old, new, &DiffOptions::default());
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                BlockChange::Changed {
+                    old_index: 1,
+                    new_index: 1,
+                    old: "The quick brown fox jumps".into(),
+                    new: "The quick brown fox leaps".into(),
+                },
+                BlockChange::Removed {
+                    index: 2,
+                    text: "Old story".into(),
+                },
+                BlockChange::Added {
+                    index: 3,
+                    text: "Contact us".into(),
+                },
+            ]
+        );
+        assert!((diff.change_ratio() - 0.75).abs() < f64::EPSILON);
+    }
+}
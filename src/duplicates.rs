@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::diff::simhash_distance;
+
+/// The bits two pages differ by at most to be near duplicates by default.
+pub const DEFAULT_DUPLICATE_DISTANCE: u32 = 4;
+
+/// Urls whose content are near duplicates of each other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    /// The simhash of the first url of the cluster.
+    pub simhash: u64,
+    /// The urls in the order they were indexed.
+    pub urls: Vec<String>,
+}
+
+/// The pages indexed with the bands of their simhash.
+#[derive(Debug, Default)]
+struct IndexState {
+    /// The url and simhash of every page by id.
+    pages: Vec<(String, u64)>,
+    /// The id of every url.
+    ids: HashMap<String, usize>,
+    /// The ids of the pages by band and bits of the band.
+    bands: HashMap<(u8, u64), Vec<usize>>,
+    /// The union find parent of every page.
+    parents: Vec<usize>,
+}
+
+impl IndexState {
+    fn root(&mut self, mut id: usize) -> usize {
+        while self.parents[id] != id {
+            self.parents[id] = self.parents[self.parents[id]];
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        // the oldest page stays the root of the cluster.
+        if a != b {
+            self.parents[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+/// An in memory index of the simhashes of the crawled pages, clustering the near duplicates,
+/// e.g. the pages of a calendar or of faceted filters generated from one template.
+#[derive(Debug)]
+pub struct DuplicateIndex {
+    /// The bits two pages differ by at most to be near duplicates.
+    max_distance: u32,
+    state: Mutex<IndexState>,
+}
+
+impl Default for DuplicateIndex {
+    fn default() -> Self {
+        Self::new(DEFAULT_DUPLICATE_DISTANCE)
+    }
+}
+
+impl DuplicateIndex {
+    /// An index of the pages at most `max_distance` bits apart.
+    pub fn new(max_distance: u32) -> Self {
+        Self {
+            max_distance: max_distance.min(63),
+            state: Default::default(),
+        }
+    }
+
+    /// The bits of the bands of a simhash. With one more band than the max distance, two
+    /// near duplicates share at least one band.
+    fn bands(&self, simhash: u64) -> impl Iterator<Item = (u8, u64)> {
+        let count = self.max_distance as usize + 1;
+        let width = 64 / count;
+
+        (0..count).map(move |band| {
+            let start = band * width;
+            let end = if band + 1 == count { 64 } else { start + width };
+            let mask = if end - start == 64 {
+                u64::MAX
+            } else {
+                (1u64 << (end - start)) - 1
+            };
+
+            (band as u8, (simhash >> start) & mask)
+        })
+    }
+
+    /// Index the simhash of a page, see `crate::diff::content_simhash`. Returns the first url
+    /// indexed of the near duplicates of the page. A url indexed again keeps its first simhash.
+    pub fn insert(&self, url: impl Into<String>, simhash: u64) -> Option<String> {
+        let url = url.into();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(id) = state.ids.get(&url).copied() {
+            let root = state.root(id);
+            return (root != id).then(|| state.pages[root].0.clone());
+        }
+
+        let id = state.pages.len();
+        let mut duplicates = Vec::new();
+
+        for band in self.bands(simhash) {
+            if let Some(candidates) = state.bands.get(&band) {
+                duplicates.extend(candidates.iter().copied().filter(|candidate| {
+                    simhash_distance(state.pages[*candidate].1, simhash) <= self.max_distance
+                }));
+            }
+            state.bands.entry(band).or_default().push(id);
+        }
+
+        state.pages.push((url.clone(), simhash));
+        state.ids.insert(url, id);
+        state.parents.push(id);
+
+        duplicates.sort_unstable();
+        duplicates.dedup();
+
+        for duplicate in &duplicates {
+            state.union(id, *duplicate);
+        }
+
+        let root = state.root(id);
+
+        (root != id).then(|| state.pages[root].0.clone())
+    }
+
+    /// The clusters of near duplicates with at least two urls, the largest first.
+    pub fn clusters(&self) -> Vec<DuplicateCluster> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        for id in 0..state.pages.len() {
+            let root = state.root(id);
+            clusters.entry(root).or_default().push(id);
+        }
+
+        let mut clusters: Vec<DuplicateCluster> = clusters
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(root, ids)| DuplicateCluster {
+                simhash: state.pages[root].1,
+                urls: ids
+                    .into_iter()
+                    .map(|id| state.pages[id].0.clone())
+                    .collect(),
+            })
+            .collect();
+
+        clusters.sort_by(|a, b| b.urls.len().cmp(&a.urls.len()).then(a.urls.cmp(&b.urls)));
+        clusters
+    }
+
+    /// The number of pages indexed.
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pages
+            .len()
+    }
+
+    /// No page is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clusters_the_near_duplicates() {
+        let index = DuplicateIndex::new(3);
+        let base = 0xf0f0_1234_abcd_0001u64;
+
+        assert_eq!(index.insert("https://a.com/cal/1", base), None);
+        assert_eq!(
+            index.insert("https://a.com/cal/2", base ^ 0b101),
+            Some("https://a.com/cal/1".into())
+        );
+        // within the distance of the second page only, joined through it.
+        assert_eq!(
+            index.insert("https://a.com/cal/3", base ^ 0b101 ^ (0b11 << 40)),
+            Some("https://a.com/cal/1".into())
+        );
+        assert_eq!(index.insert("https://a.com/about", !base), None);
+        assert_eq!(index.insert("https://a.com/about", base), None);
+
+        assert_eq!(
+            index.clusters(),
+            vec![DuplicateCluster {
+                simhash: base,
+                urls: vec![
+                    "https://a.com/cal/1".into(),
+                    "https://a.com/cal/2".into(),
+                    "https://a.com/cal/3".into(),
+                ],
+            }]
+        );
+        assert_eq!(index.len(), 4);
+    }
+
+    #[test]
+    fn splits_the_hash_in_bands() {
+        let index = DuplicateIndex::new(0);
+        assert_eq!(index.bands(42).collect::<Vec<_>>(), vec![(0, 42)]);
+
+        let index = DuplicateIndex::new(2);
+        let bands: Vec<_> = index.bands(u64::MAX).collect();
+        assert_eq!(
+            bands,
+            vec![(0, (1 << 21) - 1), (1, (1 << 21) - 1), (2, (1 << 22) - 1)]
+        );
+    }
+}
@@ -14,8 +14,8 @@ use chromiumoxide_cdp::cdp::browser_protocol::page::{
     CaptureScreenshotFormat, CaptureScreenshotParams, Viewport,
 };
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
-    CallFunctionOnReturns, GetPropertiesParams, PropertyDescriptor, RemoteObjectId,
-    RemoteObjectType,
+    CallFunctionOnReturns, ExecutionContextId, GetPropertiesParams, PropertyDescriptor,
+    RemoteObjectId, RemoteObjectType,
 };
 
 use crate::error::{CdpError, Result};
@@ -38,6 +38,15 @@ pub struct Element {
 
 impl Element {
     pub(crate) async fn new(tab: Arc<PageInner>, node_id: NodeId) -> Result<Self> {
+        Self::with_context(tab, node_id, None).await
+    }
+
+    /// Resolve the element in an execution context, the main world when `None`.
+    pub(crate) async fn with_context(
+        tab: Arc<PageInner>,
+        node_id: NodeId,
+        execution_context_id: Option<ExecutionContextId>,
+    ) -> Result<Self> {
         let backend_node_id = tab
             .execute(
                 DescribeNodeParams::builder()
@@ -51,11 +60,11 @@ impl Element {
             .backend_node_id;
 
         let resp = tab
-            .execute(
-                ResolveNodeParams::builder()
-                    .backend_node_id(backend_node_id)
-                    .build(),
-            )
+            .execute(ResolveNodeParams {
+                backend_node_id: Some(backend_node_id),
+                execution_context_id,
+                ..Default::default()
+            })
             .await?;
 
         let remote_object_id = resp
@@ -423,6 +432,41 @@ impl Element {
             .collect())
     }
 
+    /// Returns the `PropertyDescriptor`s of this element like `Element::properties`, failing
+    /// with `CdpError::ResultTooLarge` before fetching more than about `max_bytes` of them. The
+    /// size is estimated in the page from the names and the primitive values of the properties,
+    /// without running their getters, an object value is only sent as a reference.
+    pub async fn properties_with_limit(
+        &self,
+        max_bytes: usize,
+    ) -> Result<HashMap<String, PropertyDescriptor>> {
+        let size = self
+            .call_js_fn(
+                r#"function() {
+                    let size = 0;
+                    for (const name of Object.getOwnPropertyNames(this)) {
+                        const { value } = Object.getOwnPropertyDescriptor(this, name);
+                        size += name.length + 64;
+                        if (typeof value === 'string') size += value.length;
+                        else if (typeof value === 'function') size += Function.prototype.toString.call(value).length;
+                    }
+                    return size;
+                }"#,
+                false,
+            )
+            .await?
+            .result
+            .value
+            .and_then(|size| size.as_u64())
+            .unwrap_or_default();
+
+        if size > max_bytes as u64 {
+            return Err(CdpError::ResultTooLarge(size, max_bytes as u64));
+        }
+
+        self.properties().await
+    }
+
     /// Scrolls the element into and takes a screenshot of it
     pub async fn screenshot(&self, format: CaptureScreenshotFormat) -> Result<Vec<u8>> {
         let mut bounding_box = self.scroll_into_view().await?.bounding_box().await?;
@@ -0,0 +1,391 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+use crate::runtime::Instant;
+
+/// How often the email source is polled.
+const EMAIL_POLL: Duration = Duration::from_secs(2);
+
+/// The words of the links of the verification emails.
+const VERIFICATION_WORDS: [&str; 8] = [
+    "verify",
+    "verification",
+    "confirm",
+    "activate",
+    "validate",
+    "magic",
+    "login",
+    "token=",
+];
+
+/// An email received by a [`EmailSource`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Email {
+    /// The unique id of the email in the source.
+    pub id: String,
+    /// The sender.
+    #[serde(default)]
+    pub from: String,
+    /// The recipients.
+    #[serde(default)]
+    pub to: Vec<String>,
+    /// The subject.
+    #[serde(default)]
+    pub subject: String,
+    /// The html body.
+    #[serde(default)]
+    pub html: Option<String>,
+    /// The plain text body.
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// A source of the received emails, e.g. an IMAP mailbox, a test mail server API or the
+/// deliveries of a mail webhook.
+pub trait EmailSource: Send + Sync {
+    /// The emails received so far. Emails seen before may be returned again.
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Email>>>;
+}
+
+impl<S: EmailSource + ?Sized> EmailSource for Arc<S> {
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Email>>> {
+        (**self).fetch()
+    }
+}
+
+/// An inbox the emails are pushed to, e.g. by the handler of a mail webhook.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryInbox {
+    emails: Arc<Mutex<Vec<Email>>>,
+}
+
+impl MemoryInbox {
+    /// Deliver an email.
+    pub fn push(&self, email: Email) {
+        if let Ok(mut emails) = self.emails.lock() {
+            emails.push(email);
+        }
+    }
+
+    /// Remove the delivered emails.
+    pub fn clear(&self) {
+        if let Ok(mut emails) = self.emails.lock() {
+            emails.clear();
+        }
+    }
+}
+
+impl EmailSource for MemoryInbox {
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Email>>> {
+        let emails = self
+            .emails
+            .lock()
+            .map(|emails| emails.clone())
+            .unwrap_or_default();
+
+        future::ready(Ok(emails)).boxed()
+    }
+}
+
+/// An HTTP endpoint listing the received emails as a JSON array of [`Email`].
+#[derive(Debug, Clone)]
+pub struct JsonInbox {
+    /// The url of the listing.
+    pub url: String,
+    /// The bearer token of the endpoint.
+    pub bearer_token: Option<String>,
+}
+
+impl JsonInbox {
+    /// The inbox listed at the url.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            bearer_token: None,
+        }
+    }
+}
+
+impl EmailSource for JsonInbox {
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Email>>> {
+        async move {
+            let mut request = crate::http_client::HTTP_CLIENT.get(&self.url);
+
+            if let Some(token) = self.bearer_token.as_deref() {
+                request = request.bearer_auth(token);
+            }
+
+            request
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+                .map_err(|e| CdpError::msg(e.to_string()))?
+                .json()
+                .await
+                .map_err(|e| CdpError::msg(e.to_string()))
+        }
+        .boxed()
+    }
+}
+
+/// The emails waited for and the links followed.
+#[derive(Debug, Clone)]
+pub struct EmailLinkOptions {
+    /// A recipient of the email, case insensitive.
+    pub to: Option<String>,
+    /// A part of the sender, case insensitive.
+    pub from_contains: Option<String>,
+    /// A part of the subject, case insensitive.
+    pub subject_contains: Option<String>,
+    /// A part of the link followed, the first verification link when `None`.
+    pub link_contains: Option<String>,
+    /// The ids of the emails received before the flow, skipped.
+    pub skip_ids: HashSet<String>,
+    /// The max wait for the email.
+    pub timeout: Duration,
+}
+
+impl Default for EmailLinkOptions {
+    fn default() -> Self {
+        Self {
+            to: None,
+            from_contains: None,
+            subject_contains: None,
+            link_contains: None,
+            skip_ids: HashSet::new(),
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl EmailLinkOptions {
+    /// The email is waited for.
+    pub fn matches(&self, email: &Email) -> bool {
+        let contains =
+            |value: &str, part: &str| value.to_lowercase().contains(&part.to_lowercase());
+
+        !self.skip_ids.contains(&email.id)
+            && self
+                .to
+                .as_deref()
+                .map_or(true, |to| email.to.iter().any(|r| contains(r, to)))
+            && self
+                .from_contains
+                .as_deref()
+                .map_or(true, |from| contains(&email.from, from))
+            && self
+                .subject_contains
+                .as_deref()
+                .map_or(true, |subject| contains(&email.subject, subject))
+    }
+
+    /// The link of the email followed.
+    pub fn link(&self, email: &Email) -> Option<String> {
+        let links = extract_links(email);
+
+        match self.link_contains.as_deref() {
+            Some(part) => links.into_iter().find(|link| link.contains(part)),
+            _ => verification_links(links).into_iter().next(),
+        }
+    }
+}
+
+/// Decode the html entities of an href.
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&amp;", "&")
+        .replace("&#38;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#x3D;", "=")
+        .replace("&#61;", "=")
+}
+
+/// The http links of the email, the links of the html body first, without duplicates.
+pub fn extract_links(email: &Email) -> Vec<String> {
+    let mut links = Vec::new();
+
+    if let Some(html) = email.html.as_deref() {
+        let lower = html.to_ascii_lowercase();
+        let mut rest = 0;
+
+        while let Some(start) = lower[rest..].find("href=").map(|i| rest + i + 5) {
+            let quote = html[start..].chars().next();
+            let (value_start, end) = match quote {
+                Some(q @ ('"' | '\'')) => {
+                    let value_start = start + 1;
+                    let end = html[value_start..]
+                        .find(q)
+                        .map_or(html.len(), |i| value_start + i);
+                    (value_start, end)
+                }
+                _ => {
+                    let end = html[start..]
+                        .find(|c: char| c.is_whitespace() || c == '>')
+                        .map_or(html.len(), |i| start + i);
+                    (start, end)
+                }
+            };
+
+            let link = decode_entities(html[value_start..end].trim());
+            if link.starts_with("http://") || link.starts_with("https://") {
+                links.push(link);
+            }
+            rest = end.max(start);
+        }
+    }
+
+    for body in [email.text.as_deref(), email.html.as_deref()]
+        .into_iter()
+        .flatten()
+    {
+        let mut rest = body;
+
+        while let Some(start) = rest.find("http") {
+            let candidate = &rest[start..];
+            let end = candidate
+                .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | ')'))
+                .unwrap_or(candidate.len());
+            let link = candidate[..end].trim_end_matches(['.', ',', ';']);
+
+            if link.starts_with("http://") || link.starts_with("https://") {
+                links.push(decode_entities(link));
+            }
+            rest = &candidate[end.max(4)..];
+        }
+    }
+
+    let mut seen = HashSet::new();
+    links.retain(|link| seen.insert(link.clone()));
+    links
+}
+
+/// The links looking like verification links, e.g. with `verify` or `confirm`.
+pub fn verification_links(links: Vec<String>) -> Vec<String> {
+    links
+        .into_iter()
+        .filter(|link| {
+            let link = link.to_ascii_lowercase();
+            VERIFICATION_WORDS.iter().any(|word| link.contains(word))
+        })
+        .collect()
+}
+
+/// Wait for the email of the options, polling the source.
+pub async fn wait_for_email(source: &dyn EmailSource, options: &EmailLinkOptions) -> Result<Email> {
+    let deadline = Instant::now() + options.timeout;
+
+    loop {
+        match source.fetch().await {
+            Ok(emails) => {
+                if let Some(email) = emails.into_iter().find(|email| options.matches(email)) {
+                    return Ok(email);
+                }
+            }
+            Err(err) => tracing::debug!("email source failed: {err:?}"),
+        }
+
+        if Instant::now() >= deadline {
+            return Err(CdpError::Timeout);
+        }
+
+        crate::runtime::sleep(EMAIL_POLL).await;
+    }
+}
+
+impl Page {
+    /// Wait for the email of the options and navigate its verification link, completing a
+    /// signup or an email verification. Returns the followed link.
+    pub async fn follow_email_link(
+        &self,
+        source: &dyn EmailSource,
+        options: &EmailLinkOptions,
+    ) -> Result<String> {
+        let email = wait_for_email(source, options).await?;
+        let link = options.link(&email).ok_or_else(|| {
+            CdpError::msg(format!(
+                "no verification link in the email {:?}",
+                email.subject
+            ))
+        })?;
+
+        self.goto(link.as_str()).await?;
+        self.wait_for_navigation().await?;
+
+        Ok(link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email {
+            id: "1".into(),
+            from: "Acme <no-reply@acme.test>".into(),
+            to: vec!["QA+42@example.com".into()],
+            subject: "Confirm your account".into(),
+            html: Some(
+                r#"<a href="https://acme.test/unsubscribe">x</a> <a HREF='https://acme.test/verify?u=1&amp;t=abc'>Confirm</a>"#
+                    .into(),
+            ),
+            text: Some("Or open https://acme.test/verify?u=1&t=abc. Help: https://acme.test/help".into()),
+        }
+    }
+
+    #[test]
+    fn extracts_the_verification_links() {
+        let links = extract_links(&email());
+
+        assert_eq!(
+            links,
+            vec![
+                "https://acme.test/unsubscribe",
+                "https://acme.test/verify?u=1&t=abc",
+                "https://acme.test/help",
+            ]
+        );
+        assert_eq!(
+            verification_links(links),
+            vec!["https://acme.test/verify?u=1&t=abc"]
+        );
+    }
+
+    #[test]
+    fn matches_the_emails() {
+        let options = EmailLinkOptions {
+            to: Some("qa+42@example.com".into()),
+            subject_contains: Some("confirm".into()),
+            ..Default::default()
+        };
+        assert!(options.matches(&email()));
+        assert_eq!(
+            options.link(&email()).as_deref(),
+            Some("https://acme.test/verify?u=1&t=abc")
+        );
+
+        let skipped = EmailLinkOptions {
+            skip_ids: HashSet::from(["1".to_string()]),
+            ..options
+        };
+        assert!(!skipped.matches(&email()));
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_pushed_email() {
+        let inbox = MemoryInbox::default();
+        inbox.push(email());
+
+        let options = EmailLinkOptions {
+            from_contains: Some("acme.test".into()),
+            ..Default::default()
+        };
+        assert_eq!(wait_for_email(&inbox, &options).await.unwrap().id, "1");
+    }
+}
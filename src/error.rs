@@ -1,7 +1,6 @@
 use std::fmt;
 use std::io;
 use std::process::ExitStatus;
-use std::time::Instant;
 
 use base64::DecodeError;
 use futures::channel::mpsc::SendError;
@@ -13,6 +12,7 @@ use tokio_tungstenite::tungstenite::Message;
 use chromiumoxide_cdp::cdp::browser_protocol::page::FrameId;
 
 use crate::handler::frame::NavigationError;
+use crate::runtime::Instant;
 use chromiumoxide_cdp::cdp::js_protocol::runtime::ExceptionDetails;
 
 pub type Result<T, E = CdpError> = std::result::Result<T, E>;
@@ -26,9 +26,12 @@ pub enum CdpError {
     #[error("{0}")]
     Serde(#[from] serde_json::Error),
     #[error("{0}")]
-    #[cfg(feature = "simd")]
+    #[cfg(any(feature = "simd", feature = "json-sonic"))]
     SerdeSonic(#[from] sonic_rs::Error),
     #[error("{0}")]
+    #[cfg(feature = "json-simd")]
+    SimdJson(#[from] simd_json::Error),
+    #[error("{0}")]
     Chrome(#[from] chromiumoxide_types::Error),
     #[error("Received no response from the chromium instance.")]
     NoResponse,
@@ -46,8 +49,14 @@ pub enum CdpError {
     LaunchIo(#[source] io::Error, BrowserStderr),
     #[error("Request timed out.")]
     Timeout,
+    /// A navigation timed out, with the snapshots of the page, see `Page::goto_with_snapshots`.
+    #[error("{0}")]
+    NavigationTimeout(Box<crate::nav_snapshots::NavigationTimeout>),
     #[error("FrameId {0:?} not found.")]
     FrameNotFound(FrameId),
+    /// A redirect of a navigation not followed, see `crate::redirect::RedirectPolicy`.
+    #[error("{0}")]
+    Redirect(crate::redirect::RedirectViolation),
     /// Error message related to a cdp response that is not a
     /// `chromiumoxide_types::Error`
     #[error("{0}")]
@@ -64,6 +73,9 @@ pub enum CdpError {
     JavascriptException(Box<ExceptionDetails>),
     #[error("{0}")]
     Url(#[from] url::ParseError),
+    /// An evaluation result of the size exceeded the limit, see `Page::evaluate_with_limit`.
+    #[error("The result of {0} characters exceeds the limit of {1}.")]
+    ResultTooLarge(u64, u64),
     // #[error("{0}")]
     // RecvError(#[from] RecvError),
 }
@@ -72,6 +84,18 @@ impl CdpError {
     pub fn msg(msg: impl Into<String>) -> Self {
         CdpError::ChromeMessage(msg.into())
     }
+
+    /// The error of a JSON backend, whichever backend is enabled.
+    pub fn is_json(&self) -> bool {
+        match self {
+            CdpError::Serde(_) => true,
+            #[cfg(any(feature = "simd", feature = "json-sonic"))]
+            CdpError::SerdeSonic(_) => true,
+            #[cfg(feature = "json-simd")]
+            CdpError::SimdJson(_) => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -99,6 +123,7 @@ impl From<NavigationError> for CdpError {
         match err {
             NavigationError::Timeout { .. } => CdpError::Timeout,
             NavigationError::FrameNotFound { frame, .. } => CdpError::FrameNotFound(frame),
+            NavigationError::Redirect { violation, .. } => CdpError::Redirect(violation),
         }
     }
 }
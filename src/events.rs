@@ -0,0 +1,92 @@
+use chromiumoxide_cdp::cdp::CustomEvent;
+use chromiumoxide_types::{MethodId, MethodType};
+
+/// The binding called by the History API hook.
+pub(crate) const SOFT_NAVIGATION_BINDING: &str = "__chromey_soft_navigation";
+
+/// Hook `history.pushState/replaceState` and `popstate` and report the url changes through the binding.
+pub(crate) const SOFT_NAVIGATION_JS: &str = r###"(()=>{if(window.__chromeySoftNav)return;const b=window['__chromey_soft_navigation'];if(typeof b!=='function')return;window.__chromeySoftNav=1;let u=location.href;const c=k=>{const t=location.href;if(t!==u){const f=u;u=t;try{b(JSON.stringify({from:f,to:t,kind:k}))}catch(e){}}};for(const m of['pushState','replaceState']){const f=history[m];history[m]=function(){const r=f.apply(this,arguments);c(m);return r}}addEventListener('popstate',()=>c('popState'))})()"###;
+
+/// How a soft navigation was triggered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SoftNavigationKind {
+    /// `history.pushState`.
+    PushState,
+    /// `history.replaceState`.
+    ReplaceState,
+    /// The `popstate` event (back/forward).
+    PopState,
+}
+
+/// Events emitted by the crate for a page. Subscribe with `page.event_listener::<PageEvent>()`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PageEvent {
+    /// The url changed through the History API without a document load.
+    SoftNavigation {
+        /// The previous url.
+        from: String,
+        /// The new url.
+        to: String,
+        /// How the navigation was triggered.
+        kind: SoftNavigationKind,
+    },
+}
+
+impl PageEvent {
+    /// Parse the payload of the soft navigation binding.
+    pub(crate) fn soft_navigation(payload: &str) -> Option<Self> {
+        #[derive(serde::Deserialize)]
+        struct Payload {
+            from: String,
+            to: String,
+            kind: SoftNavigationKind,
+        }
+
+        serde_json::from_str::<Payload>(payload)
+            .ok()
+            .map(|p| PageEvent::SoftNavigation {
+                from: p.from,
+                to: p.to,
+                kind: p.kind,
+            })
+    }
+}
+
+impl MethodType for PageEvent {
+    fn method_id() -> MethodId {
+        "Chromey.pageEvent".into()
+    }
+}
+
+impl CustomEvent for PageEvent {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_soft_navigation_payload() {
+        assert_eq!(
+            PageEvent::soft_navigation(
+                r#"{"from":"https://a.com/","to":"https://a.com/b","kind":"popState"}"#
+            ),
+            Some(PageEvent::SoftNavigation {
+                from: "https://a.com/".into(),
+                to: "https://a.com/b".into(),
+                kind: SoftNavigationKind::PopState,
+            })
+        );
+        assert_eq!(
+            PageEvent::soft_navigation(r#"{"from":"https://a.com/","to":"https://a.com/b"}"#),
+            None
+        );
+        assert_eq!(
+            PageEvent::soft_navigation(
+                r#"{"from":"https://a.com/","to":"https://a.com/b","kind":"reload"}"#
+            ),
+            None
+        );
+        assert_eq!(PageEvent::soft_navigation("not json"), None);
+    }
+}
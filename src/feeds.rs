@@ -0,0 +1,322 @@
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+
+/// Extract the feed links of the page.
+pub(crate) const FEED_LINKS_JS: &str = r###"(()=>{const f=[];for(const l of document.querySelectorAll('link[rel~="alternate" i][href][type]')){f.push({url:l.href,type:(l.getAttribute('type')||'').toLowerCase().trim(),title:l.getAttribute('title')})}return{feeds:f,url:location.href}})()"###;
+
+/// The paths probed on the origin when the page declares no feed.
+pub const FALLBACK_FEED_PATHS: [&str; 8] = [
+    "/feed",
+    "/rss",
+    "/feed.xml",
+    "/rss.xml",
+    "/atom.xml",
+    "/index.xml",
+    "/feed.json",
+    "/feed/atom",
+];
+
+/// The feeds validated concurrently.
+const FETCH_CONCURRENCY: usize = 4;
+
+/// The timeout of fetching a feed.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The bytes of a feed sniffed for its kind and title.
+const SNIFF_LENGTH: usize = 16 * 1024;
+
+/// The kind of a feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedKind {
+    /// An RSS 0.9x or 2.0 feed.
+    Rss,
+    /// An RSS 1.0 feed.
+    Rdf,
+    /// An Atom feed.
+    Atom,
+    /// A JSON feed.
+    Json,
+}
+
+impl FeedKind {
+    /// The kind of a declared feed type, e.g. `application/rss+xml`.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or_default().trim();
+
+        match mime.to_ascii_lowercase().as_str() {
+            "application/rss+xml" => Some(Self::Rss),
+            "application/rdf+xml" => Some(Self::Rdf),
+            "application/atom+xml" => Some(Self::Atom),
+            "application/feed+json" | "application/json+feed" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Where a feed was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedSource {
+    /// A `<link rel="alternate">` of the page.
+    Link,
+    /// A common feed path of the origin.
+    Fallback,
+}
+
+/// A feed of the page.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Feed {
+    /// The absolute url of the feed.
+    pub url: String,
+    /// Where the feed was found.
+    pub source: FeedSource,
+    /// The kind of the feed, sniffed from the body when it validated, else the declared kind.
+    pub kind: FeedKind,
+    /// The title of the feed, from the body when it validated, else the declared title.
+    pub title: Option<String>,
+    /// The feed was fetched and its body is a feed.
+    pub valid: bool,
+}
+
+/// A feed link of the page.
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct FeedLink {
+    url: String,
+    #[serde(rename = "type")]
+    kind: String,
+    title: Option<String>,
+}
+
+/// The feed links of the page.
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct FeedLinks {
+    #[serde(default)]
+    feeds: Vec<FeedLink>,
+    url: String,
+}
+
+/// The text of the first element of the name, CDATA and character references decoded.
+fn xml_text(xml: &str, name: &str) -> Option<String> {
+    let open = format!("<{name}");
+    let mut rest = xml;
+
+    loop {
+        let at = rest.find(&open)?;
+        rest = &rest[at + open.len()..];
+
+        // skip `<titles>` and the like.
+        if rest.starts_with(|c: char| c == '>' || c.is_ascii_whitespace()) {
+            break;
+        }
+    }
+
+    let start = rest.find('>')? + 1;
+    let end = rest[start..].find(&format!("</{name}"))? + start;
+    let text = rest[start..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|text| text.strip_suffix("]]>"))
+        .map(String::from)
+        .unwrap_or_else(|| crate::diff::decode_entities(text));
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    (!text.is_empty()).then_some(text)
+}
+
+/// The kind and the title of a feed body, `None` when it is not a feed.
+pub fn sniff_feed(body: &[u8]) -> Option<(FeedKind, Option<String>)> {
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+    let start = body.iter().position(|b| !b.is_ascii_whitespace())?;
+    let body = &body[start..];
+
+    if body.first() == Some(&b'{') {
+        let json: serde_json::Value = serde_json::from_slice(body).ok()?;
+        let version = json.get("version")?.as_str()?;
+
+        if !version.contains("jsonfeed.org/version/") {
+            return None;
+        }
+
+        let title = json
+            .get("title")
+            .and_then(|title| title.as_str())
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty());
+
+        return Some((FeedKind::Json, title));
+    }
+
+    let head = String::from_utf8_lossy(&body[..body.len().min(SNIFF_LENGTH)]);
+
+    // the root element after the prolog, comments and doctype.
+    let mut rest: &str = &head;
+    let root = loop {
+        let at = rest.find('<')?;
+        rest = &rest[at + 1..];
+
+        if !rest.starts_with(['?', '!']) {
+            break rest;
+        }
+    };
+
+    let name_end = root
+        .find(|c: char| c == '>' || c == '/' || c.is_ascii_whitespace())
+        .unwrap_or(root.len());
+    let name = &root[..name_end];
+
+    let kind = match name {
+        "rss" => FeedKind::Rss,
+        "rdf:RDF" | "RDF" => FeedKind::Rdf,
+        "feed" if root.contains("http://www.w3.org/2005/Atom") => FeedKind::Atom,
+        _ => return None,
+    };
+
+    Some((kind, xml_text(root, "title")))
+}
+
+/// The feeds declared by the page, the first link of a url kept.
+pub(crate) fn declared_feeds(links: &FeedLinks) -> Vec<Feed> {
+    let mut seen = std::collections::HashSet::new();
+
+    links
+        .feeds
+        .iter()
+        .filter_map(|link| {
+            let kind = FeedKind::from_mime(&link.kind)?;
+            let url = url::Url::parse(&link.url).ok()?;
+
+            if !matches!(url.scheme(), "http" | "https") || !seen.insert(url.to_string()) {
+                return None;
+            }
+
+            Some(Feed {
+                url: url.to_string(),
+                source: FeedSource::Link,
+                kind,
+                title: link
+                    .title
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|title| !title.is_empty())
+                    .map(String::from),
+                valid: false,
+            })
+        })
+        .collect()
+}
+
+/// Fetch a feed through the cache layer and sniff its kind and title.
+async fn validate(mut feed: Feed) -> Feed {
+    if let Some((kind, title)) = crate::utils::fetch_resource(&feed.url, FETCH_TIMEOUT)
+        .await
+        .and_then(|(body, _)| sniff_feed(&body))
+    {
+        feed.kind = kind;
+        feed.title = title.or(feed.title);
+        feed.valid = true;
+    }
+    feed
+}
+
+/// Validate the declared feeds, probing the fallback paths of the origin when none validates.
+pub(crate) async fn resolve_feeds(links: FeedLinks) -> Vec<Feed> {
+    let mut feeds: Vec<Feed> = stream::iter(declared_feeds(&links))
+        .map(validate)
+        .buffered(FETCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    if feeds.iter().any(|feed| feed.valid) {
+        return feeds;
+    }
+
+    let Ok(base) = url::Url::parse(&links.url) else {
+        return feeds;
+    };
+
+    let fallbacks: Vec<Feed> = FALLBACK_FEED_PATHS
+        .iter()
+        .filter_map(|path| base.join(path).ok())
+        .map(|url| url.to_string())
+        .filter(|url| !feeds.iter().any(|feed| &feed.url == url))
+        .map(|url| Feed {
+            url,
+            source: FeedSource::Fallback,
+            kind: FeedKind::Rss,
+            title: None,
+            valid: false,
+        })
+        .collect();
+
+    let fallbacks: Vec<Feed> = stream::iter(fallbacks)
+        .map(validate)
+        .buffered(FETCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    // `/feed` and `/rss` often redirect to the same document, keep the first of a title and kind.
+    let mut seen = std::collections::HashSet::new();
+    feeds.extend(
+        fallbacks
+            .into_iter()
+            .filter(|feed| feed.valid && seen.insert((feed.kind, feed.title.clone()))),
+    );
+
+    feeds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_feeds() {
+        let rss = br#"<?xml version="1.0"?>
+            <!-- generated -->
+            <rss version="2.0"><channel><title><![CDATA[News & more]]></title></channel></rss>"#;
+        assert_eq!(
+            sniff_feed(rss),
+            Some((FeedKind::Rss, Some("News & more".into())))
+        );
+
+        let atom = br#"<feed xmlns="http://www.w3.org/2005/Atom"><title type="text">Tom &amp; Jerry</title></feed>"#;
+        assert_eq!(
+            sniff_feed(atom),
+            Some((FeedKind::Atom, Some("Tom & Jerry".into())))
+        );
+
+        let json = br#"{"version":"https://jsonfeed.org/version/1.1","title":"Blog","items":[]}"#;
+        assert_eq!(
+            sniff_feed(json),
+            Some((FeedKind::Json, Some("Blog".into())))
+        );
+
+        assert_eq!(
+            sniff_feed(b"<!DOCTYPE html><html><title>x</title></html>"),
+            None
+        );
+        assert_eq!(sniff_feed(br#"{"title":"not a feed"}"#), None);
+    }
+
+    #[test]
+    fn keeps_declared_feeds() {
+        let links: FeedLinks = serde_json::from_value(serde_json::json!({
+            "feeds": [
+                { "url": "https://example.com/feed.xml", "type": "application/rss+xml", "title": " Posts " },
+                { "url": "https://example.com/feed.xml", "type": "application/rss+xml", "title": null },
+                { "url": "https://example.com/es/", "type": "text/html", "title": null },
+                { "url": "https://example.com/feed.json", "type": "application/feed+json", "title": null },
+            ],
+            "url": "https://example.com/",
+        }))
+        .unwrap();
+
+        let feeds = declared_feeds(&links);
+
+        assert_eq!(feeds.len(), 2);
+        assert_eq!(feeds[0].title.as_deref(), Some("Posts"));
+        assert_eq!(feeds[1].kind, FeedKind::Json);
+    }
+}
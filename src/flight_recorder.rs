@@ -0,0 +1,202 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// The bytes of a message kept, the rest of larger messages, e.g. screenshots, is dropped.
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// The direction of a recorded message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// A command sent to the browser.
+    Sent,
+    /// A response or an event received from the browser.
+    Received,
+}
+
+/// A CDP message kept by the flight recorder.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlightRecord {
+    /// The direction of the message.
+    pub direction: Direction,
+    /// The unix time of the message in milliseconds.
+    pub timestamp_ms: u64,
+    /// The session of the message, `None` for the messages of the browser.
+    pub session_id: Option<String>,
+    /// The raw json of the message.
+    pub message: String,
+    /// The message was longer than the bytes kept.
+    pub truncated: bool,
+    /// The error of a received message which failed to parse.
+    pub error: Option<String>,
+}
+
+/// The session of a raw message.
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(rename = "sessionId")]
+    session_id: Option<String>,
+}
+
+/// A ring buffer of the last CDP messages of every session, shared by the connection recording
+/// the messages and the pages dumping them.
+#[derive(Debug)]
+pub(crate) struct FlightRecorder {
+    /// The messages kept per session.
+    capacity: usize,
+    /// The messages of the sessions, the messages of the browser under `None`.
+    sessions: Mutex<HashMap<Option<String>, VecDeque<FlightRecord>>>,
+}
+
+impl FlightRecorder {
+    /// A recorder keeping the last messages of every session.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            sessions: Default::default(),
+        }
+    }
+
+    /// Record a command sent to the browser.
+    pub(crate) fn record_sent(&self, session_id: Option<&str>, message: &str) {
+        self.push(record(
+            Direction::Sent,
+            session_id.map(Into::into),
+            message,
+            None,
+        ));
+    }
+
+    /// Record a message received from the browser, with its parse error.
+    pub(crate) fn record_received(&self, bytes: &[u8], error: Option<String>) {
+        let session_id = serde_json::from_slice::<Envelope>(bytes)
+            .ok()
+            .and_then(|envelope| envelope.session_id);
+
+        self.push(record(
+            Direction::Received,
+            session_id,
+            &String::from_utf8_lossy(bytes),
+            error,
+        ));
+    }
+
+    fn push(&self, record: FlightRecord) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            let records = sessions.entry(record.session_id.clone()).or_default();
+
+            if records.len() >= self.capacity {
+                records.pop_front();
+            }
+            records.push_back(record);
+        }
+    }
+
+    /// The messages kept of the session, oldest first.
+    pub(crate) fn records(&self, session_id: &str) -> Vec<FlightRecord> {
+        self.sessions
+            .lock()
+            .ok()
+            .and_then(|sessions| {
+                sessions
+                    .get(&Some(session_id.to_string()))
+                    .map(|records| records.iter().cloned().collect())
+            })
+            .unwrap_or_default()
+    }
+
+    /// Drop the messages of the session, e.g. once its target was destroyed.
+    pub(crate) fn forget(&self, session_id: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(&Some(session_id.to_string()));
+        }
+    }
+}
+
+/// A record of the message, truncated to the bytes kept.
+fn record(
+    direction: Direction,
+    session_id: Option<String>,
+    message: &str,
+    error: Option<String>,
+) -> FlightRecord {
+    let mut end = message.len().min(MAX_MESSAGE_BYTES);
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    FlightRecord {
+        direction,
+        timestamp_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default(),
+        session_id,
+        message: message[..end].to_string(),
+        truncated: end < message.len(),
+        error,
+    }
+}
+
+/// The records as NDJSON, one record per line.
+pub(crate) fn to_ndjson(records: &[FlightRecord]) -> serde_json::Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    for record in records {
+        serde_json::to_writer(&mut out, record)?;
+        out.push(b'\n');
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_last_messages_per_session() {
+        let recorder = FlightRecorder::new(2);
+
+        recorder.record_sent(
+            Some("a"),
+            r#"{"id":1,"method":"Page.enable","sessionId":"a"}"#,
+        );
+        recorder.record_received(br#"{"id":1,"result":{},"sessionId":"a"}"#, None);
+        recorder.record_received(
+            br#"{"method":"Page.frameNavigated","params":{},"sessionId":"a"}"#,
+            Some("data did not match any variant".into()),
+        );
+        recorder.record_received(br#"{"method":"Target.targetCreated","params":{}}"#, None);
+
+        let records = recorder.records("a");
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Received);
+        assert_eq!(
+            records[1].error.as_deref(),
+            Some("data did not match any variant")
+        );
+        assert!(recorder.records("b").is_empty());
+
+        recorder.forget("a");
+
+        assert!(recorder.records("a").is_empty());
+    }
+
+    #[test]
+    fn truncates_large_messages() {
+        let message = "é".repeat(MAX_MESSAGE_BYTES);
+        let record = record(Direction::Received, None, &message, None);
+
+        assert!(record.truncated);
+        assert!(record.message.len() <= MAX_MESSAGE_BYTES);
+
+        let ndjson = to_ndjson(&[record.clone(), record]).unwrap();
+
+        assert_eq!(ndjson.iter().filter(|b| **b == b'\n').count(), 2);
+    }
+}
@@ -0,0 +1,208 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Measure the candidate fonts against the generic families, a font is available when the text
+/// renders with other metrics than every fallback.
+pub(crate) const AVAILABLE_FONTS_JS: &str = r###"(f)=>{const t='mmmmmmmmmmlli10OoWw@#';const c=document.createElement('canvas').getContext('2d');const b=['monospace','sans-serif','serif'];const m=(font)=>{c.font='72px '+font;const x=c.measureText(t);return x.width+'|'+x.actualBoundingBoxAscent+'|'+x.actualBoundingBoxDescent};const base=b.map(m);return f.filter(n=>b.some((g,i)=>m('"'+n.replace(/"/g,'')+'",'+g)!==base[i]))}"###;
+
+/// Fonts commonly installed on windows, macos and linux hosts, probed by
+/// `Page::available_fonts`.
+pub const COMMON_FONTS: [&str; 40] = [
+    "Arial",
+    "Arial Black",
+    "Arial Narrow",
+    "Bitstream Vera Sans",
+    "Calibri",
+    "Cambria",
+    "Candara",
+    "Comic Sans MS",
+    "Consolas",
+    "Courier",
+    "Courier New",
+    "DejaVu Sans",
+    "DejaVu Sans Mono",
+    "DejaVu Serif",
+    "Droid Sans",
+    "Franklin Gothic Medium",
+    "Garamond",
+    "Geneva",
+    "Georgia",
+    "Helvetica",
+    "Helvetica Neue",
+    "Impact",
+    "Liberation Mono",
+    "Liberation Sans",
+    "Liberation Serif",
+    "Lucida Console",
+    "Lucida Grande",
+    "Menlo",
+    "Monaco",
+    "Noto Color Emoji",
+    "Noto Sans",
+    "Noto Serif",
+    "Palatino",
+    "Segoe UI",
+    "Segoe UI Emoji",
+    "Tahoma",
+    "Times",
+    "Times New Roman",
+    "Trebuchet MS",
+    "Verdana",
+];
+
+/// The glyph hinting of the rendered text, `--font-render-hinting`. Linux only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontRenderHinting {
+    /// No hinting, the most stable rendering across hosts.
+    None,
+    /// Slight hinting.
+    Slight,
+    /// Medium hinting.
+    Medium,
+    /// Full hinting.
+    Full,
+}
+
+impl FontRenderHinting {
+    /// The value of the launch flag.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FontRenderHinting::None => "none",
+            FontRenderHinting::Slight => "slight",
+            FontRenderHinting::Medium => "medium",
+            FontRenderHinting::Full => "full",
+        }
+    }
+}
+
+/// The font configuration of the launched browser, stabilizing screenshots across hosts and
+/// shaping the font fingerprint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FontConfig {
+    /// The glyph hinting, `None` keeps the browser default.
+    pub render_hinting: Option<FontRenderHinting>,
+    /// Block web fonts so the pages render with the local fonts only.
+    pub disable_remote_fonts: bool,
+    /// Restrict the browser to the fonts of the directory instead of the fonts of the host.
+    /// Applied through fontconfig, linux only.
+    pub bundled_fonts: Option<PathBuf>,
+}
+
+impl FontConfig {
+    /// The configuration rendering the same text on every host: no hinting, no web fonts and only
+    /// the fonts of the directory.
+    pub fn stable(bundled_fonts: impl Into<PathBuf>) -> Self {
+        Self {
+            render_hinting: Some(FontRenderHinting::None),
+            disable_remote_fonts: true,
+            bundled_fonts: Some(bundled_fonts.into()),
+        }
+    }
+
+    /// The launch flags of the configuration.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(hinting) = self.render_hinting {
+            args.push(format!("--font-render-hinting={}", hinting.as_str()));
+        }
+
+        if self.disable_remote_fonts {
+            args.push("--disable-remote-fonts".to_string());
+        }
+
+        args
+    }
+
+    /// Write the fontconfig file restricting the browser to the bundled fonts, returning the
+    /// `FONTCONFIG_FILE` to launch with.
+    pub fn fontconfig_file(&self) -> io::Result<Option<PathBuf>> {
+        let Some(dir) = &self.bundled_fonts else {
+            return Ok(None);
+        };
+
+        if !dir.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("font directory {} does not exist", dir.display()),
+            ));
+        }
+
+        let dir = dir.canonicalize()?;
+        let root = std::env::temp_dir().join("chromiumoxide-fonts");
+        std::fs::create_dir_all(&root)?;
+
+        // one file per directory, browsers launched with other fonts do not share it.
+        let file = root.join(format!(
+            "{}.conf",
+            hex_digest(dir.to_string_lossy().as_bytes())
+        ));
+        std::fs::write(&file, fontconfig_xml(&dir, &root.join("cache")))?;
+
+        Ok(Some(file))
+    }
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    use sha2::Digest;
+
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .take(8)
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A fontconfig file listing only the font directory.
+fn fontconfig_xml(dir: &Path, cache: &Path) -> String {
+    format!(
+        r#"<?xml version="1.0"?>
+<!DOCTYPE fontconfig SYSTEM "fonts.dtd">
+<fontconfig>
+  <dir>{}</dir>
+  <cachedir>{}</cachedir>
+</fontconfig>
+"#,
+        xml_escape(&dir.to_string_lossy()),
+        xml_escape(&cache.to_string_lossy())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_launch_flags() {
+        assert!(FontConfig::default().args().is_empty());
+
+        let config = FontConfig {
+            render_hinting: Some(FontRenderHinting::None),
+            disable_remote_fonts: true,
+            bundled_fonts: None,
+        };
+
+        assert_eq!(
+            config.args(),
+            vec!["--font-render-hinting=none", "--disable-remote-fonts"]
+        );
+        assert_eq!(config.fontconfig_file().unwrap(), None);
+    }
+
+    #[test]
+    fn lists_only_the_bundled_fonts() {
+        let xml = fontconfig_xml(Path::new("/fonts/a&b"), Path::new("/tmp/cache"));
+
+        assert!(xml.contains("<dir>/fonts/a&amp;b</dir>"));
+        assert!(!xml.contains("/usr/share/fonts"));
+
+        let missing = FontConfig::stable("/this/font/dir/does/not/exist");
+        assert!(missing.fontconfig_file().is_err());
+    }
+}
@@ -16,6 +16,8 @@ pub struct EmulationManager {
     pub has_touch: bool,
     /// Whether a reload is required to apply new emulation settings.
     pub needs_reload: bool,
+    /// Whether the device metrics of the page are overridden.
+    pub device_metrics_override: bool,
     /// Timeout to apply emulation requests.
     pub request_timeout: Duration,
 }
@@ -27,6 +29,7 @@ impl EmulationManager {
             emulating_mobile: false,
             has_touch: false,
             needs_reload: false,
+            device_metrics_override: false,
             request_timeout,
         }
     }
@@ -53,6 +56,7 @@ impl EmulationManager {
         {
             if let Ok(set_device_value) = serde_json::to_value(&set_device) {
                 chains.push((set_device.identifier(), set_device_value));
+                self.device_metrics_override = true;
             }
         }
 
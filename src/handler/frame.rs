@@ -1,15 +1,18 @@
 use std::collections::VecDeque;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use serde_json::map::Entry;
 
-use chromiumoxide_cdp::cdp::browser_protocol::network::LoaderId;
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    EventRequestWillBeSent, LoaderId, ResourceType,
+};
 use chromiumoxide_cdp::cdp::browser_protocol::page::{
-    AddScriptToEvaluateOnNewDocumentParams, CreateIsolatedWorldParams, EventFrameDetached,
-    EventFrameStartedLoading, EventFrameStoppedLoading, EventLifecycleEvent,
-    EventNavigatedWithinDocument, Frame as CdpFrame, FrameTree,
+    AddScriptToEvaluateOnNewDocumentParams, ClientNavigationReason, CreateIsolatedWorldParams,
+    EventFrameDetached, EventFrameRequestedNavigation, EventFrameStartedLoading,
+    EventFrameStoppedLoading, EventLifecycleEvent, EventNavigatedWithinDocument, Frame as CdpFrame,
+    FrameTree,
 };
 use chromiumoxide_cdp::cdp::browser_protocol::target::EventAttachedToTarget;
 use chromiumoxide_cdp::cdp::js_protocol::runtime::*;
@@ -21,9 +24,14 @@ use chromiumoxide_types::{Method, MethodId, Request};
 use spider_fingerprint::BASE_CHROME_VERSION;
 
 use crate::error::DeadlineExceeded;
+use crate::events::{PageEvent, SOFT_NAVIGATION_BINDING};
 use crate::handler::domworld::DOMWorld;
 use crate::handler::http::HttpRequest;
 use crate::handler::REQUEST_TIMEOUT;
+use crate::redirect::{
+    RedirectHop, RedirectKind, RedirectPolicy, RedirectTracker, RedirectVerdict, RedirectViolation,
+};
+use crate::runtime::Instant;
 use crate::{cmd::CommandChain, ArcHttpRequest};
 
 lazy_static::lazy_static! {
@@ -87,6 +95,8 @@ pub struct Frame {
     lifecycle_events: HashSet<MethodId>,
     /// The isolated world name.
     isolated_world_name: String,
+    /// The isolated worlds created by name.
+    named_worlds: HashMap<String, DOMWorld>,
 }
 
 impl Frame {
@@ -105,6 +115,7 @@ impl Frame {
             name: None,
             lifecycle_events: Default::default(),
             isolated_world_name,
+            named_worlds: Default::default(),
         }
     }
 
@@ -122,6 +133,7 @@ impl Frame {
             name: None,
             lifecycle_events: Default::default(),
             isolated_world_name: parent.isolated_world_name.clone(),
+            named_worlds: Default::default(),
         }
     }
 
@@ -199,9 +211,15 @@ impl Frame {
         self.lifecycle_events.contains("networkAlmostIdle")
     }
 
+    /// A named isolated world of the frame.
+    pub fn named_world(&self, name: &str) -> Option<&DOMWorld> {
+        self.named_worlds.get(name)
+    }
+
     pub fn clear_contexts(&mut self) {
         self.main_world.take_context();
         self.secondary_world.take_context();
+        self.named_worlds.clear();
     }
 
     pub fn destroy_context(&mut self, ctx_unique_id: &str) {
@@ -209,6 +227,9 @@ impl Frame {
             self.main_world.take_context();
         } else if self.secondary_world.execution_context_unique_id() == Some(ctx_unique_id) {
             self.secondary_world.take_context();
+        } else {
+            self.named_worlds
+                .retain(|_, world| world.execution_context_unique_id() != Some(ctx_unique_id));
         }
     }
 
@@ -238,6 +259,15 @@ pub struct FrameManager {
     pending_navigations: VecDeque<(FrameRequestedNavigation, NavigationWatcher)>,
     /// The currently ongoing navigation
     navigation: Option<(NavigationWatcher, Instant)>,
+    /// The redirects followed during the navigations. When `None` a navigation fails on a loop
+    /// or a chain past `DEFAULT_MAX_HOPS` and the redirects outside of a navigation are free.
+    redirect_policy: Option<RedirectPolicy>,
+    /// The redirect chain of the latest navigation.
+    redirects: RedirectTracker,
+    /// The redirect failing the ongoing navigation.
+    redirect_violation: Option<RedirectViolation>,
+    /// Stop loading the page, a redirect is not followed.
+    stop_loading: bool,
 }
 
 impl FrameManager {
@@ -250,7 +280,120 @@ impl FrameManager {
             request_timeout,
             pending_navigations: Default::default(),
             navigation: None,
+            redirect_policy: None,
+            redirects: Default::default(),
+            redirect_violation: None,
+            stop_loading: false,
+        }
+    }
+
+    /// Set the redirects followed from the next navigation.
+    pub fn set_redirect_policy(&mut self, policy: Option<RedirectPolicy>) {
+        self.redirects = RedirectTracker::new(policy.clone());
+        self.redirect_policy = policy;
+    }
+
+    /// The hops followed by the latest navigation.
+    pub fn redirect_hops(&self) -> &[RedirectHop] {
+        self.redirects.hops()
+    }
+
+    /// How long a loaded navigation waits for a client redirect.
+    fn client_redirect_wait(&self) -> Duration {
+        self.redirect_policy
+            .as_ref()
+            .map(|policy| policy.client_redirect_wait)
+            .unwrap_or_default()
+    }
+
+    /// When the ongoing navigation stops waiting for a client redirect, to wake up the target.
+    pub fn client_redirect_deadline(&self) -> Option<Instant> {
+        let (watcher, _) = self.navigation.as_ref()?;
+        Some(watcher.loaded_at? + self.client_redirect_wait())
+    }
+
+    /// Check a redirect of a frame against the redirect policy.
+    fn on_redirect(&mut self, frame_id: &FrameId, from: &str, to: &str, kind: RedirectKind) {
+        let navigated_frame = match &self.navigation {
+            Some((watcher, _)) => &watcher.frame_id,
+            _ => match &self.main_frame {
+                Some(main_frame) => main_frame,
+                _ => return,
+            },
+        };
+
+        if navigated_frame != frame_id {
+            return;
+        }
+
+        // without a policy the limits only guard a navigation in flight, the page is otherwise
+        // free to redirect, e.g. a single page app assigning `location.href` back and forth.
+        let verdict = if self.redirect_policy.is_none() && self.navigation.is_none() {
+            self.redirects.follow(from, to, kind)
+        } else {
+            self.redirects.on_redirect(from, to, kind)
+        };
+
+        match verdict {
+            RedirectVerdict::Follow => {
+                let loader_id = self.frames.get(frame_id).and_then(|f| f.loader_id.clone());
+
+                // with a policy the navigation follows the client redirects to a new document.
+                if let (Some((watcher, _)), Some(_)) = (&mut self.navigation, &self.redirect_policy)
+                {
+                    if !matches!(kind, RedirectKind::Http(_)) {
+                        watcher.loader_id = loader_id;
+                        watcher.loaded_at = None;
+                    }
+                }
+            }
+            RedirectVerdict::SameDocument => (),
+            RedirectVerdict::Ignore => self.stop_loading = true,
+            RedirectVerdict::Violation(violation) => {
+                if self.navigation.is_some() {
+                    // without a policy the page is left alone, only the navigation fails.
+                    self.stop_loading = self.redirect_policy.is_some();
+                    self.redirect_violation = Some(violation);
+                } else {
+                    self.stop_loading = true;
+                    tracing::debug!("Redirect not followed: {violation}");
+                }
+            }
+        }
+    }
+
+    /// Track the http redirects of the documents.
+    pub fn on_request_will_be_sent(&mut self, event: &EventRequestWillBeSent) {
+        if event.r#type != Some(ResourceType::Document) {
+            return;
         }
+        if let (Some(response), Some(frame_id)) = (&event.redirect_response, &event.frame_id) {
+            let kind = RedirectKind::Http(response.status as u16);
+            self.on_redirect(frame_id, &response.url, &event.request.url, kind);
+        }
+    }
+
+    /// Track the meta refreshes and the script redirects, a navigation of the user starts a new
+    /// redirect chain.
+    pub fn on_frame_requested_navigation(&mut self, event: &EventFrameRequestedNavigation) {
+        let kind = match event.reason {
+            ClientNavigationReason::MetaTagRefresh => RedirectKind::MetaRefresh,
+            ClientNavigationReason::HttpHeaderRefresh => RedirectKind::HeaderRefresh,
+            ClientNavigationReason::ScriptInitiated => RedirectKind::Script,
+            _ => {
+                if self.navigation.is_none() && self.main_frame.as_ref() == Some(&event.frame_id) {
+                    self.redirects = RedirectTracker::new(self.redirect_policy.clone());
+                }
+                return;
+            }
+        };
+
+        let from = match self.frames.get(&event.frame_id).and_then(Frame::url) {
+            Some(url) => url.to_string(),
+            _ => return,
+        };
+
+        self.on_redirect(&event.frame_id, &from, &event.url, kind);
     }
 
     /// The commands to execute in order to initialize this frame manager
@@ -354,17 +497,49 @@ impl FrameManager {
     }
 
     /// Track the request in the frame
-    pub fn on_http_request_finished(&mut self, request: HttpRequest) {
+    pub fn on_http_request_finished(&mut self, mut request: HttpRequest) {
         if let Some(id) = request.frame.as_ref() {
             if let Some(frame) = self.frames.get_mut(id) {
+                // the request of a document shares the id of its loader.
+                let document = frame.loader_id.as_ref().map(AsRef::<str>::as_ref)
+                    == Some(AsRef::<str>::as_ref(&request.request_id));
+
+                if document && self.main_frame.as_ref() == Some(id) {
+                    request.redirect_provenance = self.redirects.hops().to_vec();
+                }
+
                 frame.set_request(request);
             }
         }
     }
 
+    /// The navigation waited long enough for a client redirect once loaded.
+    fn settled(&self, watcher: &mut NavigationWatcher, now: Instant) -> bool {
+        let wait = self.client_redirect_wait();
+        if wait.is_zero() {
+            return true;
+        }
+        let loaded_at = *watcher.loaded_at.get_or_insert(now);
+        now >= loaded_at + wait
+    }
+
     pub fn poll(&mut self, now: Instant) -> Option<FrameEvent> {
+        if std::mem::take(&mut self.stop_loading) {
+            return Some(FrameEvent::StopLoading);
+        }
+
         // check if the navigation completed
-        if let Some((watcher, deadline)) = self.navigation.take() {
+        if let Some((mut watcher, deadline)) = self.navigation.take() {
+            if let Some(violation) = self.redirect_violation.take() {
+                // fail fast instead of waiting for the lifecycle of a redirect loop.
+                return Some(FrameEvent::NavigationResult(Err(
+                    NavigationError::Redirect {
+                        id: watcher.id,
+                        violation,
+                    },
+                )));
+            }
+
             if now > deadline {
                 // navigation request timed out
                 return Some(FrameEvent::NavigationResult(Err(
@@ -376,7 +551,9 @@ impl FrameManager {
             }
 
             if let Some(frame) = self.frames.get(&watcher.frame_id) {
-                if let Some(nav) = self.check_lifecycle_complete(&watcher, frame) {
+                let complete = self.check_lifecycle_complete(&watcher, frame);
+
+                if let Some(nav) = complete.filter(|_| self.settled(&mut watcher, now)) {
                     // request is complete if the frame's lifecycle is complete = frame received all
                     // required events
                     return Some(FrameEvent::NavigationResult(Ok(nav)));
@@ -396,6 +573,8 @@ impl FrameManager {
             // queue in the next navigation that is must be fulfilled until `deadline`
             let deadline = Instant::now() + req.timeout;
             self.navigation = Some((watcher, deadline));
+            self.redirects = RedirectTracker::new(self.redirect_policy.clone());
+            self.redirect_violation = None;
             return Some(FrameEvent::NavigationRequest(req.id, req.req));
         }
         None
@@ -509,7 +688,33 @@ impl FrameManager {
     }
 
     /// Notification is issued every time when binding is called
-    pub fn on_runtime_binding_called(&mut self, _ev: &EventBindingCalled) {}
+    pub fn on_runtime_binding_called(&mut self, ev: &EventBindingCalled) -> Option<PageEvent> {
+        if ev.name != SOFT_NAVIGATION_BINDING {
+            return None;
+        }
+
+        let event = PageEvent::soft_navigation(&ev.payload)?;
+
+        let frame_id = self
+            .frames
+            .values()
+            .find(|f| f.main_world.execution_context() == Some(ev.execution_context_id))
+            .map(|f| f.id.clone())
+            .or_else(|| self.main_frame.clone());
+
+        if let (Some(frame), PageEvent::SoftNavigation { to, .. }) =
+            (frame_id.and_then(|id| self.frames.get_mut(&id)), &event)
+        {
+            // the binding is callable by the page script, the History API never leaves the origin.
+            if !frame.url.as_deref().is_some_and(|url| same_origin(url, to)) {
+                tracing::debug!("Ignoring the cross origin soft navigation to {to}");
+                return None;
+            }
+            frame.navigated_within_url(to.clone());
+        }
+
+        Some(event)
+    }
 
     /// Issued when new execution context is created
     pub fn on_frame_execution_context_created(&mut self, event: &EventExecutionContextCreated) {
@@ -530,12 +735,18 @@ impl FrameManager {
                     frame
                         .main_world
                         .set_context(event.context.id, event.context.unique_id.clone());
-                } else if event.context.name == frame.isolated_world_name
-                    && frame.secondary_world.execution_context().is_none()
-                {
+                } else if event.context.name == frame.isolated_world_name {
+                    // the newest context wins, a stale context left over from a missed destroy
+                    // event would otherwise be used after `document.open()`
                     frame
                         .secondary_world
                         .set_context(event.context.id, event.context.unique_id.clone());
+                } else if !event.context.name.is_empty() {
+                    frame
+                        .named_worlds
+                        .entry(event.context.name.clone())
+                        .or_default()
+                        .set_context(event.context.id, event.context.unique_id.clone());
                 }
                 self.context_ids
                     .insert(event.context.unique_id.clone(), frame.id.clone());
@@ -651,6 +862,8 @@ pub enum FrameEvent {
     NavigationResult(Result<NavigationOk, NavigationError>),
     /// A new navigation request needs to be submitted
     NavigationRequest(NavigationId, Request),
+    /// Stop loading the page, a redirect is not followed
+    StopLoading,
     /* /// The initial page of the target has been loaded
      * InitialPageLoadFinished */
 }
@@ -665,6 +878,10 @@ pub enum NavigationError {
         id: NavigationId,
         frame: FrameId,
     },
+    Redirect {
+        id: NavigationId,
+        violation: RedirectViolation,
+    },
 }
 
 impl NavigationError {
@@ -672,6 +889,7 @@ impl NavigationError {
         match self {
             NavigationError::Timeout { id, .. } => id,
             NavigationError::FrameNotFound { id, .. } => id,
+            NavigationError::Redirect { id, .. } => id,
         }
     }
 }
@@ -703,6 +921,8 @@ pub struct NavigationWatcher {
     /// navigating to a new document by checking if a loader was included in the
     /// response.
     same_document_navigation: bool,
+    /// When the lifecycle completed, the navigation then waits for a client redirect.
+    loaded_at: Option<Instant>,
 }
 
 impl NavigationWatcher {
@@ -722,6 +942,7 @@ impl NavigationWatcher {
             frame_id: frame,
             loader_id,
             same_document_navigation: false,
+            loaded_at: None,
         }
     }
 
@@ -851,3 +1072,227 @@ impl AsRef<str> for LifecycleEvent {
         }
     }
 }
+
+/// Both urls have the same tuple origin.
+fn same_origin(a: &str, b: &str) -> bool {
+    match (url::Url::parse(a), url::Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin().is_tuple() && a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redirect::DEFAULT_MAX_HOPS;
+    use chromiumoxide_cdp::cdp::browser_protocol::page::ClientNavigationDisposition;
+
+    fn cdp_frame(id: &str, url: &str) -> CdpFrame {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "loaderId": format!("{id}-{url}"),
+            "url": url,
+            "domainAndRegistry": "a.com",
+            "securityOrigin": "https://a.com",
+            "mimeType": "text/html",
+            "secureContextType": "Secure",
+            "crossOriginIsolatedContextType": "NotIsolated",
+            "gatedAPIFeatures": []
+        }))
+        .unwrap()
+    }
+
+    /// A script of the frame assigns its location and the new document commits.
+    fn script_navigation(manager: &mut FrameManager, url: &str) {
+        manager.on_frame_requested_navigation(&EventFrameRequestedNavigation {
+            frame_id: FrameId::new("F"),
+            reason: ClientNavigationReason::ScriptInitiated,
+            url: url.into(),
+            disposition: ClientNavigationDisposition::CurrentTab,
+        });
+        manager.on_frame_navigated(&cdp_frame("F", url));
+    }
+
+    #[test]
+    fn follows_the_script_navigations_without_a_policy() {
+        let (a, b) = ("https://a.com/", "https://a.com/b");
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_frame_navigated(&cdp_frame("F", a));
+
+        for _ in 0..DEFAULT_MAX_HOPS {
+            script_navigation(&mut manager, b);
+            script_navigation(&mut manager, a);
+        }
+
+        assert!(manager.poll(Instant::now()).is_none());
+        assert_eq!(manager.main_frame().and_then(Frame::url), Some(a));
+    }
+
+    #[test]
+    fn fails_the_looping_navigation_without_a_policy() {
+        let (a, b) = ("https://a.com/", "https://a.com/b");
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_frame_navigated(&cdp_frame("F", a));
+
+        manager.goto(FrameRequestedNavigation::new(
+            NavigationId(1),
+            Request::new("Page.navigate".into(), serde_json::json!({ "url": a })),
+        ));
+        assert!(matches!(
+            manager.poll(Instant::now()),
+            Some(FrameEvent::NavigationRequest(NavigationId(1), _))
+        ));
+
+        script_navigation(&mut manager, b);
+        script_navigation(&mut manager, a);
+        script_navigation(&mut manager, b);
+
+        // the page is not stopped, only the navigation fails.
+        assert!(matches!(
+            manager.poll(Instant::now()),
+            Some(FrameEvent::NavigationResult(Err(
+                NavigationError::Redirect {
+                    violation: RedirectViolation::Loop { .. },
+                    ..
+                }
+            )))
+        ));
+        assert!(manager.poll(Instant::now()).is_none());
+    }
+
+    fn binding_called(payload: &str) -> EventBindingCalled {
+        EventBindingCalled {
+            name: SOFT_NAVIGATION_BINDING.into(),
+            payload: payload.into(),
+            execution_context_id: ExecutionContextId::new(1),
+        }
+    }
+
+    #[test]
+    fn tracks_the_same_origin_soft_navigations() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_frame_navigated(&cdp_frame("F", "https://a.com/"));
+
+        let event = manager.on_runtime_binding_called(&binding_called(
+            r#"{"from":"https://a.com/","to":"https://a.com/b","kind":"pushState"}"#,
+        ));
+
+        assert!(matches!(event, Some(PageEvent::SoftNavigation { .. })));
+        assert_eq!(manager.main_frame().unwrap().url(), Some("https://a.com/b"));
+    }
+
+    #[test]
+    fn rejects_the_cross_origin_soft_navigations() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_frame_navigated(&cdp_frame("F", "https://a.com/"));
+
+        for to in [
+            "https://evil.com/",
+            "http://a.com/",
+            "https://a.com:8443/",
+            "data:,x",
+        ] {
+            let payload = format!(r#"{{"from":"https://a.com/","to":"{to}","kind":"pushState"}}"#);
+
+            assert_eq!(
+                manager.on_runtime_binding_called(&binding_called(&payload)),
+                None
+            );
+        }
+
+        assert_eq!(manager.main_frame().unwrap().url(), Some("https://a.com/"));
+    }
+
+    fn context_created(id: i64, name: &str, is_default: bool) -> EventExecutionContextCreated {
+        serde_json::from_value(serde_json::json!({
+            "context": {
+                "id": id,
+                "origin": "https://a.com",
+                "name": name,
+                "uniqueId": format!("ctx-{id}"),
+                "auxData": {
+                    "frameId": "F",
+                    "isDefault": is_default,
+                    "type": if is_default { "default" } else { "isolated" }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn context_destroyed(id: i64) -> EventExecutionContextDestroyed {
+        serde_json::from_value(serde_json::json!({
+            "executionContextUniqueId": format!("ctx-{id}")
+        }))
+        .unwrap()
+    }
+
+    fn world_context(frame: &Frame, name: &str) -> Option<ExecutionContextId> {
+        frame
+            .named_world(name)
+            .and_then(DOMWorld::execution_context)
+    }
+
+    #[test]
+    fn maps_the_named_worlds_to_their_contexts() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_frame_navigated(&cdp_frame("F", "https://a.com/"));
+
+        manager.on_frame_execution_context_created(&context_created(1, "", true));
+        manager.on_frame_execution_context_created(&context_created(2, "a", false));
+        manager.on_frame_execution_context_created(&context_created(3, "b", false));
+
+        let frame = manager.main_frame().unwrap();
+        assert_eq!(frame.execution_context(), Some(ExecutionContextId::new(1)));
+        assert_eq!(world_context(frame, "a"), Some(ExecutionContextId::new(2)));
+        assert_eq!(world_context(frame, "b"), Some(ExecutionContextId::new(3)));
+        assert_eq!(world_context(frame, "c"), None);
+        assert_eq!(frame.secondary_world().execution_context(), None);
+
+        manager.on_frame_execution_context_destroyed(&context_destroyed(2));
+
+        let frame = manager.main_frame().unwrap();
+        assert_eq!(world_context(frame, "a"), None);
+        assert_eq!(world_context(frame, "b"), Some(ExecutionContextId::new(3)));
+        assert_eq!(frame.execution_context(), Some(ExecutionContextId::new(1)));
+    }
+
+    #[test]
+    fn replaces_the_world_contexts_with_the_newest() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_frame_navigated(&cdp_frame("F", "https://a.com/"));
+        let secondary = manager
+            .main_frame()
+            .unwrap()
+            .get_isolated_world_name()
+            .clone();
+
+        manager.on_frame_execution_context_created(&context_created(1, "", true));
+        manager.on_frame_execution_context_created(&context_created(2, &secondary, false));
+        manager.on_frame_execution_context_created(&context_created(3, "a", false));
+
+        // the document is replaced without the destroy events of the worlds.
+        manager.on_frame_execution_context_created(&context_created(4, &secondary, false));
+        manager.on_frame_execution_context_created(&context_created(5, "a", false));
+
+        let frame = manager.main_frame().unwrap();
+        assert_eq!(
+            frame.secondary_world().execution_context(),
+            Some(ExecutionContextId::new(4))
+        );
+        assert_eq!(world_context(frame, "a"), Some(ExecutionContextId::new(5)));
+        assert_eq!(frame.execution_context(), Some(ExecutionContextId::new(1)));
+
+        // the late destroy events of the replaced contexts are ignored.
+        manager.on_frame_execution_context_destroyed(&context_destroyed(2));
+        manager.on_frame_execution_context_destroyed(&context_destroyed(3));
+
+        let frame = manager.main_frame().unwrap();
+        assert_eq!(
+            frame.secondary_world().execution_context(),
+            Some(ExecutionContextId::new(4))
+        );
+        assert_eq!(world_context(frame, "a"), Some(ExecutionContextId::new(5)));
+        assert_eq!(frame.execution_context(), Some(ExecutionContextId::new(1)));
+    }
+}
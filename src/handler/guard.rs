@@ -0,0 +1,60 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// A panic of a user callback caught on the handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackPanic {
+    /// The kind of the callback, e.g. `request signer`.
+    pub callback: &'static str,
+    /// The panic message.
+    pub message: String,
+}
+
+/// The message of a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Run a user callback on the handler, a panic is logged with the callback and its context,
+/// e.g. the url of the request, and returned instead of unwinding through the handler and
+/// dropping every page of the connection. Builds with `panic = "abort"` still abort.
+pub(crate) fn catch_callback<R>(
+    callback: &'static str,
+    context: &str,
+    f: impl FnOnce() -> R,
+) -> Result<R, CallbackPanic> {
+    catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = panic_message(payload.as_ref());
+        tracing::error!("{callback} panicked for {context}: {message}");
+
+        CallbackPanic { callback, message }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catches_callback_panics() {
+        assert_eq!(catch_callback("signer", "https://a.com", || 1), Ok(1));
+
+        let panic =
+            catch_callback::<()>("signer", "https://a.com", || panic!("missing key {}", 42))
+                .unwrap_err();
+
+        assert_eq!(panic.callback, "signer");
+        assert_eq!(panic.message, "missing key 42");
+
+        let panic = catch_callback::<()>("signer", "https://a.com", || std::panic::panic_any(7))
+            .unwrap_err();
+
+        assert_eq!(panic.message, "unknown panic");
+    }
+}
@@ -33,6 +33,9 @@ pub struct HttpRequest {
     pub post_data: Option<String>,
     /// List of redirect requests leading to this one.
     pub redirect_chain: Vec<HttpRequest>,
+    /// The http redirects, meta refreshes and script redirects followed by the navigation
+    /// leading to this document, in order. Set on the document of the main frame only.
+    pub redirect_provenance: Vec<crate::redirect::RedirectHop>,
 }
 
 impl HttpRequest {
@@ -60,6 +63,7 @@ impl HttpRequest {
             resource_type: None,
             post_data: None,
             redirect_chain,
+            redirect_provenance: Vec::new(),
         }
     }
     /// Returns the request ID.
@@ -1,4 +1,5 @@
 use crate::listeners::{EventListenerRequest, EventListeners};
+use crate::runtime::Instant;
 use chromiumoxide_cdp::cdp::browser_protocol::browser::*;
 use chromiumoxide_cdp::cdp::browser_protocol::target::*;
 use chromiumoxide_cdp::cdp::events::CdpEvent;
@@ -14,13 +15,16 @@ use hashbrown::{HashMap, HashSet};
 pub(crate) use page::PageInner;
 use spider_network_blocker::intercept_manager::NetworkInterceptManager;
 use std::pin::Pin;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio_tungstenite::tungstenite::error::ProtocolError;
 use tokio_tungstenite::tungstenite::Error;
 
 use crate::cmd::{to_command_response, CommandMessage};
 use crate::conn::Connection;
+use crate::duplicates::DuplicateIndex;
 use crate::error::{CdpError, Result};
+use crate::flight_recorder::FlightRecorder;
 use crate::handler::browser::BrowserContext;
 use crate::handler::frame::FrameRequestedNavigation;
 use crate::handler::frame::{NavigationError, NavigationId, NavigationOk};
@@ -29,7 +33,10 @@ use crate::handler::session::Session;
 use crate::handler::target::TargetEvent;
 use crate::handler::target::{Target, TargetConfig};
 use crate::handler::viewport::Viewport;
+use crate::injection::{InitScript, InitScriptRegistry};
 use crate::page::Page;
+use crate::policy::{CrawlPolicy, ScopePolicy};
+use crate::webhook::SequencedEvent;
 
 /// Standard timeout in MS
 pub const REQUEST_TIMEOUT: u64 = 30_000;
@@ -40,6 +47,7 @@ pub mod commandfuture;
 pub mod domworld;
 pub mod emulation;
 pub mod frame;
+mod guard;
 pub mod http;
 pub mod httpfuture;
 mod job;
@@ -91,6 +99,20 @@ pub struct Handler {
     budget_exhausted: bool,
     /// Tracks which targets we've already attached to, to avoid multiple sessions per target.
     attached_targets: HashSet<TargetId>,
+    /// The scripts injected on every new document.
+    init_scripts: InitScriptRegistry,
+    /// The webhook bus of the browser.
+    page_events: tokio::sync::broadcast::Sender<SequencedEvent>,
+    /// The seq of the last event delivered on the webhook bus.
+    page_event_seq: u64,
+    /// The time of the last message of the websocket.
+    last_message: Option<Instant>,
+    /// Records the last messages of every session.
+    flight_recorder: Option<Arc<FlightRecorder>>,
+    /// The simhashes of the pages fingerprinted.
+    duplicate_index: Option<Arc<DuplicateIndex>>,
+    /// The hosts the documents of new targets may load from.
+    scope_policy: Option<Arc<ScopePolicy>>,
 }
 
 lazy_static::lazy_static! {
@@ -122,6 +144,16 @@ impl Handler {
         let discover = DISCOVER_ID.clone();
         let _ = conn.submit_command(discover.0, None, discover.1);
 
+        let flight_recorder = (config.flight_recorder > 0).then(|| {
+            let recorder = Arc::new(FlightRecorder::new(config.flight_recorder));
+            conn.set_flight_recorder(recorder.clone());
+            recorder
+        });
+
+        let duplicate_index = config
+            .duplicate_detection
+            .map(|max_distance| Arc::new(DuplicateIndex::new(max_distance)));
+
         let browser_contexts = config
             .context_ids
             .iter()
@@ -146,6 +178,33 @@ impl Handler {
             remaining_bytes: None,
             budget_exhausted: false,
             attached_targets: Default::default(),
+            init_scripts: Default::default(),
+            page_events: tokio::sync::broadcast::channel(crate::webhook::PAGE_EVENTS_CAPACITY).0,
+            page_event_seq: 0,
+            last_message: None,
+            flight_recorder,
+            duplicate_index,
+            scope_policy: None,
+        }
+    }
+
+    /// Swap the blocking and scope policies of the existing and new targets. Applied in one
+    /// pass of the handler, so no request is intercepted with a mix of both policies.
+    fn update_policy(&mut self, policy: &CrawlPolicy) {
+        let blocking = &policy.blocking;
+
+        self.config.ignore_visuals = blocking.ignore_visuals;
+        self.config.ignore_stylesheets = blocking.ignore_stylesheets;
+        self.config.ignore_javascript = blocking.ignore_javascript;
+        self.config.ignore_analytics = blocking.ignore_analytics;
+        self.config.only_html = blocking.only_html;
+        self.scope_policy =
+            (!policy.scope.allowed_hosts.is_empty()).then(|| Arc::new(policy.scope.clone()));
+
+        for target in self.targets.values_mut() {
+            target
+                .network_manager
+                .set_policy(blocking, self.scope_policy.clone());
         }
     }
 
@@ -169,6 +228,16 @@ impl Handler {
         self.browser_contexts.iter()
     }
 
+    /// The sender of the page events of the targets.
+    pub(crate) fn page_events(&self) -> &tokio::sync::broadcast::Sender<SequencedEvent> {
+        &self.page_events
+    }
+
+    /// The index of the simhashes of the pages fingerprinted.
+    pub(crate) fn duplicate_index(&self) -> Option<&Arc<DuplicateIndex>> {
+        self.duplicate_index.as_ref()
+    }
+
     /// received a response to a navigation request like `Page.navigate`
     fn on_navigation_response(&mut self, id: NavigationId, resp: Response) {
         if let Some(nav) = self.navigations.remove(&id) {
@@ -429,7 +498,11 @@ impl Handler {
 
         chromiumoxide_cdp::consume_event!(match params {
             |ev| self.event_listeners.start_send(ev),
-            |json| { let _ = self.event_listeners.try_send_custom(&method, json);}
+            |json| {
+                let _ = guard::catch_callback("custom event", &method, || {
+                    self.event_listeners.try_send_custom(&method, json)
+                });
+            }
         });
     }
 
@@ -470,6 +543,17 @@ impl Handler {
                 only_html: self.config.only_html && self.config.created_first_target,
                 intercept_manager: self.config.intercept_manager,
                 max_bytes_allowed: self.config.max_bytes_allowed,
+                init_scripts: self.init_scripts.for_context(browser_ctx.id()),
+                anti_debugging: self.config.anti_debugging,
+                stability_shims: self.config.stability_shims,
+                consent_policy: self.config.consent_policy,
+                header_shaping: self.config.header_shaping.clone(),
+                request_signing: self.config.request_signing.clone(),
+                network_rules: self.config.network_rules.clone(),
+                redirect_policy: self.config.redirect_policy.clone(),
+                scope_policy: self.scope_policy.clone(),
+                flight_recorder: self.flight_recorder.clone(),
+                duplicate_index: self.duplicate_index.clone(),
             },
             browser_ctx,
         );
@@ -507,6 +591,10 @@ impl Handler {
             // TODO shutdown?
             if let Some(session) = target.session_id() {
                 self.sessions.remove(session);
+
+                if let Some(recorder) = self.flight_recorder.as_ref() {
+                    recorder.forget(session.as_ref());
+                }
             }
         }
     }
@@ -628,6 +716,27 @@ impl Stream for Handler {
                     HandlerMessage::AddEventListener(req) => {
                         pin.event_listeners.add_listener(req);
                     }
+                    HandlerMessage::RegisterInitScript(script) => {
+                        for target in pin.targets.values_mut() {
+                            if script.scope.applies_to(target.browser_context().id()) {
+                                target.add_init_script(&script);
+                            }
+                        }
+                        pin.init_scripts.register(script);
+                    }
+                    HandlerMessage::UnregisterInitScript(name) => {
+                        pin.init_scripts.unregister(&name);
+                    }
+                    HandlerMessage::UpdatePolicy(policy) => pin.update_policy(&policy),
+                    HandlerMessage::Health(tx) => {
+                        let _ = tx.send(crate::health::HandlerHealth {
+                            last_message: pin.last_message.map(|at| now.duration_since(at)),
+                            targets: pin.targets.len(),
+                            pages: pin.targets.values().filter(|t| t.is_page()).count(),
+                            pending_commands: pin.pending_commands.len(),
+                            closing: pin.closing,
+                        });
+                    }
                 }
             }
 
@@ -661,6 +770,19 @@ impl Stream for Handler {
                                     }
                                 }
                             }
+                            TargetEvent::WebhookEvent(event) => {
+                                pin.page_event_seq += 1;
+                                let event = SequencedEvent {
+                                    seq: pin.page_event_seq,
+                                    target_seq: target.next_page_event_seq(),
+                                    session_id: target
+                                        .session_id()
+                                        .map(|id| id.as_ref().to_string()),
+                                    event,
+                                };
+                                // nobody listening is not an error.
+                                let _ = pin.page_events.send(event);
+                            }
                         }
                     }
 
@@ -677,6 +799,9 @@ impl Stream for Handler {
             let mut done = true;
 
             while let Poll::Ready(Some(ev)) = Pin::new(&mut pin.conn).poll_next(cx) {
+                if ev.is_ok() {
+                    pin.last_message = Some(now);
+                }
                 match ev {
                     Ok(boxed_msg) => match *boxed_msg {
                         Message::Response(resp) => {
@@ -775,6 +900,24 @@ pub struct HandlerConfig {
     pub intercept_manager: NetworkInterceptManager,
     /// The max bytes to receive.
     pub max_bytes_allowed: Option<u64>,
+    /// Neutralize `debugger` loops and devtools detection.
+    pub anti_debugging: bool,
+    /// The shims of the web apis that crash or hang headless browsers.
+    pub stability_shims: crate::stability::StabilityShims,
+    /// Answer the cookie consent banners of every document.
+    pub consent_policy: Option<crate::consent::ConsentPolicy>,
+    /// Shape the Sec-Fetch, Origin and User-Agent headers of intercepted requests.
+    pub header_shaping: Option<std::sync::Arc<crate::sec_fetch::HeaderShaping>>,
+    /// The signing rules of intercepted requests.
+    pub request_signing: Option<std::sync::Arc<crate::request_signing::RequestSigning>>,
+    /// The interception rules of the requests of every page.
+    pub network_rules: Option<std::sync::Arc<crate::network_rules::NetworkRules>>,
+    /// The redirects followed during the navigations, every redirect when `None`.
+    pub redirect_policy: Option<crate::redirect::RedirectPolicy>,
+    /// The last CDP messages kept per target for `Page::dump_flight_record`, disabled when `0`.
+    pub flight_recorder: usize,
+    /// The max bits apart of the simhashes of near duplicate pages, disabled when `None`.
+    pub duplicate_detection: Option<u32>,
 }
 
 impl Default for HandlerConfig {
@@ -797,6 +940,15 @@ impl Default for HandlerConfig {
             created_first_target: false,
             intercept_manager: NetworkInterceptManager::Unknown,
             max_bytes_allowed: None,
+            anti_debugging: false,
+            stability_shims: Default::default(),
+            consent_policy: None,
+            header_shaping: None,
+            request_signing: None,
+            network_rules: None,
+            redirect_policy: None,
+            flight_recorder: 0,
+            duplicate_detection: None,
         }
     }
 }
@@ -879,4 +1031,8 @@ pub(crate) enum HandlerMessage {
     GetPage(TargetId, OneshotSender<Option<Page>>),
     AddEventListener(EventListenerRequest),
     CloseBrowser(OneshotSender<Result<CloseReturns>>),
+    RegisterInitScript(InitScript),
+    UnregisterInitScript(String),
+    UpdatePolicy(Arc<CrawlPolicy>),
+    Health(OneshotSender<crate::health::HandlerHealth>),
 }
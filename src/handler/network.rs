@@ -6,7 +6,14 @@ use crate::auth::Credentials;
 #[cfg(feature = "_cache")]
 use crate::cache::BasicCachePolicy;
 use crate::cmd::CommandChain;
+use crate::handler::guard::{catch_callback, CallbackPanic};
 use crate::handler::http::HttpRequest;
+use crate::network_rules::{NetworkRules, RuleAction};
+use crate::policy::{BlockingPolicy, ScopePolicy};
+use crate::request_signing::RequestSigning;
+use crate::sec_fetch::{header_entries, HeaderShaping, ShapedRequest};
+use crate::security::SecurityReport;
+use crate::streaming::{detect_manifest_kind, StreamingManifestRequest};
 use aho_corasick::AhoCorasick;
 use case_insensitive_string::CaseInsensitiveString;
 use chromiumoxide_cdp::cdp::browser_protocol::fetch::{RequestPattern, RequestStage};
@@ -38,6 +45,9 @@ use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::time::Duration;
 
+/// The streaming manifests kept at most per page.
+pub const MAX_STREAMING_MANIFESTS: usize = 256;
+
 lazy_static! {
     /// General patterns for popular libraries and resources
     static ref JS_FRAMEWORK_ALLOW: Vec<&'static str> = vec![
@@ -234,6 +244,21 @@ pub struct NetworkManager {
     /// The cache policy to use.
     #[cfg(feature = "_cache")]
     pub cache_policy: Option<BasicCachePolicy>,
+    /// The streaming manifests (HLS/DASH) seen on the network since the last main frame
+    /// navigation, the oldest dropped past [`MAX_STREAMING_MANIFESTS`].
+    streaming_manifests: VecDeque<StreamingManifestRequest>,
+    /// The security header audit of the current navigation.
+    pub security_report: SecurityReport,
+    /// The header shaping rules of intercepted requests.
+    pub header_shaping: Option<std::sync::Arc<HeaderShaping>>,
+    /// The signing rules of intercepted requests.
+    pub request_signing: Option<std::sync::Arc<RequestSigning>>,
+    /// The interception rules of the requests, applied ahead of the built-in blocking.
+    pub network_rules: Option<std::sync::Arc<NetworkRules>>,
+    /// The challenge providers detected on the page.
+    challenges_detected: HashSet<&'static str>,
+    /// The hosts the documents may load from.
+    pub scope_policy: Option<std::sync::Arc<ScopePolicy>>,
 }
 
 impl NetworkManager {
@@ -267,6 +292,13 @@ impl NetworkManager {
             cache_site_key: None,
             #[cfg(feature = "_cache")]
             cache_policy: None,
+            streaming_manifests: VecDeque::new(),
+            security_report: SecurityReport::default(),
+            header_shaping: None,
+            request_signing: None,
+            network_rules: None,
+            challenges_detected: Default::default(),
+            scope_policy: None,
         }
     }
 
@@ -309,6 +341,20 @@ impl NetworkManager {
         self.push_cdp_request(SetBypassServiceWorkerParams::new(bypass));
     }
 
+    /// Swap the blocking and scope policies, applied from the next intercepted request.
+    pub fn set_policy(
+        &mut self,
+        blocking: &BlockingPolicy,
+        scope: Option<std::sync::Arc<ScopePolicy>>,
+    ) {
+        self.ignore_visuals = blocking.ignore_visuals;
+        self.block_stylesheets = blocking.ignore_stylesheets;
+        self.block_javascript = blocking.ignore_javascript;
+        self.block_analytics = blocking.ignore_analytics;
+        self.only_html = blocking.only_html;
+        self.scope_policy = scope;
+    }
+
     pub fn set_block_all(&mut self, block_all: bool) {
         self.block_all = block_all;
     }
@@ -508,6 +554,21 @@ impl NetworkManager {
         skip_networking
     }
 
+    /// Record the network metrics of the cache site reported to the remote cache server.
+    #[cfg(feature = "_cache")]
+    #[inline]
+    fn record_site_stats(&self, update: impl FnOnce(&mut crate::cache::stats::SiteStats)) {
+        if let Some(site) = self.cache_site_key.as_deref() {
+            crate::cache::stats::record(site, update);
+        }
+    }
+
+    /// Queue the panic of a user callback run for the request of the url.
+    fn on_callback_panicked(&mut self, url: &str, panic: CallbackPanic) {
+        self.queued_events
+            .push_back(NetworkEvent::CallbackPanicked(url.to_string(), panic));
+    }
+
     #[inline]
     /// Fail request
     fn fail_request_blocked(
@@ -534,6 +595,33 @@ impl NetworkManager {
         self.push_cdp_request(params);
     }
 
+    /// Fulfill a paused Fetch request with the response of a network rule.
+    fn fulfill_request_with(
+        &mut self,
+        request_id: &chromiumoxide_cdp::cdp::browser_protocol::fetch::RequestId,
+        status: u16,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) {
+        use base64::Engine;
+
+        let mut params = fetch::FulfillRequestParams::new(request_id.clone(), status as i64);
+
+        params.response_headers = Some(
+            headers
+                .iter()
+                .map(|(name, value)| fetch::HeaderEntry::new(name.clone(), value.clone()))
+                .collect(),
+        );
+        params.body = Some(
+            base64::engine::general_purpose::STANDARD
+                .encode(body)
+                .into(),
+        );
+
+        self.push_cdp_request(params);
+    }
+
     #[cfg(feature = "_cache")]
     #[inline]
     /// Fulfill a paused Fetch request from cached bytes + header map.
@@ -580,12 +668,14 @@ impl NetworkManager {
         request_id: &chromiumoxide_cdp::cdp::browser_protocol::fetch::RequestId,
         url: Option<&str>,
         intercept_response: bool,
+        headers: Option<Vec<chromiumoxide_cdp::cdp::browser_protocol::fetch::HeaderEntry>>,
     ) {
         let mut params = ContinueRequestParams::new(request_id.clone());
         if let Some(url) = url {
             params.url = Some(url.to_string());
             params.intercept_response = Some(intercept_response);
         }
+        params.headers = headers;
         self.push_cdp_request(params);
     }
 
@@ -604,6 +694,26 @@ impl NetworkManager {
                 event.resource_type,
                 event.request.url
             );
+            #[cfg(feature = "_cache")]
+            self.record_site_stats(|stats| stats.blocked += 1);
+            self.queued_events.push_back(NetworkEvent::RequestBlocked(
+                event.request.url.clone(),
+                event.resource_type.as_ref().to_string(),
+            ));
+            return self.fail_request_blocked(&event.request_id);
+        }
+
+        if *resource_type == ResourceType::Document
+            && self
+                .scope_policy
+                .as_ref()
+                .is_some_and(|scope| !scope.allows(&event.request.url))
+        {
+            tracing::debug!("Blocked (out of scope): {}", event.request.url);
+            self.queued_events.push_back(NetworkEvent::RequestBlocked(
+                event.request.url.clone(),
+                event.resource_type.as_ref().to_string(),
+            ));
             return self.fail_request_blocked(&event.request_id);
         }
 
@@ -628,6 +738,70 @@ impl NetworkManager {
         let document_resource = *resource_type == ResourceType::Document;
         let network_resource = !document_resource && crate::utils::is_data_resource(resource_type);
 
+        // User rules come ahead of the built-in blocking.
+        let network_rules = self.network_rules.clone();
+        let rule_action = match network_rules.as_deref() {
+            Some(rules) => {
+                let request = ShapedRequest {
+                    url: &event.request.url,
+                    method: &event.request.method,
+                    resource_type,
+                    document_url: (!document_resource && !self.document_target_domain.is_empty())
+                        .then_some(self.document_target_domain.as_str()),
+                };
+
+                match catch_callback("network rules", &event.request.url, || {
+                    rules.evaluate(&request)
+                }) {
+                    Ok(action) => action,
+                    Err(panic) => {
+                        self.on_callback_panicked(&event.request.url, panic);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        match rule_action {
+            Some(RuleAction::Block) => {
+                tracing::debug!(
+                    "Blocked (rule): {:?} - {}",
+                    resource_type,
+                    event.request.url
+                );
+                #[cfg(feature = "_cache")]
+                self.record_site_stats(|stats| stats.blocked += 1);
+                self.queued_events.push_back(NetworkEvent::RequestBlocked(
+                    event.request.url.clone(),
+                    event.resource_type.as_ref().to_string(),
+                ));
+                return self.fail_request_blocked(&event.request_id);
+            }
+            Some(RuleAction::RedirectTo(url)) => {
+                tracing::debug!("Redirected (rule): {} - {}", event.request.url, url);
+                return self.continue_request_with_url(
+                    &event.request_id,
+                    Some(url.as_str()),
+                    false,
+                    None,
+                );
+            }
+            Some(RuleAction::Fulfill {
+                status,
+                headers,
+                body,
+            }) => {
+                tracing::debug!(
+                    "Fulfilled (rule): {:?} - {}",
+                    resource_type,
+                    event.request.url
+                );
+                return self.fulfill_request_with(&event.request_id, *status, headers, body);
+            }
+            _ => (),
+        }
+
         // Start with static / cheap skip checks.
         let mut skip_networking =
             self.block_all || IGNORE_NETWORKING_RESOURCE_MAP.contains(resource_type.as_ref());
@@ -679,11 +853,18 @@ impl NetworkManager {
 
         // Custom interception layer.
         if !skip_networking && (javascript_resource || network_resource || document_resource) {
-            skip_networking = self.intercept_manager.intercept_detection(
-                current_url,
-                self.ignore_visuals,
-                network_resource,
-            );
+            let intercept_manager = self.intercept_manager;
+            let ignore_visuals = self.ignore_visuals;
+
+            skip_networking = match catch_callback("intercept manager", current_url, || {
+                intercept_manager.intercept_detection(current_url, ignore_visuals, network_resource)
+            }) {
+                Ok(skip) => skip,
+                Err(panic) => {
+                    self.on_callback_panicked(current_url, panic);
+                    false
+                }
+            };
         }
 
         // Custom website block list.
@@ -695,8 +876,21 @@ impl NetworkManager {
             skip_networking = false;
         }
 
+        if skip_networking
+            && rule_action == Some(&RuleAction::Allow)
+            && self.document_reload_tracker < 3
+        {
+            skip_networking = false;
+        }
+
         if skip_networking {
             tracing::debug!("Blocked: {:?} - {}", resource_type, current_url);
+            #[cfg(feature = "_cache")]
+            self.record_site_stats(|stats| stats.blocked += 1);
+            self.queued_events.push_back(NetworkEvent::RequestBlocked(
+                current_url.to_string(),
+                resource_type.as_ref().to_string(),
+            ));
             self.fulfill_request_empty_200(&event.request_id);
         } else {
             #[cfg(feature = "_cache")]
@@ -705,29 +899,117 @@ impl NetworkManager {
                     (self.cache_policy.as_ref(), self.cache_site_key.as_deref())
                 {
                     let current_url = format!("{}:{}", event.request.method, &current_url);
-
-                    if let Some((res, cache_policy)) =
-                        crate::cache::remote::get_session_cache_item(cache_site_key, &current_url)
-                    {
-                        if policy.allows_cached(&cache_policy) {
+                    let request_headers: std::collections::HashMap<String, String> = event
+                        .request
+                        .headers
+                        .inner()
+                        .as_object()
+                        .map(|headers| {
+                            headers
+                                .iter()
+                                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                                .collect()
+                        })
+                        .unwrap_or_default();
+
+                    if let Some((res, cache_policy)) = crate::cache::remote::get_session_cache_item(
+                        cache_site_key,
+                        &current_url,
+                        &request_headers,
+                    ) {
+                        if policy.allows_cached_url(Some(res.url.as_str()), &cache_policy) {
                             tracing::debug!(
                                 "Remote Cached: {:?} - {}",
                                 resource_type,
                                 &current_url
                             );
-                            return self.fulfill_request_from_cache(
-                                &event.request_id,
+                            crate::cache::stats::record(cache_site_key, |stats| {
+                                stats.requests += 1;
+                                stats.cache_hits += 1;
+                            });
+                            let range = event
+                                .request
+                                .headers
+                                .inner()
+                                .as_object()
+                                .and_then(|headers| {
+                                    headers
+                                        .iter()
+                                        .find(|(k, _)| k.eq_ignore_ascii_case("range"))
+                                })
+                                .and_then(|(_, v)| v.as_str());
+                            let (status, body, headers) = crate::cache::range::range_response(
+                                range,
+                                res.status,
                                 &res.body,
                                 &res.headers,
-                                res.status as i64,
+                            );
+                            return self.fulfill_request_from_cache(
+                                &event.request_id,
+                                body,
+                                &headers,
+                                status as i64,
                             );
                         }
                     }
                 }
             }
 
+            let shaped = self.header_shaping.as_ref().map(|shaping| {
+                catch_callback("header shaping", current_url, || {
+                    shaping.shape(
+                        ShapedRequest {
+                            url: current_url,
+                            method: &event.request.method,
+                            resource_type,
+                            document_url: (!document_resource
+                                && !self.document_target_domain.is_empty())
+                            .then_some(self.document_target_domain.as_str()),
+                        },
+                        event.request.headers.inner(),
+                    )
+                })
+            });
+            let headers = match shaped {
+                Some(Ok(headers)) => headers,
+                Some(Err(panic)) => {
+                    self.on_callback_panicked(current_url, panic);
+                    None
+                }
+                None => None,
+            };
+
+            let headers = match rule_action {
+                Some(RuleAction::ModifyHeaders(modifications)) => {
+                    let mut entries =
+                        headers.unwrap_or_else(|| header_entries(event.request.headers.inner()));
+                    RuleAction::modify_headers(modifications, &mut entries);
+                    Some(entries)
+                }
+                _ => headers,
+            };
+
+            let headers = match self.request_signing.as_ref() {
+                Some(signing) => {
+                    let shaped = headers.clone();
+
+                    match catch_callback("request signer", current_url, || {
+                        signing.sign(current_url, &event.request, shaped)
+                    }) {
+                        Ok(signed) => signed,
+                        Err(panic) => {
+                            self.on_callback_panicked(current_url, panic);
+                            headers
+                        }
+                    }
+                }
+                _ => headers,
+            };
+
             // check our frame cache for the run.
             tracing::debug!("Allowed: {:?} - {}", resource_type, current_url);
+            #[cfg(feature = "_cache")]
+            self.record_site_stats(|stats| stats.requests += 1);
             self.continue_request_with_url(
                 &event.request_id,
                 if had_replacer {
@@ -736,6 +1018,7 @@ impl NetworkManager {
                     None
                 },
                 !had_replacer,
+                headers,
             );
         }
     }
@@ -756,6 +1039,27 @@ impl NetworkManager {
         self.document_target_domain = Default::default();
     }
 
+    /// The streaming manifests seen since the last main frame navigation.
+    pub fn streaming_manifests(&self) -> Vec<StreamingManifestRequest> {
+        self.streaming_manifests.iter().cloned().collect()
+    }
+
+    /// Record a streaming manifest, dropping the oldest past [`MAX_STREAMING_MANIFESTS`].
+    fn push_streaming_manifest(&mut self, manifest: StreamingManifestRequest) {
+        if self.streaming_manifests.len() >= MAX_STREAMING_MANIFESTS {
+            self.streaming_manifests.pop_front();
+        }
+        self.streaming_manifests.push_back(manifest);
+    }
+
+    /// Forget the streaming manifests of the previous document on a main frame navigation. The
+    /// document request of the navigation shares the id of its loader and is kept, it is the
+    /// manifest itself when navigating to one.
+    pub fn on_main_frame_navigated(&mut self, loader_id: &str) {
+        self.streaming_manifests
+            .retain(|manifest| manifest.request_id.inner() == loader_id);
+    }
+
     /// Handles:
     /// - document reload tracking (`document_reload_tracker`)
     /// - redirect masking / replacement
@@ -1006,9 +1310,48 @@ impl NetworkManager {
 
         // block all network request moving forward.
         if request_failed && self.max_bytes_allowed.is_some() {
+            if !self.block_all {
+                self.queued_events
+                    .push_back(NetworkEvent::BudgetExceeded(event.response.url.clone()));
+            }
             self.set_block_all(true);
         }
 
+        let cf_mitigated = event
+            .response
+            .headers
+            .inner()
+            .get("cf-mitigated")
+            .and_then(|v| v.as_str());
+
+        let challenge = match catch_callback("challenge classifier", &event.response.url, || {
+            crate::webhook::detect_challenge(&event.response.url, cf_mitigated)
+        }) {
+            Ok(challenge) => challenge,
+            Err(panic) => {
+                self.on_callback_panicked(&event.response.url, panic);
+                None
+            }
+        };
+
+        if let Some(provider) = challenge {
+            if self.challenges_detected.insert(provider) {
+                self.queued_events
+                    .push_back(NetworkEvent::ChallengeDetected(
+                        event.response.url.clone(),
+                        provider,
+                    ));
+            }
+        }
+
+        if let Some(kind) = detect_manifest_kind(&event.response.url, &event.response.mime_type) {
+            self.push_streaming_manifest(StreamingManifestRequest {
+                request_id: event.request_id.clone(),
+                url: event.response.url.clone(),
+                kind,
+            });
+        }
+
         if let Some(mut request) = self.requests.remove(event.request_id.as_ref()) {
             request.set_response(event.response.clone());
             self.queued_events.push_back(if request_failed {
@@ -1020,6 +1363,12 @@ impl NetworkManager {
     }
 
     pub fn on_network_loading_finished(&mut self, event: &EventLoadingFinished) {
+        #[cfg(feature = "_cache")]
+        if event.encoded_data_length > 0.0 {
+            let bytes = event.encoded_data_length as u64;
+            self.record_site_stats(|stats| stats.bytes += bytes);
+        }
+
         if let Some(request) = self.requests.remove(event.request_id.as_ref()) {
             if let Some(interception_id) = request.interception_id.as_ref() {
                 self.attempted_authentications
@@ -1116,4 +1465,45 @@ pub enum NetworkEvent {
     RequestFailed(HttpRequest),
     RequestFinished(HttpRequest),
     BytesConsumed(u64),
+    /// A request was blocked, with the url and resource type.
+    RequestBlocked(String, String),
+    /// A challenge response was received, with the url and provider.
+    ChallengeDetected(String, &'static str),
+    /// The max bytes allowed were received, with the url of the last response.
+    BudgetExceeded(String),
+    /// A user callback panicked, with the url of the request.
+    CallbackPanicked(String, CallbackPanic),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::StreamingManifestKind;
+
+    fn manifest(id: usize) -> StreamingManifestRequest {
+        StreamingManifestRequest {
+            request_id: RequestId::new(id.to_string()),
+            url: format!("https://a.com/{id}.m3u8"),
+            kind: StreamingManifestKind::Hls,
+        }
+    }
+
+    #[test]
+    fn caps_and_clears_the_streaming_manifests() {
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+
+        for id in 0..MAX_STREAMING_MANIFESTS + 2 {
+            manager.push_streaming_manifest(manifest(id));
+        }
+
+        let manifests = manager.streaming_manifests();
+        assert_eq!(manifests.len(), MAX_STREAMING_MANIFESTS);
+        assert_eq!(manifests[0], manifest(2));
+
+        manager.on_main_frame_navigated("100");
+        assert_eq!(manager.streaming_manifests(), vec![manifest(100)]);
+
+        manager.on_main_frame_navigated("loader");
+        assert!(manager.streaming_manifests().is_empty());
+    }
 }
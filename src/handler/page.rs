@@ -28,18 +28,21 @@ use chromiumoxide_cdp::cdp::browser_protocol::page::{
 };
 use chromiumoxide_cdp::cdp::browser_protocol::target::{ActivateTargetParams, SessionId, TargetId};
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
-    CallFunctionOnParams, CallFunctionOnReturns, EvaluateParams, ExecutionContextId, RemoteObjectId,
+    CallArgument, CallFunctionOnParams, CallFunctionOnReturns, EvaluateParams, ExecutionContextId,
+    ReleaseObjectParams, RemoteObjectId,
 };
 use chromiumoxide_types::{Command, CommandResponse};
 
 use crate::cmd::{to_command_response, CommandMessage};
+use crate::duplicates::DuplicateIndex;
 use crate::error::{CdpError, Result};
+use crate::flight_recorder::{FlightRecord, FlightRecorder};
 use crate::handler::commandfuture::CommandFuture;
 use crate::handler::domworld::DOMWorldKind;
 use crate::handler::httpfuture::HttpFuture;
 use crate::handler::target::{GetExecutionContext, TargetMessage};
 use crate::handler::target_message_future::TargetMessageFuture;
-use crate::js::EvaluationResult;
+use crate::js::{EvaluationResult, ResultLimit, LIMIT_RESULT_JS};
 use crate::layout::{Delta, Point, ScrollBehavior};
 use crate::page::ScreenshotParams;
 use crate::{keys, utils, ArcHttpRequest};
@@ -51,13 +54,21 @@ pub struct PageHandle {
 }
 
 impl PageHandle {
-    pub fn new(target_id: TargetId, session_id: SessionId, opener_id: Option<TargetId>) -> Self {
+    pub fn new(
+        target_id: TargetId,
+        session_id: SessionId,
+        opener_id: Option<TargetId>,
+        flight_recorder: Option<Arc<FlightRecorder>>,
+        duplicate_index: Option<Arc<DuplicateIndex>>,
+    ) -> Self {
         let (commands, rx) = channel(100);
         let page = PageInner {
             target_id,
             session_id,
             opener_id,
             sender: commands,
+            flight_recorder,
+            duplicate_index,
         };
         Self {
             rx: rx.fuse(),
@@ -80,6 +91,10 @@ pub(crate) struct PageInner {
     opener_id: Option<TargetId>,
     /// The sender for the target.
     sender: Sender<TargetMessage>,
+    /// Records the last messages of every session.
+    flight_recorder: Option<Arc<FlightRecorder>>,
+    /// The simhashes of the pages fingerprinted.
+    duplicate_index: Option<Arc<DuplicateIndex>>,
 }
 
 impl PageInner {
@@ -133,6 +148,19 @@ impl PageInner {
         &self.session_id
     }
 
+    /// The last messages of the session kept by the flight recorder, oldest first.
+    pub(crate) fn flight_record(&self) -> Vec<FlightRecord> {
+        self.flight_recorder
+            .as_ref()
+            .map(|recorder| recorder.records(self.session_id.as_ref()))
+            .unwrap_or_default()
+    }
+
+    /// The index of the simhashes of the pages fingerprinted, if enabled.
+    pub(crate) fn duplicate_index(&self) -> Option<&Arc<DuplicateIndex>> {
+        self.duplicate_index.as_ref()
+    }
+
     /// The identifier of this page's target's opener target
     pub fn opener_id(&self) -> &Option<TargetId> {
         &self.opener_id
@@ -705,6 +733,59 @@ impl PageInner {
         Ok(EvaluationResult::new(resp.result))
     }
 
+    /// Serialize the result within the limit in the page, releasing the remote object. Only the
+    /// value of results fitting the limit is sent back whole, the value of a boxed result is its
+    /// `value` property.
+    pub async fn limit_result(
+        &self,
+        result: EvaluationResult,
+        limit: ResultLimit,
+        boxed: bool,
+    ) -> Result<EvaluationResult> {
+        let mut object = result.object().clone();
+
+        if let Some(object_id) = object.object_id.take() {
+            let params = CallFunctionOnParams::builder()
+                .object_id(object_id.clone())
+                .function_declaration(LIMIT_RESULT_JS)
+                .argument(
+                    CallArgument::builder()
+                        .value(serde_json::json!(limit.max_size))
+                        .build(),
+                )
+                .argument(
+                    CallArgument::builder()
+                        .value(serde_json::json!(boxed))
+                        .build(),
+                )
+                .return_by_value(true)
+                .build()
+                .map_err(CdpError::msg)?;
+
+            let resp = self.execute(params).await;
+            let _ = self.execute(ReleaseObjectParams::new(object_id)).await;
+            let resp = resp?.result;
+
+            if let Some(exception) = resp.exception_details {
+                return Err(CdpError::JavascriptException(Box::new(exception)));
+            }
+
+            let (value, truncation) = limit.apply(resp.result.value)?;
+            object.value = value;
+
+            return Ok(EvaluationResult::truncated(object, truncation));
+        }
+
+        if let Some(serde_json::Value::String(value)) = &object.value {
+            if let Some((value, truncation)) = limit.apply_str(value)? {
+                object.value = Some(value);
+                return Ok(EvaluationResult::truncated(object, Some(truncation)));
+            }
+        }
+
+        Ok(result)
+    }
+
     pub async fn execution_context(&self) -> Result<Option<ExecutionContextId>> {
         self.execution_context_for_world(None, DOMWorldKind::Main)
             .await
@@ -739,11 +820,29 @@ impl PageInner {
         let (tx, rx) = oneshot_channel();
         self.sender
             .clone()
-            .send(TargetMessage::GetExecutionContext(GetExecutionContext {
-                dom_world,
-                frame_id,
-                tx,
-            }))
+            .send(TargetMessage::GetExecutionContext(
+                GetExecutionContext::new(tx)
+                    .with_frame(frame_id)
+                    .with_dom_world(dom_world),
+            ))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// The execution context of a named isolated world.
+    pub async fn isolated_world_context(
+        &self,
+        frame_id: Option<FrameId>,
+        world_name: impl Into<String>,
+    ) -> Result<Option<ExecutionContextId>> {
+        let (tx, rx) = oneshot_channel();
+        self.sender
+            .clone()
+            .send(TargetMessage::GetExecutionContext(
+                GetExecutionContext::new(tx)
+                    .with_frame(frame_id)
+                    .with_world(world_name),
+            ))
             .await?;
         Ok(rx.await?)
     }
@@ -1,12 +1,13 @@
 use std::collections::VecDeque;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Instant;
 
 use chromiumoxide_cdp::cdp::browser_protocol::target::DetachFromTargetParams;
 use futures::channel::oneshot::Sender;
 use futures::stream::Stream;
 use futures::task::{Context, Poll};
+use futures::Future;
+use futures_timer::Delay;
 
 use crate::auth::Credentials;
 use crate::cdp::browser_protocol::target::CloseTargetParams;
@@ -20,19 +21,29 @@ use crate::handler::frame::FrameRequestedNavigation;
 use crate::handler::frame::{
     FrameEvent, FrameManager, NavigationError, NavigationId, NavigationOk,
 };
+use crate::handler::guard::catch_callback;
 use crate::handler::network::{NetworkEvent, NetworkManager};
 use crate::handler::page::PageHandle;
 use crate::handler::viewport::Viewport;
 use crate::handler::{PageInner, REQUEST_TIMEOUT};
+use crate::injection::InitScript;
+use crate::javascript::anti_debugging::{ANTI_DEBUGGING_JS, FREEZE_PROTOTYPES_JS};
+use crate::js_errors::{JsError, MAX_JS_ERRORS};
 use crate::listeners::{EventListenerRequest, EventListeners};
+use crate::runtime::Instant;
+use crate::webhook::WebhookEvent;
 use crate::{page::Page, ArcHttpRequest};
 use chromiumoxide_cdp::cdp::browser_protocol::{
     browser::BrowserContextId,
+    emulation::{ClearDeviceMetricsOverrideParams, SetDeviceMetricsOverrideParams},
     log as cdplog,
-    page::{FrameId, GetFrameTreeParams},
+    page::{
+        AddScriptToEvaluateOnNewDocumentParams, FrameId, GetFrameTreeParams, StopLoadingParams,
+    },
     target::{AttachToTargetParams, SessionId, SetAutoAttachParams, TargetId, TargetInfo},
 };
 use chromiumoxide_cdp::cdp::events::CdpEvent;
+use chromiumoxide_cdp::cdp::js_protocol::debugger;
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
     ExecutionContextId, RunIfWaitingForDebuggerParams,
 };
@@ -103,6 +114,17 @@ lazy_static::lazy_static! {
 
         (runtime_cmd.identifier(), serde_json::to_value(runtime_cmd).unwrap_or_default())
     };
+
+    /// Hide the client from devtools detection. The debugger is left disabled, `debugger`
+    /// statements only pause with an enabled debugger, see `Target::skip_all_pauses`.
+    static ref ANTI_DEBUGGING_COMMANDS: Vec<(chromiumoxide_types::MethodId, serde_json::Value)> = {
+        let console_shim = AddScriptToEvaluateOnNewDocumentParams::new(ANTI_DEBUGGING_JS);
+
+        vec![(
+            console_shim.identifier(),
+            serde_json::to_value(console_shim).unwrap_or_default(),
+        )]
+    };
 }
 
 #[derive(Debug)]
@@ -139,6 +161,44 @@ pub struct Target {
     wait_for_network_almost_idle: Vec<Sender<ArcHttpRequest>>,
     /// The sender who requested the page.
     initiator: Option<Sender<Result<Page>>>,
+    /// The uncaught javascript errors of the current document.
+    js_errors: Vec<JsError>,
+    /// The seq of the last webhook event of this target delivered.
+    page_event_seq: u64,
+    /// Wakes the target once the navigation stopped waiting for a client redirect, with the
+    /// deadline it was armed for.
+    client_redirect_timer: Option<(Instant, Delay)>,
+}
+
+/// Poll the timer of the client redirect deadline, re-armed when the deadline changed and
+/// dropped without one. Ready once the deadline passed.
+fn poll_client_redirect_timer(
+    timer: &mut Option<(Instant, Delay)>,
+    deadline: Option<Instant>,
+    now: Instant,
+    cx: &mut Context<'_>,
+) -> bool {
+    let Some(deadline) = deadline else {
+        *timer = None;
+        return false;
+    };
+
+    if timer.as_ref().map(|(armed, _)| *armed) != Some(deadline) {
+        *timer = Some((
+            deadline,
+            Delay::new(deadline.saturating_duration_since(now)),
+        ));
+    }
+
+    let ready = timer
+        .as_mut()
+        .is_some_and(|(_, delay)| Future::poll(Pin::new(delay), cx).is_ready());
+
+    if ready {
+        *timer = None;
+    }
+
+    ready
 }
 
 impl Target {
@@ -159,6 +219,10 @@ impl Target {
 
         network_manager.set_request_interception(config.request_intercept);
         network_manager.max_bytes_allowed = config.max_bytes_allowed;
+        network_manager.header_shaping = config.header_shaping.clone();
+        network_manager.request_signing = config.request_signing.clone();
+        network_manager.network_rules = config.network_rules.clone();
+        network_manager.scope_policy = config.scope_policy.clone();
 
         if let Some(ref headers) = config.extra_headers {
             network_manager.set_extra_headers(headers.clone());
@@ -171,11 +235,14 @@ impl Target {
         network_manager.only_html = config.only_html;
         network_manager.intercept_manager = config.intercept_manager;
 
+        let mut frame_manager = FrameManager::new(request_timeout);
+        frame_manager.set_redirect_policy(config.redirect_policy.clone());
+
         Self {
             info,
             r#type: ty,
             config,
-            frame_manager: FrameManager::new(request_timeout),
+            frame_manager,
             network_manager,
             emulation_manager: EmulationManager::new(request_timeout),
             session_id: None,
@@ -187,6 +254,9 @@ impl Target {
             queued_events: Default::default(),
             event_listeners: Default::default(),
             initiator: None,
+            js_errors: Default::default(),
+            page_event_seq: 0,
+            client_redirect_timer: None,
             browser_context,
         }
     }
@@ -206,6 +276,12 @@ impl Target {
         &mut self.session_id
     }
 
+    /// The seq of the next webhook event of this target delivered on the webhook bus.
+    pub(crate) fn next_page_event_seq(&mut self) -> u64 {
+        self.page_event_seq += 1;
+        self.page_event_seq
+    }
+
     /// Get the browser context.
     pub fn browser_context(&self) -> &BrowserContext {
         &self.browser_context
@@ -228,6 +304,9 @@ impl Target {
 
     /// Navigate a frame
     pub fn goto(&mut self, req: FrameRequestedNavigation) {
+        // the timer of the previous navigation must not delay or wake this one.
+        self.client_redirect_timer = None;
+
         if self.network_manager.has_target_domain() {
             self.network_manager.clear_target_domain();
             let goto_url = req
@@ -248,8 +327,13 @@ impl Target {
     fn create_page(&mut self) {
         if self.page.is_none() {
             if let Some(session) = self.session_id.clone() {
-                let handle =
-                    PageHandle::new(self.target_id().clone(), session, self.opener_id().cloned());
+                let handle = PageHandle::new(
+                    self.target_id().clone(),
+                    session,
+                    self.opener_id().cloned(),
+                    self.config.flight_recorder.clone(),
+                    self.config.duplicate_index.clone(),
+                );
                 self.page = Some(handle);
             }
         }
@@ -334,6 +418,7 @@ impl Target {
                 | CdpEvent::PageFrameDetached(_)
                 | CdpEvent::PageFrameNavigated(_)
                 | CdpEvent::PageNavigatedWithinDocument(_)
+                | CdpEvent::PageFrameRequestedNavigation(_)
                 | CdpEvent::PageLifecycleEvent(_)
                 | CdpEvent::PageFrameStartedLoading(_)
                 | CdpEvent::PageFrameStoppedLoading(_)
@@ -341,6 +426,7 @@ impl Target {
                 | CdpEvent::RuntimeExecutionContextDestroyed(_)
                 | CdpEvent::RuntimeExecutionContextsCleared(_)
                 | CdpEvent::RuntimeBindingCalled(_)
+                | CdpEvent::RuntimeExceptionThrown(_)
         );
 
         if is_session_scoped {
@@ -366,11 +452,19 @@ impl Target {
                 .on_frame_attached(ev.frame_id.clone(), Some(ev.parent_frame_id.clone())),
             CdpEvent::PageFrameDetached(ev) => self.frame_manager.on_frame_detached(ev),
             CdpEvent::PageFrameNavigated(ev) => {
+                if ev.frame.parent_id.is_none() {
+                    self.js_errors.clear();
+                    self.network_manager
+                        .on_main_frame_navigated(ev.frame.loader_id.inner());
+                }
                 self.frame_manager.on_frame_navigated(&ev.frame);
             }
             CdpEvent::PageNavigatedWithinDocument(ev) => {
                 self.frame_manager.on_frame_navigated_within_document(ev)
             }
+            CdpEvent::PageFrameRequestedNavigation(ev) => {
+                self.frame_manager.on_frame_requested_navigation(ev)
+            }
             CdpEvent::RuntimeExecutionContextCreated(ev) => {
                 self.frame_manager.on_frame_execution_context_created(ev)
             }
@@ -381,8 +475,14 @@ impl Target {
                 self.frame_manager.on_execution_contexts_cleared()
             }
             CdpEvent::RuntimeBindingCalled(ev) => {
-                // TODO check if binding registered and payload is json
-                self.frame_manager.on_runtime_binding_called(ev)
+                if let Some(event) = self.frame_manager.on_runtime_binding_called(ev) {
+                    self.event_listeners.start_send(event);
+                }
+            }
+            CdpEvent::RuntimeExceptionThrown(ev) => {
+                if self.js_errors.len() < MAX_JS_ERRORS {
+                    self.js_errors.push(JsError::from_event(ev));
+                }
             }
             CdpEvent::PageLifecycleEvent(ev) => self.frame_manager.on_page_lifecycle_event(ev),
             CdpEvent::PageFrameStartedLoading(ev) => {
@@ -423,12 +523,22 @@ impl Target {
             CdpEvent::FetchRequestPaused(ev) => self.network_manager.on_fetch_request_paused(ev),
             CdpEvent::FetchAuthRequired(ev) => self.network_manager.on_fetch_auth_required(ev),
             CdpEvent::NetworkRequestWillBeSent(ev) => {
+                self.frame_manager.on_request_will_be_sent(ev);
                 self.network_manager.on_request_will_be_sent(ev)
             }
             CdpEvent::NetworkRequestServedFromCache(ev) => {
                 self.network_manager.on_request_served_from_cache(ev)
             }
-            CdpEvent::NetworkResponseReceived(ev) => self.network_manager.on_response_received(ev),
+            CdpEvent::NetworkResponseReceived(ev) => {
+                let main_document = match (self.frame_manager.main_frame(), ev.frame_id.as_ref()) {
+                    (Some(frame), Some(frame_id)) => frame.id() == frame_id,
+                    _ => false,
+                };
+                self.network_manager
+                    .security_report
+                    .on_response_received(ev, main_document);
+                self.network_manager.on_response_received(ev)
+            }
             CdpEvent::NetworkLoadingFinished(ev) => {
                 self.network_manager.on_network_loading_finished(ev)
             }
@@ -439,7 +549,20 @@ impl Target {
         }
         chromiumoxide_cdp::consume_event!(match params {
            |ev| self.event_listeners.start_send(ev),
-           |json| { let _ = self.event_listeners.try_send_custom(&method, json);}
+           |json| {
+               if let Err(panic) = catch_callback("custom event", &method, || {
+                   self.event_listeners.try_send_custom(&method, json)
+               }) {
+                   self.queued_events.push_back(TargetEvent::WebhookEvent(
+                       WebhookEvent::CallbackPanicked {
+                           target_id: self.info.target_id.inner().clone(),
+                           callback: panic.callback,
+                           context: method.to_string(),
+                           message: panic.message,
+                       },
+                   ));
+               }
+           }
         });
     }
 
@@ -526,7 +649,8 @@ impl Target {
                     now,
                     cmds,
                     TargetInit::InitializingPage(Self::page_init_commands(
-                        self.config.request_timeout
+                        &self.config,
+                        self.frame_manager.get_isolated_world_name()
                     ))
                 );
             }
@@ -630,7 +754,16 @@ impl Target {
                                     }
                                 }
                             }
+                            if cmd.method == SetDeviceMetricsOverrideParams::IDENTIFIER {
+                                self.emulation_manager.device_metrics_override = true;
+                            } else if cmd.method == ClearDeviceMetricsOverrideParams::IDENTIFIER {
+                                self.emulation_manager.device_metrics_override = false;
+                            }
+                            let enables_debugger = cmd.method == debugger::EnableParams::IDENTIFIER;
                             self.queued_events.push_back(TargetEvent::Command(cmd));
+                            if enables_debugger && self.config.anti_debugging {
+                                self.skip_all_pauses();
+                            }
                         }
                         TargetMessage::MainFrame(tx) => {
                             let _ =
@@ -719,6 +852,7 @@ impl Target {
                             let GetExecutionContext {
                                 dom_world,
                                 frame_id,
+                                world_name,
                                 tx,
                             } = ctx;
                             let frame = if let Some(frame_id) = frame_id {
@@ -727,7 +861,11 @@ impl Target {
                                 self.frame_manager.main_frame()
                             };
 
-                            if let Some(frame) = frame {
+                            if let (Some(frame), Some(name)) = (frame, world_name) {
+                                let _ = tx.send(
+                                    frame.named_world(&name).and_then(|w| w.execution_context()),
+                                );
+                            } else if let Some(frame) = frame {
                                 match dom_world {
                                     DOMWorldKind::Main => {
                                         let _ = tx.send(frame.main_world().execution_context());
@@ -744,6 +882,30 @@ impl Target {
                         TargetMessage::Authenticate(credentials) => {
                             self.network_manager.authenticate(credentials);
                         }
+                        TargetMessage::StreamingManifests(tx) => {
+                            let _ = tx.send(self.network_manager.streaming_manifests());
+                        }
+                        TargetMessage::SecurityReport(tx) => {
+                            let _ = tx.send(self.network_manager.security_report.clone());
+                        }
+                        TargetMessage::JsErrors(tx) => {
+                            let _ = tx.send(self.js_errors.clone());
+                        }
+                        TargetMessage::NetworkRules(rules) => {
+                            self.network_manager.network_rules = rules;
+                        }
+                        TargetMessage::RedirectPolicy(policy) => {
+                            self.frame_manager.set_redirect_policy(policy);
+                        }
+                        TargetMessage::RedirectChain(tx) => {
+                            let _ = tx.send(self.frame_manager.redirect_hops().to_vec());
+                        }
+                        TargetMessage::ScopePolicy(tx) => {
+                            let _ = tx.send(self.network_manager.scope_policy.clone());
+                        }
+                        TargetMessage::DeviceMetricsOverride(tx) => {
+                            let _ = tx.send(self.emulation_manager.device_metrics_override);
+                        }
                     }
                 }
             }
@@ -772,6 +934,42 @@ impl Target {
                     NetworkEvent::BytesConsumed(n) => {
                         self.queued_events.push_back(TargetEvent::BytesConsumed(n));
                     }
+                    NetworkEvent::RequestBlocked(url, resource_type) => {
+                        self.queued_events.push_back(TargetEvent::WebhookEvent(
+                            WebhookEvent::RequestBlocked {
+                                target_id: self.info.target_id.inner().clone(),
+                                url,
+                                resource_type,
+                            },
+                        ));
+                    }
+                    NetworkEvent::ChallengeDetected(url, provider) => {
+                        self.queued_events.push_back(TargetEvent::WebhookEvent(
+                            WebhookEvent::ChallengeDetected {
+                                target_id: self.info.target_id.inner().clone(),
+                                url,
+                                provider,
+                            },
+                        ));
+                    }
+                    NetworkEvent::BudgetExceeded(url) => {
+                        self.queued_events.push_back(TargetEvent::WebhookEvent(
+                            WebhookEvent::CrawlBudgetExceeded {
+                                target_id: self.info.target_id.inner().clone(),
+                                url,
+                            },
+                        ));
+                    }
+                    NetworkEvent::CallbackPanicked(url, panic) => {
+                        self.queued_events.push_back(TargetEvent::WebhookEvent(
+                            WebhookEvent::CallbackPanicked {
+                                target_id: self.info.target_id.inner().clone(),
+                                callback: panic.callback,
+                                context: url,
+                                message: panic.message,
+                            },
+                        ));
+                    }
                 }
             }
 
@@ -781,6 +979,18 @@ impl Target {
                 }
                 match event {
                     FrameEvent::NavigationResult(res) => {
+                        if res.is_ok() {
+                            self.queued_events.push_back(TargetEvent::WebhookEvent(
+                                WebhookEvent::NavigationCompleted {
+                                    target_id: self.info.target_id.inner().clone(),
+                                    url: self
+                                        .frame_manager
+                                        .main_frame()
+                                        .and_then(|frame| frame.url())
+                                        .map(Into::into),
+                                },
+                            ));
+                        }
                         self.queued_events
                             .push_back(TargetEvent::NavigationResult(res));
                     }
@@ -788,23 +998,110 @@ impl Target {
                         self.queued_events
                             .push_back(TargetEvent::NavigationRequest(id, req));
                     }
+                    FrameEvent::StopLoading => {
+                        let stop_loading = StopLoadingParams::default();
+                        self.queued_events.push_back(TargetEvent::Request(Request {
+                            method: stop_loading.identifier(),
+                            session_id: self.session_id.clone().map(Into::into),
+                            params: serde_json::to_value(stop_loading).unwrap_or_default(),
+                        }));
+                    }
                 }
             }
 
+            let deadline = self.frame_manager.client_redirect_deadline();
+
+            if poll_client_redirect_timer(&mut self.client_redirect_timer, deadline, now, cx) {
+                cx.waker().wake_by_ref();
+            }
+
             if self.queued_events.is_empty() {
                 return None;
             }
         }
     }
 
+    /// Skip the pauses of the debugger enabled on the page, queued right after the enable
+    /// command. The debugger is only enabled on demand: an enabled debugger agent slows the
+    /// scripts down and a page timing its `debugger` statements can still tell it is attached.
+    fn skip_all_pauses(&mut self) {
+        let skip_pauses = debugger::SetSkipAllPausesParams::new(true);
+        self.queued_events.push_back(TargetEvent::Request(Request {
+            method: skip_pauses.identifier(),
+            session_id: self.session_id.clone().map(Into::into),
+            params: serde_json::to_value(skip_pauses).unwrap_or_default(),
+        }));
+    }
+
     /// Set the sender half of the channel who requested the creation of this
     /// target
     pub fn set_initiator(&mut self, tx: Sender<Result<Page>>) {
         self.initiator = Some(tx);
     }
 
-    pub(crate) fn page_init_commands(timeout: Duration) -> CommandChain {
-        CommandChain::new(INIT_COMMANDS_PARAMS.clone(), timeout)
+    pub(crate) fn page_init_commands(
+        config: &TargetConfig,
+        isolated_world: Option<&String>,
+    ) -> CommandChain {
+        let mut cmds = INIT_COMMANDS_PARAMS.clone();
+
+        if config.anti_debugging {
+            cmds.extend(ANTI_DEBUGGING_COMMANDS.iter().cloned());
+
+            if let Some(world_name) = isolated_world {
+                let freeze = AddScriptToEvaluateOnNewDocumentParams {
+                    source: FREEZE_PROTOTYPES_JS.into(),
+                    world_name: Some(world_name.clone()),
+                    include_command_line_api: None,
+                    run_immediately: None,
+                };
+                cmds.push((
+                    freeze.identifier(),
+                    serde_json::to_value(freeze).unwrap_or_default(),
+                ));
+            }
+        }
+
+        if let Some(source) = config.stability_shims.script() {
+            let shims = AddScriptToEvaluateOnNewDocumentParams::new(source);
+            cmds.push((
+                shims.identifier(),
+                serde_json::to_value(shims).unwrap_or_default(),
+            ));
+        }
+
+        if let Some(policy) = config.consent_policy {
+            let consent = AddScriptToEvaluateOnNewDocumentParams::new(policy.script());
+            cmds.push((
+                consent.identifier(),
+                serde_json::to_value(consent).unwrap_or_default(),
+            ));
+        }
+
+        cmds.extend(config.init_scripts.iter().map(InitScript::command));
+        CommandChain::new(cmds, config.request_timeout)
+    }
+
+    /// Inject a script registered after the target was created.
+    pub(crate) fn add_init_script(&mut self, script: &InitScript) {
+        match self.init_state {
+            TargetInit::AttachToTarget
+            | TargetInit::InitializingFrame(_)
+            | TargetInit::InitializingNetwork(_) => {
+                self.config.init_scripts.push(script.clone());
+            }
+            TargetInit::Closing => (),
+            _ => {
+                if let Some(session_id) = self.session_id.clone() {
+                    let (method, params) = script.command();
+                    self.queued_events.push_back(TargetEvent::Request(Request {
+                        method,
+                        session_id: Some(session_id.into()),
+                        params,
+                    }));
+                }
+            }
+        }
     }
 }
 
@@ -853,6 +1150,28 @@ pub struct TargetConfig {
     /// The maximum number of response bytes allowed for this target.
     /// When set, responses larger than this limit may be truncated or aborted.
     pub max_bytes_allowed: Option<u64>,
+    /// Scripts injected on every new document, in order.
+    pub init_scripts: Vec<InitScript>,
+    /// Skip debugger pauses, shim devtools detection and freeze the helper world builtins.
+    pub anti_debugging: bool,
+    /// The shims of the web apis that crash or hang headless browsers.
+    pub stability_shims: crate::stability::StabilityShims,
+    /// Answer the cookie consent banners of every document.
+    pub consent_policy: Option<crate::consent::ConsentPolicy>,
+    /// Shape the Sec-Fetch, Origin and User-Agent headers of intercepted requests.
+    pub header_shaping: Option<std::sync::Arc<crate::sec_fetch::HeaderShaping>>,
+    /// The signing rules of intercepted requests.
+    pub request_signing: Option<std::sync::Arc<crate::request_signing::RequestSigning>>,
+    /// The interception rules of the requests.
+    pub network_rules: Option<std::sync::Arc<crate::network_rules::NetworkRules>>,
+    /// The redirects followed during the navigations, every redirect when `None`.
+    pub redirect_policy: Option<crate::redirect::RedirectPolicy>,
+    /// The hosts the documents may load from, every host when `None`.
+    pub scope_policy: Option<std::sync::Arc<crate::policy::ScopePolicy>>,
+    /// Records the last messages of every session.
+    pub(crate) flight_recorder: Option<std::sync::Arc<crate::flight_recorder::FlightRecorder>>,
+    /// The simhashes of the pages fingerprinted.
+    pub(crate) duplicate_index: Option<std::sync::Arc<crate::duplicates::DuplicateIndex>>,
 }
 
 impl Default for TargetConfig {
@@ -872,6 +1191,17 @@ impl Default for TargetConfig {
             extra_headers: Default::default(),
             intercept_manager: NetworkInterceptManager::Unknown,
             max_bytes_allowed: None,
+            init_scripts: Default::default(),
+            anti_debugging: false,
+            stability_shims: Default::default(),
+            consent_policy: None,
+            header_shaping: None,
+            request_signing: None,
+            network_rules: None,
+            redirect_policy: None,
+            scope_policy: None,
+            flight_recorder: None,
+            duplicate_index: None,
         }
     }
 }
@@ -943,6 +1273,8 @@ pub(crate) enum TargetEvent {
     Command(CommandMessage),
     /// The bytes consumed by the network.
     BytesConsumed(u64),
+    /// An event delivered to the page event subscribers.
+    WebhookEvent(WebhookEvent),
 }
 
 // TODO this can be moved into the classes?
@@ -977,6 +1309,9 @@ pub struct GetExecutionContext {
     pub dom_world: DOMWorldKind,
     /// The if of the frame to get the `ExecutionContext` for
     pub frame_id: Option<FrameId>,
+    /// The name of an isolated world created with `Page::create_isolated_world`, takes
+    /// precedence over `dom_world`
+    pub world_name: Option<String>,
     /// Sender half of the channel to send the response back
     pub tx: Sender<Option<ExecutionContextId>>,
 }
@@ -986,9 +1321,29 @@ impl GetExecutionContext {
         Self {
             dom_world: DOMWorldKind::Main,
             frame_id: None,
+            world_name: None,
             tx,
         }
     }
+
+    /// The execution context of the frame instead of the main frame.
+    pub fn with_frame(mut self, frame_id: Option<FrameId>) -> Self {
+        self.frame_id = frame_id;
+        self
+    }
+
+    /// The execution context of the world of the frame.
+    pub fn with_dom_world(mut self, dom_world: DOMWorldKind) -> Self {
+        self.dom_world = dom_world;
+        self
+    }
+
+    /// The execution context of a named isolated world created with
+    /// `Page::create_isolated_world`, taking precedence over the dom world.
+    pub fn with_world(mut self, world_name: impl Into<String>) -> Self {
+        self.world_name = Some(world_name.into());
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -1050,4 +1405,52 @@ pub enum TargetMessage {
     /// Get the `ExecutionContext` if available
     GetExecutionContext(GetExecutionContext),
     Authenticate(Credentials),
+    /// Return the streaming manifests seen on the network
+    StreamingManifests(Sender<Vec<crate::streaming::StreamingManifestRequest>>),
+    /// Return the security header audit of the current navigation
+    SecurityReport(Sender<crate::security::SecurityReport>),
+    /// Return the uncaught javascript errors of the current document
+    JsErrors(Sender<Vec<JsError>>),
+    /// Swap the interception rules of the requests of the page
+    NetworkRules(Option<std::sync::Arc<crate::network_rules::NetworkRules>>),
+    /// Swap the redirect policy of the navigations of the page
+    RedirectPolicy(Option<crate::redirect::RedirectPolicy>),
+    /// Return the redirect hops followed by the latest navigation
+    RedirectChain(Sender<Vec<crate::redirect::RedirectHop>>),
+    /// Return the scope policy of the browser applied to the page
+    ScopePolicy(Sender<Option<std::sync::Arc<crate::policy::ScopePolicy>>>),
+    /// Return whether the device metrics of the page are overridden
+    DeviceMetricsOverride(Sender<bool>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rearms_the_stale_client_redirect_timer() {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let now = Instant::now();
+        let mut timer = None;
+
+        let stale = now + Duration::from_secs(60);
+        assert!(!poll_client_redirect_timer(
+            &mut timer,
+            Some(stale),
+            now,
+            &mut cx
+        ));
+        assert_eq!(timer.as_ref().map(|(deadline, _)| *deadline), Some(stale));
+
+        // a new navigation with an earlier deadline does not wait for the stale timer.
+        let deadline = now + Duration::from_secs(1);
+        poll_client_redirect_timer(&mut timer, Some(deadline), now, &mut cx);
+        assert_eq!(
+            timer.as_ref().map(|(deadline, _)| *deadline),
+            Some(deadline)
+        );
+
+        assert!(!poll_client_redirect_timer(&mut timer, None, now, &mut cx));
+        assert!(timer.is_none());
+    }
 }
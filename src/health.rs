@@ -0,0 +1,164 @@
+use std::path::Path;
+use std::time::Duration;
+
+/// The wait for the handler and the browser to answer a health check.
+pub(crate) const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of the handler reported to a health check.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HandlerHealth {
+    /// The time since the last message of the websocket.
+    pub last_message: Option<Duration>,
+    /// The attached targets.
+    pub targets: usize,
+    /// The attached page targets.
+    pub pages: usize,
+    /// The commands waiting for a response.
+    pub pending_commands: usize,
+    /// The browser is closing.
+    pub closing: bool,
+}
+
+/// The health of a browser, see `Browser::health`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HealthReport {
+    /// The handler answered and the websocket is not closing.
+    pub connected: bool,
+    /// The round trip of a `Browser.getVersion` command in milliseconds, `None` when it failed
+    /// or timed out.
+    pub latency_ms: Option<u64>,
+    /// The time since the last message of the websocket in milliseconds.
+    pub last_event_age_ms: Option<u64>,
+    /// The attached targets.
+    pub open_targets: usize,
+    /// The attached page targets.
+    pub open_pages: usize,
+    /// The commands waiting for a response.
+    pub pending_commands: usize,
+    /// The resident memory of the launched chromium and its child processes, linux only.
+    pub chrome_rss_bytes: Option<u64>,
+    /// The free bytes of the disk of the user data dir, holding the chromium cache.
+    pub disk_free_bytes: Option<u64>,
+}
+
+impl HealthReport {
+    /// The browser is alive, e.g. for a liveness probe.
+    pub fn is_live(&self) -> bool {
+        self.connected
+    }
+
+    /// The browser answers commands, e.g. for a readiness probe.
+    pub fn is_ready(&self) -> bool {
+        self.connected && self.latency_ms.is_some()
+    }
+}
+
+/// The parent pid of a `/proc/{pid}/stat` line, the command name may contain spaces.
+#[cfg(any(target_os = "linux", test))]
+fn parse_ppid(stat: &str) -> Option<u32> {
+    let fields = stat.get(stat.rfind(')')? + 1..)?;
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// The `VmRSS` of a `/proc/{pid}/status` file in bytes.
+#[cfg(any(target_os = "linux", test))]
+fn parse_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// The resident memory of the process and its descendants.
+#[cfg(target_os = "linux")]
+pub(crate) fn process_tree_rss(pid: u32) -> Option<u64> {
+    let mut children: std::collections::HashMap<u32, Vec<u32>> = Default::default();
+
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let child = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            Some(child) => child,
+            _ => continue,
+        };
+
+        if let Some(parent) = std::fs::read_to_string(entry.path().join("stat"))
+            .ok()
+            .and_then(|stat| parse_ppid(&stat))
+        {
+            children.entry(parent).or_default().push(child);
+        }
+    }
+
+    let mut total = None;
+    let mut pending = vec![pid];
+
+    while let Some(pid) = pending.pop() {
+        if let Some(rss) = std::fs::read_to_string(format!("/proc/{pid}/status"))
+            .ok()
+            .and_then(|status| parse_rss(&status))
+        {
+            total = Some(total.unwrap_or_default() + rss);
+        }
+        if let Some(children) = children.get(&pid) {
+            pending.extend(children);
+        }
+    }
+
+    total
+}
+
+/// The resident memory of the process and its descendants.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn process_tree_rss(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// The bytes available to unprivileged users on the disk of the path.
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)]
+pub(crate) fn disk_free(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes the zeroed struct of the valid nul terminated path.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    Some(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+/// The bytes available to unprivileged users on the disk of the path.
+#[cfg(not(unix))]
+pub(crate) fn disk_free(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_files() {
+        assert_eq!(
+            parse_ppid("4242 (chrome (renderer)) S 4000 4242 4242 0 -1"),
+            Some(4000)
+        );
+        assert_eq!(
+            parse_rss("Name:\tchrome\nVmPeak:\t 2048 kB\nVmRSS:\t  1024 kB\n"),
+            Some(1024 * 1024)
+        );
+        assert_eq!(parse_rss("Name:\tkthreadd\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_disk_free() {
+        assert!(disk_free(&std::env::temp_dir()).is_some());
+        assert_eq!(disk_free(Path::new("/does/not/exist")), None);
+    }
+}
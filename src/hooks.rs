@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventRequestWillBeSent, EventResponseReceived, ResourceType,
+};
+use futures::stream::{self, Stream, StreamExt};
+
+use crate::browser::Browser;
+use crate::bulk::{BulkOptions, JobResult};
+use crate::error::Result;
+use crate::page::Page;
+
+/// The error text of a request failed by the client, e.g. the interception blocked it.
+const BLOCKED_BY_CLIENT: &str = "net::ERR_BLOCKED_BY_CLIENT";
+
+/// A response seen by the [`CrawlHooks`] of a page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookResponse {
+    /// The url of the response.
+    pub url: String,
+    /// The HTTP status.
+    pub status: i64,
+    /// The mime type of the body.
+    pub mime_type: String,
+    /// The resource type of the request, e.g. `Document`.
+    pub resource_type: String,
+    /// The response was served from the disk cache.
+    pub from_cache: bool,
+}
+
+/// Callbacks for the lifecycle of a crawled page, so logging, quotas or custom caching plug in
+/// with one object instead of a listener per event. Every callback defaults to a no-op and
+/// runs on the listener task of the page, so it should return quickly.
+///
+/// Install the hooks on a page with [`Page::install_hooks`] or on a bulk job with
+/// [`Browser::process_urls_with_hooks`].
+pub trait CrawlHooks: Send + Sync {
+    /// A document navigation of the page started.
+    fn on_navigation_start(&self, _target_id: &str, _url: &str) {}
+
+    /// A response of the page was received.
+    fn on_response(&self, _target_id: &str, _response: &HookResponse) {}
+
+    /// A request of the page was blocked, by the interception or the browser.
+    fn on_blocked(&self, _target_id: &str, _url: &str, _reason: &str) {}
+
+    /// The job of a bulk url completed on the page.
+    fn on_extracted(&self, _target_id: &str, _url: &str) {}
+
+    /// A document of the page failed to load, or the job of a bulk url failed.
+    fn on_error(&self, _target_id: &str, _url: &str, _error: &str) {}
+}
+
+impl<H: CrawlHooks + ?Sized> CrawlHooks for Arc<H> {
+    fn on_navigation_start(&self, target_id: &str, url: &str) {
+        (**self).on_navigation_start(target_id, url)
+    }
+
+    fn on_response(&self, target_id: &str, response: &HookResponse) {
+        (**self).on_response(target_id, response)
+    }
+
+    fn on_blocked(&self, target_id: &str, url: &str, reason: &str) {
+        (**self).on_blocked(target_id, url, reason)
+    }
+
+    fn on_extracted(&self, target_id: &str, url: &str) {
+        (**self).on_extracted(target_id, url)
+    }
+
+    fn on_error(&self, target_id: &str, url: &str, error: &str) {
+        (**self).on_error(target_id, url, error)
+    }
+}
+
+/// Several hooks, called in order.
+impl CrawlHooks for Vec<Arc<dyn CrawlHooks>> {
+    fn on_navigation_start(&self, target_id: &str, url: &str) {
+        self.iter()
+            .for_each(|hooks| hooks.on_navigation_start(target_id, url))
+    }
+
+    fn on_response(&self, target_id: &str, response: &HookResponse) {
+        self.iter()
+            .for_each(|hooks| hooks.on_response(target_id, response))
+    }
+
+    fn on_blocked(&self, target_id: &str, url: &str, reason: &str) {
+        self.iter()
+            .for_each(|hooks| hooks.on_blocked(target_id, url, reason))
+    }
+
+    fn on_extracted(&self, target_id: &str, url: &str) {
+        self.iter()
+            .for_each(|hooks| hooks.on_extracted(target_id, url))
+    }
+
+    fn on_error(&self, target_id: &str, url: &str, error: &str) {
+        self.iter()
+            .for_each(|hooks| hooks.on_error(target_id, url, error))
+    }
+}
+
+/// The network events of a page driving the hooks.
+enum HookEvent {
+    Request(Arc<EventRequestWillBeSent>),
+    Response(Arc<EventResponseReceived>),
+    Failed(Arc<EventLoadingFailed>),
+}
+
+/// Why a failed request was blocked, `None` when it failed otherwise.
+fn blocked_reason(event: &EventLoadingFailed) -> Option<String> {
+    match &event.blocked_reason {
+        Some(reason) => Some(reason.as_ref().to_string()),
+        None if event.error_text == BLOCKED_BY_CLIENT => Some(event.error_text.clone()),
+        None => None,
+    }
+}
+
+fn dispatch(
+    hooks: &dyn CrawlHooks,
+    target_id: &str,
+    event: HookEvent,
+    urls: &mut HashMap<String, String>,
+) {
+    match event {
+        HookEvent::Request(event) => {
+            urls.insert(event.request_id.inner().clone(), event.request.url.clone());
+
+            // the request of a navigation shares its id with the loader.
+            if event.r#type == Some(ResourceType::Document)
+                && event.request_id.inner() == event.loader_id.inner()
+            {
+                hooks.on_navigation_start(target_id, &event.request.url);
+            }
+        }
+        HookEvent::Response(event) => {
+            urls.remove(event.request_id.inner());
+
+            hooks.on_response(
+                target_id,
+                &HookResponse {
+                    url: event.response.url.clone(),
+                    status: event.response.status,
+                    mime_type: event.response.mime_type.clone(),
+                    resource_type: event.r#type.as_ref().to_string(),
+                    from_cache: event.response.from_disk_cache.unwrap_or_default(),
+                },
+            );
+        }
+        HookEvent::Failed(event) => {
+            let url = urls.remove(event.request_id.inner()).unwrap_or_default();
+
+            if let Some(reason) = blocked_reason(&event) {
+                hooks.on_blocked(target_id, &url, &reason);
+            } else if event.r#type == ResourceType::Document && event.canceled != Some(true) {
+                hooks.on_error(target_id, &url, &event.error_text);
+            }
+        }
+    }
+}
+
+impl Page {
+    /// Drive the hooks from the network events of the page until the page closes or the returned
+    /// handle is aborted. Requires the network domain, enabled by default.
+    pub async fn install_hooks(
+        &self,
+        hooks: Arc<dyn CrawlHooks>,
+    ) -> Result<crate::runtime::JoinHandle<()>> {
+        let requests = self
+            .event_listener::<EventRequestWillBeSent>()
+            .await?
+            .map(HookEvent::Request);
+        let responses = self
+            .event_listener::<EventResponseReceived>()
+            .await?
+            .map(HookEvent::Response);
+        let failures = self
+            .event_listener::<EventLoadingFailed>()
+            .await?
+            .map(HookEvent::Failed);
+
+        let target_id = self.target_id().inner().clone();
+        let mut events = stream::select(requests, stream::select(responses, failures));
+
+        Ok(crate::runtime::spawn(async move {
+            let mut urls = Default::default();
+
+            while let Some(event) = events.next().await {
+                dispatch(&*hooks, &target_id, event, &mut urls);
+            }
+        }))
+    }
+}
+
+impl Browser {
+    /// Process the urls with the options, see [`Browser::process_urls`], driving the hooks
+    /// from every page of the job. `on_extracted` and `on_error` are called with the outcome of
+    /// every attempt.
+    pub fn process_urls_with_hooks<'a, I, T, F, Fut>(
+        &'a self,
+        urls: I,
+        options: BulkOptions,
+        hooks: Arc<dyn CrawlHooks>,
+        job: F,
+    ) -> impl Stream<Item = JobResult<T>> + 'a
+    where
+        I: IntoIterator<Item = String>,
+        I::IntoIter: 'a,
+        T: 'a,
+        F: Fn(Page, String) -> Fut + 'a,
+        Fut: Future<Output = Result<T>> + 'a,
+    {
+        let job = Arc::new(job);
+
+        self.process_urls_with_options(urls, options, move |page: Page, url: String| {
+            let (hooks, job) = (hooks.clone(), job.clone());
+
+            async move {
+                let target_id = page.target_id().inner().clone();
+                let listener = page.install_hooks(hooks.clone()).await.ok();
+
+                let result = job(page, url.clone()).await;
+
+                if let Some(listener) = listener {
+                    listener.abort();
+                }
+
+                match &result {
+                    Ok(_) => hooks.on_extracted(&target_id, &url),
+                    Err(error) => hooks.on_error(&target_id, &url, &error.to_string()),
+                }
+
+                result
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chromiumoxide_cdp::cdp::browser_protocol::network::BlockedReason;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl CrawlHooks for Recorder {
+        fn on_blocked(&self, _target_id: &str, url: &str, reason: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("blocked {url} {reason}"));
+        }
+
+        fn on_error(&self, _target_id: &str, url: &str, error: &str) {
+            self.0.lock().unwrap().push(format!("error {url} {error}"));
+        }
+    }
+
+    fn failed(error_text: &str, blocked: Option<BlockedReason>) -> EventLoadingFailed {
+        serde_json::from_value(serde_json::json!({
+            "requestId": "1",
+            "timestamp": 0.0,
+            "type": "Document",
+            "errorText": error_text,
+            "blockedReason": blocked,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn classifies_failed_requests() {
+        assert_eq!(
+            blocked_reason(&failed("net::ERR_FAILED", Some(BlockedReason::Inspector))).as_deref(),
+            Some("inspector")
+        );
+        assert_eq!(
+            blocked_reason(&failed(BLOCKED_BY_CLIENT, None)).as_deref(),
+            Some(BLOCKED_BY_CLIENT)
+        );
+        assert_eq!(blocked_reason(&failed("net::ERR_TIMED_OUT", None)), None);
+    }
+
+    #[test]
+    fn fans_out_to_every_hook() {
+        let a = Arc::new(Recorder::default());
+        let b = Arc::new(Recorder::default());
+        let hooks: Vec<Arc<dyn CrawlHooks>> = vec![a.clone(), b.clone()];
+
+        let mut urls = HashMap::new();
+        urls.insert("1".to_string(), "https://example.com/".to_string());
+        dispatch(
+            &hooks,
+            "target",
+            HookEvent::Failed(Arc::new(failed(BLOCKED_BY_CLIENT, None))),
+            &mut urls,
+        );
+        dispatch(
+            &hooks,
+            "target",
+            HookEvent::Failed(Arc::new(failed("net::ERR_TIMED_OUT", None))),
+            &mut urls,
+        );
+
+        for recorder in [a, b] {
+            assert_eq!(
+                *recorder.0.lock().unwrap(),
+                vec![
+                    format!("blocked https://example.com/ {BLOCKED_BY_CLIENT}"),
+                    "error  net::ERR_TIMED_OUT".to_string(),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,12 @@
+lazy_static::lazy_static! {
+    /// The client shared by the requests sent outside of the browser, e.g. the link checks, the
+    /// cache warming, the uploads and the webhooks. The timeouts are set per request.
+    pub(crate) static ref HTTP_CLIENT: reqwest::Client = {
+        let builder = reqwest::Client::builder();
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = builder
+            .pool_idle_timeout(std::time::Duration::from_secs(90))
+            .pool_max_idle_per_host(10);
+        builder.build().expect("failed to build HTTP_CLIENT")
+    };
+}
@@ -0,0 +1,366 @@
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+
+/// Extract the icon links and the manifest url of the page.
+pub(crate) const ICON_LINKS_JS: &str = r###"(()=>{const i=[];for(const l of document.querySelectorAll('link[rel][href]')){const r=(l.getAttribute('rel')||'').toLowerCase().split(/\s+/);if(r.includes('icon')||r.includes('apple-touch-icon')||r.includes('apple-touch-icon-precomposed')||r.includes('mask-icon')){i.push({rel:r.join(' '),url:l.href,sizes:l.getAttribute('sizes'),type:l.getAttribute('type')})}}const m=document.querySelector('link[rel~="manifest" i][href]');return{icons:i,manifest:m?m.href:null,url:location.href}})()"###;
+
+/// The icons fetched concurrently.
+const FETCH_CONCURRENCY: usize = 6;
+
+/// The timeout of fetching a manifest or an icon.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where an icon was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconSource {
+    /// A `<link rel="icon">`.
+    Link,
+    /// A `<link rel="apple-touch-icon">`.
+    AppleTouch,
+    /// A `<link rel="mask-icon">` of Safari pinned tabs.
+    MaskIcon,
+    /// The `icons` of the web app manifest.
+    Manifest,
+    /// The `/favicon.ico` of the origin, requested by browsers without an icon link.
+    Default,
+}
+
+/// The format of an icon, sniffed from its bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconFormat {
+    /// A windows icon, possibly holding several sizes.
+    Ico,
+    /// A png image.
+    Png,
+    /// A jpeg image.
+    Jpeg,
+    /// A gif image.
+    Gif,
+    /// A webp image.
+    Webp,
+    /// A vector icon without a pixel size.
+    Svg,
+}
+
+/// An icon of the page.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Icon {
+    /// The absolute url of the icon.
+    pub url: String,
+    /// Where the icon was found.
+    pub source: IconSource,
+    /// The `sizes` declared by the page, e.g. `32x32` or `any`.
+    pub declared_sizes: Option<String>,
+    /// The `type` declared by the page.
+    pub declared_type: Option<String>,
+    /// The `purpose` declared by the manifest, e.g. `maskable`.
+    pub purpose: Option<String>,
+    /// The format of the fetched icon, `None` when it failed to fetch or is not an image.
+    pub format: Option<IconFormat>,
+    /// The decoded width in pixels, the largest image of an ico.
+    pub width: Option<u32>,
+    /// The decoded height in pixels, the largest image of an ico.
+    pub height: Option<u32>,
+    /// The bytes of the icon.
+    pub byte_length: Option<usize>,
+}
+
+impl Icon {
+    fn declared(url: String, source: IconSource) -> Self {
+        Self {
+            url,
+            source,
+            declared_sizes: None,
+            declared_type: None,
+            purpose: None,
+            format: None,
+            width: None,
+            height: None,
+            byte_length: None,
+        }
+    }
+
+    /// The icon was fetched and decoded.
+    pub fn is_valid(&self) -> bool {
+        self.format.is_some()
+    }
+}
+
+/// An icon link of the page.
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct IconLink {
+    rel: String,
+    url: String,
+    sizes: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// The icon links of the page.
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct IconLinks {
+    #[serde(default)]
+    icons: Vec<IconLink>,
+    manifest: Option<String>,
+    url: String,
+}
+
+/// An icon of a web app manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ManifestIcon {
+    /// The url of the icon, relative to the manifest.
+    pub src: String,
+    /// The declared sizes, e.g. `192x192`.
+    #[serde(default)]
+    pub sizes: Option<String>,
+    /// The declared type.
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,
+    /// The purpose, e.g. `maskable`.
+    #[serde(default)]
+    pub purpose: Option<String>,
+}
+
+/// The icons of a web app manifest, resolved against the manifest url.
+pub fn manifest_icons(manifest: &serde_json::Value, manifest_url: &str) -> Vec<Icon> {
+    let base = url::Url::parse(manifest_url).ok();
+
+    manifest
+        .get("icons")
+        .and_then(|icons| serde_json::from_value::<Vec<ManifestIcon>>(icons.clone()).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|icon| {
+            let url = match &base {
+                Some(base) => base.join(&icon.src).ok()?.to_string(),
+                None => url::Url::parse(&icon.src).ok()?.to_string(),
+            };
+
+            Some(Icon {
+                declared_sizes: icon.sizes,
+                declared_type: icon.kind,
+                purpose: icon.purpose,
+                ..Icon::declared(url, IconSource::Manifest)
+            })
+        })
+        .collect()
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+}
+
+/// The format and the pixel size of the image.
+pub fn sniff_image(bytes: &[u8]) -> Option<(IconFormat, Option<(u32, u32)>)> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let size = bytes.get(16..24).map(|ihdr| {
+            (
+                u32::from_be_bytes([ihdr[0], ihdr[1], ihdr[2], ihdr[3]]),
+                u32::from_be_bytes([ihdr[4], ihdr[5], ihdr[6], ihdr[7]]),
+            )
+        });
+        return Some((IconFormat::Png, size));
+    }
+
+    if bytes.starts_with(&[0, 0, 1, 0]) {
+        let count = le_u16(bytes, 4).unwrap_or_default() as usize;
+        let size = (0..count)
+            .filter_map(|i| {
+                let entry = bytes.get(6 + i * 16..8 + i * 16)?;
+                // 0 stands for 256 pixels.
+                let side = |b: u8| if b == 0 { 256 } else { b as u32 };
+                Some((side(entry[0]), side(entry[1])))
+            })
+            .max_by_key(|(w, h)| w * h);
+        return Some((IconFormat::Ico, size));
+    }
+
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        let size = le_u16(bytes, 6).zip(le_u16(bytes, 8));
+        return Some((IconFormat::Gif, size));
+    }
+
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        let mut at = 2;
+        let mut size = None;
+
+        while at + 9 < bytes.len() && bytes[at] == 0xFF {
+            let marker = bytes[at + 1];
+            let length = be_u16(bytes, at + 2)? as usize;
+
+            if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                size = be_u16(bytes, at + 7).zip(be_u16(bytes, at + 5));
+                break;
+            }
+            at += 2 + length;
+        }
+        return Some((IconFormat::Jpeg, size));
+    }
+
+    if bytes.len() >= 16 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        let size = match &bytes[12..16] {
+            b"VP8X" => le_u24(bytes, 24)
+                .zip(le_u24(bytes, 27))
+                .map(|(w, h)| (w + 1, h + 1)),
+            b"VP8L" => bytes.get(21..25).map(|b| {
+                let bits = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                ((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1)
+            }),
+            b"VP8 " => le_u16(bytes, 26)
+                .zip(le_u16(bytes, 28))
+                .map(|(w, h)| (w & 0x3FFF, h & 0x3FFF)),
+            _ => None,
+        };
+        return Some((IconFormat::Webp, size));
+    }
+
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_ascii_lowercase();
+
+    if head.contains("<svg") {
+        return Some((IconFormat::Svg, None));
+    }
+
+    None
+}
+
+/// The icons declared by the page, the default favicon of the origin without an icon link.
+pub(crate) fn link_icons(links: &IconLinks) -> Vec<Icon> {
+    let mut icons: Vec<Icon> = links
+        .icons
+        .iter()
+        .map(|link| {
+            let source = if link.rel.contains("apple-touch-icon") {
+                IconSource::AppleTouch
+            } else if link.rel.contains("mask-icon") {
+                IconSource::MaskIcon
+            } else {
+                IconSource::Link
+            };
+
+            Icon {
+                declared_sizes: link.sizes.clone().filter(|sizes| !sizes.is_empty()),
+                declared_type: link.kind.clone().filter(|kind| !kind.is_empty()),
+                ..Icon::declared(link.url.clone(), source)
+            }
+        })
+        .collect();
+
+    if !icons.iter().any(|icon| icon.source == IconSource::Link) {
+        if let Ok(favicon) = url::Url::parse(&links.url).and_then(|url| url.join("/favicon.ico")) {
+            icons.push(Icon::declared(favicon.to_string(), IconSource::Default));
+        }
+    }
+
+    icons
+}
+
+/// Fetch the manifest icons and every icon, decoding their format and size.
+pub(crate) async fn resolve_icons(links: IconLinks) -> Vec<Icon> {
+    let mut icons = link_icons(&links);
+
+    if let Some(manifest_url) = &links.manifest {
+        if let Some(manifest) = crate::utils::fetch_resource(manifest_url, FETCH_TIMEOUT)
+            .await
+            .and_then(|(body, _)| serde_json::from_slice::<serde_json::Value>(&body).ok())
+        {
+            icons.extend(manifest_icons(&manifest, manifest_url));
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    icons.retain(|icon| seen.insert(icon.url.clone()));
+
+    let mut icons: Vec<Icon> = stream::iter(icons)
+        .map(|mut icon| async move {
+            if let Some((body, _)) = crate::utils::fetch_resource(&icon.url, FETCH_TIMEOUT).await {
+                if let Some((format, size)) = sniff_image(&body) {
+                    icon.format = Some(format);
+                    icon.width = size.map(|(w, _)| w);
+                    icon.height = size.map(|(_, h)| h);
+                }
+                icon.byte_length = Some(body.len());
+            }
+            icon
+        })
+        .buffered(FETCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    // the default favicon only counts when it exists.
+    icons.retain(|icon| icon.source != IconSource::Default || icon.is_valid());
+    icons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_image_sizes() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&32u32.to_be_bytes());
+        png.extend_from_slice(&16u32.to_be_bytes());
+        assert_eq!(sniff_image(&png), Some((IconFormat::Png, Some((32, 16)))));
+
+        let mut ico = vec![0, 0, 1, 0, 2, 0];
+        ico.extend_from_slice(&[16, 16, 0, 0, 1, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        ico.extend_from_slice(&[0, 0, 0, 0, 1, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(sniff_image(&ico), Some((IconFormat::Ico, Some((256, 256)))));
+
+        let gif = b"GIF89a\x30\x00\x18\x00";
+        assert_eq!(sniff_image(gif), Some((IconFormat::Gif, Some((48, 24)))));
+
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00,
+            0x40, 0x00, 0x80, 0x03,
+        ];
+        assert_eq!(
+            sniff_image(&jpeg),
+            Some((IconFormat::Jpeg, Some((128, 64))))
+        );
+
+        assert_eq!(
+            sniff_image(br#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg"/>"#),
+            Some((IconFormat::Svg, None))
+        );
+        assert_eq!(sniff_image(b"<html></html>"), None);
+    }
+
+    #[test]
+    fn resolves_declared_icons() {
+        let links: IconLinks = serde_json::from_value(serde_json::json!({
+            "icons": [
+                { "rel": "apple-touch-icon", "url": "https://example.com/apple.png", "sizes": "180x180", "type": null },
+            ],
+            "manifest": "https://example.com/app/manifest.json",
+            "url": "https://example.com/docs/page",
+        }))
+        .unwrap();
+
+        let icons = link_icons(&links);
+
+        assert_eq!(icons.len(), 2);
+        assert_eq!(icons[0].source, IconSource::AppleTouch);
+        assert_eq!(icons[1].url, "https://example.com/favicon.ico");
+
+        let manifest = serde_json::json!({
+            "icons": [{ "src": "icons/192.png", "sizes": "192x192", "purpose": "maskable" }]
+        });
+        let icons = manifest_icons(&manifest, "https://example.com/app/manifest.json");
+
+        assert_eq!(icons[0].url, "https://example.com/app/icons/192.png");
+        assert_eq!(icons[0].purpose.as_deref(), Some("maskable"));
+    }
+}
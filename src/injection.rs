@@ -0,0 +1,157 @@
+use chromiumoxide_cdp::cdp::browser_protocol::browser::BrowserContextId;
+use chromiumoxide_cdp::cdp::browser_protocol::page::AddScriptToEvaluateOnNewDocumentParams;
+use chromiumoxide_types::{Method, MethodId};
+
+/// Which browser contexts a registered init script is injected in.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum InjectionScope {
+    /// Inject in every page of every context.
+    #[default]
+    All,
+    /// Only inject in the pages of these contexts.
+    Contexts(Vec<BrowserContextId>),
+    /// Inject everywhere except the pages of these contexts.
+    ExceptContexts(Vec<BrowserContextId>),
+}
+
+impl InjectionScope {
+    /// Does the scope include the context? `None` is the default browser context.
+    pub fn applies_to(&self, context: Option<&BrowserContextId>) -> bool {
+        match self {
+            InjectionScope::All => true,
+            InjectionScope::Contexts(ids) => context.map_or(false, |c| ids.contains(c)),
+            InjectionScope::ExceptContexts(ids) => context.map_or(true, |c| !ids.contains(c)),
+        }
+    }
+}
+
+/// A named script injected on every new document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitScript {
+    /// The unique name of the script.
+    pub name: String,
+    /// The javascript source.
+    pub source: String,
+    /// The contexts the script is injected in.
+    pub scope: InjectionScope,
+}
+
+impl InitScript {
+    /// The `Page.addScriptToEvaluateOnNewDocument` command of the script.
+    pub(crate) fn command(&self) -> (MethodId, serde_json::Value) {
+        let cmd = AddScriptToEvaluateOnNewDocumentParams::new(self.source.clone());
+        (
+            cmd.identifier(),
+            serde_json::to_value(cmd).unwrap_or_default(),
+        )
+    }
+}
+
+/// The registered init scripts in injection order.
+#[derive(Debug, Default, Clone)]
+pub struct InitScriptRegistry {
+    scripts: Vec<InitScript>,
+}
+
+impl InitScriptRegistry {
+    /// Register a script. A script with the same name is replaced in place keeping its order.
+    pub fn register(&mut self, script: InitScript) {
+        match self.scripts.iter_mut().find(|s| s.name == script.name) {
+            Some(existing) => *existing = script,
+            _ => self.scripts.push(script),
+        }
+    }
+
+    /// Remove a script by name.
+    pub fn unregister(&mut self, name: &str) -> Option<InitScript> {
+        let idx = self.scripts.iter().position(|s| s.name == name)?;
+        Some(self.scripts.remove(idx))
+    }
+
+    /// The scripts that apply to the context in order.
+    pub fn for_context(&self, context: Option<&BrowserContextId>) -> Vec<InitScript> {
+        self.scripts
+            .iter()
+            .filter(|s| s.scope.applies_to(context))
+            .cloned()
+            .collect()
+    }
+
+    /// All the registered scripts in order.
+    pub fn scripts(&self) -> &[InitScript] {
+        &self.scripts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(name: &str, source: &str, scope: InjectionScope) -> InitScript {
+        InitScript {
+            name: name.into(),
+            source: source.into(),
+            scope,
+        }
+    }
+
+    fn sources(scripts: &[InitScript]) -> Vec<&str> {
+        scripts.iter().map(|s| s.source.as_str()).collect()
+    }
+
+    #[test]
+    fn registers_in_order_and_unregisters() {
+        let mut registry = InitScriptRegistry::default();
+        registry.register(script("a", "1", InjectionScope::All));
+        registry.register(script("b", "2", InjectionScope::All));
+        registry.register(script("c", "3", InjectionScope::All));
+
+        assert_eq!(sources(registry.scripts()), ["1", "2", "3"]);
+
+        let removed = registry.unregister("b").unwrap();
+        assert_eq!(removed.source, "2");
+        assert!(registry.unregister("b").is_none());
+        assert_eq!(sources(registry.scripts()), ["1", "3"]);
+    }
+
+    #[test]
+    fn replaces_the_duplicate_in_place() {
+        let mut registry = InitScriptRegistry::default();
+        registry.register(script("a", "1", InjectionScope::All));
+        registry.register(script("b", "2", InjectionScope::All));
+        registry.register(script("a", "1'", InjectionScope::All));
+
+        assert_eq!(sources(registry.scripts()), ["1'", "2"]);
+    }
+
+    #[test]
+    fn filters_the_scripts_of_the_context() {
+        let ctx = BrowserContextId::new("ctx");
+        let other = BrowserContextId::new("other");
+
+        let mut registry = InitScriptRegistry::default();
+        registry.register(script("all", "1", InjectionScope::All));
+        registry.register(script(
+            "only",
+            "2",
+            InjectionScope::Contexts(vec![ctx.clone()]),
+        ));
+        registry.register(script(
+            "except",
+            "3",
+            InjectionScope::ExceptContexts(vec![ctx.clone()]),
+        ));
+
+        assert_eq!(sources(&registry.for_context(None)), ["1", "3"]);
+        assert_eq!(sources(&registry.for_context(Some(&ctx))), ["1", "2"]);
+        assert_eq!(sources(&registry.for_context(Some(&other))), ["1", "3"]);
+    }
+
+    #[test]
+    fn builds_the_new_document_command() {
+        let (method, params) = script("a", "window.a = 1", InjectionScope::All).command();
+
+        assert_eq!(method, "Page.addScriptToEvaluateOnNewDocument");
+        assert_eq!(params["source"], "window.a = 1");
+    }
+}
@@ -0,0 +1,683 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use base64::{engine::general_purpose, Engine as _};
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::{
+    ContinueRequestParams, DisableParams, EnableParams, EventRequestPaused, FailRequestParams,
+    FulfillRequestParams, HeaderEntry, RequestId, RequestPattern, RequestStage,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::network::{ErrorReason, ResourceType};
+use chromiumoxide_cdp::cdp::browser_protocol::page::FrameId;
+use futures::{FutureExt, StreamExt};
+
+use crate::error::Result;
+use crate::handler::network::is_redirect_status;
+use crate::page::Page;
+use crate::runtime::JoinHandle;
+
+/// The response headers describing the encoded body, dropped when fulfilling since the body
+/// is sent decoded.
+const BODY_ENCODING_HEADERS: [&str; 3] =
+    ["content-encoding", "content-length", "transfer-encoding"];
+
+/// A paused request handed to the callback of `Page::on_request`. Change the url, the method,
+/// the headers or the post data and answer with `Decision::ContinueWith` to send it modified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterceptedRequest {
+    /// The id of the paused request.
+    pub request_id: RequestId,
+    /// The url of the request.
+    pub url: String,
+    /// The method of the request.
+    pub method: String,
+    /// The headers of the request, in the sent order.
+    pub headers: Vec<(String, String)>,
+    /// The body of the request.
+    pub post_data: Option<Vec<u8>>,
+    /// The resource type of the request.
+    pub resource_type: ResourceType,
+    /// The frame of the request.
+    pub frame_id: FrameId,
+}
+
+impl InterceptedRequest {
+    /// The request of the paused event.
+    pub fn from_event(event: &EventRequestPaused) -> Self {
+        let headers = event
+            .request
+            .headers
+            .inner()
+            .as_object()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = value
+                            .as_str()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| value.to_string());
+                        (name.clone(), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let post_data = event.request.post_data_entries.as_ref().map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.bytes.as_ref())
+                .filter_map(|bytes| {
+                    general_purpose::STANDARD
+                        .decode(AsRef::<str>::as_ref(bytes))
+                        .ok()
+                })
+                .flatten()
+                .collect()
+        });
+
+        Self {
+            request_id: event.request_id.clone(),
+            url: event.request.url.clone(),
+            method: event.request.method.clone(),
+            headers,
+            post_data,
+            resource_type: event.resource_type.clone(),
+            frame_id: event.frame_id.clone(),
+        }
+    }
+
+    /// The value of a header by case insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Set a header, replacing the value of a header of the same case insensitive name.
+    pub fn set_header(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let (name, value) = (name.into(), value.into());
+
+        match self
+            .headers
+            .iter_mut()
+            .find(|(n, _)| n.eq_ignore_ascii_case(&name))
+        {
+            Some(header) => header.1 = value,
+            _ => self.headers.push((name, value)),
+        }
+    }
+
+    /// Remove a header by case insensitive name.
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+
+    /// The request is a navigation of a frame.
+    pub fn is_navigation(&self) -> bool {
+        self.resource_type == ResourceType::Document
+    }
+
+    /// The command continuing the paused request with the changes of `self` over `original`,
+    /// only the changed parts are overridden.
+    fn continue_params(self, original: &InterceptedRequest) -> ContinueRequestParams {
+        let mut params = ContinueRequestParams::new(self.request_id.clone());
+
+        if self.url != original.url {
+            params.url = Some(self.url);
+        }
+        if self.method != original.method {
+            params.method = Some(self.method);
+        }
+        if self.post_data != original.post_data {
+            params.post_data = Some(
+                general_purpose::STANDARD
+                    .encode(self.post_data.unwrap_or_default())
+                    .into(),
+            );
+        }
+        if self.headers != original.headers {
+            params.headers = Some(
+                self.headers
+                    .into_iter()
+                    .map(|(name, value)| HeaderEntry { name, value })
+                    .collect(),
+            );
+        }
+
+        params
+    }
+}
+
+/// The response fulfilling an intercepted request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterceptedResponse {
+    /// The status of the response.
+    pub status: u16,
+    /// The headers of the response.
+    pub headers: Vec<(String, String)>,
+    /// The body of the response.
+    pub body: Vec<u8>,
+}
+
+impl InterceptedResponse {
+    /// An empty response with the status.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A `200` response of the json value.
+    pub fn json(value: &serde_json::Value) -> Self {
+        Self::new(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(value.to_string())
+    }
+
+    /// Add a header.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the body.
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// The value of a header by case insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Remove a header by case insensitive name, e.g. `Content-Security-Policy`.
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+
+    /// The body as text, lossy.
+    pub fn text(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    /// The response paused at the response stage with its body.
+    fn from_event(event: &EventRequestPaused, body: Vec<u8>) -> Self {
+        Self {
+            status: event.response_status_code.unwrap_or(200) as u16,
+            headers: event
+                .response_headers
+                .iter()
+                .flatten()
+                .map(|header| (header.name.clone(), header.value.clone()))
+                .collect(),
+            body,
+        }
+    }
+
+    /// The command fulfilling the paused request with the response.
+    fn fulfill_params(self, request_id: RequestId) -> FulfillRequestParams {
+        let mut params = FulfillRequestParams::new(request_id, self.status as i64);
+        params.response_headers = Some(
+            self.headers
+                .into_iter()
+                .filter(|(name, _)| {
+                    !BODY_ENCODING_HEADERS
+                        .iter()
+                        .any(|header| header.eq_ignore_ascii_case(name))
+                })
+                .map(|(name, value)| HeaderEntry { name, value })
+                .collect(),
+        );
+        params.body = Some(general_purpose::STANDARD.encode(self.body).into());
+        params
+    }
+}
+
+/// What is done with an intercepted request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Continue the request unchanged.
+    Continue,
+    /// Continue the request with the changes made to it.
+    ContinueWith(InterceptedRequest),
+    /// Answer the request without hitting the network.
+    Fulfill(InterceptedResponse),
+    /// Fail the request with the reason.
+    Abort(ErrorReason),
+}
+
+impl Decision {
+    /// The command answering the paused request.
+    fn into_command(self, original: &InterceptedRequest) -> Command {
+        match self {
+            Self::Continue => {
+                Command::Continue(ContinueRequestParams::new(original.request_id.clone()))
+            }
+            Self::ContinueWith(request) => Command::Continue(request.continue_params(original)),
+            Self::Fulfill(response) => {
+                Command::Fulfill(response.fulfill_params(original.request_id.clone()))
+            }
+            Self::Abort(reason) => {
+                Command::Fail(FailRequestParams::new(original.request_id.clone(), reason))
+            }
+        }
+    }
+}
+
+/// What is done with an intercepted response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseDecision {
+    /// Pass the response to the page unchanged.
+    Continue,
+    /// Pass the rewritten response to the page.
+    Fulfill(InterceptedResponse),
+    /// Fail the request with the reason.
+    Abort(ErrorReason),
+}
+
+impl ResponseDecision {
+    /// The command answering the paused response. The body was taken, an unchanged response is
+    /// fulfilled with the original.
+    fn into_command(self, request_id: RequestId, original: InterceptedResponse) -> Command {
+        match self {
+            Self::Continue => Command::Fulfill(original.fulfill_params(request_id)),
+            Self::Fulfill(response) => Command::Fulfill(response.fulfill_params(request_id)),
+            Self::Abort(reason) => Command::Fail(FailRequestParams::new(request_id, reason)),
+        }
+    }
+}
+
+/// The Fetch command of a decision.
+#[derive(Debug)]
+enum Command {
+    Continue(ContinueRequestParams),
+    Fulfill(FulfillRequestParams),
+    Fail(FailRequestParams),
+}
+
+/// Runs the callback of `Page::on_request` or `Page::on_response` until stopped.
+#[derive(Debug)]
+pub struct RequestInterceptor {
+    page: Page,
+    handle: JoinHandle<()>,
+}
+
+impl RequestInterceptor {
+    /// Stop intercepting, the requests go to the network untouched again.
+    pub async fn stop(self) -> Result<()> {
+        self.handle.abort();
+        self.page.execute(DisableParams::default()).await?;
+        Ok(())
+    }
+}
+
+/// Decide on a paused request, a panicking callback continues the request unchanged.
+async fn decide<F, Fut>(callback: &F, request: InterceptedRequest) -> Decision
+where
+    F: Fn(InterceptedRequest) -> Fut,
+    Fut: Future<Output = Decision>,
+{
+    let url = request.url.clone();
+
+    match AssertUnwindSafe(async { callback(request).await })
+        .catch_unwind()
+        .await
+    {
+        Ok(decision) => decision,
+        Err(_) => {
+            tracing::error!("request interception callback panicked for {url}");
+            Decision::Continue
+        }
+    }
+}
+
+/// Rewrite a paused response, a panicking callback passes the response unchanged.
+async fn rewrite<F, Fut>(
+    callback: &F,
+    request: InterceptedRequest,
+    response: InterceptedResponse,
+) -> ResponseDecision
+where
+    F: Fn(InterceptedRequest, InterceptedResponse) -> Fut,
+    Fut: Future<Output = ResponseDecision>,
+{
+    let url = request.url.clone();
+
+    match AssertUnwindSafe(async { callback(request, response).await })
+        .catch_unwind()
+        .await
+    {
+        Ok(decision) => decision,
+        Err(_) => {
+            tracing::error!("response interception callback panicked for {url}");
+            ResponseDecision::Continue
+        }
+    }
+}
+
+/// Send the command answering a paused request.
+async fn answer(page: &Page, command: Command) -> Result<()> {
+    match command {
+        Command::Continue(params) => page.send_command(params).await.map(|_| ()),
+        Command::Fulfill(params) => page.send_command(params).await.map(|_| ()),
+        Command::Fail(params) => page.send_command(params).await.map(|_| ()),
+    }
+}
+
+impl Page {
+    /// Pause every request of the page on the `Fetch` domain and let the async callback continue,
+    /// modify, fulfill or abort it, e.g. to mock an api, inject auth headers or short-circuit
+    /// trackers. The requests are decided concurrently.
+    ///
+    /// This replaces the patterns of other request interception on the page, disable the request
+    /// interception of the browser for the page while the callback runs.
+    pub async fn on_request<F, Fut>(&self, callback: F) -> Result<RequestInterceptor>
+    where
+        F: Fn(InterceptedRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Decision> + Send + 'static,
+    {
+        let mut events = self.event_listener::<EventRequestPaused>().await?;
+
+        self.execute(EnableParams {
+            patterns: Some(vec![RequestPattern {
+                url_pattern: Some("*".into()),
+                resource_type: None,
+                request_stage: Some(RequestStage::Request),
+            }]),
+            handle_auth_requests: None,
+        })
+        .await?;
+
+        let page = self.clone();
+        let callback = Arc::new(callback);
+
+        let handle = crate::runtime::spawn(async move {
+            while let Some(event) = events.next().await {
+                let page = page.clone();
+                let callback = callback.clone();
+
+                crate::runtime::spawn(async move {
+                    let request = InterceptedRequest::from_event(&event);
+                    let decision = decide(callback.as_ref(), request.clone()).await;
+
+                    if let Err(err) = answer(&page, decision.into_command(&request)).await {
+                        tracing::debug!("failed to answer the request {}: {err}", request.url);
+                    }
+                });
+            }
+        });
+
+        Ok(RequestInterceptor {
+            page: self.clone(),
+            handle,
+        })
+    }
+
+    /// Pause the responses of the requests matching the url pattern of the `Fetch` domain, `*`
+    /// for every request, and let the async callback rewrite them before the page sees them,
+    /// e.g. to strip a `Content-Security-Policy`, inject a script tag or patch a json api.
+    ///
+    /// The body is read in chunks with `Fetch.takeResponseBodyAsStream` and handed decoded to the
+    /// callback, the request is then fulfilled with the response of the callback. The redirects
+    /// and the failed requests continue untouched. This replaces the patterns of other request
+    /// interception on the page.
+    pub async fn on_response<F, Fut>(
+        &self,
+        url_pattern: impl Into<String>,
+        callback: F,
+    ) -> Result<RequestInterceptor>
+    where
+        F: Fn(InterceptedRequest, InterceptedResponse) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ResponseDecision> + Send + 'static,
+    {
+        let mut events = self.event_listener::<EventRequestPaused>().await?;
+
+        self.execute(EnableParams {
+            patterns: Some(vec![RequestPattern {
+                url_pattern: Some(url_pattern.into()),
+                resource_type: None,
+                request_stage: Some(RequestStage::Response),
+            }]),
+            handle_auth_requests: None,
+        })
+        .await?;
+
+        let page = self.clone();
+        let callback = Arc::new(callback);
+
+        let handle = crate::runtime::spawn(async move {
+            while let Some(event) = events.next().await {
+                let page = page.clone();
+                let callback = callback.clone();
+
+                crate::runtime::spawn(async move {
+                    let request = InterceptedRequest::from_event(&event);
+                    let request_id = event.request_id.clone();
+
+                    let status = match event.response_status_code {
+                        Some(status)
+                            if event.response_error_reason.is_none()
+                                && !is_redirect_status(status) =>
+                        {
+                            status
+                        }
+                        _ => {
+                            let command = Command::Continue(ContinueRequestParams::new(request_id));
+                            if let Err(err) = answer(&page, command).await {
+                                tracing::debug!("failed to continue {}: {err}", request.url);
+                            }
+                            return;
+                        }
+                    };
+
+                    let body = match page.response_body_stream(request_id.clone()).await {
+                        Ok(stream) => stream.read_to_end().await,
+                        Err(err) => Err(err),
+                    };
+
+                    let command = match body {
+                        Ok(body) => {
+                            let response = InterceptedResponse::from_event(&event, body);
+                            rewrite(callback.as_ref(), request.clone(), response.clone())
+                                .await
+                                .into_command(request_id, response)
+                        }
+                        Err(err) => {
+                            // an empty body, e.g. of a `204`, can not be taken.
+                            tracing::debug!(
+                                "failed to read the body {status} of {}: {err}",
+                                request.url
+                            );
+                            Command::Continue(ContinueRequestParams::new(request_id))
+                        }
+                    };
+
+                    if let Err(err) = answer(&page, command).await {
+                        tracing::debug!("failed to answer the response {}: {err}", request.url);
+                    }
+                });
+            }
+        });
+
+        Ok(RequestInterceptor {
+            page: self.clone(),
+            handle,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> InterceptedRequest {
+        InterceptedRequest {
+            request_id: RequestId::new("interception-job-1.0"),
+            url: "https://api.example.com/items".into(),
+            method: "GET".into(),
+            headers: vec![("Accept".into(), "*/*".into())],
+            post_data: None,
+            resource_type: ResourceType::Fetch,
+            frame_id: FrameId::new("frame"),
+        }
+    }
+
+    #[test]
+    fn overrides_the_changed_parts_only() {
+        let original = request();
+        let mut changed = original.clone();
+
+        changed.set_header("authorization", "Bearer token");
+        changed.set_header("accept", "application/json");
+
+        let Command::Continue(params) = Decision::ContinueWith(changed).into_command(&original)
+        else {
+            panic!("expected a continue");
+        };
+
+        assert_eq!(params.url, None);
+        assert_eq!(params.method, None);
+        assert_eq!(params.post_data, None);
+        assert_eq!(
+            params.headers,
+            Some(vec![
+                HeaderEntry::new("Accept", "application/json"),
+                HeaderEntry::new("authorization", "Bearer token"),
+            ])
+        );
+
+        let mut changed = original.clone();
+        changed.method = "POST".into();
+        changed.post_data = Some(b"{}".to_vec());
+        changed.remove_header("ACCEPT");
+
+        let params = changed.continue_params(&original);
+
+        assert_eq!(params.method.as_deref(), Some("POST"));
+        assert_eq!(
+            params
+                .post_data
+                .map(|data| AsRef::<str>::as_ref(&data).to_string()),
+            Some("e30=".into())
+        );
+        assert_eq!(params.headers, Some(Vec::new()));
+    }
+
+    #[test]
+    fn fulfills_and_aborts() {
+        let original = request();
+
+        let Command::Fulfill(params) =
+            Decision::Fulfill(InterceptedResponse::json(&serde_json::json!({"items": []})))
+                .into_command(&original)
+        else {
+            panic!("expected a fulfill");
+        };
+
+        assert_eq!(params.response_code, 200);
+        assert_eq!(
+            params.response_headers,
+            Some(vec![HeaderEntry::new("Content-Type", "application/json")])
+        );
+
+        assert!(matches!(
+            Decision::Abort(ErrorReason::BlockedByClient).into_command(&original),
+            Command::Fail(_)
+        ));
+    }
+
+    #[test]
+    fn fulfills_the_rewritten_responses_decoded() {
+        let original = InterceptedResponse::new(200)
+            .with_header("Content-Encoding", "gzip")
+            .with_header("Content-Security-Policy", "script-src 'self'")
+            .with_header("Content-Type", "text/html")
+            .with_body("<head></head>");
+
+        let Command::Fulfill(params) = ResponseDecision::Continue
+            .into_command(RequestId::new("interception-job-2.0"), original.clone())
+        else {
+            panic!("expected a fulfill");
+        };
+
+        assert_eq!(
+            params.response_headers,
+            Some(vec![
+                HeaderEntry::new("Content-Security-Policy", "script-src 'self'"),
+                HeaderEntry::new("Content-Type", "text/html"),
+            ])
+        );
+
+        let mut rewritten = original.clone();
+        rewritten.remove_header("content-security-policy");
+        rewritten.body = rewritten
+            .text()
+            .replace("<head>", "<head><script>window.patched=true</script>")
+            .into();
+
+        assert_eq!(rewritten.header("content-type"), Some("text/html"));
+
+        let Command::Fulfill(params) = ResponseDecision::Fulfill(rewritten)
+            .into_command(RequestId::new("interception-job-2.0"), original)
+        else {
+            panic!("expected a fulfill");
+        };
+
+        assert_eq!(params.response_code, 200);
+        assert_eq!(
+            params.response_headers,
+            Some(vec![HeaderEntry::new("Content-Type", "text/html")])
+        );
+        assert_eq!(
+            params
+                .body
+                .map(|body| AsRef::<str>::as_ref(&body).to_string()),
+            Some(
+                general_purpose::STANDARD
+                    .encode("<head><script>window.patched=true</script></head>")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn passes_the_response_on_a_panicking_callback() {
+        let decision = rewrite(
+            &|_: InterceptedRequest, response: InterceptedResponse| async move {
+                if response.status == 200 {
+                    panic!("callback bug")
+                }
+                ResponseDecision::Abort(ErrorReason::Failed)
+            },
+            request(),
+            InterceptedResponse::new(200),
+        )
+        .await;
+
+        assert_eq!(decision, ResponseDecision::Continue);
+    }
+
+    #[tokio::test]
+    async fn continues_on_a_panicking_callback() {
+        let decision = decide(
+            &|request: InterceptedRequest| async move {
+                if request.is_navigation() {
+                    Decision::Abort(ErrorReason::Aborted)
+                } else {
+                    panic!("callback bug")
+                }
+            },
+            request(),
+        )
+        .await;
+
+        assert_eq!(decision, Decision::Continue);
+    }
+}
@@ -0,0 +1,119 @@
+use chromiumoxide_cdp::cdp::browser_protocol::emulation::{
+    SetLocaleOverrideParams, SetTimezoneOverrideParams,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::page::AddScriptToEvaluateOnNewDocumentParams;
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::page::Page;
+
+/// Pin the defaults of the `Intl` formatters and the `toLocale*String` methods: the locale, the
+/// timezone, the hour cycle and the week info of `Intl.Locale`. `__INTL__` is the json options.
+const PIN_INTL_JS: &str = r###"(()=>{if(window.__chromeyIntl)return;window.__chromeyIntl=1;const o=__INTL__;const I=window.Intl;if(!I)return;const wrap=(C,dt)=>{if(typeof C!=='function')return C;const W=function(l,p){p=Object.assign({},p);if(dt&&o.timeZone&&p.timeZone===undefined)p.timeZone=o.timeZone;if(dt&&o.hourCycle&&p.hourCycle===undefined&&p.hour12===undefined)p.hourCycle=o.hourCycle;return new.target?Reflect.construct(C,[l===undefined?o.locale:l,p],new.target):C(l===undefined?o.locale:l,p)};W.prototype=C.prototype;W.supportedLocalesOf=C.supportedLocalesOf;Object.defineProperty(W,'name',{value:C.name});return W};for(const n of['NumberFormat','Collator','PluralRules','RelativeTimeFormat','ListFormat','DisplayNames','Segmenter'])if(I[n])I[n]=wrap(I[n],false);I.DateTimeFormat=wrap(I.DateTimeFormat,true);const loc=(P,m,dt)=>{const f=P[m];if(typeof f!=='function')return;P[m]=function(l,p){p=Object.assign({},p);if(dt&&o.timeZone&&p.timeZone===undefined)p.timeZone=o.timeZone;if(dt&&o.hourCycle&&p.hourCycle===undefined&&p.hour12===undefined)p.hourCycle=o.hourCycle;return f.call(this,l===undefined?o.locale:l,p)}};loc(Number.prototype,'toLocaleString',false);if(window.BigInt)loc(BigInt.prototype,'toLocaleString',false);for(const m of['toLocaleString','toLocaleDateString','toLocaleTimeString'])loc(Date.prototype,m,true);if(o.firstDay&&I.Locale){const L=I.Locale.prototype;const w=function(){return{firstDay:o.firstDay,weekend:o.firstDay===7?[5,6]:[6,7],minimalDays:o.minimalDays||1}};try{Object.defineProperty(L,'weekInfo',{get:w,configurable:true})}catch(e){}L.getWeekInfo=w}})()"###;
+
+/// The pinned outputs of the ICU dependent apis, so formatted numbers and dates extract the same
+/// on every host regardless of its system locale and timezone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IntlOptions {
+    /// The default locale, e.g. `en-US`.
+    pub locale: String,
+    /// The default IANA timezone, e.g. `UTC`.
+    #[serde(rename = "timeZone")]
+    pub timezone: String,
+    /// The first day of the week of `Intl.Locale`, `1` for monday to `7` for sunday.
+    #[serde(rename = "firstDay", skip_serializing_if = "Option::is_none")]
+    pub first_day_of_week: Option<u8>,
+    /// The minimal days of the first week of the year of `Intl.Locale`.
+    #[serde(rename = "minimalDays", skip_serializing_if = "Option::is_none")]
+    pub minimal_days: Option<u8>,
+    /// The default hour cycle of the date formatting, e.g. `h23`.
+    #[serde(rename = "hourCycle", skip_serializing_if = "Option::is_none")]
+    pub hour_cycle: Option<String>,
+}
+
+impl Default for IntlOptions {
+    fn default() -> Self {
+        Self {
+            locale: "en-US".into(),
+            timezone: "UTC".into(),
+            first_day_of_week: None,
+            minimal_days: None,
+            hour_cycle: None,
+        }
+    }
+}
+
+impl IntlOptions {
+    /// Pin the locale and the timezone.
+    pub fn new(locale: impl Into<String>, timezone: impl Into<String>) -> Self {
+        Self {
+            locale: locale.into(),
+            timezone: timezone.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Pin the first day of the week, `1` for monday to `7` for sunday.
+    pub fn with_first_day_of_week(mut self, day: u8) -> Self {
+        self.first_day_of_week = Some(day.clamp(1, 7));
+        self
+    }
+
+    /// Pin the default hour cycle, e.g. `h23` or `h12`.
+    pub fn with_hour_cycle(mut self, hour_cycle: impl Into<String>) -> Self {
+        self.hour_cycle = Some(hour_cycle.into());
+        self
+    }
+
+    /// The script pinning the defaults of the page.
+    pub fn script(&self) -> String {
+        let options = serde_json::to_string(self).unwrap_or_else(|_| "{}".into());
+
+        PIN_INTL_JS.replacen("__INTL__", &options, 1)
+    }
+}
+
+impl Page {
+    /// Pin the locale, the timezone and the week info of the page, on the current and every new
+    /// document.
+    pub async fn pin_intl(&self, options: &IntlOptions) -> Result<&Self> {
+        let mut locale = SetLocaleOverrideParams::default();
+        locale.locale = Some(options.locale.clone());
+        self.execute(locale).await?;
+
+        self.execute(SetTimezoneOverrideParams::new(options.timezone.clone()))
+            .await?;
+
+        let script = options.script();
+
+        self.send_command(AddScriptToEvaluateOnNewDocumentParams::new(script.clone()))
+            .await?;
+        self.evaluate_expression(script).await?;
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_the_options() {
+        let script = IntlOptions::new("de-DE", "Europe/Berlin")
+            .with_first_day_of_week(9)
+            .with_hour_cycle("h23")
+            .script();
+
+        assert!(script.contains(
+            r#"const o={"locale":"de-DE","timeZone":"Europe/Berlin","firstDay":7,"hourCycle":"h23"};"#
+        ));
+    }
+
+    #[test]
+    fn defaults_to_utc() {
+        assert!(IntlOptions::default()
+            .script()
+            .contains(r#"const o={"locale":"en-US","timeZone":"UTC"};"#));
+    }
+}
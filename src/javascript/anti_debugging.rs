@@ -0,0 +1,11 @@
+/// Stop devtools detection through the console. The protocol serializes logged values, so errors with
+/// a `stack` getter or nodes with an `id` getter tell the page a client is attached. Logged errors and
+/// nodes are replaced with a plain description before they reach the console.
+///
+/// The `debugger` statements are not shimmed: they only pause with an enabled debugger, which
+/// the anti-debugging mode leaves disabled unless the page asks for it.
+pub(crate) const ANTI_DEBUGGING_JS: &str = r###"(()=>{const c=window.console;if(!c)return;const d=a=>{try{if(a instanceof Error)return Object.prototype.toString.call(a);if(typeof Node!=='undefined'&&a instanceof Node)return '[object '+a.nodeName+']'}catch(e){return '[object]'}return a};for(const m of['log','debug','info','warn','error','dir','dirxml','table','trace','group','groupCollapsed']){const f=c[m];if(typeof f!=='function')continue;c[m]=new Proxy(f,{apply(t,s,args){return Reflect.apply(t,s,args.map(d))}})}})()"###;
+
+/// Freeze the builtin prototypes of the helper world so helper scripts always run against the
+/// original builtins.
+pub(crate) const FREEZE_PROTOTYPES_JS: &str = r###"(()=>{for(const o of[Object,Object.prototype,Array,Array.prototype,Function.prototype,String.prototype,Promise,Promise.prototype,JSON,Reflect]){try{Object.freeze(o)}catch(e){}}})()"###;
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::error::{CdpError, Result};
+
+/// Turns a local entry file and its imports into a single script ready for injection.
+pub trait ScriptBundler: Send + Sync {
+    /// Bundle the entry file without blocking the runtime.
+    fn bundle<'a>(&'a self, entry: &'a Path) -> BoxFuture<'a, Result<String>>;
+}
+
+/// A closure bundles on the task awaiting it, it should be quick or hand its work to a
+/// blocking thread itself.
+impl<F> ScriptBundler for F
+where
+    F: Fn(&Path) -> Result<String> + Send + Sync,
+{
+    fn bundle<'a>(&'a self, entry: &'a Path) -> BoxFuture<'a, Result<String>> {
+        futures::future::ready(self(entry)).boxed()
+    }
+}
+
+/// Inline the relative ES module imports of the entry into a single IIFE.
+///
+/// The modules share one scope: named imports map to the exported names, `export default` and
+/// `as` renames are rewritten to constants. Bare specifiers, namespace imports and re-exports are
+/// not supported and TypeScript is not transpiled, use a [`CommandBundler`] for those.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InlineBundler;
+
+impl InlineBundler {
+    /// Bundle the entry file, reading the modules with blocking calls.
+    pub fn bundle_blocking(&self, entry: &Path) -> Result<String> {
+        let mut state = InlineState::default();
+        state.visit(entry)?;
+        Ok(format!("(()=>{{\n{}}})();", state.output.concat()))
+    }
+}
+
+impl ScriptBundler for InlineBundler {
+    fn bundle<'a>(&'a self, entry: &'a Path) -> BoxFuture<'a, Result<String>> {
+        let (bundler, entry) = (*self, entry.to_path_buf());
+        crate::runtime::unblock(move || bundler.bundle_blocking(&entry)).boxed()
+    }
+}
+
+/// Bundle with an external program that prints the bundle to stdout, the entry path is
+/// appended to the arguments, e.g. `esbuild --bundle --format=iife`. The program runs as an
+/// async child process, killed when the bundling is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct CommandBundler {
+    /// The program to run.
+    pub program: String,
+    /// The arguments passed before the entry path.
+    pub args: Vec<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CommandBundler {
+    /// A new bundler running the program.
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Add an argument.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Bundle with `esbuild --bundle --format=iife`, which also handles TypeScript.
+    pub fn esbuild() -> Self {
+        Self::new("esbuild").arg("--bundle").arg("--format=iife")
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ScriptBundler for CommandBundler {
+    fn bundle<'a>(&'a self, entry: &'a Path) -> BoxFuture<'a, Result<String>> {
+        async move {
+            let output = crate::async_process::Command::new(&self.program)
+                .args(&self.args)
+                .arg(entry)
+                .output()
+                .await?;
+
+            if !output.status.success() {
+                return Err(CdpError::msg(format!(
+                    "{} failed to bundle {}: {}",
+                    self.program,
+                    entry.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+
+            String::from_utf8(output.stdout).map_err(|e| CdpError::msg(e.to_string()))
+        }
+        .boxed()
+    }
+}
+
+/// The name of the constant holding the default export of a module.
+fn default_export(idx: usize) -> String {
+    format!("__chromey_default_{idx}")
+}
+
+/// The quoted module specifier of an import statement and its start position.
+fn specifier(statement: &str) -> Option<(usize, &str)> {
+    let start = statement.find(['\'', '"'])?;
+    let quote = statement[start..].chars().next()?;
+    let rest = &statement[start + 1..];
+    let end = rest.find(quote)?;
+    Some((start, &rest[..end]))
+}
+
+/// Resolve a relative specifier trying the common module extensions.
+fn resolve(dir: &Path, spec: &str) -> Result<PathBuf> {
+    if !(spec.starts_with("./") || spec.starts_with("../") || spec.starts_with('/')) {
+        return Err(CdpError::msg(format!(
+            "bare module import '{spec}' is not supported by the inline bundler"
+        )));
+    }
+
+    let base = dir.join(spec);
+
+    [
+        base.clone(),
+        base.with_extension("js"),
+        base.with_extension("mjs"),
+        base.join("index.js"),
+    ]
+    .into_iter()
+    .find(|p| p.is_file())
+    .ok_or_else(|| CdpError::msg(format!("module '{}' not found", base.display())))
+}
+
+#[derive(Debug, Default)]
+struct InlineState {
+    /// The module index of each visited file.
+    modules: HashMap<PathBuf, usize>,
+    /// The module bodies in dependency order.
+    output: Vec<String>,
+}
+
+impl InlineState {
+    /// Inline the module after its dependencies and return its index.
+    fn visit(&mut self, path: &Path) -> Result<usize> {
+        let path = path.canonicalize()?;
+
+        if let Some(idx) = self.modules.get(&path) {
+            return Ok(*idx);
+        }
+
+        let idx = self.modules.len();
+        self.modules.insert(path.clone(), idx);
+
+        let source = std::fs::read_to_string(&path)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut body = String::with_capacity(source.len());
+        let mut statement = String::new();
+
+        for line in source.lines() {
+            let trimmed = line.trim_start();
+
+            if !statement.is_empty()
+                || trimmed.starts_with("import ")
+                || trimmed.starts_with("import{")
+            {
+                statement.push_str(line);
+                statement.push('\n');
+
+                if specifier(&statement).is_some() {
+                    let stmt = std::mem::take(&mut statement);
+                    body.push_str(&self.rewrite_import(&stmt, dir)?);
+                }
+                continue;
+            }
+
+            body.push_str(&rewrite_export(line, idx)?);
+            body.push('\n');
+        }
+
+        self.output.push(body);
+
+        Ok(idx)
+    }
+
+    /// Inline the imported module and bind the imported names.
+    fn rewrite_import(&mut self, statement: &str, dir: &Path) -> Result<String> {
+        let statement = statement.trim_start();
+        let statement = statement.strip_prefix("import").unwrap_or(statement);
+        let (start, spec) = specifier(statement)
+            .ok_or_else(|| CdpError::msg("import statement without a module specifier"))?;
+        let dep = self.visit(&resolve(dir, spec)?)?;
+
+        let clause = statement[..start].trim();
+        let clause = clause.strip_suffix("from").unwrap_or(clause).trim();
+
+        if clause.is_empty() {
+            return Ok(String::new());
+        }
+
+        if clause.contains('*') {
+            return Err(CdpError::msg(format!(
+                "namespace import of '{spec}' is not supported by the inline bundler"
+            )));
+        }
+
+        let (default_name, named) = match clause.find('{') {
+            Some(open) => (
+                clause[..open].trim().trim_end_matches(',').trim(),
+                clause[open + 1..].trim_end().trim_end_matches('}'),
+            ),
+            _ => (clause, ""),
+        };
+
+        let mut out = String::new();
+
+        if !default_name.is_empty() {
+            out.push_str(&format!(
+                "const {default_name} = {};\n",
+                default_export(dep)
+            ));
+        }
+
+        for item in named.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            if let Some((name, alias)) = item.split_once(" as ") {
+                let name = name.trim();
+                let value = if name == "default" {
+                    default_export(dep)
+                } else {
+                    name.to_string()
+                };
+                out.push_str(&format!("const {} = {value};\n", alias.trim()));
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+/// Strip the `export` keywords of a line.
+fn rewrite_export(line: &str, idx: usize) -> Result<String> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    if let Some(rest) = trimmed.strip_prefix("export default ") {
+        return Ok(format!("{indent}const {} = {rest}", default_export(idx)));
+    }
+
+    if trimmed.starts_with("export {") || trimmed.starts_with("export{") {
+        if trimmed.contains(" from ") {
+            return Err(CdpError::msg(
+                "re-exports are not supported by the inline bundler",
+            ));
+        }
+
+        let list = trimmed["export".len()..]
+            .trim()
+            .trim_end_matches(';')
+            .trim_start_matches('{')
+            .trim_end_matches('}');
+        let mut out = String::new();
+
+        for item in list.split(',').map(str::trim) {
+            if let Some((name, alias)) = item.split_once(" as ") {
+                let alias = alias.trim();
+                if alias == "default" {
+                    out.push_str(&format!("const {} = {};", default_export(idx), name.trim()));
+                } else {
+                    out.push_str(&format!("const {alias} = {};", name.trim()));
+                }
+            }
+        }
+
+        return Ok(out);
+    }
+
+    Ok(match trimmed.strip_prefix("export ") {
+        Some(rest) => format!("{indent}{rest}"),
+        _ => line.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn inline_relative_imports() {
+        let dir = std::env::temp_dir().join(format!("chromey_bundle_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib/util.js"),
+            "export const add = (a, b) => a + b;\nexport default function greet() { return 'hi'; }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("main.js"),
+            "import greet, { add as sum } from './lib/util.js';\nwindow.__result = [greet(), sum(1, 2)];\n",
+        )
+        .unwrap();
+
+        let bundle = InlineBundler.bundle(&dir.join("main.js")).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(bundle.starts_with("(()=>{"));
+        assert!(bundle.contains("const add = (a, b) => a + b;"));
+        assert!(bundle.contains("const __chromey_default_1 = function greet()"));
+        assert!(bundle.contains("const greet = __chromey_default_1;"));
+        assert!(bundle.contains("const sum = add;"));
+        assert!(!bundle.contains("import "));
+        assert!(
+            bundle.find("const add").unwrap() < bundle.find("window.__result").unwrap(),
+            "dependencies are inlined first"
+        );
+    }
+}
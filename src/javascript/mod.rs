@@ -1,2 +1,6 @@
+/// Countermeasures against hostile pages.
+pub mod anti_debugging;
+/// Bundle local ES modules into a single script.
+pub mod bundle;
 /// Get content from the page.
 pub mod extract;
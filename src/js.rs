@@ -1,27 +1,184 @@
 use serde::de::DeserializeOwned;
 
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
-    CallFunctionOnParams, EvaluateParams, RemoteObject,
+    CallFunctionOnParams, EvaluateParams, ExceptionDetails, RemoteObject,
 };
 
 use crate::utils::is_likely_js_function;
 
+/// Serialize the value within the max size in the page, the value is only sent back whole when
+/// it fits. The value of a boxed result is its `value` property, a string larger than the max
+/// size is sliced rather than serialized.
+pub(crate) const LIMIT_RESULT_JS: &str = r#"function(maxSize, boxed) {
+  const value = boxed ? this.value : this;
+  if (typeof value === 'string' && value.length > maxSize) {
+    return { preview: value.slice(0, maxSize), size: value.length };
+  }
+  const json = JSON.stringify(value);
+  if (json === undefined) return { size: 0 };
+  return json.length <= maxSize
+    ? { json, size: json.length }
+    : { preview: json.slice(0, maxSize), size: json.length };
+}"#;
+
+/// The evaluation failed to parse, a `SyntaxError` thrown before any code ran.
+pub(crate) fn is_syntax_error(details: &ExceptionDetails) -> bool {
+    let class_name = details
+        .exception
+        .as_ref()
+        .and_then(|exception| exception.class_name.as_deref());
+    let ran = details
+        .stack_trace
+        .as_ref()
+        .map_or(false, |stack| !stack.call_frames.is_empty());
+
+    class_name == Some("SyntaxError") && !ran
+}
+
+/// What an evaluation does with a result larger than its limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Fail with `CdpError::ResultTooLarge`.
+    #[default]
+    Error,
+    /// Return the start of the serialized result as a string, see
+    /// [`EvaluationResult::truncation`].
+    Truncate,
+}
+
+/// The max size of an evaluation result, guarding the client against a page returning a huge
+/// object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResultLimit {
+    /// The max characters of the JSON serialization of the result.
+    pub max_size: usize,
+    /// What to do with a larger result.
+    pub policy: TruncationPolicy,
+}
+
+impl Default for ResultLimit {
+    fn default() -> Self {
+        Self {
+            max_size: 16 * 1024 * 1024,
+            policy: TruncationPolicy::Error,
+        }
+    }
+}
+
+impl ResultLimit {
+    /// Fail on results larger than the size.
+    pub fn error(max_size: usize) -> Self {
+        Self {
+            max_size,
+            policy: TruncationPolicy::Error,
+        }
+    }
+
+    /// Truncate the results larger than the size.
+    pub fn truncate(max_size: usize) -> Self {
+        Self {
+            max_size,
+            policy: TruncationPolicy::Truncate,
+        }
+    }
+
+    /// The value of the payload of [`LIMIT_RESULT_JS`].
+    pub(crate) fn apply(
+        &self,
+        payload: Option<serde_json::Value>,
+    ) -> crate::error::Result<(Option<serde_json::Value>, Option<Truncation>)> {
+        let payload = payload.unwrap_or_default();
+        let size = payload["size"].as_u64().unwrap_or_default();
+
+        if let Some(json) = payload["json"].as_str() {
+            return Ok((Some(serde_json::from_str(json)?), None));
+        }
+
+        match (payload["preview"].as_str(), self.policy) {
+            (Some(preview), TruncationPolicy::Truncate) => Ok((
+                Some(preview.into()),
+                Some(Truncation {
+                    total_size: size,
+                    kept_size: preview.chars().count() as u64,
+                }),
+            )),
+            (Some(_), TruncationPolicy::Error) => Err(crate::error::CdpError::ResultTooLarge(
+                size,
+                self.max_size as u64,
+            )),
+            _ => Ok((None, None)),
+        }
+    }
+
+    /// The value of a string result, truncated to the limit.
+    pub(crate) fn apply_str(
+        &self,
+        value: &str,
+    ) -> crate::error::Result<Option<(serde_json::Value, Truncation)>> {
+        let size = value.chars().count();
+
+        if size <= self.max_size {
+            return Ok(None);
+        }
+
+        match self.policy {
+            TruncationPolicy::Error => Err(crate::error::CdpError::ResultTooLarge(
+                size as u64,
+                self.max_size as u64,
+            )),
+            TruncationPolicy::Truncate => Ok(Some((
+                value.chars().take(self.max_size).collect::<String>().into(),
+                Truncation {
+                    total_size: size as u64,
+                    kept_size: self.max_size as u64,
+                },
+            ))),
+        }
+    }
+}
+
+/// How an evaluation result was truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncation {
+    /// The characters of the JSON serialization of the whole result.
+    pub total_size: u64,
+    /// The characters kept.
+    pub kept_size: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct EvaluationResult {
     /// Mirror object referencing original JavaScript object
     inner: RemoteObject,
+    /// How the value was truncated to its limit.
+    truncation: Option<Truncation>,
 }
 
 impl EvaluationResult {
     /// Creates a new evaluation result from a remote object.
     pub fn new(inner: RemoteObject) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            truncation: None,
+        }
+    }
+
+    /// An evaluation result truncated to its limit.
+    pub(crate) fn truncated(inner: RemoteObject, truncation: Option<Truncation>) -> Self {
+        Self { inner, truncation }
     }
+
     /// Get a reference to the underlying remote object.
     pub fn object(&self) -> &RemoteObject {
         &self.inner
     }
 
+    /// How the value was truncated, the value is then the start of the JSON serialization of
+    /// the result as a string. Only results of `Page::evaluate_with_limit` are truncated.
+    pub fn truncation(&self) -> Option<&Truncation> {
+        self.truncation.as_ref()
+    }
+
     /// Get the deserialized value if available.
     pub fn value(&self) -> Option<&serde_json::Value> {
         self.object().value.as_ref()
@@ -55,6 +212,57 @@ pub enum Evaluation {
     Function(CallFunctionOnParams),
 }
 
+impl Evaluation {
+    /// The evaluation returning its result boxed in an object, `{ value }`, so a primitive
+    /// result like a huge string stays in the page instead of being sent back by value. The
+    /// promises are awaited inside the box unless `await_promise` is `false`.
+    pub(crate) fn boxed(self) -> Self {
+        match self {
+            Evaluation::Expression(mut expr) => {
+                expr.expression = if expr.await_promise == Some(false) {
+                    format!("({{ value: (\n{}\n) }})", expr.expression)
+                } else {
+                    format!(
+                        "(async () => ({{ value: await (\n{}\n) }}))()",
+                        expr.expression
+                    )
+                };
+                expr.return_by_value = Some(false);
+                Evaluation::Expression(expr)
+            }
+            Evaluation::Function(mut fun) => {
+                fun.function_declaration = if fun.await_promise == Some(false) {
+                    format!(
+                        "function(...args) {{ return {{ value: (\n{}\n).apply(this, args) }}; }}",
+                        fun.function_declaration
+                    )
+                } else {
+                    format!(
+                        "async function(...args) {{ return {{ value: await (\n{}\n).apply(this, args) }}; }}",
+                        fun.function_declaration
+                    )
+                };
+                fun.return_by_value = Some(false);
+                Evaluation::Function(fun)
+            }
+        }
+    }
+
+    /// The evaluation without the box, its result sent back by reference.
+    pub(crate) fn by_reference(self) -> Self {
+        match self {
+            Evaluation::Expression(mut expr) => {
+                expr.return_by_value = Some(false);
+                Evaluation::Expression(expr)
+            }
+            Evaluation::Function(mut fun) => {
+                fun.return_by_value = Some(false);
+                Evaluation::Function(fun)
+            }
+        }
+    }
+}
+
 impl From<&str> for Evaluation {
     fn from(expression: &str) -> Self {
         if is_likely_js_function(expression) {
@@ -82,3 +290,122 @@ impl From<CallFunctionOnParams> for Evaluation {
         Evaluation::Function(params)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_result_limits() {
+        let fits = serde_json::json!({ "json": "{\"a\":[1,2]}", "size": 11 });
+        let (value, truncation) = ResultLimit::error(64).apply(Some(fits)).unwrap();
+
+        assert_eq!(value, Some(serde_json::json!({ "a": [1, 2] })));
+        assert_eq!(truncation, None);
+
+        let large = serde_json::json!({ "preview": "{\"a\":[1", "size": 11 });
+
+        assert!(matches!(
+            ResultLimit::error(7).apply(Some(large.clone())),
+            Err(crate::error::CdpError::ResultTooLarge(11, 7))
+        ));
+
+        let (value, truncation) = ResultLimit::truncate(7).apply(Some(large)).unwrap();
+
+        assert_eq!(value, Some("{\"a\":[1".into()));
+        assert_eq!(
+            truncation,
+            Some(Truncation {
+                total_size: 11,
+                kept_size: 7
+            })
+        );
+    }
+
+    #[test]
+    fn truncates_large_strings() {
+        let size = 64 * 1024 * 1024u64;
+        let preview = "x".repeat(1024);
+        let sliced = serde_json::json!({ "preview": preview, "size": size });
+
+        let (value, truncation) = ResultLimit::truncate(1024)
+            .apply(Some(sliced.clone()))
+            .unwrap();
+
+        assert_eq!(
+            value.as_ref().and_then(|v| v.as_str()).map(str::len),
+            Some(1024)
+        );
+        assert_eq!(
+            truncation,
+            Some(Truncation {
+                total_size: size,
+                kept_size: 1024
+            })
+        );
+        assert!(matches!(
+            ResultLimit::error(1024).apply(Some(sliced)),
+            Err(crate::error::CdpError::ResultTooLarge(total, 1024)) if total == size
+        ));
+        assert!(LIMIT_RESULT_JS.contains("value.slice(0, maxSize)"));
+    }
+
+    #[test]
+    fn boxes_the_results() {
+        let Evaluation::Expression(expr) = Evaluation::from("'x'.repeat(1e8)").boxed() else {
+            panic!("expected an expression");
+        };
+
+        assert_eq!(
+            expr.expression,
+            "(async () => ({ value: await (\n'x'.repeat(1e8)\n) }))()"
+        );
+        assert_eq!(expr.return_by_value, Some(false));
+
+        let Evaluation::Function(fun) = Evaluation::from("() => 'x'.repeat(1e8)").boxed() else {
+            panic!("expected a function");
+        };
+
+        assert!(fun
+            .function_declaration
+            .contains("value: await (\n() => 'x'.repeat(1e8)\n).apply(this, args)"));
+    }
+
+    #[test]
+    fn detects_the_syntax_errors() {
+        use chromiumoxide_cdp::cdp::js_protocol::runtime::{
+            CallFrame, RemoteObjectType, ScriptId, StackTrace,
+        };
+
+        let mut exception = RemoteObject::new(RemoteObjectType::Object);
+        exception.class_name = Some("SyntaxError".into());
+        let mut details = ExceptionDetails::new(1, "Uncaught", 0, 0);
+        details.exception = Some(exception);
+
+        assert!(is_syntax_error(&details));
+
+        // a `SyntaxError` thrown by the running code, e.g. `JSON.parse`, is not a parse error.
+        let frame = CallFrame::builder()
+            .function_name("")
+            .script_id(ScriptId::new("1"))
+            .url("")
+            .line_number(0)
+            .column_number(0)
+            .build()
+            .unwrap();
+        details.stack_trace = Some(StackTrace::new(vec![frame]));
+
+        assert!(!is_syntax_error(&details));
+    }
+
+    #[test]
+    fn truncates_strings() {
+        assert!(ResultLimit::error(4).apply_str("abcd").unwrap().is_none());
+        assert!(ResultLimit::error(3).apply_str("abcd").is_err());
+
+        let (value, truncation) = ResultLimit::truncate(2).apply_str("éèà").unwrap().unwrap();
+
+        assert_eq!(value, "éè");
+        assert_eq!(truncation.total_size, 3);
+    }
+}
@@ -0,0 +1,232 @@
+use chromiumoxide_cdp::cdp::js_protocol::debugger::{EventPaused, PausedReason};
+use chromiumoxide_cdp::cdp::js_protocol::runtime::{
+    EventExceptionThrown, ExceptionDetails, RemoteObject,
+};
+
+use crate::sourcemap::OriginalLocation;
+
+/// The max amount of javascript errors kept per document.
+pub(crate) const MAX_JS_ERRORS: usize = 500;
+
+/// A single frame of a javascript stack trace.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StackFrame {
+    /// The function name, empty for anonymous functions.
+    pub function_name: String,
+    /// The id of the script.
+    pub script_id: String,
+    /// The url of the script, empty when only the script id is known.
+    pub url: String,
+    /// The zero based line number.
+    pub line_number: i64,
+    /// The zero based column number.
+    pub column_number: i64,
+    /// The location in the original source, set when source maps are resolved.
+    pub original: Option<OriginalLocation>,
+}
+
+/// An uncaught exception or unhandled promise rejection of the page.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct JsError {
+    /// The error message.
+    pub message: String,
+    /// The stack as reported by the error, e.g. `TypeError: x is undefined\n    at ...`.
+    pub stack: Option<String>,
+    /// The parsed stack frames, innermost first.
+    pub frames: Vec<StackFrame>,
+    /// The url of the script that threw.
+    pub source: Option<String>,
+    /// The zero based line number of the throw.
+    pub line_number: i64,
+    /// The zero based column number of the throw.
+    pub column_number: i64,
+    /// The error is an unhandled promise rejection.
+    pub unhandled_rejection: bool,
+    /// The time of the error in milliseconds since the epoch.
+    pub timestamp: Option<f64>,
+}
+
+impl JsError {
+    /// The error reported by `Runtime.exceptionThrown`.
+    pub fn from_event(event: &EventExceptionThrown) -> Self {
+        let mut error = Self::from_details(&event.exception_details);
+        error.timestamp = Some(*event.timestamp.inner());
+        error
+    }
+
+    /// The error of the exception details.
+    pub fn from_details(details: &ExceptionDetails) -> Self {
+        let frames: Vec<StackFrame> = details
+            .stack_trace
+            .as_ref()
+            .map(|trace| {
+                trace
+                    .call_frames
+                    .iter()
+                    .map(|frame| StackFrame {
+                        function_name: frame.function_name.clone(),
+                        script_id: frame.script_id.inner().clone(),
+                        url: frame.url.clone(),
+                        line_number: frame.line_number,
+                        column_number: frame.column_number,
+                        original: None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let source = details
+            .url
+            .clone()
+            .or_else(|| frames.first().map(|f| f.url.clone()))
+            .filter(|url| !url.is_empty());
+
+        Self {
+            message: exception_message(details.exception.as_ref())
+                .unwrap_or_else(|| details.text.clone()),
+            stack: details
+                .exception
+                .as_ref()
+                .and_then(|e| e.description.clone()),
+            frames,
+            source,
+            line_number: details.line_number,
+            column_number: details.column_number,
+            unhandled_rejection: details.text.starts_with("Uncaught (in promise)"),
+            timestamp: None,
+        }
+    }
+
+    /// The error of a debugger pause, `None` if the pause was not caused by an exception.
+    pub fn from_paused(event: &EventPaused) -> Option<Self> {
+        let unhandled_rejection = match event.reason {
+            PausedReason::Exception => false,
+            PausedReason::PromiseRejection => true,
+            _ => return None,
+        };
+
+        let exception: Option<RemoteObject> = event
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value(data).ok());
+
+        let frames: Vec<StackFrame> = event
+            .call_frames
+            .iter()
+            .map(|frame| StackFrame {
+                function_name: frame.function_name.clone(),
+                script_id: frame.location.script_id.inner().clone(),
+                url: String::new(),
+                line_number: frame.location.line_number,
+                column_number: frame.location.column_number.unwrap_or_default(),
+                original: None,
+            })
+            .collect();
+
+        let (line_number, column_number) = frames
+            .first()
+            .map(|f| (f.line_number, f.column_number))
+            .unwrap_or_default();
+
+        Some(Self {
+            message: exception_message(exception.as_ref()).unwrap_or_default(),
+            stack: exception.and_then(|e| e.description),
+            frames,
+            source: None,
+            line_number,
+            column_number,
+            unhandled_rejection,
+            timestamp: None,
+        })
+    }
+}
+
+/// The message of a thrown value: the first line of an error description or the thrown primitive.
+fn exception_message(exception: Option<&RemoteObject>) -> Option<String> {
+    let exception = exception?;
+
+    if let Some(description) = &exception.description {
+        return description.lines().next().map(str::to_string);
+    }
+
+    exception.value.as_ref().map(|value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    })
+}
+
+/// A javascript error captured with the state of the page at the moment it was thrown.
+#[derive(Debug, Clone, Default)]
+pub struct JsErrorSnapshot {
+    /// The error.
+    pub error: JsError,
+    /// A png screenshot of the viewport taken while the page was paused on the error.
+    pub screenshot: Option<Vec<u8>>,
+    /// The html of the document taken while the page was paused on the error.
+    pub html: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_from_exception_thrown() {
+        let event: EventExceptionThrown = serde_json::from_value(serde_json::json!({
+            "timestamp": 1700000000000.0,
+            "exceptionDetails": {
+                "exceptionId": 1,
+                "text": "Uncaught (in promise)",
+                "lineNumber": 3,
+                "columnNumber": 10,
+                "url": "https://example.com/app.js",
+                "stackTrace": {
+                    "callFrames": [{
+                        "functionName": "load",
+                        "scriptId": "42",
+                        "url": "https://example.com/app.js",
+                        "lineNumber": 3,
+                        "columnNumber": 10
+                    }]
+                },
+                "exception": {
+                    "type": "object",
+                    "subtype": "error",
+                    "className": "TypeError",
+                    "description": "TypeError: x is undefined\n    at load (https://example.com/app.js:4:11)"
+                }
+            }
+        }))
+        .unwrap();
+
+        let error = JsError::from_event(&event);
+
+        assert_eq!(error.message, "TypeError: x is undefined");
+        assert!(error.unhandled_rejection);
+        assert_eq!(error.source.as_deref(), Some("https://example.com/app.js"));
+        assert_eq!(error.frames.len(), 1);
+        assert_eq!(error.frames[0].function_name, "load");
+        assert_eq!(error.timestamp, Some(1700000000000.0));
+    }
+
+    #[test]
+    fn thrown_primitive_message() {
+        let event: EventExceptionThrown = serde_json::from_value(serde_json::json!({
+            "timestamp": 0.0,
+            "exceptionDetails": {
+                "exceptionId": 2,
+                "text": "Uncaught",
+                "lineNumber": 0,
+                "columnNumber": 0,
+                "exception": { "type": "string", "value": "boom" }
+            }
+        }))
+        .unwrap();
+
+        let error = JsError::from_event(&event);
+
+        assert_eq!(error.message, "boom");
+        assert!(!error.unhandled_rejection);
+        assert!(error.source.is_none());
+    }
+}
@@ -0,0 +1,104 @@
+//! The JSON backend decoding the messages of the connection, chosen with the features
+//! `json-simd` (simd-json) and `json-sonic` (sonic-rs), serde_json without either. The first
+//! enabled in that order wins. The `serde_stacker` feature overrides both: the deeply nested
+//! messages it decodes without a recursion limit are only supported by serde_json.
+//!
+//! The backends differ in speed and in their error messages, the errors are all converted to
+//! [`crate::error::CdpError`], see [`crate::error::CdpError::is_json`].
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::Result;
+
+/// The name of the backend, e.g. for logs and benchmarks.
+#[cfg(all(feature = "json-simd", not(feature = "serde_stacker")))]
+pub const BACKEND: &str = "simd-json";
+/// The name of the backend, e.g. for logs and benchmarks.
+#[cfg(all(
+    feature = "json-sonic",
+    not(any(feature = "json-simd", feature = "serde_stacker"))
+))]
+pub const BACKEND: &str = "sonic-rs";
+/// The name of the backend, e.g. for logs and benchmarks.
+#[cfg(any(
+    feature = "serde_stacker",
+    not(any(feature = "json-simd", feature = "json-sonic"))
+))]
+pub const BACKEND: &str = "serde_json";
+
+/// Deserialize the value of the json bytes.
+#[cfg(all(feature = "json-simd", not(feature = "serde_stacker")))]
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    // simd-json parses in place.
+    let mut bytes = bytes.to_vec();
+    Ok(simd_json::serde::from_slice(&mut bytes)?)
+}
+
+/// Deserialize the value of the json bytes.
+#[cfg(all(
+    feature = "json-sonic",
+    not(any(feature = "json-simd", feature = "serde_stacker"))
+))]
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(sonic_rs::from_slice(bytes)?)
+}
+
+/// Deserialize the value of the json bytes.
+#[cfg(any(
+    feature = "serde_stacker",
+    not(any(feature = "json-simd", feature = "json-sonic"))
+))]
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+/// Serialize the value as a json string.
+#[cfg(all(feature = "json-simd", not(feature = "serde_stacker")))]
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(simd_json::serde::to_string(value)?)
+}
+
+/// Serialize the value as a json string.
+#[cfg(all(
+    feature = "json-sonic",
+    not(any(feature = "json-simd", feature = "serde_stacker"))
+))]
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(sonic_rs::to_string(value)?)
+}
+
+/// Serialize the value as a json string.
+#[cfg(any(
+    feature = "serde_stacker",
+    not(any(feature = "json-simd", feature = "json-sonic"))
+))]
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chromiumoxide_cdp::cdp::CdpEventMessage;
+    use chromiumoxide_types::Message;
+
+    #[test]
+    fn round_trips_messages() {
+        let event =
+            r#"{"method":"Page.frameStoppedLoading","params":{"frameId":"F1"},"sessionId":"S1"}"#;
+
+        match *from_slice::<Box<Message<CdpEventMessage>>>(event.as_bytes()).unwrap() {
+            Message::Event(event) => assert_eq!(event.session_id.as_deref(), Some("S1")),
+            _ => panic!("expected an event"),
+        }
+
+        let json = to_string(&serde_json::json!({ "id": 1, "method": "Page.enable" })).unwrap();
+
+        assert_eq!(
+            from_slice::<serde_json::Value>(json.as_bytes()).unwrap()["method"],
+            "Page.enable"
+        );
+        assert!(from_slice::<serde_json::Value>(b"{").unwrap_err().is_json());
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::HashSet;
+
+/// Extract the declared languages, the visible text and the hreflang alternates of the page.
+pub(crate) const LANGUAGE_SIGNALS_JS: &str = r###"(()=>{const m=document.querySelector('meta[http-equiv="content-language" i]');const a=[];for(const l of document.querySelectorAll('link[rel~="alternate" i][hreflang]')){if(l.href){a.push({hreflang:l.getAttribute('hreflang')||'',url:l.href})}}const b=document.body;return{declared:document.documentElement.getAttribute('lang')||null,contentLanguage:m?m.getAttribute('content'):null,text:b?(b.innerText||'').slice(0,20000):'',alternates:a}})()"###;
+
+/// The chars of text sampled to detect the language.
+const MAX_SAMPLE_CHARS: usize = 20_000;
+
+/// The most frequent words of the languages written in the latin script.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "in", "is", "that", "for", "it", "with", "as", "was", "on",
+            "are", "you", "this", "be", "at", "by", "have",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "de", "la", "que", "el", "en", "y", "los", "del", "se", "las", "por", "un", "para",
+            "con", "una", "su", "al", "es", "lo", "como",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "de", "la", "le", "et", "les", "des", "en", "un", "du", "une", "que", "est", "pour",
+            "qui", "dans", "par", "pas", "sur", "au", "avec",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "in", "den", "von", "zu", "das", "mit", "sich", "des", "auf",
+            "für", "ist", "im", "dem", "nicht", "ein", "eine", "auch",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "di", "e", "il", "la", "che", "per", "un", "in", "del", "non", "una", "della", "sono",
+            "le", "con", "si", "gli", "da", "dei", "alla",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "de", "a", "o", "que", "e", "do", "da", "em", "um", "para", "com", "não", "uma", "os",
+            "no", "se", "na", "por", "mais", "as",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "en", "van", "het", "een", "in", "is", "dat", "op", "te", "zijn", "voor", "met",
+            "die", "niet", "aan", "er", "om", "ook", "als",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "i", "att", "det", "som", "en", "på", "är", "av", "för", "med", "till", "den",
+            "har", "de", "inte", "om", "ett", "var", "jag",
+        ],
+    ),
+    (
+        "pl",
+        &[
+            "i", "w", "nie", "na", "się", "z", "do", "to", "że", "jest", "o", "jak", "ale", "po",
+            "co", "tak", "za", "od", "są", "dla",
+        ],
+    ),
+    (
+        "tr",
+        &[
+            "ve", "bir", "bu", "da", "de", "için", "ile", "çok", "olarak", "daha", "gibi", "en",
+            "olan", "ne", "ama", "kadar", "sonra", "her", "var", "mi",
+        ],
+    ),
+];
+
+/// A detected language.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LanguageGuess {
+    /// The ISO 639-1 code of the language, e.g. `en`.
+    pub code: String,
+    /// How sure the detection is, between 0 and 1.
+    pub confidence: f64,
+}
+
+/// The languages of a page.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LanguageReport {
+    /// The language detected from the visible text.
+    pub detected: Option<LanguageGuess>,
+    /// The `lang` attribute of the document.
+    pub declared: Option<String>,
+    /// The `content-language` meta tag.
+    pub content_language: Option<String>,
+}
+
+impl LanguageReport {
+    /// The language of the page: the detected language when sure enough, else the declared one.
+    pub fn language(&self) -> Option<String> {
+        match &self.detected {
+            Some(guess) if guess.confidence >= 0.5 => Some(guess.code.clone()),
+            _ => self
+                .declared
+                .as_deref()
+                .or(self.content_language.as_deref())
+                .map(|lang| primary_subtag(&normalize_lang(lang)).to_string())
+                .filter(|lang| !lang.is_empty()),
+        }
+    }
+}
+
+/// An alternate of the page in another language or region.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct HreflangAlternate {
+    /// The normalized language tag, e.g. `en-us` or `x-default`.
+    pub hreflang: String,
+    /// The absolute url of the alternate.
+    pub url: String,
+}
+
+/// The signals extracted from the page.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LanguageSignals {
+    pub declared: Option<String>,
+    pub content_language: Option<String>,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub alternates: Vec<HreflangAlternate>,
+}
+
+impl LanguageSignals {
+    pub(crate) fn into_report(self) -> LanguageReport {
+        LanguageReport {
+            detected: detect_language(&self.text),
+            declared: self.declared.filter(|lang| !lang.trim().is_empty()),
+            content_language: self.content_language.filter(|lang| !lang.trim().is_empty()),
+        }
+    }
+}
+
+/// Lowercase the language tag with `-` separators, e.g. `en_US` to `en-us`.
+pub fn normalize_lang(lang: &str) -> String {
+    lang.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// The language of the tag, e.g. `en` of `en-us`.
+fn primary_subtag(lang: &str) -> &str {
+    lang.split('-').next().unwrap_or_default()
+}
+
+/// The language of the script, for the scripts mostly written in a single language.
+fn script_language(c: char) -> Option<&'static str> {
+    Some(match c as u32 {
+        0x3040..=0x30FF => "ja",
+        0xAC00..=0xD7AF | 0x1100..=0x11FF => "ko",
+        0x4E00..=0x9FFF => "zh",
+        0x0400..=0x04FF => "ru",
+        0x0600..=0x06FF => "ar",
+        0x0590..=0x05FF => "he",
+        0x0370..=0x03FF => "el",
+        0x0E00..=0x0E7F => "th",
+        0x0900..=0x097F => "hi",
+        _ => return None,
+    })
+}
+
+/// Detect the language of the text from its script and, for the latin script, the frequency of
+/// the most common words of every language.
+pub fn detect_language(text: &str) -> Option<LanguageGuess> {
+    let sample: String = text.chars().take(MAX_SAMPLE_CHARS).collect();
+
+    let mut scripts: Vec<(&str, usize)> = Vec::new();
+    let mut letters = 0;
+
+    for c in sample.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+
+        if let Some(lang) = script_language(c) {
+            match scripts.iter_mut().find(|(l, _)| *l == lang) {
+                Some((_, count)) => *count += 1,
+                None => scripts.push((lang, 1)),
+            }
+        }
+    }
+
+    if letters == 0 {
+        return None;
+    }
+
+    // the chinese characters are written along kana in japanese.
+    if scripts.iter().any(|(lang, _)| *lang == "ja") {
+        let han = scripts
+            .iter_mut()
+            .find(|(lang, _)| *lang == "zh")
+            .map_or(0, |(_, count)| std::mem::take(count));
+
+        if let Some((_, count)) = scripts.iter_mut().find(|(lang, _)| *lang == "ja") {
+            *count += han;
+        }
+    }
+
+    if let Some((lang, count)) = scripts.iter().max_by_key(|(_, count)| *count) {
+        if *count * 2 >= letters {
+            let code = match *lang {
+                "ru" if sample.contains(['і', 'ї', 'є', 'ґ']) => "uk",
+                "ar" if sample.contains(['پ', 'چ', 'ژ', 'گ']) => "fa",
+                lang => lang,
+            };
+
+            return Some(LanguageGuess {
+                code: code.into(),
+                confidence: (*count as f64 / letters as f64).min(1.0),
+            });
+        }
+    }
+
+    let lowered = sample.to_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    if words.len() < 3 {
+        return None;
+    }
+
+    let mut scores: Vec<(&str, usize)> = STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| {
+            let stopwords: HashSet<_> = stopwords.iter().copied().collect();
+            (
+                *lang,
+                words
+                    .iter()
+                    .filter(|word| stopwords.contains(*word))
+                    .count(),
+            )
+        })
+        .collect();
+
+    scores.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let (best, best_score) = scores[0];
+    let second = scores.get(1).map_or(0, |(_, score)| *score);
+
+    if best_score == 0 {
+        return None;
+    }
+
+    // the margin over the next language and the share of stopwords in the text.
+    let margin = (best_score - second) as f64 / best_score as f64;
+    let coverage = (best_score as f64 / words.len() as f64 * 4.0).min(1.0);
+
+    Some(LanguageGuess {
+        code: best.into(),
+        confidence: (0.5 + margin / 2.0) * coverage,
+    })
+}
+
+/// Normalize the alternates, dropping the invalid ones and the duplicates. The first url of a
+/// language is kept.
+pub fn dedup_hreflang(alternates: Vec<HreflangAlternate>) -> Vec<HreflangAlternate> {
+    let mut seen = HashSet::new();
+
+    alternates
+        .into_iter()
+        .filter_map(|alternate| {
+            let hreflang = normalize_lang(&alternate.hreflang);
+            let mut url = url::Url::parse(alternate.url.trim()).ok()?;
+
+            if hreflang.is_empty() || !url.scheme().starts_with("http") {
+                return None;
+            }
+            url.set_fragment(None);
+
+            Some(HreflangAlternate {
+                hreflang,
+                url: url.to_string(),
+            })
+        })
+        .filter(|alternate| seen.insert(alternate.hreflang.clone()))
+        .collect()
+}
+
+/// The alternate to crawl for the preferred languages in order: an exact match of the tag,
+/// then of the language, then the `x-default` alternate.
+pub fn pick_alternate<'a>(
+    alternates: &'a [HreflangAlternate],
+    preferred: &[&str],
+) -> Option<&'a HreflangAlternate> {
+    preferred
+        .iter()
+        .map(|lang| normalize_lang(lang))
+        .find_map(|lang| {
+            alternates
+                .iter()
+                .find(|alternate| alternate.hreflang == lang)
+                .or_else(|| {
+                    alternates.iter().find(|alternate| {
+                        primary_subtag(&alternate.hreflang) == primary_subtag(&lang)
+                    })
+                })
+        })
+        .or_else(|| {
+            alternates
+                .iter()
+                .find(|alternate| alternate.hreflang == "x-default")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_languages() {
+        let detect = |text: &str| detect_language(text).map(|guess| guess.code);
+
+        assert_eq!(
+            detect(
+                "The quick brown fox jumps over the lazy dog and it is in the garden with the cat."
+            )
+            .as_deref(),
+            Some("en")
+        );
+        assert_eq!(
+            detect(
+                "El perro de la casa es muy grande y los niños juegan con el perro en el parque."
+            )
+            .as_deref(),
+            Some("es")
+        );
+        assert_eq!(
+            detect("Der Hund und die Katze sind in dem Garten, das ist nicht für den Hund.")
+                .as_deref(),
+            Some("de")
+        );
+        assert_eq!(detect("東京は日本の首都です。").as_deref(), Some("ja"));
+        assert_eq!(detect("Привет, как дела?").as_deref(), Some("ru"));
+        assert_eq!(detect("1234 !!"), None);
+    }
+
+    #[test]
+    fn dedups_and_picks_alternates() {
+        let alternate = |hreflang: &str, url: &str| HreflangAlternate {
+            hreflang: hreflang.into(),
+            url: url.into(),
+        };
+
+        let alternates = dedup_hreflang(vec![
+            alternate("en_US", "https://example.com/en-us/#top"),
+            alternate("en-us", "https://example.com/other/"),
+            alternate("de", "https://example.com/de/"),
+            alternate("x-default", "https://example.com/"),
+            alternate("fr", "javascript:void(0)"),
+        ]);
+
+        assert_eq!(
+            alternates,
+            vec![
+                alternate("en-us", "https://example.com/en-us/"),
+                alternate("de", "https://example.com/de/"),
+                alternate("x-default", "https://example.com/"),
+            ]
+        );
+        assert_eq!(
+            pick_alternate(&alternates, &["de-AT"]).map(|a| a.url.as_str()),
+            Some("https://example.com/de/")
+        );
+        assert_eq!(
+            pick_alternate(&alternates, &["EN-us", "de"]).map(|a| a.url.as_str()),
+            Some("https://example.com/en-us/")
+        );
+        assert_eq!(
+            pick_alternate(&alternates, &["ja"]).map(|a| a.hreflang.as_str()),
+            Some("x-default")
+        );
+    }
+}
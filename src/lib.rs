@@ -62,27 +62,116 @@
 
 #![warn(missing_debug_implementations, rust_2018_idioms)]
 
+#[cfg(all(
+    target_arch = "wasm32",
+    any(
+        feature = "_cache",
+        feature = "server",
+        feature = "capi",
+        feature = "blocking"
+    )
+))]
+compile_error!(
+    "The cache, server, capi and blocking features need the native runtime, they are not supported on wasm32."
+);
+
+pub mod alternates;
+pub mod artifact;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod async_process;
 pub mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod browser;
+pub mod bulk;
 #[cfg(feature = "_cache")]
 pub mod cache;
+// the C API drives its own tokio runtime, it is left out with the other runtimes.
+#[cfg(all(feature = "capi", not(any(feature = "async-std", feature = "smol"))))]
+pub mod capi;
+pub mod cdp_stream;
+pub mod classify;
 #[cfg(feature = "_cache")]
 pub mod http;
 
 pub(crate) mod cmd;
+#[cfg(feature = "config-file")]
+pub mod config_file;
 pub mod conn;
+pub mod consent;
+pub mod custom_ca;
+pub mod debugger;
 pub mod detection;
+#[cfg(feature = "server")]
+pub mod devtools_proxy;
+pub mod diff;
+pub mod duplicates;
 pub mod element;
+pub mod email_links;
 pub mod error;
+pub mod events;
+pub mod feeds;
+pub mod flight_recorder;
+pub mod fonts;
 pub mod handler;
+pub mod health;
+pub mod hooks;
+pub(crate) mod http_client;
+pub mod icons;
+pub mod injection;
+pub mod interception;
+pub mod intl;
 pub mod javascript;
 pub mod js;
+pub mod js_errors;
+pub mod json;
 pub mod keys;
+pub mod language;
 pub mod layout;
+pub mod links;
 pub mod listeners;
+#[cfg(feature = "server")]
+pub mod live_view;
+pub mod manifest;
+#[cfg(feature = "mtls")]
+pub mod mtls;
+pub mod nav_snapshots;
+pub mod network_rules;
+pub mod notifications;
+pub mod oauth;
 pub mod page;
+pub mod page_state;
+pub mod paywall;
+pub mod performance;
+pub mod policy;
+#[cfg(any(test, feature = "protocol-compat"))]
+pub mod protocol_compat;
+pub mod recording;
+pub mod redirect;
+pub mod request_signing;
+pub mod rotation;
+pub mod runtime;
+pub mod scope;
+pub mod sec_fetch;
+pub mod secrets;
+pub mod security;
+pub mod selftest;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod single_file;
+pub mod sink;
+pub mod sourcemap;
+pub mod stability;
+pub mod streaming;
+pub mod urlnorm;
 pub mod utils;
+#[cfg(feature = "session-vault")]
+pub mod vault;
+#[cfg(feature = "visual-diff")]
+pub mod visual_diff;
+pub mod wait;
+pub mod webhook;
+pub mod world;
 
 use crate::handler::http::HttpRequest;
 use std::sync::Arc;
@@ -125,3 +214,6 @@ pub use sonic_rs as serde_json;
 /// Init the cache global worker.
 #[cfg(feature = "_cache")]
 pub use cache::dump_remote::init_default_cache_worker;
+/// Init the cache stats reporter.
+#[cfg(feature = "_cache")]
+pub use cache::stats::init_default_stats_reporter;
@@ -0,0 +1,329 @@
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use std::time::Duration;
+
+use crate::policy::ScopePolicy;
+
+/// Extract the absolute urls of all the anchors on the page.
+pub(crate) const EXTRACT_LINKS_JS: &str = r###"(()=>{const s=new Set();for(const a of document.querySelectorAll('a[href],area[href]')){const h=a.href;if(h&&(h.startsWith('http://')||h.startsWith('https://'))){s.add(h.split('#')[0])}}return Array.from(s)})()"###;
+
+/// Which links are checked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LinkScope {
+    /// Check every http(s) link.
+    #[default]
+    All,
+    /// Only check links on the same host as the page.
+    SameHost,
+    /// Only check links on other hosts.
+    External,
+}
+
+/// Options for `Page::check_links`.
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    /// The links to check.
+    pub scope: LinkScope,
+    /// Max links to check.
+    pub max_links: usize,
+    /// Max concurrent requests.
+    pub concurrency: usize,
+    /// Max requests started per second.
+    pub requests_per_second: u32,
+    /// Timeout per request.
+    pub timeout: Duration,
+    /// Retry with a GET request when the server does not allow HEAD.
+    pub get_fallback: bool,
+    /// Treat urls found in the local cache as valid without a request.
+    pub use_cache: bool,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            scope: LinkScope::All,
+            max_links: 500,
+            concurrency: 10,
+            requests_per_second: 20,
+            timeout: Duration::from_secs(15),
+            get_fallback: true,
+            use_cache: true,
+        }
+    }
+}
+
+/// The status of a single link.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LinkStatus {
+    /// The link url.
+    pub url: String,
+    /// The final http status code.
+    pub status: Option<u16>,
+    /// The final url if the link redirected.
+    pub redirected_to: Option<String>,
+    /// The request error if no response was received.
+    pub error: Option<String>,
+    /// The link was resolved from the cache.
+    pub from_cache: bool,
+}
+
+impl LinkStatus {
+    /// The link is broken (request failed or a 4xx/5xx status).
+    pub fn is_broken(&self) -> bool {
+        self.error.is_some() || self.status.map_or(true, |s| s >= 400)
+    }
+}
+
+/// Filter the links by the scope relative to the page url, dropping the links out of the scope
+/// policy of the browser.
+pub fn filter_links(
+    page_url: Option<&str>,
+    links: Vec<String>,
+    options: &CheckOptions,
+    policy: Option<&ScopePolicy>,
+) -> Vec<String> {
+    let page_host = page_url
+        .and_then(|u| url::Url::parse(u).ok())
+        .and_then(|u| u.host_str().map(|h| h.to_ascii_lowercase()));
+
+    links
+        .into_iter()
+        .filter(|link| policy.map_or(true, |policy| policy.allows(link)))
+        .filter(|link| {
+            if options.scope == LinkScope::All {
+                return true;
+            }
+            let same_host = match (
+                page_host.as_deref(),
+                url::Url::parse(link)
+                    .ok()
+                    .and_then(|u| u.host_str().map(|h| h.to_ascii_lowercase())),
+            ) {
+                (Some(page_host), Some(host)) => page_host == host,
+                _ => false,
+            };
+            match options.scope {
+                LinkScope::SameHost => same_host,
+                LinkScope::External => !same_host,
+                LinkScope::All => true,
+            }
+        })
+        .take(options.max_links)
+        .collect()
+}
+
+/// Check a single link with a HEAD request falling back to GET.
+pub async fn check_link(url: String, options: &CheckOptions) -> LinkStatus {
+    #[cfg(feature = "_cache")]
+    if options.use_cache && crate::cache::get_cached_url(&url, None).await.is_some() {
+        return LinkStatus {
+            url,
+            status: Some(200),
+            redirected_to: None,
+            error: None,
+            from_cache: true,
+        };
+    }
+
+    let mut result = crate::http_client::HTTP_CLIENT
+        .head(&url)
+        .timeout(options.timeout)
+        .send()
+        .await;
+
+    if options.get_fallback {
+        let retry = match &result {
+            Ok(resp) => matches!(resp.status().as_u16(), 403 | 405 | 501),
+            Err(_) => true,
+        };
+        if retry {
+            result = crate::http_client::HTTP_CLIENT
+                .get(&url)
+                .timeout(options.timeout)
+                .send()
+                .await;
+        }
+    }
+
+    match result {
+        Ok(resp) => {
+            let final_url = resp.url().as_str();
+            LinkStatus {
+                redirected_to: if final_url != url {
+                    Some(final_url.to_string())
+                } else {
+                    None
+                },
+                url,
+                status: Some(resp.status().as_u16()),
+                error: None,
+                from_cache: false,
+            }
+        }
+        Err(err) => LinkStatus {
+            url,
+            status: err.status().map(|s| s.as_u16()),
+            redirected_to: None,
+            error: Some(err.to_string()),
+            from_cache: false,
+        },
+    }
+}
+
+/// Check the links with the concurrency and rate limits of the options.
+pub async fn check_links(links: Vec<String>, options: &CheckOptions) -> Vec<LinkStatus> {
+    let concurrency = options.concurrency.max(1);
+    let tick_ms = (1000u64 / options.requests_per_second.max(1) as u64).max(1);
+    let tick = Duration::from_millis(tick_ms);
+    let mut next_tick = crate::runtime::Instant::now();
+
+    let mut results = Vec::with_capacity(links.len());
+    let mut pending = FuturesUnordered::new();
+
+    for link in links {
+        if pending.len() >= concurrency {
+            if let Some(status) = pending.next().await {
+                results.push(status);
+            }
+        }
+        let wait = next_tick.saturating_duration_since(crate::runtime::Instant::now());
+        if !wait.is_zero() {
+            crate::runtime::sleep(wait).await;
+        }
+        next_tick = crate::runtime::Instant::now() + tick;
+        pending.push(check_link(link, options));
+    }
+
+    while let Some(status) = pending.next().await {
+        results.push(status);
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn links() -> Vec<String> {
+        [
+            "https://a.com/about",
+            "https://A.com/contact",
+            "https://blog.a.com/post",
+            "https://b.com/",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn options(scope: LinkScope) -> CheckOptions {
+        CheckOptions {
+            scope,
+            use_cache: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classifies_the_links() {
+        let page = Some("https://a.com/");
+
+        assert_eq!(
+            filter_links(page, links(), &options(LinkScope::All), None).len(),
+            4
+        );
+        assert_eq!(
+            filter_links(page, links(), &options(LinkScope::SameHost), None),
+            ["https://a.com/about", "https://A.com/contact"]
+        );
+        assert_eq!(
+            filter_links(page, links(), &options(LinkScope::External), None),
+            ["https://blog.a.com/post", "https://b.com/"]
+        );
+        assert_eq!(
+            filter_links(None, links(), &options(LinkScope::SameHost), None),
+            Vec::<String>::new()
+        );
+
+        let capped = CheckOptions {
+            max_links: 1,
+            ..options(LinkScope::All)
+        };
+        assert_eq!(
+            filter_links(page, links(), &capped, None),
+            ["https://a.com/about"]
+        );
+    }
+
+    #[test]
+    fn drops_the_links_out_of_the_scope_policy() {
+        let policy = ScopePolicy::hosts(["a.com"]);
+
+        assert_eq!(
+            filter_links(
+                Some("https://a.com/"),
+                links(),
+                &options(LinkScope::External),
+                Some(&policy)
+            ),
+            ["https://blog.a.com/post"]
+        );
+    }
+
+    #[test]
+    fn broken_statuses() {
+        let status = |status: Option<u16>, error: Option<&str>| LinkStatus {
+            url: "https://a.com/".into(),
+            status,
+            redirected_to: None,
+            error: error.map(String::from),
+            from_cache: false,
+        };
+
+        assert!(!status(Some(200), None).is_broken());
+        assert!(!status(Some(301), None).is_broken());
+        assert!(status(Some(404), None).is_broken());
+        assert!(status(Some(503), None).is_broken());
+        assert!(status(None, None).is_broken());
+        assert!(status(None, Some("connection refused")).is_broken());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_get_when_head_is_not_allowed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/page", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let read = stream.read(&mut buf).await.unwrap_or_default();
+                let status = if buf[..read].starts_with(b"HEAD") {
+                    "405 Method Not Allowed"
+                } else {
+                    "200 OK"
+                };
+                let _ = stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await;
+            }
+        });
+
+        let status = check_link(url.clone(), &options(LinkScope::All)).await;
+        assert_eq!(status.status, Some(200));
+        assert!(!status.is_broken());
+
+        let head_only = CheckOptions {
+            get_fallback: false,
+            ..options(LinkScope::All)
+        };
+        let status = check_link(url, &head_only).await;
+        assert_eq!(status.status, Some(405));
+        assert!(status.is_broken());
+    }
+}
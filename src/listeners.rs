@@ -278,7 +278,7 @@ mod tests {
 
         let mut stream = EventStream::<EventAnimationCanceled>::new(rx);
 
-        tokio::task::spawn(async move {
+        crate::runtime::spawn(async move {
             loop {
                 futures::future::poll_fn(|cx| {
                     listeners.poll(cx);
@@ -0,0 +1,410 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use chromiumoxide_cdp::cdp::browser_protocol::page::{
+    EventScreencastFrame, ScreencastFrameAckParams, StartScreencastFormat, StartScreencastParams,
+    StopScreencastParams,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::target::TargetId;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::browser::Browser;
+use crate::devtools_proxy::{read_head, AccessPolicy, RequestHead};
+use crate::error::Result;
+use crate::layout::{Delta, Point};
+use crate::page::Page;
+use crate::runtime::JoinHandle;
+use crate::server::http_response;
+
+/// The viewer of the live view, `/?page=<target id>`.
+const VIEWER_HTML: &str = r#"<!doctype html><html><head><meta charset="utf-8"><title>chromey live view</title>
+<style>body{margin:0;background:#111;color:#ddd;font:14px sans-serif}#bar{padding:6px}img{display:block;max-width:100%;cursor:crosshair;outline:none}</style></head>
+<body><div id="bar"><select id="pages"></select> <input id="url" size="60" placeholder="https://"> <button id="go">go</button> <span id="status"></span></div>
+<img id="screen" tabindex="0">
+<script>
+const $=id=>document.getElementById(id),t=new URLSearchParams(location.search).get('token'),tq=t?'?token='+encodeURIComponent(t):'';let ws,meta;
+function send(m){if(ws&&ws.readyState===1)ws.send(JSON.stringify(m))}
+function point(e){const r=$('screen').getBoundingClientRect(),s=meta?meta.deviceWidth/r.width:1;return{x:(e.clientX-r.left)*s,y:(e.clientY-r.top)*s}}
+function open(id){if(ws)ws.close();ws=new WebSocket(`ws://${location.host}/live/${id}${tq}`);
+ws.onmessage=e=>{const m=JSON.parse(e.data);if(m.type==='frame'){meta=m.metadata;$('screen').src='data:image/jpeg;base64,'+m.data}else if(m.type==='error'){$('status').textContent=m.message}};
+ws.onclose=()=>$('status').textContent='disconnected';$('status').textContent='live'}
+fetch('/pages'+tq).then(r=>r.json()).then(pages=>{for(const p of pages){const o=document.createElement('option');o.value=p.id;o.textContent=p.title||p.url;$('pages').append(o)}
+const id=new URLSearchParams(location.search).get('page')||(pages[0]&&pages[0].id);if(id){$('pages').value=id;open(id)}});
+$('pages').onchange=e=>open(e.target.value);$('go').onclick=()=>send({type:'navigate',url:$('url').value});
+$('screen').onclick=e=>{$('screen').focus();send({type:'click',...point(e)})};
+$('screen').onwheel=e=>{e.preventDefault();send({type:'scroll',...point(e),deltaX:e.deltaX,deltaY:e.deltaY})};
+$('screen').onkeydown=e=>{e.preventDefault();if(e.key.length===1)send({type:'text',text:e.key});else send({type:'key',key:e.key})};
+</script></body></html>"#;
+
+/// The screencast of the live view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveViewOptions {
+    /// The jpeg quality of the frames.
+    pub quality: i64,
+    /// The max width of the frames.
+    pub max_width: i64,
+    /// The max height of the frames.
+    pub max_height: i64,
+    /// Send every nth frame.
+    pub every_nth_frame: i64,
+}
+
+impl Default for LiveViewOptions {
+    fn default() -> Self {
+        Self {
+            quality: 60,
+            max_width: 1280,
+            max_height: 960,
+            every_nth_frame: 1,
+        }
+    }
+}
+
+/// An input of the operator, a JSON text message of the live view websocket. The coordinates are
+/// css pixels of the page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveInput {
+    /// Click at the point.
+    Click { x: f64, y: f64 },
+    /// Move the mouse to the point.
+    Move { x: f64, y: f64 },
+    /// Scroll at the point.
+    Scroll {
+        x: f64,
+        y: f64,
+        #[serde(rename = "deltaX", default)]
+        delta_x: f64,
+        #[serde(rename = "deltaY", default)]
+        delta_y: f64,
+    },
+    /// Press a key, e.g. `Enter` or `Backspace`.
+    Key { key: String },
+    /// Type the text.
+    Text { text: String },
+    /// Navigate to the url.
+    Navigate { url: String },
+}
+
+impl LiveInput {
+    /// Dispatch the input to the page.
+    pub async fn dispatch(self, page: &Page) -> Result<()> {
+        match self {
+            LiveInput::Click { x, y } => {
+                page.click(Point::new(x, y)).await?;
+            }
+            LiveInput::Move { x, y } => {
+                page.move_mouse(Point::new(x, y)).await?;
+            }
+            LiveInput::Scroll {
+                x,
+                y,
+                delta_x,
+                delta_y,
+            } => {
+                page.scroll(Point::new(x, y), Delta::new(delta_x, delta_y))
+                    .await?;
+            }
+            LiveInput::Key { key } => {
+                page.press_key(key).await?;
+            }
+            LiveInput::Text { text } => {
+                page.type_str(text).await?;
+            }
+            LiveInput::Navigate { url } => {
+                page.goto(url).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A live view of the pages of a browser: the operators watch the screencast of a page in a
+/// browser UI and nudge a stuck automation with clicks, scrolls and keys.
+///
+/// `GET /` serves the viewer, `GET /pages` lists the pages and the `/live/<target id>` websocket
+/// streams the frames of the page as `{"type":"frame","data":<base64 jpeg>,"metadata":{..}}`
+/// messages, the [`LiveInput`] messages are dispatched once enabled with
+/// [`LiveViewServer::with_input`].
+///
+/// The requests are checked like the ones of the DevTools proxy: only the loopback as host and
+/// the viewer itself as origin are allowed by default, see [`LiveViewServer::with_access`]. A
+/// required token is passed to the viewer as `/?token=<token>`.
+#[derive(Debug, Clone)]
+pub struct LiveViewServer {
+    browser: Arc<Browser>,
+    options: LiveViewOptions,
+    access: AccessPolicy,
+    input: bool,
+}
+
+impl LiveViewServer {
+    /// A view only live view of the pages of the browser.
+    pub fn new(browser: Arc<Browser>) -> Self {
+        Self {
+            browser,
+            options: LiveViewOptions::default(),
+            access: AccessPolicy::default(),
+            input: false,
+        }
+    }
+
+    /// Stream the frames with the options.
+    pub fn with_options(mut self, options: LiveViewOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Who may connect, e.g. to require a token.
+    pub fn with_access(mut self, access: AccessPolicy) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Dispatch the inputs of the viewers to the pages, the clicks, keys and navigations of
+    /// anyone allowed to connect. Disabled by default.
+    pub fn with_input(mut self, enabled: bool) -> Self {
+        self.input = enabled;
+        self
+    }
+
+    /// Bind the address and spawn the task accepting the viewers.
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> std::io::Result<JoinHandle<()>> {
+        let listener = TcpListener::bind(addr).await?;
+
+        Ok(crate::runtime::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let server = self.clone();
+                        crate::runtime::spawn(async move {
+                            if let Err(err) = server.handle_connection(stream, peer).await {
+                                tracing::debug!("live view connection {peer} closed: {err}");
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        tracing::warn!("live view accept failed: {err}");
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Serve the viewer, the pages or the live websocket of the connection.
+    async fn handle_connection(
+        &self,
+        mut stream: TcpStream,
+        peer: SocketAddr,
+    ) -> std::io::Result<()> {
+        let head = read_head(&mut stream).await?;
+        let request = RequestHead::parse(&head).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid request head")
+        })?;
+
+        if let Some(status) = self.access.rejection(&request) {
+            stream
+                .write_all(http_response(status, "").as_bytes())
+                .await?;
+            return stream.shutdown().await;
+        }
+
+        let path = request.path.split('?').next().unwrap_or_default();
+
+        if let Some(target) = path.strip_prefix("/live/") {
+            if request.is_websocket() {
+                return self.live(stream, &request, target, peer).await;
+            }
+        }
+
+        let response = match path {
+            "/" => format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/html; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{VIEWER_HTML}",
+                VIEWER_HTML.len()
+            ),
+            "/pages" => http_response("200 OK", &self.pages().await.to_string()),
+            _ => http_response("404 Not Found", ""),
+        };
+
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    /// The pages of the browser.
+    async fn pages(&self) -> Value {
+        let mut pages = Vec::new();
+
+        for page in self.browser.pages().await.unwrap_or_default() {
+            pages.push(json!({
+                "id": page.target_id().as_ref(),
+                "url": page.url().await.ok().flatten().unwrap_or_default(),
+                "title": page.get_title().await.ok().flatten().unwrap_or_default(),
+            }));
+        }
+
+        Value::from(pages)
+    }
+
+    /// Stream the screencast of the page and dispatch the inputs of the viewer.
+    async fn live(
+        &self,
+        mut stream: TcpStream,
+        request: &RequestHead,
+        target: &str,
+        peer: SocketAddr,
+    ) -> std::io::Result<()> {
+        let page = self.browser.get_page(TargetId::new(target)).await.ok();
+        let (Some(page), Some(key)) = (page, request.header("sec-websocket-key")) else {
+            stream
+                .write_all(http_response("404 Not Found", "").as_bytes())
+                .await?;
+            return stream.shutdown().await;
+        };
+
+        let accept = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nupgrade: websocket\r\nconnection: Upgrade\r\nsec-websocket-accept: {}\r\n\r\n",
+            derive_accept_key(key.as_bytes())
+        );
+        stream.write_all(accept.as_bytes()).await?;
+
+        let socket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+        let (mut viewer_tx, mut viewer_rx) = socket.split();
+
+        let frames = match page.event_listener::<EventScreencastFrame>().await {
+            Ok(frames) => frames,
+            Err(err) => {
+                let _ = viewer_tx.send(error_message(&err.to_string())).await;
+                return Ok(());
+            }
+        };
+
+        let started = page
+            .start_screencast(
+                StartScreencastParams::builder()
+                    .format(StartScreencastFormat::Jpeg)
+                    .quality(self.options.quality)
+                    .max_width(self.options.max_width)
+                    .max_height(self.options.max_height)
+                    .every_nth_frame(self.options.every_nth_frame)
+                    .build(),
+            )
+            .await;
+
+        if let Err(err) = started {
+            let _ = viewer_tx.send(error_message(&err.to_string())).await;
+            return Ok(());
+        }
+
+        tracing::debug!("live view of {target} opened by {peer}");
+
+        let stream_frames = async {
+            let mut frames = frames;
+
+            while let Some(frame) = frames.next().await {
+                let _ = page
+                    .ack_screencast(ScreencastFrameAckParams::new(frame.session_id))
+                    .await;
+
+                let message = json!({
+                    "type": "frame",
+                    "data": AsRef::<str>::as_ref(&frame.data),
+                    "metadata": frame.metadata,
+                });
+
+                if viewer_tx
+                    .send(Message::text(message.to_string()))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        };
+
+        let dispatch_inputs = async {
+            while let Some(Ok(message)) = viewer_rx.next().await {
+                if message.is_close() {
+                    break;
+                }
+
+                let Ok(text) = message.to_text() else {
+                    continue;
+                };
+
+                if !self.input {
+                    tracing::debug!("live view input ignored, the input is disabled");
+                    continue;
+                }
+
+                match serde_json::from_str::<LiveInput>(text) {
+                    Ok(input) => {
+                        if let Err(err) = input.dispatch(&page).await {
+                            tracing::debug!("live view input failed: {err}");
+                        }
+                    }
+                    Err(err) => tracing::debug!("live view input ignored: {err}"),
+                }
+            }
+        };
+
+        futures::future::select(
+            std::pin::pin!(stream_frames),
+            std::pin::pin!(dispatch_inputs),
+        )
+        .await;
+
+        let _ = page.stop_screencast(StopScreencastParams::default()).await;
+
+        Ok(())
+    }
+}
+
+/// An error message of the live view websocket.
+fn error_message(message: &str) -> Message {
+    Message::text(json!({ "type": "error", "message": message }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_inputs() {
+        assert_eq!(
+            serde_json::from_str::<LiveInput>(r#"{"type":"click","x":10,"y":20.5}"#).unwrap(),
+            LiveInput::Click { x: 10.0, y: 20.5 }
+        );
+        assert_eq!(
+            serde_json::from_str::<LiveInput>(r#"{"type":"scroll","x":1,"y":2,"deltaY":120}"#)
+                .unwrap(),
+            LiveInput::Scroll {
+                x: 1.0,
+                y: 2.0,
+                delta_x: 0.0,
+                delta_y: 120.0
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<LiveInput>(r#"{"type":"key","key":"Enter"}"#).unwrap(),
+            LiveInput::Key {
+                key: "Enter".into()
+            }
+        );
+        assert!(serde_json::from_str::<LiveInput>(r#"{"type":"drag"}"#).is_err());
+    }
+
+    #[test]
+    fn serves_the_viewer() {
+        assert!(VIEWER_HTML.contains("/live/${id}${tq}"));
+        assert!(VIEWER_HTML.contains("/pages'+tq"));
+    }
+}
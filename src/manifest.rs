@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use crate::icons::{manifest_icons, Icon};
+
+/// Extract the manifest url and the service worker registrations of the page.
+pub(crate) const PWA_SIGNALS_JS: &str = r###"(async()=>{const m=document.querySelector('link[rel~="manifest" i][href]');const s={supported:'serviceWorker' in navigator,controlled:false,registrations:[]};if(s.supported){try{s.controlled=!!navigator.serviceWorker.controller;const r=await Promise.race([navigator.serviceWorker.getRegistrations(),new Promise(f=>setTimeout(()=>f([]),2000))]);s.registrations=r.map(x=>{const w=x.active||x.waiting||x.installing;return{scope:x.scope,scriptUrl:w?w.scriptURL:null,state:w?w.state:null}})}catch(e){}}return{manifest:m?m.href:null,url:location.href,serviceWorker:s}})()"###;
+
+/// The timeout of fetching the manifest.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The display modes a web app manifest can ask for.
+const DISPLAY_MODES: [&str; 4] = ["fullscreen", "standalone", "minimal-ui", "browser"];
+
+/// A service worker registration of the page.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceWorkerRegistration {
+    /// The scope of the registration.
+    pub scope: String,
+    /// The script of the newest worker.
+    pub script_url: Option<String>,
+    /// The state of the newest worker, e.g. `activated`.
+    pub state: Option<String>,
+}
+
+/// The service worker status of the page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceWorkerStatus {
+    /// The browser exposes service workers to the page, e.g. it is a secure context.
+    pub supported: bool,
+    /// The page is controlled by a service worker.
+    pub controlled: bool,
+    /// The registrations of the origin.
+    #[serde(default)]
+    pub registrations: Vec<ServiceWorkerRegistration>,
+}
+
+impl ServiceWorkerStatus {
+    /// A service worker is registered for the origin.
+    pub fn is_registered(&self) -> bool {
+        !self.registrations.is_empty()
+    }
+}
+
+/// A parsed web app manifest.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WebAppManifest {
+    /// The name of the app.
+    pub name: Option<String>,
+    /// The short name of the app.
+    pub short_name: Option<String>,
+    /// The description of the app.
+    pub description: Option<String>,
+    /// The absolute start url, the page url when missing or cross origin.
+    pub start_url: String,
+    /// The absolute navigation scope.
+    pub scope: Option<String>,
+    /// The display mode, `browser` when missing or unknown.
+    pub display: String,
+    /// The preferred orientation.
+    pub orientation: Option<String>,
+    /// The theme color.
+    pub theme_color: Option<String>,
+    /// The background color.
+    pub background_color: Option<String>,
+    /// The declared icons, resolved against the manifest url.
+    pub icons: Vec<Icon>,
+    /// The whole manifest.
+    pub raw: serde_json::Value,
+}
+
+impl WebAppManifest {
+    /// The manifest meets the install criteria of chromium: a name, a start url, an app display
+    /// mode and 192px and 512px icons.
+    pub fn is_installable(&self) -> bool {
+        let has_size = |side: &str| {
+            self.icons.iter().any(|icon| {
+                icon.declared_sizes.as_deref().is_some_and(|sizes| {
+                    sizes.split_whitespace().any(|size| {
+                        size.eq_ignore_ascii_case("any")
+                            || size
+                                .to_ascii_lowercase()
+                                .split_once('x')
+                                .is_some_and(|(w, _)| w == side)
+                    })
+                })
+            })
+        };
+
+        (self.name.is_some() || self.short_name.is_some())
+            && self.display != "browser"
+            && has_size("192")
+            && has_size("512")
+    }
+}
+
+/// The web app manifest and the service worker status of the page.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PwaInfo {
+    /// The url of the linked manifest.
+    pub manifest_url: Option<String>,
+    /// The parsed manifest, `None` without a manifest link or when it failed to fetch or parse.
+    pub manifest: Option<WebAppManifest>,
+    /// The service worker status of the page.
+    pub service_worker: ServiceWorkerStatus,
+}
+
+/// The pwa signals of the page.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PwaSignals {
+    manifest: Option<String>,
+    url: String,
+    #[serde(default)]
+    service_worker: ServiceWorkerStatus,
+}
+
+fn string_field(manifest: &serde_json::Value, key: &str) -> Option<String> {
+    manifest
+        .get(key)
+        .and_then(|value| value.as_str())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Parse a web app manifest, resolving its urls the way the browser does.
+pub fn parse_manifest(
+    body: &[u8],
+    manifest_url: &str,
+    document_url: &str,
+) -> Option<WebAppManifest> {
+    let raw: serde_json::Value = serde_json::from_slice(body).ok()?;
+
+    if !raw.is_object() {
+        return None;
+    }
+
+    let manifest_base = url::Url::parse(manifest_url).ok();
+    let document = url::Url::parse(document_url).ok();
+
+    let resolve = |key: &str| -> Option<url::Url> {
+        let value = string_field(&raw, key)?;
+        manifest_base.as_ref()?.join(&value).ok()
+    };
+
+    // a start url of another origin is ignored.
+    let start_url = resolve("start_url")
+        .filter(|start| {
+            document
+                .as_ref()
+                .is_some_and(|document| document.origin() == start.origin())
+        })
+        .map(|start| start.to_string())
+        .unwrap_or_else(|| document_url.to_string());
+
+    let display = string_field(&raw, "display")
+        .map(|display| display.to_ascii_lowercase())
+        .filter(|display| DISPLAY_MODES.contains(&display.as_str()))
+        .unwrap_or_else(|| "browser".to_string());
+
+    Some(WebAppManifest {
+        name: string_field(&raw, "name"),
+        short_name: string_field(&raw, "short_name"),
+        description: string_field(&raw, "description"),
+        start_url,
+        scope: resolve("scope").map(|scope| scope.to_string()),
+        display,
+        orientation: string_field(&raw, "orientation"),
+        theme_color: string_field(&raw, "theme_color"),
+        background_color: string_field(&raw, "background_color"),
+        icons: manifest_icons(&raw, manifest_url),
+        raw,
+    })
+}
+
+/// Fetch and parse the manifest of the page.
+pub(crate) async fn resolve_pwa(signals: PwaSignals) -> PwaInfo {
+    let manifest = match &signals.manifest {
+        Some(manifest_url) => crate::utils::fetch_resource(manifest_url, FETCH_TIMEOUT)
+            .await
+            .and_then(|(body, _)| parse_manifest(&body, manifest_url, &signals.url)),
+        None => None,
+    };
+
+    PwaInfo {
+        manifest_url: signals.manifest,
+        manifest,
+        service_worker: signals.service_worker,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_manifest() {
+        let body = br##"{
+            "name": "Example App",
+            "short_name": "Example",
+            "start_url": "../?source=pwa",
+            "scope": "/",
+            "display": "Standalone",
+            "theme_color": "#000000",
+            "icons": [
+                { "src": "icon-192.png", "sizes": "192x192", "type": "image/png" },
+                { "src": "icon-512.png", "sizes": "512x512", "type": "image/png" }
+            ]
+        }"##;
+
+        let manifest = parse_manifest(
+            body,
+            "https://example.com/app/manifest.json",
+            "https://example.com/app/page",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.name.as_deref(), Some("Example App"));
+        assert_eq!(manifest.start_url, "https://example.com/?source=pwa");
+        assert_eq!(manifest.scope.as_deref(), Some("https://example.com/"));
+        assert_eq!(manifest.display, "standalone");
+        assert_eq!(
+            manifest.icons[1].url,
+            "https://example.com/app/icon-512.png"
+        );
+        assert!(manifest.is_installable());
+    }
+
+    #[test]
+    fn falls_back_like_the_browser() {
+        let body = br#"{ "name": "Other", "start_url": "https://other.com/", "display": "kiosk" }"#;
+
+        let manifest = parse_manifest(
+            body,
+            "https://cdn.example.com/manifest.json",
+            "https://example.com/",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.start_url, "https://example.com/");
+        assert_eq!(manifest.display, "browser");
+        assert!(!manifest.is_installable());
+
+        assert!(
+            parse_manifest(b"[]", "https://example.com/m.json", "https://example.com/").is_none()
+        );
+        assert!(parse_manifest(
+            b"<html>",
+            "https://example.com/m.json",
+            "https://example.com/"
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,336 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::page::{
+    CaptureScreenshotFormat, NavigateParams, Viewport,
+};
+use futures::future::{self, Either};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CdpError, Result};
+use crate::page::{Page, ScreenshotParams};
+use crate::runtime::Instant;
+
+/// The max time of a snapshot, the page may not answer while navigating.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The snapshots taken while a navigation is pending.
+#[derive(Debug, Clone)]
+pub struct NavSnapshotOptions {
+    /// The delay of the first snapshot after the navigation started.
+    pub first_delay: Duration,
+    /// The growth of the delay between two snapshots.
+    pub factor: u32,
+    /// The max snapshots taken.
+    pub max_snapshots: usize,
+    /// Attach a jpeg thumbnail of the viewport to the snapshots.
+    pub thumbnail: bool,
+    /// The scale of the thumbnails.
+    pub thumbnail_scale: f64,
+    /// The jpeg quality of the thumbnails.
+    pub thumbnail_quality: i64,
+    /// The max wait of the navigation, the request timeout of the browser when `None`.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for NavSnapshotOptions {
+    fn default() -> Self {
+        Self {
+            first_delay: Duration::from_millis(250),
+            factor: 2,
+            max_snapshots: 8,
+            thumbnail: true,
+            thumbnail_scale: 0.25,
+            thumbnail_quality: 40,
+            timeout: None,
+        }
+    }
+}
+
+impl NavSnapshotOptions {
+    /// The offsets of the snapshots from the start of the navigation: the first delay, then
+    /// doubling the gap with the default factor, e.g. 250ms, 750ms, 1.75s, 3.75s.
+    pub fn offsets(&self) -> Vec<Duration> {
+        let mut offsets = Vec::with_capacity(self.max_snapshots);
+        let mut gap = self.first_delay;
+        let mut offset = Duration::ZERO;
+
+        for _ in 0..self.max_snapshots {
+            offset = offset.saturating_add(gap);
+            offsets.push(offset);
+            gap = gap.saturating_mul(self.factor.max(1));
+        }
+
+        offsets
+    }
+}
+
+/// The state of the page at a time of a pending navigation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NavSnapshot {
+    /// The time since the navigation started.
+    pub elapsed: Duration,
+    /// The url of the page.
+    pub url: Option<String>,
+    /// The `document.readyState`, `None` when the document did not answer.
+    pub ready_state: Option<String>,
+    /// The requests started since the navigation started.
+    pub requests_started: u64,
+    /// The requests finished.
+    pub requests_finished: u64,
+    /// The requests failed.
+    pub requests_failed: u64,
+    /// The jpeg thumbnail of the viewport.
+    #[serde(skip)]
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+impl NavSnapshot {
+    /// The requests still pending.
+    pub fn requests_pending(&self) -> u64 {
+        self.requests_started
+            .saturating_sub(self.requests_finished + self.requests_failed)
+    }
+}
+
+/// A navigation timed out, with the snapshots of what the page was doing.
+#[derive(Debug, Clone, Default)]
+pub struct NavigationTimeout {
+    /// The url navigated to.
+    pub url: String,
+    /// The time until the navigation was given up.
+    pub elapsed: Duration,
+    /// The snapshots taken, the oldest first.
+    pub snapshots: Vec<NavSnapshot>,
+}
+
+impl std::fmt::Display for NavigationTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Navigation to {} timed out after {:?}",
+            self.url, self.elapsed
+        )?;
+
+        if let Some(last) = self.snapshots.last() {
+            write!(
+                f,
+                " (readyState {}, {} requests pending, at {})",
+                last.ready_state.as_deref().unwrap_or("unknown"),
+                last.requests_pending(),
+                last.url.as_deref().unwrap_or("unknown url")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The request counters of the navigation.
+#[derive(Debug, Default)]
+struct RequestCounts {
+    started: AtomicU64,
+    finished: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// The readiness of the document.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentState {
+    href: String,
+    ready_state: String,
+    width: f64,
+    height: f64,
+}
+
+impl Page {
+    /// Take a snapshot of the pending navigation.
+    async fn nav_snapshot(
+        &self,
+        started: Instant,
+        counts: &RequestCounts,
+        options: &NavSnapshotOptions,
+    ) -> NavSnapshot {
+        let state: Option<DocumentState> = crate::runtime::timeout(
+            SNAPSHOT_TIMEOUT,
+            self.evaluate_expression(
+                "({href: location.href, readyState: document.readyState, width: innerWidth, height: innerHeight})",
+            ),
+        )
+        .await
+        .ok()
+        .and_then(|result| result.ok())
+        .and_then(|result| result.into_value().ok());
+
+        let url = match state.as_ref() {
+            Some(state) => Some(state.href.clone()),
+            _ => self.url().await.ok().flatten(),
+        };
+
+        let thumbnail = match state.as_ref() {
+            Some(state) if options.thumbnail && state.width > 0.0 && state.height > 0.0 => {
+                let params = ScreenshotParams::builder()
+                    .format(CaptureScreenshotFormat::Jpeg)
+                    .quality(options.thumbnail_quality)
+                    .clip(Viewport {
+                        x: 0.,
+                        y: 0.,
+                        width: state.width,
+                        height: state.height,
+                        scale: options.thumbnail_scale,
+                    })
+                    .build();
+
+                crate::runtime::timeout(SNAPSHOT_TIMEOUT, self.screenshot(params))
+                    .await
+                    .ok()
+                    .and_then(|result| result.ok())
+            }
+            _ => None,
+        };
+
+        NavSnapshot {
+            elapsed: started.elapsed(),
+            url,
+            ready_state: state.map(|state| state.ready_state),
+            requests_started: counts.started.load(Ordering::Relaxed),
+            requests_finished: counts.finished.load(Ordering::Relaxed),
+            requests_failed: counts.failed.load(Ordering::Relaxed),
+            thumbnail,
+        }
+    }
+
+    /// Navigate to the url, taking snapshots of the page at exponentially spaced times while
+    /// the navigation is pending. When the navigation times out, the error is a
+    /// `CdpError::NavigationTimeout` with the snapshots, showing what the page was doing.
+    pub async fn goto_with_snapshots(
+        &self,
+        params: impl Into<NavigateParams>,
+        options: &NavSnapshotOptions,
+    ) -> Result<&Self> {
+        let params = params.into();
+        let url = params.url.clone();
+        let counts = Arc::new(RequestCounts::default());
+
+        let mut started_events = self.event_listener::<EventRequestWillBeSent>().await?;
+        let mut finished_events = self.event_listener::<EventLoadingFinished>().await?;
+        let mut failed_events = self.event_listener::<EventLoadingFailed>().await?;
+
+        let counter = {
+            let counts = counts.clone();
+
+            crate::runtime::spawn(async move {
+                loop {
+                    let counter = tokio::select! {
+                        Some(_) = started_events.next() => &counts.started,
+                        Some(_) = finished_events.next() => &counts.finished,
+                        Some(_) = failed_events.next() => &counts.failed,
+                        else => break,
+                    };
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+
+        let started = Instant::now();
+        let snapshots = Mutex::new(Vec::with_capacity(options.max_snapshots));
+
+        let navigate = async {
+            match options.timeout {
+                Some(timeout) => crate::runtime::timeout(timeout, self.goto(params))
+                    .await
+                    .map_err(|_| CdpError::Timeout)
+                    .and_then(|result| result.map(|_| ())),
+                _ => self.goto(params).await.map(|_| ()),
+            }
+        };
+
+        let snapshot_loop = async {
+            for offset in options.offsets() {
+                crate::runtime::sleep(offset.saturating_sub(started.elapsed())).await;
+
+                let snapshot = self.nav_snapshot(started, &counts, options).await;
+
+                if let Ok(mut snapshots) = snapshots.lock() {
+                    snapshots.push(snapshot);
+                }
+            }
+
+            future::pending::<()>().await
+        };
+
+        let result =
+            match future::select(std::pin::pin!(navigate), std::pin::pin!(snapshot_loop)).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => unreachable!("the snapshots never complete the navigation"),
+            };
+
+        counter.abort();
+
+        match result {
+            Ok(()) => Ok(self),
+            Err(CdpError::Timeout) => {
+                Err(CdpError::NavigationTimeout(Box::new(NavigationTimeout {
+                    url,
+                    elapsed: started.elapsed(),
+                    snapshots: snapshots.into_inner().unwrap_or_default(),
+                })))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_the_snapshots_exponentially() {
+        let options = NavSnapshotOptions {
+            max_snapshots: 5,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.offsets(),
+            [250, 750, 1750, 3750, 7750]
+                .into_iter()
+                .map(Duration::from_millis)
+                .collect::<Vec<_>>()
+        );
+        assert!(NavSnapshotOptions {
+            max_snapshots: 0,
+            ..Default::default()
+        }
+        .offsets()
+        .is_empty());
+    }
+
+    #[test]
+    fn describes_the_timeout() {
+        let timeout = NavigationTimeout {
+            url: "https://example.com".into(),
+            elapsed: Duration::from_secs(30),
+            snapshots: vec![NavSnapshot {
+                ready_state: Some("interactive".into()),
+                url: Some("https://example.com/".into()),
+                requests_started: 12,
+                requests_finished: 9,
+                requests_failed: 1,
+                ..Default::default()
+            }],
+        };
+
+        assert_eq!(
+            timeout.to_string(),
+            "Navigation to https://example.com timed out after 30s (readyState interactive, 2 requests pending, at https://example.com/)"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::HeaderEntry;
+use chromiumoxide_cdp::cdp::browser_protocol::network::ResourceType;
+
+use crate::error::{CdpError, Result};
+use crate::sec_fetch::{fetch_site, set_header, ShapedRequest};
+
+/// The urls a rule applies to.
+#[derive(Debug, Clone, Default)]
+pub enum UrlPattern {
+    /// Every url.
+    #[default]
+    Any,
+    /// A glob of the whole url, `*` matches any run of characters and `?` a single one, e.g.
+    /// `*://*.doubleclick.net/*`.
+    Glob(String),
+    /// A regex searched in the url.
+    Regex(regex::Regex),
+}
+
+impl UrlPattern {
+    /// The url matches the pattern.
+    pub fn matches(&self, url: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Glob(glob) => glob_matches(glob.as_bytes(), url.as_bytes()),
+            Self::Regex(regex) => regex.is_match(url),
+        }
+    }
+}
+
+/// Match the whole input against a glob, backtracking to the last `*` on a mismatch.
+fn glob_matches(glob: &[u8], input: &[u8]) -> bool {
+    let (mut g, mut i) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while i < input.len() {
+        match glob.get(g) {
+            Some(b'*') => {
+                star = Some((g, i));
+                g += 1;
+            }
+            Some(c) if *c == b'?' || c.eq_ignore_ascii_case(&input[i]) => {
+                g += 1;
+                i += 1;
+            }
+            _ => match star {
+                Some((star_g, star_i)) => {
+                    g = star_g + 1;
+                    i = star_i + 1;
+                    star = Some((star_g, star_i + 1));
+                }
+                _ => return false,
+            },
+        }
+    }
+
+    glob[g..].iter().all(|c| *c == b'*')
+}
+
+/// The requests a rule applies to. Every condition set must hold.
+#[derive(Debug, Clone, Default)]
+pub struct RuleMatcher {
+    /// The urls of the requests.
+    pub url: UrlPattern,
+    /// The resource types of the requests, every type when empty.
+    pub resource_types: Vec<ResourceType>,
+    /// The methods of the requests, case insensitive, every method when empty.
+    pub methods: Vec<String>,
+    /// The requests are cross-site to their document or not, both when `None`.
+    pub third_party: Option<bool>,
+}
+
+impl RuleMatcher {
+    /// Match every request.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    /// Match the urls of the glob.
+    pub fn glob(glob: impl Into<String>) -> Self {
+        Self {
+            url: UrlPattern::Glob(glob.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Match the urls of the regex.
+    pub fn regex(regex: &str) -> Result<Self> {
+        let regex = regex::Regex::new(regex)
+            .map_err(|err| CdpError::msg(format!("invalid network rule regex: {err}")))?;
+
+        Ok(Self {
+            url: UrlPattern::Regex(regex),
+            ..Default::default()
+        })
+    }
+
+    /// Match the resource type, on top of the previous ones.
+    pub fn resource_type(mut self, resource_type: ResourceType) -> Self {
+        self.resource_types.push(resource_type);
+        self
+    }
+
+    /// Match the method, on top of the previous ones.
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.methods.push(method.into());
+        self
+    }
+
+    /// Match the third party requests only, or the first party requests only.
+    pub fn third_party(mut self, third_party: bool) -> Self {
+        self.third_party = Some(third_party);
+        self
+    }
+
+    /// The request matches.
+    pub fn matches(&self, request: &ShapedRequest<'_>) -> bool {
+        (self.resource_types.is_empty() || self.resource_types.contains(request.resource_type))
+            && (self.methods.is_empty()
+                || self
+                    .methods
+                    .iter()
+                    .any(|m| m.eq_ignore_ascii_case(request.method)))
+            && self
+                .third_party
+                .map_or(true, |third_party| is_third_party(request) == third_party)
+            && self.url.matches(request.url)
+    }
+}
+
+/// The request is cross-site to the document that issued it. The documents are first party.
+fn is_third_party(request: &ShapedRequest<'_>) -> bool {
+    request.document_url.is_some()
+        && fetch_site(request.url, request.document_url, false) == "cross-site"
+}
+
+/// What is done with a matching request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Continue the request, bypassing the built-in blocking.
+    Allow,
+    /// Fail the request as blocked by the client.
+    Block,
+    /// Continue the request to another url, transparently to the page.
+    RedirectTo(String),
+    /// Set the headers with a value and remove the headers without, then continue the request.
+    ModifyHeaders(Vec<(String, Option<String>)>),
+    /// Answer the request without hitting the network.
+    Fulfill {
+        /// The status of the response.
+        status: u16,
+        /// The headers of the response.
+        headers: Vec<(String, String)>,
+        /// The body of the response.
+        body: Vec<u8>,
+    },
+}
+
+impl RuleAction {
+    /// Apply the header modifications to the request headers.
+    pub(crate) fn modify_headers(
+        modifications: &[(String, Option<String>)],
+        headers: &mut Vec<HeaderEntry>,
+    ) {
+        for (name, value) in modifications {
+            set_header(headers, name, value.clone());
+        }
+    }
+}
+
+/// A rule of `NetworkRules`.
+#[derive(Debug, Clone)]
+pub struct NetworkRule {
+    /// The requests the rule applies to.
+    pub matcher: RuleMatcher,
+    /// What is done with them.
+    pub action: RuleAction,
+}
+
+/// Ordered request interception rules, the first matching rule applies and the requests
+/// without a match go through the built-in blocking.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkRules {
+    /// The rules in priority order.
+    pub rules: Vec<NetworkRule>,
+}
+
+impl NetworkRules {
+    /// No rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule.
+    pub fn rule(mut self, matcher: RuleMatcher, action: RuleAction) -> Self {
+        self.rules.push(NetworkRule { matcher, action });
+        self
+    }
+
+    /// Allow the matching requests.
+    pub fn allow(self, matcher: RuleMatcher) -> Self {
+        self.rule(matcher, RuleAction::Allow)
+    }
+
+    /// Block the matching requests.
+    pub fn block(self, matcher: RuleMatcher) -> Self {
+        self.rule(matcher, RuleAction::Block)
+    }
+
+    /// Send the matching requests to another url.
+    pub fn redirect_to(self, matcher: RuleMatcher, url: impl Into<String>) -> Self {
+        self.rule(matcher, RuleAction::RedirectTo(url.into()))
+    }
+
+    /// Set or remove headers of the matching requests.
+    pub fn modify_headers<I, K>(self, matcher: RuleMatcher, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, Option<String>)>,
+        K: Into<String>,
+    {
+        self.rule(
+            matcher,
+            RuleAction::ModifyHeaders(
+                headers
+                    .into_iter()
+                    .map(|(name, value)| (name.into(), value))
+                    .collect(),
+            ),
+        )
+    }
+
+    /// Answer the matching requests with a response.
+    pub fn fulfill(
+        self,
+        matcher: RuleMatcher,
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.rule(
+            matcher,
+            RuleAction::Fulfill {
+                status,
+                headers,
+                body: body.into(),
+            },
+        )
+    }
+
+    /// The action of the first rule matching the request.
+    pub fn evaluate(&self, request: &ShapedRequest<'_>) -> Option<&RuleAction> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matcher.matches(request))
+            .map(|rule| &rule.action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request<'a>(
+        url: &'a str,
+        resource_type: &'a ResourceType,
+        document_url: Option<&'a str>,
+    ) -> ShapedRequest<'a> {
+        ShapedRequest {
+            url,
+            method: "GET",
+            resource_type,
+            document_url,
+        }
+    }
+
+    #[test]
+    fn matches_the_globs() {
+        assert!(glob_matches(
+            b"*://*.doubleclick.net/*",
+            b"https://ad.doubleclick.net/pixel?x=1"
+        ));
+        assert!(glob_matches(
+            b"https://a.com/*.JS",
+            b"https://a.com/x/app.js"
+        ));
+        assert!(glob_matches(b"https://a.com/?", b"https://a.com/a"));
+        assert!(!glob_matches(
+            b"https://a.com/*.js",
+            b"https://a.com/app.css"
+        ));
+        assert!(!glob_matches(b"*://b.com/*", b"https://a.com/?r=b.com"));
+    }
+
+    #[test]
+    fn applies_the_first_matching_rule() {
+        let rules = NetworkRules::new()
+            .allow(RuleMatcher::glob("*://cdn.example.com/*"))
+            .block(
+                RuleMatcher::any()
+                    .resource_type(ResourceType::Script)
+                    .third_party(true),
+            )
+            .redirect_to(
+                RuleMatcher::regex(r"/old/").unwrap().method("get"),
+                "https://example.com/new/",
+            );
+
+        let script = ResourceType::Script;
+        let document = Some("https://www.example.com/");
+
+        assert_eq!(
+            rules.evaluate(&request("https://cdn.example.com/a.js", &script, document)),
+            Some(&RuleAction::Allow)
+        );
+        assert_eq!(
+            rules.evaluate(&request("https://tracker.io/t.js", &script, document)),
+            Some(&RuleAction::Block)
+        );
+        assert_eq!(
+            rules.evaluate(&request(
+                "https://static.example.com/t.js",
+                &script,
+                document
+            )),
+            None
+        );
+        assert_eq!(
+            rules.evaluate(&request(
+                "https://example.com/old/a",
+                &ResourceType::Document,
+                None
+            )),
+            Some(&RuleAction::RedirectTo("https://example.com/new/".into()))
+        );
+        assert!(RuleMatcher::regex("(").is_err());
+    }
+
+    #[test]
+    fn modifies_the_headers() {
+        let mut headers = vec![
+            HeaderEntry::new("Cookie", "a=1"),
+            HeaderEntry::new("Accept", "*/*"),
+        ];
+
+        RuleAction::modify_headers(
+            &[
+                ("cookie".into(), None),
+                ("X-Crawler".into(), Some("chromey".into())),
+            ],
+            &mut headers,
+        );
+
+        assert_eq!(
+            headers,
+            vec![
+                HeaderEntry::new("Accept", "*/*"),
+                HeaderEntry::new("X-Crawler", "chromey"),
+            ]
+        );
+    }
+}
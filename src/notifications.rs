@@ -0,0 +1,197 @@
+use chromiumoxide_cdp::cdp::browser_protocol::browser::{GrantPermissionsParams, PermissionType};
+use chromiumoxide_cdp::cdp::browser_protocol::page::AddScriptToEvaluateOnNewDocumentParams;
+use chromiumoxide_cdp::cdp::browser_protocol::target::GetTargetInfoParams;
+use chromiumoxide_cdp::cdp::js_protocol::runtime::{AddBindingParams, EventBindingCalled};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::page::Page;
+
+/// The binding receiving the notifications shown by the page.
+pub(crate) const NOTIFICATIONS_BINDING: &str = "__chromey_notifications";
+
+/// Hook the `Notification` constructor and `ServiceWorkerRegistration.showNotification`, and
+/// stub the push subscriptions when `__PUSH__` is a subscription.
+const NOTIFICATIONS_JS: &str = r###"(()=>{if(window.__chromeyNotifications)return;window.__chromeyNotifications=1;const push=__PUSH__;const send=(s,t,o)=>{try{const b=window['__chromey_notifications'];if(typeof b!=='function')return;o=o||{};b(JSON.stringify({source:s,title:String(t),body:o.body==null?null:String(o.body),icon:o.icon==null?null:String(o.icon),tag:o.tag==null?null:String(o.tag),data:o.data===undefined?null:o.data,url:location.href,timestamp:Date.now()}))}catch(e){}};const N=window.Notification;if(typeof N==='function'){const H=class Notification extends N{constructor(t,o){send('window',t,o);super(t,o)}};Object.defineProperty(window,'Notification',{value:H,writable:true,configurable:true})}const R=window.ServiceWorkerRegistration;if(R&&R.prototype.showNotification){const f=R.prototype.showNotification;R.prototype.showNotification=function(t,o){send('service_worker',t,o);return f.apply(this,arguments).catch(()=>undefined)}}const M=window.PushManager;if(push&&M){const k=s=>{const b=atob(s.replace(/-/g,'+').replace(/_/g,'/').padEnd(Math.ceil(s.length/4)*4,'='));const a=new Uint8Array(b.length);for(let i=0;i<b.length;i++)a[i]=b.charCodeAt(i);return a.buffer};let sub=null;const make=o=>{const s={endpoint:push.endpoint,expirationTime:null,options:o||{userVisibleOnly:true},getKey:n=>n==='p256dh'?k(push.p256dh):n==='auth'?k(push.auth):null,toJSON:()=>({endpoint:push.endpoint,expirationTime:null,keys:{p256dh:push.p256dh,auth:push.auth}}),unsubscribe:()=>{sub=null;return Promise.resolve(true)}};return s};M.prototype.subscribe=function(o){sub=sub||make(o);return Promise.resolve(sub)};M.prototype.getSubscription=function(){return Promise.resolve(sub)};M.prototype.permissionState=function(){return Promise.resolve('granted')}}})()"###;
+
+/// Where a notification was shown from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationSource {
+    /// `new Notification(..)`.
+    Window,
+    /// `registration.showNotification(..)` called by the page.
+    ServiceWorker,
+}
+
+/// A notification shown by the page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShownNotification {
+    /// Where the notification was shown from.
+    pub source: NotificationSource,
+    /// The title of the notification.
+    pub title: String,
+    /// The body of the notification.
+    #[serde(default)]
+    pub body: Option<String>,
+    /// The url of the icon.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// The tag replacing the notifications of the same tag.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// The data attached to the notification.
+    #[serde(default)]
+    pub data: serde_json::Value,
+    /// The url of the page showing the notification.
+    pub url: String,
+    /// The time the notification was shown in milliseconds since the epoch.
+    pub timestamp: f64,
+}
+
+impl ShownNotification {
+    /// The notification reported to the binding.
+    pub(crate) fn from_binding(event: &EventBindingCalled) -> Option<Self> {
+        if event.name == NOTIFICATIONS_BINDING {
+            serde_json::from_str(&event.payload).ok()
+        } else {
+            None
+        }
+    }
+}
+
+/// The push subscription handed to the page by the stubbed `PushManager`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushSubscriptionStub {
+    /// The push endpoint.
+    pub endpoint: String,
+    /// The base64url P-256 public key of the subscription.
+    pub p256dh: String,
+    /// The base64url authentication secret of the subscription.
+    pub auth: String,
+}
+
+impl Default for PushSubscriptionStub {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://push.chromey.invalid/subscription".into(),
+            p256dh: "BEl62iUYgUivxIkv69yViEuiBIa-Ib9-SkvMeAtA3LFgDzkrxZJjSgSnfckjBJuBkr3qBUYIHBQFLXYp5Nksh8U".into(),
+            auth: "tBHItJI5svbpez7KI4CCXg".into(),
+        }
+    }
+}
+
+/// How the notifications of a page are handled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NotificationOptions {
+    /// Grant the notification permission to the page.
+    pub grant_permission: bool,
+    /// Stub the push subscriptions with this subscription, no push service is contacted.
+    pub push: Option<PushSubscriptionStub>,
+}
+
+impl NotificationOptions {
+    /// Grant the notification permission.
+    pub fn granted() -> Self {
+        Self {
+            grant_permission: true,
+            push: None,
+        }
+    }
+
+    /// Stub the push subscriptions.
+    pub fn with_push(mut self, push: PushSubscriptionStub) -> Self {
+        self.push = Some(push);
+        self
+    }
+
+    /// The script hooking the notifications of the page.
+    pub(crate) fn script(&self) -> String {
+        let push = self
+            .push
+            .as_ref()
+            .and_then(|push| serde_json::to_string(push).ok())
+            .unwrap_or_else(|| "null".into());
+
+        NOTIFICATIONS_JS.replacen("__PUSH__", &push, 1)
+    }
+}
+
+impl Page {
+    /// Grant the notification permission to the origin, or every origin, in the browser context
+    /// of the page.
+    pub async fn grant_notifications(&self, origin: Option<&str>) -> Result<&Self> {
+        let browser_context_id = self
+            .execute(GetTargetInfoParams {
+                target_id: Some(self.target_id().clone()),
+            })
+            .await?
+            .result
+            .target_info
+            .browser_context_id;
+
+        self.execute(GrantPermissionsParams {
+            permissions: vec![PermissionType::Notifications],
+            origin: origin.map(str::to_string),
+            browser_context_id,
+        })
+        .await?;
+
+        Ok(self)
+    }
+
+    /// Capture the notifications shown by the page, from now on and on every new document.
+    /// Notifications shown inside a service worker, e.g. on a push event, are not captured.
+    pub async fn capture_notifications(
+        &self,
+        options: &NotificationOptions,
+    ) -> Result<impl futures::Stream<Item = ShownNotification> + Unpin> {
+        if options.grant_permission {
+            self.grant_notifications(None).await?;
+        }
+
+        let events = self.event_listener::<EventBindingCalled>().await?;
+        let script = options.script();
+
+        self.send_command(AddBindingParams::new(NOTIFICATIONS_BINDING))
+            .await?;
+        self.send_command(AddScriptToEvaluateOnNewDocumentParams::new(script.clone()))
+            .await?;
+        self.evaluate_expression(script).await?;
+
+        Ok(events
+            .filter_map(|event| futures::future::ready(ShownNotification::from_binding(&event))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chromiumoxide_cdp::cdp::js_protocol::runtime::ExecutionContextId;
+
+    #[test]
+    fn parses_the_notifications() {
+        let event = EventBindingCalled {
+            name: NOTIFICATIONS_BINDING.into(),
+            payload: r#"{"source":"service_worker","title":"New message","body":"Hi","icon":null,"tag":"chat","data":{"id":7},"url":"https://a.com/inbox","timestamp":1700000000000}"#.into(),
+            execution_context_id: ExecutionContextId::new(1),
+        };
+        let notification = ShownNotification::from_binding(&event).unwrap();
+
+        assert_eq!(notification.source, NotificationSource::ServiceWorker);
+        assert_eq!(notification.tag.as_deref(), Some("chat"));
+        assert_eq!(notification.data["id"], 7);
+    }
+
+    #[test]
+    fn stubs_the_push_subscriptions() {
+        assert!(NotificationOptions::granted()
+            .script()
+            .contains("const push=null;"));
+        assert!(NotificationOptions::default()
+            .with_push(PushSubscriptionStub::default())
+            .script()
+            .contains(r#"const push={"endpoint":"https://push.chromey.invalid/subscription""#));
+    }
+}
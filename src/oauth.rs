@@ -0,0 +1,688 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::EventRequestWillBeSent;
+use futures::future::{self, BoxFuture, Either, FutureExt};
+use futures::StreamExt;
+use reqwest::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Credentials;
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+use crate::runtime::Instant;
+
+/// How often the login page is checked for a form, a redirect or stored tokens.
+const LOGIN_POLL: Duration = Duration::from_millis(500);
+
+/// The forms submitted before the login is considered rejected.
+const MAX_SUBMITS: usize = 6;
+
+/// The timeout of the requests of the token endpoints.
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The device flow grant of RFC 8628.
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Find the tokens an SPA keeps in the web storage, as a JSON token response or an
+/// `access_token` entry.
+const STORAGE_TOKENS_JS: &str = r###"(()=>{const f=v=>{try{const o=JSON.parse(v);if(o&&typeof o==='object'){const t=o.access_token||o.accessToken;if(typeof t==='string')return{access_token:t,refresh_token:o.refresh_token||o.refreshToken||null,id_token:o.id_token||o.idToken||null,token_type:o.token_type||o.tokenType||null,expires_in:typeof o.expires_in==='number'?Math.floor(o.expires_in):null,scope:typeof o.scope==='string'?o.scope:null}}}catch(e){}return null};for(const s of[window.localStorage,window.sessionStorage]){try{for(let i=0;i<s.length;i++){const k=s.key(i),v=s.getItem(k),t=f(v);if(t)return t;if(/access[_-]?token/i.test(k)&&v&&!/\s/.test(v))return{access_token:v}}}catch(e){}}return null})()"###;
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The tokens of an OAuth login, for the API requests of the crawl.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthTokens {
+    /// The access token.
+    pub access_token: String,
+    /// The type of the access token, `Bearer` when not given.
+    #[serde(default)]
+    pub token_type: Option<String>,
+    /// The refresh token.
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// The OpenID Connect id token.
+    #[serde(default)]
+    pub id_token: Option<String>,
+    /// The lifetime of the access token in seconds.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    /// The granted scopes.
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// When the tokens were obtained, in seconds since the unix epoch.
+    #[serde(default)]
+    pub obtained_at: Option<u64>,
+}
+
+impl OAuthTokens {
+    fn stamped(mut self) -> Self {
+        self.obtained_at.get_or_insert_with(unix_now);
+        self
+    }
+
+    /// The access token expired, or expires within the leeway.
+    pub fn is_expired(&self, leeway: Duration) -> bool {
+        match (self.obtained_at, self.expires_in) {
+            (Some(obtained_at), Some(expires_in)) => {
+                obtained_at + expires_in <= unix_now() + leeway.as_secs()
+            }
+            _ => false,
+        }
+    }
+
+    /// The value of the `Authorization` header.
+    pub fn authorization(&self) -> String {
+        match self.token_type.as_deref() {
+            Some(token_type) if !token_type.eq_ignore_ascii_case("bearer") => {
+                format!("{token_type} {}", self.access_token)
+            }
+            _ => format!("Bearer {}", self.access_token),
+        }
+    }
+
+    /// Authorize the API request with the access token.
+    pub fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        request.header(AUTHORIZATION, self.authorization())
+    }
+}
+
+/// What the redirect of an authorization carried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectCapture {
+    /// The tokens of an implicit or hybrid flow.
+    Tokens(OAuthTokens),
+    /// The authorization code to exchange at the token endpoint.
+    Code {
+        /// The authorization code.
+        code: String,
+        /// The state echoed by the provider.
+        state: Option<String>,
+    },
+    /// The provider denied the authorization.
+    Error(String),
+}
+
+/// The tokens, code or error of the redirect url, from the fragment or the query. `None` when
+/// the url carries none of them.
+pub fn parse_redirect(url: &str) -> Option<RedirectCapture> {
+    let url = url::Url::parse(url).ok()?;
+    let params: Vec<(String, String)> = url
+        .fragment()
+        .map(|fragment| url::form_urlencoded::parse(fragment.as_bytes()))
+        .into_iter()
+        .flatten()
+        .chain(url.query_pairs())
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    if let Some(error) = param("error") {
+        return Some(RedirectCapture::Error(match param("error_description") {
+            Some(description) => format!("{error}: {description}"),
+            _ => error,
+        }));
+    }
+
+    if let Some(access_token) = param("access_token") {
+        return Some(RedirectCapture::Tokens(
+            OAuthTokens {
+                access_token,
+                token_type: param("token_type"),
+                refresh_token: param("refresh_token"),
+                id_token: param("id_token"),
+                expires_in: param("expires_in").and_then(|v| v.parse().ok()),
+                scope: param("scope"),
+                obtained_at: None,
+            }
+            .stamped(),
+        ));
+    }
+
+    param("code").map(|code| RedirectCapture::Code {
+        code,
+        state: param("state"),
+    })
+}
+
+/// The device authorization of RFC 8628, the user code is entered at the verification uri.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DeviceAuthorization {
+    /// The code polled for the tokens.
+    pub device_code: String,
+    /// The code the user enters.
+    pub user_code: String,
+    /// The page the user code is entered on.
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+    /// The page with the user code filled in.
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    /// The lifetime of the codes in seconds.
+    pub expires_in: u64,
+    /// The min seconds between two polls.
+    #[serde(default)]
+    pub interval: Option<u64>,
+}
+
+/// The reply of a token endpoint.
+#[derive(Debug)]
+enum TokenReply {
+    Tokens(OAuthTokens),
+    Pending,
+    SlowDown,
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+impl TokenReply {
+    fn parse(body: &str) -> Result<Self> {
+        if let Ok(tokens) = serde_json::from_str::<OAuthTokens>(body) {
+            return Ok(Self::Tokens(tokens.stamped()));
+        }
+
+        let error: TokenError = serde_json::from_str(body)?;
+
+        match error.error.as_str() {
+            "authorization_pending" => Ok(Self::Pending),
+            "slow_down" => Ok(Self::SlowDown),
+            _ => Err(CdpError::msg(match error.error_description {
+                Some(description) => format!("{}: {description}", error.error),
+                _ => error.error,
+            })),
+        }
+    }
+}
+
+/// An OAuth client registered with the provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OAuthClient {
+    /// The id of the client.
+    pub client_id: String,
+    /// The secret of a confidential client.
+    pub client_secret: Option<String>,
+    /// The requested scopes, space separated.
+    pub scope: Option<String>,
+    /// The token endpoint.
+    pub token_endpoint: String,
+    /// The device authorization endpoint, required by the device flow.
+    pub device_authorization_endpoint: Option<String>,
+}
+
+impl OAuthClient {
+    /// A public client of the token endpoint.
+    pub fn new(client_id: impl Into<String>, token_endpoint: impl Into<String>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            token_endpoint: token_endpoint.into(),
+            ..Default::default()
+        }
+    }
+
+    async fn post_form(&self, endpoint: &str, mut params: Vec<(&str, &str)>) -> Result<String> {
+        params.push(("client_id", &self.client_id));
+        if let Some(secret) = self.client_secret.as_deref() {
+            params.push(("client_secret", secret));
+        }
+
+        crate::http_client::HTTP_CLIENT
+            .post(endpoint)
+            .timeout(TOKEN_TIMEOUT)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| CdpError::msg(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| CdpError::msg(e.to_string()))
+    }
+
+    async fn token_request(&self, params: Vec<(&str, &str)>) -> Result<TokenReply> {
+        TokenReply::parse(&self.post_form(&self.token_endpoint, params).await?)
+    }
+
+    async fn tokens(&self, params: Vec<(&str, &str)>) -> Result<OAuthTokens> {
+        match self.token_request(params).await? {
+            TokenReply::Tokens(tokens) => Ok(tokens),
+            reply => Err(CdpError::msg(format!(
+                "unexpected reply of the token endpoint: {reply:?}"
+            ))),
+        }
+    }
+
+    /// Exchange the authorization code of a redirect for the tokens, with the PKCE verifier
+    /// of the authorization when used.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<OAuthTokens> {
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+        ];
+        if let Some(verifier) = code_verifier {
+            params.push(("code_verifier", verifier));
+        }
+
+        self.tokens(params).await
+    }
+
+    /// Refresh the tokens, the refresh token is kept when the provider does not rotate it.
+    pub async fn refresh(&self, tokens: &OAuthTokens) -> Result<OAuthTokens> {
+        let refresh_token = tokens
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| CdpError::msg("the tokens have no refresh token"))?;
+
+        let mut refreshed = self
+            .tokens(vec![
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .await?;
+
+        if refreshed.refresh_token.is_none() {
+            refreshed.refresh_token = tokens.refresh_token.clone();
+        }
+
+        Ok(refreshed)
+    }
+
+    /// Start a device authorization.
+    pub async fn request_device_code(&self) -> Result<DeviceAuthorization> {
+        let endpoint = self
+            .device_authorization_endpoint
+            .as_deref()
+            .ok_or_else(|| CdpError::msg("the client has no device authorization endpoint"))?;
+
+        let mut params = Vec::new();
+        if let Some(scope) = self.scope.as_deref() {
+            params.push(("scope", scope));
+        }
+
+        Ok(serde_json::from_str(
+            &self.post_form(endpoint, params).await?,
+        )?)
+    }
+
+    /// Poll the token endpoint until the device authorization is approved, denied or expired.
+    pub async fn poll_device_token(&self, device: &DeviceAuthorization) -> Result<OAuthTokens> {
+        let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+        let mut interval = Duration::from_secs(device.interval.unwrap_or(5).max(1));
+
+        while Instant::now() < deadline {
+            crate::runtime::sleep(interval).await;
+
+            match self
+                .token_request(vec![
+                    ("grant_type", DEVICE_CODE_GRANT),
+                    ("device_code", &device.device_code),
+                ])
+                .await?
+            {
+                TokenReply::Tokens(tokens) => return Ok(tokens),
+                TokenReply::Pending => (),
+                TokenReply::SlowDown => interval += Duration::from_secs(5),
+            }
+        }
+
+        Err(CdpError::msg("the device authorization expired"))
+    }
+}
+
+/// The answers of the login pages, e.g. from a secret store or an operator.
+pub trait LoginCallbacks: Send + Sync {
+    /// The credentials of the login form.
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>>;
+
+    /// The code of the second factor, asked once the login shows a code field.
+    fn mfa_code(&self) -> BoxFuture<'_, Result<String>> {
+        future::ready(Err(CdpError::msg(
+            "the login asked for a second factor without a mfa callback",
+        )))
+        .boxed()
+    }
+}
+
+impl LoginCallbacks for Credentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        future::ready(Ok(self.clone())).boxed()
+    }
+}
+
+/// The selectors of the fields of the login pages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoginForm {
+    /// The username or email field.
+    pub username: String,
+    /// The password field.
+    pub password: String,
+    /// The code field of the second factor.
+    pub mfa: String,
+    /// The user code field of the device verification page.
+    pub user_code: String,
+    /// The submit button, `Enter` is pressed in the last field when missing.
+    pub submit: String,
+}
+
+impl Default for LoginForm {
+    fn default() -> Self {
+        Self {
+            username: "input[type=email],input[name=username],input[name=login],input[name=email],input[autocomplete=username]".into(),
+            password: "input[type=password]".into(),
+            mfa: "input[autocomplete=one-time-code],input[name=otp],input[name=totp],input[name=mfa_code]".into(),
+            user_code: "input[name=user_code],input[name=usercode],input[name=otc]".into(),
+            submit: "button[type=submit],input[type=submit]".into(),
+        }
+    }
+}
+
+/// The empty visible fields of the login page.
+#[derive(Debug, Default, Deserialize)]
+struct EmptyFields {
+    username: bool,
+    password: bool,
+    mfa: bool,
+    user_code: bool,
+}
+
+/// How a login in a page is driven.
+#[derive(Debug, Clone)]
+pub struct OAuthLogin {
+    /// The redirect uri of the client, the tokens or the code are captured from the
+    /// navigations starting with it.
+    pub redirect_uri: String,
+    /// The client exchanging an authorization code.
+    pub client: Option<OAuthClient>,
+    /// The PKCE verifier of the authorization url.
+    pub code_verifier: Option<String>,
+    /// The selectors of the login pages.
+    pub form: LoginForm,
+    /// Capture the tokens an SPA keeps in the web storage.
+    pub capture_storage: bool,
+    /// The max duration of the login.
+    pub timeout: Duration,
+}
+
+impl OAuthLogin {
+    /// A login redirecting to the uri.
+    pub fn new(redirect_uri: impl Into<String>) -> Self {
+        Self {
+            redirect_uri: redirect_uri.into(),
+            client: None,
+            code_verifier: None,
+            form: LoginForm::default(),
+            capture_storage: true,
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+/// The progress of a login driven in a page.
+#[derive(Debug, Default)]
+struct LoginDriver {
+    credentials: Option<Credentials>,
+    submits: usize,
+}
+
+impl LoginDriver {
+    /// Fill the empty visible fields of the login page and submit them.
+    async fn step(
+        &mut self,
+        page: &Page,
+        form: &LoginForm,
+        callbacks: &dyn LoginCallbacks,
+        user_code: Option<&str>,
+    ) -> Result<()> {
+        let fields: EmptyFields = page
+            .evaluate_isolated(format!(
+                r###"(f=>{{const v=s=>{{const e=document.querySelector(s);if(!e||e.disabled||e.value)return false;const r=e.getBoundingClientRect();return r.width>0&&r.height>0}};return{{username:v(f.username),password:v(f.password),mfa:v(f.mfa),user_code:v(f.user_code)}}}})({})"###,
+                serde_json::to_string(form)?
+            ))
+            .await?
+            .into_value()
+            .unwrap_or_default();
+
+        let mut filled = Vec::new();
+
+        if fields.user_code {
+            if let Some(user_code) = user_code {
+                filled.push((&form.user_code, user_code.to_string()));
+            }
+        }
+
+        if fields.username || fields.password {
+            if self.credentials.is_none() {
+                self.credentials = Some(callbacks.credentials().await?);
+            }
+            if let Some(credentials) = &self.credentials {
+                if fields.username {
+                    filled.push((&form.username, credentials.username.clone()));
+                }
+                if fields.password {
+                    filled.push((&form.password, credentials.password.clone()));
+                }
+            }
+        }
+
+        if fields.mfa {
+            filled.push((&form.mfa, callbacks.mfa_code().await?));
+        }
+
+        let Some((last, _)) = filled.last() else {
+            return Ok(());
+        };
+        let last = (*last).clone();
+
+        self.submits += 1;
+        if self.submits > MAX_SUBMITS {
+            return Err(CdpError::msg("the login form was rejected"));
+        }
+
+        for (selector, value) in &filled {
+            page.find_element(selector.as_str())
+                .await?
+                .click()
+                .await?
+                .type_str(value)
+                .await?;
+        }
+
+        match page.find_element(form.submit.as_str()).await {
+            Ok(submit) => {
+                submit.click().await?;
+            }
+            _ => {
+                page.find_element(last).await?.press_key("Enter").await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Page {
+    /// Log in at the authorization url of an OAuth or OpenID Connect provider, filling the login
+    /// pages with the answers of the callbacks. The tokens are captured from the redirect to the
+    /// redirect uri, exchanging its code with the client of the login, or from the web storage.
+    pub async fn oauth_login(
+        &self,
+        authorize_url: &str,
+        login: &OAuthLogin,
+        callbacks: &dyn LoginCallbacks,
+    ) -> Result<OAuthTokens> {
+        let redirected: Arc<Mutex<Option<String>>> = Default::default();
+        let mut requests = self.event_listener::<EventRequestWillBeSent>().await?;
+        let listener = {
+            let (redirected, redirect_uri) = (redirected.clone(), login.redirect_uri.clone());
+
+            crate::runtime::spawn(async move {
+                while let Some(event) = requests.next().await {
+                    if event.request.url.starts_with(&redirect_uri) {
+                        if let Ok(mut redirected) = redirected.lock() {
+                            redirected.get_or_insert_with(|| event.request.url.clone());
+                        }
+                    }
+                }
+            })
+        };
+
+        let result = crate::runtime::timeout(login.timeout, async {
+            self.goto(authorize_url).await?;
+
+            let mut driver = LoginDriver::default();
+
+            loop {
+                let redirect = redirected.lock().ok().and_then(|url| url.clone());
+
+                if let Some(url) = redirect {
+                    return match parse_redirect(&url) {
+                        Some(RedirectCapture::Tokens(tokens)) => Ok(tokens),
+                        Some(RedirectCapture::Code { code, .. }) => match &login.client {
+                            Some(client) => {
+                                client
+                                    .exchange_code(
+                                        &code,
+                                        &login.redirect_uri,
+                                        login.code_verifier.as_deref(),
+                                    )
+                                    .await
+                            }
+                            _ => Err(CdpError::msg(
+                                "the login redirected with a code without a client to exchange it",
+                            )),
+                        },
+                        Some(RedirectCapture::Error(error)) => Err(CdpError::msg(error)),
+                        None => Err(CdpError::msg(format!(
+                            "the login redirected without tokens: {url}"
+                        ))),
+                    };
+                }
+
+                if login.capture_storage {
+                    if let Ok(Some(tokens)) = self
+                        .evaluate_isolated(STORAGE_TOKENS_JS)
+                        .await
+                        .and_then(|v| Ok(v.into_value::<Option<OAuthTokens>>()?))
+                    {
+                        return Ok(tokens.stamped());
+                    }
+                }
+
+                driver.step(self, &login.form, callbacks, None).await?;
+                crate::runtime::sleep(LOGIN_POLL).await;
+            }
+        })
+        .await;
+
+        listener.abort();
+
+        result.map_err(|_| CdpError::Timeout)?
+    }
+
+    /// Log in with the device flow of the client: the verification page is opened in the page
+    /// and filled with the user code and the answers of the callbacks, while the token endpoint
+    /// is polled until the authorization is approved.
+    pub async fn oauth_device_login(
+        &self,
+        client: &OAuthClient,
+        form: &LoginForm,
+        callbacks: &dyn LoginCallbacks,
+    ) -> Result<OAuthTokens> {
+        let device = client.request_device_code().await?;
+
+        self.goto(
+            device
+                .verification_uri_complete
+                .as_deref()
+                .unwrap_or(&device.verification_uri),
+        )
+        .await?;
+
+        let user_code = device
+            .verification_uri_complete
+            .is_none()
+            .then_some(device.user_code.as_str());
+
+        let poll = std::pin::pin!(client.poll_device_token(&device));
+        let drive = std::pin::pin!(async {
+            let mut driver = LoginDriver::default();
+
+            loop {
+                driver.step(self, form, callbacks, user_code).await?;
+                crate::runtime::sleep(LOGIN_POLL).await;
+            }
+        });
+
+        match future::select(poll, drive).await {
+            Either::Left((tokens, _)) => tokens,
+            Either::Right((error, _)) => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_redirects() {
+        let Some(RedirectCapture::Tokens(tokens)) = parse_redirect(
+            "https://app.example.com/cb#access_token=abc&token_type=bearer&expires_in=3600",
+        ) else {
+            panic!("no tokens");
+        };
+        assert_eq!(tokens.access_token, "abc");
+        assert_eq!(tokens.authorization(), "Bearer abc");
+        assert!(!tokens.is_expired(Duration::from_secs(60)));
+        assert!(tokens.is_expired(Duration::from_secs(7200)));
+
+        assert_eq!(
+            parse_redirect("https://app.example.com/cb?code=xyz&state=s1"),
+            Some(RedirectCapture::Code {
+                code: "xyz".into(),
+                state: Some("s1".into())
+            })
+        );
+        assert_eq!(
+            parse_redirect("https://app.example.com/cb?error=access_denied&error_description=no"),
+            Some(RedirectCapture::Error("access_denied: no".into()))
+        );
+        assert_eq!(parse_redirect("https://app.example.com/cb"), None);
+    }
+
+    #[test]
+    fn parses_the_token_replies() {
+        assert!(matches!(
+            TokenReply::parse(r#"{"access_token":"a","refresh_token":"r","expires_in":60}"#),
+            Ok(TokenReply::Tokens(tokens)) if tokens.refresh_token.as_deref() == Some("r")
+        ));
+        assert!(matches!(
+            TokenReply::parse(r#"{"error":"authorization_pending"}"#),
+            Ok(TokenReply::Pending)
+        ));
+        assert!(matches!(
+            TokenReply::parse(r#"{"error":"slow_down"}"#),
+            Ok(TokenReply::SlowDown)
+        ));
+        assert!(TokenReply::parse(r#"{"error":"access_denied"}"#).is_err());
+    }
+}
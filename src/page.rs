@@ -11,8 +11,9 @@ use chromiumoxide_cdp::cdp::browser_protocol::emulation::{
 };
 use chromiumoxide_cdp::cdp::browser_protocol::input::{DispatchDragEventType, DragData};
 use chromiumoxide_cdp::cdp::browser_protocol::network::{
-    Cookie, CookieParam, DeleteCookiesParams, GetCookiesParams, SetBlockedUrLsParams,
-    SetCookiesParams, SetExtraHttpHeadersParams, SetUserAgentOverrideParams, TimeSinceEpoch,
+    Cookie, CookieParam, DeleteCookiesParams, GetCookiesParams, GetResponseBodyParams,
+    SetBlockedUrLsParams, SetCookiesParams, SetExtraHttpHeadersParams, SetUserAgentOverrideParams,
+    TimeSinceEpoch,
 };
 use chromiumoxide_cdp::cdp::browser_protocol::page::*;
 use chromiumoxide_cdp::cdp::browser_protocol::performance::{GetMetricsParams, Metric};
@@ -32,6 +33,7 @@ use futures::{stream, SinkExt, StreamExt};
 use spider_fingerprint::configs::{AgentOs, Tier};
 
 use crate::auth::Credentials;
+use crate::cdp_stream::CdpStream;
 use crate::element::Element;
 use crate::error::{CdpError, Result};
 use crate::handler::commandfuture::CommandFuture;
@@ -40,7 +42,7 @@ use crate::handler::httpfuture::HttpFuture;
 use crate::handler::target::{GetName, GetParent, GetUrl, TargetMessage};
 use crate::handler::PageInner;
 use crate::javascript::extract::{generate_marker_js, FULL_XML_SERIALIZER_JS, OUTER_HTML};
-use crate::js::{Evaluation, EvaluationResult};
+use crate::js::{is_syntax_error, Evaluation, EvaluationResult, ResultLimit};
 use crate::layout::{Delta, Point, ScrollBehavior};
 use crate::listeners::{EventListenerRequest, EventStream};
 use crate::{utils, ArcHttpRequest};
@@ -89,6 +91,11 @@ pub struct Page {
 }
 
 impl Page {
+    /// The inner page handle.
+    pub(crate) fn inner(&self) -> &Arc<PageInner> {
+        &self.inner
+    }
+
     /// Add a custom script to eval on new document immediately.
     pub async fn add_script_to_evaluate_immediately_on_new_document(
         &self,
@@ -262,6 +269,18 @@ impl Page {
         Ok(self)
     }
 
+    /// Run a battery of known headless and bot checks in the main world of the page, e.g. the
+    /// webdriver flag, CDP runtime leaks, plugin and codec consistency and timezone or locale
+    /// mismatches, scoring how well the current stealth profile holds up.
+    pub async fn stealth_selftest(&self) -> Result<crate::selftest::SelfTestReport> {
+        let checks: Vec<crate::selftest::SelfTestCheck> = self
+            .evaluate(crate::selftest::STEALTH_SELFTEST_JS)
+            .await?
+            .into_value()?;
+
+        Ok(crate::selftest::SelfTestReport::new(checks))
+    }
+
     /// Enable page Content Security Policy by-passing.
     pub async fn set_bypass_csp(&self, enabled: bool) -> Result<&Self> {
         self.inner.set_bypass_csp(enabled).await?;
@@ -448,6 +467,22 @@ impl Page {
         Ok(())
     }
 
+    /// Track History API navigations (`pushState`, `replaceState` and `popstate`). Each url change
+    /// updates `page.url()` and emits `PageEvent::SoftNavigation`, see `page.event_listener::<PageEvent>()`.
+    pub async fn enable_soft_navigation_tracking(&self) -> Result<&Self> {
+        self.send_command(AddBindingParams::new(
+            crate::events::SOFT_NAVIGATION_BINDING,
+        ))
+        .await?;
+        self.send_command(AddScriptToEvaluateOnNewDocumentParams::new(
+            crate::events::SOFT_NAVIGATION_JS,
+        ))
+        .await?;
+        self.evaluate_expression(crate::events::SOFT_NAVIGATION_JS)
+            .await?;
+        Ok(self)
+    }
+
     /// This resolves once the navigation finished and the page is loaded.
     ///
     /// This is necessary after an interaction with the page that may trigger a
@@ -488,7 +523,7 @@ impl Page {
         timeout: std::time::Duration,
     ) -> Result<&Self> {
         let fut = self.inner.wait_for_network_idle();
-        let _ = tokio::time::timeout(timeout, fut).await;
+        let _ = crate::runtime::timeout(timeout, fut).await;
         Ok(self)
     }
 
@@ -499,7 +534,7 @@ impl Page {
         timeout: std::time::Duration,
     ) -> Result<&Self> {
         let fut = self.inner.wait_for_network_almost_idle();
-        let _ = tokio::time::timeout(timeout, fut).await;
+        let _ = crate::runtime::timeout(timeout, fut).await;
         Ok(self)
     }
 
@@ -1001,6 +1036,475 @@ impl Page {
         Ok(rx.await?)
     }
 
+    /// Returns the HLS/DASH manifests seen by the page with their variant playlists and segment
+    /// url templates. Segments are never downloaded, only the manifest bodies are read.
+    pub async fn streaming_assets(&self) -> Result<Vec<crate::streaming::StreamingAsset>> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::StreamingManifests(tx))
+            .await?;
+
+        let manifests = rx.await?;
+        let mut assets = Vec::with_capacity(manifests.len());
+
+        for manifest in manifests {
+            let body = match self
+                .execute(GetResponseBodyParams::new(manifest.request_id.clone()))
+                .await
+            {
+                Ok(res) => {
+                    if res.result.base64_encoded {
+                        utils::base64::decode(&res.result.body)
+                            .ok()
+                            .map(|b| String::from_utf8_lossy(&b).into_owned())
+                    } else {
+                        Some(res.result.body)
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!("streaming manifest body error({:?}) - {}", e, manifest.url);
+                    None
+                }
+            };
+
+            if let Some(body) = body {
+                assets.push(crate::streaming::parse_manifest(
+                    &manifest.url,
+                    manifest.kind,
+                    &body,
+                ));
+            }
+        }
+
+        Ok(assets)
+    }
+
+    /// Returns a summary of the security headers (CSP, HSTS, X-Frame-Options, referrer-policy),
+    /// cookie flags, and mixed-content findings for the current navigation and its subresources.
+    pub async fn security_report(&self) -> Result<crate::security::SecurityReport> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::SecurityReport(tx))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// Swap the interception rules of the requests of this page, applied from the next
+    /// intercepted request in place of the rules of the browser. Requires request interception.
+    pub async fn set_network_rules(
+        &self,
+        rules: Option<crate::network_rules::NetworkRules>,
+    ) -> Result<()> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::NetworkRules(rules.map(std::sync::Arc::new)))
+            .await?;
+        Ok(())
+    }
+
+    /// Swap the redirect policy of this page, checked from the next navigation in place of the
+    /// policy of the browser. Every redirect is followed with `None`.
+    pub async fn set_redirect_policy(
+        &self,
+        policy: Option<crate::redirect::RedirectPolicy>,
+    ) -> Result<()> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::RedirectPolicy(policy))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the http redirects, meta refreshes and script redirects followed since the
+    /// latest navigation, also recorded on the response of the document as
+    /// `HttpRequest::redirect_provenance`.
+    pub async fn redirect_chain(&self) -> Result<Vec<crate::redirect::RedirectHop>> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::RedirectChain(tx))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// Returns the uncaught exceptions and unhandled promise rejections of the current document
+    /// reported by `Runtime.exceptionThrown`.
+    pub async fn js_errors(&self) -> Result<Vec<crate::js_errors::JsError>> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::JsErrors(tx))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// Returns the javascript errors of the current document with the stack frames resolved
+    /// through the source maps of the scripts, so minified frames point to the original files and
+    /// lines. Reuse the resolver across pages to fetch each map once.
+    pub async fn js_errors_with_source_maps(
+        &self,
+        resolver: &mut crate::sourcemap::SourceMapResolver,
+    ) -> Result<Vec<crate::js_errors::JsError>> {
+        let mut errors = self.js_errors().await?;
+
+        for error in errors.iter_mut() {
+            resolver.resolve_error(error).await;
+        }
+
+        Ok(errors)
+    }
+
+    /// The last CDP messages of this page kept by the flight recorder, oldest first. Empty unless
+    /// the browser is configured with `BrowserConfigBuilder::with_flight_recorder`.
+    pub fn flight_record(&self) -> Vec<crate::flight_recorder::FlightRecord> {
+        self.inner.flight_record()
+    }
+
+    /// Write the flight record of this page as NDJSON to the file, e.g. after an operation failed,
+    /// returning the messages written.
+    pub async fn dump_flight_record(&self, output: impl AsRef<Path>) -> Result<usize> {
+        let records = self.flight_record();
+        utils::write(
+            output.as_ref(),
+            crate::flight_recorder::to_ndjson(&records)?,
+        )
+        .await?;
+        Ok(records.len())
+    }
+
+    /// Pause on uncaught exceptions and stream each error with a screenshot and the html of the
+    /// document captured at the moment it was thrown, the page is resumed right after the capture.
+    /// Breakpoints and `debugger` statements are deactivated while the stream is in use.
+    ///
+    /// No errors are reported when the browser is configured with `anti_debugging`, which skips
+    /// all pauses.
+    pub async fn js_error_snapshots(
+        &self,
+    ) -> Result<impl futures::Stream<Item = crate::js_errors::JsErrorSnapshot> + Unpin> {
+        use js_protocol::debugger;
+
+        let events = self.event_listener::<debugger::EventPaused>().await?;
+
+        self.execute(debugger::EnableParams::default()).await?;
+        self.execute(debugger::SetBreakpointsActiveParams::new(false))
+            .await?;
+        self.execute(debugger::SetPauseOnExceptionsParams::new(
+            debugger::SetPauseOnExceptionsState::Uncaught,
+        ))
+        .await?;
+
+        let page = self.clone();
+
+        Ok(events
+            .filter_map(move |event| {
+                let page = page.clone();
+                async move {
+                    let error = crate::js_errors::JsError::from_paused(&event);
+                    let snapshot = match error {
+                        Some(error) => Some(crate::js_errors::JsErrorSnapshot {
+                            error,
+                            screenshot: page.screenshot(ScreenshotParams::default()).await.ok(),
+                            html: page.outer_html().await.ok(),
+                        }),
+                        _ => None,
+                    };
+                    let _ = page.execute(debugger::ResumeParams::default()).await;
+                    snapshot
+                }
+            })
+            .boxed())
+    }
+
+    /// Extract the links of the page and verify them with lightweight HEAD requests (falling back to GET),
+    /// respecting the scope policy of the browser and the scope, concurrency and rate limits of
+    /// the options.
+    pub async fn check_links(
+        &self,
+        options: crate::links::CheckOptions,
+    ) -> Result<Vec<crate::links::LinkStatus>> {
+        let links: Vec<String> = self
+            .evaluate_isolated(crate::links::EXTRACT_LINKS_JS)
+            .await?
+            .into_value()?;
+        let page_url = self.url().await?;
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::ScopePolicy(tx))
+            .await?;
+        let policy = rx.await?;
+        let links =
+            crate::links::filter_links(page_url.as_deref(), links, &options, policy.as_deref());
+
+        Ok(crate::links::check_links(links, &options).await)
+    }
+
+    /// Detect the language of the page from its visible text, along the declared languages.
+    pub async fn detect_language(&self) -> Result<crate::language::LanguageReport> {
+        let signals: crate::language::LanguageSignals = self
+            .evaluate_isolated(crate::language::LANGUAGE_SIGNALS_JS)
+            .await?
+            .into_value()?;
+
+        Ok(signals.into_report())
+    }
+
+    /// The hreflang alternates of the page, normalized and deduplicated. Pick the alternate to
+    /// crawl with `crate::language::pick_alternate`.
+    pub async fn hreflang_alternates(&self) -> Result<Vec<crate::language::HreflangAlternate>> {
+        let signals: crate::language::LanguageSignals = self
+            .evaluate_isolated(crate::language::LANGUAGE_SIGNALS_JS)
+            .await?
+            .into_value()?;
+
+        Ok(crate::language::dedup_hreflang(signals.alternates))
+    }
+
+    /// The favicons, apple touch icons and web app manifest icons of the page. The manifest and
+    /// the icons are read through the cache layer when enabled, each icon is decoded for its
+    /// format and pixel size.
+    pub async fn icons(&self) -> Result<Vec<crate::icons::Icon>> {
+        let links: crate::icons::IconLinks = self
+            .evaluate_isolated(crate::icons::ICON_LINKS_JS)
+            .await?
+            .into_value()?;
+
+        Ok(crate::icons::resolve_icons(links).await)
+    }
+
+    /// The web app manifest linked by the page, fetched through the cache layer when enabled,
+    /// with the service worker registrations of the origin.
+    pub async fn manifest(&self) -> Result<crate::manifest::PwaInfo> {
+        let signals: crate::manifest::PwaSignals = self
+            .evaluate_isolated(crate::manifest::PWA_SIGNALS_JS)
+            .await?
+            .into_value()?;
+
+        Ok(crate::manifest::resolve_pwa(signals).await)
+    }
+
+    /// The RSS, Atom and JSON feeds of the page. The declared feeds are validated through the
+    /// cache layer when enabled, the common feed paths of the origin are probed when none of them
+    /// validates.
+    pub async fn feeds(&self) -> Result<Vec<crate::feeds::Feed>> {
+        let links: crate::feeds::FeedLinks = self
+            .evaluate_isolated(crate::feeds::FEED_LINKS_JS)
+            .await?
+            .into_value()?;
+
+        Ok(crate::feeds::resolve_feeds(links).await)
+    }
+
+    /// The canonical, AMP and mobile alternates of the page. Decide the representation to crawl
+    /// with `Alternates::preferred` to skip processing the same content twice.
+    pub async fn alternates(&self) -> Result<crate::alternates::Alternates> {
+        let alternates: crate::alternates::Alternates = self
+            .evaluate_isolated(crate::alternates::ALTERNATE_LINKS_JS)
+            .await?
+            .into_value()?;
+
+        Ok(alternates.normalized())
+    }
+
+    /// The fonts of `crate::fonts::COMMON_FONTS` the page can render, probed by their metrics.
+    pub async fn available_fonts(&self) -> Result<Vec<String>> {
+        self.available_fonts_of(&crate::fonts::COMMON_FONTS).await
+    }
+
+    /// The candidate fonts the page can render, probed by their metrics.
+    pub async fn available_fonts_of(&self, candidates: &[&str]) -> Result<Vec<String>> {
+        let expression = format!(
+            "({})({})",
+            crate::fonts::AVAILABLE_FONTS_JS,
+            serde_json::to_string(candidates)?
+        );
+
+        Ok(self.evaluate_expression(expression).await?.into_value()?)
+    }
+
+    /// Run a lightweight performance audit of the page: navigation timing, FCP, LCP, CLS,
+    /// the resource waterfall and the byte weight per resource category.
+    pub async fn performance_audit(&self) -> Result<crate::performance::PerformanceReport> {
+        let raw: crate::performance::RawPerformance = self
+            .evaluate_isolated(crate::performance::PERFORMANCE_AUDIT_JS)
+            .await?
+            .into_value()?;
+
+        Ok(raw.into_report())
+    }
+
+    /// Stream the Core Web Vitals (LCP, CLS, INP) of the page. The values keep reporting across
+    /// SPA route changes and each measurement is attributed to the url it was taken on.
+    pub async fn web_vitals_stream(
+        &self,
+    ) -> Result<impl futures::Stream<Item = crate::performance::WebVital> + Unpin> {
+        let events = self
+            .event_listener::<js_protocol::runtime::EventBindingCalled>()
+            .await?;
+
+        self.send_command(AddBindingParams::new(
+            crate::performance::WEB_VITALS_BINDING,
+        ))
+        .await?;
+        self.send_command(AddScriptToEvaluateOnNewDocumentParams::new(
+            crate::performance::WEB_VITALS_JS,
+        ))
+        .await?;
+        self.evaluate_expression(crate::performance::WEB_VITALS_JS)
+            .await?;
+
+        Ok(events.filter_map(|event| {
+            futures::future::ready(crate::performance::WebVital::from_binding(&event))
+        }))
+    }
+
+    /// Navigate to the url and collect the html, metadata, links, network stats and the optional
+    /// screenshot and HAR log into a single artifact. A failed step is recorded in the errors of
+    /// the artifact and the remaining steps still run.
+    pub async fn crawl(
+        &self,
+        url: impl Into<String>,
+        options: crate::artifact::ArtifactOptions,
+    ) -> Result<crate::artifact::CrawlArtifact> {
+        use browser_protocol::network::{
+            EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent, EventResponseReceived,
+        };
+        use futures::FutureExt;
+
+        let started = crate::runtime::Instant::now();
+
+        let mut sent = self.event_listener::<EventRequestWillBeSent>().await?;
+        let mut received = self.event_listener::<EventResponseReceived>().await?;
+        let mut finished = self.event_listener::<EventLoadingFinished>().await?;
+        let mut failed = self.event_listener::<EventLoadingFailed>().await?;
+
+        let mut artifact = crate::artifact::CrawlArtifact {
+            url: url.into(),
+            ..Default::default()
+        };
+
+        if let Err(err) = self.goto(artifact.url.as_str()).await {
+            artifact.push_error("navigation", err);
+        }
+
+        if let Some(timeout) = options.network_idle_timeout {
+            let _ = self.wait_for_network_idle_with_timeout(timeout).await;
+        }
+
+        match self.url().await {
+            Ok(url) => artifact.final_url = url,
+            Err(err) => artifact.push_error("url", err),
+        }
+
+        match self.content().await {
+            Ok(html) => artifact.html = Some(html),
+            Err(err) => artifact.push_error("html", err),
+        }
+
+        match self
+            .evaluate_isolated(crate::artifact::METADATA_JS)
+            .await
+            .and_then(|metadata| metadata.into_value())
+        {
+            Ok(metadata) => artifact.metadata = metadata,
+            Err(err) => artifact.push_error("metadata", err),
+        }
+
+        if options.links {
+            match self
+                .evaluate_isolated(crate::links::EXTRACT_LINKS_JS)
+                .await
+                .and_then(|links| links.into_value())
+            {
+                Ok(links) => artifact.links = links,
+                Err(err) => artifact.push_error("links", err),
+            }
+        }
+
+        if options.screenshot {
+            match self
+                .screenshot(
+                    ScreenshotParams::builder()
+                        .full_page(options.full_page_screenshot)
+                        .build(),
+                )
+                .await
+            {
+                Ok(screenshot) => artifact.screenshot = Some(screenshot),
+                Err(err) => artifact.push_error("screenshot", err),
+            }
+        }
+
+        match self.js_errors().await {
+            Ok(js_errors) => artifact.js_errors = js_errors,
+            Err(err) => artifact.push_error("js_errors", err),
+        }
+
+        // the events of the navigation are buffered by the listeners.
+        let mut recorder = crate::artifact::NetworkRecorder::default();
+
+        while let Some(Some(event)) = sent.next().now_or_never() {
+            recorder.request(
+                event.request_id.as_ref(),
+                &event.request.url,
+                &event.request.method,
+                event.r#type.as_ref().map(|kind| kind.as_ref()),
+                *event.wall_time.inner(),
+                *event.timestamp.inner(),
+                event
+                    .redirect_response
+                    .as_ref()
+                    .map(|response| response.status as u16),
+            );
+        }
+
+        while let Some(Some(event)) = received.next().now_or_never() {
+            recorder.response(
+                event.request_id.as_ref(),
+                event.response.status as u16,
+                &event.response.status_text,
+                &event.response.mime_type,
+                event.response.protocol.as_deref(),
+            );
+        }
+
+        while let Some(Some(event)) = finished.next().now_or_never() {
+            recorder.finished(
+                event.request_id.as_ref(),
+                *event.timestamp.inner(),
+                event.encoded_data_length,
+            );
+        }
+
+        while let Some(Some(event)) = failed.next().now_or_never() {
+            recorder.failed(
+                event.request_id.as_ref(),
+                *event.timestamp.inner(),
+                &event.error_text,
+            );
+        }
+
+        artifact.status = recorder.document_status();
+        artifact.network = recorder.stats();
+
+        if options.har {
+            artifact.har = Some(recorder.har());
+        }
+
+        artifact.duration_ms = started.elapsed().as_millis() as u64;
+
+        Ok(artifact)
+    }
+
     /// Set the cache key of the page
     #[cfg(feature = "_cache")]
     pub async fn set_cache_key(
@@ -1840,6 +2344,260 @@ impl Page {
         self.inner.screenshot(params).await
     }
 
+    /// Capture the page across the viewport and device pixel ratio combinations, reusing the
+    /// current navigation and only toggling the emulation between the captures. The viewport of
+    /// the page is restored afterwards, the override is cleared when the page had none.
+    pub async fn capture_matrix(
+        &self,
+        viewports: &[crate::handler::viewport::Viewport],
+        params: impl Into<ScreenshotParams>,
+    ) -> Result<Vec<ViewportCapture>> {
+        let params = params.into();
+        let initial: (i64, i64, f64, bool) = self
+            .evaluate_expression(
+                "[innerWidth, innerHeight, devicePixelRatio, navigator.maxTouchPoints > 0]",
+            )
+            .await?
+            .into_value()?;
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::DeviceMetricsOverride(tx))
+            .await?;
+        let overridden = rx.await?;
+
+        self.activate().await?;
+
+        if params.omit_background() {
+            self.execute(emulation::SetDefaultBackgroundColorOverrideParams {
+                color: Some(Rgba {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: Some(0.),
+                }),
+            })
+            .await?;
+        }
+
+        let mut captures = Vec::with_capacity(viewports.len());
+        let mut result = Ok(());
+
+        for viewport in viewports {
+            match self.capture_viewport(viewport, &params).await {
+                Ok(image) => captures.push(ViewportCapture {
+                    viewport: viewport.clone(),
+                    image,
+                }),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // restore everything even when a step fails, the first error is returned.
+        let background = if params.omit_background() {
+            self.send_command(emulation::SetDefaultBackgroundColorOverrideParams { color: None })
+                .await
+                .map(|_| ())
+        } else {
+            Ok(())
+        };
+
+        let (width, height, device_scale_factor, has_touch) = initial;
+        let metrics = if overridden {
+            self.execute(SetDeviceMetricsOverrideParams::new(
+                width,
+                height,
+                device_scale_factor,
+                false,
+            ))
+            .await
+            .map(|_| ())
+        } else {
+            self.execute(emulation::ClearDeviceMetricsOverrideParams {})
+                .await
+                .map(|_| ())
+        };
+        let touch = self
+            .execute(emulation::SetTouchEmulationEnabledParams::new(has_touch))
+            .await
+            .map(|_| ());
+
+        result
+            .and(background)
+            .and(metrics)
+            .and(touch)
+            .map(|_| captures)
+    }
+
+    /// Emulate the viewport, wait for the layout to settle and capture it.
+    async fn capture_viewport(
+        &self,
+        viewport: &crate::handler::viewport::Viewport,
+        params: &ScreenshotParams,
+    ) -> Result<Vec<u8>> {
+        let orientation = if viewport.is_landscape {
+            emulation::ScreenOrientation::new(
+                emulation::ScreenOrientationType::LandscapePrimary,
+                90,
+            )
+        } else {
+            emulation::ScreenOrientation::new(emulation::ScreenOrientationType::PortraitPrimary, 0)
+        };
+        let device_scale_factor = viewport.device_scale_factor.unwrap_or(1.);
+
+        let metrics = SetDeviceMetricsOverrideParams::builder()
+            .width(viewport.width)
+            .height(viewport.height)
+            .device_scale_factor(device_scale_factor)
+            .mobile(viewport.emulating_mobile)
+            .screen_orientation(orientation)
+            .build()
+            .map_err(CdpError::msg)?;
+
+        self.execute(metrics.clone()).await?;
+        self.execute(emulation::SetTouchEmulationEnabledParams::new(
+            viewport.has_touch,
+        ))
+        .await?;
+
+        // two frames: the resize is laid out and painted before the capture.
+        self.evaluate_expression(
+            "new Promise(r => requestAnimationFrame(() => requestAnimationFrame(() => r(true))))",
+        )
+        .await?;
+
+        let mut cdp_params = params.cdp_params.clone();
+
+        if params.full_page() {
+            let height = self.layout_metrics().await?.css_content_size.height;
+
+            let mut full = metrics;
+            full.height = height.ceil() as i64;
+            self.execute(full).await?;
+
+            cdp_params.clip = Some(Viewport {
+                x: 0.,
+                y: 0.,
+                width: viewport.width as f64,
+                height,
+                scale: 1.,
+            });
+            cdp_params.capture_beyond_viewport = Some(true);
+        }
+
+        let res = self.execute(cdp_params).await?.result;
+
+        Ok(utils::base64::decode(&res.data)?)
+    }
+
+    /// Take a screenshot of the current page, reusing the screenshot stored in the hybrid cache
+    /// when the DOM, the viewport and the options are unchanged since it was rendered, e.g. when
+    /// monitoring pages which rarely change.
+    ///
+    /// The content is hashed from the DOM snapshot: a change only drawn on a canvas or of an
+    /// image behind an unchanged url is not detected within the ttl.
+    #[cfg(feature = "_cache")]
+    pub async fn screenshot_with_cache(
+        &self,
+        params: impl Into<ScreenshotParams>,
+        options: &crate::cache::ScreenshotCacheOptions,
+    ) -> Result<crate::cache::CachedScreenshot> {
+        use crate::cache::screenshot;
+
+        let params = params.into();
+        let dom = self.content().await?;
+        let viewport: String = self
+            .evaluate(
+                "JSON.stringify([innerWidth, innerHeight, devicePixelRatio, scrollX, scrollY])",
+            )
+            .await?
+            .into_value()?;
+        let content_hash = screenshot::screenshot_content_hash(&dom, &viewport, &params);
+        let page_url = self.url().await?.unwrap_or_default();
+        let cache_url = screenshot::screenshot_cache_url(&page_url, &content_hash);
+
+        if let Some(image) =
+            screenshot::get_cached_screenshot(&cache_url, options.auth.as_deref()).await
+        {
+            return Ok(crate::cache::CachedScreenshot {
+                image,
+                content_hash,
+                hit: true,
+            });
+        }
+
+        let content_type = match params.cdp_params.format {
+            Some(CaptureScreenshotFormat::Jpeg) => "image/jpeg",
+            Some(CaptureScreenshotFormat::Webp) => "image/webp",
+            _ => "image/png",
+        };
+        let image = self.screenshot(params).await?;
+
+        screenshot::put_cached_screenshot(&cache_url, &image, content_type, options).await;
+
+        Ok(crate::cache::CachedScreenshot {
+            image,
+            content_hash,
+            hit: false,
+        })
+    }
+
+    /// Pause the animations, transitions and the caret of the page so screenshots of an
+    /// unchanged page render the same pixels, e.g. the baseline and the screenshots compared
+    /// with `Page::visual_diff`.
+    #[cfg(feature = "visual-diff")]
+    pub async fn render_deterministic(&self) -> Result<&Self> {
+        let _ = self
+            .execute(browser_protocol::animation::SetPlaybackRateParams::new(0.0))
+            .await;
+        self.evaluate_expression(crate::visual_diff::DETERMINISTIC_RENDERING_JS)
+            .await?;
+        Ok(self)
+    }
+
+    /// Compare a full page png screenshot of the current page with a baseline screenshot of a
+    /// previous crawl, reporting the changed regions with a diff image.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide::visual_diff::DiffOptions;
+    /// # async fn demo(page: Page, baseline: Vec<u8>) -> Result<()> {
+    ///     let diff = page.visual_diff(&baseline, DiffOptions::default()).await?;
+    ///     if diff.is_changed() {
+    ///         println!("{:.2}% changed in {:?}", diff.changed_ratio() * 100.0, diff.regions);
+    ///     }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "visual-diff")]
+    pub async fn visual_diff(
+        &self,
+        baseline_png: &[u8],
+        options: crate::visual_diff::DiffOptions,
+    ) -> Result<crate::visual_diff::VisualDiff> {
+        if options.deterministic {
+            self.render_deterministic().await?;
+        }
+
+        let current = self
+            .screenshot(
+                ScreenshotParams::builder()
+                    .format(CaptureScreenshotFormat::Png)
+                    .full_page(true)
+                    .build(),
+            )
+            .await?;
+
+        crate::visual_diff::diff_png(baseline_png, &current, &options)
+    }
+
     /// Take a screenshot of the current page
     pub async fn print_to_pdf(&self, params: impl Into<PrintToPdfParams>) -> Result<Vec<u8>> {
         self.inner.print_to_pdf(params).await
@@ -1902,6 +2660,86 @@ impl Page {
         Ok(pdf)
     }
 
+    /// Print the current page as pdf, read in chunks from the browser instead of one base64
+    /// string holding the whole pdf.
+    ///
+    /// # Example save a large pdf
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide_cdp::cdp::browser_protocol::page::PrintToPdfParams;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.pdf_stream(PrintToPdfParams::default())
+    ///         .await?
+    ///         .save("example.pdf")
+    ///         .await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Note Generating a pdf is currently only supported in Chrome headless.
+    pub async fn pdf_stream(&self, mut params: PrintToPdfParams) -> Result<CdpStream> {
+        params.transfer_mode = Some(PrintToPdfTransferMode::ReturnAsStream);
+        let res = self.execute(params).await?.result;
+
+        match res.stream {
+            Some(handle) => Ok(CdpStream::new(self.inner.clone(), handle)),
+            None => Ok(CdpStream::from_bytes(utils::base64::decode(&res.data)?)),
+        }
+    }
+
+    /// Take a screenshot of the current page as a [`CdpStream`], like the other large payloads.
+    ///
+    /// `Page.captureScreenshot` has no stream transfer mode, the image is received whole.
+    pub async fn screenshot_stream(
+        &self,
+        params: impl Into<ScreenshotParams>,
+    ) -> Result<CdpStream> {
+        Ok(CdpStream::from_bytes(self.screenshot(params).await?))
+    }
+
+    /// Stop tracing and read the trace in chunks.
+    ///
+    /// Tracing must be started with the stream transfer mode, e.g.
+    /// `tracing::StartParams::builder().transfer_mode(StartTransferMode::ReturnAsStream)`.
+    pub async fn stop_tracing_stream(&self) -> Result<CdpStream> {
+        let mut complete = self
+            .event_listener::<browser_protocol::tracing::EventTracingComplete>()
+            .await?;
+        self.execute(browser_protocol::tracing::EndParams::default())
+            .await?;
+
+        let complete = complete.next().await.ok_or(CdpError::NotFound)?;
+
+        match complete.stream.clone() {
+            Some(handle) => Ok(CdpStream::new(self.inner.clone(), handle)),
+            None => Err(CdpError::msg(
+                "tracing was not started with the ReturnAsStream transfer mode",
+            )),
+        }
+    }
+
+    /// Read the body of a response paused by the fetch domain in chunks, the request must be
+    /// paused at the response stage. The body is taken: the request can only be continued with
+    /// `Fetch.fulfillRequest` or failed afterwards.
+    pub async fn response_body_stream(
+        &self,
+        request_id: impl Into<browser_protocol::fetch::RequestId>,
+    ) -> Result<CdpStream> {
+        let res = self
+            .execute(browser_protocol::fetch::TakeResponseBodyAsStreamParams::new(request_id))
+            .await?;
+
+        Ok(CdpStream::new(self.inner.clone(), res.result.stream))
+    }
+
+    /// Read any stream handle of the browser in chunks, e.g. of a command not wrapped by the
+    /// page.
+    pub fn read_stream(&self, handle: impl Into<browser_protocol::io::StreamHandle>) -> CdpStream {
+        CdpStream::new(self.inner.clone(), handle.into())
+    }
+
     /// Brings page to front (activates tab)
     pub async fn bring_to_front(&self) -> Result<&Self> {
         self.send_command(BringToFrontParams::default()).await?;
@@ -2165,6 +3003,149 @@ impl Page {
         Ok(self)
     }
 
+    /// Set a breakpoint on the zero based line of every script whose url matches the regex,
+    /// including scripts loaded later. Listen to [`crate::debugger::EventPaused`] for hits.
+    pub async fn set_breakpoint(
+        &self,
+        url_regex: impl Into<String>,
+        line: i64,
+    ) -> Result<crate::debugger::Breakpoint> {
+        self.set_breakpoint_with_options(url_regex, line, Default::default())
+            .await
+    }
+
+    /// Set a breakpoint that only pauses when the javascript condition evaluates to true.
+    pub async fn set_conditional_breakpoint(
+        &self,
+        url_regex: impl Into<String>,
+        line: i64,
+        condition: impl Into<String>,
+    ) -> Result<crate::debugger::Breakpoint> {
+        let options = crate::debugger::BreakpointOptions {
+            condition: Some(condition.into()),
+            ..Default::default()
+        };
+        self.set_breakpoint_with_options(url_regex, line, options)
+            .await
+    }
+
+    /// Set a breakpoint by url regex with a column or condition.
+    pub async fn set_breakpoint_with_options(
+        &self,
+        url_regex: impl Into<String>,
+        line: i64,
+        options: crate::debugger::BreakpointOptions,
+    ) -> Result<crate::debugger::Breakpoint> {
+        self.enable_debugger().await?;
+        Ok(self
+            .execute(options.params(url_regex, line))
+            .await?
+            .result
+            .into())
+    }
+
+    /// Remove a breakpoint.
+    pub async fn remove_breakpoint(&self, id: crate::debugger::BreakpointId) -> Result<&Self> {
+        self.execute(js_protocol::debugger::RemoveBreakpointParams::new(id))
+            .await?;
+        Ok(self)
+    }
+
+    /// Pause the javascript execution on the next statement.
+    pub async fn debugger_pause(&self) -> Result<&Self> {
+        self.execute(js_protocol::debugger::PauseParams::default())
+            .await?;
+        Ok(self)
+    }
+
+    /// Resume the paused javascript execution.
+    pub async fn debugger_resume(&self) -> Result<&Self> {
+        self.execute(js_protocol::debugger::ResumeParams::default())
+            .await?;
+        Ok(self)
+    }
+
+    /// Step over the next statement of the paused execution.
+    pub async fn step_over(&self) -> Result<&Self> {
+        self.execute(js_protocol::debugger::StepOverParams::default())
+            .await?;
+        Ok(self)
+    }
+
+    /// Step into the next function call of the paused execution.
+    pub async fn step_into(&self) -> Result<&Self> {
+        self.execute(js_protocol::debugger::StepIntoParams::default())
+            .await?;
+        Ok(self)
+    }
+
+    /// Step out of the current function of the paused execution.
+    pub async fn step_out(&self) -> Result<&Self> {
+        self.execute(js_protocol::debugger::StepOutParams::default())
+            .await?;
+        Ok(self)
+    }
+
+    /// Inspect the variables in the scope chain of a paused call frame, innermost scope first.
+    /// The global scope is only included with `include_global` since it is usually huge.
+    pub async fn scopes(
+        &self,
+        call_frame: &crate::debugger::CallFrame,
+        include_global: bool,
+    ) -> Result<Vec<crate::debugger::ScopeSnapshot>> {
+        let mut scopes = Vec::with_capacity(call_frame.scope_chain.len());
+
+        for scope in &call_frame.scope_chain {
+            if scope.r#type == crate::debugger::ScopeType::Global && !include_global {
+                continue;
+            }
+
+            let variables = match &scope.object.object_id {
+                Some(object_id) => {
+                    let mut params =
+                        js_protocol::runtime::GetPropertiesParams::new(object_id.clone());
+                    params.own_properties = Some(true);
+
+                    self.execute(params)
+                        .await?
+                        .result
+                        .result
+                        .into_iter()
+                        .map(Into::into)
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+
+            scopes.push(crate::debugger::ScopeSnapshot {
+                kind: scope.r#type.clone(),
+                name: scope.name.clone(),
+                variables,
+            });
+        }
+
+        Ok(scopes)
+    }
+
+    /// Evaluates an expression in the scope of a paused call frame.
+    pub async fn evaluate_on_call_frame(
+        &self,
+        call_frame_id: crate::debugger::CallFrameId,
+        expression: impl Into<String>,
+    ) -> Result<EvaluationResult> {
+        let mut params =
+            js_protocol::debugger::EvaluateOnCallFrameParams::new(call_frame_id, expression);
+        params.return_by_value = Some(true);
+
+        let result = self.execute(params).await?.result;
+
+        if let Some(exception) = result.exception_details {
+            return Err(CdpError::JavascriptException(Box::new(exception)));
+        }
+
+        Ok(EvaluationResult::new(result.result))
+    }
+
     /// Enables page domain notifications. Enabled by default.
     /// See https://chromedevtools.github.io/devtools-protocol/tot/Page/#method-enable
     pub async fn enable_page(&self) -> Result<&Self> {
@@ -2423,7 +3404,7 @@ impl Page {
         close_on_exceed: Option<bool>,
         enable_networking: Option<bool>,
         sent_and_received: Option<bool>,
-    ) -> Result<tokio::task::JoinHandle<()>> {
+    ) -> Result<crate::runtime::JoinHandle<()>> {
         // prevent re-enabling the network - by default this should be enabled.
         if enable_networking.unwrap_or(false) {
             let _ = self.enable_network().await;
@@ -2439,7 +3420,7 @@ impl Page {
 
         let page = self.clone();
 
-        let handle = tokio::spawn(async move {
+        let handle = crate::runtime::spawn(async move {
             let mut total_bytes: u64 = 0;
 
             while let Some(ev) = rx.next().await {
@@ -2622,6 +3603,115 @@ impl Page {
         }
     }
 
+    /// Evaluates an expression or function like `Page::evaluate`, guarding the client against
+    /// a result larger than the limit, e.g. a page returning a huge object.
+    ///
+    /// The result is kept in the page and serialized there, its value is only sent back when the
+    /// serialization fits `limit.max_size`. A larger result fails with
+    /// `CdpError::ResultTooLarge` or, with `TruncationPolicy::Truncate`, returns the start of the
+    /// serialization as a string with `EvaluationResult::truncation` set. A string result is
+    /// sliced in the page, the truncated value is the start of the string itself.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide::js::ResultLimit;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let result = page
+    ///         .evaluate_with_limit("() => window.bigState", ResultLimit::truncate(1024 * 1024))
+    ///         .await?;
+    ///     if let Some(truncation) = result.truncation() {
+    ///         println!("kept {} of {}", truncation.kept_size, truncation.total_size);
+    ///     }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn evaluate_with_limit(
+        &self,
+        evaluate: impl Into<Evaluation>,
+        limit: ResultLimit,
+    ) -> Result<EvaluationResult> {
+        let evaluate = evaluate.into();
+
+        // the result is boxed in an object so a primitive, e.g. a huge string, stays in the
+        // page. An expression of statements does not parse once boxed and runs as is.
+        match self.evaluate(evaluate.clone().boxed()).await {
+            Ok(result) => self.inner.limit_result(result, limit, true).await,
+            Err(CdpError::JavascriptException(details)) if is_syntax_error(&details) => {
+                let result = self.evaluate(evaluate.by_reference()).await?;
+                self.inner.limit_result(result, limit, false).await
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Evaluates an expression or function in the isolated world of the main frame, falling back to
+    /// the main world when the isolated world is not available. Page scripts can neither see nor
+    /// patch the globals used by the evaluation.
+    pub async fn evaluate_isolated(
+        &self,
+        evaluate: impl Into<Evaluation>,
+    ) -> Result<EvaluationResult> {
+        let context = match self.secondary_execution_context().await? {
+            Some(context) => Some(context),
+            _ => self.execution_context().await?,
+        };
+
+        match evaluate.into() {
+            Evaluation::Expression(mut expr) => {
+                if expr.context_id.is_none() {
+                    expr.context_id = context;
+                }
+                self.evaluate_expression(expr).await
+            }
+            Evaluation::Function(mut fun) => {
+                if fun.execution_context_id.is_none() {
+                    fun.execution_context_id = context;
+                }
+                self.evaluate_function(fun).await
+            }
+        }
+    }
+
+    /// Create a named isolated world in the main frame. The world is created again for every new
+    /// document, see [`World`](crate::world::World) to evaluate scripts and resolve elements in it.
+    pub async fn create_isolated_world(
+        &self,
+        name: impl Into<String>,
+    ) -> Result<crate::world::World> {
+        let name = name.into();
+        let frame_id = self.mainframe().await?.ok_or(CdpError::NotFound)?;
+
+        self.execute(CreateIsolatedWorldParams {
+            frame_id: frame_id.clone(),
+            world_name: Some(name.clone()),
+            grant_univeral_access: Some(true),
+        })
+        .await?;
+
+        if let Ok(cmd) = AddScriptToEvaluateOnNewDocumentParams::builder()
+            .source("")
+            .world_name(name.clone())
+            .build()
+        {
+            self.execute(cmd).await?;
+        }
+
+        Ok(crate::world::World::new(self.clone(), name, frame_id))
+    }
+
+    /// Bundle a local entry file with its imports and evaluate it in the page's context.
+    pub async fn evaluate_module(
+        &self,
+        entry: impl AsRef<Path>,
+        bundler: &dyn crate::javascript::bundle::ScriptBundler,
+    ) -> Result<EvaluationResult> {
+        let source = bundler.bundle(entry.as_ref()).await?;
+        self.evaluate_expression(source).await
+    }
+
     /// Eexecutes a function withinthe page's context and returns the result.
     ///
     /// # Example Evaluate a promise
@@ -2730,6 +3820,22 @@ impl Page {
         Ok(self)
     }
 
+    #[cfg(feature = "_cache")]
+    /// Seed the cache entry by entry, streamed or paginated by the remote server, reporting the
+    /// progress to the callback of the options. This does nothing without the 'cache' flag.
+    pub async fn seed_cache_with_options(
+        &self,
+        cache_site: &str,
+        auth: Option<&str>,
+        remote: Option<&str>,
+        options: &crate::cache::remote::SiteSeedOptions,
+    ) -> Result<crate::cache::remote::SeedProgress> {
+        Ok(
+            crate::cache::remote::get_cache_site_with_options(cache_site, auth, remote, options)
+                .await,
+        )
+    }
+
     #[cfg(feature = "_cache")]
     /// Spawn a cache listener to store resources to memory. This does nothing without the 'cache' flag.
     /// You can pass an endpoint to `dump_remote` to store the cache to a url endpoint.
@@ -2741,7 +3847,7 @@ impl Page {
         auth: Option<String>,
         cache_strategy: Option<crate::cache::CacheStrategy>,
         dump_remote: Option<String>,
-    ) -> Result<tokio::task::JoinHandle<()>, crate::error::CdpError> {
+    ) -> Result<crate::runtime::JoinHandle<()>, crate::error::CdpError> {
         let cache_site =
             crate::cache::manager::site_key_for_target_url(target_url, auth.as_deref());
 
@@ -2770,6 +3876,39 @@ impl Page {
         Ok(self)
     }
 
+    #[cfg(feature = "_cache")]
+    /// Spawn a cache intercepter that queries the remote cache per request on a local miss,
+    /// avoiding the transfer of the whole site cache for single page jobs.
+    pub async fn spawn_cache_intercepter_read_through(
+        &self,
+        auth: Option<String>,
+        policy: Option<crate::cache::BasicCachePolicy>,
+        cache_strategy: Option<crate::cache::CacheStrategy>,
+        read_through: crate::cache::RemoteReadThrough,
+    ) -> Result<&Self> {
+        crate::cache::spawn_fetch_cache_interceptor_with_remote(
+            self.clone(),
+            auth,
+            policy,
+            cache_strategy,
+            Some(read_through),
+        )
+        .await?;
+        Ok(self)
+    }
+
+    #[cfg(feature = "mtls")]
+    /// Present client certificates (mTLS) to the matching origins. The requests of the origins are
+    /// sent with the certificate and fulfilled back to the page.
+    ///
+    /// Requires the `mtls` feature, which enables native-tls for the certificate requests.
+    pub async fn set_client_certificates(
+        &self,
+        certificates: Vec<crate::mtls::ClientCertificate>,
+    ) -> Result<crate::runtime::JoinHandle<()>> {
+        crate::mtls::spawn_client_certificate_interceptor(self.clone(), certificates).await
+    }
+
     pub async fn frame_execution_context(
         &self,
         frame_id: FrameId,
@@ -2822,10 +3961,14 @@ impl Page {
             )
             .build()
         {
-            call.execution_context_id = self
+            call.execution_context_id = match self
                 .inner
                 .execution_context_for_world(None, DOMWorldKind::Secondary)
-                .await?;
+                .await?
+            {
+                Some(ctx) => Some(ctx),
+                _ => self.inner.execution_context().await?,
+            };
             self.evaluate_function(call).await?;
         }
         // relying that document.open() will reset frame lifecycle with "init"
@@ -2838,6 +3981,41 @@ impl Page {
         Ok(self.evaluate(OUTER_HTML).await?.into_value()?)
     }
 
+    /// A stable hash of the normalized visible text and the key metadata of the rendered page,
+    /// like an ETag of the content: timestamps, tokens and ad slots don't change it. Compare it
+    /// across crawls to skip reprocessing unchanged pages, see `crate::diff::content_fingerprint`.
+    /// The simhash of the content is indexed for `Browser::duplicates` when enabled.
+    pub async fn content_fingerprint(&self) -> Result<String> {
+        let html = self.content().await?;
+        let options = crate::diff::DiffOptions::default();
+
+        if let Some(index) = self.inner.duplicate_index() {
+            if let Some(url) = self.url().await? {
+                index.insert(url, crate::diff::content_simhash(&html, &options));
+            }
+        }
+
+        Ok(crate::diff::content_fingerprint(&html, &options))
+    }
+
+    /// The first url fingerprinted of the near duplicates of the current content, indexing the
+    /// page. `None` when the page is unique or the duplicate detection of the browser is
+    /// disabled, see `BrowserConfigBuilder::with_duplicate_detection`.
+    pub async fn near_duplicate_of(&self) -> Result<Option<String>> {
+        let Some(index) = self.inner.duplicate_index() else {
+            return Ok(None);
+        };
+        let Some(url) = self.url().await? else {
+            return Ok(None);
+        };
+        let html = self.content().await?;
+
+        Ok(index.insert(
+            url,
+            crate::diff::content_simhash(&html, &Default::default()),
+        ))
+    }
+
     /// Returns the HTML content of the page
     pub async fn content_bytes(&self) -> Result<Vec<u8>> {
         Ok(self.evaluate(OUTER_HTML).await?.into_bytes()?)
@@ -3014,6 +4192,15 @@ impl From<CaptureScreenshotParams> for ScreenshotParams {
     }
 }
 
+/// A capture of `Page::capture_matrix`.
+#[derive(Debug, Clone)]
+pub struct ViewportCapture {
+    /// The viewport the page was captured with.
+    pub viewport: crate::handler::viewport::Viewport,
+    /// The image.
+    pub image: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub enum MediaTypeParams {
     /// Default CSS media type behavior for page and print
@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{Cookie, SetCookiesParams};
+use chromiumoxide_cdp::cdp::browser_protocol::page::{
+    AddScriptToEvaluateOnNewDocumentParams, RemoveScriptToEvaluateOnNewDocumentParams,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+
+/// The version of the state format.
+pub const PAGE_STATE_VERSION: u32 = 1;
+
+/// Read the storage, the scroll position and the form values of the page. Password and file
+/// inputs, hidden inputs and buttons are skipped.
+const CAPTURE_STATE_JS: &str = r###"(()=>{const r=s=>{const o={};try{for(let i=0;i<s.length;i++){const k=s.key(i);o[k]=s.getItem(k)}}catch(e){}return o};const p=el=>{if(el.id)return"#"+CSS.escape(el.id);const parts=[];for(let n=el;n&&n.nodeType===1&&n!==document.documentElement;n=n.parentElement){if(n.id){parts.unshift("#"+CSS.escape(n.id));break}let i=1;for(let s=n.previousElementSibling;s;s=s.previousElementSibling)if(s.tagName===n.tagName)i++;parts.unshift(n.tagName.toLowerCase()+":nth-of-type("+i+")")}return parts.join(" > ")};const f=[];for(const el of document.querySelectorAll("input,textarea,select")){const t=(el.type||"").toLowerCase();if(t==="password"||t==="file"||t==="hidden"||t==="submit"||t==="button")continue;const v={selector:p(el),value:el.value};if(t==="checkbox"||t==="radio")v.checked=el.checked;if(el.multiple&&el.options)v.selected=[...el.options].filter(o=>o.selected).map(o=>o.value);f.push(v)}return{origin:location.origin,local_storage:r(localStorage),session_storage:r(sessionStorage),scroll_x:scrollX,scroll_y:scrollY,form_values:f}})()"###;
+
+/// A form field of the page.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FormValue {
+    /// The css selector of the field.
+    pub selector: String,
+    /// The value of the field.
+    #[serde(default)]
+    pub value: String,
+    /// The checked state of a checkbox or radio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+    /// The selected options of a multiple select.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected: Option<Vec<String>>,
+}
+
+/// The state of a live page, exported with `Page::export_state` and imported on another browser
+/// or machine with `Page::import_state`, e.g. to move long-lived sessions between workers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageState {
+    /// The version of the format.
+    pub version: u32,
+    /// The url of the page.
+    pub url: String,
+    /// The origin of the page, `null` for opaque origins.
+    pub origin: String,
+    /// The cookies of the page.
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    /// The local storage of the origin.
+    #[serde(default)]
+    pub local_storage: BTreeMap<String, String>,
+    /// The session storage of the origin.
+    #[serde(default)]
+    pub session_storage: BTreeMap<String, String>,
+    /// The horizontal scroll position.
+    #[serde(default)]
+    pub scroll_x: f64,
+    /// The vertical scroll position.
+    #[serde(default)]
+    pub scroll_y: f64,
+    /// The form fields of the page.
+    #[serde(default)]
+    pub form_values: Vec<FormValue>,
+}
+
+#[derive(Deserialize)]
+struct CapturedState {
+    origin: String,
+    local_storage: BTreeMap<String, String>,
+    session_storage: BTreeMap<String, String>,
+    scroll_x: f64,
+    scroll_y: f64,
+    form_values: Vec<FormValue>,
+}
+
+impl PageState {
+    /// Serialize the state to json.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse the state of the json.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let state: Self = serde_json::from_str(json)?;
+
+        if state.version > PAGE_STATE_VERSION {
+            return Err(CdpError::msg(format!(
+                "unsupported page state version {}",
+                state.version
+            )));
+        }
+
+        Ok(state)
+    }
+
+    /// The script seeding the storage of the origin on its new documents.
+    fn storage_script(&self) -> Result<Option<String>> {
+        if self.origin == "null"
+            || (self.local_storage.is_empty() && self.session_storage.is_empty())
+        {
+            return Ok(None);
+        }
+
+        let origin = serde_json::to_string(&self.origin)?;
+        let local = serde_json::to_string(&self.local_storage)?;
+        let session = serde_json::to_string(&self.session_storage)?;
+
+        Ok(Some(format!(
+            r#"(()=>{{if(location.origin!=={origin})return;const w=(s,o)=>{{try{{for(const k in o)s.setItem(k,o[k])}}catch(e){{}}}};w(localStorage,{local});w(sessionStorage,{session})}})()"#
+        )))
+    }
+
+    /// The script restoring the form values and the scroll position.
+    fn restore_script(&self) -> Result<String> {
+        let fields = serde_json::to_string(&self.form_values)?;
+
+        Ok(format!(
+            r#"(()=>{{let n=0;for(const f of {fields}){{let el;try{{el=document.querySelector(f.selector)}}catch(e){{}}if(!el)continue;if(f.checked!=null)el.checked=f.checked;else if(f.selected&&el.options){{for(const o of el.options)o.selected=f.selected.includes(o.value)}}else el.value=f.value;el.dispatchEvent(new Event("input",{{bubbles:true}}));el.dispatchEvent(new Event("change",{{bubbles:true}}));n++}}window.scrollTo({},{});return n}})()"#,
+            self.scroll_x, self.scroll_y
+        ))
+    }
+}
+
+impl Page {
+    /// Export the state of the page: the url, the cookies, the local and session storage of its
+    /// origin, the scroll position and the form values.
+    pub async fn export_state(&self) -> Result<PageState> {
+        let url = self.url().await?.unwrap_or_default();
+        let cookies = self.get_cookies().await?;
+
+        let captured: CapturedState = self
+            .evaluate_isolated(CAPTURE_STATE_JS)
+            .await?
+            .into_value()?;
+
+        Ok(PageState {
+            version: PAGE_STATE_VERSION,
+            url,
+            origin: captured.origin,
+            cookies,
+            local_storage: captured.local_storage,
+            session_storage: captured.session_storage,
+            scroll_x: captured.scroll_x,
+            scroll_y: captured.scroll_y,
+            form_values: captured.form_values,
+        })
+    }
+
+    /// Import the state exported by `Page::export_state`, e.g. of another browser: restore the
+    /// cookies, seed the storage, navigate to the url, then restore the form values and the
+    /// scroll position. Returns the number of the form values restored.
+    pub async fn import_state(&self, state: &PageState) -> Result<usize> {
+        if !state.cookies.is_empty() {
+            self.execute(SetCookiesParams::new(
+                state
+                    .cookies
+                    .iter()
+                    .map(crate::rotation::cookie_param)
+                    .collect::<Vec<_>>(),
+            ))
+            .await?;
+        }
+
+        let script = match state.storage_script()? {
+            Some(source) => Some(
+                self.execute(AddScriptToEvaluateOnNewDocumentParams {
+                    source,
+                    world_name: None,
+                    include_command_line_api: None,
+                    run_immediately: None,
+                })
+                .await?
+                .result
+                .identifier,
+            ),
+            _ => None,
+        };
+
+        let navigated = if state.url.is_empty() {
+            Ok(())
+        } else {
+            self.goto(state.url.as_str()).await.map(|_| ())
+        };
+
+        // seed the storage of the first document only, later writes of the page win.
+        if let Some(identifier) = script {
+            self.send_command(RemoveScriptToEvaluateOnNewDocumentParams::new(identifier))
+                .await?;
+        }
+
+        navigated?;
+
+        let restored: usize = self
+            .evaluate_isolated(state.restore_script()?)
+            .await?
+            .into_value()?;
+
+        Ok(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> PageState {
+        PageState {
+            version: PAGE_STATE_VERSION,
+            url: "https://example.com/checkout".into(),
+            origin: "https://example.com".into(),
+            cookies: Vec::new(),
+            local_storage: BTreeMap::from([("cart".to_string(), "[1,2]".to_string())]),
+            session_storage: BTreeMap::new(),
+            scroll_x: 0.0,
+            scroll_y: 640.0,
+            form_values: vec![FormValue {
+                selector: "#email".into(),
+                value: "a@example.com".into(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trips_as_json() {
+        let state = state();
+
+        assert_eq!(
+            PageState::from_json(&state.to_json().unwrap()).unwrap(),
+            state
+        );
+        assert!(PageState::from_json(r#"{"version":99,"url":"","origin":"null"}"#).is_err());
+    }
+
+    #[test]
+    fn seeds_the_storage_of_the_origin() {
+        let mut state = state();
+        let script = state.storage_script().unwrap().unwrap();
+
+        assert!(script.contains(r#"location.origin!=="https://example.com""#));
+        assert!(script.contains(r#""cart":"[1,2]""#));
+
+        state.origin = "null".into();
+        assert!(state.storage_script().unwrap().is_none());
+    }
+
+    #[test]
+    fn restores_the_scroll_position() {
+        assert!(state()
+            .restore_script()
+            .unwrap()
+            .contains("window.scrollTo(0,640)"));
+    }
+}
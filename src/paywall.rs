@@ -0,0 +1,316 @@
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    EventResponseReceived, ResourceType, Response,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::page::AddScriptToEvaluateOnNewDocumentParams;
+use chromiumoxide_cdp::cdp::js_protocol::runtime::{AddBindingParams, EventBindingCalled};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::page::Page;
+
+/// The binding receiving the wall signals of the documents.
+pub(crate) const WALL_BINDING: &str = "__chromey_wall";
+
+/// Collect the wall signals of the document: the `isAccessibleForFree` structured data, the known
+/// paywall and login wall elements, the wall copy and the overlays locking the page.
+const COLLECT_WALL_JS: &str = r###"(()=>{const o={url:location.href,accessible_for_free:null,selectors:[],login_selectors:[],meter_text:false,subscribe_text:false,login_text:false,overlay:false,scroll_locked:false};const free=a=>{const f=!(a===false||a===0||String(a).toLowerCase()==='false');o.accessible_for_free=o.accessible_for_free===false?false:f};const walk=v=>{if(!v||typeof v!=='object')return;if(Array.isArray(v)){v.forEach(walk);return}if('isAccessibleForFree' in v)free(v.isAccessibleForFree);walk(v['@graph']);walk(v.hasPart)};for(const s of document.querySelectorAll('script[type="application/ld+json"]')){try{walk(JSON.parse(s.textContent))}catch(e){}}for(const m of document.querySelectorAll('[itemprop="isAccessibleForFree"]'))free(m.getAttribute('content'));const q=s=>{try{return!!document.querySelector(s)}catch(e){return false}};for(const s of['.tp-modal','.tp-container-inner','#piano-offer','.paywall','#paywall','[class*="paywall"]','[id*="paywall"]','[data-paywall]','.meteredContent','.poool-widget','.subscription-wall'])if(q(s))o.selectors.push(s);for(const s of['.regwall','[class*="regwall"]','[class*="registration-wall"]','[class*="login-wall"]','[class*="loginwall"]','[id*="login-wall"]'])if(q(s))o.login_selectors.push(s);const t=((document.body&&document.body.innerText)||'').slice(0,200000);o.meter_text=/\b(\d+|one|two|three|four|five)\s+(free\s+)?(articles?|stories|reads)\s+(left|remaining)\b|reached your (free )?(article |monthly )?limit|free articles? this month/i.test(t);o.subscribe_text=/subscribe (now )?to (continue|keep) reading|(already a|become a) subscriber|(only )?available (only )?to subscribers|subscribers only/i.test(t);o.login_text=/(sign|log) in to (continue|read|keep reading)|create a free account to (continue|read)|register (for free )?to (continue|read)/i.test(t);const locked=e=>!!e&&getComputedStyle(e).overflow==='hidden';o.scroll_locked=locked(document.documentElement)||locked(document.body);const w=innerWidth,h=innerHeight;for(const e of document.elementsFromPoint(w/2,h/2)){const c=getComputedStyle(e);if((c.position==='fixed'||c.position==='sticky')&&e.offsetWidth*e.offsetHeight>w*h*0.3){o.overlay=true;break}}return o})()"###;
+
+/// The kind of wall in front of the content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WallKind {
+    /// The content requires a subscription.
+    Paywall,
+    /// The content is free up to a number of articles.
+    MeteredPaywall,
+    /// The content requires an account.
+    LoginWall,
+}
+
+/// The signals of a wall, collected from the document and its response.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WallSignals {
+    /// The url of the document.
+    pub url: String,
+    /// The `isAccessibleForFree` of the structured data, `false` when any part is not free.
+    #[serde(default)]
+    pub accessible_for_free: Option<bool>,
+    /// The known paywall elements present.
+    #[serde(default)]
+    pub selectors: Vec<String>,
+    /// The known login wall elements present.
+    #[serde(default)]
+    pub login_selectors: Vec<String>,
+    /// The copy of a metered paywall, e.g. `2 free articles left`.
+    #[serde(default)]
+    pub meter_text: bool,
+    /// The copy of a paywall, e.g. `subscribe to continue reading`.
+    #[serde(default)]
+    pub subscribe_text: bool,
+    /// The copy of a login wall, e.g. `sign in to continue`.
+    #[serde(default)]
+    pub login_text: bool,
+    /// A fixed element covers the center of the viewport.
+    #[serde(default)]
+    pub overlay: bool,
+    /// The scrolling of the page is disabled.
+    #[serde(default)]
+    pub scroll_locked: bool,
+    /// The status of the document response.
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// The wall headers of the document response, e.g. `x-paywall`.
+    #[serde(default)]
+    pub response_markers: Vec<String>,
+}
+
+/// A wall detected in front of the content of a document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WallDetection {
+    /// The url of the document.
+    pub url: String,
+    /// The kind of the wall.
+    pub kind: WallKind,
+    /// The confidence of the detection, from `0` to `1`.
+    pub confidence: f32,
+    /// The signals the detection is based on.
+    pub signals: Vec<String>,
+}
+
+/// The confidence a wall is reported from by default.
+pub const DEFAULT_WALL_THRESHOLD: f32 = 0.4;
+
+impl WallSignals {
+    /// Score the signals, `None` below the confidence threshold.
+    pub fn detect(&self, threshold: f32) -> Option<WallDetection> {
+        let mut paywall = 0.0f32;
+        let mut login = 0.0f32;
+        let mut signals = Vec::new();
+
+        if self.accessible_for_free == Some(false) {
+            paywall += 0.5;
+            signals.push("structured_data".to_string());
+        }
+
+        if !self.selectors.is_empty() {
+            paywall += 0.3;
+            signals.extend(self.selectors.iter().map(|s| format!("selector:{s}")));
+        }
+
+        if self.subscribe_text {
+            paywall += 0.2;
+            signals.push("subscribe_text".into());
+        }
+
+        if self.meter_text {
+            paywall += 0.3;
+            signals.push("meter_text".into());
+        }
+
+        if !self.login_selectors.is_empty() {
+            login += 0.3;
+            signals.extend(self.login_selectors.iter().map(|s| format!("selector:{s}")));
+        }
+
+        if self.login_text {
+            login += 0.25;
+            signals.push("login_text".into());
+        }
+
+        if self.status == Some(402) {
+            paywall += 0.4;
+            signals.push("status:402".into());
+        }
+
+        if !self.response_markers.is_empty() {
+            paywall += 0.2;
+            signals.extend(self.response_markers.iter().map(|h| format!("header:{h}")));
+        }
+
+        // an overlay locking the page backs the other signals, it is no wall on its own.
+        if self.overlay && self.scroll_locked && (paywall > 0.0 || login > 0.0) {
+            paywall += 0.15;
+            login += 0.15;
+            signals.push("overlay".into());
+        }
+
+        let (kind, score) = if login > paywall {
+            (WallKind::LoginWall, login)
+        } else if self.meter_text {
+            (WallKind::MeteredPaywall, paywall)
+        } else {
+            (WallKind::Paywall, paywall)
+        };
+
+        let confidence = score.min(1.0);
+
+        if confidence < threshold || confidence <= 0.0 {
+            return None;
+        }
+
+        Some(WallDetection {
+            url: self.url.clone(),
+            kind,
+            confidence,
+            signals,
+        })
+    }
+}
+
+/// The wall headers of a response.
+fn response_markers(response: &Response) -> Vec<String> {
+    let mut markers: Vec<String> = response
+        .headers
+        .inner()
+        .as_object()
+        .map(|headers| {
+            headers
+                .keys()
+                .map(|name| name.to_ascii_lowercase())
+                .filter(|name| name.contains("paywall") || name.contains("metered"))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    markers.sort();
+    markers
+}
+
+/// An observation of `Page::wall_events`.
+enum Observed {
+    Response(String, u16, Vec<String>),
+    Signals(WallSignals),
+}
+
+impl Page {
+    /// Detect a paywall or login wall in front of the content of the current document.
+    pub async fn detect_wall(&self, threshold: f32) -> Result<Option<WallDetection>> {
+        let signals: WallSignals = self
+            .evaluate_expression(COLLECT_WALL_JS)
+            .await?
+            .into_value()?;
+
+        Ok(signals.detect(threshold))
+    }
+
+    /// Emit the paywalls and login walls detected on every new document, once loaded and again
+    /// when late walls showed up, with the status and headers of the document response.
+    pub async fn wall_events(
+        &self,
+        threshold: f32,
+    ) -> Result<impl futures::Stream<Item = WallDetection> + Unpin> {
+        let responses = self
+            .event_listener::<EventResponseReceived>()
+            .await?
+            .filter_map(|event| {
+                futures::future::ready((event.r#type == ResourceType::Document).then(|| {
+                    Observed::Response(
+                        event.response.url.clone(),
+                        event.response.status as u16,
+                        response_markers(&event.response),
+                    )
+                }))
+            });
+
+        let reports = self
+            .event_listener::<EventBindingCalled>()
+            .await?
+            .filter_map(|event| {
+                futures::future::ready(if event.name == WALL_BINDING {
+                    serde_json::from_str::<WallSignals>(&event.payload)
+                        .ok()
+                        .map(Observed::Signals)
+                } else {
+                    None
+                })
+            });
+
+        let script = format!(
+            "(()=>{{if(window.__chromeyWall)return;window.__chromeyWall=1;let l='';const s=()=>{{try{{const b=window['{WALL_BINDING}'];const p=JSON.stringify({COLLECT_WALL_JS});if(typeof b==='function'&&p!==l){{l=p;b(p)}}}}catch(e){{}}}};const r=()=>{{s();setTimeout(s,2500)}};document.readyState==='complete'?r():addEventListener('load',r)}})()"
+        );
+
+        self.send_command(AddBindingParams::new(WALL_BINDING))
+            .await?;
+        self.send_command(AddScriptToEvaluateOnNewDocumentParams::new(script))
+            .await?;
+
+        let mut document: Option<(String, u16, Vec<String>)> = None;
+
+        Ok(
+            futures::stream::select(responses, reports).filter_map(move |observed| {
+                futures::future::ready(match observed {
+                    Observed::Response(url, status, markers) => {
+                        document = Some((url, status, markers));
+                        None
+                    }
+                    Observed::Signals(mut signals) => {
+                        if let Some((url, status, markers)) = &document {
+                            if *url == signals.url {
+                                signals.status = Some(*status);
+                                signals.response_markers = markers.clone();
+                            }
+                        }
+                        signals.detect(threshold)
+                    }
+                })
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_a_metered_paywall() {
+        let detection = WallSignals {
+            url: "https://news.example.com/a".into(),
+            accessible_for_free: Some(false),
+            meter_text: true,
+            ..Default::default()
+        }
+        .detect(DEFAULT_WALL_THRESHOLD)
+        .unwrap();
+
+        assert_eq!(detection.kind, WallKind::MeteredPaywall);
+        assert!((detection.confidence - 0.8).abs() < 1e-6);
+        assert_eq!(detection.signals, vec!["structured_data", "meter_text"]);
+    }
+
+    #[test]
+    fn detects_a_login_wall() {
+        let detection = WallSignals {
+            login_selectors: vec![".regwall".into()],
+            login_text: true,
+            overlay: true,
+            scroll_locked: true,
+            ..Default::default()
+        }
+        .detect(DEFAULT_WALL_THRESHOLD)
+        .unwrap();
+
+        assert_eq!(detection.kind, WallKind::LoginWall);
+        assert!(detection.signals.contains(&"overlay".to_string()));
+    }
+
+    #[test]
+    fn ignores_the_weak_signals() {
+        let signals = WallSignals {
+            subscribe_text: true,
+            overlay: true,
+            scroll_locked: true,
+            ..Default::default()
+        };
+
+        assert!(signals.detect(DEFAULT_WALL_THRESHOLD).is_none());
+        assert!(WallSignals {
+            overlay: true,
+            scroll_locked: true,
+            ..Default::default()
+        }
+        .detect(0.0)
+        .is_none());
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+
+/// Collect the navigation timing, paint metrics, layout shifts and resource entries. The buffered observers
+/// replay the entries recorded before the script ran.
+pub(crate) const PERFORMANCE_AUDIT_JS: &str = r###"(()=>new Promise(r=>{const v={fcp:null,lcp:null,cls:0};const obs=[];const o=(t,f)=>{try{const p=new PerformanceObserver(l=>l.getEntries().forEach(f));p.observe({type:t,buffered:true});obs.push(p)}catch(e){}};o('paint',e=>{if(e.name==='first-contentful-paint')v.fcp=e.startTime});o('largest-contentful-paint',e=>{v.lcp=e.startTime});o('layout-shift',e=>{if(!e.hadRecentInput)v.cls+=e.value});setTimeout(()=>{obs.forEach(p=>p.disconnect());const n=performance.getEntriesByType('navigation')[0];r({url:location.href,navigation:n?{redirect:n.redirectEnd-n.redirectStart,dns:n.domainLookupEnd-n.domainLookupStart,connect:n.connectEnd-n.connectStart,ttfb:n.responseStart,response:n.responseEnd-n.responseStart,domInteractive:n.domInteractive,domContentLoaded:n.domContentLoadedEventEnd,load:n.loadEventEnd,transferSize:n.transferSize||0}:null,fcp:v.fcp,lcp:v.lcp,cls:v.cls,resources:performance.getEntriesByType('resource').map(x=>({name:x.name,initiatorType:x.initiatorType,startTime:x.startTime,duration:x.duration,transferSize:x.transferSize||0}))})},100)}))()"###;
+
+/// The navigation timing of the document in milliseconds.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationTiming {
+    /// Time spent on redirects.
+    pub redirect: f64,
+    /// DNS lookup time.
+    pub dns: f64,
+    /// TCP and TLS connect time.
+    pub connect: f64,
+    /// Time to first byte from the navigation start.
+    pub ttfb: f64,
+    /// Time to download the document.
+    pub response: f64,
+    /// The document became interactive.
+    pub dom_interactive: f64,
+    /// The `DOMContentLoaded` handlers finished.
+    pub dom_content_loaded: f64,
+    /// The `load` handlers finished.
+    pub load: f64,
+    /// The bytes transferred for the document.
+    pub transfer_size: u64,
+}
+
+/// A resource timing entry.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTiming {
+    /// The resource url.
+    pub name: String,
+    /// The element or api that started the request.
+    pub initiator_type: String,
+    /// The start time from the navigation start.
+    pub start_time: f64,
+    /// The total duration.
+    pub duration: f64,
+    /// The bytes transferred. Cross-origin resources without `Timing-Allow-Origin` report 0.
+    pub transfer_size: u64,
+}
+
+/// The category of a resource for the byte weight summary.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum ResourceCategory {
+    /// The html document.
+    Document,
+    /// Javascript.
+    Script,
+    /// Stylesheets.
+    Stylesheet,
+    /// Images.
+    Image,
+    /// Web fonts.
+    Font,
+    /// Audio and video.
+    Media,
+    /// Fetch and XHR requests.
+    Fetch,
+    /// Everything else.
+    Other,
+}
+
+impl ResourceCategory {
+    /// Categorize a resource by the initiator type and the url extension.
+    pub fn from_resource(initiator_type: &str, url: &str) -> Self {
+        let path = url
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let ext = path.rsplit_once('.').map(|(_, e)| e).unwrap_or_default();
+
+        match ext {
+            "js" | "mjs" => return ResourceCategory::Script,
+            "css" => return ResourceCategory::Stylesheet,
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg" | "ico" => {
+                return ResourceCategory::Image
+            }
+            "woff" | "woff2" | "ttf" | "otf" | "eot" => return ResourceCategory::Font,
+            "mp4" | "webm" | "mp3" | "m4s" | "ts" | "ogg" | "wav" => {
+                return ResourceCategory::Media
+            }
+            _ => (),
+        }
+
+        match initiator_type {
+            "script" => ResourceCategory::Script,
+            "css" | "link" => ResourceCategory::Stylesheet,
+            "img" | "image" => ResourceCategory::Image,
+            "video" | "audio" => ResourceCategory::Media,
+            "fetch" | "xmlhttprequest" | "beacon" => ResourceCategory::Fetch,
+            "iframe" | "frame" | "navigation" => ResourceCategory::Document,
+            _ => ResourceCategory::Other,
+        }
+    }
+}
+
+/// The request count and bytes of a category.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CategoryWeight {
+    /// The amount of requests.
+    pub requests: usize,
+    /// The bytes transferred.
+    pub bytes: u64,
+}
+
+/// The resource waterfall summary.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WaterfallSummary {
+    /// The amount of resources loaded.
+    pub requests: usize,
+    /// The total bytes transferred including the document.
+    pub total_bytes: u64,
+    /// The time the last resource finished from the navigation start.
+    pub finished: f64,
+    /// The slowest resources, slowest first.
+    pub slowest: Vec<ResourceTiming>,
+}
+
+/// A lightweight page performance report.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PerformanceReport {
+    /// The page url.
+    pub url: String,
+    /// The navigation timing of the document.
+    pub navigation: Option<NavigationTiming>,
+    /// First Contentful Paint in milliseconds.
+    pub first_contentful_paint: Option<f64>,
+    /// Largest Contentful Paint in milliseconds.
+    pub largest_contentful_paint: Option<f64>,
+    /// Cumulative Layout Shift score.
+    pub cumulative_layout_shift: f64,
+    /// The resource waterfall summary.
+    pub waterfall: WaterfallSummary,
+    /// The byte weight per category.
+    pub byte_weight: BTreeMap<ResourceCategory, CategoryWeight>,
+    /// All the resource entries in start order.
+    pub resources: Vec<ResourceTiming>,
+}
+
+/// The raw values returned by the audit script.
+#[derive(Debug, Default, serde::Deserialize)]
+pub(crate) struct RawPerformance {
+    url: String,
+    navigation: Option<NavigationTiming>,
+    fcp: Option<f64>,
+    lcp: Option<f64>,
+    cls: f64,
+    resources: Vec<ResourceTiming>,
+}
+
+/// The amount of slowest resources kept in the waterfall summary.
+const SLOWEST_RESOURCES: usize = 10;
+
+impl RawPerformance {
+    /// Build the report from the raw values.
+    pub(crate) fn into_report(self) -> PerformanceReport {
+        let mut resources = self.resources;
+        resources.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+
+        let mut byte_weight: BTreeMap<ResourceCategory, CategoryWeight> = BTreeMap::new();
+
+        if let Some(nav) = &self.navigation {
+            let weight = byte_weight.entry(ResourceCategory::Document).or_default();
+            weight.requests += 1;
+            weight.bytes += nav.transfer_size;
+        }
+
+        let mut finished: f64 = self.navigation.as_ref().map_or(0.0, |n| n.load);
+
+        for resource in &resources {
+            let weight = byte_weight
+                .entry(ResourceCategory::from_resource(
+                    &resource.initiator_type,
+                    &resource.name,
+                ))
+                .or_default();
+            weight.requests += 1;
+            weight.bytes += resource.transfer_size;
+            finished = finished.max(resource.start_time + resource.duration);
+        }
+
+        let mut slowest = resources.clone();
+        slowest.sort_by(|a, b| b.duration.total_cmp(&a.duration));
+        slowest.truncate(SLOWEST_RESOURCES);
+
+        PerformanceReport {
+            url: self.url,
+            waterfall: WaterfallSummary {
+                requests: resources.len(),
+                total_bytes: byte_weight.values().map(|w| w.bytes).sum(),
+                finished,
+                slowest,
+            },
+            navigation: self.navigation,
+            first_contentful_paint: self.fcp,
+            largest_contentful_paint: self.lcp,
+            cumulative_layout_shift: self.cls,
+            byte_weight,
+            resources,
+        }
+    }
+}
+
+/// The binding used to report the web vitals.
+pub(crate) const WEB_VITALS_BINDING: &str = "__chromey_web_vitals";
+
+/// Observe LCP, CLS and INP and report every update with the current url. The values reset when
+/// the History API changes the url so each soft navigation is measured on its own.
+pub(crate) const WEB_VITALS_JS: &str = r###"(()=>{if(window.__chromeyVitals)return;const b=window['__chromey_web_vitals'];if(typeof b!=='function')return;window.__chromeyVitals=1;let s={lcp:0,cls:0,inp:0};const send=(n,v)=>{try{b(JSON.stringify({name:n,value:v,url:location.href}))}catch(e){}};const o=(t,f,x)=>{try{new PerformanceObserver(l=>l.getEntries().forEach(f)).observe(Object.assign({type:t,buffered:true},x||{}))}catch(e){}};o('largest-contentful-paint',e=>{s.lcp=e.startTime;send('LCP',s.lcp)});o('layout-shift',e=>{if(!e.hadRecentInput){s.cls+=e.value;send('CLS',s.cls)}});o('event',e=>{if(e.interactionId&&e.duration>s.inp){s.inp=e.duration;send('INP',s.inp)}},{durationThreshold:16});let u=location.href;const c=()=>{if(location.href!==u){u=location.href;s={lcp:0,cls:0,inp:0}}};for(const m of['pushState','replaceState']){const f=history[m];history[m]=function(){const r=f.apply(this,arguments);c();return r}}addEventListener('popstate',c)})()"###;
+
+/// A Core Web Vitals metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum WebVitalKind {
+    /// Largest Contentful Paint in milliseconds.
+    #[serde(rename = "LCP")]
+    Lcp,
+    /// Cumulative Layout Shift score.
+    #[serde(rename = "CLS")]
+    Cls,
+    /// Interaction to Next Paint in milliseconds.
+    #[serde(rename = "INP")]
+    Inp,
+}
+
+/// A web vital measurement attributed to the url it was taken on.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WebVital {
+    /// The metric.
+    pub name: WebVitalKind,
+    /// The current value of the metric for the url.
+    pub value: f64,
+    /// The url when the measurement was taken.
+    pub url: String,
+}
+
+impl WebVital {
+    /// Parse a measurement reported through the web vitals binding.
+    pub(crate) fn from_binding(
+        event: &chromiumoxide_cdp::cdp::js_protocol::runtime::EventBindingCalled,
+    ) -> Option<Self> {
+        if event.name == WEB_VITALS_BINDING {
+            serde_json::from_str(&event.payload).ok()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categorize_resources() {
+        assert_eq!(
+            ResourceCategory::from_resource("link", "https://a.com/app.css?v=1"),
+            ResourceCategory::Stylesheet
+        );
+        assert_eq!(
+            ResourceCategory::from_resource("fetch", "https://a.com/api/items"),
+            ResourceCategory::Fetch
+        );
+        assert_eq!(
+            ResourceCategory::from_resource("css", "https://a.com/font.woff2"),
+            ResourceCategory::Font
+        );
+    }
+
+    #[test]
+    fn report_byte_weight() {
+        let report = RawPerformance {
+            url: "https://a.com/".into(),
+            navigation: Some(NavigationTiming {
+                load: 500.0,
+                transfer_size: 1000,
+                ..Default::default()
+            }),
+            resources: vec![
+                ResourceTiming {
+                    name: "https://a.com/app.js".into(),
+                    initiator_type: "script".into(),
+                    start_time: 100.0,
+                    duration: 700.0,
+                    transfer_size: 3000,
+                },
+                ResourceTiming {
+                    name: "https://a.com/logo.png".into(),
+                    initiator_type: "img".into(),
+                    start_time: 50.0,
+                    duration: 20.0,
+                    transfer_size: 500,
+                },
+            ],
+            ..Default::default()
+        }
+        .into_report();
+
+        assert_eq!(report.waterfall.total_bytes, 4500);
+        assert_eq!(report.waterfall.finished, 800.0);
+        assert_eq!(report.resources[0].name, "https://a.com/logo.png");
+        assert_eq!(report.waterfall.slowest[0].name, "https://a.com/app.js");
+        assert_eq!(
+            report.byte_weight[&ResourceCategory::Script],
+            CategoryWeight {
+                requests: 1,
+                bytes: 3000
+            }
+        );
+    }
+
+    #[test]
+    fn parse_web_vital_binding() {
+        let event = chromiumoxide_cdp::cdp::js_protocol::runtime::EventBindingCalled {
+            name: WEB_VITALS_BINDING.into(),
+            payload: r#"{"name":"CLS","value":0.12,"url":"https://a.com/route"}"#.into(),
+            execution_context_id:
+                chromiumoxide_cdp::cdp::js_protocol::runtime::ExecutionContextId::new(1),
+        };
+        let vital = WebVital::from_binding(&event).unwrap();
+
+        assert_eq!(vital.name, WebVitalKind::Cls);
+        assert_eq!(vital.url, "https://a.com/route");
+    }
+}
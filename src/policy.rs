@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use crate::handler::HandlerConfig;
+
+/// The resources dropped by the request interception.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockingPolicy {
+    /// Drop the images and other visuals.
+    pub ignore_visuals: bool,
+    /// Drop the stylesheets.
+    pub ignore_stylesheets: bool,
+    /// Drop the scripts, but the critical framework bundles.
+    pub ignore_javascript: bool,
+    /// Drop the analytics.
+    pub ignore_analytics: bool,
+    /// Only fetch the html documents.
+    pub only_html: bool,
+}
+
+impl From<&HandlerConfig> for BlockingPolicy {
+    fn from(config: &HandlerConfig) -> Self {
+        Self {
+            ignore_visuals: config.ignore_visuals,
+            ignore_stylesheets: config.ignore_stylesheets,
+            ignore_javascript: config.ignore_javascript,
+            ignore_analytics: config.ignore_analytics,
+            only_html: config.only_html,
+        }
+    }
+}
+
+/// The hosts the documents of the pages may load from, documents out of scope are blocked by
+/// the request interception.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopePolicy {
+    /// The allowed hosts, every host when empty.
+    pub allowed_hosts: Vec<String>,
+    /// Allow the subdomains of the allowed hosts.
+    pub include_subdomains: bool,
+}
+
+impl ScopePolicy {
+    /// The scope of the hosts and their subdomains.
+    pub fn hosts<I, S>(hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowed_hosts: hosts
+                .into_iter()
+                .map(|host| host.into().to_ascii_lowercase())
+                .collect(),
+            include_subdomains: true,
+        }
+    }
+
+    /// The url is in scope. Urls without a host, e.g. `about:blank` or `data:` urls, always are.
+    pub fn allows(&self, url: &str) -> bool {
+        if self.allowed_hosts.is_empty() {
+            return true;
+        }
+
+        let Some(host) = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_ascii_lowercase()))
+        else {
+            return true;
+        };
+
+        self.allowed_hosts.iter().any(|allowed| {
+            host == *allowed
+                || (self.include_subdomains
+                    && host.len() > allowed.len()
+                    && host.ends_with(allowed.as_str())
+                    && host.as_bytes()[host.len() - allowed.len() - 1] == b'.')
+        })
+    }
+}
+
+/// How gently the hosts are crawled by the bulk jobs of the browser.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolitenessPolicy {
+    /// The min delay between the starts of two urls of the same host, raising the
+    /// `per_host_delay` of the bulk options when longer.
+    pub per_host_delay: Duration,
+}
+
+/// The policies of a browser swapped at runtime with `Browser::update_policy`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrawlPolicy {
+    /// The resources dropped by the request interception.
+    pub blocking: BlockingPolicy,
+    /// The hosts the documents may load from.
+    pub scope: ScopePolicy,
+    /// The delays between the urls of the same host.
+    pub politeness: PolitenessPolicy,
+}
+
+impl From<&HandlerConfig> for CrawlPolicy {
+    fn from(config: &HandlerConfig) -> Self {
+        Self {
+            blocking: config.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_the_hosts() {
+        let scope = ScopePolicy::hosts(["Example.com"]);
+
+        assert!(scope.allows("https://example.com/a"));
+        assert!(scope.allows("https://www.example.com/"));
+        assert!(!scope.allows("https://badexample.com/"));
+        assert!(!scope.allows("https://example.org/"));
+        assert!(scope.allows("about:blank"));
+
+        let exact = ScopePolicy {
+            include_subdomains: false,
+            ..scope
+        };
+        assert!(!exact.allows("https://www.example.com/"));
+        assert!(ScopePolicy::default().allows("https://example.org/"));
+    }
+
+    #[test]
+    fn starts_from_the_handler_config() {
+        let config = HandlerConfig {
+            ignore_visuals: true,
+            ..Default::default()
+        };
+        let policy = CrawlPolicy::from(&config);
+
+        assert!(policy.blocking.ignore_visuals);
+        assert!(policy.blocking.ignore_analytics);
+        assert!(policy.scope.allowed_hosts.is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+//! Randomized variations of recorded CDP messages, mimicking what a newer chromium sends: fields
+//! unknown to the protocol definitions, new enum values, optional fields no longer sent. Every
+//! variation must still decode in the lenient mode of the connection, which keeps the raw params
+//! of an event failing to parse instead of dropping the message.
+//!
+//! ```no_run
+//! use chromiumoxide::protocol_compat::CompatHarness;
+//!
+//! let recorded = r#"{"method":"Page.frameStoppedLoading","params":{"frameId":"F1"}}"#;
+//! CompatHarness::new(7).assert_survives(recorded, 100);
+//! ```
+
+use std::fmt;
+
+use chromiumoxide_cdp::cdp::CdpEventMessage;
+use chromiumoxide_types::{CallId, Message, Method};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+
+/// A change of a recorded message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    /// A field unknown to the protocol definitions added to an object.
+    ExtraField,
+    /// A string replaced with a value unknown to the protocol definitions, e.g. a new enum value.
+    NewEnumString,
+    /// A field removed, e.g. an optional field no longer sent.
+    MissingField,
+    /// A field set to `null`.
+    NullField,
+}
+
+const MUTATIONS: [Mutation; 4] = [
+    Mutation::ExtraField,
+    Mutation::NewEnumString,
+    Mutation::MissingField,
+    Mutation::NullField,
+];
+
+/// A variation of a recorded message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variation {
+    /// The changes applied, with the json pointer of the changed value.
+    pub mutations: Vec<(Mutation, String)>,
+    /// The changed message.
+    pub message: Value,
+}
+
+/// A variation which failed to decode.
+#[derive(Debug, Clone)]
+pub struct CompatFailure {
+    /// The variation.
+    pub variation: Variation,
+    /// Why it failed.
+    pub error: String,
+}
+
+impl fmt::Display for CompatFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} after {:?}: {}",
+            self.error, self.variation.mutations, self.variation.message
+        )
+    }
+}
+
+impl std::error::Error for CompatFailure {}
+
+/// Generates the variations of recorded messages from a seed, so a failure is reproducible.
+#[derive(Debug, Clone)]
+pub struct CompatHarness {
+    rng: StdRng,
+    mutations: usize,
+}
+
+impl CompatHarness {
+    /// A harness applying up to 3 changes per variation.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            mutations: 3,
+        }
+    }
+
+    /// The max changes applied per variation.
+    pub fn with_mutations(mut self, mutations: usize) -> Self {
+        self.mutations = mutations.max(1);
+        self
+    }
+
+    /// Variations of the message, only the `params` of an event or the `result` of a response
+    /// are changed.
+    pub fn variations(&mut self, message: &Value, count: usize) -> Vec<Variation> {
+        (0..count).map(|_| self.variation(message)).collect()
+    }
+
+    fn variation(&mut self, message: &Value) -> Variation {
+        let mut message = message.clone();
+        let mut mutations = Vec::new();
+
+        for _ in 0..self.rng.random_range(1..=self.mutations) {
+            let mutation = MUTATIONS[self.rng.random_range(0..MUTATIONS.len())];
+
+            if let Some(pointer) = self.mutate(&mut message, mutation) {
+                mutations.push((mutation, pointer));
+            }
+        }
+
+        Variation { mutations, message }
+    }
+
+    /// Apply the change to a random value of the payload, returning its json pointer.
+    fn mutate(&mut self, message: &mut Value, mutation: Mutation) -> Option<String> {
+        let root = ["/params", "/result"]
+            .into_iter()
+            .find(|root| message.pointer(root).is_some())?;
+
+        let mut candidates = Vec::new();
+        collect(&message[&root[1..]], root.to_string(), &mut candidates);
+
+        let candidates: Vec<_> = candidates
+            .into_iter()
+            .filter(|(pointer, value)| match mutation {
+                Mutation::ExtraField => value.is_object(),
+                Mutation::NewEnumString => value.as_str().is_some_and(is_enum_like),
+                Mutation::MissingField | Mutation::NullField => pointer.as_str() != root,
+            })
+            .map(|(pointer, _)| pointer)
+            .collect();
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let pointer = candidates[self.rng.random_range(0..candidates.len())].clone();
+        let suffix = self.rng.random_range(0..10_000u32);
+
+        match mutation {
+            Mutation::ExtraField => {
+                let value = match self.rng.random_range(0..3) {
+                    0 => Value::from(suffix),
+                    1 => Value::from(format!("value{suffix}")),
+                    _ => serde_json::json!({ "nested": [suffix] }),
+                };
+                message
+                    .pointer_mut(&pointer)?
+                    .as_object_mut()?
+                    .insert(format!("chromeyCompat{suffix}"), value);
+            }
+            Mutation::NewEnumString => {
+                *message.pointer_mut(&pointer)? = Value::from(format!("ChromeyCompat{suffix}"));
+            }
+            Mutation::NullField => {
+                *message.pointer_mut(&pointer)? = Value::Null;
+            }
+            Mutation::MissingField => {
+                let (parent, key) = pointer.rsplit_once('/')?;
+                let key = key.replace("~1", "/").replace("~0", "~");
+
+                match message.pointer_mut(parent)? {
+                    Value::Object(map) => {
+                        map.remove(&key);
+                    }
+                    Value::Array(items) => {
+                        let index: usize = key.parse().ok()?;
+                        if index < items.len() {
+                            items.remove(index);
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+        }
+
+        Some(pointer)
+    }
+
+    /// Decode `count` variations of the recorded message, returning the first failure.
+    pub fn check(&mut self, message: &str, count: usize) -> Result<(), CompatFailure> {
+        let original: Value = serde_json::from_str(message).map_err(|err| CompatFailure {
+            variation: Variation {
+                mutations: Vec::new(),
+                message: Value::Null,
+            },
+            error: format!("recorded message is not json: {err}"),
+        })?;
+
+        std::iter::once(Variation {
+            mutations: Vec::new(),
+            message: original.clone(),
+        })
+        .chain(self.variations(&original, count))
+        .try_for_each(|variation| match survives(&original, &variation.message) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(CompatFailure { variation, error }),
+        })
+    }
+
+    /// Panic with the failing variation unless every variation of the recorded message decodes.
+    pub fn assert_survives(&mut self, message: &str, count: usize) {
+        if let Err(failure) = self.check(message, count) {
+            panic!("protocol compat failure: {failure}");
+        }
+    }
+}
+
+/// The variation decodes to a message of the same kind as the original.
+fn survives(original: &Value, variation: &Value) -> Result<(), String> {
+    let bytes = serde_json::to_vec(variation).map_err(|err| err.to_string())?;
+    let decoded =
+        crate::conn::decode_lenient::<CdpEventMessage>(&bytes).map_err(|err| err.to_string())?;
+
+    match *decoded {
+        Message::Response(response) => {
+            let id = original["id"].as_u64().map(|id| CallId::new(id as usize));
+
+            if id == Some(response.id) {
+                Ok(())
+            } else {
+                Err(format!("decoded the response {}", response.id))
+            }
+        }
+        Message::Event(event) => {
+            let method = event.identifier();
+
+            if original["method"].as_str() == Some(method.as_ref()) {
+                Ok(())
+            } else {
+                Err(format!("decoded the event {method}"))
+            }
+        }
+    }
+}
+
+/// Every value below the value with its json pointer.
+fn collect<'a>(value: &'a Value, pointer: String, out: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let key = key.replace('~', "~0").replace('/', "~1");
+                collect(child, format!("{pointer}/{key}"), out);
+            }
+        }
+        Value::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                collect(child, format!("{pointer}/{index}"), out);
+            }
+        }
+        _ => {}
+    }
+
+    out.push((pointer, value));
+}
+
+/// A string which looks like an enum value rather than free text, e.g. `Document` or `no-cors`.
+fn is_enum_like(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 40
+        && value.starts_with(|c: char| c.is_ascii_alphabetic())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST_WILL_BE_SENT: &str = r#"{"method":"Network.requestWillBeSent","sessionId":"S1","params":{"requestId":"R1","loaderId":"L1","documentURL":"https://example.com/","request":{"url":"https://example.com/","method":"GET","headers":{"Accept":"text/html"},"initialPriority":"VeryHigh","referrerPolicy":"strict-origin-when-cross-origin"},"timestamp":1.5,"wallTime":1700000000.5,"initiator":{"type":"other"},"redirectHasExtraInfo":false,"type":"Document","frameId":"F1","hasUserGesture":false}}"#;
+
+    #[test]
+    fn variations_are_reproducible() {
+        let message: Value = serde_json::from_str(REQUEST_WILL_BE_SENT).unwrap();
+
+        let first = CompatHarness::new(1).variations(&message, 10);
+        let second = CompatHarness::new(1).variations(&message, 10);
+
+        assert_eq!(first, second);
+        assert!(first.iter().any(|variation| variation.message != message));
+        assert!(first.iter().all(|variation| variation
+            .mutations
+            .iter()
+            .all(|(_, pointer)| pointer.starts_with("/params"))));
+    }
+
+    #[test]
+    fn lenient_mode_survives_variations() {
+        CompatHarness::new(42).assert_survives(REQUEST_WILL_BE_SENT, 200);
+        CompatHarness::new(42).assert_survives(r#"{"id":3,"result":{"frameId":"F1"}}"#, 50);
+    }
+
+    #[test]
+    fn new_enum_values_keep_the_raw_params() {
+        let message =
+            REQUEST_WILL_BE_SENT.replace(r#""type":"Document""#, r#""type":"Speculation""#);
+        let decoded = crate::conn::decode_lenient::<CdpEventMessage>(message.as_bytes()).unwrap();
+
+        match *decoded {
+            Message::Event(event) => {
+                assert_eq!(event.method, "Network.requestWillBeSent");
+                assert_eq!(event.session_id.as_deref(), Some("S1"));
+                assert!(matches!(
+                    event.params,
+                    chromiumoxide_cdp::cdp::CdpEvent::Other(_)
+                ));
+            }
+            _ => panic!("expected an event"),
+        }
+    }
+}
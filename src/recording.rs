@@ -0,0 +1,561 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use base64::{engine::general_purpose, Engine as _};
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::{
+    ContinueRequestParams, DisableParams, EnableParams, EventRequestPaused, FailRequestParams,
+    FulfillRequestParams, GetResponseBodyParams, HeaderEntry, RequestPattern, RequestStage,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::network::{ErrorReason, Headers};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+use crate::request_signing::sha256_hex;
+use crate::runtime::JoinHandle;
+
+/// The version of the archive format.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// A request and its response recorded by a [`Recorder`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    /// The order of the response in the recording.
+    pub sequence: u64,
+    /// The method of the request.
+    pub method: String,
+    /// The url of the request.
+    pub url: String,
+    /// The headers of the request.
+    pub request_headers: BTreeMap<String, String>,
+    /// The base64 body of the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    /// The status of the response.
+    pub status: i64,
+    /// The headers of the response, in the received order.
+    pub response_headers: Vec<(String, String)>,
+    /// The base64 body of the response.
+    pub body: String,
+}
+
+impl RecordedExchange {
+    /// The bytes of the response body.
+    pub fn body_bytes(&self) -> Vec<u8> {
+        general_purpose::STANDARD
+            .decode(&self.body)
+            .unwrap_or_default()
+    }
+}
+
+/// A portable archive of the network traffic of a navigation, a JSON file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingArchive {
+    /// The version of the format.
+    pub version: u32,
+    /// The exchanges, ordered by their sequence.
+    pub entries: Vec<RecordedExchange>,
+}
+
+impl Default for RecordingArchive {
+    fn default() -> Self {
+        Self {
+            version: ARCHIVE_VERSION,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl RecordingArchive {
+    /// Load the archive of the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let archive: Self = serde_json::from_slice(&std::fs::read(path)?)?;
+
+        if archive.version > ARCHIVE_VERSION {
+            return Err(CdpError::msg(format!(
+                "unsupported recording archive version {}",
+                archive.version
+            )));
+        }
+
+        Ok(archive)
+    }
+
+    /// Save the archive to the file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// How the replayed requests are matched to the recorded exchanges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Ignore the whole query of the urls.
+    pub ignore_query: bool,
+    /// Ignore the query params, e.g. cache busters or timestamps.
+    pub ignore_query_params: Vec<String>,
+    /// The request headers that must match, case insensitive. The headers are ignored by default.
+    pub match_headers: Vec<String>,
+    /// Ignore the bodies of the requests.
+    pub ignore_body: bool,
+}
+
+impl MatchOptions {
+    /// The key matching a request to the recorded exchanges.
+    pub fn key(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &BTreeMap<String, String>,
+        body: Option<&str>,
+    ) -> String {
+        let url = match url::Url::parse(url) {
+            Ok(mut parsed) => {
+                parsed.set_fragment(None);
+
+                if self.ignore_query {
+                    parsed.set_query(None);
+                } else if parsed.query().is_some() {
+                    let mut pairs: Vec<(String, String)> = parsed
+                        .query_pairs()
+                        .filter(|(name, _)| !self.ignore_query_params.iter().any(|p| p == name))
+                        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                        .collect();
+                    // the order of the params does not matter.
+                    pairs.sort();
+
+                    if pairs.is_empty() {
+                        parsed.set_query(None);
+                    } else {
+                        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+                    }
+                }
+
+                parsed.to_string()
+            }
+            _ => url.to_string(),
+        };
+
+        let mut key = format!("{} {url}", method.to_ascii_uppercase());
+
+        let mut names: Vec<String> = self
+            .match_headers
+            .iter()
+            .map(|name| name.to_ascii_lowercase())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let value = headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(&name))
+                .map(|(_, v)| v.as_str())
+                .unwrap_or_default();
+            key.push_str(&format!("\n{name}: {value}"));
+        }
+
+        if !self.ignore_body {
+            if let Some(body) = body.filter(|body| !body.is_empty()) {
+                key.push_str(&format!("\nbody: {}", sha256_hex(body.as_bytes())));
+            }
+        }
+
+        key
+    }
+}
+
+/// The headers of a request as a sorted map.
+fn header_map(headers: &Headers) -> BTreeMap<String, String> {
+    headers
+        .inner()
+        .as_object()
+        .map(|obj| {
+            obj.iter()
+                .map(|(k, v)| {
+                    let value = v
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| v.to_string());
+                    (k.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The base64 body of the paused request.
+fn request_body(event: &EventRequestPaused) -> Option<String> {
+    let entries = event.request.post_data_entries.as_ref()?;
+    let mut body = Vec::new();
+
+    for entry in entries {
+        if let Some(bytes) = entry.bytes.as_ref() {
+            body.extend(
+                general_purpose::STANDARD
+                    .decode(AsRef::<str>::as_ref(bytes))
+                    .ok()?,
+            );
+        }
+    }
+
+    Some(general_purpose::STANDARD.encode(body))
+}
+
+/// Records the network traffic of a page, see `Page::start_recording`.
+#[derive(Debug)]
+pub struct Recorder {
+    page: Page,
+    entries: Arc<Mutex<Vec<RecordedExchange>>>,
+    handle: JoinHandle<()>,
+}
+
+impl Recorder {
+    /// The exchanges recorded so far.
+    pub fn archive(&self) -> RecordingArchive {
+        let mut entries = self
+            .entries
+            .lock()
+            .map(|entries| entries.clone())
+            .unwrap_or_default();
+        entries.sort_by_key(|entry| entry.sequence);
+
+        RecordingArchive {
+            version: ARCHIVE_VERSION,
+            entries,
+        }
+    }
+
+    /// Stop recording and return the archive.
+    pub async fn stop(self) -> Result<RecordingArchive> {
+        self.handle.abort();
+        self.page.execute(DisableParams::default()).await?;
+        Ok(self.archive())
+    }
+}
+
+/// Answer to the requests without a recorded exchange.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplayMiss {
+    /// Fail the request as disconnected from the internet.
+    #[default]
+    Fail,
+    /// Fulfill the request with an empty `404`.
+    NotFound,
+}
+
+/// Replays an archive to a page, see `Page::start_replay`.
+#[derive(Debug)]
+pub struct Replayer {
+    page: Page,
+    misses: Arc<Mutex<Vec<String>>>,
+    handle: JoinHandle<()>,
+}
+
+impl Replayer {
+    /// The urls of the requests without a recorded exchange, to assert a complete replay.
+    pub fn misses(&self) -> Vec<String> {
+        self.misses
+            .lock()
+            .map(|misses| misses.clone())
+            .unwrap_or_default()
+    }
+
+    /// Stop replaying, the requests go to the network again.
+    pub async fn stop(self) -> Result<Vec<String>> {
+        self.handle.abort();
+        self.page.execute(DisableParams::default()).await?;
+        Ok(self.misses())
+    }
+}
+
+/// The recorded exchanges per match key, replayed in the recorded order. The last exchange of a
+/// key is repeated once the others are replayed.
+#[derive(Debug, Default)]
+pub(crate) struct ReplayIndex {
+    exchanges: HashMap<String, VecDeque<RecordedExchange>>,
+}
+
+impl ReplayIndex {
+    /// Index the exchanges of the archive.
+    pub(crate) fn new(archive: RecordingArchive, options: &MatchOptions) -> Self {
+        let mut entries = archive.entries;
+        entries.sort_by_key(|entry| entry.sequence);
+
+        let mut exchanges: HashMap<String, VecDeque<RecordedExchange>> = HashMap::new();
+
+        for entry in entries {
+            let key = options.key(
+                &entry.method,
+                &entry.url,
+                &entry.request_headers,
+                entry.request_body.as_deref(),
+            );
+            exchanges.entry(key).or_default().push_back(entry);
+        }
+
+        Self { exchanges }
+    }
+
+    /// The next exchange of the key.
+    pub(crate) fn next(&mut self, key: &str) -> Option<RecordedExchange> {
+        let queue = self.exchanges.get_mut(key)?;
+
+        if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        }
+    }
+}
+
+impl Page {
+    /// Record the network traffic of the page until the recorder is stopped, e.g. around a
+    /// navigation, into a [`RecordingArchive`] replayed with `Page::start_replay`.
+    pub async fn start_recording(&self) -> Result<Recorder> {
+        let mut events = self.event_listener::<EventRequestPaused>().await?;
+
+        self.execute(EnableParams {
+            patterns: Some(vec![RequestPattern {
+                url_pattern: Some("*".into()),
+                resource_type: None,
+                request_stage: Some(RequestStage::Response),
+            }]),
+            handle_auth_requests: None,
+        })
+        .await?;
+
+        let entries: Arc<Mutex<Vec<RecordedExchange>>> = Default::default();
+        let page = self.clone();
+        let recorded = entries.clone();
+
+        let handle = crate::runtime::spawn(async move {
+            let mut sequence = 0;
+
+            while let Some(event) = events.next().await {
+                if let Some(status) = event.response_status_code {
+                    // redirects and failed bodies are recorded without a body.
+                    let body = match page
+                        .execute(GetResponseBodyParams::new(event.request_id.clone()))
+                        .await
+                    {
+                        Ok(body) if body.result.base64_encoded => body.result.body.clone(),
+                        Ok(body) => general_purpose::STANDARD.encode(body.result.body.as_bytes()),
+                        _ => String::new(),
+                    };
+
+                    let exchange = RecordedExchange {
+                        sequence,
+                        method: event.request.method.clone(),
+                        url: event.request.url.clone(),
+                        request_headers: header_map(&event.request.headers),
+                        request_body: request_body(&event),
+                        status,
+                        response_headers: event
+                            .response_headers
+                            .iter()
+                            .flatten()
+                            .map(|header| (header.name.clone(), header.value.clone()))
+                            .collect(),
+                        body,
+                    };
+                    sequence += 1;
+
+                    if let Ok(mut entries) = recorded.lock() {
+                        entries.push(exchange);
+                    }
+                }
+
+                if let Err(err) = page
+                    .send_command(ContinueRequestParams::new(event.request_id.clone()))
+                    .await
+                {
+                    tracing::debug!("recorder failed to continue {}: {err}", event.request.url);
+                }
+            }
+        });
+
+        Ok(Recorder {
+            page: self.clone(),
+            entries,
+            handle,
+        })
+    }
+
+    /// Replay the archive to the page without a network: every request is fulfilled with its
+    /// recorded exchange, the requests without one are answered as `on_miss`.
+    pub async fn start_replay(
+        &self,
+        archive: RecordingArchive,
+        options: MatchOptions,
+        on_miss: ReplayMiss,
+    ) -> Result<Replayer> {
+        let mut events = self.event_listener::<EventRequestPaused>().await?;
+
+        self.execute(EnableParams {
+            patterns: Some(vec![RequestPattern {
+                url_pattern: Some("*".into()),
+                resource_type: None,
+                request_stage: Some(RequestStage::Request),
+            }]),
+            handle_auth_requests: None,
+        })
+        .await?;
+
+        let mut index = ReplayIndex::new(archive, &options);
+        let misses: Arc<Mutex<Vec<String>>> = Default::default();
+        let page = self.clone();
+        let missed = misses.clone();
+
+        let handle = crate::runtime::spawn(async move {
+            while let Some(event) = events.next().await {
+                let url = event.request.url.as_str();
+
+                // only the network is replayed.
+                if !url.starts_with("http") {
+                    let _ = page
+                        .send_command(ContinueRequestParams::new(event.request_id.clone()))
+                        .await;
+                    continue;
+                }
+
+                let key = options.key(
+                    &event.request.method,
+                    url,
+                    &header_map(&event.request.headers),
+                    request_body(&event).as_deref(),
+                );
+
+                let result = match index.next(&key) {
+                    Some(exchange) => {
+                        let mut params =
+                            FulfillRequestParams::new(event.request_id.clone(), exchange.status);
+                        params.response_headers = Some(
+                            exchange
+                                .response_headers
+                                .into_iter()
+                                .map(|(name, value)| HeaderEntry { name, value })
+                                .collect(),
+                        );
+                        params.body = Some(exchange.body.into());
+                        page.send_command(params).await.map(|_| ())
+                    }
+                    _ => {
+                        tracing::debug!("replay miss: {key}");
+                        if let Ok(mut misses) = missed.lock() {
+                            misses.push(url.to_string());
+                        }
+
+                        match on_miss {
+                            ReplayMiss::Fail => page
+                                .send_command(FailRequestParams::new(
+                                    event.request_id.clone(),
+                                    ErrorReason::InternetDisconnected,
+                                ))
+                                .await
+                                .map(|_| ()),
+                            ReplayMiss::NotFound => page
+                                .send_command(FulfillRequestParams::new(
+                                    event.request_id.clone(),
+                                    404,
+                                ))
+                                .await
+                                .map(|_| ()),
+                        }
+                    }
+                };
+
+                if let Err(err) = result {
+                    tracing::debug!("replayer failed to answer {url}: {err}");
+                }
+            }
+        });
+
+        Ok(Replayer {
+            page: self.clone(),
+            misses,
+            handle,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(sequence: u64, url: &str, body: &str) -> RecordedExchange {
+        RecordedExchange {
+            sequence,
+            method: "GET".into(),
+            url: url.into(),
+            status: 200,
+            body: general_purpose::STANDARD.encode(body),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_the_requests() {
+        let options = MatchOptions {
+            ignore_query_params: vec!["_".into()],
+            match_headers: vec!["Accept-Language".into()],
+            ..Default::default()
+        };
+        let mut headers = BTreeMap::new();
+        headers.insert("accept-language".to_string(), "en".to_string());
+
+        assert_eq!(
+            options.key(
+                "get",
+                "https://example.com/a?b=2&a=1&_=123#top",
+                &headers,
+                None
+            ),
+            options.key("GET", "https://example.com/a?a=1&b=2", &headers, None)
+        );
+        assert_ne!(
+            options.key("GET", "https://example.com/a", &headers, None),
+            options.key("GET", "https://example.com/a", &BTreeMap::new(), None)
+        );
+        assert_ne!(
+            options.key("POST", "https://example.com/a", &headers, Some("e30=")),
+            options.key("POST", "https://example.com/a", &headers, Some("e31="))
+        );
+    }
+
+    #[test]
+    fn replays_in_the_recorded_order() {
+        let archive = RecordingArchive {
+            version: ARCHIVE_VERSION,
+            entries: vec![
+                exchange(1, "https://example.com/poll", "second"),
+                exchange(0, "https://example.com/poll", "first"),
+            ],
+        };
+        let options = MatchOptions::default();
+        let mut index = ReplayIndex::new(archive, &options);
+        let key = options.key("GET", "https://example.com/poll", &BTreeMap::new(), None);
+
+        assert_eq!(index.next(&key).unwrap().body_bytes(), b"first");
+        assert_eq!(index.next(&key).unwrap().body_bytes(), b"second");
+        assert_eq!(index.next(&key).unwrap().body_bytes(), b"second");
+        assert!(index.next("GET https://example.com/other").is_none());
+    }
+
+    #[test]
+    fn archives_round_trip() {
+        let archive = RecordingArchive {
+            version: ARCHIVE_VERSION,
+            entries: vec![exchange(0, "https://example.com/", "<html></html>")],
+        };
+        let path =
+            std::env::temp_dir().join(format!("chromey-recording-{}.json", std::process::id()));
+
+        archive.save(&path).unwrap();
+        assert_eq!(RecordingArchive::load(&path).unwrap(), archive);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::sec_fetch::fetch_site;
+use crate::urlnorm::without_fragment;
+
+/// The hops followed at most by default, as many as Chrome follows.
+pub const DEFAULT_MAX_HOPS: usize = 20;
+
+/// How long a loaded navigation waits for a client redirect by default, disabled: the wait
+/// delays every navigation and is opt-in with `RedirectPolicy::client_redirect_wait`.
+pub const DEFAULT_CLIENT_REDIRECT_WAIT: Duration = Duration::ZERO;
+
+/// Which redirects to another origin are followed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CrossOrigin {
+    /// Follow every redirect.
+    #[default]
+    Allow,
+    /// Fail the navigation on a redirect to another origin.
+    Deny,
+    /// Follow the redirects to another origin of the same site, e.g. `a.example.com` to
+    /// `www.example.com`.
+    SameSite,
+}
+
+/// What is done with a meta refresh or a `Refresh` header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetaRefresh {
+    /// Follow the refresh as a hop of the redirect chain.
+    #[default]
+    Follow,
+    /// Stay on the document of the refresh.
+    Ignore,
+    /// Fail the navigation.
+    Deny,
+}
+
+/// How a hop of a redirect chain was issued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    /// A `3xx` response with the status.
+    Http(u16),
+    /// A `Refresh` response header.
+    HeaderRefresh,
+    /// A `<meta http-equiv="refresh">` tag.
+    MetaRefresh,
+    /// A script assigning the location of the document.
+    Script,
+}
+
+impl RedirectKind {
+    /// The hop is a meta refresh or a `Refresh` header.
+    pub fn is_refresh(&self) -> bool {
+        matches!(self, Self::HeaderRefresh | Self::MetaRefresh)
+    }
+}
+
+/// A followed hop of a redirect chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectHop {
+    /// The url redirecting.
+    pub from: String,
+    /// The url redirected to.
+    pub to: String,
+    /// How the redirect was issued.
+    pub kind: RedirectKind,
+}
+
+/// The redirects followed during a navigation, see `BrowserConfigBuilder::with_redirect_policy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    /// The hops followed at most.
+    pub max_hops: usize,
+    /// Which redirects to another origin are followed.
+    pub cross_origin: CrossOrigin,
+    /// What is done with the meta refreshes and the `Refresh` headers.
+    pub on_meta_refresh: MetaRefresh,
+    /// How long a loaded navigation waits for an immediate meta refresh or script redirect,
+    /// which the navigation then follows to the final document. Disabled when zero, the
+    /// default, e.g. `250ms` catches most immediate client redirects.
+    pub client_redirect_wait: Duration,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_hops: DEFAULT_MAX_HOPS,
+            cross_origin: CrossOrigin::default(),
+            on_meta_refresh: MetaRefresh::default(),
+            client_redirect_wait: DEFAULT_CLIENT_REDIRECT_WAIT,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Follow at most `max_hops` hops.
+    pub fn max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// Which redirects to another origin are followed.
+    pub fn cross_origin(mut self, cross_origin: CrossOrigin) -> Self {
+        self.cross_origin = cross_origin;
+        self
+    }
+
+    /// What is done with the meta refreshes and the `Refresh` headers.
+    pub fn on_meta_refresh(mut self, on_meta_refresh: MetaRefresh) -> Self {
+        self.on_meta_refresh = on_meta_refresh;
+        self
+    }
+
+    /// How long a loaded navigation waits for a client redirect.
+    pub fn client_redirect_wait(mut self, wait: Duration) -> Self {
+        self.client_redirect_wait = wait;
+        self
+    }
+}
+
+/// A redirect the policy does not follow, the navigation fails with it.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RedirectViolation {
+    /// The chain is longer than `RedirectPolicy::max_hops`.
+    #[error("Too many redirects, more than {max} hops to {url}.")]
+    TooManyHops { max: usize, url: String },
+    /// The chain redirects from a url to another a second time.
+    #[error("Redirect loop from {from} to {to}.")]
+    Loop { from: String, to: String },
+    /// A redirect to another origin denied by `RedirectPolicy::cross_origin`.
+    #[error("Cross origin redirect from {from} to {to} denied.")]
+    CrossOrigin { from: String, to: String },
+    /// A refresh denied by `RedirectPolicy::on_meta_refresh`.
+    #[error("Meta refresh from {from} to {to} denied.")]
+    MetaRefresh { from: String, to: String },
+}
+
+/// What is done with a hop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RedirectVerdict {
+    /// Follow the hop.
+    Follow,
+    /// A change of the fragment staying on the document, not a hop.
+    SameDocument,
+    /// Stop loading and stay on the current document.
+    Ignore,
+    /// Stop loading and fail the navigation.
+    Violation(RedirectViolation),
+}
+
+/// The redirect chain of a navigation, checked against the policy when set. Without a policy
+/// the loops and the chains longer than [`DEFAULT_MAX_HOPS`] fail, the frame manager only
+/// checks them while a navigation is in flight.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RedirectTracker {
+    policy: Option<RedirectPolicy>,
+    hops: Vec<RedirectHop>,
+    /// The hops followed, without the fragments of the urls.
+    edges: HashSet<(String, String)>,
+}
+
+impl RedirectTracker {
+    /// A tracker checking the hops against the policy, only the loops and the default max hops
+    /// without one.
+    pub fn new(policy: Option<RedirectPolicy>) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// The hops followed.
+    pub fn hops(&self) -> &[RedirectHop] {
+        &self.hops
+    }
+
+    /// Check a hop and record it when followed. A url redirecting to the same url a second
+    /// time is a loop, a chain visiting a url twice like a cookie check is not. The hops not
+    /// followed are not recorded.
+    pub fn on_redirect(&mut self, from: &str, to: &str, kind: RedirectKind) -> RedirectVerdict {
+        let edge = (without_fragment(from), without_fragment(to));
+
+        if edge.0 == edge.1 && from != to {
+            return RedirectVerdict::SameDocument;
+        }
+
+        if let Some(verdict) = self.check(from, to, kind, &edge) {
+            return verdict;
+        }
+
+        self.record(edge, from, to, kind)
+    }
+
+    /// Record a hop without checking it, a chain longer than [`DEFAULT_MAX_HOPS`] starts over
+    /// to keep the tracker bounded on a page redirecting for its whole life.
+    pub fn follow(&mut self, from: &str, to: &str, kind: RedirectKind) -> RedirectVerdict {
+        let edge = (without_fragment(from), without_fragment(to));
+
+        if edge.0 == edge.1 && from != to {
+            return RedirectVerdict::SameDocument;
+        }
+
+        if self.hops.len() >= DEFAULT_MAX_HOPS {
+            self.hops.clear();
+            self.edges.clear();
+        }
+
+        self.record(edge, from, to, kind)
+    }
+
+    /// Record a followed hop.
+    fn record(
+        &mut self,
+        edge: (String, String),
+        from: &str,
+        to: &str,
+        kind: RedirectKind,
+    ) -> RedirectVerdict {
+        self.edges.insert(edge);
+        self.hops.push(RedirectHop {
+            from: from.into(),
+            to: to.into(),
+            kind,
+        });
+
+        RedirectVerdict::Follow
+    }
+
+    /// The verdict of the policy on a hop not followed.
+    fn check(
+        &self,
+        from: &str,
+        to: &str,
+        kind: RedirectKind,
+        edge: &(String, String),
+    ) -> Option<RedirectVerdict> {
+        let violation = |violation| Some(RedirectVerdict::Violation(violation));
+        let max_hops = self
+            .policy
+            .as_ref()
+            .map_or(DEFAULT_MAX_HOPS, |policy| policy.max_hops);
+
+        if let Some(policy) = self.policy.as_ref().filter(|_| kind.is_refresh()) {
+            match policy.on_meta_refresh {
+                MetaRefresh::Follow => (),
+                MetaRefresh::Ignore => return Some(RedirectVerdict::Ignore),
+                MetaRefresh::Deny => {
+                    return violation(RedirectViolation::MetaRefresh {
+                        from: from.into(),
+                        to: to.into(),
+                    })
+                }
+            }
+        }
+
+        if self.edges.contains(edge) {
+            return violation(RedirectViolation::Loop {
+                from: from.into(),
+                to: to.into(),
+            });
+        }
+
+        if self.hops.len() >= max_hops {
+            return violation(RedirectViolation::TooManyHops {
+                max: max_hops,
+                url: to.into(),
+            });
+        }
+
+        let policy = self.policy.as_ref()?;
+        let allowed = match (policy.cross_origin, fetch_site(to, Some(from), true)) {
+            (CrossOrigin::Allow, _) | (_, "same-origin") => true,
+            (CrossOrigin::SameSite, "same-site") => true,
+            _ => false,
+        };
+
+        if !allowed {
+            return violation(RedirectViolation::CrossOrigin {
+                from: from.into(),
+                to: to.into(),
+            });
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_loops() {
+        let mut tracker = RedirectTracker::new(Some(RedirectPolicy::default()));
+        let (a, b) = ("https://a.com/", "https://a.com/login");
+
+        assert_eq!(
+            tracker.on_redirect(a, b, RedirectKind::Http(302)),
+            RedirectVerdict::Follow
+        );
+        // back to the start once is a cookie check, not a loop.
+        assert_eq!(
+            tracker.on_redirect(b, a, RedirectKind::Script),
+            RedirectVerdict::Follow
+        );
+        assert_eq!(
+            tracker.on_redirect(a, "https://a.com/login#retry", RedirectKind::Http(302)),
+            RedirectVerdict::Violation(RedirectViolation::Loop {
+                from: a.into(),
+                to: "https://a.com/login#retry".into(),
+            })
+        );
+        assert_eq!(
+            tracker.on_redirect(a, "https://a.com/#top", RedirectKind::Script),
+            RedirectVerdict::SameDocument
+        );
+        assert_eq!(tracker.hops().len(), 2);
+    }
+
+    #[test]
+    fn stops_the_loops_without_a_policy() {
+        let mut tracker = RedirectTracker::new(None);
+        let (a, b) = ("https://a.com/", "https://b.com/");
+
+        assert_eq!(
+            tracker.on_redirect(a, b, RedirectKind::MetaRefresh),
+            RedirectVerdict::Follow
+        );
+        assert_eq!(
+            tracker.on_redirect(b, a, RedirectKind::Script),
+            RedirectVerdict::Follow
+        );
+        assert_eq!(
+            tracker.on_redirect(a, b, RedirectKind::MetaRefresh),
+            RedirectVerdict::Violation(RedirectViolation::Loop {
+                from: a.into(),
+                to: b.into(),
+            })
+        );
+        assert_eq!(
+            tracker.hops()[0],
+            RedirectHop {
+                from: a.into(),
+                to: b.into(),
+                kind: RedirectKind::MetaRefresh,
+            }
+        );
+        assert_eq!(tracker.hops().len(), 2);
+    }
+
+    #[test]
+    fn follows_the_unchecked_hops() {
+        let mut tracker = RedirectTracker::new(None);
+        let (a, b) = ("https://a.com/", "https://a.com/b");
+
+        for _ in 0..DEFAULT_MAX_HOPS {
+            assert_eq!(
+                tracker.follow(a, b, RedirectKind::Script),
+                RedirectVerdict::Follow
+            );
+            assert_eq!(
+                tracker.follow(b, a, RedirectKind::Script),
+                RedirectVerdict::Follow
+            );
+        }
+
+        assert!(tracker.hops().len() <= DEFAULT_MAX_HOPS);
+        assert_eq!(
+            tracker.follow(a, "https://a.com/#top", RedirectKind::Script),
+            RedirectVerdict::SameDocument
+        );
+    }
+
+    #[test]
+    fn limits_the_hops_without_a_policy() {
+        let mut tracker = RedirectTracker::new(None);
+        let url = |hop: usize| format!("https://a.com/{hop}");
+
+        for hop in 0..DEFAULT_MAX_HOPS {
+            assert_eq!(
+                tracker.on_redirect(&url(hop), &url(hop + 1), RedirectKind::Http(302)),
+                RedirectVerdict::Follow
+            );
+        }
+
+        assert!(matches!(
+            tracker.on_redirect(
+                &url(DEFAULT_MAX_HOPS),
+                &url(DEFAULT_MAX_HOPS + 1),
+                RedirectKind::Http(302)
+            ),
+            RedirectVerdict::Violation(RedirectViolation::TooManyHops {
+                max: DEFAULT_MAX_HOPS,
+                ..
+            })
+        ));
+        assert_eq!(
+            RedirectPolicy::default().client_redirect_wait,
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn limits_the_hops() {
+        let mut tracker = RedirectTracker::new(Some(RedirectPolicy::default().max_hops(1)));
+
+        assert_eq!(
+            tracker.on_redirect(
+                "https://a.com/1",
+                "https://a.com/2",
+                RedirectKind::Http(301)
+            ),
+            RedirectVerdict::Follow
+        );
+        assert!(matches!(
+            tracker.on_redirect(
+                "https://a.com/2",
+                "https://a.com/3",
+                RedirectKind::Http(301)
+            ),
+            RedirectVerdict::Violation(RedirectViolation::TooManyHops { max: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn checks_the_origins_and_refreshes() {
+        let policy = RedirectPolicy::default()
+            .cross_origin(CrossOrigin::SameSite)
+            .on_meta_refresh(MetaRefresh::Ignore);
+        let mut tracker = RedirectTracker::new(Some(policy));
+
+        assert_eq!(
+            tracker.on_redirect(
+                "https://example.com/",
+                "https://www.example.com/",
+                RedirectKind::Http(301)
+            ),
+            RedirectVerdict::Follow
+        );
+        assert!(matches!(
+            tracker.on_redirect(
+                "https://www.example.com/",
+                "https://tracker.net/",
+                RedirectKind::Script
+            ),
+            RedirectVerdict::Violation(RedirectViolation::CrossOrigin { .. })
+        ));
+        assert_eq!(
+            tracker.on_redirect(
+                "https://www.example.com/",
+                "https://www.example.com/next",
+                RedirectKind::MetaRefresh
+            ),
+            RedirectVerdict::Ignore
+        );
+
+        let mut tracker = RedirectTracker::new(Some(
+            RedirectPolicy::default()
+                .cross_origin(CrossOrigin::Deny)
+                .on_meta_refresh(MetaRefresh::Deny),
+        ));
+
+        assert!(matches!(
+            tracker.on_redirect(
+                "https://example.com/",
+                "https://www.example.com/",
+                RedirectKind::Http(301)
+            ),
+            RedirectVerdict::Violation(RedirectViolation::CrossOrigin { .. })
+        ));
+        assert!(matches!(
+            tracker.on_redirect(
+                "https://example.com/",
+                "https://example.com/next",
+                RedirectKind::HeaderRefresh
+            ),
+            RedirectVerdict::Violation(RedirectViolation::MetaRefresh { .. })
+        ));
+    }
+}
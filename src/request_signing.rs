@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::HeaderEntry;
+use chromiumoxide_cdp::cdp::browser_protocol::network::Request;
+use sha2::{Digest, Sha256};
+
+use crate::sec_fetch::{header, header_entries, set_header};
+
+/// An intercepted request about to be continued, with its final url, headers and body.
+#[derive(Debug, Clone)]
+pub struct SignableRequest {
+    /// The url the request is sent to.
+    pub url: String,
+    /// The request method.
+    pub method: String,
+    /// The headers sent with the request, after header shaping.
+    pub headers: Vec<HeaderEntry>,
+    /// The request body, empty when the request has none.
+    pub body: Vec<u8>,
+}
+
+impl SignableRequest {
+    /// The value of a header by case insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    /// Set a header, replacing a header of the same case insensitive name.
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        set_header(&mut self.headers, name, Some(value.into()));
+    }
+
+    /// Remove a header by case insensitive name.
+    pub fn remove_header(&mut self, name: &str) {
+        set_header(&mut self.headers, name, None);
+    }
+}
+
+/// Computes the signature of a request and adds it to the headers.
+///
+/// Signers run on the handler for every matching request and must not block.
+pub trait RequestSigner: Send + Sync {
+    /// Sign the request.
+    fn sign(&self, request: &mut SignableRequest);
+}
+
+impl<F> RequestSigner for F
+where
+    F: Fn(&mut SignableRequest) + Send + Sync,
+{
+    fn sign(&self, request: &mut SignableRequest) {
+        self(request)
+    }
+}
+
+/// A signer for the requests matching a url pattern.
+#[derive(Clone)]
+pub struct SigningRule {
+    /// Substring the request url must contain, every request matches when `None`.
+    pub url_pattern: Option<String>,
+    /// The signer of the matching requests.
+    pub signer: Arc<dyn RequestSigner>,
+}
+
+impl std::fmt::Debug for SigningRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningRule")
+            .field("url_pattern", &self.url_pattern)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningRule {
+    fn matches(&self, url: &str) -> bool {
+        self.url_pattern
+            .as_deref()
+            .map_or(true, |p| url.contains(p))
+    }
+}
+
+/// Ordered signing rules, the first matching rule signs the request.
+///
+/// Requests are signed by the network interception of the handler, so the page request
+/// interception must be enabled and the requests must not be handled by a user interceptor.
+#[derive(Debug, Default, Clone)]
+pub struct RequestSigning {
+    /// The rules in priority order.
+    pub rules: Vec<SigningRule>,
+}
+
+impl RequestSigning {
+    /// Sign the requests whose url contains the pattern.
+    pub fn matching(
+        mut self,
+        url_pattern: impl Into<String>,
+        signer: impl RequestSigner + 'static,
+    ) -> Self {
+        self.rules.push(SigningRule {
+            url_pattern: Some(url_pattern.into()),
+            signer: Arc::new(signer),
+        });
+        self
+    }
+
+    /// Sign every request.
+    pub fn all(mut self, signer: impl RequestSigner + 'static) -> Self {
+        self.rules.push(SigningRule {
+            url_pattern: None,
+            signer: Arc::new(signer),
+        });
+        self
+    }
+
+    /// Sign the paused request sent to the url, starting from the shaped headers if any.
+    /// Returns the headers to continue the request with, the shaped headers when no rule matches.
+    pub(crate) fn sign(
+        &self,
+        url: &str,
+        request: &Request,
+        shaped: Option<Vec<HeaderEntry>>,
+    ) -> Option<Vec<HeaderEntry>> {
+        let rule = match self.rules.iter().find(|r| r.matches(url)) {
+            Some(rule) => rule,
+            _ => return shaped,
+        };
+
+        let body = request
+            .post_data_entries
+            .iter()
+            .flatten()
+            .filter_map(|entry| entry.bytes.as_ref())
+            .filter_map(|bytes| crate::utils::base64::decode(&bytes.0).ok())
+            .flatten()
+            .collect();
+
+        let mut signable = SignableRequest {
+            url: url.to_string(),
+            method: request.method.clone(),
+            headers: shaped.unwrap_or_else(|| header_entries(request.headers.inner())),
+            body,
+        };
+
+        rule.signer.sign(&mut signable);
+
+        Some(signable.headers)
+    }
+}
+
+/// The HMAC-SHA256 of the data.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner_pad: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    let outer_pad: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+
+    let inner = Sha256::new()
+        .chain_update(&inner_pad)
+        .chain_update(data)
+        .finalize();
+
+    Sha256::new()
+        .chain_update(&outer_pad)
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// The lowercase hex SHA-256 of the data, e.g. the payload hash of a SigV4 signature.
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Lowercase hex encoding.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_sha256_rfc4231() {
+        assert_eq!(
+            to_hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn sign_matching_request() {
+        let signing = RequestSigning::default().matching(
+            "api.example.com",
+            |request: &mut SignableRequest| {
+                let payload = format!(
+                    "{}\n{}\n{}",
+                    request.method,
+                    request.url,
+                    sha256_hex(&request.body)
+                );
+                let signature = to_hex(&hmac_sha256(b"secret", payload.as_bytes()));
+                request.set_header("X-Signature", signature);
+            },
+        );
+
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "url": "https://api.example.com/v1/items",
+            "method": "POST",
+            "headers": { "Content-Type": "application/json" },
+            "postDataEntries": [{ "bytes": "e30=" }],
+            "initialPriority": "High",
+            "referrerPolicy": "no-referrer"
+        }))
+        .unwrap();
+
+        let headers = signing
+            .sign("https://api.example.com/v1/items", &request, None)
+            .unwrap();
+        let payload = format!(
+            "POST\nhttps://api.example.com/v1/items\n{}",
+            sha256_hex(b"{}")
+        );
+
+        assert_eq!(header(&headers, "content-type"), Some("application/json"));
+        assert_eq!(
+            header(&headers, "x-signature"),
+            Some(to_hex(&hmac_sha256(b"secret", payload.as_bytes())).as_str())
+        );
+        assert!(signing
+            .sign("https://www.example.com/", &request, None)
+            .is_none());
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chromiumoxide_cdp::cdp::browser_protocol::browser::BrowserContextId;
+use chromiumoxide_cdp::cdp::browser_protocol::emulation::{
+    SetDeviceMetricsOverrideParams, SetLocaleOverrideParams, SetTimezoneOverrideParams,
+    SetTouchEmulationEnabledParams,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    Cookie, CookieParam, SetUserAgentOverrideParams, TimeSinceEpoch,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::page::{FontFamilies, SetFontFamiliesParams};
+use chromiumoxide_cdp::cdp::browser_protocol::storage::{
+    ClearCookiesParams, GetCookiesParams, SetCookiesParams,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::target::CreateTargetParams;
+
+use crate::browser::Browser;
+use crate::error::{CdpError, Result};
+use crate::handler::viewport::Viewport;
+use crate::page::Page;
+use crate::runtime::Instant;
+
+/// A consistent browser fingerprint assigned to a browser context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    /// The name of the fingerprint, keeping its cookie jar apart from the others.
+    pub name: String,
+    /// The user agent.
+    pub user_agent: String,
+    /// The `navigator.platform` matching the user agent.
+    pub platform: Option<String>,
+    /// The `Accept-Language` header, e.g. `en-US,en;q=0.9`.
+    pub accept_language: Option<String>,
+    /// The ICU locale, e.g. `en-US`.
+    pub locale: Option<String>,
+    /// The IANA timezone, e.g. `America/New_York`.
+    pub timezone: Option<String>,
+    /// The viewport.
+    pub viewport: Viewport,
+    /// The generic font families.
+    pub fonts: Option<FontFamilies>,
+}
+
+impl Fingerprint {
+    /// A fingerprint of the user agent with the default viewport.
+    pub fn new(name: impl Into<String>, user_agent: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            user_agent: user_agent.into(),
+            platform: None,
+            accept_language: None,
+            locale: None,
+            timezone: None,
+            viewport: Viewport::default(),
+            fonts: None,
+        }
+    }
+
+    /// Apply the fingerprint to the page.
+    pub async fn apply(&self, page: &Page) -> Result<()> {
+        let mut user_agent = SetUserAgentOverrideParams::new(self.user_agent.clone());
+        user_agent.accept_language = self.accept_language.clone();
+        user_agent.platform = self.platform.clone();
+        page.execute(user_agent).await?;
+
+        let viewport = SetDeviceMetricsOverrideParams::builder()
+            .width(self.viewport.width)
+            .height(self.viewport.height)
+            .device_scale_factor(self.viewport.device_scale_factor.unwrap_or(1.))
+            .mobile(self.viewport.emulating_mobile)
+            .build()
+            .map_err(CdpError::msg)?;
+        page.execute(viewport).await?;
+
+        if self.viewport.has_touch {
+            page.execute(SetTouchEmulationEnabledParams::new(true))
+                .await?;
+        }
+
+        if let Some(locale) = &self.locale {
+            let mut params = SetLocaleOverrideParams::default();
+            params.locale = Some(locale.clone());
+            page.execute(params).await?;
+        }
+
+        if let Some(timezone) = &self.timezone {
+            page.execute(SetTimezoneOverrideParams::new(timezone.clone()))
+                .await?;
+        }
+
+        if let Some(fonts) = &self.fonts {
+            page.execute(SetFontFamiliesParams::new(fonts.clone()))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// When the fingerprint of a browser context rotates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// Rotate after the pages opened with the fingerprint.
+    pub max_pages: Option<usize>,
+    /// Rotate after the fingerprint was assigned for the duration.
+    pub max_age: Option<Duration>,
+}
+
+impl RotationPolicy {
+    /// Never rotate, every context keeps its first fingerprint.
+    pub fn never() -> Self {
+        Self::default()
+    }
+
+    /// Rotate after every `n` pages.
+    pub fn every_pages(n: usize) -> Self {
+        Self {
+            max_pages: Some(n.max(1)),
+            max_age: None,
+        }
+    }
+
+    /// Rotate after the time window.
+    pub fn every(window: Duration) -> Self {
+        Self {
+            max_pages: None,
+            max_age: Some(window),
+        }
+    }
+
+    fn is_due(&self, pages: usize, age: Duration) -> bool {
+        self.max_pages.is_some_and(|max| pages >= max) || self.max_age.is_some_and(|max| age >= max)
+    }
+}
+
+/// The fingerprint assigned to a browser context.
+#[derive(Debug, Clone)]
+struct Assignment {
+    index: usize,
+    pages: usize,
+    since: Instant,
+}
+
+/// The fingerprint of the next page of a browser context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lease {
+    /// The fingerprint to apply.
+    pub fingerprint: Fingerprint,
+    /// The fingerprint the context used before, when it rotated with this page.
+    pub rotated_from: Option<Fingerprint>,
+}
+
+/// Assigns a consistent fingerprint per browser context and rotates it with the policy.
+///
+/// Every fingerprint keeps its own cookie jar: when the fingerprint of a context rotates the
+/// cookies of the context are stored with the old fingerprint and the cookies of the new one are
+/// restored, so a site never sees the session of one fingerprint with another.
+#[derive(Debug)]
+pub struct FingerprintRotator {
+    pool: Vec<Fingerprint>,
+    policy: RotationPolicy,
+    next: AtomicUsize,
+    assignments: Mutex<HashMap<BrowserContextId, Assignment>>,
+    jars: Mutex<HashMap<String, Vec<Cookie>>>,
+}
+
+impl FingerprintRotator {
+    /// A rotator over the pool of fingerprints, assigned round robin.
+    pub fn new(pool: Vec<Fingerprint>, policy: RotationPolicy) -> Result<Self> {
+        if pool.is_empty() {
+            return Err(CdpError::msg("the fingerprint pool is empty"));
+        }
+
+        Ok(Self {
+            pool,
+            policy,
+            next: AtomicUsize::new(0),
+            assignments: Mutex::new(HashMap::new()),
+            jars: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The rotation policy.
+    pub fn policy(&self) -> &RotationPolicy {
+        &self.policy
+    }
+
+    fn next_index(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % self.pool.len()
+    }
+
+    /// The fingerprint of the next page of the context, rotating it when the policy is due.
+    pub fn lease(&self, context: &BrowserContextId) -> Lease {
+        let mut assignments = self.assignments.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut rotated_from = None;
+
+        let assignment = assignments
+            .entry(context.clone())
+            .or_insert_with(|| Assignment {
+                index: self.next_index(),
+                pages: 0,
+                since: Instant::now(),
+            });
+
+        if assignment.pages > 0
+            && self
+                .policy
+                .is_due(assignment.pages, assignment.since.elapsed())
+        {
+            let mut index = self.next_index();
+
+            // a pool of several fingerprints always changes the fingerprint on rotation.
+            if index == assignment.index && self.pool.len() > 1 {
+                index = self.next_index();
+            }
+
+            rotated_from = Some(self.pool[assignment.index].clone());
+            *assignment = Assignment {
+                index,
+                pages: 0,
+                since: Instant::now(),
+            };
+        }
+
+        assignment.pages += 1;
+
+        Lease {
+            fingerprint: self.pool[assignment.index].clone(),
+            rotated_from,
+        }
+    }
+
+    /// The fingerprint currently assigned to the context.
+    pub fn current(&self, context: &BrowserContextId) -> Option<Fingerprint> {
+        self.assignments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(context)
+            .map(|assignment| self.pool[assignment.index].clone())
+    }
+
+    /// Forget the context, e.g. after it was disposed. The cookie jars of the fingerprints stay.
+    pub fn release(&self, context: &BrowserContextId) {
+        self.assignments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(context);
+    }
+
+    /// Open a page in the context with its fingerprint applied, swapping the cookie jar of the
+    /// context when the fingerprint rotates.
+    pub async fn new_page(
+        &self,
+        browser: &Browser,
+        context: &BrowserContextId,
+        url: impl Into<String>,
+    ) -> Result<Page> {
+        let lease = self.lease(context);
+
+        if let Some(previous) = &lease.rotated_from {
+            self.swap_jar(browser, context, previous, &lease.fingerprint)
+                .await?;
+        }
+
+        let mut params = CreateTargetParams::new("about:blank");
+        params.browser_context_id = Some(context.clone());
+
+        let page = browser.new_page(params).await?;
+
+        lease.fingerprint.apply(&page).await?;
+        page.goto(url.into()).await?;
+
+        Ok(page)
+    }
+
+    /// Store the cookies of the context with the previous fingerprint and restore the ones of the
+    /// next fingerprint.
+    async fn swap_jar(
+        &self,
+        browser: &Browser,
+        context: &BrowserContextId,
+        previous: &Fingerprint,
+        next: &Fingerprint,
+    ) -> Result<()> {
+        let mut get_cookies = GetCookiesParams::default();
+        get_cookies.browser_context_id = Some(context.clone());
+        let cookies = browser.execute(get_cookies).await?.result.cookies;
+
+        let mut clear_cookies = ClearCookiesParams::default();
+        clear_cookies.browser_context_id = Some(context.clone());
+        browser.execute(clear_cookies).await?;
+
+        let restore = {
+            let mut jars = self.jars.lock().unwrap_or_else(|e| e.into_inner());
+            jars.insert(previous.name.clone(), cookies);
+            jars.get(&next.name).cloned().unwrap_or_default()
+        };
+
+        if !restore.is_empty() {
+            let mut set_cookies =
+                SetCookiesParams::new(restore.iter().map(cookie_param).collect::<Vec<_>>());
+            set_cookies.browser_context_id = Some(context.clone());
+            browser.execute(set_cookies).await?;
+        }
+
+        Ok(())
+    }
+
+    /// The cookies stored with the fingerprint while it is rotated out.
+    pub fn stored_cookies(&self, fingerprint: &str) -> Vec<Cookie> {
+        self.jars
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(fingerprint)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// The parameter restoring a stored cookie.
+pub(crate) fn cookie_param(cookie: &Cookie) -> CookieParam {
+    let mut param = CookieParam::new(cookie.name.clone(), cookie.value.clone());
+
+    param.domain = Some(cookie.domain.clone());
+    param.path = Some(cookie.path.clone());
+    param.secure = Some(cookie.secure);
+    param.http_only = Some(cookie.http_only);
+    param.same_site = cookie.same_site.clone();
+    param.priority = Some(cookie.priority.clone());
+    param.source_scheme = Some(cookie.source_scheme.clone());
+    param.source_port = Some(cookie.source_port);
+    param.partition_key = cookie.partition_key.clone();
+
+    if !cookie.session {
+        param.expires = Some(TimeSinceEpoch::new(cookie.expires));
+    }
+
+    param
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotator(policy: RotationPolicy) -> FingerprintRotator {
+        FingerprintRotator::new(
+            vec![
+                Fingerprint::new("a", "agent-a"),
+                Fingerprint::new("b", "agent-b"),
+                Fingerprint::new("c", "agent-c"),
+            ],
+            policy,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn keeps_a_fingerprint_per_context() {
+        let rotator = rotator(RotationPolicy::never());
+        let first = BrowserContextId::from("first".to_string());
+        let second = BrowserContextId::from("second".to_string());
+
+        assert_eq!(rotator.lease(&first).fingerprint.name, "a");
+        assert_eq!(rotator.lease(&second).fingerprint.name, "b");
+        assert_eq!(rotator.lease(&first).fingerprint.name, "a");
+        assert_eq!(rotator.current(&second).unwrap().name, "b");
+    }
+
+    #[test]
+    fn rotates_every_pages() {
+        let rotator = rotator(RotationPolicy::every_pages(2));
+        let context = BrowserContextId::from("context".to_string());
+
+        assert!(rotator.lease(&context).rotated_from.is_none());
+        assert!(rotator.lease(&context).rotated_from.is_none());
+
+        let lease = rotator.lease(&context);
+        assert_eq!(lease.rotated_from.unwrap().name, "a");
+        assert_eq!(lease.fingerprint.name, "b");
+    }
+
+    #[test]
+    fn rejects_an_empty_pool() {
+        assert!(FingerprintRotator::new(Vec::new(), RotationPolicy::never()).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! The async runtime touchpoints of the browser and page.
+//!
+//! `tokio` is used by default, the `async-std` or `smol` features use the timers of their
+//! runtime instead, `async-std` takes precedence when both are enabled. The handler is polled
+//! by the caller, so the connection path never spawns, the background tasks of the pages are
+//! spawned with [`spawn`] on the runtime of the features. The native websocket, the process
+//! launching and the HTTP clients still require a tokio reactor, connect with
+//! `Browser::connect_with_transport` and a runtime agnostic websocket on the other runtimes. The
+//! C API of the `capi` feature drives its own tokio runtime and is not built with the
+//! `async-std` or `smol` features.
+
+use std::future::Future;
+use std::time::Duration;
+
+use futures::future::{select, Either};
+
+/// The monotonic clock of the timeouts, `std::time::Instant` panics on wasm32 without a clock
+/// so the clock of the browser is used there.
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+/// The monotonic clock of the timeouts, `std::time::Instant` panics on wasm32 without a clock
+/// so the clock of the browser is used there.
+#[cfg(target_arch = "wasm32")]
+pub use web_time::Instant;
+
+/// The handle of a task spawned with [`spawn`], awaiting it yields the output of the task. The
+/// task keeps running when the handle is dropped and stops with `abort`.
+#[cfg(not(any(feature = "async-std", feature = "smol")))]
+pub type JoinHandle<T> = tokio::task::JoinHandle<T>;
+
+/// The task of a [`JoinHandle`] was aborted.
+#[cfg(not(any(feature = "async-std", feature = "smol")))]
+pub type JoinError = tokio::task::JoinError;
+
+#[cfg(feature = "async-std")]
+type Task<T> = async_std::task::JoinHandle<T>;
+
+#[cfg(all(feature = "smol", not(feature = "async-std")))]
+type Task<T> = smol::Task<T>;
+
+/// The handle of a task spawned with [`spawn`], awaiting it yields the output of the task. The
+/// task keeps running when the handle is dropped and stops with `abort`.
+#[cfg(any(feature = "async-std", feature = "smol"))]
+pub struct JoinHandle<T> {
+    task: Option<Task<Result<T, futures::future::Aborted>>>,
+    abort: futures::future::AbortHandle,
+}
+
+/// The task of a [`JoinHandle`] was aborted.
+#[cfg(any(feature = "async-std", feature = "smol"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("task was aborted")]
+pub struct JoinError;
+
+#[cfg(any(feature = "async-std", feature = "smol"))]
+impl<T> JoinHandle<T> {
+    /// Stop the task at its next await point.
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+}
+
+#[cfg(any(feature = "async-std", feature = "smol"))]
+impl<T> std::fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("aborted", &self.abort.is_aborted())
+            .finish()
+    }
+}
+
+#[cfg(any(feature = "async-std", feature = "smol"))]
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let task = self
+            .task
+            .as_mut()
+            .expect("JoinHandle polled after completion");
+        let output = std::task::ready!(std::pin::Pin::new(task).poll(cx));
+
+        self.task = None;
+
+        std::task::Poll::Ready(output.map_err(|_| JoinError))
+    }
+}
+
+#[cfg(any(feature = "async-std", feature = "smol"))]
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        // a smol task is cancelled when dropped, detach it to keep it running like the others.
+        #[cfg(all(feature = "smol", not(feature = "async-std")))]
+        if let Some(task) = self.task.take() {
+            task.detach();
+        }
+    }
+}
+
+/// Spawn the task on the runtime.
+pub fn spawn<F>(fut: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(not(any(feature = "async-std", feature = "smol")))]
+    {
+        tokio::spawn(fut)
+    }
+
+    #[cfg(any(feature = "async-std", feature = "smol"))]
+    {
+        let (abort, registration) = futures::future::AbortHandle::new_pair();
+        let fut = futures::future::Abortable::new(fut, registration);
+
+        #[cfg(feature = "async-std")]
+        let task = async_std::task::spawn(fut);
+
+        #[cfg(all(feature = "smol", not(feature = "async-std")))]
+        let task = smol::spawn(fut);
+
+        JoinHandle {
+            task: Some(task),
+            abort,
+        }
+    }
+}
+
+/// Run the blocking closure off the tasks of the runtime, on its blocking thread pool. The
+/// closure runs inline on wasm32 without threads. A panic of the closure resumes on the caller.
+pub(crate) async fn unblock<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    #[cfg(target_arch = "wasm32")]
+    {
+        f()
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "async-std"))]
+    {
+        async_std::task::spawn_blocking(f).await
+    }
+
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        feature = "smol",
+        not(feature = "async-std")
+    ))]
+    {
+        smol::unblock(f).await
+    }
+
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        not(any(feature = "async-std", feature = "smol"))
+    ))]
+    {
+        match tokio::task::spawn_blocking(f).await {
+            Ok(output) => output,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => panic!("blocking task failed: {err}"),
+        }
+    }
+}
+
+/// The future did not complete before the timeout elapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Elapsed;
+
+/// Wait for the duration on the runtime.
+pub(crate) async fn sleep(duration: Duration) {
+    // tokio has no time driver on wasm32, the timer of the browser is used there.
+    #[cfg(target_arch = "wasm32")]
+    {
+        gloo_timers::future::sleep(duration).await;
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "async-std"))]
+    {
+        async_std::task::sleep(duration).await;
+    }
+
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        feature = "smol",
+        not(feature = "async-std")
+    ))]
+    {
+        smol::Timer::after(duration).await;
+    }
+
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        not(any(feature = "async-std", feature = "smol"))
+    ))]
+    {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// Run the future until it completes or the duration elapsed.
+pub(crate) async fn timeout<F: Future>(duration: Duration, fut: F) -> Result<F::Output, Elapsed> {
+    let fut = std::pin::pin!(fut);
+    let sleep = std::pin::pin!(sleep(duration));
+
+    match select(fut, sleep).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn timeout_elapses() {
+        let pending = futures::future::pending::<()>();
+
+        assert_eq!(
+            timeout(Duration::from_millis(10), pending).await,
+            Err(Elapsed)
+        );
+        assert_eq!(timeout(Duration::from_secs(1), async { 1 }).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn runs_the_blocking_closures() {
+        assert_eq!(unblock(|| 1 + 2).await, 3);
+    }
+
+    #[tokio::test]
+    async fn spawns_and_aborts() {
+        assert_eq!(spawn(async { 1 }).await.unwrap(), 1);
+
+        let handle = spawn(futures::future::pending::<()>());
+        handle.abort();
+
+        assert!(handle.await.is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::task::AtomicWaker;
+
+use crate::browser::Browser;
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+
+/// How often a running scope checks that the browser is still connected.
+const LIVENESS_INTERVAL: Duration = Duration::from_millis(250);
+
+type ScopedFuture<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+/// The tasks spawned on a scope and the pages they own.
+#[derive(Default)]
+struct ScopeState<'a> {
+    spawned: Vec<ScopedFuture<'a>>,
+    pages: Vec<Page>,
+}
+
+/// The scope of [`Browser::scope`], spawning page tasks which never outlive it.
+///
+/// The tasks are driven by the scope itself rather than a runtime, so they may borrow from the
+/// caller. Once the scope body returns or the browser disconnects, the unfinished tasks are
+/// dropped and their pages are closed.
+#[derive(Clone)]
+pub struct PageScope<'a> {
+    browser: &'a Browser,
+    state: Arc<Mutex<ScopeState<'a>>>,
+    waker: Arc<AtomicWaker>,
+}
+
+impl fmt::Debug for PageScope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (spawned, pages) = self
+            .state
+            .lock()
+            .map(|state| (state.spawned.len(), state.pages.len()))
+            .unwrap_or_default();
+
+        f.debug_struct("PageScope")
+            .field("spawned", &spawned)
+            .field("pages", &pages)
+            .finish()
+    }
+}
+
+impl<'a> PageScope<'a> {
+    fn new(browser: &'a Browser) -> Self {
+        Self {
+            browser,
+            state: Arc::new(Mutex::new(ScopeState::default())),
+            waker: Arc::new(AtomicWaker::new()),
+        }
+    }
+
+    /// The browser of the scope.
+    pub fn browser(&self) -> &'a Browser {
+        self.browser
+    }
+
+    /// Open a page on the url and run the task with it. The page is closed once the task
+    /// completes, or with the scope when the task did not complete.
+    pub async fn spawn_page<F, Fut, R>(
+        &self,
+        url: impl Into<String>,
+        task: F,
+    ) -> Result<ScopedTask<R>>
+    where
+        F: FnOnce(Page) -> Fut,
+        Fut: Future<Output = Result<R>> + 'a,
+        R: 'a,
+    {
+        let page = self.browser.new_page(url.into()).await?;
+
+        Ok(self.adopt(page, task))
+    }
+
+    /// Run the task with a page opened elsewhere, tying the page to the scope.
+    pub fn adopt<F, Fut, R>(&self, page: Page, task: F) -> ScopedTask<R>
+    where
+        F: FnOnce(Page) -> Fut,
+        Fut: Future<Output = Result<R>> + 'a,
+        R: 'a,
+    {
+        let (tx, rx) = oneshot::channel();
+        let target_id = page.target_id().clone();
+        let fut = task(page.clone());
+        let state = self.state.clone();
+
+        let scoped: ScopedFuture<'a> = Box::pin(async move {
+            let _ = tx.send(fut.await);
+
+            let page = state.lock().ok().and_then(|mut state| {
+                let index = state
+                    .pages
+                    .iter()
+                    .position(|page| page.target_id() == &target_id)?;
+                Some(state.pages.swap_remove(index))
+            });
+
+            if let Some(page) = page {
+                let _ = page.close().await;
+            }
+        });
+
+        if let Ok(mut state) = self.state.lock() {
+            state.pages.push(page);
+            state.spawned.push(scoped);
+        }
+        self.waker.wake();
+
+        ScopedTask { rx }
+    }
+
+    /// The pages of the unfinished tasks.
+    pub fn pages(&self) -> Vec<Page> {
+        self.state
+            .lock()
+            .map(|state| state.pages.clone())
+            .unwrap_or_default()
+    }
+
+    fn take_spawned(&self) -> Vec<ScopedFuture<'a>> {
+        self.state
+            .lock()
+            .map(|mut state| std::mem::take(&mut state.spawned))
+            .unwrap_or_default()
+    }
+
+    fn take_pages(&self) -> Vec<Page> {
+        self.state
+            .lock()
+            .map(|mut state| {
+                state.spawned.clear();
+                std::mem::take(&mut state.pages)
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The result of a task spawned on a [`PageScope`]. Resolves to `CdpError::Canceled` when the
+/// task was cancelled with its scope.
+#[derive(Debug)]
+pub struct ScopedTask<R> {
+    rx: oneshot::Receiver<Result<R>>,
+}
+
+impl<R> Future for ScopedTask<R> {
+    type Output = Result<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.rx).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            Poll::Ready(Err(canceled)) => Poll::Ready(Err(canceled.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Browser {
+    /// Resolves once the handler of the browser is gone, e.g. the browser crashed or the
+    /// connection dropped.
+    async fn disconnected(&self) {
+        while !self.sender.is_closed() {
+            crate::runtime::sleep(LIVENESS_INTERVAL).await;
+        }
+    }
+
+    /// Run the body with a scope spawning page tasks tied to it. When the body returns, the
+    /// unfinished tasks are cancelled and their pages closed. When the browser disconnects first,
+    /// the body and the tasks are cancelled and an error is returned.
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::browser::Browser;
+    /// # async fn demo(browser: Browser) -> chromiumoxide::error::Result<()> {
+    /// let titles = browser
+    ///     .scope(|scope| async move {
+    ///         let a = scope
+    ///             .spawn_page("https://example.com", |page| async move { page.get_title().await })
+    ///             .await?;
+    ///         let b = scope
+    ///             .spawn_page("https://example.org", |page| async move { page.get_title().await })
+    ///             .await?;
+    ///
+    ///         Ok::<_, chromiumoxide::error::CdpError>((a.await?, b.await?))
+    ///     })
+    ///     .await??;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn scope<'a, F, Fut, R>(&'a self, f: F) -> Result<R>
+    where
+        F: FnOnce(PageScope<'a>) -> Fut,
+        Fut: Future<Output = R> + 'a,
+    {
+        let scope = PageScope::new(self);
+
+        let mut body = Box::pin(f(scope.clone()));
+        let mut tasks: FuturesUnordered<ScopedFuture<'a>> = FuturesUnordered::new();
+        let mut disconnected = Box::pin(self.disconnected());
+
+        let outcome = futures::future::poll_fn(|cx| {
+            scope.waker.register(cx.waker());
+
+            if let Poll::Ready(result) = body.as_mut().poll(cx) {
+                return Poll::Ready(Ok(result));
+            }
+
+            tasks.extend(scope.take_spawned());
+            while let Poll::Ready(Some(())) = tasks.poll_next_unpin(cx) {}
+
+            if disconnected.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Err(CdpError::msg(
+                    "the browser disconnected while the scope was running",
+                )));
+            }
+
+            Poll::Pending
+        })
+        .await;
+
+        drop(tasks);
+        drop(body);
+
+        let pages = scope.take_pages();
+
+        if outcome.is_ok() {
+            for page in pages {
+                let _ = page.close().await;
+            }
+        }
+
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelled_tasks_resolve_to_an_error() {
+        let (tx, rx) = oneshot::channel::<Result<u32>>();
+        tx.send(Ok(1)).unwrap();
+        assert_eq!(futures::executor::block_on(ScopedTask { rx }).unwrap(), 1);
+
+        let (tx, rx) = oneshot::channel::<Result<u32>>();
+        drop(tx);
+        assert!(matches!(
+            futures::executor::block_on(ScopedTask { rx }),
+            Err(CdpError::Canceled(_))
+        ));
+    }
+}
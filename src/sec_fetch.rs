@@ -0,0 +1,434 @@
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::HeaderEntry;
+use chromiumoxide_cdp::cdp::browser_protocol::network::ResourceType;
+
+/// How the `Origin` header of a matching request is shaped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum OriginHeader {
+    /// Leave the header as sent by the browser.
+    #[default]
+    Keep,
+    /// Send the document origin on cross-origin cors and non GET/HEAD requests, drop it otherwise.
+    Normalize,
+    /// Always send this origin.
+    Set(String),
+    /// Never send the header.
+    Remove,
+}
+
+/// Header shaping for the requests matching a url pattern.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeaderRule {
+    /// Substring the request url must contain, every request matches when `None`.
+    pub url_pattern: Option<String>,
+    /// Derive `Sec-Fetch-Site/Mode/Dest` from the resource type and the document, replacing
+    /// missing or inconsistent values.
+    pub normalize: bool,
+    /// Force the `Sec-Fetch-Site` value.
+    pub site: Option<String>,
+    /// Force the `Sec-Fetch-Mode` value.
+    pub mode: Option<String>,
+    /// Force the `Sec-Fetch-Dest` value.
+    pub dest: Option<String>,
+    /// How the `Origin` header is shaped.
+    pub origin: OriginHeader,
+    /// Send the reduced form of the `User-Agent` header.
+    pub reduce_user_agent: bool,
+}
+
+impl HeaderRule {
+    /// A rule for every request.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// A rule for the requests whose url contains the pattern.
+    pub fn matching(url_pattern: impl Into<String>) -> Self {
+        Self {
+            url_pattern: Some(url_pattern.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Derive the `Sec-Fetch-*` headers and the `Origin` from the request.
+    pub fn normalized(mut self) -> Self {
+        self.normalize = true;
+        self.origin = OriginHeader::Normalize;
+        self
+    }
+
+    /// Force the `Sec-Fetch-Site` value.
+    pub fn site(mut self, site: impl Into<String>) -> Self {
+        self.site = Some(site.into());
+        self
+    }
+
+    /// Force the `Sec-Fetch-Mode` value.
+    pub fn mode(mut self, mode: impl Into<String>) -> Self {
+        self.mode = Some(mode.into());
+        self
+    }
+
+    /// Force the `Sec-Fetch-Dest` value.
+    pub fn dest(mut self, dest: impl Into<String>) -> Self {
+        self.dest = Some(dest.into());
+        self
+    }
+
+    /// Shape the `Origin` header.
+    pub fn origin(mut self, origin: OriginHeader) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Send the reduced form of the `User-Agent` header.
+    pub fn reduce_user_agent(mut self, reduce: bool) -> Self {
+        self.reduce_user_agent = reduce;
+        self
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        self.url_pattern
+            .as_deref()
+            .map_or(true, |p| url.contains(p))
+    }
+}
+
+/// The request being shaped.
+#[derive(Debug, Clone, Copy)]
+pub struct ShapedRequest<'a> {
+    /// The request url.
+    pub url: &'a str,
+    /// The request method.
+    pub method: &'a str,
+    /// The resource type of the request.
+    pub resource_type: &'a ResourceType,
+    /// The url of the document that issued the request.
+    pub document_url: Option<&'a str>,
+}
+
+/// Ordered header shaping rules, the first matching rule applies.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeaderShaping {
+    /// The rules in priority order.
+    pub rules: Vec<HeaderRule>,
+}
+
+impl HeaderShaping {
+    /// Add a rule.
+    pub fn rule(mut self, rule: HeaderRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Shape the headers of the request, `None` when the headers are unchanged.
+    pub fn shape(
+        &self,
+        request: ShapedRequest<'_>,
+        headers: &serde_json::Value,
+    ) -> Option<Vec<HeaderEntry>> {
+        let rule = self.rules.iter().find(|r| r.matches(request.url))?;
+        headers.as_object()?;
+        let original = header_entries(headers);
+        let mut shaped = original.clone();
+
+        let mode = rule.mode.clone().or_else(|| {
+            rule.normalize
+                .then(|| fetch_mode(request.resource_type).into())
+        });
+        let dest = rule.dest.clone().or_else(|| {
+            rule.normalize
+                .then(|| fetch_dest(request.resource_type).into())
+        });
+        let site = rule.site.clone().or_else(|| {
+            rule.normalize.then(|| {
+                let initiator = header(&shaped, "referer").map(str::to_string);
+                fetch_site(
+                    request.url,
+                    initiator.as_deref().or(request.document_url),
+                    *request.resource_type == ResourceType::Document,
+                )
+                .into()
+            })
+        });
+
+        if let Some(site) = site {
+            set_header(&mut shaped, "Sec-Fetch-Site", Some(site));
+        }
+        if let Some(mode) = &mode {
+            set_header(&mut shaped, "Sec-Fetch-Mode", Some(mode.clone()));
+        }
+        if let Some(dest) = dest {
+            set_header(&mut shaped, "Sec-Fetch-Dest", Some(dest));
+        }
+
+        match &rule.origin {
+            OriginHeader::Keep => (),
+            OriginHeader::Set(origin) => set_header(&mut shaped, "Origin", Some(origin.clone())),
+            OriginHeader::Remove => set_header(&mut shaped, "Origin", None),
+            OriginHeader::Normalize => {
+                let mode = mode
+                    .as_deref()
+                    .or_else(|| header(&shaped, "sec-fetch-mode"))
+                    .unwrap_or_else(|| fetch_mode(request.resource_type))
+                    .to_string();
+                let safe_method = request.method.eq_ignore_ascii_case("GET")
+                    || request.method.eq_ignore_ascii_case("HEAD");
+                let origin = request.document_url.and_then(origin_of);
+                let cross_origin = origin != origin_of(request.url);
+
+                if safe_method && !(mode == "cors" && cross_origin) {
+                    set_header(&mut shaped, "Origin", None);
+                } else if let Some(origin) = origin {
+                    set_header(&mut shaped, "Origin", Some(origin));
+                }
+            }
+        }
+
+        if rule.reduce_user_agent {
+            if let Some(ua) = header(&shaped, "user-agent").map(reduce_user_agent) {
+                set_header(&mut shaped, "User-Agent", Some(ua));
+            }
+        }
+
+        (shaped != original).then_some(shaped)
+    }
+}
+
+/// The header entries of the request headers object.
+pub(crate) fn header_entries(headers: &serde_json::Value) -> Vec<HeaderEntry> {
+    headers
+        .as_object()
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|(name, value)| HeaderEntry {
+                    name: name.clone(),
+                    value: value.as_str().map(str::to_string).unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The value of a header by case insensitive name.
+pub(crate) fn header<'a>(headers: &'a [HeaderEntry], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+/// Replace or remove a header by case insensitive name.
+pub(crate) fn set_header(headers: &mut Vec<HeaderEntry>, name: &str, value: Option<String>) {
+    let existing = headers
+        .iter()
+        .position(|h| h.name.eq_ignore_ascii_case(name));
+
+    match (existing, value) {
+        (Some(idx), Some(value)) => headers[idx].value = value,
+        (Some(idx), None) => {
+            headers.remove(idx);
+        }
+        (None, Some(value)) => headers.push(HeaderEntry {
+            name: name.to_string(),
+            value,
+        }),
+        (None, None) => (),
+    }
+}
+
+/// The `Sec-Fetch-Mode` a browser sends for the resource type.
+pub fn fetch_mode(resource_type: &ResourceType) -> &'static str {
+    match resource_type {
+        ResourceType::Document => "navigate",
+        ResourceType::Xhr
+        | ResourceType::Fetch
+        | ResourceType::EventSource
+        | ResourceType::Font
+        | ResourceType::Manifest
+        | ResourceType::Preflight => "cors",
+        ResourceType::WebSocket => "websocket",
+        _ => "no-cors",
+    }
+}
+
+/// The `Sec-Fetch-Dest` a browser sends for the resource type.
+pub fn fetch_dest(resource_type: &ResourceType) -> &'static str {
+    match resource_type {
+        ResourceType::Document => "document",
+        ResourceType::Stylesheet => "style",
+        ResourceType::Image => "image",
+        ResourceType::Font => "font",
+        ResourceType::Script => "script",
+        ResourceType::TextTrack => "track",
+        ResourceType::Manifest => "manifest",
+        ResourceType::WebSocket => "websocket",
+        _ => "empty",
+    }
+}
+
+/// The scheme, host and port of a url.
+fn origin_of(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+/// The registrable part of a host, approximated by its last two labels.
+fn site_of(host: &str) -> &str {
+    let mut dots = host.rmatch_indices('.').map(|(i, _)| i);
+    dots.next();
+    match dots.next() {
+        Some(idx) => &host[idx + 1..],
+        _ => host,
+    }
+}
+
+/// The `Sec-Fetch-Site` of a request made from the initiator url.
+pub fn fetch_site(url: &str, initiator: Option<&str>, navigation: bool) -> &'static str {
+    let initiator = match initiator.and_then(|i| url::Url::parse(i).ok()) {
+        Some(initiator) => initiator,
+        _ => return if navigation { "none" } else { "same-origin" },
+    };
+    let target = match url::Url::parse(url) {
+        Ok(target) => target,
+        _ => return "cross-site",
+    };
+
+    if target.origin() == initiator.origin() {
+        return "same-origin";
+    }
+
+    match (target.host_str(), initiator.host_str()) {
+        (Some(a), Some(b)) if target.scheme() == initiator.scheme() && site_of(a) == site_of(b) => {
+            "same-site"
+        }
+        _ => "cross-site",
+    }
+}
+
+/// The reduced form of a Chrome user agent: the platform is frozen and the minor version
+/// is zeroed, as sent by Chrome with user agent reduction.
+pub fn reduce_user_agent(user_agent: &str) -> String {
+    let (open, close) = match (user_agent.find('('), user_agent.find(')')) {
+        (Some(open), Some(close)) if open < close => (open, close),
+        _ => return user_agent.to_string(),
+    };
+
+    let platform = &user_agent[open + 1..close];
+    let reduced_platform = if platform.contains("Android") {
+        "Linux; Android 10; K"
+    } else if platform.contains("Windows") {
+        "Windows NT 10.0; Win64; x64"
+    } else if platform.contains("Mac OS X") {
+        "Macintosh; Intel Mac OS X 10_15_7"
+    } else if platform.contains("CrOS") {
+        "X11; CrOS x86_64 14541.0.0"
+    } else if platform.contains("Linux") {
+        "X11; Linux x86_64"
+    } else {
+        platform
+    };
+
+    let mut reduced = format!(
+        "{}({}){}",
+        &user_agent[..open],
+        reduced_platform,
+        &user_agent[close + 1..]
+    );
+
+    if let Some(start) = reduced.find("Chrome/") {
+        let version_start = start + "Chrome/".len();
+        let version_end = reduced[version_start..]
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .map_or(reduced.len(), |i| version_start + i);
+        let major = reduced[version_start..version_end]
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        if !major.is_empty() {
+            reduced.replace_range(version_start..version_end, &format!("{major}.0.0.0"));
+        }
+    }
+
+    reduced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_cross_site_fetch() {
+        let shaping = HeaderShaping::default().rule(HeaderRule::all().normalized());
+        let headers = serde_json::json!({
+            "Accept": "*/*",
+            "Sec-Fetch-Site": "none",
+            "Sec-Fetch-Mode": "navigate"
+        });
+
+        let shaped = shaping
+            .shape(
+                ShapedRequest {
+                    url: "https://api.other.com/v1/items",
+                    method: "POST",
+                    resource_type: &ResourceType::Fetch,
+                    document_url: Some("https://www.example.com/shop"),
+                },
+                &headers,
+            )
+            .unwrap();
+
+        assert_eq!(header(&shaped, "sec-fetch-site"), Some("cross-site"));
+        assert_eq!(header(&shaped, "sec-fetch-mode"), Some("cors"));
+        assert_eq!(header(&shaped, "sec-fetch-dest"), Some("empty"));
+        assert_eq!(header(&shaped, "origin"), Some("https://www.example.com"));
+        assert_eq!(header(&shaped, "accept"), Some("*/*"));
+    }
+
+    #[test]
+    fn unchanged_headers_are_not_rewritten() {
+        let shaping = HeaderShaping::default().rule(HeaderRule::matching("/static/").normalized());
+        let headers = serde_json::json!({ "Sec-Fetch-Site": "same-origin" });
+
+        let request = ShapedRequest {
+            url: "https://example.com/api",
+            method: "GET",
+            resource_type: &ResourceType::Xhr,
+            document_url: Some("https://example.com/"),
+        };
+
+        assert!(shaping.shape(request, &headers).is_none());
+    }
+
+    #[test]
+    fn fetch_site_values() {
+        assert_eq!(
+            fetch_site(
+                "https://cdn.example.com/a.js",
+                Some("https://www.example.com/"),
+                false
+            ),
+            "same-site"
+        );
+        assert_eq!(
+            fetch_site(
+                "https://example.com/a",
+                Some("https://example.com/b"),
+                false
+            ),
+            "same-origin"
+        );
+        assert_eq!(fetch_site("https://example.com/", None, true), "none");
+    }
+
+    #[test]
+    fn reduce_chrome_user_agent() {
+        assert_eq!(
+            reduce_user_agent("Mozilla/5.0 (Windows NT 6.1; WOW64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.6099.109 Safari/537.36"),
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"
+        );
+    }
+}
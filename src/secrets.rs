@@ -0,0 +1,390 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::auth::Credentials;
+use crate::error::{CdpError, Result};
+use crate::oauth::LoginCallbacks;
+
+#[cfg(feature = "totp")]
+pub use self::totp::{Totp, TotpAlgorithm};
+
+/// The kind of the one time code asked by a login.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SecretKind {
+    /// A code of an authenticator app.
+    Totp,
+    /// A code sent by SMS.
+    Sms,
+    /// A code sent by email.
+    Email,
+    /// Another code, e.g. a recovery code.
+    Other(String),
+}
+
+/// The source of the one time codes asked in the middle of a login, e.g. a TOTP generator or a
+/// callback reading the last SMS of a phone gateway.
+pub trait SecretProvider: Send + Sync {
+    /// The code of the kind for the account.
+    fn code<'a>(&'a self, kind: &'a SecretKind, account: &'a str) -> BoxFuture<'a, Result<String>>;
+}
+
+impl<P: SecretProvider + ?Sized> SecretProvider for Arc<P> {
+    fn code<'a>(&'a self, kind: &'a SecretKind, account: &'a str) -> BoxFuture<'a, Result<String>> {
+        (**self).code(kind, account)
+    }
+}
+
+/// A provider from an async callback, e.g. polling an SMS inbox.
+pub struct SecretCallback<F>(pub F);
+
+impl<F, Fut> SecretProvider for SecretCallback<F>
+where
+    F: Fn(SecretKind, String) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<String>> + Send + 'static,
+{
+    fn code<'a>(&'a self, kind: &'a SecretKind, account: &'a str) -> BoxFuture<'a, Result<String>> {
+        (self.0)(kind.clone(), account.to_string()).boxed()
+    }
+}
+
+/// The providers tried in order until one gives a code.
+impl SecretProvider for Vec<Arc<dyn SecretProvider>> {
+    fn code<'a>(&'a self, kind: &'a SecretKind, account: &'a str) -> BoxFuture<'a, Result<String>> {
+        async move {
+            let mut last_error = None;
+
+            for provider in self {
+                match provider.code(kind, account).await {
+                    Ok(code) => return Ok(code),
+                    Err(error) => last_error = Some(error),
+                }
+            }
+
+            Err(last_error
+                .unwrap_or_else(|| CdpError::msg(format!("no provider of {kind:?} codes"))))
+        }
+        .boxed()
+    }
+}
+
+/// The answers of a login asking for a second factor, the codes come from the provider.
+#[derive(Clone)]
+pub struct SecretLogin {
+    /// The credentials of the login form.
+    pub credentials: Credentials,
+    /// The source of the codes.
+    pub provider: Arc<dyn SecretProvider>,
+    /// The kind of code the login asks for.
+    pub kind: SecretKind,
+}
+
+impl SecretLogin {
+    /// A login answering the second factor with the codes of the provider.
+    pub fn new(
+        credentials: Credentials,
+        kind: SecretKind,
+        provider: Arc<dyn SecretProvider>,
+    ) -> Self {
+        Self {
+            credentials,
+            provider,
+            kind,
+        }
+    }
+}
+
+impl std::fmt::Debug for SecretLogin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretLogin")
+            .field("username", &self.credentials.username)
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl LoginCallbacks for SecretLogin {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        futures::future::ready(Ok(self.credentials.clone())).boxed()
+    }
+
+    fn mfa_code(&self) -> BoxFuture<'_, Result<String>> {
+        self.provider.code(&self.kind, &self.credentials.username)
+    }
+}
+
+#[cfg(feature = "totp")]
+mod totp {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use futures::future::{BoxFuture, FutureExt};
+    use hmac::{Hmac, Mac};
+
+    use super::{SecretKind, SecretProvider};
+    use crate::error::{CdpError, Result};
+
+    /// A code valid for less than this waits for the next period, so it is not stale once
+    /// submitted.
+    const MIN_REMAINING: Duration = Duration::from_secs(3);
+
+    /// The hash of the codes.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum TotpAlgorithm {
+        /// HMAC-SHA1, used by most authenticator apps.
+        #[default]
+        Sha1,
+        /// HMAC-SHA256.
+        Sha256,
+        /// HMAC-SHA512.
+        Sha512,
+    }
+
+    /// A time based one time password generator of RFC 6238.
+    #[derive(Clone, PartialEq, Eq)]
+    pub struct Totp {
+        secret: Vec<u8>,
+        /// The digits of a code.
+        pub digits: u32,
+        /// The lifetime of a code.
+        pub period: Duration,
+        /// The hash of the codes.
+        pub algorithm: TotpAlgorithm,
+    }
+
+    impl std::fmt::Debug for Totp {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Totp")
+                .field("digits", &self.digits)
+                .field("period", &self.period)
+                .field("algorithm", &self.algorithm)
+                .finish_non_exhaustive()
+        }
+    }
+
+    /// Decode the base32 of RFC 4648, ignoring the case, the spaces and the padding.
+    fn decode_base32(input: &str) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(input.len() * 5 / 8);
+        let (mut buffer, mut bits) = (0u64, 0u32);
+
+        for c in input
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        {
+            let value = match c.to_ascii_uppercase() {
+                c @ 'A'..='Z' => c as u8 - b'A',
+                c @ '2'..='7' => c as u8 - b'2' + 26,
+                _ => return None,
+            };
+            buffer = (buffer << 5) | value as u64;
+            bits += 5;
+
+            if bits >= 8 {
+                bits -= 8;
+                out.push((buffer >> bits) as u8);
+                buffer &= (1 << bits) - 1;
+            }
+        }
+
+        Some(out)
+    }
+
+    fn hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+        let mut mac = <M as Mac>::new_from_slice(key).expect("hmac to take any key length");
+        mac.update(message);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    impl Totp {
+        /// A generator of 6 digit codes every 30 seconds with the raw secret.
+        pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+            Self {
+                secret: secret.into(),
+                digits: 6,
+                period: Duration::from_secs(30),
+                algorithm: TotpAlgorithm::Sha1,
+            }
+        }
+
+        /// A generator with the base32 secret shown by the setup of the second factor.
+        pub fn from_base32(secret: &str) -> Result<Self> {
+            decode_base32(secret)
+                .filter(|secret| !secret.is_empty())
+                .map(Self::new)
+                .ok_or_else(|| CdpError::msg("invalid base32 totp secret"))
+        }
+
+        /// A generator from an `otpauth://totp/...` uri, e.g. decoded from the setup QR code.
+        pub fn from_uri(uri: &str) -> Result<Self> {
+            let uri = url::Url::parse(uri)?;
+
+            if uri.scheme() != "otpauth" || uri.host_str() != Some("totp") {
+                return Err(CdpError::msg("not an otpauth totp uri"));
+            }
+
+            let param = |name: &str| {
+                uri.query_pairs()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.into_owned())
+            };
+
+            let mut totp = Self::from_base32(
+                &param("secret").ok_or_else(|| CdpError::msg("the uri has no secret"))?,
+            )?;
+
+            if let Some(digits) = param("digits").and_then(|v| v.parse().ok()) {
+                totp.digits = digits;
+            }
+            if let Some(period) = param("period").and_then(|v| v.parse().ok()) {
+                totp.period = Duration::from_secs(period);
+            }
+            totp.algorithm = match param("algorithm").as_deref() {
+                Some(a) if a.eq_ignore_ascii_case("sha256") => TotpAlgorithm::Sha256,
+                Some(a) if a.eq_ignore_ascii_case("sha512") => TotpAlgorithm::Sha512,
+                _ => TotpAlgorithm::Sha1,
+            };
+
+            Ok(totp)
+        }
+
+        /// The code at the time.
+        pub fn code_at(&self, time: SystemTime) -> String {
+            let secs = time
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            let counter = (secs / self.period.as_secs().max(1)).to_be_bytes();
+
+            let digest = match self.algorithm {
+                TotpAlgorithm::Sha1 => hmac::<Hmac<sha1::Sha1>>(&self.secret, &counter),
+                TotpAlgorithm::Sha256 => hmac::<Hmac<sha2::Sha256>>(&self.secret, &counter),
+                TotpAlgorithm::Sha512 => hmac::<Hmac<sha2::Sha512>>(&self.secret, &counter),
+            };
+
+            let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+            let binary = u32::from_be_bytes([
+                digest[offset] & 0x7f,
+                digest[offset + 1],
+                digest[offset + 2],
+                digest[offset + 3],
+            ]);
+            let digits = self.digits.clamp(1, 9);
+
+            format!(
+                "{:0width$}",
+                binary % 10u32.pow(digits),
+                width = digits as usize
+            )
+        }
+
+        /// The code now.
+        pub fn code(&self) -> String {
+            self.code_at(SystemTime::now())
+        }
+
+        /// The time the code of now stays valid.
+        pub fn remaining(&self) -> Duration {
+            let period = self.period.as_secs().max(1);
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+
+            Duration::from_secs(period - secs % period)
+        }
+    }
+
+    impl SecretProvider for Totp {
+        fn code<'a>(
+            &'a self,
+            kind: &'a SecretKind,
+            _account: &'a str,
+        ) -> BoxFuture<'a, Result<String>> {
+            async move {
+                if *kind != SecretKind::Totp {
+                    return Err(CdpError::msg(format!("a totp can not give {kind:?} codes")));
+                }
+
+                let remaining = self.remaining();
+                if remaining < MIN_REMAINING {
+                    crate::runtime::sleep(remaining).await;
+                }
+
+                Ok(self.code())
+            }
+            .boxed()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn generates_the_rfc_6238_codes() {
+            let totp = Totp {
+                digits: 8,
+                ..Totp::from_base32("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap()
+            };
+            assert_eq!(totp.secret, b"12345678901234567890");
+
+            let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+            assert_eq!(totp.code_at(at(59)), "94287082");
+            assert_eq!(totp.code_at(at(1111111109)), "07081804");
+            assert_eq!(totp.code_at(at(20000000000)), "65353130");
+        }
+
+        #[test]
+        fn reads_the_otpauth_uris() {
+            let totp = Totp::from_uri(
+                "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example&digits=8&period=60&algorithm=SHA256",
+            )
+            .unwrap();
+
+            assert_eq!(totp.secret, b"Hello!\xde\xad\xbe\xef");
+            assert_eq!(totp.digits, 8);
+            assert_eq!(totp.period, Duration::from_secs(60));
+            assert_eq!(totp.algorithm, TotpAlgorithm::Sha256);
+            assert!(Totp::from_uri("otpauth://hotp/x?secret=JBSWY3DPEHPK3PXP").is_err());
+            assert!(Totp::from_base32("not base32!").is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn falls_back_to_the_next_provider() {
+        let sms: Arc<dyn SecretProvider> = Arc::new(SecretCallback(
+            |kind: SecretKind, account: String| async move {
+                match kind {
+                    SecretKind::Sms => Ok(format!("123456-{account}")),
+                    _ => Err(CdpError::msg("no code")),
+                }
+            },
+        ));
+        let failing: Arc<dyn SecretProvider> =
+            Arc::new(SecretCallback(|_: SecretKind, _: String| async {
+                Err::<String, _>(CdpError::msg("offline"))
+            }));
+        let providers: Vec<Arc<dyn SecretProvider>> = vec![failing, sms];
+
+        let login = SecretLogin::new(
+            Credentials {
+                username: "alice".into(),
+                password: "secret".into(),
+            },
+            SecretKind::Sms,
+            Arc::new(providers),
+        );
+
+        assert_eq!(login.mfa_code().await.unwrap(), "123456-alice");
+        assert!(login
+            .provider
+            .code(&SecretKind::Email, "alice")
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use chromiumoxide_cdp::cdp::browser_protocol::network::{EventResponseReceived, ResourceType};
+
+use crate::utils::header_value;
+
+/// The max amount of subresource findings kept per navigation.
+const MAX_SUBRESOURCE_FINDINGS: usize = 500;
+/// The max amount of cookies kept per navigation.
+const MAX_COOKIES: usize = 500;
+
+/// A single issue found while auditing the security headers.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SecurityFinding {
+    /// The document has no `Content-Security-Policy` header.
+    MissingContentSecurityPolicy,
+    /// The https document has no `Strict-Transport-Security` header.
+    MissingStrictTransportSecurity,
+    /// The document has no `X-Frame-Options` header or `frame-ancestors` directive.
+    MissingFrameProtection,
+    /// The document has no `Referrer-Policy` header.
+    MissingReferrerPolicy,
+    /// The document has no `X-Content-Type-Options: nosniff` header.
+    MissingContentTypeOptions,
+    /// A cookie is set without the `Secure` flag on a https response.
+    CookieWithoutSecure(String),
+    /// A cookie is set without the `HttpOnly` flag.
+    CookieWithoutHttpOnly(String),
+    /// A cookie is set without a `SameSite` attribute.
+    CookieWithoutSameSite(String),
+    /// A subresource was loaded over http on a https document.
+    MixedContent(String),
+}
+
+/// The flags of a cookie set by a response.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CookieAudit {
+    /// The cookie name.
+    pub name: String,
+    /// The `Domain` attribute without its leading dot, else the host of the response.
+    pub domain: String,
+    /// The url of the response that set the cookie.
+    pub url: String,
+    /// The `Secure` flag is set.
+    pub secure: bool,
+    /// The `HttpOnly` flag is set.
+    pub http_only: bool,
+    /// The `SameSite` attribute value.
+    pub same_site: Option<String>,
+}
+
+/// Security header summary for a navigation and its subresources.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SecurityReport {
+    /// The document url audited.
+    pub url: Option<String>,
+    /// The `Content-Security-Policy` header.
+    pub content_security_policy: Option<String>,
+    /// The `Strict-Transport-Security` header.
+    pub strict_transport_security: Option<String>,
+    /// The `X-Frame-Options` header.
+    pub x_frame_options: Option<String>,
+    /// The `Referrer-Policy` header.
+    pub referrer_policy: Option<String>,
+    /// The `X-Content-Type-Options` header.
+    pub x_content_type_options: Option<String>,
+    /// The cookies set by the document and subresources, the last one set per domain and name.
+    pub cookies: Vec<CookieAudit>,
+    /// All the issues found.
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityReport {
+    /// Is the document served over https?
+    fn is_https(&self) -> bool {
+        self.url
+            .as_deref()
+            .map_or(false, |u| u.starts_with("https://"))
+    }
+
+    /// Parse the cookies of a `Set-Cookie` header. CDP joins multiple headers with a new line.
+    pub fn audit_set_cookie(url: &str, set_cookie: &str) -> Vec<CookieAudit> {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_ascii_lowercase()))
+            .unwrap_or_default();
+
+        set_cookie
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split(';');
+                let name = parts.next()?.split('=').next()?.trim();
+
+                if name.is_empty() {
+                    return None;
+                }
+
+                let mut cookie = CookieAudit {
+                    name: name.to_string(),
+                    domain: host.clone(),
+                    url: url.to_string(),
+                    ..Default::default()
+                };
+
+                for attr in parts {
+                    let attr = attr.trim();
+                    let (key, value) = attr.split_once('=').unwrap_or((attr, ""));
+
+                    if key.eq_ignore_ascii_case("secure") {
+                        cookie.secure = true;
+                    } else if key.eq_ignore_ascii_case("httponly") {
+                        cookie.http_only = true;
+                    } else if key.eq_ignore_ascii_case("samesite") {
+                        cookie.same_site = Some(value.trim().to_string());
+                    } else if key.eq_ignore_ascii_case("domain") {
+                        let domain = value.trim().trim_start_matches('.');
+                        if !domain.is_empty() {
+                            cookie.domain = domain.to_ascii_lowercase();
+                        }
+                    }
+                }
+
+                Some(cookie)
+            })
+            .collect()
+    }
+
+    /// Record the cookies and add the findings. A cookie set again replaces the previous one
+    /// without new findings.
+    fn on_cookies(&mut self, url: &str, set_cookie: &str) {
+        let https = url.starts_with("https://");
+
+        for cookie in Self::audit_set_cookie(url, set_cookie) {
+            if let Some(previous) = self
+                .cookies
+                .iter_mut()
+                .find(|c| c.name == cookie.name && c.domain == cookie.domain)
+            {
+                *previous = cookie;
+                continue;
+            }
+            if self.cookies.len() >= MAX_COOKIES {
+                continue;
+            }
+            if self.findings.len() < MAX_SUBRESOURCE_FINDINGS {
+                if https && !cookie.secure {
+                    self.findings
+                        .push(SecurityFinding::CookieWithoutSecure(cookie.name.clone()));
+                }
+                if !cookie.http_only {
+                    self.findings
+                        .push(SecurityFinding::CookieWithoutHttpOnly(cookie.name.clone()));
+                }
+                if cookie.same_site.is_none() {
+                    self.findings
+                        .push(SecurityFinding::CookieWithoutSameSite(cookie.name.clone()));
+                }
+            }
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// Audit a response. The main document response resets the report.
+    pub fn on_response_received(&mut self, event: &EventResponseReceived, main_document: bool) {
+        let response = &event.response;
+
+        if !response.url.starts_with("http") {
+            return;
+        }
+
+        let headers = &response.headers;
+
+        if main_document && event.r#type == ResourceType::Document {
+            let header = |name: &str| header_value(headers, name).map(str::to_string);
+
+            *self = SecurityReport {
+                url: Some(response.url.clone()),
+                content_security_policy: header("content-security-policy"),
+                strict_transport_security: header("strict-transport-security"),
+                x_frame_options: header("x-frame-options"),
+                referrer_policy: header("referrer-policy"),
+                x_content_type_options: header("x-content-type-options"),
+                ..Default::default()
+            };
+
+            if self.content_security_policy.is_none() {
+                self.findings
+                    .push(SecurityFinding::MissingContentSecurityPolicy);
+            }
+            if self.is_https() && self.strict_transport_security.is_none() {
+                self.findings
+                    .push(SecurityFinding::MissingStrictTransportSecurity);
+            }
+            let frame_ancestors = self
+                .content_security_policy
+                .as_deref()
+                .map_or(false, |csp| csp.contains("frame-ancestors"));
+            if self.x_frame_options.is_none() && !frame_ancestors {
+                self.findings.push(SecurityFinding::MissingFrameProtection);
+            }
+            if self.referrer_policy.is_none() {
+                self.findings.push(SecurityFinding::MissingReferrerPolicy);
+            }
+            if !self
+                .x_content_type_options
+                .as_deref()
+                .map_or(false, |v| v.trim().eq_ignore_ascii_case("nosniff"))
+            {
+                self.findings
+                    .push(SecurityFinding::MissingContentTypeOptions);
+            }
+        } else if self.is_https()
+            && response.url.starts_with("http://")
+            && self.findings.len() < MAX_SUBRESOURCE_FINDINGS
+        {
+            self.findings
+                .push(SecurityFinding::MixedContent(response.url.clone()));
+        }
+
+        if let Some(set_cookie) = header_value(headers, "set-cookie") {
+            self.on_cookies(&response.url, set_cookie);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(
+        url: &str,
+        resource_type: &str,
+        headers: serde_json::Value,
+    ) -> EventResponseReceived {
+        serde_json::from_value(serde_json::json!({
+            "requestId": "1",
+            "loaderId": "L",
+            "timestamp": 0.0,
+            "type": resource_type,
+            "hasExtraInfo": false,
+            "response": {
+                "url": url,
+                "status": 200,
+                "statusText": "OK",
+                "headers": headers,
+                "mimeType": "text/html",
+                "charset": "utf-8",
+                "connectionReused": false,
+                "connectionId": 0.0,
+                "encodedDataLength": 0.0,
+                "securityState": "secure"
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_the_joined_set_cookie_headers() {
+        let cookies = SecurityReport::audit_set_cookie(
+            "https://www.a.com/",
+            "sid=1; Path=/; Secure; HttpOnly; SameSite=Lax\ntheme=dark\n=empty\npref=1; Domain=.A.com; secure",
+        );
+
+        assert_eq!(
+            cookies,
+            [
+                CookieAudit {
+                    name: "sid".into(),
+                    domain: "www.a.com".into(),
+                    url: "https://www.a.com/".into(),
+                    secure: true,
+                    http_only: true,
+                    same_site: Some("Lax".into()),
+                },
+                CookieAudit {
+                    name: "theme".into(),
+                    domain: "www.a.com".into(),
+                    url: "https://www.a.com/".into(),
+                    ..Default::default()
+                },
+                CookieAudit {
+                    name: "pref".into(),
+                    domain: "a.com".into(),
+                    url: "https://www.a.com/".into(),
+                    secure: true,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_the_cookies_missing_attributes() {
+        let mut report = SecurityReport::default();
+        report.on_cookies("https://a.com/", "theme=dark");
+
+        assert_eq!(
+            report.findings,
+            [
+                SecurityFinding::CookieWithoutSecure("theme".into()),
+                SecurityFinding::CookieWithoutHttpOnly("theme".into()),
+                SecurityFinding::CookieWithoutSameSite("theme".into()),
+            ]
+        );
+
+        let mut report = SecurityReport::default();
+        report.on_cookies("http://a.com/", "sid=1; HttpOnly; SameSite=Strict");
+
+        assert!(report.findings.is_empty());
+    }
+
+    #[test]
+    fn dedupes_and_caps_the_cookies() {
+        let mut report = SecurityReport::default();
+
+        for _ in 0..3 {
+            report.on_cookies("https://a.com/", "sid=1; Secure; HttpOnly");
+        }
+        assert_eq!(report.cookies.len(), 1);
+        assert_eq!(report.findings.len(), 1);
+
+        report.on_cookies("https://a.com/", "sid=2; Secure; HttpOnly; SameSite=Lax");
+        assert_eq!(report.cookies[0].same_site.as_deref(), Some("Lax"));
+
+        for i in 0..MAX_COOKIES + 10 {
+            report.on_cookies(
+                "https://a.com/",
+                &format!("c{i}=1; Secure; HttpOnly; SameSite=Lax"),
+            );
+        }
+        assert_eq!(report.cookies.len(), MAX_COOKIES);
+    }
+
+    #[test]
+    fn resets_on_the_main_document_response() {
+        let mut report = SecurityReport::default();
+        report.on_response_received(
+            &response(
+                "https://a.com/",
+                "Document",
+                serde_json::json!({"set-cookie": "sid=1"}),
+            ),
+            true,
+        );
+        report.on_response_received(
+            &response("http://cdn.a.com/x.js", "Script", serde_json::json!({})),
+            false,
+        );
+
+        assert_eq!(report.cookies.len(), 1);
+        assert!(report.findings.contains(&SecurityFinding::MixedContent(
+            "http://cdn.a.com/x.js".into()
+        )));
+
+        report.on_response_received(
+            &response(
+                "https://b.com/",
+                "Document",
+                serde_json::json!({
+                    "content-security-policy": "default-src 'self'; frame-ancestors 'none'",
+                    "strict-transport-security": "max-age=31536000",
+                    "referrer-policy": "no-referrer",
+                    "x-content-type-options": "nosniff"
+                }),
+            ),
+            true,
+        );
+
+        assert_eq!(report.url.as_deref(), Some("https://b.com/"));
+        assert!(report.cookies.is_empty());
+        assert!(report.findings.is_empty());
+    }
+}
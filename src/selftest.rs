@@ -0,0 +1,127 @@
+/// Run the headless and bot checks in the main world of the page, where the stealth scripts
+/// apply. Every check reports `{name, passed, detail}`.
+pub(crate) const STEALTH_SELFTEST_JS: &str = r###"(async()=>{const r=[];const c=(name,passed,detail)=>r.push({name,passed:!!passed,detail:detail==null?null:String(detail)});const n=navigator;const ua=n.userAgent||'';const chromeUa=/Chrome\//.test(ua);const mobile=/Android|iPhone|iPad|Mobile/.test(ua);try{c('webdriver',n.webdriver!==true,n.webdriver)}catch(e){c('webdriver',false,e)}try{const b=n.userAgentData?n.userAgentData.brands.map(x=>x.brand).join(','):'';c('headless_user_agent',!/Headless/i.test(ua)&&!/Headless/i.test(b),ua)}catch(e){c('headless_user_agent',false,e)}try{let d=false;const e=new Error('');Object.defineProperty(e,'stack',{configurable:false,get(){d=true;return''}});console.debug(e);c('cdp_runtime_leak',!d,null)}catch(e){c('cdp_runtime_leak',false,e)}try{c('chrome_object',!chromeUa||typeof window.chrome==='object',typeof window.chrome)}catch(e){c('chrome_object',false,e)}try{const p=n.plugins?n.plugins.length:0;const m=n.mimeTypes?n.mimeTypes.length:0;c('plugins',mobile||!chromeUa||(p>0&&m>0),p+' plugins, '+m+' mime types')}catch(e){c('plugins',false,e)}try{const v=document.createElement('video');const a=document.createElement('audio');const h=v.canPlayType('video/mp4; codecs="avc1.42E01E"');const mp3=a.canPlayType('audio/mpeg');c('codecs',!chromeUa||(h!==''&&mp3!==''),'h264='+h+', mp3='+mp3)}catch(e){c('codecs',false,e)}try{const l=n.languages||[];c('languages',l.length>0&&l[0]===n.language,n.language+' / '+l.join(','))}catch(e){c('languages',false,e)}try{const o=Intl.DateTimeFormat().resolvedOptions();const a=(o.locale||'').split('-')[0].toLowerCase();const b=(n.language||'').split('-')[0].toLowerCase();c('locale_consistency',a===b,o.locale+' / '+n.language)}catch(e){c('locale_consistency',false,e)}try{const tz=Intl.DateTimeFormat().resolvedOptions().timeZone;const now=new Date();now.setSeconds(0,0);const p={};for(const x of new Intl.DateTimeFormat('en-US',{timeZone:tz,hourCycle:'h23',year:'numeric',month:'2-digit',day:'2-digit',hour:'2-digit',minute:'2-digit'}).formatToParts(now)){p[x.type]=x.value}const o=(Date.UTC(+p.year,+p.month-1,+p.day,+p.hour%24,+p.minute)-now.getTime())/60000;c('timezone_consistency',Math.abs(o+now.getTimezoneOffset())<=1,tz+' '+o+' / '+(-now.getTimezoneOffset()))}catch(e){c('timezone_consistency',false,e)}try{let s='prompt';if(n.permissions){s=(await n.permissions.query({name:'notifications'})).state}c('permissions',!(typeof Notification!=='undefined'&&Notification.permission==='denied'&&s==='prompt'),(typeof Notification!=='undefined'?Notification.permission:'none')+' / '+s)}catch(e){c('permissions',false,e)}try{const g=document.createElement('canvas').getContext('webgl');let v='';if(g){const i=g.getExtension('WEBGL_debug_renderer_info');v=i?g.getParameter(i.UNMASKED_RENDERER_WEBGL):g.getParameter(g.RENDERER)}c('webgl_renderer',!!g&&!/SwiftShader|llvmpipe|software/i.test(v),v||'no webgl')}catch(e){c('webgl_renderer',false,e)}try{c('window_dimensions',window.outerWidth>0&&window.outerHeight>0&&screen.width>=window.innerWidth,window.outerWidth+'x'+window.outerHeight+' in '+screen.width+'x'+screen.height)}catch(e){c('window_dimensions',false,e)}try{c('hardware',(n.hardwareConcurrency||0)>0,n.hardwareConcurrency)}catch(e){c('hardware',false,e)}try{const d=n.userAgentData;let ok=true;if(d&&d.platform){const p=(n.platform||'').toLowerCase();const q=d.platform.toLowerCase();ok=(q==='windows'&&p.startsWith('win'))||(q==='macos'&&p.startsWith('mac'))||(q==='linux'&&p.includes('linux'))||(q==='android'&&p.includes('linux'))||(q==='chrome os'&&p.includes('cros'))||!['windows','macos','linux','android','chrome os'].includes(q)}c('platform_consistency',ok,(d?d.platform:'none')+' / '+n.platform)}catch(e){c('platform_consistency',false,e)}return r})()"###;
+
+/// The weights of the checks, the leaks bot detectors rely on the most weigh more.
+const CHECK_WEIGHTS: [(&str, u32); 14] = [
+    ("webdriver", 5),
+    ("headless_user_agent", 5),
+    ("cdp_runtime_leak", 4),
+    ("chrome_object", 3),
+    ("plugins", 2),
+    ("codecs", 2),
+    ("languages", 2),
+    ("locale_consistency", 2),
+    ("timezone_consistency", 3),
+    ("permissions", 2),
+    ("webgl_renderer", 2),
+    ("window_dimensions", 2),
+    ("hardware", 1),
+    ("platform_consistency", 3),
+];
+
+/// The weight of a check missing from the table.
+const DEFAULT_WEIGHT: u32 = 1;
+
+/// The result of a check of the self-test.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SelfTestCheck {
+    /// The name of the check, e.g. `webdriver`.
+    pub name: String,
+    /// The page passed the check.
+    pub passed: bool,
+    /// What the check observed.
+    #[serde(default)]
+    pub detail: Option<String>,
+    /// How much the check counts towards the score.
+    #[serde(default)]
+    pub weight: u32,
+}
+
+/// The report of the headless detection self-test.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SelfTestReport {
+    /// The checks that ran.
+    pub checks: Vec<SelfTestCheck>,
+    /// The weighted share of the passed checks, from 0 to 100.
+    pub score: f64,
+}
+
+impl SelfTestReport {
+    /// Weigh and score the checks.
+    pub fn new(mut checks: Vec<SelfTestCheck>) -> Self {
+        for check in checks.iter_mut() {
+            check.weight = CHECK_WEIGHTS
+                .iter()
+                .find(|(name, _)| *name == check.name)
+                .map_or(DEFAULT_WEIGHT, |(_, weight)| *weight);
+        }
+
+        let total: u32 = checks.iter().map(|check| check.weight).sum();
+        let passed: u32 = checks
+            .iter()
+            .filter(|check| check.passed)
+            .map(|check| check.weight)
+            .sum();
+
+        let score = if total == 0 {
+            100.0
+        } else {
+            f64::from(passed) * 100.0 / f64::from(total)
+        };
+
+        Self { checks, score }
+    }
+
+    /// The checks the page failed.
+    pub fn failed(&self) -> impl Iterator<Item = &SelfTestCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+
+    /// The check of the name.
+    pub fn check(&self, name: &str) -> Option<&SelfTestCheck> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+
+    /// The score reaches the threshold.
+    pub fn is_passing(&self, threshold: f64) -> bool {
+        self.score >= threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, passed: bool) -> SelfTestCheck {
+        SelfTestCheck {
+            name: name.into(),
+            passed,
+            detail: None,
+            weight: 0,
+        }
+    }
+
+    #[test]
+    fn weighs_the_checks() {
+        let report = SelfTestReport::new(vec![
+            check("webdriver", false),
+            check("hardware", true),
+            check("unknown", true),
+        ]);
+
+        assert_eq!(report.check("webdriver").unwrap().weight, 5);
+        assert_eq!(report.check("unknown").unwrap().weight, DEFAULT_WEIGHT);
+        assert!((report.score - 2.0 * 100.0 / 7.0).abs() < 1e-9);
+        assert_eq!(report.failed().count(), 1);
+        assert!(!report.is_passing(90.0));
+    }
+
+    #[test]
+    fn scores_an_empty_report() {
+        let report = SelfTestReport::new(Vec::new());
+
+        assert_eq!(report.score, 100.0);
+        assert!(report.is_passing(100.0));
+    }
+}
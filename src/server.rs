@@ -0,0 +1,670 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::browser::Browser;
+use crate::cdp::browser_protocol::target::TargetId;
+use crate::devtools_proxy::token_matches;
+use crate::error::CdpError;
+use crate::health::HealthReport;
+use crate::page::{Page, ScreenshotParams};
+use crate::runtime::JoinHandle;
+
+/// Invalid JSON was received.
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON is not a valid request object.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The method parameters are invalid.
+pub const INVALID_PARAMS: i64 = -32602;
+/// The browser failed to run the method.
+pub const BROWSER_ERROR: i64 = -32000;
+/// The token of the handshake is missing or wrong.
+pub const UNAUTHORIZED: i64 = -32001;
+
+/// The method of the handshake, the first request of every connection, e.g.
+/// `{"jsonrpc":"2.0","id":0,"method":"rpc.authenticate","params":{"token":"<token>"}}`.
+pub const AUTHENTICATE: &str = "rpc.authenticate";
+
+/// The max length of the handshake line, the connection is closed on a longer line.
+const MAX_HANDSHAKE_BYTES: usize = 4 * 1024;
+/// The max length of a request line after the handshake.
+const MAX_REQUEST_BYTES: usize = 8 * 1024 * 1024;
+/// The time a connection has to send the handshake before it is closed.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The max length of the request line of a health probe.
+const MAX_PROBE_LINE_BYTES: usize = 8 * 1024;
+/// The time a health probe has to send its request line.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A JSON-RPC 2.0 request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcRequest {
+    /// The id of the request, a notification without a response when `None`.
+    #[serde(default)]
+    pub id: Option<Value>,
+    /// The method to run, e.g. `page.goto`.
+    pub method: String,
+    /// The named parameters of the method.
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// A JSON-RPC 2.0 error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RpcError {
+    /// The error code.
+    pub code: i64,
+    /// The error message.
+    pub message: String,
+}
+
+impl RpcError {
+    /// A new error.
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The method parameters are invalid.
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+impl From<CdpError> for RpcError {
+    fn from(err: CdpError) -> Self {
+        Self::new(BROWSER_ERROR, err.to_string())
+    }
+}
+
+/// A JSON-RPC 2.0 response.
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcResponse {
+    jsonrpc: &'static str,
+    /// The id of the request.
+    pub id: Value,
+    /// The result of a successful method.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// The error of a failed method.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    /// The response of the method result.
+    pub fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// Parse a request line, the error response when the line is not a valid request.
+pub fn parse_request(line: &str) -> Result<RpcRequest, RpcResponse> {
+    let value: Value = serde_json::from_str(line).map_err(|err| {
+        RpcResponse::new(
+            Value::Null,
+            Err(RpcError::new(PARSE_ERROR, err.to_string())),
+        )
+    })?;
+
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+
+    serde_json::from_value(value)
+        .map_err(|err| RpcResponse::new(id, Err(RpcError::new(INVALID_REQUEST, err.to_string()))))
+}
+
+/// A string parameter of the method.
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params(format!("missing string param `{name}`")))
+}
+
+/// A JSON-RPC server driving the browser over newline delimited JSON on TCP, so other languages
+/// can reuse the pages of the browser with its caching and stealth behavior.
+///
+/// The methods take named parameters, pages are addressed by the `pageId` returned by
+/// `browser.newPage`:
+///
+/// - `browser.version`, `browser.newPage { url? }`, `browser.pages`
+/// - `page.goto { pageId, url }`, `page.content`, `page.url`, `page.title`, `page.close`
+/// - `page.evaluate { pageId, expression }`
+/// - `page.screenshot { pageId, fullPage? }`, the base64 encoded png
+///
+/// Every connection starts with the [`AUTHENTICATE`] handshake carrying the token of the
+/// server, the connection is closed on a wrong token, a handshake line over 4 KiB or no
+/// handshake within 10 seconds. A first line looking like a HTTP request closes the connection
+/// as well, a page open in a local browser cannot smuggle requests in a form post. The server controls the browser, bind it to the loopback, e.g. `127.0.0.1:9333`.
+#[derive(Debug, Clone)]
+pub struct RpcServer {
+    browser: Arc<Browser>,
+    token: String,
+}
+
+impl RpcServer {
+    /// Serve the browser, the token of the handshake is random, see [`RpcServer::token`].
+    pub fn new(browser: Arc<Browser>) -> Self {
+        Self {
+            browser,
+            token: general_purpose::URL_SAFE_NO_PAD.encode(rand::random::<[u8; 24]>()),
+        }
+    }
+
+    /// Use the shared secret as the token of the handshake.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = token.into();
+        self
+    }
+
+    /// The token of the handshake, to hand to the clients.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Bind the address and spawn the task accepting the connections. Bind to the loopback, the
+    /// token is the only protection of the server.
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> std::io::Result<JoinHandle<()>> {
+        let listener = TcpListener::bind(addr).await?;
+
+        Ok(crate::runtime::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let server = self.clone();
+                        crate::runtime::spawn(async move {
+                            if let Err(err) = server.handle_connection(stream).await {
+                                tracing::debug!("rpc connection {peer} closed: {err}");
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        tracing::warn!("rpc accept failed: {err}");
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Answer the requests of the connection, one JSON request per line after the handshake.
+    async fn handle_connection(&self, stream: TcpStream) -> std::io::Result<()> {
+        let (read, mut write) = stream.into_split();
+        let mut reader = BufReader::new(read);
+
+        // the line is read before the token is checked, bound its size and the wait for it.
+        let handshake = crate::runtime::timeout(HANDSHAKE_TIMEOUT, async {
+            loop {
+                match read_line(&mut reader, MAX_HANDSHAKE_BYTES).await? {
+                    Some(line) if line.trim().is_empty() => continue,
+                    line => return Ok::<_, std::io::Error>(line),
+                }
+            }
+        })
+        .await
+        .map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::TimedOut, "rpc handshake timed out")
+        })??;
+
+        let Some(line) = handshake else {
+            return Ok(());
+        };
+
+        if is_http_request(&line) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "http request on the rpc server",
+            ));
+        }
+
+        let response = self.authenticate(&line);
+        let mut bytes = serde_json::to_vec(&response).unwrap_or_default();
+        bytes.push(b'\n');
+        write.write_all(&bytes).await?;
+
+        if response.error.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "rpc handshake failed",
+            ));
+        }
+
+        while let Some(line) = read_line(&mut reader, MAX_REQUEST_BYTES).await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(response) = self.handle(&line).await {
+                let mut bytes = serde_json::to_vec(&response).unwrap_or_default();
+                bytes.push(b'\n');
+                write.write_all(&bytes).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the handshake line, the response is an error unless it is the [`AUTHENTICATE`]
+    /// request with the token of the server.
+    pub fn authenticate(&self, line: &str) -> RpcResponse {
+        handshake(line, &self.token)
+    }
+
+    /// Run the request line, `None` for notifications.
+    pub async fn handle(&self, line: &str) -> Option<RpcResponse> {
+        match parse_request(line) {
+            Ok(request) => {
+                let result = self.call(&request.method, &request.params).await;
+                request.id.map(|id| RpcResponse::new(id, result))
+            }
+            Err(response) => Some(response),
+        }
+    }
+
+    /// The page of the `pageId` param.
+    async fn page(&self, params: &Value) -> Result<Page, RpcError> {
+        let page_id = str_param(params, "pageId")?;
+
+        self.browser
+            .get_page(TargetId::new(page_id))
+            .await
+            .map_err(|_| RpcError::invalid_params(format!("page `{page_id}` not found")))
+    }
+
+    /// Run the method.
+    pub async fn call(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        let value = match method {
+            "browser.version" => {
+                let version = self.browser.version().await?;
+                json!({
+                    "product": version.product,
+                    "protocolVersion": version.protocol_version,
+                    "userAgent": version.user_agent,
+                })
+            }
+            "browser.newPage" => {
+                let url = params
+                    .get("url")
+                    .and_then(Value::as_str)
+                    .unwrap_or("about:blank");
+                let page = self.browser.new_page(url).await?;
+                json!({ "pageId": page.target_id().as_ref() })
+            }
+            "browser.pages" => {
+                let pages = self.browser.pages().await?;
+                Value::from(
+                    pages
+                        .iter()
+                        .map(|page| page.target_id().as_ref().to_string())
+                        .collect::<Vec<_>>(),
+                )
+            }
+            "page.goto" => {
+                let url = str_param(params, "url")?;
+                let page = self.page(params).await?;
+                page.goto(url).await?;
+                json!({ "url": page.url().await? })
+            }
+            "page.content" => Value::from(self.page(params).await?.content().await?),
+            "page.url" => Value::from(self.page(params).await?.url().await?),
+            "page.title" => Value::from(self.page(params).await?.get_title().await?),
+            "page.evaluate" => {
+                let expression = str_param(params, "expression")?;
+                let page = self.page(params).await?;
+                page.evaluate(expression)
+                    .await?
+                    .value()
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            "page.screenshot" => {
+                let full_page = params.get("fullPage").and_then(Value::as_bool);
+                let page = self.page(params).await?;
+                let screenshot = page
+                    .screenshot(
+                        ScreenshotParams::builder()
+                            .full_page(full_page.unwrap_or(false))
+                            .build(),
+                    )
+                    .await?;
+                Value::from(general_purpose::STANDARD.encode(screenshot))
+            }
+            "page.close" => {
+                self.page(params).await?.close().await?;
+                Value::Null
+            }
+            _ => {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("method `{method}` not found"),
+                ))
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+/// The response of the handshake line with the token of the server.
+fn handshake(line: &str, token: &str) -> RpcResponse {
+    let request = match parse_request(line) {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    let id = request.id.unwrap_or_default();
+    let accepted = request.method == AUTHENTICATE
+        && request
+            .params
+            .get("token")
+            .and_then(Value::as_str)
+            .map_or(false, |sent| token_matches(sent, token));
+
+    if accepted {
+        RpcResponse::new(id, Ok(Value::Bool(true)))
+    } else {
+        RpcResponse::new(
+            id,
+            Err(RpcError::new(
+                UNAUTHORIZED,
+                "authenticate with the token first",
+            )),
+        )
+    }
+}
+
+/// Read a line of at most `max` bytes without its line ending, `None` at the end of the stream.
+async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max: usize,
+) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let read = (&mut *reader)
+        .take(max as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+
+    if read == 0 {
+        return Ok(None);
+    }
+
+    if line.last() == Some(&b'\n') {
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+    } else if line.len() > max {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("line longer than {max} bytes"),
+        ));
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// The line is the request line of a HTTP request, e.g. `POST / HTTP/1.1`.
+fn is_http_request(line: &str) -> bool {
+    const METHODS: [&str; 9] = [
+        "GET ", "POST ", "PUT ", "HEAD ", "DELETE ", "OPTIONS ", "PATCH ", "CONNECT ", "TRACE ",
+    ];
+
+    METHODS.iter().any(|method| line.starts_with(method)) || line.contains(" HTTP/1.")
+}
+
+/// A health probe of a service deployment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    /// `/healthz`, the browser is alive.
+    Live,
+    /// `/readyz`, the browser answers commands.
+    Ready,
+}
+
+impl Probe {
+    /// The probe of the request target.
+    fn from_target(target: &str) -> Option<Self> {
+        match target.split('?').next() {
+            Some("/healthz") | Some("/livez") => Some(Probe::Live),
+            Some("/readyz") => Some(Probe::Ready),
+            _ => None,
+        }
+    }
+
+    fn passes(self, report: &HealthReport) -> bool {
+        match self {
+            Probe::Live => report.is_live(),
+            Probe::Ready => report.is_ready(),
+        }
+    }
+}
+
+/// A HTTP/1.1 response closing the connection.
+pub(crate) fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Answer the probe of the connection.
+async fn answer_probe(browser: &Browser, stream: TcpStream) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let request = crate::runtime::timeout(
+        PROBE_TIMEOUT,
+        read_line(&mut BufReader::new(read), MAX_PROBE_LINE_BYTES),
+    )
+    .await
+    .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "health probe timed out"))??
+    .unwrap_or_default();
+
+    let response = match request
+        .split_whitespace()
+        .nth(1)
+        .and_then(Probe::from_target)
+    {
+        Some(probe) => {
+            let report = browser.health().await;
+            let status = if probe.passes(&report) {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            http_response(status, &serde_json::to_string(&report)?)
+        }
+        _ => http_response("404 Not Found", ""),
+    };
+
+    write.write_all(response.as_bytes()).await?;
+    write.shutdown().await
+}
+
+/// Bind the address and spawn the task answering `/healthz` (liveness) and `/readyz` (readiness)
+/// probes over plain HTTP with the JSON [`HealthReport`] of the browser, `503` when the probe
+/// fails. A request line over 8 KiB or not sent within 5 seconds closes the connection.
+pub async fn serve_health(
+    browser: Arc<Browser>,
+    addr: impl ToSocketAddrs,
+) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+
+    Ok(crate::runtime::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let browser = browser.clone();
+                    crate::runtime::spawn(async move {
+                        if let Err(err) = answer_probe(&browser, stream).await {
+                            tracing::debug!("health probe of {peer} failed: {err}");
+                        }
+                    });
+                }
+                Err(err) => {
+                    tracing::warn!("health probe accept failed: {err}");
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requests() {
+        let request =
+            parse_request(r#"{"jsonrpc":"2.0","id":1,"method":"page.goto","params":{"url":"https://example.com"}}"#)
+                .unwrap();
+
+        assert_eq!(request.id, Some(Value::from(1)));
+        assert_eq!(request.method, "page.goto");
+        assert_eq!(str_param(&request.params, "url"), Ok("https://example.com"));
+        assert_eq!(
+            str_param(&request.params, "pageId").unwrap_err().code,
+            INVALID_PARAMS
+        );
+
+        let notification = parse_request(r#"{"jsonrpc":"2.0","method":"page.close"}"#).unwrap();
+
+        assert_eq!(notification.id, None);
+    }
+
+    #[test]
+    fn error_responses() {
+        let response = parse_request("{").unwrap_err();
+
+        assert_eq!(response.error.map(|e| e.code), Some(PARSE_ERROR));
+
+        let response = parse_request(r#"{"jsonrpc":"2.0","id":"a"}"#).unwrap_err();
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["id"], "a");
+        assert_eq!(json["error"]["code"], INVALID_REQUEST);
+        assert!(json.get("result").is_none());
+    }
+
+    #[test]
+    fn rejects_the_http_requests() {
+        assert!(is_http_request("GET / HTTP/1.1"));
+        assert!(is_http_request("POST /rpc HTTP/1.1"));
+        assert!(!is_http_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"rpc.authenticate"}"#
+        ));
+    }
+
+    #[test]
+    fn checks_the_handshake_token() {
+        let code = |line: &str| handshake(line, "s3cret").error.map(|err| err.code);
+
+        assert_eq!(
+            code(
+                r#"{"jsonrpc":"2.0","id":0,"method":"rpc.authenticate","params":{"token":"s3cret"}}"#
+            ),
+            None
+        );
+        assert_eq!(
+            code(
+                r#"{"jsonrpc":"2.0","id":0,"method":"rpc.authenticate","params":{"token":"guess"}}"#
+            ),
+            Some(UNAUTHORIZED)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc":"2.0","id":0,"method":"browser.version"}"#),
+            Some(UNAUTHORIZED)
+        );
+        assert_eq!(code("GET / HTTP/1.1"), Some(PARSE_ERROR));
+    }
+
+    #[test]
+    fn health_probes() {
+        let ready = HealthReport {
+            connected: true,
+            latency_ms: Some(3),
+            ..Default::default()
+        };
+        let stuck = HealthReport {
+            connected: true,
+            ..Default::default()
+        };
+
+        assert_eq!(Probe::from_target("/healthz?verbose"), Some(Probe::Live));
+        assert_eq!(Probe::from_target("/metrics"), None);
+        assert!(Probe::Ready.passes(&ready));
+        assert!(!Probe::Ready.passes(&stuck));
+        assert!(Probe::Live.passes(&stuck));
+        assert!(!Probe::Live.passes(&HealthReport::default()));
+        assert!(http_response("200 OK", "{}")
+            .ends_with("content-length: 2\r\nconnection: close\r\n\r\n{}"));
+    }
+
+    #[tokio::test]
+    async fn bounded_lines() {
+        let mut reader = BufReader::new(&b"{\"id\":1}\r\nnext\nlast"[..]);
+
+        assert_eq!(
+            read_line(&mut reader, 16).await.unwrap().as_deref(),
+            Some("{\"id\":1}")
+        );
+        assert_eq!(
+            read_line(&mut reader, 16).await.unwrap().as_deref(),
+            Some("next")
+        );
+        assert_eq!(
+            read_line(&mut reader, 16).await.unwrap().as_deref(),
+            Some("last")
+        );
+        assert_eq!(read_line(&mut reader, 16).await.unwrap(), None);
+
+        let long = vec![b'a'; 64];
+        let mut reader = BufReader::new(&long[..]);
+        let err = read_line(&mut reader, 16).await.unwrap_err();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut reader = BufReader::new(&b"0123456789abcdef\n"[..]);
+
+        assert_eq!(
+            read_line(&mut reader, 16).await.unwrap().as_deref(),
+            Some("0123456789abcdef")
+        );
+    }
+
+    #[tokio::test]
+    async fn bounded_probe_lines() {
+        let probe = b"GET /readyz HTTP/1.1\r\nhost: localhost\r\n\r\n";
+        let mut reader = BufReader::new(&probe[..]);
+
+        assert_eq!(
+            read_line(&mut reader, MAX_PROBE_LINE_BYTES)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("GET /readyz HTTP/1.1")
+        );
+
+        let long = format!("GET /{} HTTP/1.1\r\n", "a".repeat(MAX_PROBE_LINE_BYTES));
+        let mut reader = BufReader::new(long.as_bytes());
+
+        assert!(read_line(&mut reader, MAX_PROBE_LINE_BYTES).await.is_err());
+    }
+}
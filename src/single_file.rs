@@ -0,0 +1,345 @@
+use base64::Engine;
+use chromiumoxide_cdp::cdp::browser_protocol::page::{
+    CaptureSnapshotFormat, CaptureSnapshotParams,
+};
+
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+use crate::utils;
+
+/// A resource of an MHTML archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MhtmlPart {
+    /// The `Content-Type` of the part, e.g. `text/css`.
+    pub content_type: String,
+    /// The `Content-Location` of the part, the url of the resource or a `cid:` url of a frame.
+    pub location: Option<String>,
+    /// The `Content-ID` of the part.
+    pub content_id: Option<String>,
+    /// The decoded body.
+    pub body: Vec<u8>,
+}
+
+impl MhtmlPart {
+    /// The mime type without the parameters.
+    pub fn mime_type(&self) -> &str {
+        self.content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+
+    /// The part as a `data:` url.
+    pub fn data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.mime_type(),
+            base64::engine::general_purpose::STANDARD.encode(&self.body)
+        )
+    }
+}
+
+/// An MHTML archive, e.g. of `Page::capture_mhtml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MhtmlArchive {
+    /// The parts in order, the document first.
+    pub parts: Vec<MhtmlPart>,
+}
+
+/// Unfold the headers of a header block, lowercasing the names.
+fn parse_headers(block: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for line in block.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    headers
+}
+
+/// Split the header block from the body.
+fn split_head(input: &str) -> Option<(&str, &str)> {
+    let crlf = input.find("\r\n\r\n").map(|i| (i, 4));
+    let lf = input.find("\n\n").map(|i| (i, 2));
+
+    let (index, len) = match (crlf, lf) {
+        (Some(a), Some(b)) => {
+            if a.0 <= b.0 {
+                a
+            } else {
+                b
+            }
+        }
+        (a, b) => a.or(b)?,
+    };
+
+    Some((&input[..index], &input[index + len..]))
+}
+
+/// Decode a quoted-printable body.
+fn decode_quoted_printable(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'=' {
+            if bytes[i + 1..].starts_with(b"\r\n") {
+                i += 3;
+                continue;
+            }
+            if bytes[i + 1..].starts_with(b"\n") {
+                i += 2;
+                continue;
+            }
+            if let Some(hex) = input.get(i + 1..i + 3) {
+                if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                    out.push(byte);
+                    i += 3;
+                    continue;
+                }
+            }
+        }
+
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    out
+}
+
+impl MhtmlArchive {
+    /// Parse the archive.
+    pub fn parse(mhtml: &str) -> Result<Self> {
+        let (head, body) =
+            split_head(mhtml).ok_or_else(|| CdpError::msg("mhtml without headers"))?;
+
+        let boundary = parse_headers(head)
+            .into_iter()
+            .find(|(name, _)| name == "content-type")
+            .and_then(|(_, value)| {
+                value.split(';').find_map(|param| {
+                    let (key, value) = param.split_once('=')?;
+                    key.trim()
+                        .eq_ignore_ascii_case("boundary")
+                        .then(|| value.trim().trim_matches('"').to_string())
+                })
+            })
+            .ok_or_else(|| CdpError::msg("mhtml without a multipart boundary"))?;
+
+        let delimiter = format!("--{boundary}");
+        let mut parts = Vec::new();
+
+        // the preamble comes before the first delimiter.
+        for section in body.split(delimiter.as_str()).skip(1) {
+            if section.starts_with("--") {
+                break;
+            }
+
+            let section = section
+                .strip_prefix("\r\n")
+                .or_else(|| section.strip_prefix('\n'))
+                .unwrap_or(section);
+
+            let Some((head, content)) = split_head(section) else {
+                continue;
+            };
+
+            let content = content
+                .strip_suffix("\r\n")
+                .or_else(|| content.strip_suffix('\n'))
+                .unwrap_or(content);
+
+            let headers = parse_headers(head);
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, value)| value.clone())
+            };
+
+            let body = match header("content-transfer-encoding")
+                .map(|encoding| encoding.to_ascii_lowercase())
+                .as_deref()
+            {
+                Some("base64") => utils::base64::decode(
+                    content
+                        .bytes()
+                        .filter(|b| !b.is_ascii_whitespace())
+                        .collect::<Vec<_>>(),
+                )
+                .map_err(|err| CdpError::msg(format!("invalid base64 mhtml part: {err}")))?,
+                Some("quoted-printable") => decode_quoted_printable(content),
+                _ => content.as_bytes().to_vec(),
+            };
+
+            parts.push(MhtmlPart {
+                content_type: header("content-type").unwrap_or_else(|| "text/plain".into()),
+                location: header("content-location"),
+                content_id: header("content-id"),
+                body,
+            });
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// The document of the archive, the first html part.
+    pub fn document(&self) -> Option<&MhtmlPart> {
+        self.parts
+            .iter()
+            .find(|part| part.mime_type().eq_ignore_ascii_case("text/html"))
+    }
+
+    /// The document as a single html file with the images, the stylesheets, the fonts and the
+    /// frames of the archive inlined as `data:` urls.
+    #[cfg(feature = "_cache")]
+    pub async fn to_single_file(&self) -> Result<String> {
+        use crate::cache::SubresourceRewrite;
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        let document = self
+            .document()
+            .ok_or_else(|| CdpError::msg("mhtml without a document"))?;
+
+        let is_css = |part: &MhtmlPart| part.mime_type().eq_ignore_ascii_case("text/css");
+        let key = |part: &MhtmlPart| {
+            let location = part.location.as_deref()?;
+            // the lookups are made with the absolute urls of the references.
+            Some(
+                url::Url::parse(location)
+                    .map(|mut url| {
+                        url.set_fragment(None);
+                        url.to_string()
+                    })
+                    .unwrap_or_else(|_| location.to_string()),
+            )
+        };
+
+        let mut inlined: HashMap<String, String> = self
+            .parts
+            .iter()
+            .filter(|part| !std::ptr::eq(*part, document) && !is_css(*part))
+            .filter_map(|part| Some((key(part)?, part.data_url())))
+            .collect();
+
+        // the fonts and images of the stylesheets resolve against the stylesheets.
+        let assets = SubresourceRewrite::LocalPaths(Arc::new(inlined.clone()));
+
+        for part in self.parts.iter().filter(|part| is_css(*part)) {
+            let Some(location) = key(part) else {
+                continue;
+            };
+
+            let css = String::from_utf8_lossy(&part.body);
+            let css = match url::Url::parse(&location) {
+                Ok(base) => assets
+                    .rewrite_css(&base, &css)
+                    .unwrap_or_else(|| css.into_owned()),
+                _ => css.into_owned(),
+            };
+
+            inlined.insert(
+                location,
+                MhtmlPart {
+                    body: css.into_bytes(),
+                    ..part.clone()
+                }
+                .data_url(),
+            );
+        }
+
+        let html = crate::cache::rewrite_base_tag_with_subresources(
+            &document.body,
+            &document.location.as_deref(),
+            Some(&SubresourceRewrite::LocalPaths(Arc::new(inlined))),
+        )
+        .await;
+
+        Ok(html)
+    }
+}
+
+impl Page {
+    /// Capture the page as an MHTML archive of its document, frames and resources.
+    pub async fn capture_mhtml(&self) -> Result<String> {
+        Ok(self
+            .execute(CaptureSnapshotParams {
+                format: Some(CaptureSnapshotFormat::Mhtml),
+            })
+            .await?
+            .result
+            .data)
+    }
+
+    /// Save the page as a single self-contained html file, the images, stylesheets, fonts and
+    /// frames captured with the MHTML snapshot inlined. Returns the bytes written.
+    #[cfg(feature = "_cache")]
+    pub async fn save_single_file(&self, output: impl AsRef<std::path::Path>) -> Result<u64> {
+        let archive = MhtmlArchive::parse(&self.capture_mhtml().await?)?;
+        let html = archive.to_single_file().await?;
+
+        utils::write(output.as_ref(), html.as_bytes()).await?;
+
+        Ok(html.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MHTML: &str = "From: <Saved by Blink>\r\nSubject: Example\r\nMIME-Version: 1.0\r\nContent-Type: multipart/related;\r\n\ttype=\"text/html\";\r\n\tboundary=\"----MultipartBoundary--abc----\"\r\n\r\n\r\n------MultipartBoundary--abc----\r\nContent-Type: text/html\r\nContent-Transfer-Encoding: quoted-printable\r\nContent-Location: https://example.com/\r\n\r\n<html><head><link rel=3D\"stylesheet\" href=3D\"/s.css\"></head><body><img src=\r\n=3D\"/a.png\"></body></html>\r\n------MultipartBoundary--abc----\r\nContent-Type: text/css\r\nContent-Transfer-Encoding: quoted-printable\r\nContent-Location: https://example.com/s.css\r\n\r\n@font-face{src:url(f.woff2)}\r\n------MultipartBoundary--abc----\r\nContent-Type: image/png\r\nContent-Transfer-Encoding: base64\r\nContent-Location: https://example.com/a.png\r\n\r\niVBO\r\nRw==\r\n------MultipartBoundary--abc----\r\nContent-Type: font/woff2\r\nContent-Transfer-Encoding: base64\r\nContent-Location: https://example.com/f.woff2\r\n\r\nd09GMg==\r\n------MultipartBoundary--abc------\r\n";
+
+    #[test]
+    fn parses_the_parts() {
+        let archive = MhtmlArchive::parse(MHTML).unwrap();
+
+        assert_eq!(archive.parts.len(), 4);
+        assert_eq!(
+            archive.document().unwrap().body,
+            br#"<html><head><link rel="stylesheet" href="/s.css"></head><body><img src="/a.png"></body></html>"#
+        );
+        assert_eq!(archive.parts[2].body, b"\x89PNG");
+        assert_eq!(
+            archive.parts[2].location.as_deref(),
+            Some("https://example.com/a.png")
+        );
+        assert!(MhtmlArchive::parse("<html></html>").is_err());
+    }
+
+    #[test]
+    fn decodes_quoted_printable() {
+        assert_eq!(
+            decode_quoted_printable("a=3Db=\r\nc=E2=82=AC"),
+            "a=bc€".as_bytes()
+        );
+    }
+
+    #[cfg(feature = "_cache")]
+    #[tokio::test]
+    async fn inlines_the_resources() {
+        let html = MhtmlArchive::parse(MHTML)
+            .unwrap()
+            .to_single_file()
+            .await
+            .unwrap();
+
+        assert!(html.contains(r#"src="data:image/png;base64,iVBORw==""#));
+        // the font of the stylesheet is inlined in the inlined stylesheet.
+        assert!(html.contains(&format!(
+            "href=\"data:text/css;base64,{}\"",
+            base64::engine::general_purpose::STANDARD
+                .encode(r#"@font-face{src:url("data:font/woff2;base64,d09GMg==")}"#)
+        )));
+    }
+}
@@ -0,0 +1,135 @@
+/// The NDJSON sink.
+pub mod ndjson;
+/// The object store sink of large artifacts.
+#[cfg(feature = "object-store")]
+pub mod object_store;
+/// The parquet sink.
+#[cfg(feature = "parquet")]
+pub mod parquet;
+
+pub use ndjson::NdjsonSink;
+#[cfg(feature = "object-store")]
+pub use object_store::{ObjectStoreConfig, ObjectStoreSink, ObjectUpload, UploadOptions};
+#[cfg(feature = "parquet")]
+pub use parquet::{ParquetRecord, ParquetSink};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A sink of crawl output, e.g. [`crate::artifact::CrawlArtifact`]s or the
+/// [`crate::artifact::HarEntry`]s of the network logs.
+pub trait RecordSink<T> {
+    /// Write the record, rotating to a new file once the current one is full.
+    fn write(&mut self, record: &T) -> io::Result<()>;
+
+    /// Flush the buffered records to the current file.
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// When a sink rotates to a new file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    /// Rotate once the file reached the bytes.
+    pub max_bytes: Option<u64>,
+    /// Rotate once the file holds the records.
+    pub max_records: Option<u64>,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_bytes: Some(128 * 1024 * 1024),
+            max_records: None,
+        }
+    }
+}
+
+impl Rotation {
+    /// Never rotate.
+    pub fn never() -> Self {
+        Self {
+            max_bytes: None,
+            max_records: None,
+        }
+    }
+
+    /// The file of the bytes and records is full.
+    pub(crate) fn is_full(&self, bytes: u64, records: u64) -> bool {
+        self.max_bytes.is_some_and(|max| bytes >= max)
+            || self.max_records.is_some_and(|max| records >= max)
+    }
+}
+
+/// The numbered files of a sink, `{dir}/{prefix}-00000.{extension}` onwards.
+#[derive(Debug, Clone)]
+pub(crate) struct RotatingFiles {
+    dir: PathBuf,
+    prefix: String,
+    extension: &'static str,
+    index: u32,
+    written: Vec<PathBuf>,
+}
+
+impl RotatingFiles {
+    pub(crate) fn new(
+        dir: impl Into<PathBuf>,
+        prefix: impl Into<String>,
+        extension: &'static str,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: prefix.into(),
+            extension,
+            index: 0,
+            written: Vec::new(),
+        }
+    }
+
+    /// The path of the next file, skipping the files of a previous run.
+    pub(crate) fn next_path(&mut self) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        loop {
+            let path = self.path(self.index);
+            self.index += 1;
+
+            if !path.exists() {
+                self.written.push(path.clone());
+                return Ok(path);
+            }
+        }
+    }
+
+    fn path(&self, index: u32) -> PathBuf {
+        self.dir
+            .join(format!("{}-{index:05}.{}", self.prefix, self.extension))
+    }
+
+    /// The files written.
+    pub(crate) fn written(&self) -> &[PathBuf] {
+        &self.written
+    }
+
+    /// The directory of the files.
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_limits() {
+        let rotation = Rotation {
+            max_bytes: Some(100),
+            max_records: Some(10),
+        };
+
+        assert!(!rotation.is_full(99, 9));
+        assert!(rotation.is_full(100, 0));
+        assert!(rotation.is_full(0, 10));
+        assert!(!Rotation::never().is_full(u64::MAX, u64::MAX));
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::{RecordSink, RotatingFiles, Rotation};
+
+/// A sink writing one JSON record per line, rotating to a new file once the current one is full.
+#[derive(Debug)]
+pub struct NdjsonSink<T> {
+    files: RotatingFiles,
+    rotation: Rotation,
+    file: Option<BufWriter<File>>,
+    bytes: u64,
+    records: u64,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: Serialize> NdjsonSink<T> {
+    /// A sink writing `{dir}/{prefix}-00000.ndjson` onwards, the files are created on the first
+    /// record.
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>, rotation: Rotation) -> Self {
+        Self {
+            files: RotatingFiles::new(dir, prefix, "ndjson"),
+            rotation,
+            file: None,
+            bytes: 0,
+            records: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The directory of the files.
+    pub fn dir(&self) -> &Path {
+        self.files.dir()
+    }
+
+    /// The files written so far.
+    pub fn files(&self) -> &[PathBuf] {
+        self.files.written()
+    }
+
+    /// Flush the current file and return the files written.
+    pub fn close(mut self) -> io::Result<Vec<PathBuf>> {
+        self.flush()?;
+        Ok(self.files.written().to_vec())
+    }
+}
+
+impl<T: Serialize> RecordSink<T> for NdjsonSink<T> {
+    fn write(&mut self, record: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        if self.file.is_some() && self.rotation.is_full(self.bytes, self.records) {
+            self.flush()?;
+            self.file = None;
+        }
+
+        if self.file.is_none() {
+            self.bytes = 0;
+            self.records = 0;
+            self.file = Some(BufWriter::new(File::create(self.files.next_path()?)?));
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(&line)?;
+        }
+
+        self.bytes += line.len() as u64;
+        self.records += 1;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T> Drop for NdjsonSink<T> {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_by_records() {
+        let dir = std::env::temp_dir().join(format!("chromey-ndjson-sink-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut sink = NdjsonSink::new(
+            &dir,
+            "artifacts",
+            Rotation {
+                max_bytes: None,
+                max_records: Some(2),
+            },
+        );
+
+        for value in 0..5 {
+            sink.write(&serde_json::json!({ "value": value })).unwrap();
+        }
+
+        let files = sink.close().unwrap();
+
+        assert_eq!(files.len(), 3);
+        assert!(files[0].ends_with("artifacts-00000.ndjson"));
+
+        let first = std::fs::read_to_string(&files[0]).unwrap();
+        let last = std::fs::read_to_string(&files[2]).unwrap();
+
+        assert_eq!(first, "{\"value\":0}\n{\"value\":1}\n");
+        assert_eq!(last, "{\"value\":4}\n");
+
+        // a new sink does not overwrite the files of the previous run.
+        let mut resumed = NdjsonSink::new(&dir, "artifacts", Rotation::never());
+        resumed.write(&1).unwrap();
+
+        assert!(resumed.files()[0].ends_with("artifacts-00003.ndjson"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
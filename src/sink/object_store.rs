@@ -0,0 +1,619 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::stream::{self, StreamExt};
+use reqwest::Method;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::artifact::iso8601;
+use crate::error::{CdpError, Result};
+use crate::runtime::JoinHandle;
+
+/// The timeout of a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The attempts of a request.
+const MAX_ATTEMPTS: u32 = 3;
+/// The delay before the first retry, doubled on every retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// The min size of a multipart upload part.
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// An S3 compatible bucket, signed with AWS signature v4. GCS is reached through its XML API
+/// with HMAC keys.
+#[derive(Clone)]
+pub struct ObjectStoreConfig {
+    /// The endpoint, e.g. `https://s3.us-east-1.amazonaws.com`.
+    pub endpoint: String,
+    /// The region of the signature.
+    pub region: String,
+    /// The bucket, addressed path style.
+    pub bucket: String,
+    /// The access key id.
+    pub access_key: String,
+    /// The secret access key.
+    pub secret_key: String,
+    /// The session token of temporary credentials.
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for ObjectStoreConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectStoreConfig")
+            .field("endpoint", &self.endpoint)
+            .field("region", &self.region)
+            .field("bucket", &self.bucket)
+            .field("access_key", &self.access_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ObjectStoreConfig {
+    /// An AWS S3 bucket.
+    pub fn s3(
+        bucket: impl Into<String>,
+        region: impl Into<String>,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        let region = region.into();
+
+        Self {
+            endpoint: format!("https://s3.{region}.amazonaws.com"),
+            region,
+            bucket: bucket.into(),
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            session_token: None,
+        }
+    }
+
+    /// A GCS bucket with the HMAC keys of a service account.
+    pub fn gcs(
+        bucket: impl Into<String>,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            endpoint: "https://storage.googleapis.com".to_string(),
+            region: "auto".to_string(),
+            bucket: bucket.into(),
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            session_token: None,
+        }
+    }
+
+    /// Use another S3 compatible endpoint, e.g. MinIO or R2.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sign with the session token of temporary credentials.
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+
+    /// The url and the signed headers of the request on the key.
+    fn sign(
+        &self,
+        method: &Method,
+        key: &str,
+        query: &[(&str, &str)],
+        payload: &[u8],
+        now: SystemTime,
+    ) -> (String, Vec<(&'static str, String)>) {
+        let host = url::Url::parse(&self.endpoint)
+            .ok()
+            .and_then(|endpoint| {
+                let host = endpoint.host_str()?.to_string();
+                Some(match endpoint.port() {
+                    Some(port) => format!("{host}:{port}"),
+                    _ => host,
+                })
+            })
+            .unwrap_or_default();
+
+        let secs = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        // 2023-11-14T22:13:20.000Z into 20231114T221320Z.
+        let amz_date = format!("{}Z", iso8601(secs)[..19].replace(['-', ':'], ""));
+        let date = &amz_date[..8];
+
+        let path = format!(
+            "/{}/{}",
+            uri_encode(&self.bucket, true),
+            uri_encode(key, false)
+        );
+
+        let mut query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", uri_encode(name, true), uri_encode(value, true)))
+            .collect::<Vec<_>>();
+        query.sort();
+        let query = query.join("&");
+
+        let payload_hash = hex(&Sha256::digest(payload));
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        let canonical_headers = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+            .collect::<String>();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{method}\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}"
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", self.secret_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        headers.push((
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                self.access_key
+            ),
+        ));
+
+        let url = if query.is_empty() {
+            format!("{}{path}", self.endpoint)
+        } else {
+            format!("{}{path}?{query}", self.endpoint)
+        };
+
+        (url, headers)
+    }
+
+    /// Send the signed request, retrying failed requests and `429` or `5xx` responses.
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let mut backoff = RETRY_BACKOFF;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let (url, headers) = self.sign(&method, key, query, &body, SystemTime::now());
+
+            let mut request = crate::http_client::HTTP_CLIENT
+                .request(method.clone(), url)
+                .timeout(REQUEST_TIMEOUT)
+                .body(body.clone());
+
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+
+            if let Some(content_type) = content_type {
+                request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+            }
+
+            match request.send().await {
+                Ok(res) if res.status().is_success() => return Ok(res),
+                Ok(res) if res.status().as_u16() != 429 && !res.status().is_server_error() => {
+                    return Err(CdpError::msg(format!(
+                        "object store {method} {key} failed: {}",
+                        res.status()
+                    )));
+                }
+                Ok(res) => {
+                    tracing::debug!(
+                        "object store {method} {key} attempt {attempt} failed: {}",
+                        res.status()
+                    );
+                }
+                Err(err) => {
+                    tracing::debug!("object store {method} {key} attempt {attempt} failed: {err}");
+                }
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                crate::runtime::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+
+        Err(CdpError::msg(format!(
+            "object store {method} {key} failed after {MAX_ATTEMPTS} attempts"
+        )))
+    }
+
+    /// Upload the object, in parts of the size when larger.
+    pub async fn put(&self, key: &str, upload: &ObjectUpload, part_size: usize) -> Result<()> {
+        let part_size = part_size.max(MIN_PART_SIZE);
+
+        if upload.body.len() <= part_size {
+            self.send(
+                Method::PUT,
+                key,
+                &[],
+                upload.body.clone(),
+                Some(upload.content_type.as_str()),
+            )
+            .await?;
+
+            return Ok(());
+        }
+
+        let created = self
+            .send(
+                Method::POST,
+                key,
+                &[("uploads", "")],
+                Vec::new(),
+                Some(upload.content_type.as_str()),
+            )
+            .await?
+            .text()
+            .await
+            .map_err(|err| CdpError::msg(err.to_string()))?;
+
+        let upload_id = xml_value(&created, "UploadId")
+            .ok_or_else(|| CdpError::msg(format!("object store {key} returned no upload id")))?;
+
+        let uploaded = async {
+            let mut etags = Vec::new();
+
+            for (index, part) in upload.body.chunks(part_size).enumerate() {
+                let number = (index + 1).to_string();
+                let res = self
+                    .send(
+                        Method::PUT,
+                        key,
+                        &[("partNumber", &number), ("uploadId", &upload_id)],
+                        part.to_vec(),
+                        None,
+                    )
+                    .await?;
+
+                etags.push(
+                    res.headers()
+                        .get(reqwest::header::ETAG)
+                        .and_then(|etag| etag.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string(),
+                );
+            }
+
+            self.send(
+                Method::POST,
+                key,
+                &[("uploadId", &upload_id)],
+                complete_multipart_body(&etags).into_bytes(),
+                Some("application/xml"),
+            )
+            .await
+            .map(|_| ())
+        }
+        .await;
+
+        if uploaded.is_err() {
+            let _ = self
+                .send(
+                    Method::DELETE,
+                    key,
+                    &[("uploadId", &upload_id)],
+                    Vec::new(),
+                    None,
+                )
+                .await;
+        }
+
+        uploaded
+    }
+}
+
+/// The HMAC-SHA256 of the data.
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(data);
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+
+    outer.finalize().into()
+}
+
+/// The lowercase hex of the bytes.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Percent encode everything but the unreserved characters, and `/` unless `encode_slash`.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+
+    encoded
+}
+
+/// The text of the first element of the xml.
+fn xml_value(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+
+    Some(xml[start..end].to_string())
+}
+
+/// The body completing a multipart upload of the part etags.
+fn complete_multipart_body(etags: &[String]) -> String {
+    let parts = etags
+        .iter()
+        .enumerate()
+        .map(|(index, etag)| {
+            format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{etag}</ETag></Part>",
+                index + 1
+            )
+        })
+        .collect::<String>();
+
+    format!("<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>")
+}
+
+/// A large artifact to upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectUpload {
+    /// The url of the page of the artifact.
+    pub url: String,
+    /// The bytes.
+    pub body: Vec<u8>,
+    /// The content type of the object.
+    pub content_type: String,
+    /// The extension of the key.
+    pub extension: String,
+}
+
+impl ObjectUpload {
+    /// An artifact of the page.
+    pub fn new(
+        url: impl Into<String>,
+        body: Vec<u8>,
+        content_type: impl Into<String>,
+        extension: impl Into<String>,
+    ) -> Self {
+        Self {
+            url: url.into(),
+            body,
+            content_type: content_type.into(),
+            extension: extension.into(),
+        }
+    }
+
+    /// A png screenshot of the page.
+    pub fn png(url: impl Into<String>, body: Vec<u8>) -> Self {
+        Self::new(url, body, "image/png", "png")
+    }
+
+    /// A pdf of the page.
+    pub fn pdf(url: impl Into<String>, body: Vec<u8>) -> Self {
+        Self::new(url, body, "application/pdf", "pdf")
+    }
+
+    /// A MHTML snapshot of the page.
+    pub fn mhtml(url: impl Into<String>, body: Vec<u8>) -> Self {
+        Self::new(url, body, "multipart/related", "mhtml")
+    }
+
+    /// A WARC record of the page.
+    pub fn warc(url: impl Into<String>, body: Vec<u8>) -> Self {
+        Self::new(url, body, "application/warc", "warc")
+    }
+
+    /// The key of the template, replacing `{host}`, `{date}` (`YYYY-MM-DD`), `{hash}` (the
+    /// sha256 of the bytes) and `{ext}`.
+    pub fn key(&self, template: &str) -> String {
+        let host = url::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string());
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+
+        template
+            .replace("{host}", &host)
+            .replace("{date}", &iso8601(secs)[..10])
+            .replace("{hash}", &hex(&Sha256::digest(&self.body)))
+            .replace("{ext}", &self.extension)
+    }
+}
+
+/// How the artifacts are uploaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadOptions {
+    /// The key template, see [`ObjectUpload::key`].
+    pub key_template: String,
+    /// The part size of multipart uploads, at least 5 MiB.
+    pub part_size: usize,
+    /// The uploads running at the same time.
+    pub concurrency: usize,
+    /// The uploads waiting before [`ObjectStoreSink::upload`] waits for room.
+    pub queue: usize,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            key_template: "{host}/{date}/{hash}.{ext}".to_string(),
+            part_size: 8 * 1024 * 1024,
+            concurrency: 4,
+            queue: 64,
+        }
+    }
+}
+
+/// A sink uploading artifacts to an object store on a background worker. The queue of the
+/// worker is bounded, so a slow store applies backpressure to the crawl instead of buffering the
+/// artifacts in memory.
+#[derive(Debug)]
+pub struct ObjectStoreSink {
+    sender: mpsc::Sender<(String, ObjectUpload)>,
+    worker: JoinHandle<()>,
+    key_template: String,
+}
+
+impl ObjectStoreSink {
+    /// Spawn the upload worker of the store.
+    pub fn spawn(config: ObjectStoreConfig, options: UploadOptions) -> Self {
+        let (sender, receiver) = mpsc::channel::<(String, ObjectUpload)>(options.queue.max(1));
+        let config = Arc::new(config);
+        let part_size = options.part_size;
+
+        let uploads = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|upload| (upload, receiver))
+        });
+
+        let worker = crate::runtime::spawn(uploads.for_each_concurrent(
+            options.concurrency.max(1),
+            move |(key, upload)| {
+                let config = config.clone();
+
+                async move {
+                    match config.put(&key, &upload, part_size).await {
+                        Ok(_) => tracing::debug!("object store uploaded {key}"),
+                        Err(err) => tracing::warn!("object store upload of {key} failed: {err}"),
+                    }
+                }
+            },
+        ));
+
+        Self {
+            sender,
+            worker,
+            key_template: options.key_template,
+        }
+    }
+
+    /// Queue the upload, waiting for room in the queue. Returns the key of the object.
+    pub async fn upload(&self, upload: ObjectUpload) -> Result<String> {
+        let key = upload.key(&self.key_template);
+
+        self.sender
+            .send((key.clone(), upload))
+            .await
+            .map_err(|_| CdpError::msg("object store worker stopped"))?;
+
+        Ok(key)
+    }
+
+    /// Queue the upload without waiting, `None` when the queue is full.
+    pub fn try_upload(&self, upload: ObjectUpload) -> Option<String> {
+        let key = upload.key(&self.key_template);
+
+        self.sender.try_send((key.clone(), upload)).ok()?;
+
+        Some(key)
+    }
+
+    /// Stop accepting uploads and wait for the queued uploads to finish.
+    pub async fn close(self) {
+        drop(self.sender);
+        let _ = self.worker.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn keys_are_templated() {
+        let upload = ObjectUpload::png("https://example.com/a?b", b"png".to_vec());
+        let key = upload.key("{host}/{date}/{hash}.{ext}");
+        let parts = key.split('/').collect::<Vec<_>>();
+
+        assert_eq!(parts[0], "example.com");
+        assert_eq!(parts[1].len(), 10);
+        assert!(parts[2].ends_with(".png"));
+        assert_eq!(parts[2].len(), 64 + 4);
+    }
+
+    #[test]
+    fn signs_path_style_requests() {
+        let config = ObjectStoreConfig::s3("bucket", "us-east-1", "AKID", "secret");
+        let (url, headers) = config.sign(
+            &Method::PUT,
+            "example.com/a b.png",
+            &[("uploadId", "x/y"), ("partNumber", "1")],
+            b"",
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+
+        assert_eq!(
+            url,
+            "https://s3.us-east-1.amazonaws.com/bucket/example.com/a%20b.png?partNumber=1&uploadId=x%2Fy"
+        );
+        assert!(headers
+            .iter()
+            .any(|(name, value)| *name == "x-amz-date" && value == "20231114T221320Z"));
+        assert!(headers.iter().any(|(name, value)| *name == "authorization"
+            && value.starts_with("AWS4-HMAC-SHA256 Credential=AKID/20231114/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature=")));
+        assert_eq!(
+            xml_value("<a><UploadId>id</UploadId></a>", "UploadId").as_deref(),
+            Some("id")
+        );
+    }
+}
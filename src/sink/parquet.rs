@@ -0,0 +1,426 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::file::properties::WriterProperties;
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{
+    ArrayRef, BinaryArray, Float64Array, Int64Array, RecordBatch, StringArray, UInt16Array,
+    UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+
+use super::{RecordSink, RotatingFiles, Rotation};
+use crate::artifact::{CrawlArtifact, HarEntry};
+
+/// A record with a fixed columnar layout.
+pub trait ParquetRecord: Clone {
+    /// The schema of the records.
+    fn schema() -> SchemaRef;
+
+    /// The batch of the records in the schema.
+    fn to_batch(records: &[Self]) -> Result<RecordBatch, ArrowError>;
+}
+
+fn io_error(err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+fn string_list<'a>(values: impl Iterator<Item = Vec<&'a str>>) -> ArrayRef {
+    let mut builder = ListBuilder::new(StringBuilder::new());
+
+    for list in values {
+        for value in list {
+            builder.values().append_value(value);
+        }
+        builder.append(true);
+    }
+
+    Arc::new(builder.finish())
+}
+
+fn list_field(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+        false,
+    )
+}
+
+impl ParquetRecord for CrawlArtifact {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("url", DataType::Utf8, false),
+            Field::new("final_url", DataType::Utf8, true),
+            Field::new("status", DataType::UInt16, true),
+            Field::new("title", DataType::Utf8, true),
+            Field::new("description", DataType::Utf8, true),
+            Field::new("canonical", DataType::Utf8, true),
+            Field::new("lang", DataType::Utf8, true),
+            Field::new("html", DataType::Utf8, true),
+            list_field("links"),
+            Field::new("requests", DataType::UInt64, false),
+            Field::new("failed_requests", DataType::UInt64, false),
+            Field::new("bytes", DataType::UInt64, false),
+            Field::new("screenshot", DataType::Binary, true),
+            Field::new("har", DataType::Utf8, true),
+            Field::new("js_errors", DataType::UInt64, false),
+            list_field("errors"),
+            Field::new("duration_ms", DataType::UInt64, false),
+        ]))
+    }
+
+    fn to_batch(records: &[Self]) -> Result<RecordBatch, ArrowError> {
+        let har = records
+            .iter()
+            .map(|record| {
+                record
+                    .har
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()
+                    .map_err(|err| ArrowError::ExternalError(Box::new(err)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let errors = records
+            .iter()
+            .map(|record| {
+                record
+                    .errors
+                    .iter()
+                    .map(|error| format!("{}: {}", error.step, error.message))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|record| record.url.as_str()),
+            )),
+            Arc::new(StringArray::from_iter(
+                records.iter().map(|record| record.final_url.as_deref()),
+            )),
+            Arc::new(UInt16Array::from_iter(
+                records.iter().map(|record| record.status),
+            )),
+            Arc::new(StringArray::from_iter(
+                records
+                    .iter()
+                    .map(|record| record.metadata.title.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                records
+                    .iter()
+                    .map(|record| record.metadata.description.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                records
+                    .iter()
+                    .map(|record| record.metadata.canonical.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                records.iter().map(|record| record.metadata.lang.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                records.iter().map(|record| record.html.as_deref()),
+            )),
+            string_list(
+                records
+                    .iter()
+                    .map(|record| record.links.iter().map(String::as_str).collect()),
+            ),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|record| record.network.requests as u64),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|record| record.network.failed as u64),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|record| record.network.bytes),
+            )),
+            Arc::new(BinaryArray::from_iter(
+                records.iter().map(|record| record.screenshot.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(har.iter().map(Option::as_deref))),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|record| record.js_errors.len() as u64),
+            )),
+            string_list(
+                errors
+                    .iter()
+                    .map(|errors| errors.iter().map(String::as_str).collect()),
+            ),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|record| record.duration_ms),
+            )),
+        ];
+
+        RecordBatch::try_new(Self::schema(), columns)
+    }
+}
+
+impl ParquetRecord for HarEntry {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("started_date_time", DataType::Utf8, false),
+            Field::new("time", DataType::Float64, false),
+            Field::new("method", DataType::Utf8, false),
+            Field::new("url", DataType::Utf8, false),
+            Field::new("http_version", DataType::Utf8, false),
+            Field::new("status", DataType::UInt16, false),
+            Field::new("status_text", DataType::Utf8, false),
+            Field::new("mime_type", DataType::Utf8, false),
+            Field::new("body_size", DataType::Int64, false),
+            Field::new("resource_type", DataType::Utf8, true),
+            Field::new("error", DataType::Utf8, true),
+        ]))
+    }
+
+    fn to_batch(records: &[Self]) -> Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|entry| entry.started_date_time.as_str()),
+            )),
+            Arc::new(Float64Array::from_iter_values(
+                records.iter().map(|entry| entry.time),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|entry| entry.request.method.as_str()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|entry| entry.request.url.as_str()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records
+                    .iter()
+                    .map(|entry| entry.request.http_version.as_str()),
+            )),
+            Arc::new(UInt16Array::from_iter_values(
+                records.iter().map(|entry| entry.response.status),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records
+                    .iter()
+                    .map(|entry| entry.response.status_text.as_str()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records
+                    .iter()
+                    .map(|entry| entry.response.content.mime_type.as_str()),
+            )),
+            Arc::new(Int64Array::from_iter_values(
+                records.iter().map(|entry| entry.response.body_size),
+            )),
+            Arc::new(StringArray::from_iter(
+                records.iter().map(|entry| entry.resource_type.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                records.iter().map(|entry| entry.error.as_deref()),
+            )),
+        ];
+
+        RecordBatch::try_new(Self::schema(), columns)
+    }
+}
+
+/// A sink writing the records to parquet files in row groups of `batch_size` records, rotating
+/// to a new file once the current one is full.
+pub struct ParquetSink<T: ParquetRecord> {
+    files: RotatingFiles,
+    rotation: Rotation,
+    properties: WriterProperties,
+    batch_size: usize,
+    pending: Vec<T>,
+    writer: Option<ArrowWriter<File>>,
+    records: u64,
+}
+
+impl<T: ParquetRecord> std::fmt::Debug for ParquetSink<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParquetSink")
+            .field("files", &self.files)
+            .field("rotation", &self.rotation)
+            .field("batch_size", &self.batch_size)
+            .field("pending", &self.pending.len())
+            .field("records", &self.records)
+            .finish()
+    }
+}
+
+impl<T: ParquetRecord> ParquetSink<T> {
+    /// A sink writing `{dir}/{prefix}-00000.parquet` onwards with the default writer properties,
+    /// the files are created on the first row group.
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>, rotation: Rotation) -> Self {
+        Self::with_properties(dir, prefix, rotation, WriterProperties::default())
+    }
+
+    /// A sink writing the files with the writer properties, e.g. the compression.
+    pub fn with_properties(
+        dir: impl Into<PathBuf>,
+        prefix: impl Into<String>,
+        rotation: Rotation,
+        properties: WriterProperties,
+    ) -> Self {
+        Self {
+            files: RotatingFiles::new(dir, prefix, "parquet"),
+            rotation,
+            properties,
+            batch_size: 256,
+            pending: Vec::new(),
+            writer: None,
+            records: 0,
+        }
+    }
+
+    /// Write the records in row groups of the size.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// The directory of the files.
+    pub fn dir(&self) -> &Path {
+        self.files.dir()
+    }
+
+    /// The files written so far.
+    pub fn files(&self) -> &[PathBuf] {
+        self.files.written()
+    }
+
+    /// Write the pending records and the footer of the current file, returning the files
+    /// written. A parquet file is only readable once closed.
+    pub fn close(mut self) -> io::Result<Vec<PathBuf>> {
+        self.flush()?;
+        self.finish_file()?;
+        Ok(self.files.written().to_vec())
+    }
+
+    fn finish_file(&mut self) -> io::Result<()> {
+        self.records = 0;
+
+        match self.writer.take() {
+            Some(writer) => writer.close().map(|_| ()).map_err(io_error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the records to the current file.
+    fn write_batch(&mut self, records: &[T]) -> io::Result<()> {
+        if self.writer.is_none() {
+            let file = File::create(self.files.next_path()?)?;
+            let writer = ArrowWriter::try_new(file, T::schema(), Some(self.properties.clone()))
+                .map_err(io_error)?;
+
+            self.writer = Some(writer);
+        }
+
+        let batch = T::to_batch(records).map_err(io_error)?;
+
+        if let Some(writer) = self.writer.as_mut() {
+            writer
+                .write(&batch)
+                .and_then(|_| writer.flush())
+                .map_err(io_error)?;
+
+            self.records += records.len() as u64;
+
+            if self
+                .rotation
+                .is_full(writer.bytes_written() as u64, self.records)
+            {
+                self.finish_file()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: ParquetRecord> RecordSink<T> for ParquetSink<T> {
+    fn write(&mut self, record: &T) -> io::Result<()> {
+        self.pending.push(record.clone());
+
+        // a file holds whole row groups, split a group at the record limit of the file.
+        let limit = match self.rotation.max_records {
+            Some(max) => (max.saturating_sub(self.records) as usize).clamp(1, self.batch_size),
+            _ => self.batch_size,
+        };
+
+        if self.pending.len() >= limit {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the pending records as a row group, the file stays open until closed or rotated.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+
+        self.write_batch(&pending)
+    }
+}
+
+impl<T: ParquetRecord> Drop for ParquetSink<T> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        let _ = self.finish_file();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn rotates_by_records() {
+        let dir = std::env::temp_dir().join(format!("chromey-parquet-sink-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut sink = ParquetSink::new(
+            &dir,
+            "artifacts",
+            Rotation {
+                max_bytes: None,
+                max_records: Some(2),
+            },
+        );
+
+        for index in 0..3 {
+            sink.write(&CrawlArtifact {
+                url: format!("https://example.com/{index}"),
+                links: vec!["https://example.com/".into()],
+                ..Default::default()
+            })
+            .unwrap();
+        }
+
+        let files = sink.close().unwrap();
+
+        assert_eq!(files.len(), 2);
+
+        let rows = files
+            .iter()
+            .map(|path| {
+                ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+                    .unwrap()
+                    .build()
+                    .unwrap()
+                    .map(|batch| batch.unwrap().num_rows())
+                    .sum::<usize>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(rows, vec![2, 1]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{CdpError, Result};
+use crate::js_errors::{JsError, StackFrame};
+
+/// The timeout of the request of a script or source map.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The max size of a script or source map fetched for resolution.
+const MAX_SOURCE_MAP_BYTES: usize = 20 * 1024 * 1024;
+
+/// A position in an original source file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct OriginalLocation {
+    /// The url of the original source.
+    pub source: String,
+    /// The zero based line number in the original source.
+    pub line_number: i64,
+    /// The zero based column number in the original source.
+    pub column_number: i64,
+    /// The original name of the symbol, if mapped.
+    pub name: Option<String>,
+}
+
+/// A single decoded mapping segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Mapping {
+    generated_column: i64,
+    source: Option<(usize, i64, i64)>,
+    name: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSourceMap {
+    version: u8,
+    #[serde(default)]
+    source_root: Option<String>,
+    #[serde(default)]
+    sources: Vec<Option<String>>,
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    mappings: String,
+}
+
+/// A parsed version 3 source map.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// The resolved urls of the original sources.
+    sources: Vec<String>,
+    /// The symbol names.
+    names: Vec<String>,
+    /// The mappings of each generated line, sorted by column.
+    lines: Vec<Vec<Mapping>>,
+}
+
+impl SourceMap {
+    /// Parse a source map. Relative sources are resolved against the url of the map.
+    pub fn parse(json: &str, map_url: Option<&str>) -> Result<Self> {
+        let raw: RawSourceMap = serde_json::from_str(json)?;
+
+        if raw.version != 3 {
+            return Err(CdpError::msg(format!(
+                "unsupported source map version {}",
+                raw.version
+            )));
+        }
+
+        let base = map_url.and_then(|u| url::Url::parse(u).ok());
+        let root = raw.source_root.unwrap_or_default();
+
+        let sources = raw
+            .sources
+            .into_iter()
+            .map(|source| {
+                let source = format!("{root}{}", source.unwrap_or_default());
+                base.as_ref()
+                    .and_then(|b| b.join(&source).ok())
+                    .map(|u| u.to_string())
+                    .unwrap_or(source)
+            })
+            .collect();
+
+        Ok(Self {
+            sources,
+            names: raw.names,
+            lines: decode_mappings(&raw.mappings)?,
+        })
+    }
+
+    /// The original location of a zero based position in the generated script.
+    pub fn lookup(&self, line_number: i64, column_number: i64) -> Option<OriginalLocation> {
+        let line = self.lines.get(usize::try_from(line_number).ok()?)?;
+        let idx = line.partition_point(|m| m.generated_column <= column_number);
+        let mapping = line.get(idx.checked_sub(1)?)?;
+        let (source, line_number, column_number) = mapping.source?;
+
+        Some(OriginalLocation {
+            source: self.sources.get(source)?.clone(),
+            line_number,
+            column_number,
+            name: mapping.name.and_then(|n| self.names.get(n).cloned()),
+        })
+    }
+}
+
+/// Decode a single base64 VLQ digit.
+fn vlq_digit(c: u8) -> Option<i64> {
+    let v = match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return None,
+    };
+    Some(v as i64)
+}
+
+/// Decode the base64 VLQ values of a segment.
+fn decode_segment(segment: &str) -> Result<Vec<i64>> {
+    let mut values = Vec::with_capacity(5);
+    let mut value = 0i64;
+    let mut shift = 0u32;
+
+    for c in segment.bytes() {
+        let digit = vlq_digit(c)
+            .ok_or_else(|| CdpError::msg(format!("invalid source map mapping '{segment}'")))?;
+
+        if shift > 60 {
+            return Err(CdpError::msg("source map mapping overflow"));
+        }
+
+        value += (digit & 31) << shift;
+
+        if digit & 32 != 0 {
+            shift += 5;
+        } else {
+            let negative = value & 1 == 1;
+            value >>= 1;
+            values.push(if negative { -value } else { value });
+            value = 0;
+            shift = 0;
+        }
+    }
+
+    Ok(values)
+}
+
+/// Decode the `mappings` field into the segments of each generated line.
+fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Mapping>>> {
+    let mut lines = Vec::new();
+    let (mut source, mut original_line, mut original_column, mut name) = (0i64, 0i64, 0i64, 0i64);
+
+    for line in mappings.split(';') {
+        let mut generated_column = 0i64;
+        let mut segments = Vec::new();
+
+        for segment in line.split(',').filter(|s| !s.is_empty()) {
+            let values = decode_segment(segment)?;
+
+            if values.is_empty() {
+                return Err(CdpError::msg(format!(
+                    "invalid source map mapping '{segment}'"
+                )));
+            }
+
+            generated_column += values[0];
+
+            let mut mapping = Mapping {
+                generated_column,
+                source: None,
+                name: None,
+            };
+
+            if values.len() >= 4 {
+                source += values[1];
+                original_line += values[2];
+                original_column += values[3];
+                mapping.source = usize::try_from(source)
+                    .ok()
+                    .map(|s| (s, original_line, original_column));
+            }
+
+            if values.len() >= 5 {
+                name += values[4];
+                mapping.name = usize::try_from(name).ok();
+            }
+
+            segments.push(mapping);
+        }
+
+        segments.sort_by_key(|m| m.generated_column);
+        lines.push(segments);
+    }
+
+    Ok(lines)
+}
+
+/// The `sourceMappingURL` comment of a script.
+fn source_mapping_url(script: &str) -> Option<&str> {
+    script.lines().rev().take(5).find_map(|line| {
+        let line = line.trim();
+        line.strip_prefix("//# sourceMappingURL=")
+            .or_else(|| line.strip_prefix("//@ sourceMappingURL="))
+            .map(str::trim)
+    })
+}
+
+/// Fetch a text resource, reading from the cache layer first when enabled.
+async fn fetch_text(url: &str, use_cache: bool) -> Option<String> {
+    #[cfg(feature = "_cache")]
+    if use_cache {
+        if let Some(body) = crate::cache::get_cached_url(url, None).await {
+            return String::from_utf8(body).ok();
+        }
+    }
+    #[cfg(not(feature = "_cache"))]
+    let _ = use_cache;
+
+    let response = crate::http_client::HTTP_CLIENT
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .ok()?;
+
+    if !response.status().is_success()
+        || response
+            .content_length()
+            .map_or(false, |len| len as usize > MAX_SOURCE_MAP_BYTES)
+    {
+        return None;
+    }
+
+    let body = response.bytes().await.ok()?;
+
+    if body.len() > MAX_SOURCE_MAP_BYTES {
+        return None;
+    }
+
+    String::from_utf8(body.to_vec()).ok()
+}
+
+/// Resolves generated script positions to their original sources. Scripts and maps are fetched
+/// once per resolver, through the cache layer when the `cache` features are enabled.
+#[derive(Debug, Clone)]
+pub struct SourceMapResolver {
+    /// The source map of each script url, `None` when the script has no usable map.
+    maps: HashMap<String, Option<Arc<SourceMap>>>,
+    /// Read the scripts and maps from the cache first.
+    use_cache: bool,
+}
+
+impl Default for SourceMapResolver {
+    fn default() -> Self {
+        Self {
+            maps: Default::default(),
+            use_cache: true,
+        }
+    }
+}
+
+impl SourceMapResolver {
+    /// A new resolver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the scripts and maps from the cache before the network.
+    pub fn with_cache(mut self, use_cache: bool) -> Self {
+        self.use_cache = use_cache;
+        self
+    }
+
+    /// The source map of a script.
+    pub async fn source_map(&mut self, script_url: &str) -> Option<Arc<SourceMap>> {
+        if let Some(map) = self.maps.get(script_url) {
+            return map.clone();
+        }
+
+        let map = self.load(script_url).await.map(Arc::new);
+        self.maps.insert(script_url.to_string(), map.clone());
+        map
+    }
+
+    async fn load(&self, script_url: &str) -> Option<SourceMap> {
+        if !(script_url.starts_with("http://") || script_url.starts_with("https://")) {
+            return None;
+        }
+
+        let script = fetch_text(script_url, self.use_cache).await?;
+        let reference = source_mapping_url(&script)?;
+
+        if let Some(data) = reference.strip_prefix("data:") {
+            let (meta, payload) = data.split_once(',')?;
+            let json = if meta.ends_with(";base64") {
+                String::from_utf8(crate::utils::base64::decode(payload).ok()?).ok()?
+            } else {
+                payload.to_string()
+            };
+            return SourceMap::parse(&json, Some(script_url)).ok();
+        }
+
+        let map_url = url::Url::parse(script_url).ok()?.join(reference).ok()?;
+        let json = fetch_text(map_url.as_str(), self.use_cache).await?;
+
+        SourceMap::parse(&json, Some(map_url.as_str())).ok()
+    }
+
+    /// Set the original location of the frame.
+    pub async fn resolve_frame(&mut self, frame: &mut StackFrame) {
+        if frame.url.is_empty() {
+            return;
+        }
+
+        if let Some(map) = self.source_map(&frame.url).await {
+            frame.original = map.lookup(frame.line_number, frame.column_number);
+        }
+    }
+
+    /// Set the original locations of all the frames of the error.
+    pub async fn resolve_error(&mut self, error: &mut JsError) {
+        for frame in error.frames.iter_mut() {
+            self.resolve_frame(frame).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_vlq_segments() {
+        assert_eq!(decode_segment("AAAA").unwrap(), vec![0, 0, 0, 0]);
+        assert_eq!(decode_segment("SAAQ").unwrap(), vec![9, 0, 0, 8]);
+        assert_eq!(decode_segment("D").unwrap(), vec![-1]);
+        assert_eq!(decode_segment("gB").unwrap(), vec![16]);
+        assert!(decode_segment("A$").is_err());
+    }
+
+    #[test]
+    fn lookup_original_location() {
+        let map = SourceMap::parse(
+            r#"{"version":3,"sources":["src/app.ts"],"names":["load"],"mappings":"AAAA,SAAQA;AACA"}"#,
+            Some("https://example.com/static/app.js.map"),
+        )
+        .unwrap();
+
+        let location = map.lookup(0, 12).unwrap();
+        assert_eq!(location.source, "https://example.com/static/src/app.ts");
+        assert_eq!((location.line_number, location.column_number), (0, 8));
+        assert_eq!(location.name.as_deref(), Some("load"));
+
+        let location = map.lookup(1, 0).unwrap();
+        assert_eq!((location.line_number, location.column_number), (1, 8));
+        assert!(map.lookup(5, 0).is_none());
+    }
+
+    #[test]
+    fn find_source_mapping_url() {
+        assert_eq!(
+            source_mapping_url("var a=1;\n//# sourceMappingURL=app.js.map\n"),
+            Some("app.js.map")
+        );
+        assert_eq!(source_mapping_url("var a=1;"), None);
+    }
+}
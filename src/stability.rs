@@ -0,0 +1,98 @@
+/// Replace `speechSynthesis` with a silent engine: utterances start and end right away and no
+/// voices are listed, so code awaiting `onend` does not hang.
+const SPEECH_SYNTHESIS_JS: &str = r###"(()=>{const E=typeof EventTarget==='function'?EventTarget:Object;const s=new E();const fire=(u,t)=>{try{const e=typeof SpeechSynthesisEvent==='function'?new SpeechSynthesisEvent(t,{utterance:u}):new Event(t);typeof u['on'+t]==='function'&&u['on'+t](e);u.dispatchEvent&&u.dispatchEvent(e)}catch(e){}};Object.assign(s,{speaking:false,pending:false,paused:false,onvoiceschanged:null,getVoices:()=>[],speak:u=>{setTimeout(()=>{fire(u,'start');fire(u,'end')},0)},cancel:()=>{},pause:()=>{},resume:()=>{}});try{Object.defineProperty(window,'speechSynthesis',{value:s,configurable:true})}catch(e){}})()"###;
+
+/// Settle the WebAudio promises that never settle without an audio device: `resume` and
+/// `startRendering` resolve after `__MS__`ms, rendering a silent buffer.
+const WEB_AUDIO_JS: &str = r###"(()=>{const ms=__MS__;const race=(p,v)=>Promise.race([p,new Promise(r=>setTimeout(()=>r(v()),ms))]);const A=window.AudioContext||window.webkitAudioContext;if(A&&A.prototype.resume){const f=A.prototype.resume;A.prototype.resume=function(){return race(f.apply(this,arguments).catch(()=>undefined),()=>undefined)}}const O=window.OfflineAudioContext;if(O&&O.prototype.startRendering){const f=O.prototype.startRendering;O.prototype.startRendering=function(){const c=this;return race(f.apply(this,arguments),()=>c.createBuffer(1,Math.max(1,c.length||1),c.sampleRate||44100))}}})()"###;
+
+/// Settle `HTMLMediaElement.play` after `__MS__`ms when the playback never starts.
+const MEDIA_PLAY_JS: &str = r###"(()=>{const M=window.HTMLMediaElement;if(!M||!M.prototype.play)return;const ms=__MS__;const f=M.prototype.play;M.prototype.play=function(){const p=f.apply(this,arguments);return p&&typeof p.then==='function'?Promise.race([p,new Promise(r=>setTimeout(r,ms))]):p}})()"###;
+
+/// The shims of the web apis that crash or hang in some headless environments, applied at the
+/// start of every document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StabilityShims {
+    /// Stub `speechSynthesis`.
+    pub speech_synthesis: bool,
+    /// Settle the hanging `AudioContext.resume` and `OfflineAudioContext.startRendering`.
+    pub web_audio: bool,
+    /// Settle the hanging `HTMLMediaElement.play`.
+    pub media_play: bool,
+    /// The milliseconds before a hanging promise is settled, `1000` when `0`.
+    pub settle_timeout_ms: u64,
+}
+
+impl StabilityShims {
+    /// Every shim.
+    pub fn all() -> Self {
+        Self {
+            speech_synthesis: true,
+            web_audio: true,
+            media_play: true,
+            settle_timeout_ms: 0,
+        }
+    }
+
+    /// Any shim is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.speech_synthesis || self.web_audio || self.media_play
+    }
+
+    /// The script of the enabled shims, `None` when none is enabled.
+    pub fn script(&self) -> Option<String> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let ms = match self.settle_timeout_ms {
+            0 => 1000,
+            ms => ms,
+        }
+        .to_string();
+
+        let mut script = String::new();
+
+        if self.speech_synthesis {
+            script.push_str(SPEECH_SYNTHESIS_JS);
+            script.push(';');
+        }
+
+        if self.web_audio {
+            script.push_str(&WEB_AUDIO_JS.replacen("__MS__", &ms, 1));
+            script.push(';');
+        }
+
+        if self.media_play {
+            script.push_str(&MEDIA_PLAY_JS.replacen("__MS__", &ms, 1));
+            script.push(';');
+        }
+
+        Some(script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_enabled_shims() {
+        assert!(StabilityShims::default().script().is_none());
+
+        let script = StabilityShims {
+            web_audio: true,
+            settle_timeout_ms: 250,
+            ..Default::default()
+        }
+        .script()
+        .unwrap();
+
+        assert!(script.contains("const ms=250;"));
+        assert!(!script.contains("speechSynthesis"));
+        assert!(StabilityShims::all()
+            .script()
+            .unwrap()
+            .contains("const ms=1000;"));
+    }
+}
@@ -0,0 +1,325 @@
+use chromiumoxide_cdp::cdp::browser_protocol::network::RequestId;
+
+/// The kind of adaptive streaming manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum StreamingManifestKind {
+    /// HTTP Live Streaming playlist (`.m3u8`).
+    Hls,
+    /// MPEG-DASH media presentation description (`.mpd`).
+    Dash,
+}
+
+/// A streaming manifest seen on the network that has not been parsed yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamingManifestRequest {
+    /// The network request id used to retrieve the body.
+    pub request_id: RequestId,
+    /// The manifest url.
+    pub url: String,
+    /// The kind of manifest.
+    pub kind: StreamingManifestKind,
+}
+
+/// A variant (rendition) listed in a manifest.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StreamingVariant {
+    /// The absolute url of the variant playlist or the representation id for DASH.
+    pub url: String,
+    /// The advertised bandwidth in bits per second.
+    pub bandwidth: Option<u64>,
+    /// The resolution as `WIDTHxHEIGHT`.
+    pub resolution: Option<String>,
+    /// The codecs string.
+    pub codecs: Option<String>,
+}
+
+/// A parsed streaming manifest. Segments are listed, never downloaded.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StreamingAsset {
+    /// The manifest url.
+    pub url: String,
+    /// The kind of manifest.
+    pub kind: StreamingManifestKind,
+    /// The variant playlists or representations.
+    pub variants: Vec<StreamingVariant>,
+    /// The segment urls or segment url templates (DASH `$Number$` style templates are kept as-is).
+    pub segments: Vec<String>,
+}
+
+/// Detect a streaming manifest from the response url and mime type.
+pub fn detect_manifest_kind(url: &str, mime_type: &str) -> Option<StreamingManifestKind> {
+    let mime = mime_type.trim().to_ascii_lowercase();
+
+    if mime == "application/vnd.apple.mpegurl"
+        || mime == "application/x-mpegurl"
+        || mime == "audio/mpegurl"
+        || mime == "audio/x-mpegurl"
+    {
+        return Some(StreamingManifestKind::Hls);
+    }
+
+    if mime == "application/dash+xml" {
+        return Some(StreamingManifestKind::Dash);
+    }
+
+    let path = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    if path.ends_with(".m3u8") {
+        Some(StreamingManifestKind::Hls)
+    } else if path.ends_with(".mpd") {
+        Some(StreamingManifestKind::Dash)
+    } else {
+        None
+    }
+}
+
+/// Resolve a manifest reference against the manifest url.
+fn resolve(base: Option<&url::Url>, reference: &str) -> String {
+    match base.and_then(|b| b.join(reference).ok()) {
+        Some(u) => u.to_string(),
+        _ => reference.to_string(),
+    }
+}
+
+/// Get an attribute value from a HLS attribute list. `BANDWIDTH=1280000,CODECS="avc1"`
+fn hls_attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let next = quoted[end..].trim_start_matches('"');
+            (&quoted[..end], next.strip_prefix(',').unwrap_or(next))
+        } else {
+            match after.split_once(',') {
+                Some((v, n)) => (v, n),
+                None => (after, ""),
+            }
+        };
+
+        if key.trim().eq_ignore_ascii_case(name) {
+            return Some(value.to_string());
+        }
+
+        rest = next;
+    }
+
+    None
+}
+
+/// Parse a HLS playlist. Master playlists return variants, media playlists return segments.
+pub fn parse_hls(manifest_url: &str, body: &str) -> StreamingAsset {
+    let base = url::Url::parse(manifest_url).ok();
+    let mut variants = Vec::new();
+    let mut segments = Vec::new();
+    let mut pending_variant: Option<StreamingVariant> = None;
+
+    for line in body.lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(attributes) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            pending_variant = Some(StreamingVariant {
+                url: Default::default(),
+                bandwidth: hls_attribute(attributes, "BANDWIDTH").and_then(|b| b.parse().ok()),
+                resolution: hls_attribute(attributes, "RESOLUTION"),
+                codecs: hls_attribute(attributes, "CODECS"),
+            });
+        } else if let Some(attributes) = line.strip_prefix("#EXT-X-MEDIA:") {
+            if let Some(uri) = hls_attribute(attributes, "URI") {
+                variants.push(StreamingVariant {
+                    url: resolve(base.as_ref(), &uri),
+                    codecs: hls_attribute(attributes, "CODECS"),
+                    ..Default::default()
+                });
+            }
+        } else if let Some(attributes) = line.strip_prefix("#EXT-X-MAP:") {
+            if let Some(uri) = hls_attribute(attributes, "URI") {
+                segments.push(resolve(base.as_ref(), &uri));
+            }
+        } else if !line.starts_with('#') {
+            let url = resolve(base.as_ref(), line);
+
+            match pending_variant.take() {
+                Some(mut variant) => {
+                    variant.url = url;
+                    variants.push(variant);
+                }
+                _ => segments.push(url),
+            }
+        }
+    }
+
+    StreamingAsset {
+        url: manifest_url.to_string(),
+        kind: StreamingManifestKind::Hls,
+        variants,
+        segments,
+    }
+}
+
+/// Get an attribute value from a single XML start tag.
+fn xml_attribute(tag: &str, name: &str) -> Option<String> {
+    let needle = format!("{name}=");
+    let mut search = tag;
+
+    while let Some(idx) = search.find(&needle) {
+        let boundary = idx == 0 || search.as_bytes()[idx - 1].is_ascii_whitespace();
+        let after = &search[idx + needle.len()..];
+
+        if boundary {
+            let quote = after.chars().next()?;
+            if quote == '"' || quote == '\'' {
+                let value = &after[1..];
+                let end = value.find(quote)?;
+                return Some(value[..end].to_string());
+            }
+        }
+
+        search = after;
+    }
+
+    None
+}
+
+/// Collect the start tags with the given local name.
+fn xml_start_tags<'a>(body: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{name}");
+
+    body.match_indices(open.as_str())
+        .filter_map(|(idx, m)| {
+            let rest = &body[idx + m.len()..];
+            match rest.chars().next() {
+                Some(c) if c.is_ascii_whitespace() || c == '>' || c == '/' => {
+                    rest.find('>').map(|end| &rest[..end])
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parse a DASH MPD. Representations return variants and segment templates are kept unexpanded.
+pub fn parse_dash(manifest_url: &str, body: &str) -> StreamingAsset {
+    let mut base = url::Url::parse(manifest_url).ok();
+
+    if let Some(start) = body.find("<BaseURL>") {
+        let rest = &body[start + "<BaseURL>".len()..];
+        if let Some(end) = rest.find("</BaseURL>") {
+            let base_url = rest[..end].trim();
+            if let Some(b) = base.as_ref().and_then(|b| b.join(base_url).ok()) {
+                base = Some(b);
+            }
+        }
+    }
+
+    let variants = xml_start_tags(body, "Representation")
+        .into_iter()
+        .map(|tag| StreamingVariant {
+            url: xml_attribute(tag, "id").unwrap_or_default(),
+            bandwidth: xml_attribute(tag, "bandwidth").and_then(|b| b.parse().ok()),
+            resolution: match (xml_attribute(tag, "width"), xml_attribute(tag, "height")) {
+                (Some(w), Some(h)) => Some(format!("{w}x{h}")),
+                _ => None,
+            },
+            codecs: xml_attribute(tag, "codecs"),
+        })
+        .collect();
+
+    let mut segments = Vec::new();
+
+    for tag in xml_start_tags(body, "SegmentTemplate") {
+        for attr in ["initialization", "media"] {
+            if let Some(template) = xml_attribute(tag, attr) {
+                segments.push(resolve(base.as_ref(), &template));
+            }
+        }
+    }
+
+    for tag in xml_start_tags(body, "SegmentURL") {
+        if let Some(media) = xml_attribute(tag, "media") {
+            segments.push(resolve(base.as_ref(), &media));
+        }
+    }
+
+    StreamingAsset {
+        url: manifest_url.to_string(),
+        kind: StreamingManifestKind::Dash,
+        variants,
+        segments,
+    }
+}
+
+/// Parse the manifest body for the kind.
+pub fn parse_manifest(
+    manifest_url: &str,
+    kind: StreamingManifestKind,
+    body: &str,
+) -> StreamingAsset {
+    match kind {
+        StreamingManifestKind::Hls => parse_hls(manifest_url, body),
+        StreamingManifestKind::Dash => parse_dash(manifest_url, body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_manifests() {
+        assert_eq!(
+            detect_manifest_kind("https://a.com/live/master.m3u8?token=1", "text/plain"),
+            Some(StreamingManifestKind::Hls)
+        );
+        assert_eq!(
+            detect_manifest_kind("https://a.com/manifest", "application/dash+xml"),
+            Some(StreamingManifestKind::Dash)
+        );
+        assert_eq!(
+            detect_manifest_kind("https://a.com/app.js", "text/javascript"),
+            None
+        );
+    }
+
+    #[test]
+    fn hls_master_playlist() {
+        let body = "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"\nlow/index.m3u8\n#EXT-X-STREAM-INF:BANDWIDTH=2560000\nhttps://cdn.a.com/high.m3u8\n";
+        let asset = parse_hls("https://a.com/live/master.m3u8", body);
+
+        assert_eq!(asset.variants.len(), 2);
+        assert_eq!(asset.variants[0].url, "https://a.com/live/low/index.m3u8");
+        assert_eq!(asset.variants[0].bandwidth, Some(1280000));
+        assert_eq!(asset.variants[0].resolution.as_deref(), Some("640x360"));
+        assert_eq!(
+            asset.variants[0].codecs.as_deref(),
+            Some("avc1.4d401e,mp4a.40.2")
+        );
+        assert_eq!(asset.variants[1].url, "https://cdn.a.com/high.m3u8");
+        assert!(asset.segments.is_empty());
+    }
+
+    #[test]
+    fn dash_templates() {
+        let body = r#"<MPD><Period><AdaptationSet><SegmentTemplate initialization="init-$RepresentationID$.mp4" media="seg-$RepresentationID$-$Number$.m4s"/><Representation id="720p" bandwidth="3000000" width="1280" height="720" codecs="avc1.64001f"/></AdaptationSet></Period></MPD>"#;
+        let asset = parse_dash("https://a.com/v/manifest.mpd", body);
+
+        assert_eq!(asset.variants.len(), 1);
+        assert_eq!(asset.variants[0].resolution.as_deref(), Some("1280x720"));
+        assert_eq!(
+            asset.segments,
+            vec![
+                "https://a.com/v/init-$RepresentationID$.mp4".to_string(),
+                "https://a.com/v/seg-$RepresentationID$-$Number$.m4s".to_string()
+            ]
+        );
+    }
+}
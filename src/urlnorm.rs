@@ -0,0 +1,293 @@
+//! Url normalization and canonicalization, the rules behind the cache site keys and the
+//! canonical urls of the pages: two urls of the same document normalize to the same string.
+//!
+//! Parsing already lowercases the scheme and the host, converts internationalized hosts to
+//! their punycode form, strips the default ports and removes the dot segments of the path.
+//! On top of it the percent-encoding is normalized and the fragments, the tracking parameters
+//! and the empty queries are dropped.
+
+use std::borrow::Cow;
+
+/// The query parameters of the analytics and ad click trackers, compared case insensitively.
+/// Every `utm_*` parameter is a tracking parameter as well.
+pub const TRACKING_PARAMS: &[&str] = &[
+    "__hsfp",
+    "__hssc",
+    "__hstc",
+    "_branch_match_id",
+    "_ga",
+    "_gl",
+    "_hsenc",
+    "_hsmi",
+    "_openstat",
+    "dclid",
+    "fbclid",
+    "gbraid",
+    "gclid",
+    "gclsrc",
+    "hsctatracking",
+    "igshid",
+    "li_fat_id",
+    "mc_cid",
+    "mc_eid",
+    "mkt_tok",
+    "msclkid",
+    "oly_anon_id",
+    "oly_enc_id",
+    "rb_clickid",
+    "s_cid",
+    "srsltid",
+    "ttclid",
+    "twclid",
+    "vero_conv",
+    "vero_id",
+    "wbraid",
+    "wickedid",
+    "yclid",
+];
+
+/// The parameter tracks the visit rather than selects the content.
+pub fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/// How a url is normalized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// Drop the fragment.
+    pub strip_fragment: bool,
+    /// Drop the tracking parameters, see [`TRACKING_PARAMS`].
+    pub strip_tracking_params: bool,
+    /// More parameters to drop, compared case insensitively.
+    pub extra_params: Vec<String>,
+    /// Sort the query parameters by name, keeping the order of the repeated names.
+    pub sort_query: bool,
+    /// Drop the `www.` of the host.
+    pub strip_www: bool,
+    /// Drop the trailing slash of the paths other than `/`.
+    pub strip_trailing_slash: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            strip_fragment: true,
+            strip_tracking_params: true,
+            extra_params: Vec::new(),
+            sort_query: true,
+            strip_www: false,
+            strip_trailing_slash: false,
+        }
+    }
+}
+
+impl NormalizeOptions {
+    /// Only the lossless rules, the url keeps its fragment, parameters and their order.
+    pub fn lossless() -> Self {
+        Self {
+            strip_fragment: false,
+            strip_tracking_params: false,
+            sort_query: false,
+            ..Default::default()
+        }
+    }
+
+    /// The parameter is dropped.
+    fn drops(&self, name: &str) -> bool {
+        (self.strip_tracking_params && is_tracking_param(name))
+            || self
+                .extra_params
+                .iter()
+                .any(|extra| extra.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The unreserved characters, never percent-encoded in a normalized url.
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Decode the percent-encoded unreserved characters and uppercase the hex digits of the other
+/// escapes, e.g. `%7euser%2f` becomes `~user%2F`.
+pub fn normalize_percent_encoding(input: &str) -> Cow<'_, str> {
+    if !input.contains('%') {
+        return Cow::Borrowed(input);
+    }
+
+    let bytes = input.as_bytes();
+    let mut out = String::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = input
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                if is_unreserved(byte) {
+                    out.push(byte as char);
+                } else {
+                    out.push('%');
+                    out.push_str(&input[i + 1..i + 3].to_ascii_uppercase());
+                }
+                i += 3;
+                continue;
+            }
+        }
+
+        let len = input[i..].chars().next().map_or(1, char::len_utf8);
+        out.push_str(&input[i..i + len]);
+        i += len;
+    }
+
+    Cow::Owned(out)
+}
+
+/// The normalized url, `None` when it does not parse.
+pub fn normalize(url: &str, options: &NormalizeOptions) -> Option<String> {
+    let mut url = url::Url::parse(url.trim()).ok()?;
+
+    if options.strip_fragment {
+        url.set_fragment(None);
+    }
+
+    if options.strip_www {
+        if let Some(host) = url.host_str().and_then(|host| host.strip_prefix("www.")) {
+            if host.contains('.') {
+                let host = host.to_string();
+                url.set_host(Some(&host)).ok()?;
+            }
+        }
+    }
+
+    if !url.cannot_be_a_base() {
+        let mut path = normalize_percent_encoding(url.path()).into_owned();
+
+        if options.strip_trailing_slash && path.len() > 1 && path.ends_with('/') {
+            path.pop();
+        }
+
+        url.set_path(&path);
+    }
+
+    if let Some(query) = url.query() {
+        let mut params: Vec<String> = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .filter(|param| {
+                let name = param.split('=').next().unwrap_or_default();
+                !options.drops(&normalize_percent_encoding(name))
+            })
+            .map(|param| normalize_percent_encoding(param).into_owned())
+            .collect();
+
+        if options.sort_query {
+            params.sort_by(|a, b| {
+                let name = |param: &str| param.split('=').next().unwrap_or_default().to_string();
+                name(a).cmp(&name(b))
+            });
+        }
+
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            url.set_query(Some(&params.join("&")));
+        }
+    }
+
+    Some(url.into())
+}
+
+/// The canonical form of the url with the default options, `None` when it does not parse.
+pub fn canonicalize(url: &str) -> Option<String> {
+    normalize(url, &NormalizeOptions::default())
+}
+
+/// The url without its fragment, the url itself when it does not parse.
+pub fn without_fragment(url: &str) -> String {
+    url::Url::parse(url)
+        .map(|mut url| {
+            url.set_fragment(None);
+            url.into()
+        })
+        .unwrap_or_else(|_| url.to_string())
+}
+
+/// The urls are the same document once canonicalized.
+pub fn same_document(a: &str, b: &str) -> bool {
+    match (canonicalize(a), canonicalize(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalizes_the_urls() {
+        assert_eq!(
+            canonicalize(
+                "HTTPS://Example.COM:443/a/./b/../c/%7euser?utm_source=x&b=2&a=1&gclid=z#top"
+            )
+            .as_deref(),
+            Some("https://example.com/a/c/~user?a=1&b=2")
+        );
+        assert_eq!(
+            canonicalize("http://example.com:80?utm_medium=email").as_deref(),
+            Some("http://example.com/")
+        );
+        assert_eq!(
+            canonicalize("https://bücher.example/").as_deref(),
+            Some("https://xn--bcher-kva.example/")
+        );
+        assert_eq!(canonicalize("not a url"), None);
+    }
+
+    #[test]
+    fn keeps_the_order_of_repeated_params() {
+        assert_eq!(
+            canonicalize("https://a.com/?tag=b&id=1&tag=a").as_deref(),
+            Some("https://a.com/?id=1&tag=b&tag=a")
+        );
+        assert_eq!(
+            normalize(
+                "https://a.com/?utm_source=x&z=1#f",
+                &NormalizeOptions::lossless()
+            )
+            .as_deref(),
+            Some("https://a.com/?utm_source=x&z=1#f")
+        );
+    }
+
+    #[test]
+    fn normalizes_the_percent_encoding() {
+        assert_eq!(normalize_percent_encoding("%7e%41%2f%2Fé"), "~A%2F%2Fé");
+        assert_eq!(normalize_percent_encoding("100%"), "100%");
+        assert!(matches!(
+            normalize_percent_encoding("/plain"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn applies_the_optional_rules() {
+        let options = NormalizeOptions {
+            strip_www: true,
+            strip_trailing_slash: true,
+            extra_params: vec!["sessionid".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            normalize("https://www.a.com/docs/?SessionId=1", &options).as_deref(),
+            Some("https://a.com/docs")
+        );
+        assert!(same_document(
+            "https://a.com/p?fbclid=1#x",
+            "https://A.com/p"
+        ));
+    }
+}
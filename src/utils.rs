@@ -1,13 +1,58 @@
 use std::path::{Path, PathBuf};
 
-use chromiumoxide_cdp::cdp::browser_protocol::network::ResourceType;
+use chromiumoxide_cdp::cdp::browser_protocol::network::{Headers, ResourceType};
 
 /// Write to file with configured runtime
 pub(crate) async fn write<P: AsRef<Path> + Unpin, C: AsRef<[u8]>>(
     path: P,
     contents: C,
 ) -> std::io::Result<()> {
-    tokio::fs::write(path.as_ref(), contents.as_ref()).await
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        tokio::fs::write(path.as_ref(), contents.as_ref()).await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        std::fs::write(path.as_ref(), contents.as_ref())
+    }
+}
+
+/// Fetch a resource of the page, e.g. a manifest or an icon, from the local cache or else over
+/// http. Returns the body with the content type, `None` on a failure or an error status.
+pub(crate) async fn fetch_resource(
+    url: &str,
+    timeout: std::time::Duration,
+) -> Option<(Vec<u8>, Option<String>)> {
+    #[cfg(feature = "_cache")]
+    if let Some((body, headers)) =
+        crate::cache::manager::get_cached_url_with_metadata(url, None, None).await
+    {
+        let content_type = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.clone());
+        return Some((body, content_type));
+    }
+
+    let resp = crate::http_client::HTTP_CLIENT
+        .get(url)
+        .timeout(timeout)
+        .send()
+        .await
+        .ok()?;
+
+    if !resp.status().is_success() {
+        return None;
+    }
+
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+
+    Some((resp.bytes().await.ok()?.to_vec(), content_type))
 }
 
 /// Canonicalize path
@@ -15,7 +60,10 @@ pub(crate) async fn write<P: AsRef<Path> + Unpin, C: AsRef<[u8]>>(
 /// Chromium sandboxing does not support Window UNC paths which are used by Rust
 /// when the path is relative. See https://bugs.chromium.org/p/chromium/issues/detail?id=1415018.
 pub(crate) async fn canonicalize<P: AsRef<Path> + Unpin>(path: P) -> std::io::Result<PathBuf> {
+    #[cfg(not(target_arch = "wasm32"))]
     let path = tokio::fs::canonicalize(path.as_ref()).await?;
+    #[cfg(target_arch = "wasm32")]
+    let path = std::fs::canonicalize(path.as_ref())?;
 
     Ok(dunce::simplified(&path).to_path_buf())
 }
@@ -130,6 +178,15 @@ pub fn is_data_resource(resource_type: &ResourceType) -> bool {
     )
 }
 
+/// Get a header value from CDP headers ignoring the case of the name.
+pub fn header_value<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers.inner().as_object().and_then(|obj| {
+        obj.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .and_then(|(_, v)| v.as_str())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
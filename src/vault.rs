@@ -0,0 +1,601 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chromiumoxide_cdp::cdp::browser_protocol::network::{Cookie, SetCookiesParams};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+
+/// The header of a vault file.
+const MAGIC: &[u8; 8] = b"CHRVLT01";
+
+/// The nonce length of ChaCha20-Poly1305.
+const NONCE_LEN: usize = 12;
+
+/// The PBKDF2 rounds deriving a key from a passphrase.
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// Read the local storage of the origin of the page.
+const CAPTURE_STORAGE_JS: &str = r###"(()=>{const o={};try{for(let i=0;i<localStorage.length;i++){const k=localStorage.key(i);o[k]=localStorage.getItem(k)}}catch(e){}return{origin:location.origin,items:o}})()"###;
+
+/// The key encrypting a vault file.
+#[derive(Clone)]
+pub struct VaultKey([u8; 32]);
+
+impl fmt::Debug for VaultKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VaultKey(..)")
+    }
+}
+
+impl VaultKey {
+    /// A raw 256 bit key.
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// Derive the key from a passphrase with PBKDF2-HMAC-SHA256. The salt should be unique to
+    /// the deployment and kept with the vault file.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+        Self(key)
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+}
+
+/// Encrypt the plaintext, `MAGIC | nonce | ciphertext`.
+fn seal(key: &VaultKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let ciphertext = key
+        .cipher()
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| CdpError::msg("failed to encrypt the session vault"))?;
+
+    let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+
+    Ok(sealed)
+}
+
+/// Decrypt a sealed vault, failing on another key or a tampered file.
+fn unseal(key: &VaultKey, sealed: &[u8]) -> Result<Vec<u8>> {
+    let body = sealed
+        .strip_prefix(MAGIC.as_slice())
+        .filter(|body| body.len() >= NONCE_LEN)
+        .ok_or_else(|| CdpError::msg("not a session vault file"))?;
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+
+    key.cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CdpError::msg("failed to decrypt the session vault, wrong key or corrupted"))
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs_f64())
+        .unwrap_or_default()
+}
+
+/// The health of a login session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionHealth {
+    /// The session is usable.
+    #[default]
+    Healthy,
+    /// The login expired, the account must sign in again.
+    Expired,
+    /// The account was banned or suspended.
+    Banned,
+}
+
+impl SessionHealth {
+    /// Classify the response of a page loaded with the session: a `401` or a redirect to a
+    /// login page expired it, a `403` or a suspension page banned it.
+    pub fn from_response(status: i64, final_url: &str) -> Self {
+        let path = url::Url::parse(final_url)
+            .map(|url| url.path().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let banned = ["banned", "suspended", "locked", "disabled"];
+        let login = ["login", "signin", "sign-in", "sign_in", "logon"];
+
+        if status == 403 || banned.iter().any(|marker| path.contains(marker)) {
+            SessionHealth::Banned
+        } else if status == 401 || login.iter().any(|marker| path.contains(marker)) {
+            SessionHealth::Expired
+        } else {
+            SessionHealth::Healthy
+        }
+    }
+
+    /// The session can be checked out.
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, SessionHealth::Healthy)
+    }
+}
+
+/// A named login session: the cookies and local storage of an account and the fingerprint it
+/// was used with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredSession {
+    /// The name of the session, e.g. the account.
+    pub name: String,
+    /// The cookies of the session.
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    /// The local storage items per origin.
+    #[serde(default)]
+    pub local_storage: BTreeMap<String, BTreeMap<String, String>>,
+    /// The name of the fingerprint the session is paired with, so the account always shows the
+    /// same browser.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// The health of the session.
+    #[serde(default)]
+    pub health: SessionHealth,
+    /// When the session was last checked in, in seconds since the epoch.
+    #[serde(default)]
+    pub last_used: f64,
+}
+
+#[derive(Deserialize)]
+struct CapturedStorage {
+    origin: String,
+    items: BTreeMap<String, String>,
+}
+
+impl StoredSession {
+    /// An empty session.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            cookies: Vec::new(),
+            local_storage: BTreeMap::new(),
+            fingerprint: None,
+            health: SessionHealth::Healthy,
+            last_used: 0.0,
+        }
+    }
+
+    /// Pair the session with the fingerprint.
+    pub fn with_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.fingerprint = Some(fingerprint.into());
+        self
+    }
+
+    /// Every persistent cookie of the session expired.
+    pub fn is_expired(&self, now: f64) -> bool {
+        let mut persistent = self
+            .cookies
+            .iter()
+            .filter(|cookie| !cookie.session)
+            .peekable();
+
+        persistent.peek().is_some() && persistent.all(|cookie| cookie.expires <= now)
+    }
+
+    /// Restore the cookies on the page and seed the local storage of every new document of a
+    /// stored origin. Call before navigating.
+    pub async fn apply(&self, page: &Page) -> Result<()> {
+        if !self.cookies.is_empty() {
+            page.execute(SetCookiesParams::new(
+                self.cookies
+                    .iter()
+                    .map(crate::rotation::cookie_param)
+                    .collect::<Vec<_>>(),
+            ))
+            .await?;
+        }
+
+        if !self.local_storage.is_empty() {
+            let items = serde_json::to_string(&self.local_storage)?;
+            let source = format!(
+                r#"(()=>{{const s=({items})[location.origin];if(!s)return;try{{for(const k in s){{if(localStorage.getItem(k)===null)localStorage.setItem(k,s[k])}}}}catch(e){{}}}})()"#
+            );
+            page.add_script_to_evaluate_on_new_document(Some(source))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Capture the cookies and the local storage of the current origin of the page.
+    pub async fn capture(&mut self, page: &Page) -> Result<()> {
+        self.cookies = page.get_cookies().await?;
+
+        let storage: CapturedStorage = page
+            .evaluate_isolated(CAPTURE_STORAGE_JS)
+            .await?
+            .into_value()?;
+
+        // opaque origins, e.g. `about:blank`, have no storage to keep.
+        if storage.origin != "null" {
+            if storage.items.is_empty() {
+                self.local_storage.remove(&storage.origin);
+            } else {
+                self.local_storage.insert(storage.origin, storage.items);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The sessions of a vault and the ones checked out.
+#[derive(Debug, Default)]
+struct VaultState {
+    sessions: BTreeMap<String, StoredSession>,
+    checked_out: HashSet<String>,
+}
+
+#[derive(Debug)]
+struct VaultInner {
+    path: PathBuf,
+    key: Mutex<VaultKey>,
+    state: Mutex<VaultState>,
+}
+
+/// Named login sessions stored encrypted on disk with ChaCha20-Poly1305.
+///
+/// Workers check a session out, use it on a page and check it back in with its updated cookies,
+/// a session is held by one worker at a time. The health reported for a session (expired or
+/// banned) keeps it from being checked out until it is replaced. Every change is written to the
+/// file right away.
+#[derive(Debug, Clone)]
+pub struct SessionVault {
+    inner: Arc<VaultInner>,
+}
+
+impl SessionVault {
+    /// Open the vault file, creating an empty vault when it does not exist.
+    pub fn open(path: impl AsRef<Path>, key: VaultKey) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let sessions = match std::fs::read(&path) {
+            Ok(sealed) => serde_json::from_slice(&unseal(&key, &sealed)?)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            inner: Arc::new(VaultInner {
+                path,
+                key: Mutex::new(key),
+                state: Mutex::new(VaultState {
+                    sessions,
+                    checked_out: HashSet::new(),
+                }),
+            }),
+        })
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, VaultState> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write the sessions to the file, replacing it atomically.
+    fn persist(&self, sessions: &BTreeMap<String, StoredSession>) -> Result<()> {
+        let key = self.inner.key.lock().unwrap_or_else(|e| e.into_inner());
+        let sealed = seal(&key, &serde_json::to_vec(sessions)?)?;
+
+        if let Some(dir) = self
+            .inner
+            .path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let tmp = self.inner.path.with_extension("tmp");
+        std::fs::write(&tmp, sealed)?;
+        std::fs::rename(&tmp, &self.inner.path)?;
+
+        Ok(())
+    }
+
+    /// Add or replace a session.
+    pub fn insert(&self, session: StoredSession) -> Result<()> {
+        let mut state = self.state();
+        state.sessions.insert(session.name.clone(), session);
+        self.persist(&state.sessions)
+    }
+
+    /// Remove a session.
+    pub fn remove(&self, name: &str) -> Result<Option<StoredSession>> {
+        let mut state = self.state();
+        let removed = state.sessions.remove(name);
+
+        if removed.is_some() {
+            self.persist(&state.sessions)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// The names of the sessions.
+    pub fn names(&self) -> Vec<String> {
+        self.state().sessions.keys().cloned().collect()
+    }
+
+    /// A copy of the session.
+    pub fn get(&self, name: &str) -> Option<StoredSession> {
+        self.state().sessions.get(name).cloned()
+    }
+
+    /// Check out the session of the name, failing when it is missing, unhealthy or held by
+    /// another worker.
+    pub fn checkout(&self, name: &str) -> Result<SessionLease> {
+        let mut state = self.state();
+
+        let session = state
+            .sessions
+            .get(name)
+            .ok_or_else(|| CdpError::msg(format!("no session named {name}")))?;
+
+        if !session.health.is_healthy() {
+            return Err(CdpError::msg(format!(
+                "the session {name} is {:?}",
+                session.health
+            )));
+        }
+
+        let session = session.clone();
+
+        if !state.checked_out.insert(name.to_string()) {
+            return Err(CdpError::msg(format!("the session {name} is checked out")));
+        }
+
+        Ok(SessionLease::new(self.clone(), session))
+    }
+
+    /// Check out the healthy session unused for the longest, rotating the accounts. Sessions
+    /// whose cookies all expired are marked expired on the way.
+    pub fn checkout_any(&self) -> Result<SessionLease> {
+        let mut state = self.state();
+        let now = unix_now();
+        let mut expired = false;
+
+        for session in state.sessions.values_mut() {
+            if session.health.is_healthy() && session.is_expired(now) {
+                session.health = SessionHealth::Expired;
+                expired = true;
+            }
+        }
+
+        if expired {
+            self.persist(&state.sessions)?;
+        }
+
+        let session = state
+            .sessions
+            .values()
+            .filter(|session| {
+                session.health.is_healthy() && !state.checked_out.contains(&session.name)
+            })
+            .min_by(|a, b| a.last_used.total_cmp(&b.last_used))
+            .cloned()
+            .ok_or_else(|| CdpError::msg("no healthy session is available"))?;
+
+        state.checked_out.insert(session.name.clone());
+
+        Ok(SessionLease::new(self.clone(), session))
+    }
+
+    /// Record the health of a session, e.g. after a login wall or a ban page.
+    pub fn report(&self, name: &str, health: SessionHealth) -> Result<()> {
+        let mut state = self.state();
+
+        match state.sessions.get_mut(name) {
+            Some(session) => session.health = health,
+            None => return Err(CdpError::msg(format!("no session named {name}"))),
+        }
+
+        self.persist(&state.sessions)
+    }
+
+    /// Re-encrypt the vault with a new key.
+    pub fn rotate_key(&self, key: VaultKey) -> Result<()> {
+        let state = self.state();
+        let previous = std::mem::replace(
+            &mut *self.inner.key.lock().unwrap_or_else(|e| e.into_inner()),
+            key,
+        );
+
+        if let Err(err) = self.persist(&state.sessions) {
+            *self.inner.key.lock().unwrap_or_else(|e| e.into_inner()) = previous;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn release(&self, name: &str) {
+        self.state().checked_out.remove(name);
+    }
+
+    fn checkin(&self, mut session: StoredSession) -> Result<()> {
+        let mut state = self.state();
+        state.checked_out.remove(&session.name);
+
+        // a session removed while checked out stays removed.
+        if !state.sessions.contains_key(&session.name) {
+            return Ok(());
+        }
+
+        session.last_used = unix_now();
+        state.sessions.insert(session.name.clone(), session);
+
+        self.persist(&state.sessions)
+    }
+}
+
+/// A session checked out of a [`SessionVault`]. Check it in to store its changes, dropping it
+/// releases the session unchanged.
+#[derive(Debug)]
+pub struct SessionLease {
+    vault: SessionVault,
+    session: Option<StoredSession>,
+}
+
+impl SessionLease {
+    fn new(vault: SessionVault, session: StoredSession) -> Self {
+        Self {
+            vault,
+            session: Some(session),
+        }
+    }
+
+    /// The session.
+    pub fn session(&self) -> &StoredSession {
+        self.session.as_ref().expect("the lease holds its session")
+    }
+
+    /// The session, to update before the checkin.
+    pub fn session_mut(&mut self) -> &mut StoredSession {
+        self.session.as_mut().expect("the lease holds its session")
+    }
+
+    /// Restore the session on the page, see [`StoredSession::apply`].
+    pub async fn apply(&self, page: &Page) -> Result<()> {
+        self.session().apply(page).await
+    }
+
+    /// Capture the cookies and storage of the page into the session.
+    pub async fn capture(&mut self, page: &Page) -> Result<()> {
+        self.session_mut().capture(page).await
+    }
+
+    /// Record the health of the session, stored with the checkin.
+    pub fn mark(&mut self, health: SessionHealth) {
+        self.session_mut().health = health;
+    }
+
+    /// Store the session in the vault and release it.
+    pub fn checkin(mut self) -> Result<()> {
+        match self.session.take() {
+            Some(session) => self.vault.checkin(session),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SessionLease {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            self.vault.release(&session.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "chromiumoxide-vault-{}-{}",
+            name,
+            rand::random::<u64>()
+        ));
+        dir.join("sessions.vault")
+    }
+
+    fn key(byte: u8) -> VaultKey {
+        VaultKey::new([byte; 32])
+    }
+
+    #[test]
+    fn seals_with_the_key() {
+        let sealed = seal(&key(1), b"sessions").unwrap();
+
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(unseal(&key(1), &sealed).unwrap(), b"sessions");
+        assert!(unseal(&key(2), &sealed).is_err());
+        assert!(unseal(&key(1), b"CHRVLT01short").is_err());
+    }
+
+    #[test]
+    fn classifies_the_health() {
+        assert_eq!(
+            SessionHealth::from_response(200, "https://example.com/feed"),
+            SessionHealth::Healthy
+        );
+        assert_eq!(
+            SessionHealth::from_response(200, "https://example.com/accounts/login?next=/"),
+            SessionHealth::Expired
+        );
+        assert_eq!(
+            SessionHealth::from_response(401, "https://example.com/api"),
+            SessionHealth::Expired
+        );
+        assert_eq!(
+            SessionHealth::from_response(200, "https://example.com/account/suspended"),
+            SessionHealth::Banned
+        );
+    }
+
+    #[test]
+    fn checks_sessions_out_once() {
+        let path = vault_path("checkout");
+        let vault = SessionVault::open(&path, key(1)).unwrap();
+        vault
+            .insert(StoredSession::new("alice").with_fingerprint("desktop"))
+            .unwrap();
+        vault.insert(StoredSession::new("bob")).unwrap();
+
+        let alice = vault.checkout("alice").unwrap();
+        assert!(vault.checkout("alice").is_err());
+        assert_eq!(alice.session().fingerprint.as_deref(), Some("desktop"));
+
+        // the next unused session rotates to bob.
+        let bob = vault.checkout_any().unwrap();
+        assert_eq!(bob.session().name, "bob");
+        assert!(vault.checkout_any().is_err());
+
+        drop(bob);
+        let mut alice = alice;
+        alice.mark(SessionHealth::Banned);
+        alice.checkin().unwrap();
+
+        assert_eq!(vault.checkout_any().unwrap().session().name, "bob");
+        assert!(vault.checkout("alice").is_err());
+
+        let reopened = SessionVault::open(&path, key(1)).unwrap();
+        assert_eq!(reopened.names(), vec!["alice", "bob"]);
+        assert_eq!(reopened.get("alice").unwrap().health, SessionHealth::Banned);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn rotates_the_key() {
+        let path = vault_path("rotate");
+        let vault = SessionVault::open(&path, key(1)).unwrap();
+        vault.insert(StoredSession::new("alice")).unwrap();
+
+        vault.rotate_key(key(2)).unwrap();
+
+        assert!(SessionVault::open(&path, key(1)).is_err());
+        assert_eq!(
+            SessionVault::open(&path, key(2)).unwrap().names(),
+            vec!["alice"]
+        );
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,414 @@
+//! Visual change detection between two screenshots: a perceptual hash telling whether the page
+//! looks different at all and a pixel diff locating the changed regions, with a diff image
+//! highlighting them.
+
+use std::io::Cursor;
+
+use crate::error::{CdpError, Result};
+
+/// How two screenshots are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// The max difference of a color channel of a pixel still considered unchanged, absorbing
+    /// e.g. anti aliasing and compression noise.
+    pub threshold: u8,
+    /// The size in pixels of the cells the changed pixels are grouped in to build the regions.
+    pub block_size: u32,
+    /// The changed pixels a cell needs to be part of a region.
+    pub min_block_pixels: u32,
+    /// Pause the animations, transitions and the caret before the screenshot so unchanged pages
+    /// render the same pixels, see `Page::visual_diff`.
+    pub deterministic: bool,
+    /// The RGBA color of the changed pixels in the diff image.
+    pub highlight: [u8; 4],
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 32,
+            block_size: 16,
+            min_block_pixels: 4,
+            deterministic: true,
+            highlight: [255, 0, 0, 255],
+        }
+    }
+}
+
+/// A rectangle of changed pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ChangedRegion {
+    /// The left of the region.
+    pub x: u32,
+    /// The top of the region.
+    pub y: u32,
+    /// The width of the region.
+    pub width: u32,
+    /// The height of the region.
+    pub height: u32,
+    /// The changed pixels within the region.
+    pub changed_pixels: u64,
+}
+
+/// The changes between two screenshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisualDiff {
+    /// The width of the compared area, the larger of both screenshots.
+    pub width: u32,
+    /// The height of the compared area, the larger of both screenshots.
+    pub height: u32,
+    /// The changed pixels, pixels only in one of the screenshots are changed.
+    pub changed_pixels: u64,
+    /// The hamming distance of the perceptual hashes, 0 for similar looking screenshots.
+    pub perceptual_distance: u32,
+    /// The changed regions, top to bottom.
+    pub regions: Vec<ChangedRegion>,
+    /// A png of the current screenshot faded with the changed pixels highlighted.
+    pub diff_image: Vec<u8>,
+}
+
+impl VisualDiff {
+    /// The share of changed pixels, between 0 and 1.
+    pub fn changed_ratio(&self) -> f64 {
+        let total = self.width as u64 * self.height as u64;
+
+        if total == 0 {
+            0.0
+        } else {
+            self.changed_pixels as f64 / total as f64
+        }
+    }
+
+    /// Any region changed.
+    pub fn is_changed(&self) -> bool {
+        !self.regions.is_empty()
+    }
+}
+
+/// A decoded RGBA image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rgba {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Rgba {
+    fn pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        if x < self.width && y < self.height {
+            let at = ((y * self.width + x) * 4) as usize;
+            self.pixels.get(at..at + 4)
+        } else {
+            None
+        }
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Rgba> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| CdpError::msg(format!("invalid png: {err}")))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|err| CdpError::msg(format!("invalid png: {err}")))?;
+    let buf = &buf[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buf.to_vec(),
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|p| [*p, *p, *p, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(CdpError::msg("invalid png: unexpanded palette"));
+        }
+    };
+
+    Ok(Rgba {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn encode_png(image: &Rgba) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.pixels))
+        .map_err(|err| CdpError::msg(format!("failed to encode the diff image: {err}")))?;
+
+    Ok(out)
+}
+
+fn luma(pixel: &[u8]) -> f64 {
+    0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64
+}
+
+/// The difference hash of the image: the brightness gradients of a 9x8 thumbnail.
+fn dhash(image: &Rgba) -> u64 {
+    const W: u32 = 9;
+    const H: u32 = 8;
+
+    let mut thumb = [0f64; (W * H) as usize];
+
+    for ty in 0..H {
+        for tx in 0..W {
+            let x0 = tx * image.width / W;
+            let x1 = ((tx + 1) * image.width / W).max(x0 + 1).min(image.width);
+            let y0 = ty * image.height / H;
+            let y1 = ((ty + 1) * image.height / H).max(y0 + 1).min(image.height);
+            let (mut sum, mut count) = (0.0, 0.0);
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    if let Some(pixel) = image.pixel(x, y) {
+                        sum += luma(pixel);
+                        count += 1.0;
+                    }
+                }
+            }
+
+            if count > 0.0 {
+                thumb[(ty * W + tx) as usize] = sum / count;
+            }
+        }
+    }
+
+    let mut hash = 0u64;
+
+    for y in 0..H {
+        for x in 0..W - 1 {
+            hash <<= 1;
+            if thumb[(y * W + x) as usize] < thumb[(y * W + x + 1) as usize] {
+                hash |= 1;
+            }
+        }
+    }
+
+    hash
+}
+
+/// Compare two png screenshots.
+pub fn diff_png(baseline: &[u8], current: &[u8], options: &DiffOptions) -> Result<VisualDiff> {
+    let baseline = decode_png(baseline)?;
+    let current = decode_png(current)?;
+
+    let width = baseline.width.max(current.width);
+    let height = baseline.height.max(current.height);
+    let block = options.block_size.max(1);
+    let (cols, rows) = ((width + block - 1) / block, (height + block - 1) / block);
+
+    let mut blocks = vec![0u32; (cols * rows) as usize];
+    let mut diff = Rgba {
+        width,
+        height,
+        pixels: vec![0; (width * height * 4) as usize],
+    };
+    let mut changed_pixels = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let at = ((y * width + x) * 4) as usize;
+
+            let changed = match (baseline.pixel(x, y), current.pixel(x, y)) {
+                (Some(a), Some(b)) => a
+                    .iter()
+                    .zip(b)
+                    .any(|(a, b)| a.abs_diff(*b) > options.threshold),
+                _ => true,
+            };
+
+            if changed {
+                changed_pixels += 1;
+                blocks[((y / block) * cols + x / block) as usize] += 1;
+                diff.pixels[at..at + 4].copy_from_slice(&options.highlight);
+            } else if let Some(pixel) = current.pixel(x, y) {
+                // fade the unchanged pixels so the changes stand out.
+                let faded = (255.0 - (255.0 - luma(pixel)) * 0.25) as u8;
+                diff.pixels[at..at + 4].copy_from_slice(&[faded, faded, faded, 255]);
+            }
+        }
+    }
+
+    Ok(VisualDiff {
+        width,
+        height,
+        changed_pixels,
+        perceptual_distance: (dhash(&baseline) ^ dhash(&current)).count_ones(),
+        regions: regions(
+            &blocks,
+            cols,
+            rows,
+            block,
+            width,
+            height,
+            options.min_block_pixels,
+        ),
+        diff_image: encode_png(&diff)?,
+    })
+}
+
+/// Merge the neighbouring cells with changes into regions.
+fn regions(
+    blocks: &[u32],
+    cols: u32,
+    rows: u32,
+    block: u32,
+    width: u32,
+    height: u32,
+    min_block_pixels: u32,
+) -> Vec<ChangedRegion> {
+    let changed = |i: usize| blocks[i] >= min_block_pixels.max(1);
+    let mut seen = vec![false; blocks.len()];
+    let mut regions = Vec::new();
+
+    for start in 0..blocks.len() {
+        if seen[start] || !changed(start) {
+            continue;
+        }
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (cols, rows, 0, 0);
+        let mut changed_pixels = 0u64;
+        let mut stack = vec![start];
+        seen[start] = true;
+
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i as u32 % cols, i as u32 / cols);
+
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+            changed_pixels += blocks[i] as u64;
+
+            let mut neighbours = Vec::with_capacity(4);
+            if x > 0 {
+                neighbours.push(i - 1);
+            }
+            if x + 1 < cols {
+                neighbours.push(i + 1);
+            }
+            if y > 0 {
+                neighbours.push(i - cols as usize);
+            }
+            if y + 1 < rows {
+                neighbours.push(i + cols as usize);
+            }
+
+            for n in neighbours {
+                if !seen[n] && changed(n) {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+
+        let (x, y) = (min_x * block, min_y * block);
+
+        regions.push(ChangedRegion {
+            x,
+            y,
+            width: ((max_x + 1) * block).min(width) - x,
+            height: ((max_y + 1) * block).min(height) - y,
+            changed_pixels,
+        });
+    }
+
+    regions.sort_by_key(|region| (region.y, region.x));
+    regions
+}
+
+/// Style pausing what renders differently between two screenshots of the same page.
+pub(crate) const DETERMINISTIC_RENDERING_JS: &str = r#"(() => {
+  const style = document.createElement('style');
+  style.setAttribute('data-chromey-deterministic', '');
+  style.textContent = '*, *::before, *::after { animation-play-state: paused !important; transition: none !important; caret-color: transparent !important; scroll-behavior: auto !important; }';
+  (document.head || document.documentElement).appendChild(style);
+  document.getAnimations && document.getAnimations().forEach((a) => { try { a.pause(); a.currentTime = 0; } catch (e) {} });
+  return true;
+})()"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32, paint: impl Fn(u32, u32) -> [u8; 4]) -> Vec<u8> {
+        let mut pixels = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&paint(x, y));
+            }
+        }
+
+        encode_png(&Rgba {
+            width,
+            height,
+            pixels,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn identical_screenshots_have_no_changes() {
+        let image = png(32, 32, |x, _| [x as u8 * 8, 0, 0, 255]);
+        let diff = diff_png(&image, &image, &DiffOptions::default()).unwrap();
+
+        assert!(!diff.is_changed());
+        assert_eq!(diff.changed_pixels, 0);
+        assert_eq!(diff.perceptual_distance, 0);
+        assert_eq!(decode_png(&diff.diff_image).unwrap().width, 32);
+    }
+
+    #[test]
+    fn locates_changed_regions() {
+        let baseline = png(64, 64, |_, _| [255, 255, 255, 255]);
+        let current = png(64, 64, |x, y| {
+            if (40..50).contains(&x) && (8..12).contains(&y) {
+                [0, 0, 0, 255]
+            } else {
+                [250, 250, 250, 255]
+            }
+        });
+
+        let diff = diff_png(&baseline, &current, &DiffOptions::default()).unwrap();
+
+        assert_eq!(diff.changed_pixels, 40);
+        assert_eq!(
+            diff.regions,
+            vec![ChangedRegion {
+                x: 32,
+                y: 0,
+                width: 32,
+                height: 16,
+                changed_pixels: 40
+            }]
+        );
+    }
+
+    #[test]
+    fn grown_pages_are_changed() {
+        let baseline = png(16, 16, |_, _| [0, 0, 0, 255]);
+        let current = png(16, 32, |_, _| [0, 0, 0, 255]);
+
+        let diff = diff_png(&baseline, &current, &DiffOptions::default()).unwrap();
+
+        assert_eq!((diff.width, diff.height), (16, 32));
+        assert_eq!(diff.changed_pixels, 16 * 16);
+        assert!((diff.changed_ratio() - 0.5).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,511 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent,
+};
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, StreamExt};
+
+use crate::error::{CdpError, Result};
+use crate::page::Page;
+use crate::runtime::Instant;
+use crate::runtime::JoinHandle;
+
+/// The max wait of [`Page::wait_for`].
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the conditions are checked.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A readiness condition of a page, checked until it holds. Conditions compose with [`all`],
+/// [`any`], [`not`] and [`WaitConditionExt`].
+pub trait WaitCondition: Send {
+    /// The condition holds now. Stateful conditions, e.g. [`network_idle`], start observing the
+    /// page on the first check.
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>>;
+
+    /// The condition in words, for the timeout errors.
+    fn describe(&self) -> String;
+}
+
+impl WaitCondition for Box<dyn WaitCondition> {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        (**self).check(page)
+    }
+
+    fn describe(&self) -> String {
+        (**self).describe()
+    }
+}
+
+/// An element matches the selector.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    selector: String,
+    visible: bool,
+}
+
+/// An element matches the selector.
+pub fn selector(selector: impl Into<String>) -> Selector {
+    Selector {
+        selector: selector.into(),
+        visible: false,
+    }
+}
+
+/// A rendered element with a size matches the selector.
+pub fn visible(selector: impl Into<String>) -> Selector {
+    Selector {
+        selector: selector.into(),
+        visible: true,
+    }
+}
+
+impl WaitCondition for Selector {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            let selector = serde_json::to_string(&self.selector)?;
+            let expression = if self.visible {
+                format!(
+                    r###"(()=>{{const e=document.querySelector({selector});if(!e)return false;const r=e.getBoundingClientRect();return r.width>0&&r.height>0&&getComputedStyle(e).visibility!=='hidden'}})()"###
+                )
+            } else {
+                format!("!!document.querySelector({selector})")
+            };
+
+            Ok(page
+                .evaluate_isolated(expression)
+                .await?
+                .into_value()
+                .unwrap_or_default())
+        }
+        .boxed()
+    }
+
+    fn describe(&self) -> String {
+        match self.visible {
+            true => format!("visible({:?})", self.selector),
+            _ => format!("selector({:?})", self.selector),
+        }
+    }
+}
+
+/// The url of the page contains the pattern, `*` matching any text.
+#[derive(Debug, Clone)]
+pub struct UrlMatches {
+    pattern: String,
+}
+
+/// The url of the page contains the pattern, `*` matching any text.
+pub fn url_matches(pattern: impl Into<String>) -> UrlMatches {
+    UrlMatches {
+        pattern: pattern.into(),
+    }
+}
+
+/// The text contains the parts of the pattern between the `*`, in order.
+fn matches_pattern(text: &str, pattern: &str) -> bool {
+    let mut rest = text;
+
+    for part in pattern.split('*').filter(|part| !part.is_empty()) {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+impl WaitCondition for UrlMatches {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            Ok(page
+                .url()
+                .await?
+                .map_or(false, |url| matches_pattern(&url, &self.pattern)))
+        }
+        .boxed()
+    }
+
+    fn describe(&self) -> String {
+        format!("url_matches({:?})", self.pattern)
+    }
+}
+
+/// The expression is truthy.
+#[derive(Debug, Clone)]
+pub struct JsCondition {
+    expression: String,
+}
+
+/// The expression evaluated in the page is truthy.
+pub fn js(expression: impl Into<String>) -> JsCondition {
+    JsCondition {
+        expression: expression.into(),
+    }
+}
+
+impl WaitCondition for JsCondition {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            Ok(page
+                .evaluate_isolated(format!("!!({})", self.expression))
+                .await?
+                .into_value()
+                .unwrap_or_default())
+        }
+        .boxed()
+    }
+
+    fn describe(&self) -> String {
+        format!("js({:?})", self.expression)
+    }
+}
+
+/// The requests in flight and the time of the last network activity.
+#[derive(Debug)]
+struct IdleState {
+    inflight: HashSet<String>,
+    last_activity: Instant,
+}
+
+/// The network of the page is quiet, see [`network_idle`].
+#[derive(Debug)]
+pub struct NetworkIdle {
+    quiet: Duration,
+    state: Option<(Arc<Mutex<IdleState>>, JoinHandle<()>)>,
+}
+
+/// No request of the page is in flight and none started or ended for the quiet milliseconds.
+/// The requests are observed from the first check, the requests started before are ignored.
+pub fn network_idle(quiet_ms: u64) -> NetworkIdle {
+    NetworkIdle {
+        quiet: Duration::from_millis(quiet_ms),
+        state: None,
+    }
+}
+
+/// A network event of the idle observer.
+enum NetworkActivity {
+    Started(String),
+    Ended(String),
+}
+
+impl NetworkIdle {
+    async fn observe(page: &Page) -> Result<(Arc<Mutex<IdleState>>, JoinHandle<()>)> {
+        let started = page
+            .event_listener::<EventRequestWillBeSent>()
+            .await?
+            .map(|event| NetworkActivity::Started(event.request_id.as_ref().to_string()));
+        let finished = page
+            .event_listener::<EventLoadingFinished>()
+            .await?
+            .map(|event| NetworkActivity::Ended(event.request_id.as_ref().to_string()));
+        let failed = page
+            .event_listener::<EventLoadingFailed>()
+            .await?
+            .map(|event| NetworkActivity::Ended(event.request_id.as_ref().to_string()));
+
+        let state = Arc::new(Mutex::new(IdleState {
+            inflight: HashSet::new(),
+            last_activity: Instant::now(),
+        }));
+        let mut events = stream::select(started, stream::select(finished, failed));
+
+        let handle = {
+            let state = state.clone();
+
+            crate::runtime::spawn(async move {
+                while let Some(activity) = events.next().await {
+                    if let Ok(mut state) = state.lock() {
+                        match activity {
+                            NetworkActivity::Started(id) => state.inflight.insert(id),
+                            NetworkActivity::Ended(id) => state.inflight.remove(&id),
+                        };
+                        state.last_activity = Instant::now();
+                    }
+                }
+            })
+        };
+
+        Ok((state, handle))
+    }
+}
+
+impl WaitCondition for NetworkIdle {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            if self.state.is_none() {
+                self.state = Some(Self::observe(page).await?);
+            }
+
+            Ok(self
+                .state
+                .as_ref()
+                .and_then(|(state, _)| state.lock().ok())
+                .map_or(false, |state| {
+                    state.inflight.is_empty() && state.last_activity.elapsed() >= self.quiet
+                }))
+        }
+        .boxed()
+    }
+
+    fn describe(&self) -> String {
+        format!("network_idle({})", self.quiet.as_millis())
+    }
+}
+
+impl Drop for NetworkIdle {
+    fn drop(&mut self) {
+        if let Some((_, handle)) = self.state.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// Conditions combined by [`all`] and [`any`]: a tuple of conditions or a vector of boxed ones.
+pub trait IntoConditions {
+    /// The boxed conditions.
+    fn into_conditions(self) -> Vec<Box<dyn WaitCondition>>;
+}
+
+impl IntoConditions for Vec<Box<dyn WaitCondition>> {
+    fn into_conditions(self) -> Vec<Box<dyn WaitCondition>> {
+        self
+    }
+}
+
+macro_rules! tuple_conditions {
+    ($($name:ident),+) => {
+        impl<$($name: WaitCondition + 'static),+> IntoConditions for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn into_conditions(self) -> Vec<Box<dyn WaitCondition>> {
+                let ($($name,)+) = self;
+                vec![$(Box::new($name)),+]
+            }
+        }
+    };
+}
+
+tuple_conditions!(A);
+tuple_conditions!(A, B);
+tuple_conditions!(A, B, C);
+tuple_conditions!(A, B, C, D);
+tuple_conditions!(A, B, C, D, E);
+tuple_conditions!(A, B, C, D, E, F);
+
+/// Every condition holds at the same check, or any of them.
+pub struct Combined {
+    conditions: Vec<Box<dyn WaitCondition>>,
+    every: bool,
+}
+
+/// Every condition holds at the same check, e.g.
+/// `all((selector(".price"), network_idle(500), url_matches("/product/")))`.
+pub fn all(conditions: impl IntoConditions) -> Combined {
+    Combined {
+        conditions: conditions.into_conditions(),
+        every: true,
+    }
+}
+
+/// Any of the conditions holds.
+pub fn any(conditions: impl IntoConditions) -> Combined {
+    Combined {
+        conditions: conditions.into_conditions(),
+        every: false,
+    }
+}
+
+impl WaitCondition for Combined {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            let mut holds = Vec::with_capacity(self.conditions.len());
+
+            // every condition is checked, so the stateful ones observe the page from the start.
+            for condition in self.conditions.iter_mut() {
+                holds.push(condition.check(page).await?);
+            }
+
+            Ok(match self.every {
+                true => holds.iter().all(|holds| *holds),
+                _ => holds.iter().any(|holds| *holds),
+            })
+        }
+        .boxed()
+    }
+
+    fn describe(&self) -> String {
+        let conditions: Vec<String> = self.conditions.iter().map(|c| c.describe()).collect();
+
+        match self.every {
+            true => format!("all({})", conditions.join(", ")),
+            _ => format!("any({})", conditions.join(", ")),
+        }
+    }
+}
+
+/// The condition does not hold.
+pub struct Not<C>(C);
+
+/// The condition does not hold, e.g. a spinner is gone.
+pub fn not<C: WaitCondition>(condition: C) -> Not<C> {
+    Not(condition)
+}
+
+impl<C: WaitCondition> WaitCondition for Not<C> {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        async move { Ok(!self.0.check(page).await?) }.boxed()
+    }
+
+    fn describe(&self) -> String {
+        format!("not({})", self.0.describe())
+    }
+}
+
+/// The condition holds within the timeout, see [`WaitConditionExt::within`].
+pub struct Within<C> {
+    condition: C,
+    timeout: Duration,
+    started: Option<Instant>,
+}
+
+impl<C: WaitCondition> WaitCondition for Within<C> {
+    fn check<'a>(&'a mut self, page: &'a Page) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            let started = *self.started.get_or_insert_with(Instant::now);
+
+            if self.condition.check(page).await? {
+                return Ok(true);
+            }
+
+            if started.elapsed() >= self.timeout {
+                return Err(CdpError::msg(format!(
+                    "timed out after {:?} waiting for {}",
+                    self.timeout,
+                    self.condition.describe()
+                )));
+            }
+
+            Ok(false)
+        }
+        .boxed()
+    }
+
+    fn describe(&self) -> String {
+        format!("{}.within({:?})", self.condition.describe(), self.timeout)
+    }
+}
+
+/// The combinators of the conditions.
+pub trait WaitConditionExt: WaitCondition + Sized + 'static {
+    /// This and the other condition hold.
+    fn and(self, other: impl WaitCondition + 'static) -> Combined {
+        all((self, other))
+    }
+
+    /// This or the other condition holds.
+    fn or(self, other: impl WaitCondition + 'static) -> Combined {
+        any((self, other))
+    }
+
+    /// Fail the wait when the condition does not hold within the timeout of its first check.
+    fn within(self, timeout: Duration) -> Within<Self> {
+        Within {
+            condition: self,
+            timeout,
+            started: None,
+        }
+    }
+
+    /// Box the condition, e.g. to collect conditions of different types.
+    fn boxed(self) -> Box<dyn WaitCondition> {
+        Box::new(self)
+    }
+}
+
+impl<C: WaitCondition + 'static> WaitConditionExt for C {}
+
+impl Page {
+    /// Wait until the condition holds, for up to 30 seconds.
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// use chromiumoxide::wait::{all, network_idle, selector, url_matches};
+    /// # async fn demo(page: Page) -> chromiumoxide::error::Result<()> {
+    /// page.wait_for(all((
+    ///     selector(".price"),
+    ///     network_idle(500),
+    ///     url_matches("/product/"),
+    /// )))
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for(&self, condition: impl WaitCondition) -> Result<&Self> {
+        self.wait_for_with_timeout(condition, DEFAULT_WAIT_TIMEOUT)
+            .await
+    }
+
+    /// Wait until the condition holds, failing once the timeout elapsed.
+    pub async fn wait_for_with_timeout(
+        &self,
+        mut condition: impl WaitCondition,
+        timeout: Duration,
+    ) -> Result<&Self> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if condition.check(self).await? {
+                return Ok(self);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(CdpError::msg(format!(
+                    "timed out after {timeout:?} waiting for {}",
+                    condition.describe()
+                )));
+            }
+
+            crate::runtime::sleep(DEFAULT_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_url_patterns() {
+        assert!(matches_pattern("https://shop.test/product/42", "/product/"));
+        assert!(matches_pattern(
+            "https://shop.test/product/42?x=1",
+            "shop.test/*/42"
+        ));
+        assert!(!matches_pattern("https://shop.test/cart", "/product/"));
+        assert!(!matches_pattern(
+            "https://shop.test/42/product",
+            "product*42"
+        ));
+        assert!(matches_pattern("anything", "*"));
+    }
+
+    #[test]
+    fn describes_the_compositions() {
+        let condition = all((
+            selector(".price"),
+            network_idle(500),
+            not(visible(".spinner")).or(url_matches("/product/")),
+        ))
+        .within(Duration::from_secs(5));
+
+        assert_eq!(
+            condition.describe(),
+            "all(selector(\".price\"), network_idle(500), any(not(visible(\".spinner\")), url_matches(\"/product/\"))).within(5s)"
+        );
+    }
+}
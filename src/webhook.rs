@@ -0,0 +1,429 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::runtime::JoinHandle;
+
+/// The page events buffered for slow subscribers before they lag.
+pub(crate) const PAGE_EVENTS_CAPACITY: usize = 512;
+
+/// How long a partial batch waits for more events before it is delivered.
+const BATCH_WINDOW: Duration = Duration::from_secs(1);
+
+/// The timeout of the delivery of a batch.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The delivery attempts of a batch.
+const MAX_ATTEMPTS: u32 = 4;
+
+/// The delay before the first retry, doubled on every attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The kind of a page event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    /// A navigation of a page completed.
+    NavigationCompleted,
+    /// A bot challenge or captcha was served to a page.
+    ChallengeDetected,
+    /// A page received the max bytes allowed, the remaining requests are blocked.
+    CrawlBudgetExceeded,
+    /// A request of a page was blocked.
+    RequestBlocked,
+    /// A user callback of a page panicked.
+    CallbackPanicked,
+}
+
+/// A page event of the browser published on [`Browser::page_events`](crate::Browser::page_events)
+/// for the webhooks, the events of a single page are [`crate::events::PageEvent`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A navigation of a page completed.
+    NavigationCompleted {
+        /// The target of the page.
+        target_id: String,
+        /// The url of the main frame.
+        url: Option<String>,
+    },
+    /// A bot challenge or captcha was served to a page.
+    ChallengeDetected {
+        /// The target of the page.
+        target_id: String,
+        /// The url of the challenge response.
+        url: String,
+        /// The challenge provider, e.g. `cloudflare`.
+        provider: &'static str,
+    },
+    /// A page received the max bytes allowed, the remaining requests are blocked.
+    CrawlBudgetExceeded {
+        /// The target of the page.
+        target_id: String,
+        /// The url of the response exceeding the budget.
+        url: String,
+    },
+    /// A request of a page was blocked.
+    RequestBlocked {
+        /// The target of the page.
+        target_id: String,
+        /// The url of the request.
+        url: String,
+        /// The resource type of the request.
+        resource_type: String,
+    },
+    /// A user callback of a page panicked, e.g. a request signer. The panic was caught and the
+    /// page kept running.
+    CallbackPanicked {
+        /// The target of the page.
+        target_id: String,
+        /// The kind of the callback, e.g. `request signer`.
+        callback: &'static str,
+        /// The url of the request or the method of the event handled by the callback.
+        context: String,
+        /// The panic message.
+        message: String,
+    },
+}
+
+impl WebhookEvent {
+    /// The kind of the event.
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::NavigationCompleted { .. } => WebhookEventKind::NavigationCompleted,
+            WebhookEvent::ChallengeDetected { .. } => WebhookEventKind::ChallengeDetected,
+            WebhookEvent::CrawlBudgetExceeded { .. } => WebhookEventKind::CrawlBudgetExceeded,
+            WebhookEvent::RequestBlocked { .. } => WebhookEventKind::RequestBlocked,
+            WebhookEvent::CallbackPanicked { .. } => WebhookEventKind::CallbackPanicked,
+        }
+    }
+
+    /// The target of the page of the event.
+    pub fn target_id(&self) -> &str {
+        match self {
+            WebhookEvent::NavigationCompleted { target_id, .. }
+            | WebhookEvent::ChallengeDetected { target_id, .. }
+            | WebhookEvent::CrawlBudgetExceeded { target_id, .. }
+            | WebhookEvent::RequestBlocked { target_id, .. }
+            | WebhookEvent::CallbackPanicked { target_id, .. } => target_id,
+        }
+    }
+}
+
+/// A [`WebhookEvent`] delivered on the webhook bus of the browser, see `Browser::page_events`.
+///
+/// Only the events of the webhook bus are sequenced, the CDP events and the
+/// [`crate::events::PageEvent`]s of `Page::event_listener` carry no seq. On the bus the events of
+/// a target are delivered in the order the target raised them and `seq` strictly increases
+/// across the browser. A subscriber lagging behind the bus misses the oldest events, the gaps
+/// are detected with a [`SequenceTracker`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SequencedEvent {
+    /// The position of the event on the webhook bus of the browser, starting at `1`.
+    pub seq: u64,
+    /// The position of the event among the webhook events of its target, starting at `1`.
+    pub target_seq: u64,
+    /// The session of the target, `None` before the target attached.
+    pub session_id: Option<String>,
+    /// The event.
+    #[serde(flatten)]
+    pub event: WebhookEvent,
+}
+
+/// The events missed between two events of the bus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MissedEvents {
+    /// The events of the browser missed.
+    pub total: u64,
+    /// The events of the target of the event missed.
+    pub target: u64,
+}
+
+impl MissedEvents {
+    /// No event was missed.
+    pub fn is_empty(&self) -> bool {
+        self.total == 0 && self.target == 0
+    }
+}
+
+/// Detect the events a subscriber of the webhook bus missed, e.g. after it lagged. The events of
+/// `Page::event_listener` are not sequenced and cannot be tracked.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceTracker {
+    /// The last seq of the browser.
+    last: Option<u64>,
+    /// The last seq of every target.
+    targets: std::collections::HashMap<String, u64>,
+}
+
+impl SequenceTracker {
+    /// Track the event, returning the events missed since the previous event of the browser
+    /// and of its target. The first event of a target starts the tracking of the target, the
+    /// events of the target before the subscription are not counted.
+    pub fn observe(&mut self, event: &SequencedEvent) -> MissedEvents {
+        let total = self
+            .last
+            .map(|last| event.seq.saturating_sub(last + 1))
+            .unwrap_or_default();
+        self.last = Some(event.seq);
+
+        let target = self
+            .targets
+            .insert(event.event.target_id().to_string(), event.target_seq)
+            .map(|last| event.target_seq.saturating_sub(last + 1))
+            .unwrap_or_default();
+
+        MissedEvents { total, target }
+    }
+
+    /// Stop tracking the target, e.g. once it was closed.
+    pub fn forget_target(&mut self, target_id: &str) {
+        self.targets.remove(target_id);
+    }
+}
+
+/// The challenge provider of a response, `None` when the response is not a challenge.
+pub(crate) fn detect_challenge(url: &str, cf_mitigated: Option<&str>) -> Option<&'static str> {
+    if cf_mitigated == Some("challenge") || url.starts_with("https://challenges.cloudflare.com/") {
+        Some("cloudflare")
+    } else if url.contains("/recaptcha/api") {
+        Some("recaptcha")
+    } else if url.starts_with("https://hcaptcha.com/") || url.contains(".hcaptcha.com/") {
+        Some("hcaptcha")
+    } else if url.contains("captcha-delivery.com/") {
+        Some("datadome")
+    } else {
+        None
+    }
+}
+
+/// Deliver the page events of the browser to an external endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebhookConfig {
+    /// The endpoint receiving the events as a JSON `POST`.
+    pub url: String,
+    /// The kinds of the events delivered, every event when empty.
+    pub events: Vec<WebhookEventKind>,
+    /// The max events delivered in a single request, `0` or `1` delivers every event on its own.
+    pub batch: usize,
+}
+
+impl WebhookConfig {
+    /// Deliver every event to the url.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Is the event delivered?
+    pub fn accepts(&self, event: &WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event.kind())
+    }
+}
+
+/// A delivered page event.
+#[derive(Debug, Serialize)]
+struct WebhookEvent {
+    #[serde(flatten)]
+    event: SequencedEvent,
+    /// The unix time of the event in milliseconds.
+    timestamp: u64,
+}
+
+/// The body of a webhook request.
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    events: &'a [WebhookEvent],
+}
+
+/// The current unix time in milliseconds.
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// POST the events to the url, retrying failed requests and `429` or `5xx` responses.
+async fn deliver(url: &str, events: &[WebhookEvent]) {
+    let body = match serde_json::to_vec(&WebhookPayload { events }) {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::warn!("webhook payload failed: {err}");
+            return;
+        }
+    };
+
+    let mut backoff = RETRY_BACKOFF;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let request = crate::http_client::HTTP_CLIENT
+            .post(url)
+            .timeout(DELIVERY_TIMEOUT)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.clone());
+
+        match request.send().await {
+            Ok(res) if res.status().is_success() => return,
+            Ok(res) if res.status().as_u16() != 429 && !res.status().is_server_error() => {
+                tracing::warn!("webhook {url} rejected the events: {}", res.status());
+                return;
+            }
+            Ok(res) => {
+                tracing::debug!("webhook {url} attempt {attempt} failed: {}", res.status());
+            }
+            Err(err) => {
+                tracing::debug!("webhook {url} attempt {attempt} failed: {err}");
+            }
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            crate::runtime::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    tracing::warn!("webhook {url} dropped {} events", events.len());
+}
+
+/// Spawn a background task delivering the page events of the receiver to the webhook until the
+/// browser is dropped.
+pub(crate) fn spawn_webhook(
+    mut events: broadcast::Receiver<SequencedEvent>,
+    config: WebhookConfig,
+) -> JoinHandle<()> {
+    crate::runtime::spawn(async move {
+        let batch_size = config.batch.max(1);
+        let mut batch = Vec::with_capacity(batch_size);
+
+        loop {
+            let next = if batch.is_empty() {
+                Ok(events.recv().await)
+            } else {
+                crate::runtime::timeout(BATCH_WINDOW, events.recv()).await
+            };
+
+            let closed = match next {
+                Ok(Ok(event)) => {
+                    if config.accepts(&event.event) {
+                        batch.push(WebhookEvent {
+                            event,
+                            timestamp: unix_millis(),
+                        });
+                    }
+                    if batch.len() < batch_size {
+                        continue;
+                    }
+                    false
+                }
+                Ok(Err(RecvError::Lagged(skipped))) => {
+                    tracing::warn!("webhook {} skipped {skipped} events", config.url);
+                    continue;
+                }
+                Ok(Err(RecvError::Closed)) => true,
+                // the batch window elapsed.
+                Err(_) => false,
+            };
+
+            if !batch.is_empty() {
+                deliver(&config.url, &batch).await;
+                batch.clear();
+            }
+
+            if closed {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_filters_events() {
+        let blocked = WebhookEvent::RequestBlocked {
+            target_id: "target".into(),
+            url: "https://example.com/ads.js".into(),
+            resource_type: "Script".into(),
+        };
+
+        assert!(WebhookConfig::new("https://hooks.example.com").accepts(&blocked));
+
+        let config = WebhookConfig {
+            url: "https://hooks.example.com".into(),
+            events: vec![WebhookEventKind::ChallengeDetected],
+            batch: 10,
+        };
+
+        assert!(!config.accepts(&blocked));
+    }
+
+    #[test]
+    fn event_payload() {
+        let event = WebhookEvent {
+            event: SequencedEvent {
+                seq: 7,
+                target_seq: 2,
+                session_id: Some("session".into()),
+                event: WebhookEvent::ChallengeDetected {
+                    target_id: "target".into(),
+                    url: "https://challenges.cloudflare.com/turnstile".into(),
+                    provider: "cloudflare",
+                },
+            },
+            timestamp: 1,
+        };
+        let json = serde_json::to_value(WebhookPayload { events: &[event] }).unwrap();
+
+        assert_eq!(json["events"][0]["type"], "challenge_detected");
+        assert_eq!(json["events"][0]["provider"], "cloudflare");
+        assert_eq!(json["events"][0]["timestamp"], 1);
+        assert_eq!(json["events"][0]["seq"], 7);
+        assert_eq!(json["events"][0]["target_seq"], 2);
+        assert_eq!(json["events"][0]["session_id"], "session");
+    }
+
+    #[test]
+    fn tracker_detects_gaps() {
+        let event = |seq, target: &str, target_seq| SequencedEvent {
+            seq,
+            target_seq,
+            session_id: None,
+            event: WebhookEvent::NavigationCompleted {
+                target_id: target.into(),
+                url: None,
+            },
+        };
+        let mut tracker = SequenceTracker::default();
+
+        assert!(tracker.observe(&event(3, "a", 1)).is_empty());
+        assert!(tracker.observe(&event(4, "b", 5)).is_empty());
+        assert_eq!(
+            tracker.observe(&event(7, "a", 3)),
+            MissedEvents {
+                total: 2,
+                target: 1
+            }
+        );
+        assert_eq!(tracker.observe(&event(8, "b", 6)), MissedEvents::default());
+    }
+
+    #[test]
+    fn detect_challenges() {
+        assert_eq!(
+            detect_challenge("https://example.com/", Some("challenge")),
+            Some("cloudflare")
+        );
+        assert_eq!(
+            detect_challenge("https://www.google.com/recaptcha/api.js", None),
+            Some("recaptcha")
+        );
+        assert_eq!(detect_challenge("https://example.com/", None), None);
+    }
+}
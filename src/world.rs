@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use chromiumoxide_cdp::cdp::browser_protocol::page::{CreateIsolatedWorldParams, FrameId};
+use chromiumoxide_cdp::cdp::js_protocol::runtime::ExecutionContextId;
+
+use crate::element::Element;
+use crate::error::Result;
+use crate::js::{Evaluation, EvaluationResult};
+use crate::page::Page;
+
+/// A named isolated world of a frame. Scripts evaluated in the world share the DOM with the page
+/// but not the javascript globals, so page scripts can neither detect nor clobber them.
+#[derive(Debug, Clone)]
+pub struct World {
+    /// The page of the world.
+    page: Page,
+    /// The name of the world.
+    name: String,
+    /// The frame the world belongs to.
+    frame_id: FrameId,
+}
+
+impl World {
+    pub(crate) fn new(page: Page, name: String, frame_id: FrameId) -> Self {
+        Self {
+            page,
+            name,
+            frame_id,
+        }
+    }
+
+    /// The name of the world.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The frame the world belongs to.
+    pub fn frame_id(&self) -> &FrameId {
+        &self.frame_id
+    }
+
+    /// The execution context of the world. The world is created again if the document was replaced
+    /// before the new context was reported.
+    pub async fn execution_context(&self) -> Result<ExecutionContextId> {
+        if let Some(ctx) = self
+            .page
+            .inner()
+            .isolated_world_context(Some(self.frame_id.clone()), self.name.clone())
+            .await?
+        {
+            return Ok(ctx);
+        }
+
+        let params = CreateIsolatedWorldParams {
+            frame_id: self.frame_id.clone(),
+            world_name: Some(self.name.clone()),
+            grant_univeral_access: Some(true),
+        };
+
+        Ok(self.page.execute(params).await?.result.execution_context_id)
+    }
+
+    /// Evaluates an expression or function in the world and returns the result.
+    pub async fn evaluate(&self, evaluate: impl Into<Evaluation>) -> Result<EvaluationResult> {
+        let ctx = self.execution_context().await?;
+
+        match evaluate.into() {
+            Evaluation::Expression(mut expr) => {
+                expr.context_id = Some(ctx);
+                self.page.evaluate_expression(expr).await
+            }
+            Evaluation::Function(mut fun) => {
+                fun.execution_context_id = Some(ctx);
+                self.page.evaluate_function(fun).await
+            }
+        }
+    }
+
+    /// Returns the first element matching the selector, resolved in the world.
+    pub async fn find_element(&self, selector: impl Into<String>) -> Result<Element> {
+        let ctx = self.execution_context().await?;
+        let root = self.page.get_document().await?.node_id;
+        let node_id = self.page.inner().find_element(selector, root).await?;
+
+        Element::with_context(Arc::clone(self.page.inner()), node_id, Some(ctx)).await
+    }
+
+    /// Returns all the elements matching the selector, resolved in the world.
+    pub async fn find_elements(&self, selector: impl Into<String>) -> Result<Vec<Element>> {
+        let ctx = self.execution_context().await?;
+        let root = self.page.get_document().await?.node_id;
+        let node_ids = self.page.inner().find_elements(selector, root).await?;
+        let mut elements = Vec::with_capacity(node_ids.len());
+
+        for node_id in node_ids {
+            elements.push(
+                Element::with_context(Arc::clone(self.page.inner()), node_id, Some(ctx)).await?,
+            );
+        }
+
+        Ok(elements)
+    }
+}
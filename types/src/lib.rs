@@ -101,12 +101,36 @@ impl EventMessage for CdpJsonEventMessage {
     fn session_id(&self) -> Option<&str> {
         self.params.get("sessionId").and_then(|x| x.as_str())
     }
+
+    fn from_raw(
+        method: String,
+        session_id: Option<String>,
+        params: serde_json::Value,
+    ) -> Option<Self> {
+        Some(Self {
+            method: method.into(),
+            session_id,
+            params,
+        })
+    }
 }
 
 /// A trait that mark
 pub trait EventMessage: Method + DeserializeOwned + Debug {
     /// The identifier of the session this event was meant for.
     fn session_id(&self) -> Option<&str>;
+
+    /// The event of a message whose params failed to parse, e.g. after chromium added a field
+    /// value unknown to the protocol definitions, keeping the raw params. `None` drops the
+    /// message.
+    fn from_raw(
+        method: String,
+        session_id: Option<String>,
+        params: serde_json::Value,
+    ) -> Option<Self> {
+        let _ = (method, session_id, params);
+        None
+    }
 }
 
 /// `Method`s are message types that contain the field `method =